@@ -8,13 +8,28 @@ use crate::{
             AggregationJobWriter, InitialWrite, ReportAggregationUpdate as _,
             WritableReportAggregation,
         },
-        error::{handle_ping_pong_error, ReportRejection, ReportRejectionReason},
+        allocation_tracking::AllocationMetrics,
+        client_telemetry::ClientTelemetry,
+        error::{
+            handle_ping_pong_error, ReportRejection, ReportRejectionDetails, ReportRejectionReason,
+        },
         error::{BatchMismatch, OptOutReason},
         query_type::{CollectableQueryType, UploadableQueryType},
+        report_policy::{AcceptAllReportPolicy, PolicyUpload, ReportPolicy, ReportPolicyEvaluator},
+        report_sink::{report_sink_from_config, ReportSinkPublisher, ReportSinkRecord},
         report_writer::{ReportWriteBatcher, WritableReport},
+        request_memory::{aggregate_request_memory_histogram, RequestMemoryBudget},
+        upload_labels::UploadLabels,
+        upload_queue::UploadQueue,
+        upload_router::UploadRouter,
+        upload_sampler::UploadSampler,
+        upload_validation::{UploadValidation, UploadValidationPermit},
     },
     cache::{GlobalHpkeKeypairCache, PeerAggregatorCache},
-    config::TaskprovConfig,
+    config::{
+        ReportSinkConfig, ResponseCompressionConfig, TaskprovConfig, UploadLabelConfig,
+        UploadRoutingConfig, UploadSamplingConfig, UploadValidationConfig,
+    },
 };
 use backoff::{backoff::Backoff, Notify};
 use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
@@ -32,46 +47,45 @@ use janus_aggregator_core::{
         self,
         models::{
             AggregateShareJob, AggregationJob, AggregationJobState, BatchAggregation,
-            BatchAggregationState, CollectionJob, CollectionJobState, LeaderStoredReport,
-            ReportAggregation, ReportAggregationState,
+            BatchAggregationState, CollectionJob, CollectionJobProgress, CollectionJobState,
+            LeaderStoredReport, ReportAggregation, ReportAggregationState,
         },
-        Datastore, Error as DatastoreError,
+        Datastore, Error as DatastoreError, Transaction,
     },
-    query_type::AccumulableQueryType,
-    task::{self, AggregatorTask, VerifyKey},
+    query_type::{AccumulableQueryType, CollectableQueryType as _},
+    task::{self, AggregatorTask, HelperRequestHeader, UnknownExtensionPolicy, VerifyKey},
     taskprov::PeerAggregator,
 };
-#[cfg(feature = "fpvec_bounded_l2")]
-use janus_core::vdaf::Prio3FixedPointBoundedL2VecSumBitSize;
 use janus_core::{
-    auth_tokens::AuthenticationToken,
+    auth_tokens::{AuthenticationToken, AuthenticationTokenHash},
+    dp::NoDifferentialPrivacy,
     hpke::{self, HpkeApplicationInfo, HpkeKeypair, Label},
     retries::retry_http_request_notify,
     time::{Clock, DurationExt, IntervalExt, TimeExt},
     vdaf::{
-        new_prio3_sum_vec_field64_multiproof_hmacsha256_aes128,
-        Prio3SumVecField64MultiproofHmacSha256Aes128, VdafInstance, VERIFY_KEY_LENGTH,
+        MeasurementLengthLimits, Prio3SumVecField64MultiproofHmacSha256Aes128, VdafInstance,
+        VERIFY_KEY_LENGTH, VERIFY_KEY_LENGTH_HMACSHA256_AES128,
     },
-    Runtime,
+    vdaf_dispatch, Runtime,
 };
 use janus_messages::{
     query_type::{FixedSize, TimeInterval},
     taskprov::{DpMechanism, TaskConfig},
     AggregateShare, AggregateShareAad, AggregateShareReq, AggregationJobContinueReq,
-    AggregationJobId, AggregationJobInitializeReq, AggregationJobResp, AggregationJobStep,
+    AggregationJobId, AggregationJobInitializeReq, AggregationJobResp, AggregationJobStep, BatchId,
     BatchSelector, Collection, CollectionJobId, CollectionReq, Duration, ExtensionType, HpkeConfig,
     HpkeConfigList, InputShareAad, Interval, PartialBatchSelector, PlaintextInputShare,
-    PrepareError, PrepareResp, PrepareStepResult, Report, ReportIdChecksum, ReportShare, Role,
-    TaskId,
+    PrepareError, PrepareResp, PrepareStepResult, Query, Report, ReportIdChecksum, ReportShare,
+    Role, TaskId, Time,
 };
 use opentelemetry::{
     metrics::{Counter, Histogram, Meter, Unit},
     KeyValue,
 };
+#[cfg(feature = "test-util")]
+use prio::vdaf::dummy;
 #[cfg(feature = "fpvec_bounded_l2")]
 use prio::vdaf::prio3::Prio3FixedPointBoundedL2VecSumMultithreaded;
-#[cfg(feature = "test-util")]
-use prio::vdaf::{dummy, PrepareTransition, VdafError};
 use prio::{
     codec::{Decode, Encode, ParameterizedDecode},
     dp::DifferentialPrivacyStrategy,
@@ -79,22 +93,25 @@ use prio::{
     vdaf::{
         self,
         poplar1::Poplar1,
-        prio3::{Prio3, Prio3Count, Prio3Histogram, Prio3Sum, Prio3SumVecMultithreaded},
+        prio3::{Prio3Count, Prio3Histogram, Prio3Sum, Prio3SumVecMultithreaded},
         xof::XofTurboShake128,
     },
 };
+use rand::random;
 use reqwest::Client;
 use ring::digest::{digest, SHA256};
+use serde::Serialize;
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeSet, HashMap, HashSet},
     fmt::Debug,
     hash::Hash,
     panic,
     sync::{Arc, Mutex as SyncMutex},
     time::{Duration as StdDuration, Instant},
 };
-use tokio::{join, sync::Mutex, try_join};
+use tokio::{join, sync::Mutex, time::sleep, try_join};
 use tracing::{debug, info, trace_span, warn, Level};
+use trillium::Headers;
 use url::Url;
 
 #[cfg(test)]
@@ -104,18 +121,38 @@ pub mod aggregation_job_continue;
 pub mod aggregation_job_creator;
 pub mod aggregation_job_driver;
 pub mod aggregation_job_writer;
+pub(crate) mod allocation_tracking;
 pub mod batch_creator;
+pub mod canary;
+mod client_telemetry;
 pub mod collection_job_driver;
 #[cfg(test)]
 mod collection_job_tests;
+pub mod deployment;
 mod error;
 pub mod garbage_collector;
 pub mod http_handlers;
+pub mod leader_election;
+pub mod metrics_snapshotter;
+pub mod peer_health_prober;
 pub mod problem_details;
 pub mod query_type;
+pub mod report_policy;
+pub mod report_sink;
 pub mod report_writer;
+mod request_memory;
+mod response_compression;
+pub mod result_sink;
+pub mod retry_classification;
+#[cfg(test)]
+mod simulation_tests;
 #[cfg(test)]
 mod taskprov_tests;
+mod upload_labels;
+pub mod upload_queue;
+mod upload_router;
+mod upload_sampler;
+mod upload_validation;
 
 pub(crate) fn aggregate_step_failure_counter(meter: &Meter) -> Counter<u64> {
     let aggregate_step_failure_counter = meter
@@ -153,6 +190,7 @@ pub(crate) fn aggregate_step_failure_counter(meter: &Meter) -> Counter<u64> {
         "missing_prepare_message",
         "missing_or_malformed_taskprov_extension",
         "unexpected_taskprov_extension",
+        "unknown_extension",
     ] {
         aggregate_step_failure_counter.add(0, &[KeyValue::new("type", failure_type)]);
     }
@@ -160,6 +198,167 @@ pub(crate) fn aggregate_step_failure_counter(meter: &Meter) -> Counter<u64> {
     aggregate_step_failure_counter
 }
 
+/// Identifies the batch whose report count is requested by a collector ahead of issuing a
+/// collection request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BatchReportCountQuery {
+    /// A batch interval of a time-interval task.
+    TimeInterval(Interval),
+    /// A batch of a fixed-size task.
+    FixedSize(BatchId),
+}
+
+/// The number of reports in a batch, along with the task's batch size limits, allowing collectors
+/// to avoid issuing collection requests that would fail the batch size check.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub(crate) struct BatchReportCount {
+    /// The number of reports currently in the batch.
+    pub report_count: u64,
+    /// The task's minimum batch size.
+    pub min_batch_size: u64,
+    /// The task's maximum batch size, for fixed-size tasks that have one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_batch_size: Option<u64>,
+    /// Whether a collection request for the batch would currently pass the batch size check.
+    pub valid_batch_size: bool,
+}
+
+/// The outcome of checking a collection request without creating a collection job, allowing
+/// collectors to validate queries cheaply.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub(crate) struct CollectionPreflight {
+    /// Whether a collection job created from the request would currently be accepted.
+    pub accepted: bool,
+    /// The code of the error the request would be rejected with, if it would be rejected.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<&'static str>,
+    /// A description of why the request would be rejected, if it would be rejected.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    /// The batch that would be collected, in human-readable form, if the request would be
+    /// accepted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub batch: Option<String>,
+    /// The number of reports currently in the batch, if it could be determined.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub report_count: Option<u64>,
+    /// The task's minimum batch size.
+    pub min_batch_size: u64,
+    /// The task's maximum batch size, for fixed-size tasks that have one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_batch_size: Option<u64>,
+}
+
+/// A decoded summary of a finished collection job, served by the collection summary extension to
+/// deployments in which the leader also holds the collector's HPKE keypair.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct CollectionSummary {
+    /// The batch that was collected, in human-readable form.
+    pub batch: String,
+    /// The number of reports included in the collection.
+    pub report_count: u64,
+    /// The start of the smallest interval containing the timestamps of all included reports, in
+    /// seconds since the UNIX epoch.
+    pub interval_start: u64,
+    /// The duration of the smallest interval containing the timestamps of all included reports,
+    /// in seconds.
+    pub interval_duration: u64,
+    /// The unsharded aggregate result.
+    pub aggregate_result: serde_json::Value,
+}
+
+impl CollectionSummary {
+    /// Decodes the result of a finished collection job: decrypts the helper's aggregate share as
+    /// the collector would, then unshards it with the leader's aggregate share, which is stored
+    /// unencrypted.
+    pub(crate) fn decode<const SEED_SIZE: usize, Q: CollectableQueryType, A>(
+        vdaf: &A,
+        collection_job: &CollectionJob<SEED_SIZE, Q, A>,
+        collector_hpke_keypair: &HpkeKeypair,
+    ) -> Result<Self, Error>
+    where
+        A: vdaf::Aggregator<SEED_SIZE, 16> + vdaf::Collector,
+        A::AggregateResult: Serialize,
+    {
+        let (
+            report_count,
+            client_timestamp_interval,
+            helper_aggregate_share,
+            leader_aggregate_share,
+        ) = match collection_job.state() {
+            CollectionJobState::Finished {
+                report_count,
+                client_timestamp_interval,
+                encrypted_helper_aggregate_share,
+                leader_aggregate_share,
+            } => (
+                *report_count,
+                *client_timestamp_interval,
+                encrypted_helper_aggregate_share,
+                leader_aggregate_share,
+            ),
+            state => {
+                return Err(Error::Internal(format!(
+                    "collection job {} is in state {state}, not finished",
+                    collection_job.id()
+                )))
+            }
+        };
+
+        let helper_aggregate_share = A::AggregateShare::get_decoded_with_param(
+            &(vdaf, collection_job.aggregation_parameter()),
+            &hpke::open(
+                collector_hpke_keypair,
+                &HpkeApplicationInfo::new(&Label::AggregateShare, &Role::Helper, &Role::Collector),
+                helper_aggregate_share,
+                &AggregateShareAad::new(
+                    *collection_job.task_id(),
+                    collection_job
+                        .aggregation_parameter()
+                        .get_encoded()
+                        .map_err(Error::ResponseEncode)?,
+                    BatchSelector::<Q>::new(collection_job.batch_identifier().clone()),
+                )
+                .get_encoded()
+                .map_err(Error::ResponseEncode)?,
+            )?,
+        )
+        .map_err(|err| Error::Internal(format!("couldn't decode helper aggregate share: {err}")))?;
+        let aggregate_result = vdaf.unshard(
+            collection_job.aggregation_parameter(),
+            [leader_aggregate_share.clone(), helper_aggregate_share],
+            usize::try_from(report_count)
+                .map_err(|err| Error::Internal(format!("report count overflow: {err}")))?,
+        )?;
+
+        Ok(Self {
+            batch: collection_job.batch_identifier().to_string(),
+            report_count,
+            interval_start: client_timestamp_interval.start().as_seconds_since_epoch(),
+            interval_duration: client_timestamp_interval.duration().as_seconds(),
+            aggregate_result: serde_json::to_value(aggregate_result).map_err(|err| {
+                Error::Internal(format!("couldn't serialize aggregate result: {err}"))
+            })?,
+        })
+    }
+}
+
+/// Operational statistics about a task, served without authentication so that the operators of
+/// the task's clients can confirm that their reports are being delivered. Only coarse counts and
+/// times are included, never anything derived from report contents.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub(crate) struct PublicTaskStats {
+    /// The number of reports accepted over the past week, to the nearest hour. This is computed
+    /// from the task's metrics snapshots, so it is `None` if none were taken during the past week.
+    pub reports_accepted_past_week: Option<u64>,
+    /// The time at which a collection of the task last finished, in seconds since the UNIX epoch,
+    /// or `None` if the task has never been collected.
+    pub last_collection_time: Option<u64>,
+}
+
+/// The period over which [`PublicTaskStats::reports_accepted_past_week`] counts reports.
+const PUBLIC_TASK_STATS_WINDOW: Duration = Duration::from_seconds(7 * 24 * 60 * 60);
+
 /// Aggregator implements a DAP aggregator.
 pub struct Aggregator<C: Clock> {
     /// Datastore used for durable storage.
@@ -170,6 +369,18 @@ pub struct Aggregator<C: Clock> {
     cfg: Config,
     /// Report writer, with support for batching.
     report_writer: Arc<ReportWriteBatcher<C>>,
+    /// Queue to which uploaded reports are sent, if uploads are ingested asynchronously. If unset,
+    /// uploaded reports are written directly to the datastore.
+    upload_queue: Option<Arc<dyn UploadQueue>>,
+    /// Router of uploads between leader instances, if uploads are routed. If unset, all uploads
+    /// are handled by this instance.
+    upload_router: Option<UploadRouter>,
+    /// Concurrency limits for upload validation.
+    upload_validation: UploadValidation,
+    /// Sampler of uploaded report metadata.
+    upload_sampler: UploadSampler<C>,
+    /// Publisher of accepted reports to a message queue, if configured.
+    report_sink: Option<ReportSinkPublisher>,
     /// Cache of task aggregators.
     task_aggregators: Mutex<HashMap<TaskId, Arc<TaskAggregator<C>>>>,
 
@@ -180,19 +391,37 @@ pub struct Aggregator<C: Clock> {
     /// Counter tracking the number of failed message decodes while handling the
     /// `tasks/{task-id}/reports` endpoint.
     upload_decode_failure_counter: Counter<u64>,
+    /// Counter tracking the number of reports accepted by the `tasks/{task-id}/reports` endpoint
+    /// despite carrying extensions of an unknown type.
+    upload_unknown_extension_counter: Counter<u64>,
     /// Counters tracking the number of failures to step client reports through the aggregation
     /// process.
     aggregate_step_failure_counter: Counter<u64>,
+    /// Histogram of the approximate peak memory used while handling each aggregate request.
+    aggregate_request_memory_histogram: Histogram<u64>,
+    /// Metrics on the allocations made while handling each aggregate request.
+    aggregate_request_allocation_metrics: AllocationMetrics,
+    /// Counters tracking uploads by the client software that sent them.
+    client_telemetry: ClientTelemetry,
+    /// Counters tracking uploads by the value of the configured upload label header, if any.
+    upload_labels: Option<UploadLabels>,
+
+    /// Screens uploads against the deployment's report policy.
+    report_policy: ReportPolicyEvaluator,
 
     /// Cache of global HPKE keypairs and configs.
     global_hpke_keypairs: GlobalHpkeKeypairCache,
 
     /// Cache of taskprov peer aggregators.
     peer_aggregators: PeerAggregatorCache,
+
+    /// Hash of a random token, against which incoming tokens are validated when the requested task
+    /// has no token hash to validate them against.
+    placeholder_auth_token_hash: AuthenticationTokenHash,
 }
 
 /// Config represents a configuration for an Aggregator.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Config {
     /// Defines the maximum size of a batch of uploaded reports which will be written in a single
     /// transaction.
@@ -217,7 +446,64 @@ pub struct Config {
     /// becomes aware of key state changes.
     pub global_hpke_configs_refresh_interval: StdDuration,
 
+    /// Defines the minimum time taken to reject a request to an authenticated endpoint because the
+    /// task is unrecognized or the request's authentication token is invalid. Padding these
+    /// responses to a uniform duration keeps their timing from revealing whether a task exists.
+    pub min_auth_failure_response_time: StdDuration,
+
+    /// Defines which endpoints compress their responses for clients that accept it.
+    pub response_compression: ResponseCompressionConfig,
+
+    /// Collector HPKE keypairs held by this aggregator. If any are configured, the leader serves
+    /// decoded summaries of finished collection jobs for tasks whose collector HPKE config matches
+    /// one of them. This is only appropriate for trusted deployments in which a single operator
+    /// runs both the leader and the collector.
+    pub collector_hpke_keypairs: Vec<HpkeKeypair>,
+
+    /// Defines the concurrency limits for upload validation.
+    pub upload_validation: UploadValidationConfig,
+
+    /// Defines sampling of uploaded report metadata for debugging.
+    pub upload_sampling: UploadSamplingConfig,
+
+    /// Defines routing of uploads between several leader instances sharing this datastore. If
+    /// unset, all uploads are handled by this instance.
+    pub upload_routing: Option<UploadRoutingConfig>,
+
+    /// If set, uploads are additionally counted by the value of a request header set by a trusted
+    /// reverse proxy.
+    pub upload_label: Option<UploadLabelConfig>,
+
+    /// If set, accepted reports are additionally published to a message queue, for downstream
+    /// analytics or auditing.
+    pub report_sink: Option<ReportSinkConfig>,
+
+    /// If set, the leader serves operational statistics about each task, such as the number of
+    /// reports accepted over the past week, without authentication.
+    pub public_task_stats: bool,
+
     pub taskprov_config: TaskprovConfig,
+
+    /// Bounds on the measurement length of tasks the helper opts into via taskprov.
+    pub measurement_length_limits: MeasurementLengthLimits,
+
+    /// If set, the leader rejects collection requests whose batch interval spans more than this
+    /// many multiples of the task's time precision, since collecting a batch interval requires
+    /// reading each of them. Collectors must collect shorter batch intervals instead.
+    pub max_collection_interval_time_precisions: Option<u64>,
+
+    /// If set, the helper abandons any aggregate request whose handling is estimated to hold more
+    /// than this many bytes of memory, counting the request body, the decoded request, decrypted
+    /// input shares, and preparation states. This protects small helper instances from
+    /// pathologically large aggregation jobs.
+    pub max_aggregate_request_memory_bytes: Option<u64>,
+
+    /// If set, requests are checked against requirements of the DAP specification which are
+    /// otherwise tolerated, i.e. that the Accept header allows the response's media type, that
+    /// authenticated endpoints are sent an authentication token, and that no unknown query
+    /// parameters are present. Non-conforming requests are rejected. This is intended for
+    /// interoperability testing, to catch divergence between implementations.
+    pub strict_conformance: bool,
 }
 
 impl Default for Config {
@@ -228,21 +514,60 @@ impl Default for Config {
             batch_aggregation_shard_count: 1,
             task_counter_shard_count: 32,
             global_hpke_configs_refresh_interval: GlobalHpkeKeypairCache::DEFAULT_REFRESH_INTERVAL,
+            min_auth_failure_response_time: StdDuration::ZERO,
+            response_compression: ResponseCompressionConfig::default(),
+            collector_hpke_keypairs: Vec::new(),
+            upload_validation: UploadValidationConfig::default(),
+            upload_sampling: UploadSamplingConfig::default(),
+            upload_routing: None,
+            upload_label: None,
+            report_sink: None,
+            public_task_stats: false,
             taskprov_config: TaskprovConfig::default(),
+            measurement_length_limits: MeasurementLengthLimits::default(),
+            max_collection_interval_time_precisions: None,
+            max_aggregate_request_memory_bytes: None,
+            strict_conformance: false,
         }
     }
 }
 
+/// How an upload is authenticated against its task's upload authentication token, if any.
+enum UploadAuthentication<'a> {
+    /// The upload was received from a client, which presented this token in a request with these
+    /// headers.
+    Client {
+        auth_token: Option<&'a AuthenticationToken>,
+        request_headers: &'a Headers,
+    },
+    /// The upload was taken from the upload queue, and was authenticated when it was queued.
+    Queued,
+}
+
 impl<C: Clock> Aggregator<C> {
+    /// Creates a new aggregator. If `secondary_datastore` is set, uploaded reports are written to
+    /// it as well as to `datastore`, e.g. while migrating to a new datastore.
     async fn new<R: Runtime + Send + Sync + 'static>(
         datastore: Arc<Datastore<C>>,
+        secondary_datastore: Option<Arc<Datastore<C>>>,
         clock: C,
         runtime: R,
         meter: &Meter,
         cfg: Config,
     ) -> Result<Self, Error> {
+        let report_sink = cfg
+            .report_sink
+            .as_ref()
+            .map(|config| {
+                report_sink_from_config(config)
+                    .map(|sink| ReportSinkPublisher::new(&runtime, meter, sink, config))
+            })
+            .transpose()
+            .map_err(|err| Error::Internal(format!("invalid report sink config: {err:#}")))?;
+
         let report_writer = Arc::new(ReportWriteBatcher::new(
             Arc::clone(&datastore),
+            secondary_datastore,
             runtime,
             cfg.task_counter_shard_count,
             cfg.max_upload_batch_size,
@@ -267,9 +592,29 @@ impl<C: Clock> Aggregator<C> {
             .init();
         upload_decode_failure_counter.add(0, &[]);
 
+        let upload_unknown_extension_counter = meter
+            .u64_counter("janus_upload_unknown_extensions")
+            .with_description(
+                "Number of reports accepted by the tasks/{task-id}/reports endpoint despite \
+                 carrying extensions of an unknown type.",
+            )
+            .with_unit(Unit::new("{report}"))
+            .init();
+        upload_unknown_extension_counter.add(0, &[]);
+
         let aggregate_step_failure_counter = aggregate_step_failure_counter(meter);
         aggregate_step_failure_counter.add(0, &[]);
 
+        let aggregate_request_memory_histogram = aggregate_request_memory_histogram(meter);
+        let aggregate_request_allocation_metrics = AllocationMetrics::new(meter);
+
+        let report_policy = ReportPolicyEvaluator::new(meter, Arc::new(AcceptAllReportPolicy));
+        let client_telemetry = ClientTelemetry::new(meter);
+        let upload_labels = cfg
+            .upload_label
+            .as_ref()
+            .map(|config| UploadLabels::new(meter, config));
+
         let global_hpke_keypairs = GlobalHpkeKeypairCache::new(
             datastore.clone(),
             cfg.global_hpke_configs_refresh_interval,
@@ -278,20 +623,58 @@ impl<C: Clock> Aggregator<C> {
 
         let peer_aggregators = PeerAggregatorCache::new(&datastore).await?;
 
+        let upload_validation = UploadValidation::new(meter, &cfg.upload_validation);
+        let upload_sampler = UploadSampler::new(Arc::clone(&datastore), &cfg.upload_sampling);
+        let upload_router = cfg
+            .upload_routing
+            .clone()
+            .map(|config| UploadRouter::new(meter, config))
+            .transpose()
+            .map_err(|err| Error::Internal(format!("invalid upload routing config: {err}")))?;
+
         Ok(Self {
             datastore,
             clock,
             cfg,
             report_writer,
+            upload_queue: None,
+            upload_router,
+            upload_validation,
+            upload_sampler,
+            report_sink,
             task_aggregators: Mutex::new(HashMap::new()),
             upload_decrypt_failure_counter,
             upload_decode_failure_counter,
+            upload_unknown_extension_counter,
             aggregate_step_failure_counter,
+            aggregate_request_memory_histogram,
+            aggregate_request_allocation_metrics,
+            client_telemetry,
+            upload_labels,
+            report_policy,
             global_hpke_keypairs,
             peer_aggregators,
+            placeholder_auth_token_hash: AuthenticationTokenHash::from(&random()),
         })
     }
 
+    /// Configures this aggregator to send uploaded reports to the given queue, rather than
+    /// writing them to the datastore. Reports must then be ingested by an
+    /// [`upload_queue::UploadIngester`].
+    fn with_upload_queue(mut self, upload_queue: Arc<dyn UploadQueue>) -> Self {
+        self.upload_queue = Some(upload_queue);
+        self
+    }
+
+    /// Configures this aggregator to evaluate uploads against the given report policy, rather
+    /// than accepting all of them.
+    fn with_report_policy(self, report_policy: Arc<dyn ReportPolicy>) -> Self {
+        Self {
+            report_policy: self.report_policy.with_policy(report_policy),
+            ..self
+        }
+    }
+
     async fn handle_hpke_config(
         &self,
         task_id_base64: Option<&[u8]>,
@@ -346,7 +729,41 @@ impl<C: Clock> Aggregator<C> {
         }
     }
 
-    async fn handle_upload(&self, task_id: &TaskId, report_bytes: &[u8]) -> Result<(), Arc<Error>> {
+    async fn handle_upload(
+        &self,
+        task_id: &TaskId,
+        report_bytes: &[u8],
+        auth_token: Option<&AuthenticationToken>,
+        request_headers: &Headers,
+    ) -> Result<(), Arc<Error>> {
+        self.handle_upload_inner(
+            task_id,
+            report_bytes,
+            UploadAuthentication::Client {
+                auth_token,
+                request_headers,
+            },
+        )
+        .await
+    }
+
+    /// Handles an upload taken from the upload queue, which was authenticated when it was queued.
+    async fn handle_queued_upload(
+        &self,
+        task_id: &TaskId,
+        report_bytes: &[u8],
+    ) -> Result<(), Arc<Error>> {
+        self.handle_upload_inner(task_id, report_bytes, UploadAuthentication::Queued)
+            .await
+    }
+
+    async fn handle_upload_inner(
+        &self,
+        task_id: &TaskId,
+        report_bytes: &[u8],
+        authentication: UploadAuthentication<'_>,
+    ) -> Result<(), Arc<Error>> {
+        let cheap_permit = self.upload_validation.enter_cheap_stage().await;
         let report = Report::get_decoded(report_bytes).map_err(|err| Arc::new(Error::from(err)))?;
 
         let task_aggregator = self
@@ -356,15 +773,66 @@ impl<C: Clock> Aggregator<C> {
         if task_aggregator.task.role() != &Role::Leader {
             return Err(Arc::new(Error::UnrecognizedTask(*task_id)));
         }
+        if let UploadAuthentication::Client {
+            auth_token,
+            request_headers,
+        } = authentication
+        {
+            if !task_aggregator.task.check_upload_auth_token(auth_token) {
+                return Err(Arc::new(Error::UnauthorizedRequest(*task_id)));
+            }
+            // Queued uploads were evaluated against the report policy when they were queued.
+            if self
+                .report_policy
+                .evaluate(&PolicyUpload::new(
+                    &task_aggregator.task,
+                    report.metadata(),
+                    self.clock.now(),
+                    request_headers,
+                ))
+                .await
+                .is_some()
+            {
+                return Err(Arc::new(Error::ReportRejected(ReportRejection::new(
+                    *task_id,
+                    *report.metadata().id(),
+                    *report.metadata().time(),
+                    ReportRejectionReason::PolicyRejected,
+                ))));
+            }
+        }
+        if let Some(upload_queue) = &self.upload_queue {
+            // The remainder of upload validation happens when the report is ingested.
+            return upload_queue
+                .send(task_id, report_bytes)
+                .await
+                .map_err(Arc::new);
+        }
+        let report_id = *report.metadata().id();
+        let report_time = *report.metadata().time();
         task_aggregator
             .handle_upload(
                 &self.clock,
                 &self.global_hpke_keypairs,
                 &self.upload_decrypt_failure_counter,
                 &self.upload_decode_failure_counter,
+                &self.upload_unknown_extension_counter,
+                &self.upload_validation,
+                cheap_permit,
+                &self.upload_sampler,
                 report,
             )
-            .await
+            .await?;
+        if let Some(report_sink) = &self.report_sink {
+            report_sink.publish(ReportSinkRecord::new(
+                *task_id,
+                report_id,
+                report_time,
+                self.clock.now(),
+                report_bytes.to_vec(),
+            ));
+        }
+        Ok(())
     }
 
     async fn handle_aggregate_init(
@@ -375,12 +843,13 @@ impl<C: Clock> Aggregator<C> {
         auth_token: Option<AuthenticationToken>,
         taskprov_task_config: Option<&TaskConfig>,
     ) -> Result<AggregationJobResp, Error> {
-        let task_aggregator = match self.task_aggregator_for(task_id).await? {
-            Some(task_aggregator) => {
-                if task_aggregator.task.role() != &Role::Helper {
-                    return Err(Error::UnrecognizedTask(*task_id));
-                }
-                if self.cfg.taskprov_config.enabled && taskprov_task_config.is_some() {
+        let task_aggregator = if self.cfg.taskprov_config.enabled && taskprov_task_config.is_some()
+        {
+            match self.task_aggregator_for(task_id).await? {
+                Some(task_aggregator) => {
+                    if task_aggregator.task.role() != &Role::Helper {
+                        return Err(Error::UnrecognizedTask(*task_id));
+                    }
                     self.taskprov_authorize_request(
                         &Role::Leader,
                         task_id,
@@ -388,50 +857,58 @@ impl<C: Clock> Aggregator<C> {
                         auth_token.as_ref(),
                     )
                     .await?;
-                } else if !task_aggregator
-                    .task
-                    .check_aggregator_auth_token(auth_token.as_ref())
-                {
-                    return Err(Error::UnauthorizedRequest(*task_id));
+                    task_aggregator
                 }
-                task_aggregator
-            }
-            None if self.cfg.taskprov_config.enabled && taskprov_task_config.is_some() => {
-                self.taskprov_opt_in(
-                    &Role::Leader,
-                    task_id,
-                    taskprov_task_config.unwrap(),
-                    auth_token.as_ref(),
-                )
-                .await?;
+                None => {
+                    self.taskprov_opt_in(
+                        &Role::Leader,
+                        task_id,
+                        taskprov_task_config.unwrap(),
+                        auth_token.as_ref(),
+                    )
+                    .await?;
 
-                // Retry fetching the aggregator, since the last function would have just inserted
-                // its task.
-                debug!(
-                    ?task_id,
-                    "taskprov: opt-in successful, retrying task acquisition"
-                );
-                self.task_aggregator_for(task_id).await?.ok_or_else(|| {
-                    Error::Internal("unexpectedly failed to create task".to_string())
-                })?
-            }
-            _ => {
-                return Err(Error::UnrecognizedTask(*task_id));
+                    // Retry fetching the aggregator, since the last function would have just
+                    // inserted its task.
+                    debug!(
+                        ?task_id,
+                        "taskprov: opt-in successful, retrying task acquisition"
+                    );
+                    self.task_aggregator_for(task_id).await?.ok_or_else(|| {
+                        Error::Internal("unexpectedly failed to create task".to_string())
+                    })?
+                }
             }
+        } else {
+            self.authorized_task_aggregator_for(task_id, Role::Helper, auth_token.as_ref())
+                .await?
         };
 
-        task_aggregator
-            .handle_aggregate_init(
-                &self.datastore,
-                &self.clock,
-                &self.global_hpke_keypairs,
-                &self.aggregate_step_failure_counter,
-                self.cfg.batch_aggregation_shard_count,
-                aggregation_job_id,
-                taskprov_task_config.is_some(),
-                req_bytes,
-            )
-            .await
+        let memory_budget =
+            RequestMemoryBudget::new(*task_id, self.cfg.max_aggregate_request_memory_bytes);
+        let result = match memory_budget.charge(req_bytes.len()) {
+            Ok(()) => {
+                self.aggregate_request_allocation_metrics
+                    .track(
+                        "init",
+                        task_aggregator.handle_aggregate_init(
+                            &self.datastore,
+                            &self.clock,
+                            &self.global_hpke_keypairs,
+                            &self.aggregate_step_failure_counter,
+                            &memory_budget,
+                            self.cfg.batch_aggregation_shard_count,
+                            aggregation_job_id,
+                            taskprov_task_config.is_some(),
+                            req_bytes,
+                        ),
+                    )
+                    .await
+            }
+            Err(err) => Err(err),
+        };
+        memory_budget.record(&self.aggregate_request_memory_histogram, "init");
+        result
     }
 
     async fn handle_aggregate_continue(
@@ -442,15 +919,8 @@ impl<C: Clock> Aggregator<C> {
         auth_token: Option<AuthenticationToken>,
         taskprov_task_config: Option<&TaskConfig>,
     ) -> Result<AggregationJobResp, Error> {
-        let task_aggregator = self
-            .task_aggregator_for(task_id)
-            .await?
-            .ok_or(Error::UnrecognizedTask(*task_id))?;
-        if task_aggregator.task.role() != &Role::Helper {
-            return Err(Error::UnrecognizedTask(*task_id));
-        }
-
-        if self.cfg.taskprov_config.enabled && taskprov_task_config.is_some() {
+        let task_aggregator = if self.cfg.taskprov_config.enabled && taskprov_task_config.is_some()
+        {
             self.taskprov_authorize_request(
                 &Role::Leader,
                 task_id,
@@ -458,26 +928,36 @@ impl<C: Clock> Aggregator<C> {
                 auth_token.as_ref(),
             )
             .await?;
-        } else if !task_aggregator
-            .task
-            .check_aggregator_auth_token(auth_token.as_ref())
-        {
-            return Err(Error::UnauthorizedRequest(*task_id));
-        }
+            self.taskprov_task_aggregator_for(task_id).await?
+        } else {
+            self.authorized_task_aggregator_for(task_id, Role::Helper, auth_token.as_ref())
+                .await?
+        };
 
-        let req = AggregationJobContinueReq::get_decoded(req_bytes)?;
-        // unwrap safety: SHA-256 computed by ring should always be 32 bytes
-        let request_hash = digest(&SHA256, req_bytes).as_ref().try_into().unwrap();
+        // The request body and the decoded request are held for the duration of the request.
+        let memory_budget =
+            RequestMemoryBudget::new(*task_id, self.cfg.max_aggregate_request_memory_bytes);
+        let charge_result = memory_budget.charge(req_bytes.len().saturating_mul(2));
+        memory_budget.record(&self.aggregate_request_memory_histogram, "continue");
+        charge_result?;
 
-        task_aggregator
-            .handle_aggregate_continue(
-                &self.datastore,
-                &self.aggregate_step_failure_counter,
-                self.cfg.batch_aggregation_shard_count,
-                aggregation_job_id,
-                req,
-                request_hash,
-            )
+        self.aggregate_request_allocation_metrics
+            .track("continue", async {
+                let req = AggregationJobContinueReq::get_decoded(req_bytes)?;
+                // unwrap safety: SHA-256 computed by ring should always be 32 bytes
+                let request_hash = digest(&SHA256, req_bytes).as_ref().try_into().unwrap();
+
+                task_aggregator
+                    .handle_aggregate_continue(
+                        &self.datastore,
+                        &self.aggregate_step_failure_counter,
+                        self.cfg.batch_aggregation_shard_count,
+                        aggregation_job_id,
+                        req,
+                        request_hash,
+                    )
+                    .await
+            })
             .await
     }
 
@@ -488,15 +968,8 @@ impl<C: Clock> Aggregator<C> {
         auth_token: Option<AuthenticationToken>,
         taskprov_task_config: Option<&TaskConfig>,
     ) -> Result<(), Error> {
-        let task_aggregator = self
-            .task_aggregator_for(task_id)
-            .await?
-            .ok_or(Error::UnrecognizedTask(*task_id))?;
-        if task_aggregator.task.role() != &Role::Helper {
-            return Err(Error::UnrecognizedTask(*task_id));
-        }
-
-        if self.cfg.taskprov_config.enabled && taskprov_task_config.is_some() {
+        let task_aggregator = if self.cfg.taskprov_config.enabled && taskprov_task_config.is_some()
+        {
             self.taskprov_authorize_request(
                 &Role::Leader,
                 task_id,
@@ -504,12 +977,11 @@ impl<C: Clock> Aggregator<C> {
                 auth_token.as_ref(),
             )
             .await?;
-        } else if !task_aggregator
-            .task
-            .check_aggregator_auth_token(auth_token.as_ref())
-        {
-            return Err(Error::UnauthorizedRequest(*task_id));
-        }
+            self.taskprov_task_aggregator_for(task_id).await?
+        } else {
+            self.authorized_task_aggregator_for(task_id, Role::Helper, auth_token.as_ref())
+                .await?
+        };
 
         task_aggregator
             .handle_aggregate_delete(&self.datastore, aggregation_job_id)
@@ -526,21 +998,16 @@ impl<C: Clock> Aggregator<C> {
         auth_token: Option<AuthenticationToken>,
     ) -> Result<(), Error> {
         let task_aggregator = self
-            .task_aggregator_for(task_id)
-            .await?
-            .ok_or(Error::UnrecognizedTask(*task_id))?;
-        if task_aggregator.task.role() != &Role::Leader {
-            return Err(Error::UnrecognizedTask(*task_id));
-        }
-        if !task_aggregator
-            .task
-            .check_collector_auth_token(auth_token.as_ref())
-        {
-            return Err(Error::UnauthorizedRequest(*task_id));
-        }
+            .authorized_task_aggregator_for(task_id, Role::Leader, auth_token.as_ref())
+            .await?;
 
         task_aggregator
-            .handle_create_collection_job(&self.datastore, collection_job_id, req_bytes)
+            .handle_create_collection_job(
+                &self.datastore,
+                self.cfg.max_collection_interval_time_precisions,
+                collection_job_id,
+                req_bytes,
+            )
             .await
     }
 
@@ -555,24 +1022,47 @@ impl<C: Clock> Aggregator<C> {
         auth_token: Option<AuthenticationToken>,
     ) -> Result<Option<Vec<u8>>, Error> {
         let task_aggregator = self
-            .task_aggregator_for(task_id)
-            .await?
-            .ok_or(Error::UnrecognizedTask(*task_id))?;
-        if task_aggregator.task.role() != &Role::Leader {
-            return Err(Error::UnrecognizedTask(*task_id));
-        }
-        if !task_aggregator
-            .task
-            .check_collector_auth_token(auth_token.as_ref())
-        {
-            return Err(Error::UnauthorizedRequest(*task_id));
-        }
+            .authorized_task_aggregator_for(task_id, Role::Leader, auth_token.as_ref())
+            .await?;
 
         task_aggregator
             .handle_get_collection_job(&self.datastore, collection_job_id)
             .await
     }
 
+    /// Estimates how long until the given collection job, which the caller has already
+    /// authorized, will complete. Returns `None` if no estimate can be made, for instance because
+    /// the collection job's aggregation work has stalled.
+    async fn collection_job_time_to_completion(
+        &self,
+        task_id: &TaskId,
+        collection_job_id: &CollectionJobId,
+    ) -> Result<Option<Duration>, Error> {
+        let progress = self
+            .datastore
+            .run_read_only_tx("collection_job_time_to_completion", |tx| {
+                let (task_id, collection_job_id) = (*task_id, *collection_job_id);
+                Box::pin(async move {
+                    tx.get_collection_job_progress(
+                        &task_id,
+                        &collection_job_id,
+                        &CollectionJobProgress::DEFAULT_THROUGHPUT_WINDOW,
+                    )
+                    .await
+                })
+            })
+            .await?;
+        let now = self.clock.now();
+        Ok(progress
+            .as_ref()
+            .and_then(CollectionJobProgress::estimated_completion)
+            .map(|estimated_completion| {
+                estimated_completion
+                    .difference(&now)
+                    .unwrap_or(Duration::ZERO)
+            }))
+    }
+
     /// Handle a DELETE request for a collection job.
     async fn handle_delete_collection_job(
         &self,
@@ -581,18 +1071,8 @@ impl<C: Clock> Aggregator<C> {
         auth_token: Option<AuthenticationToken>,
     ) -> Result<(), Error> {
         let task_aggregator = self
-            .task_aggregator_for(task_id)
-            .await?
-            .ok_or(Error::UnrecognizedTask(*task_id))?;
-        if task_aggregator.task.role() != &Role::Leader {
-            return Err(Error::UnrecognizedTask(*task_id));
-        }
-        if !task_aggregator
-            .task
-            .check_collector_auth_token(auth_token.as_ref())
-        {
-            return Err(Error::UnauthorizedRequest(*task_id));
-        }
+            .authorized_task_aggregator_for(task_id, Role::Leader, auth_token.as_ref())
+            .await?;
 
         task_aggregator
             .handle_delete_collection_job(&self.datastore, collection_job_id)
@@ -601,6 +1081,171 @@ impl<C: Clock> Aggregator<C> {
         Ok(())
     }
 
+    /// Handle a request for the number of reports in a batch. Only supported by the leader. The
+    /// count is the same one checked against the task's batch size limits when a collection job is
+    /// created, so collectors can use it to decide whether to issue a collection request.
+    async fn handle_get_batch_report_count(
+        &self,
+        task_id: &TaskId,
+        query: BatchReportCountQuery,
+        auth_token: Option<AuthenticationToken>,
+    ) -> Result<BatchReportCount, Error> {
+        let task_aggregator = self
+            .authorized_task_aggregator_for(task_id, Role::Leader, auth_token.as_ref())
+            .await?;
+
+        let task = Arc::clone(&task_aggregator.task);
+        let report_count = self
+            .datastore
+            .run_tx("get_batch_report_count", |tx| {
+                let task = Arc::clone(&task);
+                Box::pin(async move {
+                    match (task.query_type(), query) {
+                        (
+                            task::QueryType::TimeInterval,
+                            BatchReportCountQuery::TimeInterval(batch_interval),
+                        ) => {
+                            TimeInterval::validate_collection_identifier(&task, &batch_interval)
+                                .map_err(|violation| {
+                                    datastore::Error::User(
+                                        Error::BatchIntervalMisaligned(
+                                            *task.id(),
+                                            batch_interval.to_string(),
+                                            violation,
+                                        )
+                                        .into(),
+                                    )
+                                })?;
+                            TimeInterval::count_client_reports(tx, &task, &batch_interval).await
+                        }
+                        (
+                            task::QueryType::FixedSize { .. },
+                            BatchReportCountQuery::FixedSize(batch_id),
+                        ) => FixedSize::count_client_reports(tx, &task, &batch_id).await,
+                        _ => Err(datastore::Error::User(
+                            Error::BadRequest(
+                                "batch selector does not match the task's query type".to_string(),
+                            )
+                            .into(),
+                        )),
+                    }
+                })
+            })
+            .await?;
+
+        Ok(BatchReportCount {
+            report_count,
+            min_batch_size: task.min_batch_size(),
+            max_batch_size: match task.query_type() {
+                task::QueryType::FixedSize { max_batch_size, .. } => *max_batch_size,
+                task::QueryType::TimeInterval => None,
+            },
+            valid_batch_size: task.validate_batch_size(report_count),
+        })
+    }
+
+    /// Handle a request to check a collection request without creating a collection job. Only
+    /// supported by the leader. `req_bytes` is an encoded [`CollectionReq`], which is checked as
+    /// it would be when creating a collection job, without any changes to the datastore.
+    async fn handle_collection_preflight(
+        &self,
+        task_id: &TaskId,
+        req_bytes: &[u8],
+        auth_token: Option<AuthenticationToken>,
+    ) -> Result<CollectionPreflight, Error> {
+        let task_aggregator = self
+            .authorized_task_aggregator_for(task_id, Role::Leader, auth_token.as_ref())
+            .await?;
+
+        task_aggregator
+            .handle_collection_preflight(
+                &self.datastore,
+                self.cfg.max_collection_interval_time_precisions,
+                req_bytes,
+            )
+            .await
+    }
+
+    /// Handle a request for a task's public statistics. Only supported by the leader. No
+    /// authentication is required, so an unrecognized task is reported as such.
+    async fn handle_get_public_task_stats(
+        &self,
+        task_id: &TaskId,
+    ) -> Result<PublicTaskStats, Error> {
+        self.task_aggregator_for(task_id)
+            .await?
+            .filter(|task_aggregator| task_aggregator.task.role() == &Role::Leader)
+            .ok_or(Error::UnrecognizedTask(*task_id))?;
+
+        let since = self
+            .clock
+            .now()
+            .sub(&PUBLIC_TASK_STATS_WINDOW)
+            .unwrap_or_else(|_| Time::from_seconds_since_epoch(0));
+        let (upload_counter, snapshots, last_collection_time) = self
+            .datastore
+            .run_read_only_tx("get_public_task_stats", |tx| {
+                let task_id = *task_id;
+                Box::pin(async move {
+                    try_join!(
+                        tx.get_task_upload_counter(&task_id),
+                        tx.get_task_metrics_snapshots(&task_id, &since),
+                        tx.get_latest_finished_collection_time(&task_id),
+                    )
+                })
+            })
+            .await?;
+        let upload_counter = upload_counter.ok_or(Error::UnrecognizedTask(*task_id))?;
+
+        Ok(PublicTaskStats {
+            reports_accepted_past_week: snapshots.first().map(|snapshot| {
+                upload_counter
+                    .report_success()
+                    .saturating_sub(snapshot.reports_accepted())
+            }),
+            last_collection_time: last_collection_time.map(|time| time.as_seconds_since_epoch()),
+        })
+    }
+
+    /// Handle a request for a decoded summary of a finished collection job. Only supported by the
+    /// leader, and only for tasks whose collector HPKE keypair is configured. Returns `None` if
+    /// the collection job has not finished yet.
+    async fn handle_get_collection_summary(
+        &self,
+        task_id: &TaskId,
+        collection_job_id: &CollectionJobId,
+        auth_token: Option<AuthenticationToken>,
+    ) -> Result<Option<CollectionSummary>, Error> {
+        let task_aggregator = self
+            .authorized_task_aggregator_for(task_id, Role::Leader, auth_token.as_ref())
+            .await?;
+
+        let collector_hpke_keypair = task_aggregator
+            .task
+            .collector_hpke_config()
+            .and_then(|collector_hpke_config| {
+                self.cfg
+                    .collector_hpke_keypairs
+                    .iter()
+                    .find(|keypair| keypair.config() == collector_hpke_config)
+            })
+            .ok_or_else(|| {
+                Error::BadRequest(
+                    "collection summaries are not available for this task".to_string(),
+                )
+            })?;
+
+        task_aggregator
+            .vdaf_ops
+            .handle_get_collection_summary(
+                &self.datastore,
+                Arc::clone(&task_aggregator.task),
+                collection_job_id,
+                collector_hpke_keypair,
+            )
+            .await
+    }
+
     /// Handle an aggregate share request. Only supported by the helper. `req_bytes` is an encoded
     /// [`AggregateShareReq`]. Returns an [`AggregateShare`].
     async fn handle_aggregate_share(
@@ -610,17 +1255,9 @@ impl<C: Clock> Aggregator<C> {
         auth_token: Option<AuthenticationToken>,
         taskprov_task_config: Option<&TaskConfig>,
     ) -> Result<AggregateShare, Error> {
-        let task_aggregator = self
-            .task_aggregator_for(task_id)
-            .await?
-            .ok_or(Error::UnrecognizedTask(*task_id))?;
-        if task_aggregator.task.role() != &Role::Helper {
-            return Err(Error::UnrecognizedTask(*task_id));
-        }
-
         // Authorize the request and retrieve the collector's HPKE config. If this is a taskprov task, we
         // have to use the peer aggregator's collector config rather than the main task.
-        let collector_hpke_config =
+        let (task_aggregator, collector_hpke_config) =
             if self.cfg.taskprov_config.enabled && taskprov_task_config.is_some() {
                 let (peer_aggregator, _, _) = self
                     .taskprov_authorize_request(
@@ -631,21 +1268,22 @@ impl<C: Clock> Aggregator<C> {
                     )
                     .await?;
 
-                peer_aggregator.collector_hpke_config()
+                (
+                    self.taskprov_task_aggregator_for(task_id).await?,
+                    peer_aggregator.collector_hpke_config().clone(),
+                )
             } else {
-                if !task_aggregator
-                    .task
-                    .check_aggregator_auth_token(auth_token.as_ref())
-                {
-                    return Err(Error::UnauthorizedRequest(*task_id));
-                }
-
-                task_aggregator
+                let task_aggregator = self
+                    .authorized_task_aggregator_for(task_id, Role::Helper, auth_token.as_ref())
+                    .await?;
+                let collector_hpke_config = task_aggregator
                     .task
                     .collector_hpke_config()
                     .ok_or_else(|| {
                         Error::Internal("task is missing collector_hpke_config".to_string())
                     })?
+                    .clone();
+                (task_aggregator, collector_hpke_config)
             };
 
         task_aggregator
@@ -654,7 +1292,7 @@ impl<C: Clock> Aggregator<C> {
                 &self.clock,
                 self.cfg.batch_aggregation_shard_count,
                 req_bytes,
-                collector_hpke_config,
+                &collector_hpke_config,
             )
             .await
     }
@@ -687,6 +1325,9 @@ impl<C: Clock> Aggregator<C> {
             })
             .await?;
         match task_opt {
+            // Tasks that are not yet served, or are being purged, are treated as if they don't
+            // exist. They are not cached, so that a task is served once it is activated.
+            Some(task) if !task.state().is_served() => Ok(None),
             Some(task) => {
                 let task_agg =
                     Arc::new(TaskAggregator::new(task, Arc::clone(&self.report_writer))?);
@@ -704,6 +1345,71 @@ impl<C: Clock> Aggregator<C> {
         }
     }
 
+    /// Looks up the task aggregator for a request that must be authenticated with a token
+    /// recognized by the task: the aggregator auth token if this aggregator is the helper, or the
+    /// collector auth token if it is the leader. An unrecognized task, a task in which this
+    /// aggregator has a different role, and a missing or invalid token all produce the same
+    /// [`Error::UnauthorizedRequest`], no sooner than [`Config::min_auth_failure_response_time`]
+    /// after the lookup began, so that neither the response nor its timing reveals whether the
+    /// task exists.
+    ///
+    /// This changes what peers see on the wire: a request for an unrecognized task is answered
+    /// with `unauthorizedRequest` rather than `unrecognizedTask`. Leaders that retry only on
+    /// `unrecognizedTask` will abandon aggregation jobs for tasks that the helper has not been
+    /// provisioned with yet; Janus leaders retry `unauthorizedRequest` on aggregation job
+    /// initialization, up to their maximum number of attempts.
+    async fn authorized_task_aggregator_for(
+        &self,
+        task_id: &TaskId,
+        role: Role,
+        auth_token: Option<&AuthenticationToken>,
+    ) -> Result<Arc<TaskAggregator<C>>, Error> {
+        let start = Instant::now();
+        let task_aggregator = self
+            .task_aggregator_for(task_id)
+            .await?
+            .filter(|task_aggregator| task_aggregator.task.role() == &role);
+        let token_hash = task_aggregator
+            .as_ref()
+            .and_then(|task_aggregator| match role {
+                Role::Leader => task_aggregator.task.collector_auth_token_hash(),
+                _ => task_aggregator.task.aggregator_auth_token_hash(),
+            });
+
+        // Validate the token against a placeholder hash if there is no real one to check it
+        // against, so that the same work is done whether or not the task exists.
+        let token_valid = auth_token.map_or(false, |auth_token| {
+            token_hash
+                .unwrap_or(&self.placeholder_auth_token_hash)
+                .validate(auth_token)
+        });
+
+        match task_aggregator {
+            Some(task_aggregator) if token_valid && token_hash.is_some() => Ok(task_aggregator),
+            _ => {
+                sleep(
+                    self.cfg
+                        .min_auth_failure_response_time
+                        .saturating_sub(start.elapsed()),
+                )
+                .await;
+                Err(Error::UnauthorizedRequest(*task_id))
+            }
+        }
+    }
+
+    /// Looks up the task aggregator for a helper request that was already authenticated by the
+    /// taskprov peer aggregator's token.
+    async fn taskprov_task_aggregator_for(
+        &self,
+        task_id: &TaskId,
+    ) -> Result<Arc<TaskAggregator<C>>, Error> {
+        self.task_aggregator_for(task_id)
+            .await?
+            .filter(|task_aggregator| task_aggregator.task.role() == &Role::Helper)
+            .ok_or(Error::UnrecognizedTask(*task_id))
+    }
+
     /// Opts in or out of a taskprov task.
     #[tracing::instrument(skip(self, aggregator_auth_token), err(level = Level::DEBUG))]
     async fn taskprov_opt_in(
@@ -735,34 +1441,38 @@ impl<C: Clock> Aggregator<C> {
             }
         }
 
-        let vdaf_instance =
-            task_config
-                .vdaf_config()
-                .vdaf_type()
-                .try_into()
-                .map_err(|err: &str| {
-                    Error::InvalidTask(*task_id, OptOutReason::InvalidParameter(err.to_string()))
-                })?;
+        let vdaf_instance: VdafInstance = task_config
+            .vdaf_config()
+            .vdaf_type()
+            .try_into()
+            .map_err(|err: &str| {
+                Error::InvalidTask(*task_id, OptOutReason::InvalidParameter(err.to_string()))
+            })?;
+        vdaf_instance
+            .check_measurement_length(&self.cfg.measurement_length_limits)
+            .map_err(|err| {
+                Error::InvalidTask(*task_id, OptOutReason::InvalidParameter(err.to_string()))
+            })?;
 
         let vdaf_verify_key = peer_aggregator.derive_vdaf_verify_key(task_id, &vdaf_instance);
 
         let task = Arc::new(
-            AggregatorTask::new(
+            // Taskprov task has no per-task HPKE keys
+            AggregatorTask::builder(
                 *task_id,
                 leader_url,
                 task_config.query_config().query().try_into()?,
                 vdaf_instance,
                 vdaf_verify_key,
-                task_config.query_config().max_batch_query_count() as u64,
-                Some(*task_config.task_expiration()),
-                peer_aggregator.report_expiry_age().cloned(),
-                task_config.query_config().min_batch_size() as u64,
-                *task_config.query_config().time_precision(),
-                *peer_aggregator.tolerable_clock_skew(),
-                // Taskprov task has no per-task HPKE keys
-                [],
                 task::AggregatorTaskParameters::TaskprovHelper,
             )
+            .with_max_batch_query_count(task_config.query_config().max_batch_query_count() as u64)
+            .with_task_expiration(Some(*task_config.task_expiration()))
+            .with_report_expiry_age(peer_aggregator.report_expiry_age().cloned())
+            .with_min_batch_size(task_config.query_config().min_batch_size() as u64)
+            .with_time_precision(*task_config.query_config().time_precision())
+            .with_tolerable_clock_skew(*peer_aggregator.tolerable_clock_skew())
+            .build()
             .map_err(|err| Error::InvalidTask(*task_id, OptOutReason::TaskParameters(err)))?,
         );
         self.datastore
@@ -864,107 +1574,13 @@ impl<C: Clock> TaskAggregator<C> {
     /// Create a new aggregator. `report_recipient` is used to decrypt reports received by this
     /// aggregator.
     fn new(task: AggregatorTask, report_writer: Arc<ReportWriteBatcher<C>>) -> Result<Self, Error> {
-        let vdaf_ops = match task.vdaf() {
-            VdafInstance::Prio3Count => {
-                let vdaf = Prio3::new_count(2)?;
-                let verify_key = task.vdaf_verify_key()?;
-                VdafOps::Prio3Count(Arc::new(vdaf), verify_key)
-            }
-
-            VdafInstance::Prio3Sum { bits } => {
-                let vdaf = Prio3::new_sum(2, *bits)?;
-                let verify_key = task.vdaf_verify_key()?;
-                VdafOps::Prio3Sum(Arc::new(vdaf), verify_key)
-            }
-
-            VdafInstance::Prio3SumVec {
-                bits,
-                length,
-                chunk_length,
-            } => {
-                let vdaf = Prio3::new_sum_vec_multithreaded(2, *bits, *length, *chunk_length)?;
-                let verify_key = task.vdaf_verify_key()?;
-                VdafOps::Prio3SumVec(Arc::new(vdaf), verify_key)
-            }
-
-            VdafInstance::Prio3SumVecField64MultiproofHmacSha256Aes128 {
-                proofs,
-                bits,
-                length,
-                chunk_length,
-            } => {
-                let vdaf = new_prio3_sum_vec_field64_multiproof_hmacsha256_aes128(
-                    *proofs,
-                    *bits,
-                    *length,
-                    *chunk_length,
-                )?;
-                let verify_key = task.vdaf_verify_key()?;
-                VdafOps::Prio3SumVecField64MultiproofHmacSha256Aes128(Arc::new(vdaf), verify_key)
-            }
-
-            VdafInstance::Prio3Histogram {
-                length,
-                chunk_length,
-            } => {
-                let vdaf = Prio3::new_histogram(2, *length, *chunk_length)?;
-                let verify_key = task.vdaf_verify_key()?;
-                VdafOps::Prio3Histogram(Arc::new(vdaf), verify_key)
-            }
-
-            #[cfg(feature = "fpvec_bounded_l2")]
-            VdafInstance::Prio3FixedPointBoundedL2VecSum {
-                bitsize,
+        let vdaf_ops = vdaf_dispatch!(task.vdaf(), (vdaf, VdafType, VERIFY_KEY_LEN, dp_strategy, DpStrategy) => {
+            <VdafType as IntoVdafOps<VERIFY_KEY_LEN, DpStrategy>>::into_vdaf_ops(
+                vdaf,
+                &task,
                 dp_strategy,
-                length,
-            } => {
-                match bitsize {
-                    Prio3FixedPointBoundedL2VecSumBitSize::BitSize16 => {
-                        let vdaf: Prio3FixedPointBoundedL2VecSumMultithreaded<FixedI16<U15>> =
-                            Prio3::new_fixedpoint_boundedl2_vec_sum_multithreaded(2, *length)?;
-                        let verify_key = task.vdaf_verify_key()?;
-                        VdafOps::Prio3FixedPoint16BitBoundedL2VecSum(Arc::new(vdaf), verify_key, vdaf_ops_strategies::Prio3FixedPointBoundedL2VecSum::from_vdaf_dp_strategy(dp_strategy.clone()))
-                    }
-                    Prio3FixedPointBoundedL2VecSumBitSize::BitSize32 => {
-                        let vdaf: Prio3FixedPointBoundedL2VecSumMultithreaded<FixedI32<U31>> =
-                            Prio3::new_fixedpoint_boundedl2_vec_sum_multithreaded(2, *length)?;
-                        let verify_key = task.vdaf_verify_key()?;
-                        VdafOps::Prio3FixedPoint32BitBoundedL2VecSum(Arc::new(vdaf), verify_key, vdaf_ops_strategies::Prio3FixedPointBoundedL2VecSum::from_vdaf_dp_strategy(dp_strategy.clone()))
-                    }
-                }
-            }
-
-            VdafInstance::Poplar1 { bits } => {
-                let vdaf = Poplar1::new_turboshake128(*bits);
-                let verify_key = task.vdaf_verify_key()?;
-                VdafOps::Poplar1(Arc::new(vdaf), verify_key)
-            }
-
-            #[cfg(feature = "test-util")]
-            VdafInstance::Fake => VdafOps::Fake(Arc::new(dummy::Vdaf::new(1))),
-
-            #[cfg(feature = "test-util")]
-            VdafInstance::FakeFailsPrepInit => VdafOps::Fake(Arc::new(
-                dummy::Vdaf::new(1).with_prep_init_fn(|_| -> Result<(), VdafError> {
-                    Err(VdafError::Uncategorized(
-                        "FakeFailsPrepInit failed at prep_init".to_string(),
-                    ))
-                }),
-            )),
-
-            #[cfg(feature = "test-util")]
-            VdafInstance::FakeFailsPrepStep => {
-                VdafOps::Fake(Arc::new(dummy::Vdaf::new(1).with_prep_step_fn(
-                    |_| -> Result<PrepareTransition<dummy::Vdaf, 0, 16>, VdafError> {
-                        Err(VdafError::Uncategorized(
-                            "FakeFailsPrepStep failed at prep_step".to_string(),
-                        ))
-                    },
-                )))
-            }
-
-            _ => panic!("VDAF {:?} is not yet supported", task.vdaf()),
-        };
+            )?
+        });
 
         Ok(Self {
             task: Arc::new(task),
@@ -993,6 +1609,10 @@ impl<C: Clock> TaskAggregator<C> {
         global_hpke_keypairs: &GlobalHpkeKeypairCache,
         upload_decrypt_failure_counter: &Counter<u64>,
         upload_decode_failure_counter: &Counter<u64>,
+        upload_unknown_extension_counter: &Counter<u64>,
+        upload_validation: &UploadValidation,
+        cheap_permit: UploadValidationPermit,
+        upload_sampler: &UploadSampler<C>,
         report: Report,
     ) -> Result<(), Arc<Error>> {
         self.vdaf_ops
@@ -1001,6 +1621,10 @@ impl<C: Clock> TaskAggregator<C> {
                 global_hpke_keypairs,
                 upload_decrypt_failure_counter,
                 upload_decode_failure_counter,
+                upload_unknown_extension_counter,
+                upload_validation,
+                cheap_permit,
+                upload_sampler,
                 &self.task,
                 &self.report_writer,
                 report,
@@ -1014,6 +1638,7 @@ impl<C: Clock> TaskAggregator<C> {
         clock: &C,
         global_hpke_keypairs: &GlobalHpkeKeypairCache,
         aggregate_step_failure_counter: &Counter<u64>,
+        memory_budget: &RequestMemoryBudget,
         batch_aggregation_shard_count: u64,
         aggregation_job_id: &AggregationJobId,
         require_taskprov_extension: bool,
@@ -1025,6 +1650,7 @@ impl<C: Clock> TaskAggregator<C> {
                 clock,
                 global_hpke_keypairs,
                 aggregate_step_failure_counter,
+                memory_budget,
                 Arc::clone(&self.task),
                 batch_aggregation_shard_count,
                 aggregation_job_id,
@@ -1069,6 +1695,7 @@ impl<C: Clock> TaskAggregator<C> {
     async fn handle_create_collection_job(
         &self,
         datastore: &Datastore<C>,
+        max_interval_time_precisions: Option<u64>,
         collection_job_id: &CollectionJobId,
         req_bytes: &[u8],
     ) -> Result<(), Error> {
@@ -1076,12 +1703,29 @@ impl<C: Clock> TaskAggregator<C> {
             .handle_create_collection_job(
                 datastore,
                 Arc::clone(&self.task),
+                max_interval_time_precisions,
                 collection_job_id,
                 req_bytes,
             )
             .await
     }
 
+    async fn handle_collection_preflight(
+        &self,
+        datastore: &Datastore<C>,
+        max_interval_time_precisions: Option<u64>,
+        req_bytes: &[u8],
+    ) -> Result<CollectionPreflight, Error> {
+        self.vdaf_ops
+            .handle_collection_preflight(
+                datastore,
+                Arc::clone(&self.task),
+                max_interval_time_precisions,
+                req_bytes,
+            )
+            .await
+    }
+
     async fn handle_get_collection_job(
         &self,
         datastore: &Datastore<C>,
@@ -1127,7 +1771,7 @@ impl<C: Clock> TaskAggregator<C> {
 mod vdaf_ops_strategies {
     use std::sync::Arc;
 
-    use janus_core::vdaf::vdaf_dp_strategies;
+    use janus_core::dp::NoDifferentialPrivacy;
     use prio::dp::distributions::ZCdpDiscreteGaussian;
 
     pub enum Prio3FixedPointBoundedL2VecSum {
@@ -1135,18 +1779,15 @@ mod vdaf_ops_strategies {
         ZCdpDiscreteGaussian(Arc<ZCdpDiscreteGaussian>),
     }
 
-    impl Prio3FixedPointBoundedL2VecSum {
-        pub fn from_vdaf_dp_strategy(
-            dp_strategy: vdaf_dp_strategies::Prio3FixedPointBoundedL2VecSum,
-        ) -> Self {
-            match dp_strategy {
-                vdaf_dp_strategies::Prio3FixedPointBoundedL2VecSum::NoDifferentialPrivacy => {
-                    Prio3FixedPointBoundedL2VecSum::NoDifferentialPrivacy
-                }
-                vdaf_dp_strategies::Prio3FixedPointBoundedL2VecSum::ZCdpDiscreteGaussian(s) => {
-                    Prio3FixedPointBoundedL2VecSum::ZCdpDiscreteGaussian(Arc::new(s))
-                }
-            }
+    impl From<NoDifferentialPrivacy> for Prio3FixedPointBoundedL2VecSum {
+        fn from(_: NoDifferentialPrivacy) -> Self {
+            Prio3FixedPointBoundedL2VecSum::NoDifferentialPrivacy
+        }
+    }
+
+    impl From<ZCdpDiscreteGaussian> for Prio3FixedPointBoundedL2VecSum {
+        fn from(strategy: ZCdpDiscreteGaussian) -> Self {
+            Prio3FixedPointBoundedL2VecSum::ZCdpDiscreteGaussian(Arc::new(strategy))
         }
     }
 }
@@ -1182,6 +1823,96 @@ enum VdafOps {
     Fake(Arc<dummy::Vdaf>),
 }
 
+/// Converts a VDAF constructed by [`vdaf_dispatch`] into [`VdafOps`]. Every VDAF type that
+/// [`vdaf_dispatch`] produces must implement this, so a VDAF that the aggregator doesn't know how to
+/// store fails to compile rather than panicking at runtime.
+trait IntoVdafOps<const SEED_SIZE: usize, DpStrategy> {
+    fn into_vdaf_ops(
+        self,
+        task: &AggregatorTask,
+        dp_strategy: DpStrategy,
+    ) -> Result<VdafOps, Error>;
+}
+
+macro_rules! impl_into_vdaf_ops {
+    ($Vdaf:ty, $SEED_SIZE:expr, $variant:ident) => {
+        impl IntoVdafOps<{ $SEED_SIZE }, NoDifferentialPrivacy> for $Vdaf {
+            fn into_vdaf_ops(
+                self,
+                task: &AggregatorTask,
+                _: NoDifferentialPrivacy,
+            ) -> Result<VdafOps, Error> {
+                Ok(VdafOps::$variant(Arc::new(self), task.vdaf_verify_key()?))
+            }
+        }
+    };
+}
+
+impl_into_vdaf_ops!(Prio3Count, VERIFY_KEY_LENGTH, Prio3Count);
+impl_into_vdaf_ops!(Prio3Sum, VERIFY_KEY_LENGTH, Prio3Sum);
+impl_into_vdaf_ops!(Prio3SumVecMultithreaded, VERIFY_KEY_LENGTH, Prio3SumVec);
+impl_into_vdaf_ops!(
+    Prio3SumVecField64MultiproofHmacSha256Aes128,
+    VERIFY_KEY_LENGTH_HMACSHA256_AES128,
+    Prio3SumVecField64MultiproofHmacSha256Aes128
+);
+impl_into_vdaf_ops!(Prio3Histogram, VERIFY_KEY_LENGTH, Prio3Histogram);
+impl_into_vdaf_ops!(
+    Poplar1<XofTurboShake128, 16>,
+    VERIFY_KEY_LENGTH,
+    Poplar1
+);
+
+#[cfg(feature = "fpvec_bounded_l2")]
+impl<S> IntoVdafOps<VERIFY_KEY_LENGTH, S>
+    for Prio3FixedPointBoundedL2VecSumMultithreaded<FixedI16<U15>>
+where
+    vdaf_ops_strategies::Prio3FixedPointBoundedL2VecSum: From<S>,
+{
+    fn into_vdaf_ops(self, task: &AggregatorTask, dp_strategy: S) -> Result<VdafOps, Error> {
+        Ok(VdafOps::Prio3FixedPoint16BitBoundedL2VecSum(
+            Arc::new(self),
+            task.vdaf_verify_key()?,
+            dp_strategy.into(),
+        ))
+    }
+}
+
+#[cfg(feature = "fpvec_bounded_l2")]
+impl<S> IntoVdafOps<VERIFY_KEY_LENGTH, S>
+    for Prio3FixedPointBoundedL2VecSumMultithreaded<FixedI32<U31>>
+where
+    vdaf_ops_strategies::Prio3FixedPointBoundedL2VecSum: From<S>,
+{
+    fn into_vdaf_ops(self, task: &AggregatorTask, dp_strategy: S) -> Result<VdafOps, Error> {
+        Ok(VdafOps::Prio3FixedPoint32BitBoundedL2VecSum(
+            Arc::new(self),
+            task.vdaf_verify_key()?,
+            dp_strategy.into(),
+        ))
+    }
+}
+
+// Other crates in the dependency graph may enable `janus_core/test-util` without enabling this
+// crate's `test-util` feature, in which case `vdaf_dispatch` still produces fake VDAFs.
+janus_core::if_vdaf_dispatch_test_util! {
+    impl IntoVdafOps<0, NoDifferentialPrivacy> for prio::vdaf::dummy::Vdaf {
+        fn into_vdaf_ops(
+            self,
+            _: &AggregatorTask,
+            _: NoDifferentialPrivacy,
+        ) -> Result<VdafOps, Error> {
+            #[cfg(feature = "test-util")]
+            return Ok(VdafOps::Fake(Arc::new(self)));
+
+            #[cfg(not(feature = "test-util"))]
+            Err(Error::Internal(
+                "fake VDAFs require the test-util feature".to_string(),
+            ))
+        }
+    }
+}
+
 /// Emits a match block dispatching on a [`VdafOps`] object. Takes a `&VdafOps` as the first
 /// argument, followed by a pseudo-pattern and body. The pseudo-pattern takes variable names for the
 /// constructed VDAF and the verify key, a type alias name that the block can use to explicitly
@@ -1334,6 +2065,10 @@ impl VdafOps {
         global_hpke_keypairs: &GlobalHpkeKeypairCache,
         upload_decrypt_failure_counter: &Counter<u64>,
         upload_decode_failure_counter: &Counter<u64>,
+        upload_unknown_extension_counter: &Counter<u64>,
+        upload_validation: &UploadValidation,
+        cheap_permit: UploadValidationPermit,
+        upload_sampler: &UploadSampler<C>,
         task: &AggregatorTask,
         report_writer: &ReportWriteBatcher<C>,
         report: Report,
@@ -1347,6 +2082,10 @@ impl VdafOps {
                         global_hpke_keypairs,
                         upload_decrypt_failure_counter,
                         upload_decode_failure_counter,
+                        upload_unknown_extension_counter,
+                        upload_validation,
+                        cheap_permit,
+                        upload_sampler,
                         task,
                         report_writer,
                         report,
@@ -1362,6 +2101,10 @@ impl VdafOps {
                         global_hpke_keypairs,
                         upload_decrypt_failure_counter,
                         upload_decode_failure_counter,
+                        upload_unknown_extension_counter,
+                        upload_validation,
+                        cheap_permit,
+                        upload_sampler,
                         task,
                         report_writer,
                         report,
@@ -1376,7 +2119,15 @@ impl VdafOps {
     ///
     /// [1]: https://www.ietf.org/archive/id/draft-ietf-ppm-dap-07.html#name-helper-initialization
     #[tracing::instrument(
-        skip(self, datastore, global_hpke_keypairs, aggregate_step_failure_counter, task, req_bytes),
+        skip(
+            self,
+            datastore,
+            global_hpke_keypairs,
+            aggregate_step_failure_counter,
+            memory_budget,
+            task,
+            req_bytes
+        ),
         fields(task_id = ?task.id()),
         err(level = Level::DEBUG)
     )]
@@ -1386,6 +2137,7 @@ impl VdafOps {
         clock: &C,
         global_hpke_keypairs: &GlobalHpkeKeypairCache,
         aggregate_step_failure_counter: &Counter<u64>,
+        memory_budget: &RequestMemoryBudget,
         task: Arc<AggregatorTask>,
         batch_aggregation_shard_count: u64,
         aggregation_job_id: &AggregationJobId,
@@ -1401,6 +2153,7 @@ impl VdafOps {
                         global_hpke_keypairs,
                         Arc::clone(vdaf),
                         aggregate_step_failure_counter,
+                        memory_budget,
                         task,
                         batch_aggregation_shard_count,
                         aggregation_job_id,
@@ -1419,6 +2172,7 @@ impl VdafOps {
                         global_hpke_keypairs,
                         Arc::clone(vdaf),
                         aggregate_step_failure_counter,
+                        memory_budget,
                         task,
                         batch_aggregation_shard_count,
                         aggregation_job_id,
@@ -1516,6 +2270,10 @@ impl VdafOps {
         global_hpke_keypairs: &GlobalHpkeKeypairCache,
         upload_decrypt_failure_counter: &Counter<u64>,
         upload_decode_failure_counter: &Counter<u64>,
+        upload_unknown_extension_counter: &Counter<u64>,
+        upload_validation: &UploadValidation,
+        cheap_permit: UploadValidationPermit,
+        upload_sampler: &UploadSampler<C>,
         task: &AggregatorTask,
         report_writer: &ReportWriteBatcher<C>,
         report: Report,
@@ -1531,32 +2289,58 @@ impl VdafOps {
     {
         // Shorthand function for generating an Error::ReportRejected with proper parameters and
         // recording it in the report_writer.
-        let reject_report = |reason| {
+        let reject_report = |reason, details| {
             let report_id = *report.metadata().id();
             let report_time = *report.metadata().time();
             async move {
-                let rejection = ReportRejection::new(*task.id(), report_id, report_time, reason);
-                report_writer.write_rejection(rejection).await;
+                let rejection = ReportRejection::new(*task.id(), report_id, report_time, reason)
+                    .with_details(details);
+                report_writer.write_rejection(rejection.clone()).await;
                 Ok::<_, Arc<Error>>(Arc::new(Error::ReportRejected(rejection)))
             }
         };
 
-        let report_deadline = clock
-            .now()
+        let now = clock.now();
+        let report_deadline = now
             .add(task.tolerable_clock_skew())
             .map_err(|err| Arc::new(Error::from(err)))?;
 
         // Reject reports from too far in the future.
         // https://www.ietf.org/archive/id/draft-ietf-ppm-dap-07.html#section-4.4.2-21
         if report.metadata().time().is_after(&report_deadline) {
-            return Err(reject_report(ReportRejectionReason::TooEarly).await?);
+            return Err(reject_report(
+                ReportRejectionReason::TooEarly,
+                ReportRejectionDetails {
+                    tolerable_clock_skew: Some(*task.tolerable_clock_skew()),
+                    latest_acceptable_time: Some(report_deadline),
+                    ..Default::default()
+                },
+            )
+            .await?);
         }
 
         // Reject reports after a task has expired.
         // https://www.ietf.org/archive/id/draft-ietf-ppm-dap-07.html#section-4.4.2-20
+        if !task.state().accepts_uploads() {
+            return Err(reject_report(
+                ReportRejectionReason::TaskExpired,
+                ReportRejectionDetails {
+                    latest_acceptable_time: task.task_expiration().copied(),
+                    ..Default::default()
+                },
+            )
+            .await?);
+        }
         if let Some(task_expiration) = task.task_expiration() {
             if report.metadata().time().is_after(task_expiration) {
-                return Err(reject_report(ReportRejectionReason::TaskExpired).await?);
+                return Err(reject_report(
+                    ReportRejectionReason::TaskExpired,
+                    ReportRejectionDetails {
+                        latest_acceptable_time: Some(*task_expiration),
+                        ..Default::default()
+                    },
+                )
+                .await?);
             }
         }
 
@@ -1567,11 +2351,27 @@ impl VdafOps {
                 .time()
                 .add(report_expiry_age)
                 .map_err(|err| Arc::new(Error::from(err)))?;
-            if clock.now().is_after(&report_expiry_time) {
-                return Err(reject_report(ReportRejectionReason::Expired).await?);
+            if now.is_after(&report_expiry_time) {
+                return Err(reject_report(
+                    ReportRejectionReason::Expired,
+                    ReportRejectionDetails {
+                        earliest_acceptable_time: now.sub(report_expiry_age).ok(),
+                        ..Default::default()
+                    },
+                )
+                .await?);
             }
         }
 
+        // Reports that were recently accepted would be ignored by the datastore as duplicates, so
+        // there is no need to validate them again.
+        if upload_validation.is_recently_accepted(task.id(), report.metadata().id()) {
+            return Ok(());
+        }
+        let expensive_permit = upload_validation
+            .enter_expensive_stage(task.id(), cheap_permit)
+            .await;
+
         // Decode (and in the case of the leader input share, decrypt) the remaining fields of the
         // report before storing them in the datastore. The spec does not require the
         // `tasks/{task-id}/reports` handler to do this, but it exercises HPKE decryption, saves us
@@ -1588,7 +2388,11 @@ impl VdafOps {
                         "public share decoding failed",
                     );
                     upload_decode_failure_counter.add(1, &[]);
-                    return Err(reject_report(ReportRejectionReason::DecodeFailure).await?);
+                    return Err(reject_report(
+                        ReportRejectionReason::DecodeFailure,
+                        ReportRejectionDetails::default(),
+                    )
+                    .await?);
                 }
             };
 
@@ -1619,9 +2423,25 @@ impl VdafOps {
             // Verify that the report's HPKE config ID is known.
             // https://www.ietf.org/archive/id/draft-ietf-ppm-dap-07.html#section-4.4.2-17
             (None, None) => {
-                return Err(reject_report(ReportRejectionReason::OutdatedHpkeConfig(
-                    *report.leader_encrypted_input_share().config_id(),
-                ))
+                // Tell the client which configurations we can decrypt with, so it knows to refetch
+                // them.
+                let hpke_config_ids = task
+                    .hpke_keys()
+                    .keys()
+                    .copied()
+                    .chain(global_hpke_keypairs.configs().iter().map(|c| *c.id()))
+                    .collect::<BTreeSet<_>>()
+                    .into_iter()
+                    .collect();
+                return Err(reject_report(
+                    ReportRejectionReason::OutdatedHpkeConfig(
+                        *report.leader_encrypted_input_share().config_id(),
+                    ),
+                    ReportRejectionDetails {
+                        hpke_config_ids: Some(hpke_config_ids),
+                        ..Default::default()
+                    },
+                )
                 .await?);
             }
             (None, Some(global_hpke_keypair)) => try_hpke_open(&global_hpke_keypair),
@@ -1646,7 +2466,11 @@ impl VdafOps {
                     "Report decryption failed",
                 );
                 upload_decrypt_failure_counter.add(1, &[]);
-                return Err(reject_report(ReportRejectionReason::DecryptFailure).await?);
+                return Err(reject_report(
+                    ReportRejectionReason::DecryptFailure,
+                    ReportRejectionDetails::default(),
+                )
+                .await?);
             }
         };
 
@@ -1673,10 +2497,40 @@ impl VdafOps {
                     "Leader input share decoding failed",
                 );
                 upload_decode_failure_counter.add(1, &[]);
-                return Err(reject_report(ReportRejectionReason::DecodeFailure).await?);
+                return Err(reject_report(
+                    ReportRejectionReason::DecodeFailure,
+                    ReportRejectionDetails::default(),
+                )
+                .await?);
+            }
+        };
+
+        if extensions
+            .iter()
+            .any(|extension| matches!(extension.extension_type(), ExtensionType::Unknown(_)))
+        {
+            match task.unknown_extension_policy() {
+                UnknownExtensionPolicy::Reject => {
+                    debug!(
+                        report.task_id = %task.id(),
+                        report.metadata = ?report.metadata(),
+                        "Report carries extensions of an unknown type",
+                    );
+                    upload_decode_failure_counter.add(1, &[]);
+                    return Err(reject_report(
+                        ReportRejectionReason::DecodeFailure,
+                        ReportRejectionDetails::default(),
+                    )
+                    .await?);
+                }
+                UnknownExtensionPolicy::Accept => {}
+                UnknownExtensionPolicy::AcceptWithMetric => {
+                    upload_unknown_extension_counter.add(1, &[]);
+                }
             }
-        };
+        }
 
+        let sample = upload_sampler.sample(task.id(), &report, now, &extensions);
         let report = LeaderStoredReport::new(
             *task.id(),
             report.metadata().clone(),
@@ -1686,11 +2540,20 @@ impl VdafOps {
             report.helper_encrypted_input_share().clone(),
         );
 
+        // Validation is complete; the write below may wait for a batch of reports to fill.
+        drop(expensive_permit);
+
+        let report_id = *report.metadata().id();
         report_writer
             .write_report(Box::new(WritableReport::<SEED_SIZE, Q, A>::new(
                 vdaf, report,
             )))
-            .await
+            .await?;
+        upload_validation.record_accepted(task.id(), &report_id);
+        if let Some(sample) = sample {
+            upload_sampler.record(sample).await;
+        }
+        Ok(())
     }
 }
 
@@ -1715,6 +2578,7 @@ impl VdafOps {
         global_hpke_keypairs: &GlobalHpkeKeypairCache,
         vdaf: Arc<A>,
         aggregate_step_failure_counter: &Counter<u64>,
+        memory_budget: &RequestMemoryBudget,
         task: Arc<AggregatorTask>,
         batch_aggregation_shard_count: u64,
         aggregation_job_id: &AggregationJobId,
@@ -1738,7 +2602,11 @@ impl VdafOps {
     {
         // unwrap safety: SHA-256 computed by ring should always be 32 bytes
         let request_hash = digest(&SHA256, req_bytes).as_ref().try_into().unwrap();
-        let req = AggregationJobInitializeReq::<Q>::get_decoded(req_bytes)?;
+        // A copy of each report share in the request body is held until the aggregation job is
+        // written. The request's preparation initialization messages are decoded one at a time, so
+        // that the decoded request is never held in memory alongside those copies.
+        memory_budget.charge(req_bytes.len())?;
+        let req = AggregationJobInitializeReq::<Q>::decode_streaming(req_bytes)?;
 
         let report_deadline = clock
             .now()
@@ -1747,9 +2615,9 @@ impl VdafOps {
 
         // If two ReportShare messages have the same report ID, then the helper MUST abort with
         // error "invalidMessage". (§4.5.1.2)
-        let mut seen_report_ids = HashSet::with_capacity(req.prepare_inits().len());
+        let mut seen_report_ids = HashSet::new();
         for prepare_init in req.prepare_inits() {
-            if !seen_report_ids.insert(*prepare_init.report_share().metadata().id()) {
+            if !seen_report_ids.insert(*prepare_init?.report_share().metadata().id()) {
                 return Err(Error::InvalidMessage(
                     Some(*task.id()),
                     "aggregate request contains duplicate report IDs",
@@ -1760,7 +2628,9 @@ impl VdafOps {
         // Decrypt shares & prepare initialization states. (§4.4.4.1)
         let mut report_share_data = Vec::new();
         let agg_param = A::AggregationParam::get_decoded(req.aggregation_parameter())?;
-        for (ord, prepare_init) in req.prepare_inits().iter().enumerate() {
+        for (ord, prepare_init) in req.prepare_inits().enumerate() {
+            let prepare_init = prepare_init?;
+
             // If decryption fails, then the aggregator MUST fail with error `hpke-decrypt-error`. (§4.4.2.2)
             let input_share_aad = InputShareAad::new(
                 *task.id(),
@@ -1830,6 +2700,9 @@ impl VdafOps {
                     PrepareError::HpkeDecryptError
                 })
             });
+            if let Ok(plaintext) = &plaintext {
+                memory_budget.charge(plaintext.len())?;
+            }
 
             let plaintext_input_share = plaintext.and_then(|plaintext| {
                 let plaintext_input_share =
@@ -1889,6 +2762,20 @@ impl VdafOps {
                     return Err(PrepareError::InvalidMessage);
                 }
 
+                if extensions
+                    .keys()
+                    .any(|extension_type| matches!(extension_type, ExtensionType::Unknown(_)))
+                {
+                    debug!(
+                        task_id = %task.id(),
+                        metadata = ?prepare_init.report_share().metadata(),
+                        "Received report share with extensions of an unknown type",
+                    );
+                    aggregate_step_failure_counter
+                        .add(1, &[KeyValue::new("type", "unknown_extension")]);
+                    return Err(PrepareError::InvalidMessage);
+                }
+
                 Ok(plaintext_input_share)
             });
 
@@ -1966,6 +2853,18 @@ impl VdafOps {
                 })
             });
 
+            if let Ok((state, outgoing_message)) = &init_rslt {
+                let state_len = match state {
+                    PingPongState::Continued(prepare_state) => prepare_state.encoded_len(),
+                    PingPongState::Finished(_) => None,
+                };
+                memory_budget.charge(
+                    state_len
+                        .unwrap_or(0)
+                        .saturating_add(outgoing_message.encoded_len().unwrap_or(0)),
+                )?;
+            }
+
             let (report_aggregation_state, prepare_step_result, output_share) = match init_rslt {
                 Ok((PingPongState::Continued(prepare_state), outgoing_message)) => {
                     // Helper is not finished. Await the next message from the Leader to advance to
@@ -2013,17 +2912,14 @@ impl VdafOps {
         }
 
         // Store data to datastore.
-        let req = Arc::new(req);
-        let min_client_timestamp = req
-            .prepare_inits()
+        let min_client_timestamp = report_share_data
             .iter()
-            .map(|prepare_init| *prepare_init.report_share().metadata().time())
+            .map(|rsd| *rsd.report_share.metadata().time())
             .min()
             .ok_or_else(|| Error::EmptyAggregation(*task.id()))?;
-        let max_client_timestamp = req
-            .prepare_inits()
+        let max_client_timestamp = report_share_data
             .iter()
-            .map(|prepare_init| *prepare_init.report_share().metadata().time())
+            .map(|rsd| *rsd.report_share.metadata().time())
             .max()
             .ok_or_else(|| Error::EmptyAggregation(*task.id()))?;
         let client_timestamp_interval = Interval::new(
@@ -2352,6 +3248,7 @@ impl VdafOps {
         &self,
         datastore: &Datastore<C>,
         task: Arc<AggregatorTask>,
+        max_interval_time_precisions: Option<u64>,
         collection_job_id: &CollectionJobId,
         collection_req_bytes: &[u8],
     ) -> Result<(), Error> {
@@ -2363,7 +3260,14 @@ impl VdafOps {
                         TimeInterval,
                         VdafType,
                         _,
-                    >(datastore, task, Arc::clone(vdaf), collection_job_id, collection_req_bytes)
+                    >(
+                        datastore,
+                        task,
+                        Arc::clone(vdaf),
+                        max_interval_time_precisions,
+                        collection_job_id,
+                        collection_req_bytes,
+                    )
                     .await
                 })
             }
@@ -2374,7 +3278,14 @@ impl VdafOps {
                         FixedSize,
                         VdafType,
                         _,
-                    >(datastore, task, Arc::clone(vdaf), collection_job_id, collection_req_bytes)
+                    >(
+                        datastore,
+                        task,
+                        Arc::clone(vdaf),
+                        max_interval_time_precisions,
+                        collection_job_id,
+                        collection_req_bytes,
+                    )
                     .await
                 })
             }
@@ -2390,6 +3301,7 @@ impl VdafOps {
         datastore: &Datastore<C>,
         task: Arc<AggregatorTask>,
         vdaf: Arc<A>,
+        max_interval_time_precisions: Option<u64>,
         collection_job_id: &CollectionJobId,
         req_bytes: &[u8],
     ) -> Result<(), Error>
@@ -2437,48 +3349,17 @@ impl VdafOps {
                         }
                     }
 
-                    let collection_identifier =
-                        Q::collection_identifier_for_query(tx, &task, req.query())
-                            .await?
-                            .ok_or_else(|| {
-                                datastore::Error::User(
-                                    Error::BatchInvalid(
-                                        *task.id(),
-                                        "no batch ready for collection".to_string(),
-                                    )
-                                    .into(),
-                                )
-                            })?;
-
-                    // Check that the batch interval is valid for the task
-                    // https://www.ietf.org/archive/id/draft-ietf-ppm-dap-02.html#section-4.5.6.1.1
-                    if !Q::validate_collection_identifier(&task, &collection_identifier) {
-                        return Err(datastore::Error::User(
-                            Error::BatchInvalid(*task.id(), format!("{collection_identifier}"))
-                                .into(),
-                        ));
-                    }
-
                     debug!(collect_request = ?req, "Cache miss, creating new collection job");
-                    let (_, report_count) = try_join!(
-                        Q::validate_query_count::<SEED_SIZE, C, A>(
+                    let (collection_identifier, _) =
+                        Self::validate_collection_req::<SEED_SIZE, Q, A, C>(
                             tx,
-                            &vdaf,
                             &task,
-                            &collection_identifier,
+                            &vdaf,
+                            max_interval_time_precisions,
+                            req.query(),
                             &aggregation_param,
-                        ),
-                        Q::count_client_reports(tx, &task, &collection_identifier),
-                    )?;
-
-                    // Batch size must be validated while handling CollectReq and hence before
-                    // creating a collection job.
-                    // https://www.ietf.org/archive/id/draft-ietf-ppm-dap-02.html#section-4.5.6
-                    if !task.validate_batch_size(report_count) {
-                        return Err(datastore::Error::User(
-                            Error::InvalidBatchSize(*task.id(), report_count).into(),
-                        ));
-                    }
+                        )
+                        .await?;
 
                     tx.put_collection_job(&CollectionJob::<SEED_SIZE, Q, A>::new(
                         *task.id(),
@@ -2496,6 +3377,216 @@ impl VdafOps {
             .await?)
     }
 
+    /// Checks that a collection job for `query` and `aggregation_param` may be created: that it
+    /// identifies a batch that is valid for the task, that collecting it would not exceed the
+    /// task's batch query limit or overlap previously collected batches, and that the batch has an
+    /// acceptable number of reports. If `max_interval_time_precisions` is set, the batch may not
+    /// span more than that many multiples of the task's time precision. Returns the identifier of
+    /// the batch to collect, along with the number of reports in it.
+    async fn validate_collection_req<
+        const SEED_SIZE: usize,
+        Q: CollectableQueryType,
+        A: vdaf::Aggregator<SEED_SIZE, 16> + Send + Sync + 'static,
+        C: Clock,
+    >(
+        tx: &Transaction<'_, C>,
+        task: &AggregatorTask,
+        vdaf: &A,
+        max_interval_time_precisions: Option<u64>,
+        query: &Query<Q>,
+        aggregation_param: &A::AggregationParam,
+    ) -> Result<(Q::BatchIdentifier, u64), datastore::Error>
+    where
+        A::AggregationParam: Send + Sync + Eq + Hash,
+    {
+        let collection_identifier = Q::collection_identifier_for_query(tx, task, query)
+            .await?
+            .ok_or_else(|| {
+                datastore::Error::User(
+                    Error::BatchInvalid(*task.id(), "no batch ready for collection".to_string())
+                        .into(),
+                )
+            })?;
+
+        // Check that the batch interval is valid for the task
+        // https://www.ietf.org/archive/id/draft-ietf-ppm-dap-02.html#section-4.5.6.1.1
+        Q::validate_collection_identifier(task, &collection_identifier).map_err(|violation| {
+            datastore::Error::User(
+                Error::BatchIntervalMisaligned(
+                    *task.id(),
+                    collection_identifier.to_string(),
+                    violation,
+                )
+                .into(),
+            )
+        })?;
+        if let Some(max_interval_time_precisions) = max_interval_time_precisions {
+            // Only count as many batches as needed, since the batch interval may be very long.
+            let limit = usize::try_from(max_interval_time_precisions).unwrap_or(usize::MAX);
+            if Q::batch_identifiers_for_collection_identifier(task, &collection_identifier)
+                .take(limit.saturating_add(1))
+                .count()
+                > limit
+            {
+                return Err(datastore::Error::User(
+                    Error::BatchIntervalTooLong(*task.id(), max_interval_time_precisions).into(),
+                ));
+            }
+        }
+
+        let (_, report_count) = try_join!(
+            Q::validate_query_count::<SEED_SIZE, C, A>(
+                tx,
+                vdaf,
+                task,
+                &collection_identifier,
+                aggregation_param,
+            ),
+            Q::count_client_reports(tx, task, &collection_identifier),
+        )?;
+
+        // Batch size must be validated while handling CollectReq and hence before
+        // creating a collection job.
+        // https://www.ietf.org/archive/id/draft-ietf-ppm-dap-02.html#section-4.5.6
+        if !task.validate_batch_size(report_count) {
+            return Err(datastore::Error::User(
+                Error::InvalidBatchSize(*task.id(), report_count).into(),
+            ));
+        }
+
+        Ok((collection_identifier, report_count))
+    }
+
+    /// Handle requests to the leader's collection preflight endpoint, checking a collection
+    /// request as [`Self::handle_create_collection_job`] would, without creating a collection job.
+    async fn handle_collection_preflight<C: Clock>(
+        &self,
+        datastore: &Datastore<C>,
+        task: Arc<AggregatorTask>,
+        max_interval_time_precisions: Option<u64>,
+        collection_req_bytes: &[u8],
+    ) -> Result<CollectionPreflight, Error> {
+        match task.query_type() {
+            task::QueryType::TimeInterval => {
+                vdaf_ops_dispatch!(self, (vdaf, _, VdafType, VERIFY_KEY_LENGTH) => {
+                    Self::handle_collection_preflight_generic::<
+                        VERIFY_KEY_LENGTH,
+                        TimeInterval,
+                        VdafType,
+                        _,
+                    >(
+                        datastore,
+                        task,
+                        Arc::clone(vdaf),
+                        max_interval_time_precisions,
+                        collection_req_bytes,
+                    )
+                    .await
+                })
+            }
+            task::QueryType::FixedSize { .. } => {
+                vdaf_ops_dispatch!(self, (vdaf, _, VdafType, VERIFY_KEY_LENGTH) => {
+                    Self::handle_collection_preflight_generic::<
+                        VERIFY_KEY_LENGTH,
+                        FixedSize,
+                        VdafType,
+                        _,
+                    >(
+                        datastore,
+                        task,
+                        Arc::clone(vdaf),
+                        max_interval_time_precisions,
+                        collection_req_bytes,
+                    )
+                    .await
+                })
+            }
+        }
+    }
+
+    async fn handle_collection_preflight_generic<
+        const SEED_SIZE: usize,
+        Q: CollectableQueryType,
+        A: vdaf::Aggregator<SEED_SIZE, 16> + Send + Sync + 'static,
+        C: Clock,
+    >(
+        datastore: &Datastore<C>,
+        task: Arc<AggregatorTask>,
+        vdaf: Arc<A>,
+        max_interval_time_precisions: Option<u64>,
+        req_bytes: &[u8],
+    ) -> Result<CollectionPreflight, Error>
+    where
+        A::AggregationParam: 'static + Send + Sync + PartialEq + Eq + Hash,
+        A::AggregateShare: Send + Sync,
+    {
+        let req = Arc::new(CollectionReq::<Q>::get_decoded(req_bytes)?);
+        let aggregation_param = Arc::new(A::AggregationParam::get_decoded(
+            req.aggregation_parameter(),
+        )?);
+
+        let result = datastore
+            .run_tx("collection_preflight", |tx| {
+                let (task, vdaf, req, aggregation_param) = (
+                    Arc::clone(&task),
+                    Arc::clone(&vdaf),
+                    Arc::clone(&req),
+                    Arc::clone(&aggregation_param),
+                );
+                Box::pin(async move {
+                    Self::validate_collection_req::<SEED_SIZE, Q, A, C>(
+                        tx,
+                        &task,
+                        &vdaf,
+                        max_interval_time_precisions,
+                        req.query(),
+                        &aggregation_param,
+                    )
+                    .await
+                })
+            })
+            .await
+            .map_err(Error::from);
+
+        let mut preflight = CollectionPreflight {
+            accepted: false,
+            error: None,
+            detail: None,
+            batch: None,
+            report_count: None,
+            min_batch_size: task.min_batch_size(),
+            max_batch_size: match task.query_type() {
+                task::QueryType::FixedSize { max_batch_size, .. } => *max_batch_size,
+                task::QueryType::TimeInterval => None,
+            },
+        };
+        match result {
+            Ok((collection_identifier, report_count)) => {
+                preflight.accepted = true;
+                preflight.batch = Some(collection_identifier.to_string());
+                preflight.report_count = Some(report_count);
+            }
+            // Rejections of the request itself are reported in the response. Other errors, e.g.
+            // datastore failures, fail the request as they would fail collection job creation.
+            Err(
+                err @ (Error::BatchInvalid(..)
+                | Error::BatchIntervalMisaligned(..)
+                | Error::BatchIntervalTooLong(..)
+                | Error::BatchOverlap(..)
+                | Error::BatchQueriedTooManyTimes(..)
+                | Error::InvalidBatchSize(..)),
+            ) => {
+                if let Error::InvalidBatchSize(_, report_count) = err {
+                    preflight.report_count = Some(report_count);
+                }
+                preflight.error = Some(err.error_code());
+                preflight.detail = Some(err.to_string());
+            }
+            Err(err) => return Err(err),
+        }
+        Ok(preflight)
+    }
+
     /// Handle GET requests to the leader's `tasks/{task-id}/collection_jobs/{collection-job-id}`
     /// endpoint. The return value is an encoded `CollectResp<Q>`.
     /// <https://www.ietf.org/archive/id/draft-ietf-ppm-dap-07.html#name-collecting-results>
@@ -2548,22 +3639,37 @@ impl VdafOps {
         A::AggregationParam: Send + Sync,
         A::AggregateShare: Send + Sync,
     {
-        let collection_job = datastore
-            .run_tx("get_collection_job", |tx| {
-                let (task, vdaf, collection_job_id) =
-                    (Arc::clone(&task), Arc::clone(&vdaf), *collection_job_id);
+        // Collection jobs are polled far more often than they are written, so look them up on the
+        // read replica, if any. A job created moments ago may not have been replicated yet, so
+        // check the primary database before giving up on it.
+        let collection_job = match datastore
+            .run_read_only_tx("get_collection_job", |tx| {
+                let (task_id, vdaf, collection_job_id) =
+                    (*task.id(), Arc::clone(&vdaf), *collection_job_id);
                 Box::pin(async move {
-                    tx.get_collection_job::<SEED_SIZE, Q, A>(&vdaf, task.id(), &collection_job_id)
-                        .await?
-                        .ok_or_else(|| {
-                            datastore::Error::User(
-                                Error::UnrecognizedCollectionJob(*task.id(), collection_job_id)
-                                    .into(),
-                            )
-                        })
+                    tx.get_collection_job::<SEED_SIZE, Q, A>(&vdaf, &task_id, &collection_job_id)
+                        .await
                 })
             })
-            .await?;
+            .await?
+        {
+            Some(collection_job) => collection_job,
+            None => datastore
+                .run_tx("get_collection_job", |tx| {
+                    let (task_id, vdaf, collection_job_id) =
+                        (*task.id(), Arc::clone(&vdaf), *collection_job_id);
+                    Box::pin(async move {
+                        tx.get_collection_job::<SEED_SIZE, Q, A>(
+                            &vdaf,
+                            &task_id,
+                            &collection_job_id,
+                        )
+                        .await
+                    })
+                })
+                .await?
+                .ok_or_else(|| Error::UnrecognizedCollectionJob(*task.id(), *collection_job_id))?,
+        };
 
         match collection_job.state() {
             CollectionJobState::Start => {
@@ -2643,6 +3749,95 @@ impl VdafOps {
         }
     }
 
+    #[tracing::instrument(
+        skip(self, datastore, task, collector_hpke_keypair),
+        fields(task_id = ?task.id()),
+        err(level = Level::DEBUG)
+    )]
+    async fn handle_get_collection_summary<C: Clock>(
+        &self,
+        datastore: &Datastore<C>,
+        task: Arc<AggregatorTask>,
+        collection_job_id: &CollectionJobId,
+        collector_hpke_keypair: &HpkeKeypair,
+    ) -> Result<Option<CollectionSummary>, Error> {
+        match task.query_type() {
+            task::QueryType::TimeInterval => {
+                vdaf_ops_dispatch!(self, (vdaf, _, VdafType, VERIFY_KEY_LENGTH) => {
+                    Self::handle_get_collection_summary_generic::<
+                        VERIFY_KEY_LENGTH,
+                        TimeInterval,
+                        VdafType,
+                        _,
+                    >(datastore, task, Arc::clone(vdaf), collection_job_id, collector_hpke_keypair)
+                    .await
+                })
+            }
+            task::QueryType::FixedSize { .. } => {
+                vdaf_ops_dispatch!(self, (vdaf, _, VdafType, VERIFY_KEY_LENGTH) => {
+                    Self::handle_get_collection_summary_generic::<
+                        VERIFY_KEY_LENGTH,
+                        FixedSize,
+                        VdafType,
+                        _,
+                    >(datastore, task, Arc::clone(vdaf), collection_job_id, collector_hpke_keypair)
+                    .await
+                })
+            }
+        }
+    }
+
+    async fn handle_get_collection_summary_generic<
+        const SEED_SIZE: usize,
+        Q: CollectableQueryType,
+        A: vdaf::Aggregator<SEED_SIZE, 16> + vdaf::Collector + Send + Sync + 'static,
+        C: Clock,
+    >(
+        datastore: &Datastore<C>,
+        task: Arc<AggregatorTask>,
+        vdaf: Arc<A>,
+        collection_job_id: &CollectionJobId,
+        collector_hpke_keypair: &HpkeKeypair,
+    ) -> Result<Option<CollectionSummary>, Error>
+    where
+        A::AggregationParam: Send + Sync,
+        A::AggregateShare: Send + Sync,
+        A::AggregateResult: Serialize,
+    {
+        let collection_job = datastore
+            .run_tx("get_collection_summary", |tx| {
+                let (task, vdaf, collection_job_id) =
+                    (Arc::clone(&task), Arc::clone(&vdaf), *collection_job_id);
+                Box::pin(async move {
+                    tx.get_collection_job::<SEED_SIZE, Q, A>(&vdaf, task.id(), &collection_job_id)
+                        .await?
+                        .ok_or_else(|| {
+                            datastore::Error::User(
+                                Error::UnrecognizedCollectionJob(*task.id(), collection_job_id)
+                                    .into(),
+                            )
+                        })
+                })
+            })
+            .await?;
+
+        match collection_job.state() {
+            CollectionJobState::Start => return Ok(None),
+            CollectionJobState::Finished { .. } => (),
+            CollectionJobState::Abandoned => {
+                return Err(Error::AbandonedCollectionJob(
+                    *task.id(),
+                    *collection_job_id,
+                ))
+            }
+            CollectionJobState::Deleted => {
+                return Err(Error::DeletedCollectionJob(*task.id(), *collection_job_id))
+            }
+        }
+
+        CollectionSummary::decode(vdaf.as_ref(), &collection_job, collector_hpke_keypair).map(Some)
+    }
+
     #[tracing::instrument(skip(self, datastore, task), fields(task_id = ?task.id()), err(level = Level::DEBUG))]
     async fn handle_delete_collection_job<C: Clock>(
         &self,
@@ -2788,18 +3983,20 @@ impl VdafOps {
         let aggregate_share_req = Arc::new(AggregateShareReq::<Q>::get_decoded(req_bytes)?);
 
         // §4.4.4.3: check that the batch interval meets the requirements from §4.6
-        if !Q::validate_collection_identifier(
+        Q::validate_collection_identifier(
             &task,
             aggregate_share_req.batch_selector().batch_identifier(),
-        ) {
-            return Err(Error::BatchInvalid(
+        )
+        .map_err(|violation| {
+            Error::BatchIntervalMisaligned(
                 *task.id(),
-                format!(
-                    "{}",
-                    aggregate_share_req.batch_selector().batch_identifier()
-                ),
-            ));
-        }
+                aggregate_share_req
+                    .batch_selector()
+                    .batch_identifier()
+                    .to_string(),
+                violation,
+            )
+        })?;
 
         // Reject requests for aggregation shares that are eligible for GC, to prevent replay
         // attacks.
@@ -3078,6 +4275,7 @@ impl<'a, E> Notify<E> for &RequestTimer<'a> {
         url,
         request_body,
         auth_token,
+        helper_request_headers,
         http_request_duration_histogram,
     ),
     fields(url = %url),
@@ -3091,6 +4289,7 @@ async fn send_request_to_helper(
     route_label: &'static str,
     request_body: Option<RequestBody>,
     auth_token: &AuthenticationToken,
+    helper_request_headers: &[HelperRequestHeader],
     http_request_duration_histogram: &Histogram<f64>,
 ) -> Result<Bytes, Error> {
     let (auth_header, auth_value) = auth_token.request_authentication();
@@ -3108,6 +4307,9 @@ async fn send_request_to_helper(
         let mut request = http_client
             .request(method.clone(), url.clone())
             .header(auth_header, auth_value.as_str());
+        for header in helper_request_headers {
+            request = request.header(header.name(), header.value());
+        }
         if let Some(request_body) = request_body.clone() {
             request = request
                 .header(CONTENT_TYPE, request_body.content_type)
@@ -3161,25 +4363,33 @@ pub(crate) mod test_util {
 
 #[cfg(test)]
 mod tests {
-    use crate::aggregator::{
-        error::ReportRejectionReason, test_util::default_aggregator_config, Aggregator, Config,
-        Error,
+    use crate::{
+        aggregator::{
+            error::{ReportRejectionDetails, ReportRejectionReason},
+            report_policy::{PolicyDecision, PolicyUpload, ReportPolicy},
+            test_util::default_aggregator_config,
+            upload_queue::{DirectoryUploadQueue, UploadIngester, UploadQueue},
+            Aggregator, Config, Error,
+        },
+        config::UploadSamplingConfig,
     };
     use assert_matches::assert_matches;
+    use async_trait::async_trait;
     use futures::future::try_join_all;
     use janus_aggregator_core::{
         datastore::{
-            models::{CollectionJob, CollectionJobState, TaskUploadCounter},
+            models::{CollectionJob, CollectionJobState, TaskUploadCounter, UploadSample},
             test_util::{ephemeral_datastore, EphemeralDatastore},
             Datastore,
         },
         task::{
             test_util::{Task, TaskBuilder},
-            AggregatorTask, QueryType,
+            AggregatorTask, QueryType, UnknownExtensionPolicy,
         },
         test_util::noop_meter,
     };
     use janus_core::{
+        auth_tokens::{AuthenticationToken, AuthenticationTokenHash},
         hpke::{
             self, test_util::generate_test_hpke_config_and_private_key_with_id,
             HpkeApplicationInfo, HpkeKeypair, Label,
@@ -3193,9 +4403,9 @@ mod tests {
         Runtime,
     };
     use janus_messages::{
-        query_type::TimeInterval, Duration, Extension, HpkeCiphertext, HpkeConfig, HpkeConfigId,
-        InputShareAad, Interval, PlaintextInputShare, Query, Report, ReportId, ReportMetadata,
-        ReportShare, Role, TaskId, Time,
+        query_type::TimeInterval, Duration, Extension, ExtensionType, HpkeCiphertext, HpkeConfig,
+        HpkeConfigId, InputShareAad, Interval, PlaintextInputShare, Query, Report, ReportId,
+        ReportMetadata, ReportShare, Role, TaskId, Time,
     };
     use prio::{
         codec::Encode,
@@ -3203,6 +4413,7 @@ mod tests {
     };
     use rand::random;
     use std::{collections::HashSet, iter, sync::Arc, time::Duration as StdDuration};
+    use trillium::Headers;
 
     pub(super) fn create_report_custom(
         task: &AggregatorTask,
@@ -3294,6 +4505,7 @@ mod tests {
 
         let aggregator = Aggregator::new(
             Arc::clone(&datastore),
+            None,
             clock.clone(),
             runtime,
             &noop_meter(),
@@ -3326,7 +4538,12 @@ mod tests {
         let report = create_report(&leader_task, clock.now());
 
         aggregator
-            .handle_upload(task.id(), &report.get_encoded().unwrap())
+            .handle_upload(
+                task.id(),
+                &report.get_encoded().unwrap(),
+                None,
+                &Headers::new(),
+            )
             .await
             .unwrap();
 
@@ -3344,7 +4561,12 @@ mod tests {
 
         // Report uploads are idempotent.
         aggregator
-            .handle_upload(task.id(), &report.get_encoded().unwrap())
+            .handle_upload(
+                task.id(),
+                &report.get_encoded().unwrap(),
+                None,
+                &Headers::new(),
+            )
             .await
             .unwrap();
 
@@ -3357,7 +4579,12 @@ mod tests {
             leader_task.current_hpke_key(),
         );
         aggregator
-            .handle_upload(task.id(), &mutated_report.get_encoded().unwrap())
+            .handle_upload(
+                task.id(),
+                &mutated_report.get_encoded().unwrap(),
+                None,
+                &Headers::new(),
+            )
             .await
             .unwrap();
 
@@ -3388,6 +4615,77 @@ mod tests {
         )
     }
 
+    #[tokio::test]
+    async fn upload_secondary_datastore() {
+        install_test_trace_subscriber();
+
+        let clock = MockClock::default();
+        let vdaf = Prio3Count::new_count(2).unwrap();
+        let task = TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Prio3Count).build();
+        let leader_task = task.leader_view().unwrap();
+
+        let primary_ephemeral_datastore = ephemeral_datastore().await;
+        let primary_ds = Arc::new(primary_ephemeral_datastore.datastore(clock.clone()).await);
+        let secondary_ephemeral_datastore = ephemeral_datastore().await;
+        let secondary_ds = Arc::new(secondary_ephemeral_datastore.datastore(clock.clone()).await);
+        for ds in [&primary_ds, &secondary_ds] {
+            ds.put_aggregator_task(&leader_task).await.unwrap();
+        }
+
+        let aggregator = Aggregator::new(
+            Arc::clone(&primary_ds),
+            Some(Arc::clone(&secondary_ds)),
+            clock.clone(),
+            TestRuntime::default(),
+            &noop_meter(),
+            Config {
+                max_upload_batch_size: 1000,
+                max_upload_batch_write_delay: StdDuration::from_millis(500),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let report = create_report(&leader_task, clock.now());
+        aggregator
+            .handle_upload(
+                task.id(),
+                &report.get_encoded().unwrap(),
+                None,
+                &Headers::new(),
+            )
+            .await
+            .unwrap();
+
+        // The report is written to both datastores.
+        for ds in [&primary_ds, &secondary_ds] {
+            let (got_report, got_counter) = ds
+                .run_unnamed_tx(|tx| {
+                    let vdaf = vdaf.clone();
+                    let task_id = *task.id();
+                    let report_id = *report.metadata().id();
+                    Box::pin(async move {
+                        Ok((
+                            tx.get_client_report(&vdaf, &task_id, &report_id)
+                                .await
+                                .unwrap(),
+                            tx.get_task_upload_counter(&task_id).await.unwrap(),
+                        ))
+                    })
+                })
+                .await
+                .unwrap();
+            assert!(got_report
+                .unwrap()
+                .eq_report(&vdaf, leader_task.current_hpke_key(), &report));
+            assert_eq!(
+                got_counter,
+                Some(TaskUploadCounter::new_with_values(0, 0, 0, 0, 0, 1, 0, 0))
+            );
+        }
+    }
+
     #[tokio::test]
     async fn upload_batch() {
         install_test_trace_subscriber();
@@ -3412,7 +4710,11 @@ mod tests {
             let aggregator = Arc::clone(&aggregator);
             let enc = r.get_encoded().unwrap();
             let task_id = task.id();
-            async move { aggregator.handle_upload(task_id, &enc).await }
+            async move {
+                aggregator
+                    .handle_upload(task_id, &enc, None, &Headers::new())
+                    .await
+            }
         }))
         .await
         .unwrap();
@@ -3444,6 +4746,211 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn upload_queue() {
+        install_test_trace_subscriber();
+
+        let (vdaf, aggregator, clock, task, datastore, _ephemeral_datastore) =
+            setup_upload_test(default_aggregator_config()).await;
+        let spool_dir = tempfile::tempdir().unwrap();
+        let upload_queue: Arc<dyn UploadQueue> =
+            Arc::new(DirectoryUploadQueue::new(spool_dir.path().to_path_buf()));
+        let aggregator = aggregator.with_upload_queue(Arc::clone(&upload_queue));
+
+        let leader_task = task.leader_view().unwrap();
+        let report = create_report(&leader_task, clock.now());
+        let future_report = create_report(
+            &leader_task,
+            clock
+                .now()
+                .add(task.tolerable_clock_skew())
+                .unwrap()
+                .add(&Duration::from_seconds(1))
+                .unwrap(),
+        );
+        // Reports which are rejected at ingestion time are still accepted by the upload endpoint.
+        for report in [&report, &future_report] {
+            aggregator
+                .handle_upload(
+                    task.id(),
+                    &report.get_encoded().unwrap(),
+                    None,
+                    &Headers::new(),
+                )
+                .await
+                .unwrap();
+        }
+
+        // Nothing has been written to the datastore yet.
+        let got_reports = datastore
+            .run_unnamed_tx(|tx| {
+                let vdaf = vdaf.clone();
+                let task_id = *task.id();
+                Box::pin(async move { tx.get_client_reports_for_task(&vdaf, &task_id).await })
+            })
+            .await
+            .unwrap();
+        assert!(got_reports.is_empty());
+
+        let ingester = UploadIngester::new(
+            Arc::clone(&datastore),
+            None,
+            clock.clone(),
+            TestRuntime::default(),
+            &noop_meter(),
+            // Both reports will be handled in a single batch.
+            Config {
+                max_upload_batch_size: 2,
+                max_upload_batch_write_delay: StdDuration::from_secs(86400),
+                ..Default::default()
+            },
+            Arc::clone(&upload_queue),
+            10,
+            StdDuration::from_secs(1),
+        )
+        .await
+        .unwrap();
+        assert_eq!(ingester.ingest().await.unwrap(), 2);
+
+        // The valid report was written, the future report was rejected, and both were removed
+        // from the queue.
+        let (got_report_ids, got_counter) = datastore
+            .run_unnamed_tx(|tx| {
+                let vdaf = vdaf.clone();
+                let task_id = *task.id();
+                Box::pin(async move {
+                    Ok((
+                        tx.get_client_reports_for_task(&vdaf, &task_id)
+                            .await
+                            .unwrap()
+                            .iter()
+                            .map(|report| *report.metadata().id())
+                            .collect::<Vec<_>>(),
+                        tx.get_task_upload_counter(&task_id).await.unwrap(),
+                    ))
+                })
+            })
+            .await
+            .unwrap();
+        assert_eq!(got_report_ids, Vec::from([*report.metadata().id()]));
+        assert_eq!(
+            got_counter,
+            Some(TaskUploadCounter::new_with_values(0, 0, 0, 0, 0, 1, 1, 0))
+        );
+        assert!(upload_queue.receive(10).await.unwrap().is_empty());
+    }
+
+    /// Rejects uploads arriving through the "blocked" gateway, and flags those arriving through the
+    /// "suspect" gateway.
+    #[derive(Debug)]
+    struct GatewayReportPolicy;
+
+    #[async_trait]
+    impl ReportPolicy for GatewayReportPolicy {
+        async fn evaluate(&self, upload: &PolicyUpload<'_>) -> PolicyDecision {
+            match upload.request_headers().get_str("x-gateway") {
+                Some("blocked") => PolicyDecision::Reject("blocked_gateway"),
+                Some("suspect") => PolicyDecision::Flag("suspect_gateway"),
+                _ => PolicyDecision::Accept,
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn upload_report_policy() {
+        install_test_trace_subscriber();
+
+        let (vdaf, aggregator, clock, task, datastore, _ephemeral_datastore) =
+            setup_upload_test(default_aggregator_config()).await;
+        let aggregator = aggregator.with_report_policy(Arc::new(GatewayReportPolicy));
+        let leader_task = task.leader_view().unwrap();
+
+        let mut report_ids = Vec::new();
+        for gateway in [None, Some("suspect"), Some("blocked")] {
+            let report = create_report(&leader_task, clock.now());
+            let mut headers = Headers::new();
+            if let Some(gateway) = gateway {
+                headers.insert("x-gateway", gateway);
+            }
+            let result = aggregator
+                .handle_upload(task.id(), &report.get_encoded().unwrap(), None, &headers)
+                .await;
+            if gateway == Some("blocked") {
+                assert_matches!(result.unwrap_err().as_ref(), Error::ReportRejected(rejection) => {
+                    assert_eq!(rejection.task_id(), task.id());
+                    assert_eq!(rejection.report_id(), report.metadata().id());
+                    assert_eq!(rejection.reason(), &ReportRejectionReason::PolicyRejected);
+                });
+            } else {
+                result.unwrap();
+                report_ids.push(*report.metadata().id());
+            }
+        }
+
+        // Accepted and flagged reports are written, but rejected reports are not.
+        let mut got_report_ids = datastore
+            .run_unnamed_tx(|tx| {
+                let vdaf = vdaf.clone();
+                let task_id = *task.id();
+                Box::pin(async move { tx.get_client_reports_for_task(&vdaf, &task_id).await })
+            })
+            .await
+            .unwrap()
+            .iter()
+            .map(|report| *report.metadata().id())
+            .collect::<Vec<_>>();
+        got_report_ids.sort();
+        report_ids.sort();
+        assert_eq!(got_report_ids, report_ids);
+    }
+
+    #[tokio::test]
+    async fn upload_sampling() {
+        install_test_trace_subscriber();
+
+        let (_, aggregator, clock, task, datastore, _ephemeral_datastore) =
+            setup_upload_test(Config {
+                upload_sampling: UploadSamplingConfig {
+                    samples_per_million: 1_000_000,
+                    ..Default::default()
+                },
+                ..default_aggregator_config()
+            })
+            .await;
+        let report = create_report(&task.leader_view().unwrap(), clock.now());
+
+        aggregator
+            .handle_upload(
+                task.id(),
+                &report.get_encoded().unwrap(),
+                None,
+                &Headers::new(),
+            )
+            .await
+            .unwrap();
+
+        let got_samples = datastore
+            .run_unnamed_tx(|tx| {
+                let task_id = *task.id();
+                Box::pin(async move { tx.get_upload_samples_for_task(&task_id).await })
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            got_samples,
+            Vec::from([UploadSample::new(
+                *task.id(),
+                *report.metadata().id(),
+                *report.metadata().time(),
+                clock.now(),
+                Vec::new(),
+                report.public_share().len() as u64,
+                report.leader_encrypted_input_share().payload().len() as u64,
+                report.helper_encrypted_input_share().payload().len() as u64,
+            )])
+        );
+    }
+
     #[tokio::test]
     async fn upload_wrong_hpke_config_id() {
         install_test_trace_subscriber();
@@ -3478,7 +4985,12 @@ mod tests {
         );
 
         let result = aggregator
-            .handle_upload(task.id(), &report.get_encoded().unwrap())
+            .handle_upload(
+                task.id(),
+                &report.get_encoded().unwrap(),
+                None,
+                &Headers::new(),
+            )
             .await
             .unwrap_err();
         assert_matches!(result.as_ref(), Error::ReportRejected(rejection) => {
@@ -3520,7 +5032,12 @@ mod tests {
         );
 
         aggregator
-            .handle_upload(task.id(), &report.get_encoded().unwrap())
+            .handle_upload(
+                task.id(),
+                &report.get_encoded().unwrap(),
+                None,
+                &Headers::new(),
+            )
             .await
             .unwrap();
 
@@ -3570,7 +5087,12 @@ mod tests {
         );
 
         let upload_error = aggregator
-            .handle_upload(task.id(), &report.get_encoded().unwrap())
+            .handle_upload(
+                task.id(),
+                &report.get_encoded().unwrap(),
+                None,
+                &Headers::new(),
+            )
             .await
             .unwrap_err();
         assert_matches!(upload_error.as_ref(), Error::ReportRejected(rejection) => {
@@ -3578,6 +5100,16 @@ mod tests {
             assert_eq!(report.metadata().id(), rejection.report_id());
             assert_eq!(report.metadata().time(), rejection.time());
             assert_matches!(rejection.reason(), ReportRejectionReason::TooEarly);
+            assert_eq!(
+                rejection.details(),
+                &ReportRejectionDetails {
+                    tolerable_clock_skew: Some(*task.tolerable_clock_skew()),
+                    latest_acceptable_time: Some(
+                        clock.now().add(task.tolerable_clock_skew()).unwrap()
+                    ),
+                    ..Default::default()
+                }
+            );
         });
 
         // Wait for the report writer to have completed one write task.
@@ -3645,7 +5177,12 @@ mod tests {
 
         // Try to upload the report, verify that we get the expected error.
         let error = aggregator
-            .handle_upload(task.id(), &report.get_encoded().unwrap())
+            .handle_upload(
+                task.id(),
+                &report.get_encoded().unwrap(),
+                None,
+                &Headers::new(),
+            )
             .await
             .unwrap_err();
         assert_matches!(
@@ -3735,7 +5272,12 @@ mod tests {
             ),
         ] {
             aggregator
-                .handle_upload(task.id(), &report.get_encoded().unwrap())
+                .handle_upload(
+                    task.id(),
+                    &report.get_encoded().unwrap(),
+                    None,
+                    &Headers::new(),
+                )
                 .await
                 .unwrap();
 
@@ -3777,7 +5319,12 @@ mod tests {
 
         // Try to upload the report, verify that we get the expected error.
         let error = aggregator
-            .handle_upload(task.id(), &report.get_encoded().unwrap())
+            .handle_upload(
+                task.id(),
+                &report.get_encoded().unwrap(),
+                None,
+                &Headers::new(),
+            )
             .await
             .unwrap_err();
         assert_matches!(
@@ -3833,7 +5380,12 @@ mod tests {
 
         // Try to upload the report, verify that we get the expected error.
         let error = aggregator
-            .handle_upload(task.id(), &report.get_encoded().unwrap())
+            .handle_upload(
+                task.id(),
+                &report.get_encoded().unwrap(),
+                None,
+                &Headers::new(),
+            )
             .await
             .unwrap_err();
         assert_matches!(
@@ -3889,7 +5441,12 @@ mod tests {
 
         // Try to upload the report, verify that we get the expected error.
         let error = aggregator
-            .handle_upload(task.id(), &report.get_encoded().unwrap())
+            .handle_upload(
+                task.id(),
+                &report.get_encoded().unwrap(),
+                None,
+                &Headers::new(),
+            )
             .await
             .unwrap_err();
         assert_matches!(
@@ -3944,7 +5501,12 @@ mod tests {
 
         // Try to upload the report, verify that we get the expected error.
         let error = aggregator
-            .handle_upload(task.id(), &report.get_encoded().unwrap())
+            .handle_upload(
+                task.id(),
+                &report.get_encoded().unwrap(),
+                None,
+                &Headers::new(),
+            )
             .await
             .unwrap_err();
         assert_matches!(
@@ -4013,7 +5575,12 @@ mod tests {
 
         // Try to upload the report, verify that we get the expected error.
         let error = aggregator
-            .handle_upload(task.id(), &report.get_encoded().unwrap())
+            .handle_upload(
+                task.id(),
+                &report.get_encoded().unwrap(),
+                None,
+                &Headers::new(),
+            )
             .await
             .unwrap_err();
         assert_matches!(
@@ -4044,6 +5611,139 @@ mod tests {
         )
     }
 
+    #[tokio::test]
+    async fn upload_report_unknown_extension() {
+        install_test_trace_subscriber();
+        let (vdaf, aggregator, clock, task, datastore, _ephemeral_datastore) =
+            setup_upload_test(default_aggregator_config()).await;
+
+        let lenient_task = TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Prio3Count)
+            .build()
+            .leader_view()
+            .unwrap()
+            .with_unknown_extension_policy(UnknownExtensionPolicy::AcceptWithMetric)
+            .unwrap();
+        datastore.put_aggregator_task(&lenient_task).await.unwrap();
+
+        let report_with_unknown_extension = |task: &AggregatorTask| {
+            let report_metadata = ReportMetadata::new(random(), clock.now());
+            let (public_share, measurements) =
+                vdaf.shard(&true, report_metadata.id().as_ref()).unwrap();
+            let associated_data = InputShareAad::new(
+                *task.id(),
+                report_metadata.clone(),
+                public_share.get_encoded().unwrap(),
+            );
+            let report = create_report(task, clock.now());
+            Report::new(
+                report_metadata,
+                public_share.get_encoded().unwrap(),
+                hpke::seal(
+                    task.current_hpke_key().config(),
+                    &HpkeApplicationInfo::new(&Label::InputShare, &Role::Client, &Role::Leader),
+                    &PlaintextInputShare::new(
+                        Vec::from([Extension::new(
+                            ExtensionType::Unknown(0x1234),
+                            Vec::from("unknown"),
+                        )]),
+                        measurements[0].get_encoded().unwrap(),
+                    )
+                    .get_encoded()
+                    .unwrap(),
+                    &associated_data.get_encoded().unwrap(),
+                )
+                .unwrap(),
+                // The helper's share is not examined by the leader.
+                report.helper_encrypted_input_share().clone(),
+            )
+        };
+
+        // The default policy rejects reports carrying unknown extensions.
+        let task = task.leader_view().unwrap();
+        let report = report_with_unknown_extension(&task);
+        let error = aggregator
+            .handle_upload(
+                task.id(),
+                &report.get_encoded().unwrap(),
+                None,
+                &Headers::new(),
+            )
+            .await
+            .unwrap_err();
+        assert_matches!(
+            error.as_ref(),
+            Error::ReportRejected(rejection) => {
+                assert_eq!(report.metadata().id(), rejection.report_id());
+                assert_matches!(rejection.reason(), ReportRejectionReason::DecodeFailure);
+            }
+        );
+
+        // A lenient task accepts them.
+        let report = report_with_unknown_extension(&lenient_task);
+        aggregator
+            .handle_upload(
+                lenient_task.id(),
+                &report.get_encoded().unwrap(),
+                None,
+                &Headers::new(),
+            )
+            .await
+            .unwrap();
+        let got_report = datastore
+            .run_unnamed_tx(|tx| {
+                let (vdaf, task_id, report_id) =
+                    (vdaf.clone(), *lenient_task.id(), *report.metadata().id());
+                Box::pin(async move { tx.get_client_report(&vdaf, &task_id, &report_id).await })
+            })
+            .await
+            .unwrap();
+        assert!(got_report.is_some());
+    }
+
+    #[tokio::test]
+    async fn upload_report_authentication() {
+        install_test_trace_subscriber();
+        let (_, aggregator, clock, _, datastore, _ephemeral_datastore) =
+            setup_upload_test(default_aggregator_config()).await;
+
+        let upload_auth_token: AuthenticationToken = random();
+        let task = TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Prio3Count)
+            .build()
+            .leader_view()
+            .unwrap()
+            .with_upload_auth_token_hash(Some(AuthenticationTokenHash::from(&upload_auth_token)))
+            .unwrap();
+        datastore.put_aggregator_task(&task).await.unwrap();
+
+        // Uploads without the task's token are rejected.
+        for auth_token in [None, Some(random::<AuthenticationToken>())] {
+            let report = create_report(&task, clock.now());
+            let error = aggregator
+                .handle_upload(
+                    task.id(),
+                    &report.get_encoded().unwrap(),
+                    auth_token.as_ref(),
+                    &Headers::new(),
+                )
+                .await
+                .unwrap_err();
+            assert_matches!(error.as_ref(), Error::UnauthorizedRequest(task_id) => {
+                assert_eq!(task_id, task.id());
+            });
+        }
+
+        let report = create_report(&task, clock.now());
+        aggregator
+            .handle_upload(
+                task.id(),
+                &report.get_encoded().unwrap(),
+                Some(&upload_auth_token),
+                &Headers::new(),
+            )
+            .await
+            .unwrap();
+    }
+
     pub(crate) fn generate_helper_report_share<V: vdaf::Client<16>>(
         task_id: TaskId,
         report_metadata: ReportMetadata,
@@ -4,7 +4,7 @@ use crate::aggregator::{
         test_util::{decode_response_body, take_problem_details},
     },
     tests::generate_helper_report_share,
-    Config,
+    Config, Error,
 };
 use assert_matches::assert_matches;
 use http::StatusCode;
@@ -676,3 +676,95 @@ async fn aggregation_job_init_wrong_query() {
         }),
     );
 }
+
+#[tokio::test]
+async fn aggregation_job_init_exceeds_memory_budget() {
+    let test_case = setup_aggregate_init_test_without_sending_request(
+        dummy::Vdaf::new(1),
+        VdafInstance::Fake,
+        dummy::AggregationParam(0),
+        0,
+        random(),
+    )
+    .await;
+
+    // Leave room for the request body and the decoded request, but not for any decrypted input
+    // shares.
+    let request_len = test_case
+        .aggregation_job_init_req
+        .get_encoded()
+        .unwrap()
+        .len();
+    let memory_budget = u64::try_from(request_len * 2).unwrap();
+    let handler = aggregator_handler(
+        Arc::clone(&test_case.datastore),
+        test_case.clock.clone(),
+        TestRuntime::default(),
+        &noop_meter(),
+        Config {
+            max_aggregate_request_memory_bytes: Some(memory_budget),
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+
+    let mut response = put_aggregation_job(
+        &test_case.task,
+        &test_case.aggregation_job_id,
+        &test_case.aggregation_job_init_req,
+        &handler,
+    )
+    .await;
+    assert_eq!(
+        take_problem_details(&mut response).await,
+        json!({
+            "status": StatusCode::PAYLOAD_TOO_LARGE.as_u16(),
+            "type": "https://docs.divviup.org/references/janus-errors#aggregate-request-too-large",
+            "title": "The aggregate request is too large to be processed.",
+            "taskid": format!("{}", test_case.task.id()),
+            "detail": Error::AggregateRequestTooLarge(*test_case.task.id(), memory_budget)
+                .to_string(),
+        }),
+    );
+
+    // Nothing was written for the abandoned aggregation job.
+    let (task_id, aggregation_job_id) = (*test_case.task.id(), test_case.aggregation_job_id);
+    let aggregation_job = test_case
+        .datastore
+        .run_unnamed_tx(|tx| {
+            Box::pin(async move {
+                tx.get_aggregation_job::<0, TimeInterval, dummy::Vdaf>(
+                    &task_id,
+                    &aggregation_job_id,
+                )
+                .await
+            })
+        })
+        .await
+        .unwrap();
+    assert!(aggregation_job.is_none());
+
+    // The same request is accepted by a helper with a larger budget.
+    let handler = aggregator_handler(
+        Arc::clone(&test_case.datastore),
+        test_case.clock.clone(),
+        TestRuntime::default(),
+        &noop_meter(),
+        Config {
+            max_aggregate_request_memory_bytes: Some(memory_budget * 4),
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+
+    let response = put_aggregation_job(
+        &test_case.task,
+        &test_case.aggregation_job_id,
+        &test_case.aggregation_job_init_req,
+        &handler,
+    )
+    .await;
+    assert_eq!(response.status(), Some(Status::Ok));
+}
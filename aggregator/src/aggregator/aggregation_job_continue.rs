@@ -553,8 +553,8 @@ mod tests {
             &request,
             &handler,
             Status::BadRequest,
-            "urn:ietf:params:ppm:dap:error:unrecognizedTask",
-            "An endpoint received a message with an unknown task ID.",
+            "urn:ietf:params:ppm:dap:error:unauthorizedRequest",
+            "The request's authorization is not valid.",
             None,
         )
         .await;
@@ -586,8 +586,8 @@ mod tests {
             take_problem_details(&mut test_conn).await,
             json!({
                 "status": Status::BadRequest as u16,
-                "type": "urn:ietf:params:ppm:dap:error:unrecognizedTask",
-                "title": "An endpoint received a message with an unknown task ID.",
+                "type": "urn:ietf:params:ppm:dap:error:unauthorizedRequest",
+                "title": "The request's authorization is not valid.",
                 "taskid": format!("{}", task.id()),
             })
         );
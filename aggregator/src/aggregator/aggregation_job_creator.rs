@@ -1,11 +1,8 @@
 use crate::aggregator::{
     aggregation_job_writer::{AggregationJobWriter, InitialWrite},
     batch_creator::BatchCreator,
-};
-#[cfg(feature = "fpvec_bounded_l2")]
-use fixed::{
-    types::extra::{U15, U31},
-    FixedI16, FixedI32,
+    deployment::DeploymentFence,
+    leader_election::{LeaderElection, LeaderElectionStates},
 };
 use futures::future::try_join_all;
 use itertools::Itertools as _;
@@ -13,40 +10,29 @@ use janus_aggregator_core::{
     datastore::{
         self,
         models::{
-            AggregationJob, AggregationJobState, ReportAggregationMetadata,
-            ReportAggregationMetadataState,
+            AggregationJob, AggregationJobCreatorTaskState, AggregationJobState,
+            ReportAggregationMetadata, ReportAggregationMetadataState,
         },
         Datastore,
     },
     task::{self, AggregatorTask},
 };
-#[cfg(feature = "fpvec_bounded_l2")]
-use janus_core::vdaf::Prio3FixedPointBoundedL2VecSumBitSize;
 use janus_core::{
+    ids::generate_aggregation_job_id,
     time::{Clock, DurationExt as _, TimeExt as _},
-    vdaf::{
-        new_prio3_sum_vec_field64_multiproof_hmacsha256_aes128,
-        Prio3SumVecField64MultiproofHmacSha256Aes128, VdafInstance, VERIFY_KEY_LENGTH,
-        VERIFY_KEY_LENGTH_HMACSHA256_AES128,
-    },
+    vdaf::VdafDispatchGroup,
+    vdaf_dispatch, vdaf_dispatch_no_aggregation_parameter,
 };
 use janus_messages::{
     query_type::TimeInterval, AggregationJobStep, Duration as DurationMsg, Interval, Role, TaskId,
+    Time,
 };
 use opentelemetry::{
     metrics::{Histogram, Meter, Unit},
     KeyValue,
 };
-#[cfg(feature = "fpvec_bounded_l2")]
-use prio::vdaf::prio3::Prio3FixedPointBoundedL2VecSumMultithreaded;
-use prio::{
-    codec::Encode,
-    vdaf::{
-        self,
-        prio3::{Prio3, Prio3Count, Prio3Histogram, Prio3Sum, Prio3SumVecMultithreaded},
-    },
-};
-use rand::{random, thread_rng, Rng};
+use prio::{codec::Encode, vdaf};
+use rand::{thread_rng, Rng};
 use std::{
     cmp::min,
     collections::{HashMap, HashSet},
@@ -57,19 +43,37 @@ use tokio::{
     time::{self, sleep_until, Instant, MissedTickBehavior},
     try_join,
 };
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 use trillium_tokio::{CloneCounterObserver, Stopper};
 
+/// The name of the leader lease held by the replica creating aggregation jobs.
+pub const LEADER_LEASE_NAME: &str = "aggregation_job_creator";
+
+/// Why the aggregation job creator is updating its view of tasks.
+enum TasksUpdateTrigger {
+    /// The task update frequency elapsed.
+    Scheduled,
+    /// The task discovery interval elapsed. Tasks are only updated if the set of tasks changed.
+    Discovery,
+}
+
 pub struct AggregationJobCreator<C: Clock> {
     // Dependencies.
-    datastore: Datastore<C>,
+    datastore: Arc<Datastore<C>>,
     meter: Meter,
+    /// If set, only the replica holding the leader lease creates aggregation jobs.
+    leader_election: Option<LeaderElection<C>>,
+    /// Only the active deployment creates aggregation jobs.
+    deployment_fence: DeploymentFence,
 
     // Configuration values.
     /// The number of batch aggregation shards to use per batch.
     batch_aggregation_shard_count: u64,
     /// How frequently we look for new tasks to start creating aggregation jobs for.
     tasks_update_frequency: Duration,
+    /// If set, how frequently we check for newly provisioned or deleted tasks, between full task
+    /// updates.
+    task_discovery_interval: Option<Duration>,
     /// How frequently we attempt to create new aggregation jobs for each task.
     aggregation_job_creation_interval: Duration,
     /// The minimum number of client reports to include in an aggregation job. For time-interval
@@ -82,6 +86,9 @@ pub struct AggregationJobCreator<C: Clock> {
     max_aggregation_job_size: usize,
     /// Maximum number of reports to load at a time when creating aggregation jobs.
     aggregation_job_creation_report_window: usize,
+    /// If set, the maximum number of client reports to include in an aggregation job while
+    /// working through a backlog of reports.
+    max_adaptive_aggregation_job_size: Option<usize>,
 }
 
 impl<C: Clock + 'static> AggregationJobCreator<C> {
@@ -100,14 +107,79 @@ impl<C: Clock + 'static> AggregationJobCreator<C> {
             "invalid configuration: max_aggregation_job_size cannot be zero"
         );
         AggregationJobCreator {
-            datastore,
+            datastore: Arc::new(datastore),
             meter,
+            leader_election: None,
+            deployment_fence: DeploymentFence::default(),
             batch_aggregation_shard_count,
             tasks_update_frequency,
+            task_discovery_interval: None,
             aggregation_job_creation_interval,
             min_aggregation_job_size,
             max_aggregation_job_size,
             aggregation_job_creation_report_window,
+            max_adaptive_aggregation_job_size: None,
+        }
+    }
+
+    /// Create larger aggregation jobs, of up to `max_adaptive_aggregation_job_size` reports, when
+    /// a task has more unaggregated reports than fit in one report window. This reduces the number
+    /// of requests to the helper while upload volume is high, while keeping jobs small, and thus
+    /// quick to complete, otherwise.
+    pub fn with_max_adaptive_aggregation_job_size(
+        self,
+        max_adaptive_aggregation_job_size: usize,
+    ) -> Self {
+        Self {
+            max_adaptive_aggregation_job_size: Some(max_adaptive_aggregation_job_size),
+            ..self
+        }
+    }
+
+    /// Check which tasks exist every `task_discovery_interval`, in addition to loading all tasks
+    /// every task update, so that job creation starts promptly for newly provisioned tasks and
+    /// stops promptly for deleted ones. Only task IDs are read on each check; tasks are loaded only
+    /// once the set of task IDs has changed.
+    pub fn with_task_discovery_interval(self, task_discovery_interval: Duration) -> Self {
+        Self {
+            task_discovery_interval: Some(task_discovery_interval),
+            ..self
+        }
+    }
+
+    /// Only create aggregation jobs while holding the aggregation job creator's leader lease, so that
+    /// multiple replicas don't duplicate work. The lease is renewed each time tasks are updated, so
+    /// `lease_duration` should be several times the task update frequency.
+    pub fn with_leader_election(
+        self,
+        lease_duration: Duration,
+        leader_elections: &LeaderElectionStates,
+    ) -> Self {
+        let leader_election = LeaderElection::new(
+            Arc::clone(&self.datastore),
+            &self.meter,
+            LEADER_LEASE_NAME,
+            lease_duration,
+        )
+        .with_states(leader_elections)
+        .with_deployment_fence(self.deployment_fence.clone());
+        Self {
+            leader_election: Some(leader_election),
+            ..self
+        }
+    }
+
+    /// Only create aggregation jobs while this binary's deployment is active. This is checked in
+    /// the same transaction that creates the jobs, so a deployment that has been replaced by its
+    /// standby can't create any more jobs.
+    pub fn with_deployment_fence(self, deployment_fence: DeploymentFence) -> Self {
+        let leader_election = self
+            .leader_election
+            .map(|leader_election| leader_election.with_deployment_fence(deployment_fence.clone()));
+        Self {
+            leader_election,
+            deployment_fence,
+            ..self
         }
     }
 
@@ -133,6 +205,14 @@ impl<C: Clock + 'static> AggregationJobCreator<C> {
         // the loop.)
         let mut tasks_update_ticker = time::interval(self.tasks_update_frequency);
         tasks_update_ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut task_discovery_ticker = self.task_discovery_interval.map(|interval| {
+            let mut ticker = time::interval(interval);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            ticker
+        });
+        // Whether we held the leader lease as of the last task update. Task discovery only starts
+        // job creation workers while we do.
+        let mut holds_leader_lease = false;
 
         // This tracks the stoppers used to shut down the per-task worker by task ID.
         let mut job_creation_task_shutdown_handles: HashMap<TaskId, Stopper> = HashMap::new();
@@ -140,12 +220,57 @@ impl<C: Clock + 'static> AggregationJobCreator<C> {
         let observer = CloneCounterObserver::new();
 
         loop {
-            if stopper
-                .stop_future(tasks_update_ticker.tick())
-                .await
-                .is_none()
-            {
-                break;
+            let tick = async {
+                match &mut task_discovery_ticker {
+                    Some(task_discovery_ticker) => tokio::select! {
+                        biased;
+                        _ = tasks_update_ticker.tick() => TasksUpdateTrigger::Scheduled,
+                        _ = task_discovery_ticker.tick() => TasksUpdateTrigger::Discovery,
+                    },
+                    None => {
+                        tasks_update_ticker.tick().await;
+                        TasksUpdateTrigger::Scheduled
+                    }
+                }
+            };
+            let trigger = match stopper.stop_future(tick).await {
+                Some(trigger) => trigger,
+                None => break,
+            };
+
+            match trigger {
+                TasksUpdateTrigger::Scheduled => {
+                    if let Some(leader_election) = &self.leader_election {
+                        holds_leader_lease = leader_election.try_acquire().await;
+                        if !holds_leader_lease {
+                            // Another replica is creating aggregation jobs, so stop any of ours.
+                            for (task_id, task_stopper) in
+                                job_creation_task_shutdown_handles.drain()
+                            {
+                                info!(%task_id, "Stopping job creation worker");
+                                task_stopper.stop();
+                            }
+                            continue;
+                        }
+                    }
+                }
+
+                TasksUpdateTrigger::Discovery => {
+                    if self.leader_election.is_some() && !holds_leader_lease {
+                        continue;
+                    }
+                    match self
+                        .tasks_changed(&job_creation_task_shutdown_handles)
+                        .await
+                    {
+                        Ok(true) => debug!("Discovered task changes"),
+                        Ok(false) => continue,
+                        Err(error) => {
+                            error!(?error, "Couldn't check for task changes");
+                            continue;
+                        }
+                    }
+                }
             }
             let start = Instant::now();
 
@@ -175,6 +300,28 @@ impl<C: Clock + 'static> AggregationJobCreator<C> {
             task_stopper.stop();
         }
         observer.await;
+
+        if let Some(leader_election) = &self.leader_election {
+            leader_election.release().await;
+        }
+    }
+
+    /// Returns true if the set of leader tasks in the datastore differs from the set of tasks we
+    /// are running job creation workers for.
+    async fn tasks_changed(
+        &self,
+        job_creation_task_shutdown_handles: &HashMap<TaskId, Stopper>,
+    ) -> Result<bool, datastore::Error> {
+        let task_ids = self
+            .datastore
+            .run_tx("aggregation_job_creator_get_task_ids", |tx| {
+                Box::pin(async move { tx.get_task_ids_by_role(&Role::Leader).await })
+            })
+            .await?;
+        Ok(task_ids.len() != job_creation_task_shutdown_handles.len()
+            || task_ids
+                .iter()
+                .any(|task_id| !job_creation_task_shutdown_handles.contains_key(task_id)))
     }
 
     #[tracing::instrument(name = "AggregationJobCreator::update_tasks", skip_all, err)]
@@ -233,6 +380,53 @@ impl<C: Clock + 'static> AggregationJobCreator<C> {
         Ok(())
     }
 
+    /// Returns how frequently to attempt to create aggregation jobs for `task`.
+    fn aggregation_job_creation_interval(&self, task: &AggregatorTask) -> Duration {
+        task.aggregation_job_sizing()
+            .aggregation_job_creation_interval()
+            .map(|interval| Duration::from_secs(interval.as_seconds()))
+            .unwrap_or(self.aggregation_job_creation_interval)
+    }
+
+    /// Returns the minimum and maximum number of reports to include in each of `task`'s
+    /// aggregation jobs, after `report_count` unaggregated reports were loaded. The task's own
+    /// sizing takes precedence over the configured sizes. If a full report window was loaded, more
+    /// reports are likely waiting, so jobs may grow to the adaptive maximum size, if one is set.
+    fn aggregation_job_size_bounds(
+        &self,
+        task: &AggregatorTask,
+        report_count: usize,
+    ) -> Result<(usize, usize), datastore::Error> {
+        let sizing = task.aggregation_job_sizing();
+        let mut max_aggregation_job_size = sizing
+            .max_aggregation_job_size()
+            .map(usize::try_from)
+            .transpose()?
+            .unwrap_or(self.max_aggregation_job_size);
+        let min_aggregation_job_size = sizing
+            .min_aggregation_job_size()
+            .map(usize::try_from)
+            .transpose()?
+            .unwrap_or(self.min_aggregation_job_size)
+            .min(max_aggregation_job_size);
+
+        if let Some(max_adaptive_aggregation_job_size) = self.max_adaptive_aggregation_job_size {
+            if report_count >= self.aggregation_job_creation_report_window
+                && max_adaptive_aggregation_job_size > max_aggregation_job_size
+            {
+                debug!(
+                    task_id = %task.id(),
+                    report_count,
+                    max_adaptive_aggregation_job_size,
+                    "Backlog of reports, creating larger aggregation jobs"
+                );
+                max_aggregation_job_size = max_adaptive_aggregation_job_size;
+            }
+        }
+
+        Ok((min_aggregation_job_size, max_aggregation_job_size))
+    }
+
     #[tracing::instrument(
         name = "AggregationJobCreator::run_for_task",
         skip(self, stopper, job_creation_time_histogram)
@@ -244,11 +438,16 @@ impl<C: Clock + 'static> AggregationJobCreator<C> {
         task: Arc<AggregatorTask>,
     ) {
         debug!(task_id = %task.id(), "Job creation worker started");
-        let mut next_run_instant = Instant::now();
-        if !self.aggregation_job_creation_interval.is_zero() {
-            next_run_instant +=
-                thread_rng().gen_range(Duration::ZERO..self.aggregation_job_creation_interval);
+        let aggregation_job_creation_interval = self.aggregation_job_creation_interval(&task);
+        let discovered_at = self.datastore.clock().now();
+        let mut next_run_delay = Duration::ZERO;
+        if !aggregation_job_creation_interval.is_zero() {
+            next_run_delay =
+                thread_rng().gen_range(Duration::ZERO..aggregation_job_creation_interval);
         }
+        let mut next_run_instant = Instant::now() + next_run_delay;
+        self.put_task_state(task.id(), discovered_at, None, next_run_delay, None)
+            .await;
 
         loop {
             if stopper
@@ -262,26 +461,78 @@ impl<C: Clock + 'static> AggregationJobCreator<C> {
 
             debug!(task_id = %task.id(), "Creating aggregation jobs for task");
             let (start, mut status) = (Instant::now(), "success");
+            let run_at = self.datastore.clock().now();
+            let mut last_error = None;
             match Arc::clone(&self)
                 .create_aggregation_jobs_for_task(Arc::clone(&task))
                 .await
             {
-                Ok(true) => next_run_instant = Instant::now(),
+                Ok(true) => next_run_delay = Duration::ZERO,
 
-                Ok(false) => {
-                    next_run_instant = Instant::now() + self.aggregation_job_creation_interval
-                }
+                Ok(false) => next_run_delay = aggregation_job_creation_interval,
 
                 Err(err) => {
                     error!(task_id = %task.id(), %err, "Couldn't create aggregation jobs for task");
                     status = "error";
-                    next_run_instant = Instant::now() + self.aggregation_job_creation_interval;
+                    last_error = Some(err.to_string());
+                    next_run_delay = aggregation_job_creation_interval;
                 }
             }
+            next_run_instant = Instant::now() + next_run_delay;
             job_creation_time_histogram.record(
                 start.elapsed().as_secs_f64(),
                 &[KeyValue::new("status", status)],
             );
+            self.put_task_state(
+                task.id(),
+                discovered_at,
+                Some(run_at),
+                next_run_delay,
+                last_error,
+            )
+            .await;
+        }
+    }
+
+    /// Records the scheduling state of a task's job creation worker, for the admin API. The next
+    /// run is scheduled `next_run_delay` from now. Failures are logged rather than returned, since
+    /// they don't affect job creation.
+    async fn put_task_state(
+        &self,
+        task_id: &TaskId,
+        discovered_at: Time,
+        last_run_at: Option<Time>,
+        next_run_delay: Duration,
+        last_error: Option<String>,
+    ) {
+        let next_run_at = match self
+            .datastore
+            .clock()
+            .now()
+            .add(&DurationMsg::from_seconds(next_run_delay.as_secs()))
+        {
+            Ok(next_run_at) => next_run_at,
+            Err(error) => {
+                warn!(%task_id, ?error, "Couldn't compute next job creation time for task");
+                return;
+            }
+        };
+        let state = Arc::new(AggregationJobCreatorTaskState::new(
+            *task_id,
+            discovered_at,
+            last_run_at,
+            next_run_at,
+            last_error,
+        ));
+        if let Err(error) = self
+            .datastore
+            .run_tx("aggregation_job_creator_put_task_state", |tx| {
+                let state = Arc::clone(&state);
+                Box::pin(async move { tx.put_aggregation_job_creator_task_state(&state).await })
+            })
+            .await
+        {
+            warn!(%task_id, ?error, "Couldn't record job creation state for task");
         }
     }
 
@@ -292,245 +543,53 @@ impl<C: Clock + 'static> AggregationJobCreator<C> {
         fields(task_id = ?task.id()),
         err
     )]
-    async fn create_aggregation_jobs_for_task(
+    pub(super) async fn create_aggregation_jobs_for_task(
         self: Arc<Self>,
         task: Arc<AggregatorTask>,
     ) -> anyhow::Result<bool> {
-        match (task.query_type(), task.vdaf()) {
-            (task::QueryType::TimeInterval, VdafInstance::Prio3Count) => {
-                let vdaf = Arc::new(Prio3::new_count(2)?);
-                self.create_aggregation_jobs_for_time_interval_task_no_param::<VERIFY_KEY_LENGTH, Prio3Count>(task, vdaf)
-                    .await
-            }
-
-            (task::QueryType::TimeInterval, VdafInstance::Prio3Sum { bits }) => {
-                let vdaf = Arc::new(Prio3::new_sum(2, *bits)?);
-                self.create_aggregation_jobs_for_time_interval_task_no_param::<VERIFY_KEY_LENGTH, Prio3Sum>(task, vdaf)
-                    .await
-            }
-
-            (
-                task::QueryType::TimeInterval,
-                VdafInstance::Prio3SumVec {
-                    bits,
-                    length,
-                    chunk_length,
-                },
-            ) => {
-                let vdaf = Arc::new(Prio3::new_sum_vec_multithreaded(
-                    2,
-                    *bits,
-                    *length,
-                    *chunk_length,
-                )?);
-                self.create_aggregation_jobs_for_time_interval_task_no_param::<VERIFY_KEY_LENGTH, Prio3SumVecMultithreaded>(task, vdaf)
-                    .await
-            }
-
-            (
-                task::QueryType::TimeInterval,
-                VdafInstance::Prio3SumVecField64MultiproofHmacSha256Aes128 {
-                    proofs,
-                    bits,
-                    length,
-                    chunk_length,
-                },
-            ) => {
-                let vdaf = Arc::new(new_prio3_sum_vec_field64_multiproof_hmacsha256_aes128(
-                    *proofs,
-                    *bits,
-                    *length,
-                    *chunk_length,
-                )?);
-                self.create_aggregation_jobs_for_time_interval_task_no_param::<
-                    VERIFY_KEY_LENGTH_HMACSHA256_AES128,
-                    Prio3SumVecField64MultiproofHmacSha256Aes128,
-                >(task, vdaf).await
-            }
-
-            (
-                task::QueryType::TimeInterval,
-                VdafInstance::Prio3Histogram {
-                    length,
-                    chunk_length,
-                },
-            ) => {
-                let vdaf = Arc::new(Prio3::new_histogram(2, *length, *chunk_length)?);
-                self.create_aggregation_jobs_for_time_interval_task_no_param::<VERIFY_KEY_LENGTH, Prio3Histogram>(task, vdaf)
-                    .await
-            }
-
-            #[cfg(feature = "fpvec_bounded_l2")]
-            (
-                task::QueryType::TimeInterval,
-                VdafInstance::Prio3FixedPointBoundedL2VecSum {
-                    bitsize,
-                    dp_strategy: _,
-                    length,
-                },
-            ) => match bitsize {
-                Prio3FixedPointBoundedL2VecSumBitSize::BitSize16 => {
-                    let vdaf: Arc<Prio3FixedPointBoundedL2VecSumMultithreaded<FixedI16<U15>>> =
-                        Arc::new(Prio3::new_fixedpoint_boundedl2_vec_sum_multithreaded(
-                            2, *length,
-                        )?);
-                    self.create_aggregation_jobs_for_time_interval_task_no_param::<VERIFY_KEY_LENGTH, Prio3FixedPointBoundedL2VecSumMultithreaded<FixedI16<U15>>>(task, vdaf)
+        match task.query_type() {
+            task::QueryType::TimeInterval => match task.vdaf().dispatch_group() {
+                // Reports can't be aggregated under VDAFs with an aggregation parameter until a
+                // collector chooses one, so their aggregation jobs are created from collection
+                // jobs.
+                VdafDispatchGroup::Poplar1 => {
+                    vdaf_dispatch!(task.vdaf(), (vdaf, VdafType, VERIFY_KEY_LENGTH) => {
+                        self.create_aggregation_jobs_for_time_interval_task_with_param::<VERIFY_KEY_LENGTH, VdafType>(task, Arc::new(vdaf))
                             .await
+                    })
                 }
-                Prio3FixedPointBoundedL2VecSumBitSize::BitSize32 => {
-                    let vdaf: Arc<Prio3FixedPointBoundedL2VecSumMultithreaded<FixedI32<U31>>> =
-                        Arc::new(Prio3::new_fixedpoint_boundedl2_vec_sum_multithreaded(
-                            2, *length,
-                        )?);
-                    self.create_aggregation_jobs_for_time_interval_task_no_param::<VERIFY_KEY_LENGTH, Prio3FixedPointBoundedL2VecSumMultithreaded<FixedI32<U31>>>(task, vdaf)
+
+                VdafDispatchGroup::Prio3
+                | VdafDispatchGroup::Prio3FixedPointBoundedL2VecSum
+                | VdafDispatchGroup::Fake => {
+                    vdaf_dispatch_no_aggregation_parameter!(task.vdaf(), (vdaf, VdafType, VERIFY_KEY_LENGTH) => {
+                        self.create_aggregation_jobs_for_time_interval_task_no_param::<VERIFY_KEY_LENGTH, VdafType>(task, Arc::new(vdaf))
                             .await
+                    }, _ => {
+                        error!(vdaf = ?task.vdaf(), "VDAF is not yet supported");
+                        panic!("VDAF {:?} is not yet supported", task.vdaf());
+                    })
                 }
             },
 
-            (
-                task::QueryType::FixedSize {
-                    max_batch_size,
-                    batch_time_window_size,
-                },
-                VdafInstance::Prio3Count,
-            ) => {
-                let vdaf = Arc::new(Prio3::new_count(2)?);
-                let max_batch_size = *max_batch_size;
-                let batch_time_window_size = *batch_time_window_size;
-                self.create_aggregation_jobs_for_fixed_size_task_no_param::<
-                    VERIFY_KEY_LENGTH,
-                    Prio3Count,
-                >(task, vdaf, max_batch_size, batch_time_window_size).await
-            }
-
-            (
-                task::QueryType::FixedSize {
-                    max_batch_size,
-                    batch_time_window_size,
-                },
-                VdafInstance::Prio3Sum { bits },
-            ) => {
-                let vdaf = Arc::new(Prio3::new_sum(2, *bits)?);
-                let max_batch_size = *max_batch_size;
-                let batch_time_window_size = *batch_time_window_size;
-                self.create_aggregation_jobs_for_fixed_size_task_no_param::<
-                    VERIFY_KEY_LENGTH,
-                    Prio3Sum,
-                >(task, vdaf, max_batch_size, batch_time_window_size).await
-            }
-
-            (
-                task::QueryType::FixedSize {
-                    max_batch_size,
-                    batch_time_window_size,
-                },
-                VdafInstance::Prio3SumVec {
-                    bits,
-                    length,
-                    chunk_length,
-                },
-            ) => {
-                let vdaf = Arc::new(Prio3::new_sum_vec_multithreaded(
-                    2,
-                    *bits,
-                    *length,
-                    *chunk_length,
-                )?);
-                let max_batch_size = *max_batch_size;
-                let batch_time_window_size = *batch_time_window_size;
-                self.create_aggregation_jobs_for_fixed_size_task_no_param::<
-                    VERIFY_KEY_LENGTH,
-                    Prio3SumVecMultithreaded,
-                >(task, vdaf, max_batch_size, batch_time_window_size).await
-            }
-
-            (
-                task::QueryType::FixedSize {
-                    max_batch_size,
-                    batch_time_window_size,
-                },
-                VdafInstance::Prio3SumVecField64MultiproofHmacSha256Aes128 {
-                    proofs,
-                    bits,
-                    length,
-                    chunk_length,
-                },
-            ) => {
-                let vdaf = Arc::new(new_prio3_sum_vec_field64_multiproof_hmacsha256_aes128(
-                    *proofs,
-                    *bits,
-                    *length,
-                    *chunk_length,
-                )?);
-                let max_batch_size = *max_batch_size;
-                let batch_time_window_size = *batch_time_window_size;
-                self.create_aggregation_jobs_for_fixed_size_task_no_param::<
-                    VERIFY_KEY_LENGTH_HMACSHA256_AES128,
-                    Prio3SumVecField64MultiproofHmacSha256Aes128,
-                >(task, vdaf, max_batch_size, batch_time_window_size).await
-            }
-
-            (
-                task::QueryType::FixedSize {
-                    max_batch_size,
-                    batch_time_window_size,
-                },
-                VdafInstance::Prio3Histogram {
-                    length,
-                    chunk_length,
-                },
-            ) => {
-                let vdaf = Arc::new(Prio3::new_histogram(2, *length, *chunk_length)?);
-                let max_batch_size = *max_batch_size;
-                let batch_time_window_size = *batch_time_window_size;
-                self.create_aggregation_jobs_for_fixed_size_task_no_param::<
-                    VERIFY_KEY_LENGTH,
-                    Prio3Histogram,
-                >(task, vdaf, max_batch_size, batch_time_window_size).await
-            }
-
-            #[cfg(feature = "fpvec_bounded_l2")]
-            (
-                task::QueryType::FixedSize {
-                    max_batch_size,
-                    batch_time_window_size,
-                },
-                VdafInstance::Prio3FixedPointBoundedL2VecSum {
-                    bitsize,
-                    dp_strategy: _,
-                    length,
-                },
-            ) => {
+            task::QueryType::FixedSize {
+                max_batch_size,
+                batch_time_window_size,
+            } => {
                 let max_batch_size = *max_batch_size;
                 let batch_time_window_size = *batch_time_window_size;
-
-                match bitsize {
-                    janus_core::vdaf::Prio3FixedPointBoundedL2VecSumBitSize::BitSize16 => {
-                        let vdaf: Arc<Prio3FixedPointBoundedL2VecSumMultithreaded<FixedI16<U15>>> =
-                            Arc::new(Prio3::new_fixedpoint_boundedl2_vec_sum_multithreaded(
-                                2, *length,
-                            )?);
-                        self.create_aggregation_jobs_for_fixed_size_task_no_param::<
-                                VERIFY_KEY_LENGTH,
-                            Prio3FixedPointBoundedL2VecSumMultithreaded<FixedI16<U15>>,
-                            >(task, vdaf, max_batch_size, batch_time_window_size).await
-                    }
-                    janus_core::vdaf::Prio3FixedPointBoundedL2VecSumBitSize::BitSize32 => {
-                        let vdaf: Arc<Prio3FixedPointBoundedL2VecSumMultithreaded<FixedI32<U31>>> =
-                            Arc::new(Prio3::new_fixedpoint_boundedl2_vec_sum_multithreaded(
-                                2, *length,
-                            )?);
-                        self.create_aggregation_jobs_for_fixed_size_task_no_param::<
-                                VERIFY_KEY_LENGTH,
-                            Prio3FixedPointBoundedL2VecSumMultithreaded<FixedI32<U31>>,
-                            >(task, vdaf, max_batch_size, batch_time_window_size).await
-                    }
-                }
-            }
-
-            _ => {
-                error!(vdaf = ?task.vdaf(), "VDAF is not yet supported");
-                panic!("VDAF {:?} is not yet supported", task.vdaf());
+                vdaf_dispatch_no_aggregation_parameter!(task.vdaf(), (vdaf, VdafType, VERIFY_KEY_LENGTH) => {
+                    self.create_aggregation_jobs_for_fixed_size_task_no_param::<VERIFY_KEY_LENGTH, VdafType>(
+                        task,
+                        Arc::new(vdaf),
+                        max_batch_size,
+                        batch_time_window_size,
+                    )
+                    .await
+                }, _ => {
+                    error!(vdaf = ?task.vdaf(), "VDAF is not yet supported");
+                    panic!("VDAF {:?} is not yet supported", task.vdaf());
+                })
             }
         }
     }
@@ -564,6 +623,11 @@ impl<C: Clock + 'static> AggregationJobCreator<C> {
                     self.aggregation_job_creation_report_window;
 
                 Box::pin(async move {
+                    if !this.deployment_fence.is_active(tx).await? {
+                        debug!("Deployment is on standby, not creating aggregation jobs");
+                        return Ok(false);
+                    }
+
                     // Find some unaggregated client reports.
                     let mut reports = tx
                         .get_unaggregated_client_reports_for_task(
@@ -573,6 +637,8 @@ impl<C: Clock + 'static> AggregationJobCreator<C> {
                         )
                         .await?;
                     reports.sort_by_key(|report_metadata| *report_metadata.time());
+                    let (min_aggregation_job_size, max_aggregation_job_size) =
+                        this.aggregation_job_size_bounds(&task, reports.len())?;
 
                     // Generate aggregation jobs & report aggregations based on the reports we read.
                     // We attempt to generate reports from touching a minimal number of batches by
@@ -609,7 +675,7 @@ impl<C: Clock + 'static> AggregationJobCreator<C> {
                             // least the minimum aggregation job size available. If we run out of
                             // reports from `reports_by_batch` without meeting the minimum
                             // aggregation job size, we are done generating aggregation jobs.
-                            if outstanding_reports.len() < this.min_aggregation_job_size {
+                            if outstanding_reports.len() < min_aggregation_job_size {
                                 if let Some((_, new_reports)) = reports_by_batch.next() {
                                     outstanding_reports.extend(new_reports);
                                     continue;
@@ -624,12 +690,10 @@ impl<C: Clock + 'static> AggregationJobCreator<C> {
                             // For the rest of the iteration of this loop, we'll generate a single
                             // aggregation job.
                             let agg_job_reports: Vec<_> = outstanding_reports
-                                .drain(
-                                    ..min(this.max_aggregation_job_size, outstanding_reports.len()),
-                                )
+                                .drain(..min(max_aggregation_job_size, outstanding_reports.len()))
                                 .collect();
 
-                            let aggregation_job_id = random();
+                            let aggregation_job_id = generate_aggregation_job_id();
                             debug!(
                                 task_id = %task.id(),
                                 %aggregation_job_id,
@@ -709,6 +773,141 @@ impl<C: Clock + 'static> AggregationJobCreator<C> {
             .await?)
     }
 
+    async fn create_aggregation_jobs_for_time_interval_task_with_param<
+        const SEED_SIZE: usize,
+        A: vdaf::Aggregator<SEED_SIZE, 16>,
+    >(
+        self: Arc<Self>,
+        task: Arc<AggregatorTask>,
+        vdaf: Arc<A>,
+    ) -> anyhow::Result<bool>
+    where
+        A: Send + Sync + 'static,
+        A::AggregateShare: Send + Sync,
+        A::AggregationParam: Send + Sync + PartialEq + Eq,
+        A::InputShare: Send + Sync + PartialEq,
+        A::PrepareMessage: Send + Sync,
+        A::PrepareShare: Send + Sync,
+        A::PrepareState: Send + Sync + Encode,
+        A::PublicShare: Send + Sync + PartialEq,
+        A::OutputShare: Send + Sync,
+    {
+        Ok(self
+            .datastore
+            .run_tx("aggregation_job_creator_time_with_param", |tx| {
+                let this = Arc::clone(&self);
+                let task = Arc::clone(&task);
+                let vdaf = Arc::clone(&vdaf);
+                let batch_aggregation_shard_count = self.batch_aggregation_shard_count;
+                let aggregation_job_creation_report_window =
+                    self.aggregation_job_creation_report_window;
+
+                Box::pin(async move {
+                    if !this.deployment_fence.is_active(tx).await? {
+                        debug!("Deployment is on standby, not creating aggregation jobs");
+                        return Ok(false);
+                    }
+
+                    // The aggregation parameter is chosen by the Collector, so reports can only be
+                    // aggregated once a collection job requests them. Find reports covered by
+                    // outstanding collection jobs which have not yet been aggregated with the
+                    // relevant aggregation parameter. Reports are not scrubbed, since they may be
+                    // aggregated again with a later collection job's aggregation parameter.
+                    let reports = tx
+                        .get_unaggregated_client_report_ids_by_collect_for_task::<SEED_SIZE, A>(
+                            task.id(),
+                            aggregation_job_creation_report_window,
+                        )
+                        .await?;
+                    let (_, max_aggregation_job_size) =
+                        this.aggregation_job_size_bounds(&task, reports.len())?;
+
+                    let mut reports_by_aggregation_param: HashMap<
+                        Vec<u8>,
+                        (A::AggregationParam, Vec<_>),
+                    > = HashMap::new();
+                    for (report_metadata, aggregation_param) in reports {
+                        reports_by_aggregation_param
+                            .entry(aggregation_param.get_encoded()?)
+                            .or_insert_with(|| (aggregation_param, Vec::new()))
+                            .1
+                            .push(report_metadata);
+                    }
+
+                    // Every report requested by a collection job must be aggregated before the
+                    // collection job can complete, so the minimum aggregation job size does not
+                    // apply.
+                    let mut aggregation_job_writer =
+                        AggregationJobWriter::<SEED_SIZE, _, _, InitialWrite, _>::new(
+                            Arc::clone(&task),
+                            batch_aggregation_shard_count,
+                            None,
+                        );
+                    for (aggregation_param, reports) in reports_by_aggregation_param.into_values() {
+                        for agg_job_reports in reports.chunks(max_aggregation_job_size) {
+                            let aggregation_job_id = generate_aggregation_job_id();
+                            debug!(
+                                task_id = %task.id(),
+                                %aggregation_job_id,
+                                report_count = %agg_job_reports.len(),
+                                "Creating aggregation job"
+                            );
+
+                            let min_client_timestamp = agg_job_reports
+                                .iter()
+                                .map(|report_metadata| report_metadata.time())
+                                .min()
+                                .unwrap(); // unwrap safety: agg_job_reports is non-empty
+                            let max_client_timestamp = agg_job_reports
+                                .iter()
+                                .map(|report_metadata| report_metadata.time())
+                                .max()
+                                .unwrap(); // unwrap safety: agg_job_reports is non-empty
+                            let client_timestamp_interval = Interval::new(
+                                *min_client_timestamp,
+                                max_client_timestamp
+                                    .difference(min_client_timestamp)?
+                                    .add(&DurationMsg::from_seconds(1))?,
+                            )?;
+
+                            let aggregation_job = AggregationJob::<SEED_SIZE, TimeInterval, A>::new(
+                                *task.id(),
+                                aggregation_job_id,
+                                aggregation_param.clone(),
+                                (),
+                                client_timestamp_interval,
+                                AggregationJobState::InProgress,
+                                AggregationJobStep::from(0),
+                            );
+
+                            let report_aggregations = agg_job_reports
+                                .iter()
+                                .enumerate()
+                                .map(|(ord, report_metadata)| {
+                                    Ok(ReportAggregationMetadata::new(
+                                        *task.id(),
+                                        aggregation_job_id,
+                                        *report_metadata.id(),
+                                        *report_metadata.time(),
+                                        ord.try_into()?,
+                                        ReportAggregationMetadataState::Start,
+                                    ))
+                                })
+                                .collect::<Result<_, datastore::Error>>()?;
+
+                            aggregation_job_writer.put(aggregation_job, report_aggregations)?;
+                        }
+                    }
+
+                    // Write the aggregation jobs and report aggregations we created.
+                    aggregation_job_writer.write(tx, vdaf).await?;
+
+                    Ok(!aggregation_job_writer.is_empty())
+                })
+            })
+            .await?)
+    }
+
     async fn create_aggregation_jobs_for_fixed_size_task_no_param<
         const SEED_SIZE: usize,
         A: vdaf::Aggregator<SEED_SIZE, 16, AggregationParam = ()>,
@@ -744,6 +943,11 @@ impl<C: Clock + 'static> AggregationJobCreator<C> {
                     self.aggregation_job_creation_report_window;
 
                 Box::pin(async move {
+                    if !this.deployment_fence.is_active(tx).await? {
+                        debug!("Deployment is on standby, not creating aggregation jobs");
+                        return Ok(false);
+                    }
+
                     // Find unaggregated client reports.
                     let unaggregated_reports = tx
                         .get_unaggregated_client_reports_for_task(
@@ -752,6 +956,8 @@ impl<C: Clock + 'static> AggregationJobCreator<C> {
                             aggregation_job_creation_report_window,
                         )
                         .await?;
+                    let (min_aggregation_job_size, max_aggregation_job_size) =
+                        this.aggregation_job_size_bounds(&task, unaggregated_reports.len())?;
 
                     let mut aggregation_job_writer = AggregationJobWriter::new(
                         Arc::clone(&task),
@@ -759,8 +965,8 @@ impl<C: Clock + 'static> AggregationJobCreator<C> {
                         None,
                     );
                     let mut batch_creator = BatchCreator::new(
-                        this.min_aggregation_job_size,
-                        this.max_aggregation_job_size,
+                        min_aggregation_job_size,
+                        max_aggregation_job_size,
                         *task.id(),
                         task_min_batch_size,
                         task_max_batch_size,
@@ -790,14 +996,17 @@ mod tests {
         datastore::{
             models::{
                 merge_batch_aggregations_by_batch, AggregationJob, AggregationJobState,
-                BatchAggregation, BatchAggregationState, LeaderStoredReport, ReportAggregation,
-                ReportAggregationState,
+                BatchAggregation, BatchAggregationState, CollectionJob, CollectionJobState,
+                LeaderStoredReport, ReportAggregation, ReportAggregationState,
             },
             test_util::ephemeral_datastore,
             Transaction,
         },
         query_type::AccumulableQueryType,
-        task::{test_util::TaskBuilder, QueryType as TaskQueryType},
+        task::{
+            test_util::TaskBuilder, AggregationJobSizing, AggregatorTask,
+            QueryType as TaskQueryType,
+        },
         test_util::noop_meter,
     };
     use janus_core::{
@@ -809,12 +1018,18 @@ mod tests {
     use janus_messages::{
         codec::ParameterizedDecode,
         query_type::{FixedSize, TimeInterval},
-        AggregationJobStep, Interval, PrepareError, ReportId, ReportIdChecksum, ReportMetadata,
-        Role, TaskId, Time,
+        AggregationJobStep, Interval, PrepareError, Query, ReportId, ReportIdChecksum,
+        ReportMetadata, Role, TaskId, Time,
     };
-    use prio::vdaf::{
-        self, dummy,
-        prio3::{Prio3, Prio3Count},
+    use prio::{
+        codec::Encode,
+        idpf::IdpfInput,
+        vdaf::{
+            self, dummy,
+            poplar1::{Poplar1, Poplar1AggregationParam},
+            prio3::{Prio3, Prio3Count},
+            xof::XofTurboShake128,
+        },
     };
     use rand::random;
     use std::{
@@ -1018,6 +1233,66 @@ mod tests {
         assert!(helper_batch_aggregations.is_empty());
     }
 
+    #[tokio::test]
+    async fn aggregation_job_creator_task_discovery() {
+        install_test_trace_subscriber();
+        let clock = MockClock::default();
+        let ephemeral_datastore = ephemeral_datastore().await;
+        let ds = ephemeral_datastore.datastore(clock.clone()).await;
+
+        // Start the aggregation job creator before any tasks exist. Since tasks are only fully
+        // updated hourly, the task provisioned below can only be picked up by task discovery.
+        const TASK_DISCOVERY_INTERVAL: Duration = Duration::from_secs(1);
+        let job_creator = Arc::new(
+            AggregationJobCreator::new(
+                ds,
+                noop_meter(),
+                BATCH_AGGREGATION_SHARD_COUNT,
+                Duration::from_secs(3600),
+                Duration::from_secs(1),
+                1,
+                100,
+                5000,
+            )
+            .with_task_discovery_interval(TASK_DISCOVERY_INTERVAL),
+        );
+        let stopper = Stopper::new();
+        let task_handle = task::spawn(Arc::clone(&job_creator).run(stopper.clone()));
+        time::sleep(TASK_DISCOVERY_INTERVAL).await;
+
+        let leader_task = Arc::new(
+            TaskBuilder::new(TaskQueryType::TimeInterval, VdafInstance::Prio3Count)
+                .build()
+                .leader_view()
+                .unwrap(),
+        );
+        job_creator
+            .datastore
+            .run_unnamed_tx(|tx| {
+                let leader_task = Arc::clone(&leader_task);
+                Box::pin(async move { tx.put_aggregator_task(&leader_task).await })
+            })
+            .await
+            .unwrap();
+        time::sleep(4 * TASK_DISCOVERY_INTERVAL).await;
+        stopper.stop();
+        task_handle.await.unwrap();
+
+        // The task was discovered, and aggregation jobs were created for it without error.
+        let task_id = *leader_task.id();
+        let state = job_creator
+            .datastore
+            .run_unnamed_tx(|tx| {
+                Box::pin(async move { tx.get_aggregation_job_creator_task_state(&task_id).await })
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(state.discovered_at(), &clock.now());
+        assert_eq!(state.last_run_at(), Some(&clock.now()));
+        assert_eq!(state.last_error(), None);
+    }
+
     #[tokio::test]
     async fn create_aggregation_jobs_for_time_interval_task() {
         // Setup.
@@ -1210,6 +1485,157 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn create_aggregation_jobs_for_time_interval_task_sizing() {
+        // Setup.
+        install_test_trace_subscriber();
+        let clock = MockClock::default();
+        let ephemeral_datastore = ephemeral_datastore().await;
+        let ds = ephemeral_datastore.datastore(clock.clone()).await;
+        const REPORT_COUNT: usize = 25;
+
+        // Both tasks override the configured sizes, which would not allow any jobs to be created.
+        let sizing = AggregationJobSizing::new(
+            Some(1),
+            Some(10),
+            Some(janus_messages::Duration::from_seconds(5)),
+        );
+        let tasks: Vec<_> = iter::repeat_with(|| {
+            Arc::new(
+                TaskBuilder::new(TaskQueryType::TimeInterval, VdafInstance::Prio3Count)
+                    .build()
+                    .leader_view()
+                    .unwrap()
+                    .with_aggregation_job_sizing(sizing)
+                    .unwrap(),
+            )
+        })
+        .take(2)
+        .collect();
+        let vdaf = Arc::new(Prio3::new_count(2).unwrap());
+        let helper_hpke_keypair = generate_test_hpke_config_and_private_key();
+        let report_time = clock.now();
+
+        let mut want_ra_states = HashMap::new();
+        for task in &tasks {
+            let reports: Vec<_> = iter::repeat_with(|| {
+                let report_metadata = ReportMetadata::new(random(), report_time);
+                let transcript = run_vdaf(
+                    vdaf.as_ref(),
+                    task.vdaf_verify_key().unwrap().as_bytes(),
+                    &(),
+                    report_metadata.id(),
+                    &false,
+                );
+                LeaderStoredReport::generate(
+                    *task.id(),
+                    report_metadata,
+                    helper_hpke_keypair.config(),
+                    Vec::new(),
+                    &transcript,
+                )
+            })
+            .take(REPORT_COUNT)
+            .collect();
+            for report in &reports {
+                want_ra_states.insert(
+                    *report.metadata().id(),
+                    report
+                        .as_start_leader_report_aggregation(random(), 0)
+                        .state()
+                        .clone(),
+                );
+            }
+
+            ds.run_unnamed_tx(|tx| {
+                let (task, vdaf, reports) = (Arc::clone(task), Arc::clone(&vdaf), reports.clone());
+                Box::pin(async move {
+                    tx.put_aggregator_task(&task).await.unwrap();
+                    for report in &reports {
+                        tx.put_client_report(vdaf.as_ref(), report).await.unwrap();
+                    }
+                    Ok(())
+                })
+            })
+            .await
+            .unwrap();
+        }
+        let want_ra_states = Arc::new(want_ra_states);
+
+        // Run. The first task's reports fit in one report window, so its own maximum applies. The
+        // second task's reports fill a report window, so larger jobs are created.
+        let job_creator = AggregationJobCreator::new(
+            ds,
+            noop_meter(),
+            BATCH_AGGREGATION_SHARD_COUNT,
+            Duration::from_secs(3600),
+            Duration::from_secs(3600),
+            100,
+            200,
+            5000,
+        )
+        .with_max_adaptive_aggregation_job_size(20);
+        assert_eq!(
+            job_creator.aggregation_job_creation_interval(&tasks[0]),
+            Duration::from_secs(5)
+        );
+        Arc::new(job_creator)
+            .create_aggregation_jobs_for_task(Arc::clone(&tasks[0]))
+            .await
+            .unwrap();
+
+        let job_creator = Arc::new(
+            AggregationJobCreator::new(
+                ephemeral_datastore.datastore(clock.clone()).await,
+                noop_meter(),
+                BATCH_AGGREGATION_SHARD_COUNT,
+                Duration::from_secs(3600),
+                Duration::from_secs(3600),
+                100,
+                200,
+                REPORT_COUNT,
+            )
+            .with_max_adaptive_aggregation_job_size(20),
+        );
+        Arc::clone(&job_creator)
+            .create_aggregation_jobs_for_task(Arc::clone(&tasks[1]))
+            .await
+            .unwrap();
+
+        // Verify.
+        for (task, want_job_sizes) in tasks.iter().zip([[10, 10, 5].as_slice(), &[20, 5]]) {
+            let agg_jobs = job_creator
+                .datastore
+                .run_unnamed_tx(|tx| {
+                    let (task, vdaf, want_ra_states) = (
+                        Arc::clone(task),
+                        Arc::clone(&vdaf),
+                        Arc::clone(&want_ra_states),
+                    );
+                    Box::pin(async move {
+                        Ok(read_and_verify_aggregate_info_for_task::<
+                            VERIFY_KEY_LENGTH,
+                            TimeInterval,
+                            _,
+                            _,
+                        >(
+                            tx, vdaf.as_ref(), task.id(), want_ra_states.as_ref()
+                        )
+                        .await
+                        .0)
+                    })
+                })
+                .await
+                .unwrap();
+            let mut job_sizes: Vec<_> = agg_jobs
+                .iter()
+                .map(|(_, report_aggs)| report_aggs.len())
+                .collect();
+            job_sizes.sort_unstable_by(|a, b| b.cmp(a));
+            assert_eq!(job_sizes, want_job_sizes);
+        }
+    }
+
     #[tokio::test]
     async fn create_aggregation_jobs_for_time_interval_task_not_enough_reports() {
         // Setup.
@@ -1600,6 +2026,242 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn create_aggregation_jobs_for_time_interval_task_with_param() {
+        // Setup.
+        install_test_trace_subscriber();
+        let clock = MockClock::default();
+        let ephemeral_datastore = ephemeral_datastore().await;
+        let ds = ephemeral_datastore.datastore(clock.clone()).await;
+        const MIN_AGGREGATION_JOB_SIZE: usize = 5;
+        const MAX_AGGREGATION_JOB_SIZE: usize = 10;
+
+        let task = Arc::new(
+            TaskBuilder::new(
+                TaskQueryType::TimeInterval,
+                VdafInstance::Poplar1 { bits: 1 },
+            )
+            .build()
+            .leader_view()
+            .unwrap(),
+        );
+        let vdaf = Arc::new(Poplar1::new_turboshake128(1));
+        let first_aggregation_param = Poplar1AggregationParam::try_from_prefixes(Vec::from([
+            IdpfInput::from_bools(&[false]),
+        ]))
+        .unwrap();
+        let second_aggregation_param =
+            Poplar1AggregationParam::try_from_prefixes(Vec::from([IdpfInput::from_bools(&[true])]))
+                .unwrap();
+
+        let report_time = clock.now();
+        let batch_interval = Interval::new(
+            report_time
+                .to_batch_interval_start(task.time_precision())
+                .unwrap(),
+            *task.time_precision(),
+        )
+        .unwrap();
+        let helper_hpke_keypair = generate_test_hpke_config_and_private_key();
+        let reports: Arc<Vec<_>> = Arc::new(
+            iter::repeat_with(|| {
+                let report_metadata = ReportMetadata::new(random(), report_time);
+                let transcript = run_vdaf(
+                    vdaf.as_ref(),
+                    task.vdaf_verify_key().unwrap().as_bytes(),
+                    &first_aggregation_param,
+                    report_metadata.id(),
+                    &IdpfInput::from_bools(&[false]),
+                );
+                LeaderStoredReport::generate(
+                    *task.id(),
+                    report_metadata,
+                    helper_hpke_keypair.config(),
+                    Vec::new(),
+                    &transcript,
+                )
+            })
+            .take(MAX_AGGREGATION_JOB_SIZE + 1)
+            .collect(),
+        );
+        let all_report_ids: HashSet<ReportId> = reports
+            .iter()
+            .map(|report| *report.metadata().id())
+            .collect();
+
+        ds.run_unnamed_tx(|tx| {
+            let task = Arc::clone(&task);
+            let vdaf = Arc::clone(&vdaf);
+            let reports = Arc::clone(&reports);
+
+            Box::pin(async move {
+                tx.put_aggregator_task(&task).await.unwrap();
+                for report in reports.iter() {
+                    tx.put_client_report(vdaf.as_ref(), report).await.unwrap();
+                }
+                Ok(())
+            })
+        })
+        .await
+        .unwrap();
+
+        let job_creator = Arc::new(AggregationJobCreator::new(
+            ds,
+            noop_meter(),
+            1,
+            Duration::from_secs(3600),
+            Duration::from_secs(1),
+            MIN_AGGREGATION_JOB_SIZE,
+            MAX_AGGREGATION_JOB_SIZE,
+            5000,
+        ));
+        let put_collection_job = |aggregation_param: Poplar1AggregationParam| {
+            let task_id = *task.id();
+            job_creator.datastore.run_unnamed_tx(move |tx| {
+                let aggregation_param = aggregation_param.clone();
+                Box::pin(async move {
+                    tx.put_collection_job(&CollectionJob::<
+                        VERIFY_KEY_LENGTH,
+                        TimeInterval,
+                        Poplar1<XofTurboShake128, 16>,
+                    >::new(
+                        task_id,
+                        random(),
+                        Query::new_time_interval(batch_interval),
+                        aggregation_param,
+                        batch_interval,
+                        CollectionJobState::Start,
+                    ))
+                    .await
+                })
+            })
+        };
+        let has_unaggregated_reports = |aggregation_param: &Poplar1AggregationParam| {
+            let task_id = *task.id();
+            let aggregation_param = aggregation_param.get_encoded().unwrap();
+            job_creator.datastore.run_unnamed_tx(move |tx| {
+                let aggregation_param = aggregation_param.clone();
+                Box::pin(async move {
+                    tx.interval_has_unaggregated_reports_for_aggregation_param(
+                        &task_id,
+                        &batch_interval,
+                        &aggregation_param,
+                    )
+                    .await
+                })
+            })
+        };
+
+        // Without a collection job, there is no aggregation parameter to aggregate with.
+        assert!(!Arc::clone(&job_creator)
+            .create_aggregation_jobs_for_task(Arc::clone(&task))
+            .await
+            .unwrap());
+        assert!(read_aggregation_jobs_with_param(&job_creator, &task)
+            .await
+            .is_empty());
+
+        // Once a collection job is created, every report in its batch interval is aggregated with
+        // its aggregation parameter, regardless of the minimum aggregation job size.
+        put_collection_job(first_aggregation_param.clone())
+            .await
+            .unwrap();
+        assert!(has_unaggregated_reports(&first_aggregation_param)
+            .await
+            .unwrap());
+        assert!(Arc::clone(&job_creator)
+            .create_aggregation_jobs_for_task(Arc::clone(&task))
+            .await
+            .unwrap());
+        assert!(!Arc::clone(&job_creator)
+            .create_aggregation_jobs_for_task(Arc::clone(&task))
+            .await
+            .unwrap());
+        assert!(!has_unaggregated_reports(&first_aggregation_param)
+            .await
+            .unwrap());
+        assert!(has_unaggregated_reports(&second_aggregation_param)
+            .await
+            .unwrap());
+
+        let agg_jobs = read_aggregation_jobs_with_param(&job_creator, &task).await;
+        assert_eq!(agg_jobs.len(), 2);
+        let mut seen_report_ids = HashSet::new();
+        for (agg_job, report_ids) in &agg_jobs {
+            assert_eq!(agg_job.aggregation_parameter(), &first_aggregation_param);
+            assert_eq!(agg_job.step(), AggregationJobStep::from(0));
+            assert!(report_ids.len() <= MAX_AGGREGATION_JOB_SIZE);
+            for report_id in report_ids {
+                assert!(seen_report_ids.insert(*report_id));
+            }
+        }
+        assert_eq!(all_report_ids, seen_report_ids);
+
+        // A later collection job with a different aggregation parameter aggregates the same reports
+        // again.
+        put_collection_job(second_aggregation_param.clone())
+            .await
+            .unwrap();
+        assert!(Arc::clone(&job_creator)
+            .create_aggregation_jobs_for_task(Arc::clone(&task))
+            .await
+            .unwrap());
+        assert!(!has_unaggregated_reports(&second_aggregation_param)
+            .await
+            .unwrap());
+
+        let agg_jobs = read_aggregation_jobs_with_param(&job_creator, &task).await;
+        assert_eq!(agg_jobs.len(), 4);
+        let seen_report_ids: HashSet<_> = agg_jobs
+            .iter()
+            .filter(|(agg_job, _)| agg_job.aggregation_parameter() == &second_aggregation_param)
+            .flat_map(|(_, report_ids)| report_ids.iter().copied())
+            .collect();
+        assert_eq!(all_report_ids, seen_report_ids);
+    }
+
+    async fn read_aggregation_jobs_with_param(
+        job_creator: &AggregationJobCreator<MockClock>,
+        task: &Arc<AggregatorTask>,
+    ) -> Vec<(
+        AggregationJob<VERIFY_KEY_LENGTH, TimeInterval, Poplar1<XofTurboShake128, 16>>,
+        Vec<ReportId>,
+    )> {
+        job_creator
+            .datastore
+            .run_unnamed_tx(|tx| {
+                let task = Arc::clone(task);
+                Box::pin(async move {
+                    let vdaf = Poplar1::new_turboshake128(1);
+                    let agg_jobs = tx
+                        .get_aggregation_jobs_for_task::<
+                            VERIFY_KEY_LENGTH,
+                            TimeInterval,
+                            Poplar1<XofTurboShake128, 16>,
+                        >(task.id())
+                        .await?;
+                    let mut result = Vec::new();
+                    for agg_job in agg_jobs {
+                        let report_ids = tx
+                            .get_report_aggregations_for_aggregation_job(
+                                &vdaf,
+                                &Role::Leader,
+                                task.id(),
+                                agg_job.id(),
+                            )
+                            .await?
+                            .iter()
+                            .map(|ra| *ra.report_id())
+                            .collect();
+                        result.push((agg_job, report_ids));
+                    }
+                    Ok(result)
+                })
+            })
+            .await
+            .unwrap()
+    }
+
     #[tokio::test]
     async fn create_aggregation_jobs_for_fixed_size_task() {
         // Setup.
@@ -1,9 +1,13 @@
 use super::{error::handle_ping_pong_error, Error, RequestBody};
 use crate::aggregator::{
     aggregate_step_failure_counter,
-    aggregation_job_writer::{AggregationJobWriter, UpdateWrite, WritableReportAggregation},
+    aggregation_job_writer::{
+        AggregationJobWriter, InitialWrite, UpdateWrite, WritableReportAggregation,
+    },
+    deployment::DeploymentFence,
     http_handlers::AGGREGATION_JOB_ROUTE,
     query_type::CollectableQueryType,
+    retry_classification::{FailureDomain, RetryClassifier},
     send_request_to_helper,
 };
 use anyhow::{anyhow, Result};
@@ -11,6 +15,7 @@ use backoff::backoff::Backoff;
 use bytes::Bytes;
 use derivative::Derivative;
 use futures::future::BoxFuture;
+use http::StatusCode;
 use janus_aggregator_core::{
     datastore::{
         self,
@@ -22,11 +27,15 @@ use janus_aggregator_core::{
     },
     task::{self, AggregatorTask, VerifyKey},
 };
-use janus_core::{retries::is_retryable_http_status, time::Clock, vdaf_dispatch};
+use janus_core::{
+    time::{Clock, IntervalExt},
+    vdaf_dispatch,
+};
 use janus_messages::{
+    problem_type::DapProblemType,
     query_type::{FixedSize, TimeInterval},
-    AggregationJobContinueReq, AggregationJobInitializeReq, AggregationJobResp,
-    PartialBatchSelector, PrepareContinue, PrepareError, PrepareInit, PrepareResp,
+    AggregationJobContinueReq, AggregationJobInitializeReq, AggregationJobResp, AggregationJobStep,
+    Interval, PartialBatchSelector, PrepareContinue, PrepareError, PrepareInit, PrepareResp,
     PrepareStepResult, ReportShare, Role,
 };
 use opentelemetry::{
@@ -38,16 +47,19 @@ use prio::{
     topology::ping_pong::{PingPongContinuedValue, PingPongState, PingPongTopology},
     vdaf,
 };
+use rand::random;
 use reqwest::Method;
 use std::{collections::HashSet, sync::Arc, time::Duration};
 use tokio::try_join;
-use tracing::{debug, error, info, trace_span, warn};
+use tracing::{debug, error, field, info, info_span, trace_span, warn, Instrument, Span};
 
 #[derive(Derivative)]
 #[derivative(Debug)]
 pub struct AggregationJobDriver<B> {
     // Configuration.
     batch_aggregation_shard_count: u64,
+    max_concurrent_jobs_per_task: Option<usize>,
+    deployment_fence: DeploymentFence,
 
     // Dependencies.
     http_client: reqwest::Client,
@@ -60,7 +72,11 @@ pub struct AggregationJobDriver<B> {
     #[derivative(Debug = "ignore")]
     job_retry_counter: Counter<u64>,
     #[derivative(Debug = "ignore")]
+    job_adaptation_counter: Counter<u64>,
+    #[derivative(Debug = "ignore")]
     http_request_duration_histogram: Histogram<f64>,
+    #[derivative(Debug = "ignore")]
+    retry_classifier: RetryClassifier,
 }
 
 impl<B> AggregationJobDriver<B>
@@ -89,6 +105,18 @@ where
             .init();
         job_retry_counter.add(0, &[]);
 
+        let job_adaptation_counter = meter
+            .u64_counter("janus_aggregation_job_adaptations")
+            .with_description(
+                "Count of aggregation jobs that were split because the helper rejected them as too \
+                 large, or delayed because the helper was rate limiting requests.",
+            )
+            .with_unit(Unit::new("{job}"))
+            .init();
+        for adaptation in ["split", "delayed"] {
+            job_adaptation_counter.add(0, &[KeyValue::new("type", adaptation)]);
+        }
+
         let http_request_duration_histogram = meter
             .f64_histogram("janus_http_request_duration")
             .with_description(
@@ -97,17 +125,52 @@ where
             .with_unit(Unit::new("s"))
             .init();
 
+        let retry_classifier = RetryClassifier::new(meter);
+
         Self {
             batch_aggregation_shard_count,
+            max_concurrent_jobs_per_task: None,
+            deployment_fence: DeploymentFence::default(),
             http_client,
             backoff,
             aggregate_step_failure_counter,
             job_cancel_counter,
             job_retry_counter,
+            job_adaptation_counter,
             http_request_duration_histogram,
+            retry_classifier,
+        }
+    }
+
+    /// Limits how many aggregation jobs of a single task may be leased at once, across all job
+    /// drivers sharing the datastore, so that a task with many jobs does not starve other tasks. By
+    /// default, there is no limit.
+    pub fn with_max_concurrent_jobs_per_task(self, max_concurrent_jobs_per_task: usize) -> Self {
+        Self {
+            max_concurrent_jobs_per_task: Some(max_concurrent_jobs_per_task),
+            ..self
         }
     }
 
+    /// Only acquire aggregation jobs while this binary's deployment is active.
+    pub fn with_deployment_fence(self, deployment_fence: DeploymentFence) -> Self {
+        Self {
+            deployment_fence,
+            ..self
+        }
+    }
+
+    #[tracing::instrument(
+        name = "AggregationJobDriver::step_aggregation_job",
+        skip_all,
+        fields(
+            task_id = %lease.leased().task_id(),
+            aggregation_job_id = %lease.leased().aggregation_job_id(),
+            lease_attempts = lease.lease_attempts(),
+            step = field::Empty,
+        ),
+        err,
+    )]
     async fn step_aggregation_job<C: Clock>(
         &self,
         datastore: Arc<Datastore<C>>,
@@ -202,7 +265,9 @@ where
                     ))
                 })
             })
+            .instrument(info_span!("AggregationJobDriver::load_job"))
             .await?;
+        Span::current().record("step", field::display(aggregation_job.step()));
 
         // Figure out the next step based on the non-error report aggregation states, and dispatch accordingly.
         let (mut saw_start, mut saw_waiting, mut saw_finished) = (false, false, false);
@@ -256,6 +321,11 @@ where
     }
 
     #[allow(clippy::too_many_arguments)]
+    #[tracing::instrument(
+        name = "AggregationJobDriver::step_aggregation_job_aggregate_init",
+        skip_all,
+        err
+    )]
     async fn step_aggregation_job_aggregate_init<
         const SEED_SIZE: usize,
         C: Clock,
@@ -393,7 +463,7 @@ where
                 prepare_inits,
             );
 
-            let resp_bytes = send_request_to_helper(
+            let result = send_request_to_helper(
                 &self.http_client,
                 self.backoff.clone(),
                 Method::PUT,
@@ -411,9 +481,40 @@ where
                 task.aggregator_auth_token().ok_or_else(|| {
                     Error::InvalidConfiguration("no aggregator auth token in task")
                 })?,
+                task.helper_request_headers(),
                 &self.http_request_duration_histogram,
             )
-            .await?;
+            .await;
+            let resp_bytes = match result {
+                // If the helper rejects the request as too large, split the aggregation job in
+                // two, rather than failing it.
+                Err(Error::Http(error_response))
+                    if error_response.status() == StatusCode::PAYLOAD_TOO_LARGE
+                        && stepped_aggregations.len() > 1 =>
+                {
+                    return self
+                        .split_aggregation_job(
+                            datastore,
+                            vdaf,
+                            lease,
+                            task,
+                            aggregation_job,
+                            stepped_aggregations,
+                            report_aggregations_to_write,
+                        )
+                        .await;
+                }
+                // A Janus helper rejects requests for tasks it doesn't have as unauthorized, so
+                // that it doesn't reveal which tasks exist. The helper may not have been
+                // provisioned with the task yet, so allow this to be retried.
+                Err(Error::Http(error_response))
+                    if error_response.dap_problem_type()
+                        == Some(&DapProblemType::UnauthorizedRequest) =>
+                {
+                    return Err(Error::AggregationJobInitUnauthorized(error_response));
+                }
+                result => result?,
+            };
             AggregationJobResp::get_decoded(&resp_bytes)?
         } else {
             // If there are no prepare inits to send (because every report aggregation was filtered by
@@ -435,6 +536,163 @@ where
         .await
     }
 
+    /// Replaces an aggregation job that the helper rejected as too large with two new aggregation
+    /// jobs, each with half of the reports that were sent to the helper. The original aggregation
+    /// job is abandoned, keeping any report aggregations that already failed.
+    #[allow(clippy::too_many_arguments)]
+    #[tracing::instrument(name = "AggregationJobDriver::split_aggregation_job", skip_all, err)]
+    async fn split_aggregation_job<
+        const SEED_SIZE: usize,
+        C: Clock,
+        Q: CollectableQueryType,
+        A: vdaf::Aggregator<SEED_SIZE, 16> + Send + Sync + 'static,
+    >(
+        &self,
+        datastore: &Datastore<C>,
+        vdaf: Arc<A>,
+        lease: Arc<Lease<AcquiredAggregationJob>>,
+        task: Arc<AggregatorTask>,
+        aggregation_job: AggregationJob<SEED_SIZE, Q, A>,
+        stepped_aggregations: Vec<SteppedAggregation<SEED_SIZE, A>>,
+        mut report_aggregations_to_write: Vec<WritableReportAggregation<SEED_SIZE, A>>,
+    ) -> Result<(), Error>
+    where
+        A::AggregationParam: Send + Sync + PartialEq + Eq,
+        A::AggregateShare: Send + Sync,
+        A::InputShare: Send + Sync,
+        A::OutputShare: Send + Sync,
+        A::PrepareState: Send + Sync + Encode,
+        A::PrepareShare: Send + Sync,
+        A::PrepareMessage: Send + Sync,
+        A::PublicShare: Send + Sync,
+    {
+        let mut first_half: Vec<_> = stepped_aggregations
+            .into_iter()
+            .map(|stepped_aggregation| stepped_aggregation.report_aggregation)
+            .collect();
+        let second_half = first_half.split_off(first_half.len() / 2);
+
+        let mut new_aggregation_job_writer =
+            AggregationJobWriter::<SEED_SIZE, _, _, InitialWrite, _>::new(
+                Arc::clone(&task),
+                self.batch_aggregation_shard_count,
+                None,
+            );
+        let mut new_aggregation_job_ids = Vec::new();
+        for report_aggregations in [&first_half, &second_half] {
+            let aggregation_job_id = random();
+            let client_timestamp_interval = report_aggregations
+                .iter()
+                .try_fold(Interval::EMPTY, |interval, report_aggregation| {
+                    interval.merged_with(report_aggregation.time())
+                })?;
+            let report_aggregations = report_aggregations
+                .iter()
+                .enumerate()
+                .map(|(ord, report_aggregation)| {
+                    Ok(WritableReportAggregation::new(
+                        ReportAggregation::new(
+                            *task.id(),
+                            aggregation_job_id,
+                            *report_aggregation.report_id(),
+                            *report_aggregation.time(),
+                            ord.try_into()?,
+                            None,
+                            report_aggregation.state().clone(),
+                        ),
+                        None,
+                    ))
+                })
+                .collect::<Result<_, Error>>()?;
+            new_aggregation_job_writer.put(
+                AggregationJob::<SEED_SIZE, Q, A>::new(
+                    *task.id(),
+                    aggregation_job_id,
+                    aggregation_job.aggregation_parameter().clone(),
+                    aggregation_job.partial_batch_identifier().clone(),
+                    client_timestamp_interval,
+                    AggregationJobState::InProgress,
+                    AggregationJobStep::from(0),
+                ),
+                report_aggregations,
+            )?;
+            new_aggregation_job_ids.push(aggregation_job_id);
+        }
+
+        // The report aggregations of the original aggregation job are left as they are, except
+        // for those that failed while preparing the request.
+        report_aggregations_to_write.extend(
+            first_half
+                .into_iter()
+                .chain(second_half)
+                .map(|report_aggregation| WritableReportAggregation::new(report_aggregation, None)),
+        );
+        let mut original_aggregation_job_writer =
+            AggregationJobWriter::<SEED_SIZE, _, _, UpdateWrite, _>::new(
+                Arc::clone(&task),
+                self.batch_aggregation_shard_count,
+                None,
+            );
+        original_aggregation_job_writer.put(
+            aggregation_job.with_state(AggregationJobState::Abandoned),
+            report_aggregations_to_write,
+        )?;
+
+        let new_aggregation_job_writer = Arc::new(new_aggregation_job_writer);
+        let original_aggregation_job_writer = Arc::new(original_aggregation_job_writer);
+        datastore
+            .run_tx("split_aggregation_job", |tx| {
+                let vdaf = Arc::clone(&vdaf);
+                let new_aggregation_job_writer = Arc::clone(&new_aggregation_job_writer);
+                let original_aggregation_job_writer = Arc::clone(&original_aggregation_job_writer);
+                let lease = Arc::clone(&lease);
+
+                Box::pin(async move {
+                    // These writes are made serially, since both writers may update the same batch
+                    // aggregations.
+                    new_aggregation_job_writer
+                        .write(tx, Arc::clone(&vdaf))
+                        .await?;
+                    original_aggregation_job_writer.write(tx, vdaf).await?;
+                    tx.release_aggregation_job(&lease, None).await
+                })
+            })
+            .await?;
+
+        info!(
+            new_aggregation_job_ids = ?new_aggregation_job_ids,
+            "Helper rejected aggregation job as too large, split it in two"
+        );
+        self.job_adaptation_counter
+            .add(1, &[KeyValue::new("type", "split")]);
+        Ok(())
+    }
+
+    /// Releases the lease on an aggregation job without stepping it, such that it can't be
+    /// reacquired until `delay` has passed. Releasing the lease resets its attempt count, so the
+    /// delayed step doesn't count as a failed attempt.
+    async fn delay_aggregation_job<C: Clock>(
+        &self,
+        datastore: Arc<Datastore<C>>,
+        lease: Arc<Lease<AcquiredAggregationJob>>,
+        delay: Duration,
+    ) -> Result<(), Error> {
+        datastore
+            .run_tx("delay_aggregation_job", |tx| {
+                let lease = Arc::clone(&lease);
+                Box::pin(async move { tx.release_aggregation_job(&lease, Some(&delay)).await })
+            })
+            .await?;
+        self.job_adaptation_counter
+            .add(1, &[KeyValue::new("type", "delayed")]);
+        Ok(())
+    }
+
+    #[tracing::instrument(
+        name = "AggregationJobDriver::step_aggregation_job_aggregate_continue",
+        skip_all,
+        err
+    )]
     async fn step_aggregation_job_aggregate_continue<
         const SEED_SIZE: usize,
         C: Clock,
@@ -518,6 +776,7 @@ where
             // case, and Janus never acts as the leader with taskprov enabled.
             task.aggregator_auth_token()
                 .ok_or_else(|| Error::InvalidConfiguration("no aggregator auth token in task"))?,
+            task.helper_request_headers(),
             &self.http_request_duration_histogram,
         )
         .await?;
@@ -537,6 +796,11 @@ where
     }
 
     #[allow(clippy::too_many_arguments)]
+    #[tracing::instrument(
+        name = "AggregationJobDriver::process_response_from_helper",
+        skip_all,
+        err
+    )]
     async fn process_response_from_helper<
         const SEED_SIZE: usize,
         C: Clock,
@@ -691,11 +955,12 @@ where
                 Box::pin(async move {
                     try_join!(
                         aggregation_job_writer.write(tx, Arc::clone(&vdaf)),
-                        tx.release_aggregation_job(&lease),
+                        tx.release_aggregation_job(&lease, None),
                     )?;
                     Ok(())
                 })
             })
+            .instrument(info_span!("AggregationJobDriver::commit"))
             .await?;
         Ok(())
     }
@@ -754,7 +1019,7 @@ where
     {
         let vdaf = Arc::new(vdaf);
         let batch_aggregation_shard_count = self.batch_aggregation_shard_count;
-        let (aggregation_job_uri, aggregator_auth_token) = datastore
+        let (aggregation_job_uri, aggregator_auth_token, helper_request_headers) = datastore
             .run_tx("cancel_aggregation_job", |tx| {
                 let vdaf = Arc::clone(&vdaf);
                 let lease = Arc::clone(&lease);
@@ -803,6 +1068,7 @@ where
                     let aggregation_job_uri =
                         task.aggregation_job_uri(lease.leased().aggregation_job_id());
                     let aggregator_auth_token = task.aggregator_auth_token().cloned();
+                    let helper_request_headers = task.helper_request_headers().to_vec();
 
                     let mut aggregation_job_writer =
                         AggregationJobWriter::<SEED_SIZE, _, _, UpdateWrite, _>::new(
@@ -814,10 +1080,14 @@ where
 
                     try_join!(
                         aggregation_job_writer.write(tx, vdaf),
-                        tx.release_aggregation_job(&lease),
+                        tx.release_aggregation_job(&lease, None),
                     )?;
 
-                    Ok((aggregation_job_uri, aggregator_auth_token))
+                    Ok((
+                        aggregation_job_uri,
+                        aggregator_auth_token,
+                        helper_request_headers,
+                    ))
                 })
             })
             .await?;
@@ -841,6 +1111,7 @@ where
             // case, and Janus never acts as the leader with taskprov enabled.
             &aggregator_auth_token
                 .ok_or_else(|| Error::InvalidConfiguration("task has no aggregator auth token"))?,
+            &helper_request_headers,
             &self.http_request_duration_histogram,
         )
         .await;
@@ -854,17 +1125,36 @@ where
         lease_duration: Duration,
     ) -> impl Fn(usize) -> BoxFuture<'static, Result<Vec<Lease<AcquiredAggregationJob>>, datastore::Error>>
     {
+        let max_concurrent_jobs_per_task = self.max_concurrent_jobs_per_task;
+        let deployment_fence = self.deployment_fence.clone();
         move |max_acquire_count: usize| {
-            let datastore = Arc::clone(&datastore);
+            let (datastore, deployment_fence) = (Arc::clone(&datastore), deployment_fence.clone());
             Box::pin(async move {
                 datastore
                     .run_tx("acquire_aggregation_jobs", |tx| {
+                        let deployment_fence = deployment_fence.clone();
                         Box::pin(async move {
-                            tx.acquire_incomplete_aggregation_jobs(
-                                &lease_duration,
-                                max_acquire_count,
-                            )
-                            .await
+                            if !deployment_fence.is_active(tx).await? {
+                                debug!("Deployment is on standby, not acquiring aggregation jobs");
+                                return Ok(Vec::new());
+                            }
+                            match max_concurrent_jobs_per_task {
+                                Some(max_concurrent_jobs_per_task) => {
+                                    tx.acquire_incomplete_aggregation_jobs_with_task_limit(
+                                        &lease_duration,
+                                        max_acquire_count,
+                                        max_concurrent_jobs_per_task,
+                                    )
+                                    .await
+                                }
+                                None => {
+                                    tx.acquire_incomplete_aggregation_jobs(
+                                        &lease_duration,
+                                        max_acquire_count,
+                                    )
+                                    .await
+                                }
+                            }
                         })
                     })
                     .await
@@ -872,6 +1162,52 @@ where
         }
     }
 
+    /// Produce a closure for use as a `[JobDriver::JobReleaser]`.
+    pub fn make_job_releaser_callback<C: Clock>(
+        &self,
+        datastore: Arc<Datastore<C>>,
+    ) -> impl Fn(Lease<AcquiredAggregationJob>) -> BoxFuture<'static, Result<(), datastore::Error>>
+    {
+        move |lease| {
+            let (datastore, lease) = (Arc::clone(&datastore), Arc::new(lease));
+            Box::pin(async move {
+                datastore
+                    .run_tx("release_aggregation_job", |tx| {
+                        let lease = Arc::clone(&lease);
+                        Box::pin(async move { tx.release_aggregation_job(&lease, None).await })
+                    })
+                    .await
+            })
+        }
+    }
+
+    /// Records the failure domain of a failed attempt to step the leased aggregation job, so that
+    /// it is kept with the job if the job is abandoned. This is best-effort: errors are logged, but
+    /// otherwise ignored.
+    async fn record_failure_domain<C: Clock>(
+        &self,
+        datastore: Arc<Datastore<C>>,
+        lease: &Arc<Lease<AcquiredAggregationJob>>,
+        failure_domain: FailureDomain,
+    ) {
+        if let Err(error) = datastore
+            .run_tx("record_aggregation_job_failure_domain", |tx| {
+                let lease = Arc::clone(lease);
+                Box::pin(async move {
+                    tx.set_aggregation_job_last_failure_domain(
+                        lease.leased().task_id(),
+                        lease.leased().aggregation_job_id(),
+                        failure_domain.as_str(),
+                    )
+                    .await
+                })
+            })
+            .await
+        {
+            error!(?error, "Failed to record failure domain of aggregation job");
+        }
+    }
+
     /// Produce a closure for use as a `[JobDriver::JobStepper]`.
     pub fn make_job_stepper_callback<C: Clock>(
         self: Arc<Self>,
@@ -889,7 +1225,8 @@ where
                         max_attempts = %maximum_attempts_before_failure,
                         "Abandoning job due to too many failed attempts"
                     );
-                    this.job_cancel_counter.add(1, &[]);
+                    this.job_cancel_counter
+                        .add(1, &[KeyValue::new("reason", "max_attempts")]);
                     return this.abandon_aggregation_job(datastore, lease).await;
                 }
 
@@ -902,8 +1239,38 @@ where
                     .await
                 {
                     Ok(_) => Ok(()),
+                    // If the helper is rate limiting requests and says when to try again, wait
+                    // until then, rather than counting this as a failed attempt.
+                    Err(Error::Http(error_response))
+                        if error_response.status() == StatusCode::TOO_MANY_REQUESTS
+                            && error_response.retry_after().is_some() =>
+                    {
+                        // Unwrap safety: checked above.
+                        let retry_after = error_response.retry_after().unwrap();
+                        info!(
+                            ?retry_after,
+                            "Helper is rate limiting requests, delaying aggregation job"
+                        );
+                        this.delay_aggregation_job(datastore, lease, retry_after)
+                            .await
+                    }
                     Err(error) => {
-                        if !Self::is_retryable_error(&error) {
+                        let class = this.retry_classifier.classify(&error);
+                        let failure_domain = class.domain();
+                        this.record_failure_domain(Arc::clone(&datastore), &lease, failure_domain)
+                            .await;
+
+                        if class.is_retryable() {
+                            // Retryable failures, e.g. transient datastore or peer aggregator
+                            // errors, are retried once the lease expires.
+                            warn!(
+                                %attempts,
+                                max_attempts = %maximum_attempts_before_failure,
+                                failure_domain = failure_domain.as_str(),
+                                ?error,
+                                "Job step failed, will retry"
+                            );
+                        } else {
                             // Make a best-effort attempt to immediately cancel the aggregation job.
                             // on fatal errors. This protects the helper from performing wasted
                             // work.
@@ -917,10 +1284,17 @@ where
                             warn!(
                                 %attempts,
                                 max_attempts = %maximum_attempts_before_failure,
+                                failure_domain = failure_domain.as_str(),
                                 ?error,
                                 "Abandoning job due to fatal error"
                             );
-                            this.job_cancel_counter.add(1, &[]);
+                            this.job_cancel_counter.add(
+                                1,
+                                &[
+                                    KeyValue::new("reason", "fatal_error"),
+                                    KeyValue::new("failure_domain", failure_domain.as_str()),
+                                ],
+                            );
                             if let Err(error) = this.abandon_aggregation_job(datastore, lease).await
                             {
                                 error!(error = ?error, "Failed to abandon job");
@@ -932,25 +1306,6 @@ where
             })
         }
     }
-
-    /// Determines whether the given [`Error`] is retryable in the context of aggregation job
-    /// processing.
-    fn is_retryable_error(error: &Error) -> bool {
-        match error {
-            Error::Http(http_error_response) => {
-                is_retryable_http_status(http_error_response.status())
-            }
-            Error::Datastore(error) => match error {
-                datastore::Error::Db(_) | datastore::Error::Pool(_) => true,
-                datastore::Error::User(error) => match error.downcast_ref::<Error>() {
-                    Some(error) => Self::is_retryable_error(error),
-                    None => false,
-                },
-                _ => false,
-            },
-            _ => false,
-        }
-    }
 }
 
 /// SteppedAggregation represents a report aggregation along with the associated preparation-state
@@ -964,7 +1319,9 @@ struct SteppedAggregation<const SEED_SIZE: usize, A: vdaf::Aggregator<SEED_SIZE,
 mod tests {
     use crate::{
         aggregator::{
-            aggregation_job_driver::AggregationJobDriver, test_util::BATCH_AGGREGATION_SHARD_COUNT,
+            aggregation_job_driver::AggregationJobDriver,
+            http_handlers::aggregator_handler,
+            test_util::{default_aggregator_config, BATCH_AGGREGATION_SHARD_COUNT},
             Error,
         },
         binary_utils::job_driver::JobDriver,
@@ -990,7 +1347,10 @@ mod tests {
         hpke::test_util::generate_test_hpke_config_and_private_key,
         report_id::ReportIdChecksumExt,
         retries::test_util::LimitedRetryer,
-        test_util::{install_test_trace_subscriber, run_vdaf, runtime::TestRuntimeManager},
+        test_util::{
+            install_test_trace_subscriber, run_vdaf,
+            runtime::{TestRuntime, TestRuntimeManager},
+        },
         time::{Clock, IntervalExt, MockClock, TimeExt},
         vdaf::{VdafInstance, VERIFY_KEY_LENGTH},
         Runtime,
@@ -1614,7 +1974,7 @@ mod tests {
                         .unwrap()
                         .unwrap();
                     let batch_aggregations = merge_batch_aggregations_by_batch(
-                        tx.get_batch_aggregations_for_task::<VERIFY_KEY_LENGTH, TimeInterval, Prio3Count>(&vdaf, task.id())
+                        tx.get_batch_aggregations_for_task::<VERIFY_KEY_LENGTH, TimeInterval, Prio3Count>(vdaf.as_ref(), task.id())
                             .await
                             .unwrap(),
                     );
@@ -2253,7 +2613,7 @@ mod tests {
                         .await
                         .unwrap();
                     let batch_aggregations = merge_batch_aggregations_by_batch(
-                        tx.get_batch_aggregations_for_task::<VERIFY_KEY_LENGTH, TimeInterval, Prio3Count>(&vdaf, task.id())
+                        tx.get_batch_aggregations_for_task::<VERIFY_KEY_LENGTH, TimeInterval, Prio3Count>(vdaf.as_ref(), task.id())
                             .await
                             .unwrap(),
                     );
@@ -2452,7 +2812,7 @@ mod tests {
             .unwrap_err();
         assert_matches!(
             error,
-            Error::Http(error_response) => {
+            Error::AggregationJobInitUnauthorized(error_response) => {
                 assert_eq!(error_response.status(), StatusCode::INTERNAL_SERVER_ERROR);
                 assert_eq!(*error_response.dap_problem_type().unwrap(), DapProblemType::UnauthorizedRequest);
             }
@@ -3466,6 +3826,7 @@ mod tests {
         aggregation_job: AggregationJob<VERIFY_KEY_LENGTH, TimeInterval, Prio3Count>,
         batch_identifier: Interval,
         report_aggregation: ReportAggregation<VERIFY_KEY_LENGTH, Prio3Count>,
+        clock: MockClock,
         _ephemeral_datastore: EphemeralDatastore,
         datastore: Arc<Datastore<MockClock>>,
         lease: Lease<AcquiredAggregationJob>,
@@ -3582,6 +3943,7 @@ mod tests {
             batch_identifier,
             aggregation_job,
             report_aggregation,
+            clock,
             _ephemeral_datastore: ephemeral_datastore,
             datastore,
             lease,
@@ -3677,7 +4039,7 @@ mod tests {
                         .unwrap()
                         .unwrap();
                     let batch_aggregations = merge_batch_aggregations_by_batch(
-                        tx.get_batch_aggregations_for_task::<VERIFY_KEY_LENGTH, TimeInterval, Prio3Count>(&vdaf, task.id())
+                        tx.get_batch_aggregations_for_task::<VERIFY_KEY_LENGTH, TimeInterval, Prio3Count>(vdaf.as_ref(), task.id())
                             .await
                             .unwrap(),
                     );
@@ -3937,9 +4299,13 @@ mod tests {
                         .unwrap()
                         .unwrap();
                     let got_batch_aggregations = merge_batch_aggregations_by_batch(
-                        tx.get_batch_aggregations_for_task::<VERIFY_KEY_LENGTH, TimeInterval, Prio3Count>(&vdaf, task.id())
-                            .await
-                            .unwrap(),
+                        tx.get_batch_aggregations_for_task::<
+                            VERIFY_KEY_LENGTH,
+                            TimeInterval,
+                            Prio3Count,
+                        >(&vdaf, task.id())
+                        .await
+                        .unwrap(),
                     );
 
                     Ok((got_aggregation_job, got_batch_aggregations))
@@ -3979,6 +4345,182 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn retry_aggregation_job_while_helper_lacks_task() {
+        install_test_trace_subscriber();
+        let clock = MockClock::default();
+        let mut runtime_manager = TestRuntimeManager::new();
+        let helper_ephemeral_datastore = ephemeral_datastore().await;
+        let ephemeral_datastore = ephemeral_datastore().await;
+        let ds = Arc::new(ephemeral_datastore.datastore(clock.clone()).await);
+        let stopper = Stopper::new();
+
+        // Run a Janus helper that has not been provisioned with the task, and so rejects requests
+        // for it as unauthorized.
+        let helper_handler = aggregator_handler(
+            Arc::new(helper_ephemeral_datastore.datastore(clock.clone()).await),
+            clock.clone(),
+            TestRuntime::default(),
+            &noop_meter(),
+            default_aggregator_config(),
+        )
+        .await
+        .unwrap();
+        let helper_server_handle = trillium_tokio::config()
+            .without_signals()
+            .with_host("127.0.0.1")
+            .with_port(0)
+            .spawn(helper_handler);
+        let helper_port = helper_server_handle
+            .info()
+            .await
+            .tcp_socket_addr()
+            .unwrap()
+            .port();
+
+        let task = TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Prio3Count)
+            .with_helper_aggregator_endpoint(
+                format!("http://127.0.0.1:{helper_port}/").parse().unwrap(),
+            )
+            .build();
+
+        let leader_task = task.leader_view().unwrap();
+        let aggregation_job_id = random();
+        let verify_key: VerifyKey<VERIFY_KEY_LENGTH> = task.vdaf_verify_key().unwrap();
+
+        let helper_hpke_keypair = generate_test_hpke_config_and_private_key();
+
+        let vdaf = Prio3::new_count(2).unwrap();
+        let time = clock
+            .now()
+            .to_batch_interval_start(task.time_precision())
+            .unwrap();
+        let report_metadata = ReportMetadata::new(random(), time);
+        let transcript = run_vdaf(
+            &vdaf,
+            verify_key.as_bytes(),
+            &(),
+            report_metadata.id(),
+            &false,
+        );
+        let report = LeaderStoredReport::generate(
+            *task.id(),
+            report_metadata,
+            helper_hpke_keypair.config(),
+            Vec::new(),
+            &transcript,
+        );
+
+        // Set up fixtures in the database.
+        ds.run_unnamed_tx(|tx| {
+            let vdaf = vdaf.clone();
+            let task = leader_task.clone();
+            let report = report.clone();
+            Box::pin(async move {
+                tx.put_aggregator_task(&task).await.unwrap();
+
+                tx.put_client_report(&vdaf, &report).await.unwrap();
+                tx.scrub_client_report(report.task_id(), report.metadata().id())
+                    .await
+                    .unwrap();
+
+                tx.put_aggregation_job(&AggregationJob::<
+                    VERIFY_KEY_LENGTH,
+                    TimeInterval,
+                    Prio3Count,
+                >::new(
+                    *task.id(),
+                    aggregation_job_id,
+                    (),
+                    (),
+                    Interval::new(Time::from_seconds_since_epoch(0), Duration::from_seconds(1))
+                        .unwrap(),
+                    AggregationJobState::InProgress,
+                    AggregationJobStep::from(0),
+                ))
+                .await
+                .unwrap();
+
+                tx.put_report_aggregation(
+                    &report.as_start_leader_report_aggregation(aggregation_job_id, 0),
+                )
+                .await
+                .unwrap();
+
+                Ok(())
+            })
+        })
+        .await
+        .unwrap();
+
+        // Set up the aggregation job driver.
+        let aggregation_job_driver = Arc::new(AggregationJobDriver::new(
+            reqwest::Client::new(),
+            LimitedRetryer::new(0),
+            &noop_meter(),
+            BATCH_AGGREGATION_SHARD_COUNT,
+        ));
+        let job_driver = Arc::new(
+            JobDriver::new(
+                clock.clone(),
+                runtime_manager.with_label("stepper"),
+                noop_meter(),
+                stopper.clone(),
+                StdDuration::from_secs(1),
+                10,
+                StdDuration::from_secs(60),
+                aggregation_job_driver.make_incomplete_job_acquirer_callback(
+                    Arc::clone(&ds),
+                    StdDuration::from_secs(600),
+                ),
+                aggregation_job_driver.make_job_stepper_callback(Arc::clone(&ds), 3),
+            )
+            .unwrap(),
+        );
+
+        // Start up the job driver.
+        let task_handle = runtime_manager.with_label("driver").spawn(job_driver.run());
+
+        // Run the job driver until we try to step the aggregation job four times. The helper
+        // rejects the first three attempts as unauthorized, which must not abandon the job, while
+        // the fourth attempt abandons the job because it has run out of attempts.
+        for i in 1..=4 {
+            // Wait for the next task to be spawned and to complete.
+            runtime_manager.wait_for_completed_tasks("stepper", i).await;
+
+            let got_aggregation_job_state = ds
+                .run_unnamed_tx(|tx| {
+                    let task_id = *task.id();
+                    Box::pin(async move {
+                        Ok(*tx
+                            .get_aggregation_job::<VERIFY_KEY_LENGTH, TimeInterval, Prio3Count>(
+                                &task_id,
+                                &aggregation_job_id,
+                            )
+                            .await
+                            .unwrap()
+                            .unwrap()
+                            .state())
+                    })
+                })
+                .await
+                .unwrap();
+            let want_aggregation_job_state = if i < 4 {
+                AggregationJobState::InProgress
+            } else {
+                AggregationJobState::Abandoned
+            };
+            assert_eq!(got_aggregation_job_state, want_aggregation_job_state, "{i}");
+
+            // Advance the clock by the lease duration, so that the job driver can pick up the job
+            // and try again.
+            clock.advance(&Duration::from_seconds(600));
+        }
+        stopper.stop();
+        task_handle.await.unwrap();
+        helper_server_handle.stop().await;
+    }
+
     #[tokio::test]
     async fn abandon_failing_aggregation_job_with_fatal_error() {
         install_test_trace_subscriber();
@@ -4164,7 +4706,7 @@ mod tests {
         assert!(!no_more_requests_mock.matched_async().await);
 
         // Confirm in the database that the job was abandoned.
-        let (got_aggregation_job, got_batch_aggregations) = ds
+        let (got_aggregation_job, got_batch_aggregations, got_last_failure_domain) = ds
             .run_unnamed_tx(|tx| {
                 let vdaf = vdaf.clone();
                 let task = task.clone();
@@ -4176,12 +4718,25 @@ mod tests {
                         .unwrap()
                         .unwrap();
                     let got_batch_aggregations = merge_batch_aggregations_by_batch(
-                        tx.get_batch_aggregations_for_task::<VERIFY_KEY_LENGTH, TimeInterval, Prio3Count>(&vdaf, task.id())
-                            .await
-                            .unwrap(),
+                        tx.get_batch_aggregations_for_task::<
+                            VERIFY_KEY_LENGTH,
+                            TimeInterval,
+                            Prio3Count,
+                        >(&vdaf, task.id())
+                        .await
+                        .unwrap(),
                     );
 
-                    Ok((got_aggregation_job, got_batch_aggregations))
+                    let got_last_failure_domain = tx
+                        .get_aggregation_job_last_failure_domain(task.id(), &aggregation_job_id)
+                        .await
+                        .unwrap();
+
+                    Ok((
+                        got_aggregation_job,
+                        got_batch_aggregations,
+                        got_last_failure_domain,
+                    ))
                 })
             })
             .await
@@ -4216,5 +4771,277 @@ mod tests {
                 },
             )]),
         );
+        assert_eq!(got_last_failure_domain.as_deref(), Some("peer"));
+    }
+
+    #[tokio::test]
+    async fn split_aggregation_job_too_large_for_helper() {
+        // Setup: insert two client reports and add them to a new aggregation job.
+        install_test_trace_subscriber();
+        let mut server = mockito::Server::new_async().await;
+        let clock = MockClock::default();
+        let ephemeral_datastore = ephemeral_datastore().await;
+        let ds = Arc::new(ephemeral_datastore.datastore(clock.clone()).await);
+        let vdaf = Arc::new(Prio3::new_count(2).unwrap());
+
+        let task = TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Prio3Count)
+            .with_helper_aggregator_endpoint(server.url().parse().unwrap())
+            .build()
+            .leader_view()
+            .unwrap();
+        let time = clock
+            .now()
+            .to_batch_interval_start(task.time_precision())
+            .unwrap();
+        let batch_identifier = TimeInterval::to_batch_identifier(&task, &(), &time).unwrap();
+        let verify_key: VerifyKey<VERIFY_KEY_LENGTH> = task.vdaf_verify_key().unwrap();
+        let helper_hpke_keypair = generate_test_hpke_config_and_private_key();
+        let reports: Vec<_> = (0..2)
+            .map(|_| {
+                let report_metadata = ReportMetadata::new(random(), time);
+                let transcript = run_vdaf(
+                    vdaf.as_ref(),
+                    verify_key.as_bytes(),
+                    &(),
+                    report_metadata.id(),
+                    &false,
+                );
+                LeaderStoredReport::generate(
+                    *task.id(),
+                    report_metadata,
+                    helper_hpke_keypair.config(),
+                    Vec::new(),
+                    &transcript,
+                )
+            })
+            .collect();
+        let aggregation_job_id = random();
+
+        let lease = ds
+            .run_unnamed_tx(|tx| {
+                let (vdaf, task, reports) = (Arc::clone(&vdaf), task.clone(), reports.clone());
+                Box::pin(async move {
+                    tx.put_aggregator_task(&task).await.unwrap();
+                    tx.put_aggregation_job(&AggregationJob::<
+                        VERIFY_KEY_LENGTH,
+                        TimeInterval,
+                        Prio3Count,
+                    >::new(
+                        *task.id(),
+                        aggregation_job_id,
+                        (),
+                        (),
+                        Interval::from_time(&time).unwrap(),
+                        AggregationJobState::InProgress,
+                        AggregationJobStep::from(0),
+                    ))
+                    .await
+                    .unwrap();
+                    for (ord, report) in reports.iter().enumerate() {
+                        tx.put_client_report(vdaf.borrow(), report).await.unwrap();
+                        tx.scrub_client_report(report.task_id(), report.metadata().id())
+                            .await
+                            .unwrap();
+                        tx.put_report_aggregation(&report.as_start_leader_report_aggregation(
+                            aggregation_job_id,
+                            ord.try_into().unwrap(),
+                        ))
+                        .await
+                        .unwrap();
+                    }
+                    tx.put_batch_aggregation(&BatchAggregation::<
+                        VERIFY_KEY_LENGTH,
+                        TimeInterval,
+                        Prio3Count,
+                    >::new(
+                        *task.id(),
+                        batch_identifier,
+                        (),
+                        0,
+                        Interval::from_time(&time).unwrap(),
+                        BatchAggregationState::Aggregating {
+                            aggregate_share: None,
+                            report_count: 0,
+                            checksum: ReportIdChecksum::default(),
+                            aggregation_jobs_created: 1,
+                            aggregation_jobs_terminated: 0,
+                        },
+                    ))
+                    .await
+                    .unwrap();
+
+                    Ok(tx
+                        .acquire_incomplete_aggregation_jobs(&StdDuration::from_secs(60), 1)
+                        .await
+                        .unwrap()
+                        .remove(0))
+                })
+            })
+            .await
+            .unwrap();
+
+        // Setup: the helper rejects the aggregation job as too large.
+        let mocked_aggregate_failure = server
+            .mock(
+                "PUT",
+                task.aggregation_job_uri(&aggregation_job_id)
+                    .unwrap()
+                    .unwrap()
+                    .path(),
+            )
+            .with_status(413)
+            .expect(1)
+            .create_async()
+            .await;
+
+        // Run: step the aggregation job.
+        let aggregation_job_driver = AggregationJobDriver::new(
+            reqwest::Client::new(),
+            LimitedRetryer::new(0),
+            &noop_meter(),
+            BATCH_AGGREGATION_SHARD_COUNT,
+        );
+        aggregation_job_driver
+            .step_aggregation_job(Arc::clone(&ds), Arc::new(lease))
+            .await
+            .unwrap();
+
+        mocked_aggregate_failure.assert_async().await;
+
+        // Verify: the original aggregation job is abandoned, and each of its reports is in a new
+        // aggregation job of its own.
+        let (aggregation_jobs, report_aggregations, batch_aggregations) = ds
+            .run_unnamed_tx(|tx| {
+                let (vdaf, task) = (Arc::clone(&vdaf), task.clone());
+                Box::pin(async move {
+                    let aggregation_jobs = tx
+                        .get_aggregation_jobs_for_task::<VERIFY_KEY_LENGTH, TimeInterval, Prio3Count>(
+                            task.id(),
+                        )
+                        .await
+                        .unwrap();
+                    let report_aggregations = tx
+                        .get_report_aggregations_for_task(vdaf.as_ref(), &Role::Leader, task.id())
+                        .await
+                        .unwrap();
+                    let batch_aggregations = merge_batch_aggregations_by_batch(
+                        tx.get_batch_aggregations_for_task::<VERIFY_KEY_LENGTH, TimeInterval, Prio3Count>(vdaf.as_ref(), task.id())
+                            .await
+                            .unwrap(),
+                    );
+                    Ok((aggregation_jobs, report_aggregations, batch_aggregations))
+                })
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(aggregation_jobs.len(), 3);
+        for aggregation_job in &aggregation_jobs {
+            let job_report_ids: Vec<_> = report_aggregations
+                .iter()
+                .filter(|report_aggregation| {
+                    report_aggregation.aggregation_job_id() == aggregation_job.id()
+                })
+                .map(|report_aggregation| {
+                    assert_matches!(
+                        report_aggregation.state(),
+                        ReportAggregationState::StartLeader { .. }
+                    );
+                    *report_aggregation.report_id()
+                })
+                .collect();
+            if aggregation_job.id() == &aggregation_job_id {
+                assert_eq!(aggregation_job.state(), &AggregationJobState::Abandoned);
+                assert_eq!(job_report_ids.len(), 2);
+            } else {
+                assert_eq!(aggregation_job.state(), &AggregationJobState::InProgress);
+                assert_eq!(aggregation_job.step(), AggregationJobStep::from(0));
+                assert_eq!(job_report_ids.len(), 1);
+            }
+        }
+
+        assert_eq!(batch_aggregations.len(), 1);
+        assert_matches!(
+            batch_aggregations[0].state(),
+            BatchAggregationState::Aggregating {
+                aggregation_jobs_created: 3,
+                aggregation_jobs_terminated: 1,
+                ..
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn delay_aggregation_job_when_helper_rate_limits() {
+        let mut test_case = setup_cancel_aggregation_job_test().await;
+
+        // Setup: the helper is rate limiting requests.
+        let mocked_aggregate_failure = test_case
+            .mock_helper
+            .mock(
+                "PUT",
+                test_case
+                    .task
+                    .aggregation_job_uri(test_case.aggregation_job.id())
+                    .unwrap()
+                    .unwrap()
+                    .path(),
+            )
+            .with_status(429)
+            .with_header("retry-after", "120")
+            .expect(1)
+            .create_async()
+            .await;
+
+        // Run: step the aggregation job.
+        let aggregation_job_driver = Arc::new(AggregationJobDriver::new(
+            reqwest::Client::new(),
+            LimitedRetryer::new(0),
+            &noop_meter(),
+            BATCH_AGGREGATION_SHARD_COUNT,
+        ));
+        let stepper =
+            aggregation_job_driver.make_job_stepper_callback(Arc::clone(&test_case.datastore), 3);
+        stepper(test_case.lease).await.unwrap();
+
+        mocked_aggregate_failure.assert_async().await;
+
+        // Verify: the aggregation job is untouched, and can't be acquired again until the helper's
+        // requested delay has passed.
+        let acquire = |datastore: Arc<Datastore<MockClock>>| async move {
+            datastore
+                .run_unnamed_tx(|tx| {
+                    Box::pin(async move {
+                        tx.acquire_incomplete_aggregation_jobs(&StdDuration::from_secs(60), 1)
+                            .await
+                    })
+                })
+                .await
+                .unwrap()
+        };
+        let got_aggregation_job = test_case
+            .datastore
+            .run_unnamed_tx(|tx| {
+                let (task, aggregation_job_id) =
+                    (test_case.task.clone(), *test_case.aggregation_job.id());
+                Box::pin(async move {
+                    tx.get_aggregation_job::<VERIFY_KEY_LENGTH, TimeInterval, Prio3Count>(
+                        task.id(),
+                        &aggregation_job_id,
+                    )
+                    .await
+                })
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(got_aggregation_job, test_case.aggregation_job);
+        assert!(acquire(Arc::clone(&test_case.datastore)).await.is_empty());
+
+        test_case.clock.advance(&Duration::from_seconds(120));
+        let leases = acquire(Arc::clone(&test_case.datastore)).await;
+        assert_eq!(leases.len(), 1);
+        // The delay did not count as a failed attempt.
+        assert_eq!(leases[0].lease_attempts(), 1);
     }
 }
@@ -320,8 +320,17 @@ impl WriteType for InitialWrite {
         // foreign-key constraint on the related aggregation job existing. We could
         // speed things up for initial writes by switching to DEFERRED constraints:
         // https://www.postgresql.org/docs/current/sql-set-constraints.html
-        tx.put_aggregation_job(&aggregation_job_info.aggregation_job)
-            .await?;
+        let rslt = tx
+            .put_aggregation_job(&aggregation_job_info.aggregation_job)
+            .await;
+        if matches!(rslt, Err(Error::MutationTargetAlreadyExists)) {
+            // If we generated this aggregation job's ID, it collided with an existing aggregation
+            // job; retrying the transaction generates a fresh ID. If the leader chose the ID, then
+            // a concurrent request created the same aggregation job, which the retried transaction
+            // will find.
+            tx.retry();
+        }
+        rslt?;
         try_join_all(
             aggregation_job_info
                 .report_aggregations
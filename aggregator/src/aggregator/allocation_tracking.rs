@@ -0,0 +1,236 @@
+//! Attribution of heap allocations to the helper's handling of aggregate requests.
+//!
+//! With the `allocation-tracking` feature enabled, the aggregator binaries install
+//! [`TrackingAllocator`] as their global allocator, which counts the allocations made while a
+//! tracked future is being polled. The number and total size of the allocations made while handling
+//! each aggregate request are then recorded. Allocations made by other tasks on the request's
+//! behalf, such as database connection tasks, are not attributed to it. Without the feature,
+//! tracking is a no-op.
+
+use opentelemetry::metrics::Meter;
+use std::future::Future;
+
+#[cfg(feature = "allocation-tracking")]
+use {
+    opentelemetry::{
+        metrics::{Histogram, Unit},
+        KeyValue,
+    },
+    std::{
+        alloc::{GlobalAlloc, Layout, System},
+        cell::Cell,
+        pin::Pin,
+        ptr,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc,
+        },
+        task::{Context, Poll},
+    },
+};
+
+// Library code must not install a global allocator, as it would conflict with any installed by the
+// binary linking it. Only this crate's own unit tests install it here.
+#[cfg(all(test, feature = "allocation-tracking"))]
+#[global_allocator]
+static ALLOCATOR: TrackingAllocator = TrackingAllocator;
+
+/// Metrics on the allocations made while handling aggregate requests.
+#[derive(Debug)]
+pub(crate) struct AllocationMetrics {
+    #[cfg(feature = "allocation-tracking")]
+    allocations_histogram: Histogram<u64>,
+    #[cfg(feature = "allocation-tracking")]
+    allocated_bytes_histogram: Histogram<u64>,
+}
+
+impl AllocationMetrics {
+    #[cfg_attr(not(feature = "allocation-tracking"), allow(unused_variables))]
+    pub(crate) fn new(meter: &Meter) -> Self {
+        Self {
+            #[cfg(feature = "allocation-tracking")]
+            allocations_histogram: meter
+                .u64_histogram("janus_aggregate_request_allocations")
+                .with_description(
+                    "Number of heap allocations made while handling each aggregate request in \
+                     the helper.",
+                )
+                .with_unit(Unit::new("{allocation}"))
+                .init(),
+            #[cfg(feature = "allocation-tracking")]
+            allocated_bytes_histogram: meter
+                .u64_histogram("janus_aggregate_request_allocated_bytes")
+                .with_description(
+                    "Total size of the heap allocations made while handling each aggregate \
+                     request in the helper.",
+                )
+                .with_unit(Unit::new("By"))
+                .init(),
+        }
+    }
+
+    /// Runs `future` to completion, recording the allocations made while it is polled, labeled
+    /// with the aggregation step being handled.
+    #[cfg(feature = "allocation-tracking")]
+    pub(crate) async fn track<F: Future>(&self, step: &'static str, future: F) -> F::Output {
+        let counters = Arc::new(AllocationCounters::default());
+        let output = Tracked {
+            future: Box::pin(future),
+            counters: Arc::clone(&counters),
+        }
+        .await;
+
+        let attributes = [KeyValue::new("step", step)];
+        self.allocations_histogram
+            .record(counters.allocations.load(Ordering::Relaxed), &attributes);
+        self.allocated_bytes_histogram
+            .record(counters.bytes.load(Ordering::Relaxed), &attributes);
+        output
+    }
+
+    /// Runs `future` to completion.
+    #[cfg(not(feature = "allocation-tracking"))]
+    pub(crate) async fn track<F: Future>(&self, _step: &'static str, future: F) -> F::Output {
+        future.await
+    }
+}
+
+/// Running totals of the allocations attributed to a tracked future.
+#[cfg(feature = "allocation-tracking")]
+#[derive(Debug, Default)]
+struct AllocationCounters {
+    allocations: AtomicU64,
+    bytes: AtomicU64,
+}
+
+#[cfg(feature = "allocation-tracking")]
+thread_local! {
+    /// The counters of the tracked future currently being polled on this thread, if any. This is
+    /// a raw pointer, rather than an `Arc`, so that the allocator never allocates or frees memory
+    /// itself; it is only set while [`Tracked::poll`] is on the stack.
+    static CURRENT_COUNTERS: Cell<*const AllocationCounters> = const { Cell::new(ptr::null()) };
+}
+
+/// Wraps a future, attributing allocations made while it is polled to its counters.
+#[cfg(feature = "allocation-tracking")]
+struct Tracked<F> {
+    future: Pin<Box<F>>,
+    counters: Arc<AllocationCounters>,
+}
+
+#[cfg(feature = "allocation-tracking")]
+impl<F: Future> Future for Tracked<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let _guard = CurrentCountersGuard::enter(&this.counters);
+        this.future.as_mut().poll(cx)
+    }
+}
+
+/// Sets the current thread's counters, restoring the previous ones when dropped, even if the
+/// tracked future panics.
+#[cfg(feature = "allocation-tracking")]
+struct CurrentCountersGuard {
+    previous: *const AllocationCounters,
+}
+
+#[cfg(feature = "allocation-tracking")]
+impl CurrentCountersGuard {
+    fn enter(counters: &Arc<AllocationCounters>) -> Self {
+        Self {
+            previous: CURRENT_COUNTERS.with(|current| current.replace(Arc::as_ptr(counters))),
+        }
+    }
+}
+
+#[cfg(feature = "allocation-tracking")]
+impl Drop for CurrentCountersGuard {
+    fn drop(&mut self) {
+        CURRENT_COUNTERS.with(|current| current.set(self.previous));
+    }
+}
+
+/// A global allocator which delegates to the system allocator, counting allocations made while a
+/// tracked future is being polled. Binaries must install it with `#[global_allocator]` for
+/// allocations to be counted.
+#[cfg(feature = "allocation-tracking")]
+pub struct TrackingAllocator;
+
+#[cfg(feature = "allocation-tracking")]
+impl TrackingAllocator {
+    fn count(size: usize) {
+        // `try_with` fails only while the thread is being torn down, when nothing is tracked.
+        let _ = CURRENT_COUNTERS.try_with(|current| {
+            // Safety: the pointer is only set while `Tracked::poll`, which holds a reference to
+            // the counters, is on this thread's stack.
+            if let Some(counters) = unsafe { current.get().as_ref() } {
+                counters.allocations.fetch_add(1, Ordering::Relaxed);
+                counters
+                    .bytes
+                    .fetch_add(u64::try_from(size).unwrap_or(u64::MAX), Ordering::Relaxed);
+            }
+        });
+    }
+}
+
+#[cfg(feature = "allocation-tracking")]
+unsafe impl GlobalAlloc for TrackingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        Self::count(layout.size());
+        System.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        Self::count(layout.size());
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        Self::count(new_size);
+        System.realloc(ptr, layout, new_size)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[cfg(all(test, feature = "allocation-tracking"))]
+mod tests {
+    use crate::aggregator::allocation_tracking::{AllocationCounters, Tracked};
+    use std::{hint::black_box, sync::atomic::Ordering, sync::Arc};
+
+    #[tokio::test]
+    async fn counts_allocations_while_polled() {
+        let counters = Arc::new(AllocationCounters::default());
+        let (vec, nested_counters) = Tracked {
+            future: Box::pin(async {
+                let vec = black_box(Vec::<u8>::with_capacity(4096));
+                tokio::task::yield_now().await;
+
+                // Allocations made by a nested tracked future are attributed only to it.
+                let nested_counters = Arc::new(AllocationCounters::default());
+                Tracked {
+                    future: Box::pin(async { black_box(Vec::<u8>::with_capacity(1024)) }),
+                    counters: Arc::clone(&nested_counters),
+                }
+                .await;
+                (vec, nested_counters)
+            }),
+            counters: Arc::clone(&counters),
+        }
+        .await;
+        assert_eq!(vec.capacity(), 4096);
+
+        // Allocations made outside of a tracked future are not counted.
+        drop(black_box(Vec::<u8>::with_capacity(8192)));
+
+        assert!(counters.allocations.load(Ordering::Relaxed) >= 1);
+        assert!(counters.bytes.load(Ordering::Relaxed) >= 4096);
+        assert!(counters.bytes.load(Ordering::Relaxed) < 4096 + 1024);
+        assert_eq!(nested_counters.allocations.load(Ordering::Relaxed), 1);
+        assert_eq!(nested_counters.bytes.load(Ordering::Relaxed), 1024);
+    }
+}
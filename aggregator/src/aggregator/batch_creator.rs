@@ -9,13 +9,15 @@ use janus_aggregator_core::datastore::{
     },
     Error, Transaction,
 };
-use janus_core::time::{Clock, DurationExt, TimeExt};
+use janus_core::{
+    ids::{generate_aggregation_job_id, generate_batch_id},
+    time::{Clock, DurationExt, TimeExt},
+};
 use janus_messages::{
     query_type::FixedSize, AggregationJobStep, BatchId, Duration, Interval, ReportId,
     ReportMetadata, TaskId, Time,
 };
 use prio::{codec::Encode, vdaf::Aggregator};
-use rand::random;
 use std::{
     cmp::{max, min, Ordering},
     collections::{binary_heap::PeekMut, hash_map, BinaryHeap, HashMap, HashSet, VecDeque},
@@ -267,7 +269,7 @@ where
                 properties.effective_task_max_batch_size,
             );
             if desired_aggregation_job_size >= new_batch_threshold {
-                let batch_id = random();
+                let batch_id = generate_batch_id();
                 new_batches.push((batch_id, *time_bucket_start));
                 let outstanding_batch = OutstandingBatch::new(
                     properties.task_id,
@@ -311,7 +313,7 @@ where
         >,
         report_ids_to_scrub: &mut HashSet<ReportId>,
     ) -> Result<(), Error> {
-        let aggregation_job_id = random();
+        let aggregation_job_id = generate_aggregation_job_id();
         debug!(
             task_id = %task_id,
             %batch_id,
@@ -409,11 +411,21 @@ where
             try_join_all(
                 self.new_batches
                     .iter()
-                    .map(|(batch_id, time_bucket_start)| tx.put_outstanding_batch(
-                        &self.properties.task_id,
-                        batch_id,
-                        time_bucket_start,
-                    ))
+                    .map(|(batch_id, time_bucket_start)| async move {
+                        let rslt = tx
+                            .put_outstanding_batch(
+                                &self.properties.task_id,
+                                batch_id,
+                                time_bucket_start,
+                            )
+                            .await;
+                        if matches!(rslt, Err(Error::MutationTargetAlreadyExists)) {
+                            // The generated batch ID collided with an existing batch. Retrying the
+                            // transaction generates a fresh ID.
+                            tx.retry();
+                        }
+                        rslt
+                    })
             ),
             try_join_all(
                 unaggregated_report_ids
@@ -0,0 +1,310 @@
+//! A synthetic canary, which continuously exercises a dedicated task end to end, giving operators
+//! a black-box signal of whether the deployment is healthy.
+//!
+//! Each run of the canary uploads a batch of synthetic reports to the canary task's leader (this
+//! aggregator) and helper, waits for the batch interval containing them to end, then collects that
+//! interval as the task's collector would, and checks that every report was counted. The outcome
+//! and latency of each run are exported as metrics.
+//!
+//! The canary task must be provisioned in both aggregators like any other task, as a time interval
+//! task using Prio3Count, in which this aggregator is the leader. Its time precision bounds how
+//! long each run takes, so it should be short.
+
+use anyhow::{anyhow, Context, Result};
+use backoff::ExponentialBackoff;
+use janus_aggregator_core::{
+    datastore::Datastore,
+    task::{AggregatorTask, QueryType},
+};
+use janus_client::Client;
+use janus_collector::{Collection, Collector};
+use janus_core::{
+    auth_tokens::AuthenticationToken,
+    hpke::HpkeKeypair,
+    time::{Clock, IntervalExt, TimeExt},
+    vdaf::VdafInstance,
+};
+use janus_messages::{query_type::TimeInterval, Interval, Query, Role, TaskId, Time};
+use opentelemetry::{
+    metrics::{Counter, Histogram, Meter, Unit},
+    KeyValue,
+};
+use prio::vdaf::prio3::{Prio3, Prio3Count};
+use std::{
+    sync::Arc,
+    time::{Duration as StdDuration, Instant},
+};
+use tokio::time::sleep;
+use tracing::{info, warn};
+use url::Url;
+
+pub struct Canary<C: Clock> {
+    // Dependencies.
+    datastore: Arc<Datastore<C>>,
+    clock: C,
+    http_client: reqwest::Client,
+
+    // Configuration.
+    task_id: TaskId,
+    leader_endpoint: Url,
+    collector_auth_token: AuthenticationToken,
+    collector_hpke_keypair: HpkeKeypair,
+    reports_per_run: u64,
+    collection_timeout: StdDuration,
+
+    // Metrics.
+    run_counter: Counter<u64>,
+    duration_histogram: Histogram<f64>,
+}
+
+impl<C: Clock> Canary<C> {
+    /// Creates a canary for the task with the given ID, which is served by this aggregator at
+    /// `leader_endpoint`. Each run uploads at least `reports_per_run` reports, and gives up on
+    /// collecting them after `collection_timeout`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        datastore: Arc<Datastore<C>>,
+        clock: C,
+        meter: &Meter,
+        task_id: TaskId,
+        leader_endpoint: Url,
+        collector_auth_token: AuthenticationToken,
+        collector_hpke_keypair: HpkeKeypair,
+        reports_per_run: u64,
+        collection_timeout: StdDuration,
+    ) -> Result<Self> {
+        let http_client = janus_client::default_http_client().context("couldn't create client")?;
+
+        let run_counter = meter
+            .u64_counter("janus_canary_runs")
+            .with_description(
+                "Number of runs of the synthetic canary, by result: success, upload_failed, \
+                 collection_failed, or incorrect_result.",
+            )
+            .with_unit(Unit::new("{run}"))
+            .init();
+        let duration_histogram = meter
+            .f64_histogram("janus_canary_duration")
+            .with_description(
+                "The amount of time elapsed in each stage of a successful canary run: uploading \
+                 reports, or collecting them.",
+            )
+            .with_unit(Unit::new("s"))
+            .init();
+
+        Ok(Self {
+            datastore,
+            clock,
+            http_client,
+            task_id,
+            leader_endpoint,
+            collector_auth_token,
+            collector_hpke_keypair,
+            reports_per_run,
+            collection_timeout,
+            run_counter,
+            duration_histogram,
+        })
+    }
+
+    /// Runs the canary once, uploading a batch of reports, then collecting it. This takes at least
+    /// until the end of the current batch interval of the canary task.
+    pub async fn run(&self) -> Result<()> {
+        let task_id = self.task_id;
+        let task = self
+            .datastore
+            .run_tx("canary_get_task", |tx| {
+                Box::pin(async move { tx.get_aggregator_task(&task_id).await })
+            })
+            .await
+            .context("couldn't get canary task")?
+            .ok_or_else(|| anyhow!("canary task {task_id} not found"))?;
+        check_task(&task)?;
+
+        let report_count = self.reports_per_run.max(task.min_batch_size());
+        let time_precision = *task.time_precision();
+        let batch_interval = Interval::new(
+            self.clock.now().to_batch_interval_start(&time_precision)?,
+            time_precision,
+        )?;
+        let vdaf = Prio3::new_count(2)?;
+
+        let upload_start = Instant::now();
+        if let Err(err) = self
+            .upload(&task, vdaf.clone(), batch_interval.start(), report_count)
+            .await
+        {
+            self.record_result("upload_failed");
+            return Err(err.context("couldn't upload canary reports"));
+        }
+        self.record_duration("upload", upload_start.elapsed());
+
+        // Reports in the batch interval can't be collected until it ends.
+        let batch_interval_end = batch_interval.end();
+        let now = self.clock.now();
+        if batch_interval_end.is_after(&now) {
+            let wait = batch_interval_end.difference(&now)?;
+            sleep(StdDuration::from_secs(wait.as_seconds())).await;
+        }
+
+        let collection_start = Instant::now();
+        let collection = match self.collect(vdaf, batch_interval).await {
+            Ok(collection) => collection,
+            Err(err) => {
+                self.record_result("collection_failed");
+                return Err(err.context("couldn't collect canary reports"));
+            }
+        };
+        if collection.report_count() != report_count
+            || *collection.aggregate_result() != report_count
+        {
+            self.record_result("incorrect_result");
+            return Err(anyhow!(
+                "canary collected {} reports with aggregate result {}, but {report_count} were \
+                 uploaded",
+                collection.report_count(),
+                collection.aggregate_result(),
+            ));
+        }
+        self.record_duration("collection", collection_start.elapsed());
+        self.record_result("success");
+        info!(%task_id, report_count, ?batch_interval, "Canary run succeeded");
+        Ok(())
+    }
+
+    async fn upload(
+        &self,
+        task: &AggregatorTask,
+        vdaf: Prio3Count,
+        time: &Time,
+        report_count: u64,
+    ) -> Result<()> {
+        let client = Client::builder(
+            self.task_id,
+            self.leader_endpoint.clone(),
+            task.peer_aggregator_endpoint().clone(),
+            *task.time_precision(),
+            vdaf,
+        )
+        .with_http_client(self.http_client.clone())
+        .build()
+        .await?;
+        for _ in 0..report_count {
+            client.upload_with_time(&true, *time).await?;
+        }
+        Ok(())
+    }
+
+    async fn collect(
+        &self,
+        vdaf: Prio3Count,
+        batch_interval: Interval,
+    ) -> Result<Collection<u64, TimeInterval>> {
+        let collector = Collector::builder(
+            self.task_id,
+            self.leader_endpoint.clone(),
+            self.collector_auth_token.clone(),
+            self.collector_hpke_keypair.clone(),
+            vdaf,
+        )
+        .with_http_client(self.http_client.clone())
+        .with_collect_poll_backoff(ExponentialBackoff {
+            initial_interval: StdDuration::from_secs(5),
+            max_interval: StdDuration::from_secs(30),
+            multiplier: 1.2,
+            max_elapsed_time: Some(self.collection_timeout),
+            ..Default::default()
+        })
+        .build()?;
+        Ok(collector
+            .collect(Query::new_time_interval(batch_interval), &())
+            .await?)
+    }
+
+    fn record_result(&self, result: &'static str) {
+        if result != "success" {
+            warn!(task_id = %self.task_id, result, "Canary run failed");
+        }
+        self.run_counter.add(1, &[KeyValue::new("result", result)]);
+    }
+
+    fn record_duration(&self, stage: &'static str, elapsed: StdDuration) {
+        self.duration_histogram
+            .record(elapsed.as_secs_f64(), &[KeyValue::new("stage", stage)]);
+    }
+}
+
+/// Checks that the canary task is one the canary can exercise.
+fn check_task(task: &AggregatorTask) -> Result<()> {
+    if task.role() != &Role::Leader {
+        return Err(anyhow!("this aggregator is not the canary task's leader"));
+    }
+    if task.query_type() != &QueryType::TimeInterval {
+        return Err(anyhow!("canary task must use the time interval query type"));
+    }
+    if task.vdaf() != &VdafInstance::Prio3Count {
+        return Err(anyhow!("canary task must use Prio3Count"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::aggregator::canary::Canary;
+    use janus_aggregator_core::{
+        datastore::test_util::ephemeral_datastore,
+        task::{test_util::TaskBuilder, QueryType},
+        test_util::noop_meter,
+    };
+    use janus_core::{
+        hpke::test_util::generate_test_hpke_config_and_private_key,
+        test_util::install_test_trace_subscriber, time::MockClock, vdaf::VdafInstance,
+    };
+    use rand::random;
+    use std::{sync::Arc, time::Duration as StdDuration};
+
+    #[tokio::test]
+    async fn rejects_unsupported_tasks() {
+        install_test_trace_subscriber();
+
+        let clock = MockClock::default();
+        let ephemeral_datastore = ephemeral_datastore().await;
+        let ds = Arc::new(ephemeral_datastore.datastore(clock.clone()).await);
+
+        let helper_task = TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Prio3Count)
+            .build()
+            .helper_view()
+            .unwrap();
+        let sum_task =
+            TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Prio3Sum { bits: 8 })
+                .build()
+                .leader_view()
+                .unwrap();
+        for task in [&helper_task, &sum_task] {
+            ds.put_aggregator_task(task).await.unwrap();
+        }
+
+        let canary = |task_id| {
+            Canary::new(
+                Arc::clone(&ds),
+                clock.clone(),
+                &noop_meter(),
+                task_id,
+                "https://leader.example.com/".parse().unwrap(),
+                random(),
+                generate_test_hpke_config_and_private_key(),
+                1,
+                StdDuration::from_secs(60),
+            )
+            .unwrap()
+        };
+
+        // Runs fail without uploading anything if the task doesn't exist or isn't supported.
+        let err = canary(random()).run().await.unwrap_err();
+        assert!(err.to_string().contains("not found"), "{err}");
+        let err = canary(*helper_task.id()).run().await.unwrap_err();
+        assert!(err.to_string().contains("leader"), "{err}");
+        let err = canary(*sum_task.id()).run().await.unwrap_err();
+        assert!(err.to_string().contains("Prio3Count"), "{err}");
+    }
+}
@@ -0,0 +1,174 @@
+//! Metrics describing the client software that uploads reports.
+//!
+//! Clients identify themselves in the `User-Agent` header, conventionally starting with a
+//! `product/version` token, e.g. `janus_client/0.7.0/client` or `divviup-ts/0.1.0`. Uploads are
+//! counted per task, client product, and version, so that operators can follow the progress of
+//! client fleet upgrades, and correlate upload errors with particular client versions. Nothing is
+//! stored per report.
+
+use janus_messages::TaskId;
+use opentelemetry::{
+    metrics::{Counter, Meter, Unit},
+    KeyValue,
+};
+use std::{collections::HashSet, sync::Mutex};
+
+/// The maximum number of distinct client product and version pairs that are reported. Uploads from
+/// any further clients are counted under [`ClientTelemetry::OTHER`], to bound the cardinality of
+/// the metric, since the header is chosen by clients.
+const MAX_DISTINCT_CLIENTS: usize = 64;
+
+/// The maximum length of a reported label value. Longer values are truncated.
+const MAX_LABEL_LENGTH: usize = 32;
+
+#[derive(Debug)]
+pub(crate) struct ClientTelemetry {
+    upload_counter: Counter<u64>,
+    seen_clients: Mutex<HashSet<(String, String)>>,
+}
+
+impl ClientTelemetry {
+    /// Label used for clients that do not send a recognizable `User-Agent` header.
+    const UNKNOWN: &'static str = "unknown";
+
+    /// Label used for clients beyond the first [`MAX_DISTINCT_CLIENTS`].
+    const OTHER: &'static str = "other";
+
+    pub(crate) fn new(meter: &Meter) -> Self {
+        let upload_counter = meter
+            .u64_counter("janus_upload_clients")
+            .with_description(
+                "Number of uploads to the tasks/{task-id}/reports endpoint, by task and by the \
+                 client product and version given in the User-Agent header.",
+            )
+            .with_unit(Unit::new("{report}"))
+            .init();
+
+        Self {
+            upload_counter,
+            seen_clients: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Counts an upload for the given task from the client identified by `user_agent`.
+    /// `succeeded` indicates whether the upload was accepted.
+    pub(crate) fn record_upload(
+        &self,
+        task_id: &TaskId,
+        user_agent: Option<&str>,
+        succeeded: bool,
+    ) {
+        let (client, version) = self.admit(parse_user_agent(user_agent));
+        self.upload_counter.add(
+            1,
+            &[
+                KeyValue::new("task_id", task_id.to_string()),
+                KeyValue::new("client", client),
+                KeyValue::new("version", version),
+                KeyValue::new("result", if succeeded { "success" } else { "error" }),
+            ],
+        );
+    }
+
+    /// Returns the given client product and version if they have been seen before, or if there
+    /// is room to report another client, and [`Self::OTHER`] otherwise.
+    fn admit(&self, client: (String, String)) -> (String, String) {
+        let mut seen_clients = self.seen_clients.lock().unwrap();
+        if seen_clients.contains(&client) {
+            return client;
+        }
+        if seen_clients.len() < MAX_DISTINCT_CLIENTS {
+            seen_clients.insert(client.clone());
+            return client;
+        }
+        (Self::OTHER.to_string(), Self::OTHER.to_string())
+    }
+}
+
+/// Extracts the client product and version from the leading `product/version` token of a
+/// `User-Agent` header. Characters other than ASCII alphanumerics, `.`, `_`, `+`, and `-` are
+/// dropped, and the results are truncated to [`MAX_LABEL_LENGTH`].
+fn parse_user_agent(user_agent: Option<&str>) -> (String, String) {
+    let token = user_agent
+        .and_then(|user_agent| user_agent.split_whitespace().next())
+        .unwrap_or_default();
+    let mut parts = token.split('/');
+    let client = sanitize_label(parts.next().unwrap_or_default());
+    let version = sanitize_label(parts.next().unwrap_or_default());
+
+    match (client.is_empty(), version.is_empty()) {
+        (true, _) => (
+            ClientTelemetry::UNKNOWN.to_string(),
+            ClientTelemetry::UNKNOWN.to_string(),
+        ),
+        (false, true) => (client, ClientTelemetry::UNKNOWN.to_string()),
+        (false, false) => (client, version),
+    }
+}
+
+/// Drops characters other than ASCII alphanumerics, `.`, `_`, `+`, and `-` from a client-supplied
+/// metric label, and truncates it to [`MAX_LABEL_LENGTH`].
+pub(super) fn sanitize_label(value: &str) -> String {
+    value
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '+' | '-'))
+        .take(MAX_LABEL_LENGTH)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_user_agent, ClientTelemetry, MAX_DISTINCT_CLIENTS};
+    use janus_aggregator_core::test_util::noop_meter;
+
+    fn pair(client: &str, version: &str) -> (String, String) {
+        (client.to_string(), version.to_string())
+    }
+
+    #[test]
+    fn parse_user_agents() {
+        let long_user_agent = format!("{}/1", "a".repeat(100));
+        for (user_agent, expected) in [
+            (
+                Some("janus_client/0.7.0/client"),
+                pair("janus_client", "0.7.0"),
+            ),
+            (
+                Some("divviup-ts/0.1.0 (browser)"),
+                pair("divviup-ts", "0.1.0"),
+            ),
+            (
+                Some("Mozilla/5.0 (X11; Linux x86_64)"),
+                pair("Mozilla", "5.0"),
+            ),
+            (Some("curl"), pair("curl", "unknown")),
+            (Some("sdk/1.0\u{1F600}<script>"), pair("sdk", "1.0script")),
+            (Some(""), pair("unknown", "unknown")),
+            (Some("/1.0"), pair("unknown", "unknown")),
+            (None, pair("unknown", "unknown")),
+            (Some(long_user_agent.as_str()), pair(&"a".repeat(32), "1")),
+        ] {
+            assert_eq!(parse_user_agent(user_agent), expected, "{user_agent:?}");
+        }
+    }
+
+    #[test]
+    fn distinct_clients_are_bounded() {
+        let telemetry = ClientTelemetry::new(&noop_meter());
+
+        for i in 0..MAX_DISTINCT_CLIENTS {
+            let client = pair(&format!("client{i}"), "1.0");
+            assert_eq!(telemetry.admit(client.clone()), client);
+        }
+
+        // Clients seen before are still admitted, but new ones are not.
+        assert_eq!(
+            telemetry.admit(pair("client0", "1.0")),
+            pair("client0", "1.0")
+        );
+        assert_eq!(
+            telemetry.admit(pair("client0", "2.0")),
+            pair("other", "other")
+        );
+    }
+}
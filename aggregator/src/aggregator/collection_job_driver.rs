@@ -1,8 +1,13 @@
 //! Implements portions of collect sub-protocol for DAP leader and helper.
 
 use crate::aggregator::{
-    aggregate_share::compute_aggregate_share, empty_batch_aggregations,
-    http_handlers::AGGREGATE_SHARES_ROUTE, query_type::CollectableQueryType,
+    aggregate_share::compute_aggregate_share,
+    deployment::DeploymentFence,
+    empty_batch_aggregations,
+    http_handlers::AGGREGATE_SHARES_ROUTE,
+    query_type::CollectableQueryType,
+    result_sink::{ResultRecorder, ResultSink},
+    retry_classification::{FailureDomain, RetryClassifier},
     send_request_to_helper, Error, RequestBody,
 };
 use backoff::backoff::Backoff;
@@ -20,7 +25,7 @@ use janus_aggregator_core::{
     },
     task,
 };
-use janus_core::{retries::is_retryable_http_status, time::Clock, vdaf_dispatch};
+use janus_core::{hpke::HpkeKeypair, time::Clock, vdaf::VdafInstance, vdaf_dispatch};
 use janus_messages::{
     query_type::{FixedSize, QueryType, TimeInterval},
     AggregateShare, AggregateShareReq, BatchSelector,
@@ -35,9 +40,10 @@ use prio::{
     vdaf,
 };
 use reqwest::Method;
+use serde::Serialize;
 use std::{sync::Arc, time::Duration};
 use tokio::try_join;
-use tracing::{error, info, warn};
+use tracing::{debug, error, info, warn};
 
 /// Drives a collection job.
 #[derive(Derivative)]
@@ -54,6 +60,10 @@ pub struct CollectionJobDriver<B> {
     // The minimum duration to wait before retrying a collection job that has been stepped but was
     // not ready yet because not all included reports had finished aggregation.
     min_collection_job_retry_delay: Duration,
+    // Records the results of finished collection jobs, if a result sink is configured.
+    result_recorder: Option<ResultRecorder>,
+    // Collection jobs are only acquired while this binary's deployment is active.
+    deployment_fence: DeploymentFence,
 }
 
 impl<B> CollectionJobDriver<B>
@@ -74,6 +84,32 @@ where
             metrics: CollectionJobDriverMetrics::new(meter),
             batch_aggregation_shard_count,
             min_collection_job_retry_delay,
+            result_recorder: None,
+            deployment_fence: DeploymentFence::default(),
+        }
+    }
+
+    /// Only acquire collection jobs while this binary's deployment is active.
+    pub fn with_deployment_fence(self, deployment_fence: DeploymentFence) -> Self {
+        Self {
+            deployment_fence,
+            ..self
+        }
+    }
+
+    /// Records the decoded result of each collection job this driver finishes to the given
+    /// [`ResultSink`]. Only jobs belonging to tasks whose collector HPKE config matches one of
+    /// `collector_hpke_keypairs` are recorded. Jobs that are finished by reusing the result of an
+    /// earlier collection of the same batch are not recorded again.
+    pub fn with_result_sink(
+        self,
+        meter: &Meter,
+        sink: Arc<dyn ResultSink>,
+        collector_hpke_keypairs: Vec<HpkeKeypair>,
+    ) -> Self {
+        Self {
+            result_recorder: Some(ResultRecorder::new(meter, sink, collector_hpke_keypairs)),
+            ..self
         }
     }
 
@@ -89,7 +125,16 @@ where
     /// will not yield a result. The collection job lease will eventually expire, allowing a later run
     /// of the collection job driver to try again. Both aggregate shares will be recomputed at that
     /// time.
-    #[tracing::instrument(skip(self, datastore), err)]
+    #[tracing::instrument(
+        name = "CollectionJobDriver::step_collection_job",
+        skip_all,
+        fields(
+            task_id = %lease.leased().task_id(),
+            collection_job_id = %lease.leased().collection_job_id(),
+            lease_attempts = lease.lease_attempts(),
+        ),
+        err,
+    )]
     pub async fn step_collection_job<C: Clock>(
         &self,
         datastore: Arc<Datastore<C>>,
@@ -128,7 +173,7 @@ where
         C: Clock,
         Q: CollectableQueryType,
         S: DifferentialPrivacyStrategy,
-        A: vdaf::AggregatorWithNoise<SEED_SIZE, 16, S> + Send + Sync,
+        A: vdaf::AggregatorWithNoise<SEED_SIZE, 16, S> + vdaf::Collector + Send + Sync,
     >(
         &self,
         datastore: Arc<Datastore<C>>,
@@ -140,6 +185,7 @@ where
         A: 'static,
         A::AggregationParam: Send + Sync,
         A::AggregateShare: 'static + Send + Sync,
+        A::AggregateResult: Serialize,
         A::OutputShare: PartialEq + Eq + Send + Sync,
     {
         let rslt = datastore
@@ -204,15 +250,34 @@ where
                         {
                             let task_id = *task.id();
                             let batch_identifier = collection_job.batch_identifier().clone();
+                            // Reports for VDAFs with an aggregation parameter are aggregated once
+                            // per aggregation parameter, so we must check for reports not yet
+                            // aggregated with this collection job's aggregation parameter.
+                            let aggregation_param = match task.vdaf() {
+                                VdafInstance::Poplar1 { .. } => {
+                                    Some(collection_job.aggregation_parameter().get_encoded()?)
+                                }
+                                _ => None,
+                            };
 
                             async move {
-                                if let Some(batch_interval) =
-                                    Q::to_batch_interval(&batch_identifier)
-                                {
-                                    tx.interval_has_unaggregated_reports(&task_id, batch_interval)
+                                match (Q::to_batch_interval(&batch_identifier), aggregation_param) {
+                                    (Some(batch_interval), Some(aggregation_param)) => {
+                                        tx.interval_has_unaggregated_reports_for_aggregation_param(
+                                            &task_id,
+                                            batch_interval,
+                                            &aggregation_param,
+                                        )
                                         .await
-                                } else {
-                                    Ok(false)
+                                    }
+                                    (Some(batch_interval), None) => {
+                                        tx.interval_has_unaggregated_reports(
+                                            &task_id,
+                                            batch_interval,
+                                        )
+                                        .await
+                                    }
+                                    (None, _) => Ok(false),
                                 }
                             }
                         },
@@ -355,6 +420,7 @@ where
             // case, and Janus never acts as the leader with taskprov enabled.
             task.aggregator_auth_token()
                 .ok_or_else(|| Error::InvalidConfiguration("no aggregator auth token in task"))?,
+            task.helper_request_headers(),
             &self.metrics.http_request_duration_histogram,
         )
         .await?;
@@ -378,7 +444,7 @@ where
                 .collect::<Vec<_>>(),
         );
 
-        datastore
+        let finished = datastore
             .run_tx("step_collection_job_2", |tx| {
                 let vdaf = Arc::clone(&vdaf);
                 let lease = Arc::clone(&lease);
@@ -413,6 +479,7 @@ where
                                 tx.release_collection_job(&lease, None),
                             )?;
                             metrics.jobs_finished_counter.add( 1, &[]);
+                            Ok(true)
                         }
 
                         CollectionJobState::Deleted => {
@@ -425,6 +492,7 @@ where
                                 "collection job was deleted while lease was held. Discarding aggregate results.",
                             );
                             metrics.deleted_jobs_encountered_counter.add( 1, &[]);
+                            Ok(false)
                         }
 
                         state => {
@@ -439,11 +507,17 @@ where
                             );
                         }
                     }
-
-                    Ok(())
                 })
             })
             .await?;
+
+        if finished {
+            if let Some(result_recorder) = &self.result_recorder {
+                result_recorder
+                    .record(vdaf.as_ref(), &task, &collection_job)
+                    .await;
+            }
+        }
         Ok(())
     }
 
@@ -528,12 +602,18 @@ where
         lease_duration: Duration,
     ) -> impl Fn(usize) -> BoxFuture<'static, Result<Vec<Lease<AcquiredCollectionJob>>, datastore::Error>>
     {
+        let deployment_fence = self.deployment_fence.clone();
         move |maximum_acquire_count| {
-            let datastore = Arc::clone(&datastore);
+            let (datastore, deployment_fence) = (Arc::clone(&datastore), deployment_fence.clone());
             Box::pin(async move {
                 datastore
                     .run_tx("acquire_collection_jobs", |tx| {
+                        let deployment_fence = deployment_fence.clone();
                         Box::pin(async move {
+                            if !deployment_fence.is_active(tx).await? {
+                                debug!("Deployment is on standby, not acquiring collection jobs");
+                                return Ok(Vec::new());
+                            }
                             tx.acquire_incomplete_collection_jobs(
                                 &lease_duration,
                                 maximum_acquire_count,
@@ -546,6 +626,52 @@ where
         }
     }
 
+    /// Produce a closure for use as a `[JobDriver::JobReleaser]`.
+    pub fn make_job_releaser_callback<C: Clock>(
+        &self,
+        datastore: Arc<Datastore<C>>,
+    ) -> impl Fn(Lease<AcquiredCollectionJob>) -> BoxFuture<'static, Result<(), datastore::Error>>
+    {
+        move |lease| {
+            let (datastore, lease) = (Arc::clone(&datastore), Arc::new(lease));
+            Box::pin(async move {
+                datastore
+                    .run_tx("release_collection_job", |tx| {
+                        let lease = Arc::clone(&lease);
+                        Box::pin(async move { tx.release_collection_job(&lease, None).await })
+                    })
+                    .await
+            })
+        }
+    }
+
+    /// Records the failure domain of a failed attempt to step the leased collection job, so that
+    /// it is kept with the job if the job is abandoned. This is best-effort: errors are logged, but
+    /// otherwise ignored.
+    async fn record_failure_domain<C: Clock>(
+        &self,
+        datastore: Arc<Datastore<C>>,
+        lease: &Arc<Lease<AcquiredCollectionJob>>,
+        failure_domain: FailureDomain,
+    ) {
+        if let Err(error) = datastore
+            .run_tx("record_collection_job_failure_domain", |tx| {
+                let lease = Arc::clone(lease);
+                Box::pin(async move {
+                    tx.set_collection_job_last_failure_domain(
+                        lease.leased().task_id(),
+                        lease.leased().collection_job_id(),
+                        failure_domain.as_str(),
+                    )
+                    .await
+                })
+            })
+            .await
+        {
+            error!(?error, "Failed to record failure domain of collection job");
+        }
+    }
+
     /// Produce a closure for use as a `[JobDriver::JobStepper]`.
     pub fn make_job_stepper_callback<C: Clock>(
         self: Arc<Self>,
@@ -563,7 +689,9 @@ where
                         max_attempts = %maximum_attempts_before_failure,
                         "Abandoning job due to too many failed attempts"
                     );
-                    this.metrics.jobs_abandoned_counter.add(1, &[]);
+                    this.metrics
+                        .jobs_abandoned_counter
+                        .add(1, &[KeyValue::new("reason", "max_attempts")]);
                     return this.abandon_collection_job(datastore, lease).await;
                 }
 
@@ -577,7 +705,22 @@ where
                 {
                     Ok(_) => Ok(()),
                     Err(error) => {
-                        if !Self::is_retryable_error(&error) {
+                        let class = this.metrics.retry_classifier.classify(&error);
+                        let failure_domain = class.domain();
+                        this.record_failure_domain(Arc::clone(&datastore), &lease, failure_domain)
+                            .await;
+
+                        if class.is_retryable() {
+                            // Retryable failures, e.g. transient datastore or peer aggregator
+                            // errors, are retried once the lease expires.
+                            warn!(
+                                %attempts,
+                                max_attempts = %maximum_attempts_before_failure,
+                                failure_domain = failure_domain.as_str(),
+                                ?error,
+                                "Job step failed, will retry"
+                            );
+                        } else {
                             // Make a best-effort attempt to immediately cancel the collection job.
                             // on fatal errors. This protects the helper from performing wasted
                             // work.
@@ -591,10 +734,17 @@ where
                             warn!(
                                 attempts = %attempts,
                                 max_attempts = %maximum_attempts_before_failure,
+                                failure_domain = failure_domain.as_str(),
                                 ?error,
                                 "Abandoning job due to fatal error"
                             );
-                            this.metrics.jobs_abandoned_counter.add(1, &[]);
+                            this.metrics.jobs_abandoned_counter.add(
+                                1,
+                                &[
+                                    KeyValue::new("reason", "fatal_error"),
+                                    KeyValue::new("failure_domain", failure_domain.as_str()),
+                                ],
+                            );
                             if let Err(error) = this.abandon_collection_job(datastore, lease).await
                             {
                                 error!(error = ?error, "Failed to abandon job");
@@ -606,25 +756,6 @@ where
             })
         }
     }
-
-    /// Determines whether the given [`Error`] is retryable in the context of collection job
-    /// processing.
-    fn is_retryable_error(error: &Error) -> bool {
-        match error {
-            Error::Http(http_error_response) => {
-                is_retryable_http_status(http_error_response.status())
-            }
-            Error::Datastore(error) => match error {
-                datastore::Error::Db(_) | datastore::Error::Pool(_) => true,
-                datastore::Error::User(error) => match error.downcast_ref::<Error>() {
-                    Some(error) => Self::is_retryable_error(error),
-                    None => false,
-                },
-                _ => false,
-            },
-            _ => false,
-        }
-    }
 }
 
 /// Holds various metrics instruments for a collection job driver.
@@ -636,6 +767,7 @@ struct CollectionJobDriverMetrics {
     deleted_jobs_encountered_counter: Counter<u64>,
     unexpected_job_state_counter: Counter<u64>,
     job_steps_retried_counter: Counter<u64>,
+    retry_classifier: RetryClassifier,
 }
 
 impl CollectionJobDriverMetrics {
@@ -689,6 +821,8 @@ impl CollectionJobDriverMetrics {
             .init();
         job_steps_retried_counter.add(0, &[]);
 
+        let retry_classifier = RetryClassifier::new(meter);
+
         Self {
             jobs_finished_counter,
             http_request_duration_histogram,
@@ -696,6 +830,7 @@ impl CollectionJobDriverMetrics {
             deleted_jobs_encountered_counter,
             unexpected_job_state_counter,
             job_steps_retried_counter,
+            retry_classifier,
         }
     }
 }
@@ -704,12 +839,15 @@ impl CollectionJobDriverMetrics {
 mod tests {
     use crate::{
         aggregator::{
-            collection_job_driver::CollectionJobDriver, test_util::BATCH_AGGREGATION_SHARD_COUNT,
+            collection_job_driver::CollectionJobDriver,
+            result_sink::{CollectionResultRecord, ResultSink},
+            test_util::BATCH_AGGREGATION_SHARD_COUNT,
             Error,
         },
         binary_utils::job_driver::JobDriver,
     };
     use assert_matches::assert_matches;
+    use async_trait::async_trait;
     use http::{header::CONTENT_TYPE, StatusCode};
     use janus_aggregator_core::{
         datastore::{
@@ -723,11 +861,12 @@ mod tests {
         },
         task::{
             test_util::{Task, TaskBuilder},
-            QueryType,
+            HelperRequestHeader, QueryType,
         },
         test_util::noop_meter,
     };
     use janus_core::{
+        hpke::{self, HpkeApplicationInfo, Label},
         retries::test_util::LimitedRetryer,
         test_util::{install_test_trace_subscriber, runtime::TestRuntimeManager},
         time::{Clock, IntervalExt, MockClock, TimeExt},
@@ -735,16 +874,19 @@ mod tests {
         Runtime,
     };
     use janus_messages::{
-        problem_type::DapProblemType, query_type::TimeInterval, AggregateShare, AggregateShareReq,
-        AggregationJobStep, BatchSelector, Duration, HpkeCiphertext, HpkeConfigId, Interval, Query,
-        ReportIdChecksum,
+        problem_type::DapProblemType, query_type::TimeInterval, AggregateShare, AggregateShareAad,
+        AggregateShareReq, AggregationJobStep, BatchSelector, Duration, HpkeCiphertext,
+        HpkeConfigId, Interval, Query, ReportIdChecksum, Role,
     };
     use prio::{
         codec::{Decode, Encode},
         vdaf::dummy,
     };
     use rand::random;
-    use std::{sync::Arc, time::Duration as StdDuration};
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration as StdDuration,
+    };
     use trillium_tokio::Stopper;
 
     async fn setup_collection_job_test_case(
@@ -906,7 +1048,16 @@ mod tests {
             .with_min_batch_size(10)
             .build();
 
-        let leader_task = task.leader_view().unwrap();
+        // The helper sits behind a gateway that requires an extra header.
+        let leader_task = task
+            .leader_view()
+            .unwrap()
+            .with_helper_request_headers(Vec::from([HelperRequestHeader::new(
+                "X-Api-Key".to_string(),
+                "gateway-key".to_string(),
+            )
+            .unwrap()]))
+            .unwrap();
         let agg_auth_token = task.aggregator_auth_token();
         let batch_interval = Interval::new(clock.now(), Duration::from_seconds(2000)).unwrap();
         let aggregation_param = dummy::AggregationParam(0);
@@ -1157,6 +1308,7 @@ mod tests {
         let mocked_failed_aggregate_share = server
             .mock("POST", task.aggregate_shares_uri().unwrap().path())
             .match_header(header, value.as_str())
+            .match_header("X-Api-Key", "gateway-key")
             .match_header(
                 CONTENT_TYPE.as_str(),
                 AggregateShareReq::<TimeInterval>::MEDIA_TYPE,
@@ -1230,6 +1382,7 @@ mod tests {
         let mocked_aggregate_share = server
             .mock("POST", task.aggregate_shares_uri().unwrap().path())
             .match_header(header, value.as_str())
+            .match_header("X-Api-Key", "gateway-key")
             .match_header(
                 CONTENT_TYPE.as_str(),
                 AggregateShareReq::<TimeInterval>::MEDIA_TYPE,
@@ -1367,6 +1520,89 @@ mod tests {
         .unwrap();
     }
 
+    /// A fake result sink, which records the results it is given.
+    #[derive(Debug, Default)]
+    struct FakeResultSink {
+        records: Mutex<Vec<CollectionResultRecord>>,
+    }
+
+    #[async_trait]
+    impl ResultSink for FakeResultSink {
+        async fn record(&self, record: &CollectionResultRecord) -> anyhow::Result<()> {
+            self.records.lock().unwrap().push(record.clone());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn drive_collection_job_records_result() {
+        install_test_trace_subscriber();
+        let mut server = mockito::Server::new_async().await;
+        let clock = MockClock::default();
+        let ephemeral_datastore = ephemeral_datastore().await;
+        let ds = Arc::new(ephemeral_datastore.datastore(clock.clone()).await);
+
+        let (task, lease, collection_job) =
+            setup_collection_job_test_case(&mut server, clock, Arc::clone(&ds), true).await;
+
+        let helper_response = AggregateShare::new(
+            hpke::seal(
+                task.collector_hpke_keypair().config(),
+                &HpkeApplicationInfo::new(&Label::AggregateShare, &Role::Helper, &Role::Collector),
+                &dummy::AggregateShare(7).get_encoded().unwrap(),
+                &AggregateShareAad::new(
+                    *task.id(),
+                    collection_job
+                        .aggregation_parameter()
+                        .get_encoded()
+                        .unwrap(),
+                    BatchSelector::new_time_interval(*collection_job.batch_identifier()),
+                )
+                .get_encoded()
+                .unwrap(),
+            )
+            .unwrap(),
+        );
+        let mocked_aggregate_share = server
+            .mock("POST", task.aggregate_shares_uri().unwrap().path())
+            .with_status(200)
+            .with_header(CONTENT_TYPE.as_str(), AggregateShare::MEDIA_TYPE)
+            .with_body(helper_response.get_encoded().unwrap())
+            .create_async()
+            .await;
+
+        let result_sink = Arc::new(FakeResultSink::default());
+        let collection_job_driver = CollectionJobDriver::new(
+            reqwest::Client::builder().build().unwrap(),
+            LimitedRetryer::new(0),
+            &noop_meter(),
+            BATCH_AGGREGATION_SHARD_COUNT,
+            StdDuration::ZERO,
+        )
+        .with_result_sink(
+            &noop_meter(),
+            Arc::clone(&result_sink) as Arc<dyn ResultSink>,
+            Vec::from([task.collector_hpke_keypair().clone()]),
+        );
+
+        collection_job_driver
+            .step_collection_job(Arc::clone(&ds), Arc::new(lease.unwrap()))
+            .await
+            .unwrap();
+
+        mocked_aggregate_share.assert_async().await;
+
+        let records = result_sink.records.lock().unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].task_id(), task.id());
+        assert_eq!(
+            records[0].collection_job_id(),
+            collection_job.id().to_string()
+        );
+        assert_eq!(records[0].report_count(), 10);
+        assert_eq!(records[0].aggregate_result(), &serde_json::json!(7));
+    }
+
     #[tokio::test]
     async fn abandon_collection_job() {
         // Setup: insert a collection job into the datastore.
@@ -1500,26 +1736,34 @@ mod tests {
         failure_mock.assert_async().await;
         assert!(!no_more_requests_mock.matched_async().await);
 
-        // Confirm that the collection job was abandoned.
-        let collection_job_after = ds
+        // Confirm that the collection job was abandoned, and that the failure domain of its last
+        // failed step was recorded.
+        let (collection_job_after, last_failure_domain) = ds
             .run_unnamed_tx(|tx| {
                 let collection_job = collection_job.clone();
                 Box::pin(async move {
-                    tx.get_collection_job::<0, TimeInterval, dummy::Vdaf>(
-                        &dummy::Vdaf::new(1),
-                        collection_job.task_id(),
-                        collection_job.id(),
-                    )
-                    .await
+                    Ok((
+                        tx.get_collection_job::<0, TimeInterval, dummy::Vdaf>(
+                            &dummy::Vdaf::new(1),
+                            collection_job.task_id(),
+                            collection_job.id(),
+                        )
+                        .await?,
+                        tx.get_collection_job_last_failure_domain(
+                            collection_job.task_id(),
+                            collection_job.id(),
+                        )
+                        .await?,
+                    ))
                 })
             })
             .await
-            .unwrap()
             .unwrap();
         assert_eq!(
-            collection_job_after,
+            collection_job_after.unwrap(),
             collection_job.with_state(CollectionJobState::Abandoned),
         );
+        assert_eq!(last_failure_domain.as_deref(), Some("peer"));
     }
 
     #[tokio::test]
@@ -1602,26 +1846,34 @@ mod tests {
         failure_mock.assert_async().await;
         assert!(!no_more_requests_mock.matched_async().await);
 
-        // Confirm that the collection job was abandoned.
-        let collection_job_after = ds
+        // Confirm that the collection job was abandoned, and that the failure domain of its last
+        // failed step was recorded.
+        let (collection_job_after, last_failure_domain) = ds
             .run_unnamed_tx(|tx| {
                 let collection_job = collection_job.clone();
                 Box::pin(async move {
-                    tx.get_collection_job::<0, TimeInterval, dummy::Vdaf>(
-                        &dummy::Vdaf::new(1),
-                        collection_job.task_id(),
-                        collection_job.id(),
-                    )
-                    .await
+                    Ok((
+                        tx.get_collection_job::<0, TimeInterval, dummy::Vdaf>(
+                            &dummy::Vdaf::new(1),
+                            collection_job.task_id(),
+                            collection_job.id(),
+                        )
+                        .await?,
+                        tx.get_collection_job_last_failure_domain(
+                            collection_job.task_id(),
+                            collection_job.id(),
+                        )
+                        .await?,
+                    ))
                 })
             })
             .await
-            .unwrap()
             .unwrap();
         assert_eq!(
-            collection_job_after,
+            collection_job_after.unwrap(),
             collection_job.with_state(CollectionJobState::Abandoned),
         );
+        assert_eq!(last_failure_domain.as_deref(), Some("peer"));
     }
 
     #[tokio::test]
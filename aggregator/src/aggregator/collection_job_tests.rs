@@ -278,6 +278,14 @@ async fn collection_job_success_fixed_size() {
 
         let test_conn = test_case.post_collection_job(&collection_job_id).await;
         assert_eq!(test_conn.status(), Some(Status::Accepted));
+        // All of the batch's aggregation jobs have terminated, so the collection job is expected
+        // to be ready as soon as the collection job driver runs.
+        assert_eq!(
+            test_conn
+                .response_headers()
+                .get_str(KnownHeaderName::RetryAfter),
+            Some("1")
+        );
 
         // Update the collection job with the aggregate shares. collection job should now be complete.
         let batch_id = test_case
@@ -0,0 +1,93 @@
+//! Support for running an active/standby pair of leader deployments against the same datastore.
+//!
+//! Each binary in such a deployment is configured with the deployment's name. Only the deployment
+//! recorded as active in the datastore creates aggregation jobs, steps aggregation and collection
+//! jobs, or acquires leader leases; the other deployment is a warm standby. Running `janus_cli
+//! promote` makes a deployment active. Promotion expires all outstanding job and leader leases, so
+//! that the newly active deployment takes over without waiting for them to expire, and clears the
+//! lease tokens of outstanding job leases, so that work still in flight in the previously active
+//! deployment fails to commit.
+
+use janus_aggregator_core::datastore::{Error, Transaction};
+use janus_core::time::Clock;
+use std::sync::Arc;
+
+/// Guards work which only the active deployment may perform.
+#[derive(Clone, Debug, Default)]
+pub struct DeploymentFence {
+    deployment: Option<Arc<str>>,
+}
+
+impl DeploymentFence {
+    /// Creates a fence for the named deployment. A binary which is not part of a named deployment
+    /// is always considered active.
+    pub fn new(deployment: Option<&str>) -> Self {
+        Self {
+            deployment: deployment.map(Arc::from),
+        }
+    }
+
+    /// The name of the deployment this binary is part of, if any.
+    pub fn deployment(&self) -> Option<&str> {
+        self.deployment.as_deref()
+    }
+
+    /// Returns whether this binary's deployment is active. This must be checked in the same
+    /// transaction as the guarded work: the active deployment is locked against promotion until the
+    /// transaction completes.
+    pub async fn is_active<C: Clock>(&self, tx: &Transaction<'_, C>) -> Result<bool, Error> {
+        let Some(deployment) = &self.deployment else {
+            return Ok(true);
+        };
+        Ok(tx
+            .get_active_deployment()
+            .await?
+            .is_some_and(|active_deployment| active_deployment.deployment() == &**deployment))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DeploymentFence;
+    use janus_aggregator_core::datastore::test_util::ephemeral_datastore;
+    use janus_core::{test_util::install_test_trace_subscriber, time::MockClock};
+
+    #[tokio::test]
+    async fn deployment_fence() {
+        install_test_trace_subscriber();
+        let ephemeral_datastore = ephemeral_datastore().await;
+        let datastore = ephemeral_datastore.datastore(MockClock::default()).await;
+
+        let is_active = |fence: DeploymentFence| {
+            datastore.run_unnamed_tx(move |tx| {
+                let fence = fence.clone();
+                Box::pin(async move { fence.is_active(tx).await })
+            })
+        };
+        let (unnamed, east, west) = (
+            DeploymentFence::default(),
+            DeploymentFence::new(Some("east")),
+            DeploymentFence::new(Some("west")),
+        );
+
+        // Before any deployment is promoted, only binaries outside a named deployment are active.
+        assert!(is_active(unnamed.clone()).await.unwrap());
+        assert!(!is_active(east.clone()).await.unwrap());
+        assert!(!is_active(west.clone()).await.unwrap());
+
+        for (promoted, standby) in [(&east, &west), (&west, &east)] {
+            let deployment = promoted.deployment().unwrap().to_string();
+            datastore
+                .run_unnamed_tx(|tx| {
+                    let deployment = deployment.clone();
+                    Box::pin(async move { tx.promote_deployment(&deployment).await })
+                })
+                .await
+                .unwrap();
+
+            assert!(is_active(unnamed.clone()).await.unwrap());
+            assert!(is_active(promoted.clone()).await.unwrap());
+            assert!(!is_active(standby.clone()).await.unwrap());
+        }
+    }
+}
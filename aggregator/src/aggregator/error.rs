@@ -1,8 +1,8 @@
-use janus_aggregator_core::{datastore, task};
+use janus_aggregator_core::{datastore, query_type::BatchIntervalViolation, task};
 use janus_core::http::HttpErrorResponse;
 use janus_messages::{
-    AggregationJobId, AggregationJobStep, CollectionJobId, HpkeConfigId, Interval, PrepareError,
-    ReportId, ReportIdChecksum, Role, TaskId, Time,
+    AggregationJobId, AggregationJobStep, CollectionJobId, Duration, HpkeConfigId, Interval,
+    PrepareError, ReportId, ReportIdChecksum, Role, TaskId, Time,
 };
 use opentelemetry::{metrics::Counter, KeyValue};
 use prio::{topology::ping_pong::PingPongError, vdaf::VdafError};
@@ -85,6 +85,18 @@ pub enum Error {
     /// because the interval failed boundary checks.
     #[error("task {0}: invalid batch interval: {1}")]
     BatchInvalid(TaskId, String),
+    /// Corresponds to `batchInvalid` in DAP. A collect, aggregate share or batch report count
+    /// request was rejected because its batch interval is not aligned to the task's time
+    /// precision.
+    #[error("task {0}: invalid batch interval {1}: {2}")]
+    BatchIntervalMisaligned(TaskId, String, BatchIntervalViolation),
+    /// Corresponds to `batchInvalid` in DAP. A collect request was rejected because its batch
+    /// interval spans more multiples of the task's time precision than the leader allows.
+    #[error(
+        "task {0}: batch interval spans more than {1} time precisions; collect shorter batch \
+         intervals and combine their results instead"
+    )]
+    BatchIntervalTooLong(TaskId, u64),
     /// Corresponds to `invalidBatchSize in DAP. The number of reports in the batch is invalid for
     /// the task's parameters.
     #[error("task {0}: invalid number of reports ({1})")]
@@ -116,6 +128,11 @@ pub enum Error {
     /// HTTP server returned an error status code.
     #[error("HTTP response status {0}")]
     Http(Box<HttpErrorResponse>),
+    /// The helper rejected a request to initialize an aggregation job with `unauthorizedRequest`.
+    /// A Janus helper responds this way to tasks it has not been provisioned with yet, so unlike
+    /// other unauthorized requests, this may succeed if it is retried.
+    #[error("helper rejected aggregation job initialization as unauthorized: {0}")]
+    AggregationJobInitUnauthorized(Box<HttpErrorResponse>),
     /// An aggregate share request was rejected.
     #[error("task {0}: {1}")]
     AggregateShareRequestRejected(TaskId, String),
@@ -135,6 +152,10 @@ pub enum Error {
     /// A catch-all error representing an issue with a request.
     #[error("request error: {0}")]
     BadRequest(String),
+    /// A request was rejected by strict conformance checks, because it does not conform to the DAP
+    /// specification in some way that would otherwise be tolerated.
+    #[error("non-conformant request: {0}")]
+    NonConformantRequest(String),
     /// Corresponds to taskprov `invalidTask`. See the [Taskprov specification][1] for details.
     ///
     /// [1]: https://www.ietf.org/archive/id/draft-wang-ppm-dap-taskprov-04.html#name-conventions-and-definitions
@@ -143,6 +164,13 @@ pub enum Error {
     /// An error occurred when trying to ensure differential privacy.
     #[error("differential privacy error: {0}")]
     DifferentialPrivacy(VdafError),
+    /// An error from the upload queue.
+    #[error("upload queue error: {0}")]
+    UploadQueue(String),
+    /// An aggregate request was abandoned because handling it would use more memory than the
+    /// configured per-request budget. The leader should retry with a smaller aggregation job.
+    #[error("task {0}: aggregate request exceeds memory budget of {1} bytes")]
+    AggregateRequestTooLarge(TaskId, u64),
 }
 
 /// A newtype around `Arc<Error>`. This is needed to host a customized implementation of
@@ -165,12 +193,14 @@ impl Deref for ArcError {
 }
 
 /// Contains details that describe the report and why it was rejected.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ReportRejection {
     task_id: TaskId,
     report_id: ReportId,
     time: Time,
     reason: ReportRejectionReason,
+    // Boxed to keep the size of `Error` down, since most rejections carry no details.
+    details: Box<ReportRejectionDetails>,
 }
 
 impl ReportRejection {
@@ -185,6 +215,16 @@ impl ReportRejection {
             report_id,
             time,
             reason,
+            details: Box::default(),
+        }
+    }
+
+    /// Attaches machine-readable details to the rejection, which are relayed to the client in the
+    /// problem document.
+    pub fn with_details(self, details: ReportRejectionDetails) -> Self {
+        Self {
+            details: Box::new(details),
+            ..self
         }
     }
 
@@ -203,6 +243,10 @@ impl ReportRejection {
     pub fn reason(&self) -> &ReportRejectionReason {
         &self.reason
     }
+
+    pub fn details(&self) -> &ReportRejectionDetails {
+        &self.details
+    }
 }
 
 impl Display for ReportRejection {
@@ -225,6 +269,8 @@ pub enum ReportRejectionReason {
     Expired,
     TooEarly,
     OutdatedHpkeConfig(HpkeConfigId),
+    /// The deployment's report policy rejected the report.
+    PolicyRejected,
 }
 
 impl ReportRejectionReason {
@@ -241,10 +287,26 @@ impl ReportRejectionReason {
             ReportRejectionReason::OutdatedHpkeConfig(_) => {
                 "Report is using an outdated HPKE configuration."
             }
+            ReportRejectionReason::PolicyRejected => "Report was rejected by the aggregator.",
         }
     }
 }
 
+/// Machine-readable hints describing what the aggregator would have accepted, allowing clients to
+/// correct a rejected upload (e.g. by adjusting their clock or refetching HPKE configurations)
+/// rather than retrying it unchanged.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReportRejectionDetails {
+    /// How far in the future report timestamps may be, relative to the aggregator's clock.
+    pub tolerable_clock_skew: Option<Duration>,
+    /// The earliest report timestamp the aggregator would currently accept.
+    pub earliest_acceptable_time: Option<Time>,
+    /// The latest report timestamp the aggregator would currently accept.
+    pub latest_acceptable_time: Option<Time>,
+    /// The IDs of the HPKE configurations the aggregator can currently decrypt reports with.
+    pub hpke_config_ids: Option<Vec<HpkeConfigId>>,
+}
+
 impl Display for ReportRejectionReason {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         write!(f, "{:?}", self)
@@ -293,6 +355,8 @@ impl Error {
             Error::Datastore(_) => "datastore",
             Error::Vdaf(_) => "vdaf",
             Error::BatchInvalid(_, _) => "batch_invalid",
+            Error::BatchIntervalMisaligned(_, _, _) => "batch_interval_misaligned",
+            Error::BatchIntervalTooLong(_, _) => "batch_interval_too_long",
             Error::InvalidBatchSize(_, _) => "invalid_batch_size",
             Error::Url(_) => "url",
             Error::BatchMismatch { .. } => "batch_mismatch",
@@ -302,13 +366,17 @@ impl Error {
             Error::TaskParameters(_) => "task_parameters",
             Error::HttpClient(_) => "http_client",
             Error::Http { .. } => "http",
+            Error::AggregationJobInitUnauthorized(_) => "aggregation_job_init_unauthorized",
             Error::AggregateShareRequestRejected(_, _) => "aggregate_share_request_rejected",
             Error::EmptyAggregation(_) => "empty_aggregation",
             Error::Internal(_) => "internal",
             Error::ForbiddenMutation { .. } => "forbidden_mutation",
             Error::BadRequest(_) => "bad_request",
+            Error::NonConformantRequest(_) => "non_conformant_request",
             Error::InvalidTask(_, _) => "invalid_task",
             Error::DifferentialPrivacy(_) => "differential_privacy",
+            Error::UploadQueue(_) => "upload_queue",
+            Error::AggregateRequestTooLarge(_, _) => "aggregate_request_too_large",
         }
     }
 }
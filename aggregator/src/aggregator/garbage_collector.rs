@@ -1,15 +1,26 @@
+use crate::feature_flags::{FeatureFlags, FeatureFlagsConfig, Flag};
 use anyhow::{Context, Error, Result};
 use futures::future::{join_all, try_join_all, OptionFuture};
-use janus_aggregator_core::datastore::{self, Datastore};
-use janus_core::time::Clock;
-use janus_messages::TaskId;
-use opentelemetry::metrics::{Counter, Meter, Unit};
+use janus_aggregator_core::{
+    datastore::{self, Datastore},
+    task::{AggregatorTask, TaskState},
+};
+use janus_core::time::{Clock, TimeExt};
+use janus_messages::{Duration, TaskId, Time};
+use opentelemetry::{
+    metrics::{Counter, Meter, Unit},
+    KeyValue,
+};
 use std::sync::{
     atomic::{AtomicU64, Ordering},
     Arc,
 };
 use tokio::{sync::Semaphore, try_join};
-use tracing::error;
+use tracing::{error, info};
+
+/// The name of the leader lease held by the garbage collector, if replicas elect a leader to
+/// collect garbage.
+pub const LEADER_LEASE_NAME: &str = "garbage_collector";
 
 pub struct GarbageCollector<C: Clock> {
     // Dependencies.
@@ -21,11 +32,16 @@ pub struct GarbageCollector<C: Clock> {
     collection_limit: u64,
     tasks_per_tx: usize,
     concurrent_tx_semaphore: Option<Semaphore>,
+    aggregation_job_ttl: Option<Duration>,
+    retention_period: Duration,
+    feature_flags: Arc<FeatureFlags>,
 
     // Metrics.
     deleted_report_counter: Counter<u64>,
     deleted_aggregation_job_counter: Counter<u64>,
+    deleted_terminal_aggregation_job_counter: Counter<u64>,
     deleted_batch_counter: Counter<u64>,
+    task_lifecycle_transition_counter: Counter<u64>,
 }
 
 impl<C: Clock> GarbageCollector<C> {
@@ -37,6 +53,7 @@ impl<C: Clock> GarbageCollector<C> {
         collection_limit: u64,
         tasks_per_tx: usize,
         concurrent_tx_limit: Option<usize>,
+        aggregation_job_ttl: Option<Duration>,
     ) -> Self {
         let deleted_report_counter = meter
             .u64_counter("janus_gc_deleted_reports")
@@ -48,14 +65,31 @@ impl<C: Clock> GarbageCollector<C> {
             .with_description("Count of aggregation jobs deleted by the garbage collector.")
             .with_unit(Unit::new("{job}"))
             .init();
+        let deleted_terminal_aggregation_job_counter = meter
+            .u64_counter("janus_gc_deleted_terminal_aggregation_jobs")
+            .with_description(
+                "Count of aggregation jobs deleted by the garbage collector because they had been \
+                 in a terminal state for longer than the aggregation job TTL.",
+            )
+            .with_unit(Unit::new("{job}"))
+            .init();
         let deleted_batch_counter = meter
             .u64_counter("janus_gc_deleted_batches")
             .with_description("Count of batches deleted by the garbage collector.")
             .with_unit(Unit::new("{batch}"))
             .init();
+        let task_lifecycle_transition_counter = meter
+            .u64_counter("janus_task_lifecycle_transitions")
+            .with_description(
+                "Count of task lifecycle transitions made by the garbage collector, by the states \
+                 moved from and to.",
+            )
+            .with_unit(Unit::new("{transition}"))
+            .init();
 
         deleted_report_counter.add(0, &[]);
         deleted_aggregation_job_counter.add(0, &[]);
+        deleted_terminal_aggregation_job_counter.add(0, &[]);
         deleted_batch_counter.add(0, &[]);
 
         let concurrent_tx_semaphore = concurrent_tx_limit.map(Semaphore::new);
@@ -67,9 +101,32 @@ impl<C: Clock> GarbageCollector<C> {
             collection_limit,
             deleted_report_counter,
             deleted_aggregation_job_counter,
+            deleted_terminal_aggregation_job_counter,
             deleted_batch_counter,
+            task_lifecycle_transition_counter,
             tasks_per_tx,
             concurrent_tx_semaphore,
+            aggregation_job_ttl,
+            retention_period: Duration::ZERO,
+            feature_flags: Arc::new(FeatureFlags::from_config(&FeatureFlagsConfig::default())),
+        }
+    }
+
+    /// Sets how long data is kept past each task's report expiry age before it is deleted. By
+    /// default, data is deleted as soon as it expires.
+    pub fn with_retention_period(self, retention_period: Duration) -> Self {
+        Self {
+            retention_period,
+            ..self
+        }
+    }
+
+    /// Sets the feature flags consulted by the garbage collector. By default, every flag takes its
+    /// default state.
+    pub fn with_feature_flags(self, feature_flags: Arc<FeatureFlags>) -> Self {
+        Self {
+            feature_flags,
+            ..self
         }
     }
 
@@ -78,14 +135,25 @@ impl<C: Clock> GarbageCollector<C> {
         // TODO(#224): add support for handling only a subset of tasks in a single job (i.e. sharding).
 
         // Retrieve tasks.
-        let task_ids: Vec<_> = self
+        let tasks = self
             .datastore
             .run_tx("garbage_collector_get_tasks", |tx| {
                 Box::pin(async move { tx.get_aggregator_tasks().await })
             })
             .await
-            .context("couldn't retrieve tasks")?
-            .into_iter()
+            .context("couldn't retrieve tasks")?;
+
+        // Advance task lifecycles. Tasks being purged are deleted outright, so there is nothing
+        // else to collect for them.
+        join_all(tasks.iter().map(|task| async move {
+            if let Err(err) = self.advance_task_lifecycle(task).await {
+                error!(task_id = %task.id(), ?err, "Couldn't advance task lifecycle")
+            }
+        }))
+        .await;
+        let task_ids: Vec<_> = tasks
+            .iter()
+            .filter(|task| task.state() != &TaskState::Purging)
             .map(|task| *task.id())
             .collect();
 
@@ -113,37 +181,142 @@ impl<C: Clock> GarbageCollector<C> {
         Ok(())
     }
 
+    /// Moves the given task to the next state in its lifecycle, if it is due to move:
+    ///
+    /// - Active tasks become expiring once their task expiration has passed.
+    /// - Expiring tasks become expired once every report they could have accepted has expired.
+    /// - Purging tasks are deleted, along with all of their data.
+    ///
+    /// Tasks are only provisioned, activated, and purged by operators.
+    #[tracing::instrument(
+        name = "GarbageCollector::advance_task_lifecycle",
+        skip(self, task),
+        fields(task_id = %task.id())
+    )]
+    async fn advance_task_lifecycle(&self, task: &AggregatorTask) -> Result<()> {
+        let from = *task.state();
+        let to = match self
+            .datastore
+            .run_tx("garbage_collector_task_lifecycle", |tx| {
+                let task = task.clone();
+                Box::pin(async move {
+                    let to = match next_task_state(&task, &tx.clock().now()) {
+                        Some(to) => to,
+                        None => return Ok(None),
+                    };
+                    if to == TaskState::Deleted {
+                        tx.delete_task(task.id()).await?;
+                    } else {
+                        tx.update_task_state(task.id(), task.state(), &to).await?;
+                    }
+                    Ok(Some(to))
+                })
+            })
+            .await?
+        {
+            Some(to) => to,
+            None => return Ok(()),
+        };
+
+        info!(?from, ?to, "Task lifecycle transition");
+        self.task_lifecycle_transition_counter.add(
+            1,
+            &[
+                KeyValue::new("from", format!("{from:?}")),
+                KeyValue::new("to", format!("{to:?}")),
+            ],
+        );
+        Ok(())
+    }
+
     #[tracing::instrument(name = "GarbageCollector::gc_tasks", skip(self))]
     async fn gc_tasks(&self, task_ids: Vec<TaskId>) -> Result<()> {
         let task_ids = Arc::new(task_ids);
-        let (client_reports_deleted, aggregation_jobs_deleted, batches_deleted) = self
+        let (
+            client_reports_deleted,
+            aggregation_jobs_deleted,
+            terminal_aggregation_jobs_deleted,
+            batches_deleted,
+        ) = self
             .datastore
             .run_tx("garbage_collector", |tx| {
                 let task_ids = Arc::clone(&task_ids);
                 let report_limit = self.report_limit;
                 let aggregation_limit = self.aggregation_limit;
                 let collection_limit = self.collection_limit;
+                let aggregation_job_ttl = self.aggregation_job_ttl;
+                let retention_period = self.retention_period;
+                let feature_flags = Arc::clone(&self.feature_flags);
 
                 Box::pin(async move {
                     let client_reports_deleted = Arc::new(AtomicU64::new(0));
                     let aggregation_jobs_deleted = Arc::new(AtomicU64::new(0));
+                    let terminal_aggregation_jobs_deleted = Arc::new(AtomicU64::new(0));
                     let batches_deleted = Arc::new(AtomicU64::new(0));
 
                     try_join_all(task_ids.iter().map(|task_id| {
                         let client_reports_deleted = Arc::clone(&client_reports_deleted);
                         let aggregation_jobs_deleted = Arc::clone(&aggregation_jobs_deleted);
+                        let terminal_aggregation_jobs_deleted =
+                            Arc::clone(&terminal_aggregation_jobs_deleted);
                         let batches_deleted = Arc::clone(&batches_deleted);
+                        let aggregation_job_ttl = aggregation_job_ttl.filter(|_| {
+                            feature_flags.is_enabled(
+                                &Flag::GC_DELETE_TERMINAL_AGGREGATION_JOBS,
+                                Some(task_id),
+                            )
+                        });
 
                         async move {
-                            let (report_count, agg_job_count, batch_count) = try_join!(
-                                tx.delete_expired_client_reports(task_id, report_limit),
-                                tx.delete_expired_aggregation_artifacts(task_id, aggregation_limit),
-                                tx.delete_expired_collection_artifacts(task_id, collection_limit),
+                            // Terminal aggregation jobs share the aggregation limit with expired
+                            // aggregation jobs.
+                            let delete_terminal_aggregation_jobs = async {
+                                match aggregation_job_ttl {
+                                    Some(ttl) => {
+                                        tx.delete_terminal_aggregation_jobs(
+                                            task_id,
+                                            &ttl,
+                                            aggregation_limit,
+                                        )
+                                        .await
+                                    }
+                                    None => Ok(0),
+                                }
+                            };
+
+                            // There is at most one upload sample per report, so upload samples
+                            // share the client report limit.
+                            let (
+                                report_count,
+                                agg_job_count,
+                                terminal_agg_job_count,
+                                batch_count,
+                                _,
+                            ) = try_join!(
+                                tx.delete_expired_client_reports(
+                                    task_id,
+                                    &retention_period,
+                                    report_limit
+                                ),
+                                tx.delete_expired_aggregation_artifacts(
+                                    task_id,
+                                    &retention_period,
+                                    aggregation_limit
+                                ),
+                                delete_terminal_aggregation_jobs,
+                                tx.delete_expired_collection_artifacts(
+                                    task_id,
+                                    &retention_period,
+                                    collection_limit
+                                ),
+                                tx.delete_expired_upload_samples(task_id, report_limit),
                             )
                             .with_context(|| format!("Couldn't GC {task_id}"))?;
 
                             client_reports_deleted.fetch_add(report_count, Ordering::Relaxed);
                             aggregation_jobs_deleted.fetch_add(agg_job_count, Ordering::Relaxed);
+                            terminal_aggregation_jobs_deleted
+                                .fetch_add(terminal_agg_job_count, Ordering::Relaxed);
                             batches_deleted.fetch_add(batch_count, Ordering::Relaxed);
 
                             Ok::<_, Error>(())
@@ -155,6 +328,7 @@ impl<C: Clock> GarbageCollector<C> {
                     Ok((
                         client_reports_deleted.load(Ordering::Relaxed),
                         aggregation_jobs_deleted.load(Ordering::Relaxed),
+                        terminal_aggregation_jobs_deleted.load(Ordering::Relaxed),
                         batches_deleted.load(Ordering::Relaxed),
                     ))
                 })
@@ -164,12 +338,35 @@ impl<C: Clock> GarbageCollector<C> {
         self.deleted_report_counter.add(client_reports_deleted, &[]);
         self.deleted_aggregation_job_counter
             .add(aggregation_jobs_deleted, &[]);
+        self.deleted_terminal_aggregation_job_counter
+            .add(terminal_aggregation_jobs_deleted, &[]);
         self.deleted_batch_counter.add(batches_deleted, &[]);
 
         Ok(())
     }
 }
 
+/// Returns the state that the garbage collector should move the given task to at time `now`, if
+/// any.
+fn next_task_state(task: &AggregatorTask, now: &Time) -> Option<TaskState> {
+    match task.state() {
+        TaskState::Active => task
+            .task_expiration()
+            .filter(|task_expiration| now.is_after(task_expiration))
+            .map(|_| TaskState::Expiring),
+        TaskState::Expiring => task
+            .task_expiration()
+            .zip(task.report_expiry_age())
+            .and_then(|(task_expiration, report_expiry_age)| {
+                task_expiration.add(report_expiry_age).ok()
+            })
+            .filter(|reports_expired_at| now.is_after(reports_expired_at))
+            .map(|_| TaskState::Expired),
+        TaskState::Purging => Some(TaskState::Deleted),
+        _ => None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::aggregator::garbage_collector::GarbageCollector;
@@ -182,7 +379,7 @@ mod tests {
             },
             test_util::ephemeral_datastore,
         },
-        task::{self, test_util::TaskBuilder},
+        task::{self, test_util::TaskBuilder, TaskState},
         test_util::noop_meter,
     };
     use janus_core::{
@@ -197,7 +394,7 @@ mod tests {
     };
     use prio::vdaf::dummy;
     use rand::random;
-    use std::sync::Arc;
+    use std::{collections::HashSet, sync::Arc};
 
     const OLDEST_ALLOWED_REPORT_TIMESTAMP: Time = Time::from_seconds_since_epoch(1000);
     const REPORT_EXPIRY_AGE: Duration = Duration::from_seconds(500);
@@ -299,6 +496,7 @@ mod tests {
             u64::try_from(i64::MAX).unwrap(),
             1,
             Some(1),
+            None,
         )
         .gc_tasks(Vec::from([*task.id()]))
         .await
@@ -463,6 +661,7 @@ mod tests {
             u64::try_from(i64::MAX).unwrap(),
             1,
             Some(1),
+            None,
         )
         .gc_tasks(Vec::from([*task.id()]))
         .await
@@ -625,6 +824,7 @@ mod tests {
             u64::try_from(i64::MAX).unwrap(),
             1,
             Some(1),
+            None,
         )
         .gc_tasks(Vec::from([*task.id()]))
         .await
@@ -803,6 +1003,7 @@ mod tests {
             u64::try_from(i64::MAX).unwrap(),
             1,
             Some(1),
+            None,
         )
         .gc_tasks(Vec::from([*task.id()]))
         .await
@@ -856,4 +1057,234 @@ mod tests {
         .await
         .unwrap();
     }
+
+    #[tokio::test]
+    async fn gc_task_retention_period() {
+        install_test_trace_subscriber();
+
+        let clock = MockClock::new(OLDEST_ALLOWED_REPORT_TIMESTAMP);
+        let ephemeral_datastore = ephemeral_datastore().await;
+        let ds = Arc::new(ephemeral_datastore.datastore(clock.clone()).await);
+        let vdaf = dummy::Vdaf::new(1);
+        const RETENTION_PERIOD: Duration = Duration::from_seconds(100);
+
+        let task = TaskBuilder::new(task::QueryType::TimeInterval, VdafInstance::Fake)
+            .with_report_expiry_age(Some(REPORT_EXPIRY_AGE))
+            .build()
+            .leader_view()
+            .unwrap();
+        let report = LeaderStoredReport::new_dummy(
+            *task.id(),
+            clock.now().sub(&Duration::from_seconds(2)).unwrap(),
+        );
+        ds.run_unnamed_tx(|tx| {
+            let (task, vdaf, report) = (task.clone(), vdaf.clone(), report.clone());
+            Box::pin(async move {
+                tx.put_aggregator_task(&task).await?;
+                tx.put_client_report(&vdaf, &report).await
+            })
+        })
+        .await
+        .unwrap();
+
+        let garbage_collector = GarbageCollector::new(
+            Arc::clone(&ds),
+            &noop_meter(),
+            u64::try_from(i64::MAX).unwrap(),
+            u64::try_from(i64::MAX).unwrap(),
+            u64::try_from(i64::MAX).unwrap(),
+            1,
+            Some(1),
+            None,
+        )
+        .with_retention_period(RETENTION_PERIOD);
+        let report_count = || async {
+            // Reset the clock to "undo" read-based expiry.
+            let now = clock.now();
+            clock.set(OLDEST_ALLOWED_REPORT_TIMESTAMP);
+            let count = ds
+                .run_unnamed_tx(|tx| {
+                    let (vdaf, task_id) = (vdaf.clone(), *task.id());
+                    Box::pin(async move {
+                        tx.get_client_reports_for_task::<0, dummy::Vdaf>(&vdaf, &task_id)
+                            .await
+                    })
+                })
+                .await
+                .unwrap()
+                .len();
+            clock.set(now);
+            count
+        };
+
+        // Once the report expires, it is kept for the retention period.
+        clock.advance(&REPORT_EXPIRY_AGE);
+        garbage_collector
+            .gc_tasks(Vec::from([*task.id()]))
+            .await
+            .unwrap();
+        assert_eq!(report_count().await, 1);
+
+        // After the retention period, the report is deleted.
+        clock.advance(&RETENTION_PERIOD);
+        garbage_collector
+            .gc_tasks(Vec::from([*task.id()]))
+            .await
+            .unwrap();
+        assert_eq!(report_count().await, 0);
+    }
+
+    #[tokio::test]
+    async fn task_lifecycle() {
+        install_test_trace_subscriber();
+
+        let clock = MockClock::new(OLDEST_ALLOWED_REPORT_TIMESTAMP);
+        let ephemeral_datastore = ephemeral_datastore().await;
+        let ds = Arc::new(ephemeral_datastore.datastore(clock.clone()).await);
+
+        let task_expiration = OLDEST_ALLOWED_REPORT_TIMESTAMP
+            .add(&Duration::from_seconds(100))
+            .unwrap();
+        let build_task = |state| {
+            TaskBuilder::new(task::QueryType::TimeInterval, VdafInstance::Fake)
+                .with_task_expiration(Some(task_expiration))
+                .with_report_expiry_age(Some(REPORT_EXPIRY_AGE))
+                .build()
+                .leader_view()
+                .unwrap()
+                .with_state(state)
+        };
+        let provisioned_task = build_task(TaskState::Provisioned);
+        let active_task = build_task(TaskState::Active);
+        let expiring_task = build_task(TaskState::Expiring);
+        let purging_task = build_task(TaskState::Purging);
+
+        ds.run_unnamed_tx(|tx| {
+            let tasks = Vec::from([
+                provisioned_task.clone(),
+                active_task.clone(),
+                expiring_task.clone(),
+                purging_task.clone(),
+            ]);
+            Box::pin(async move {
+                for task in tasks {
+                    tx.put_aggregator_task(&task).await?;
+                }
+                Ok(())
+            })
+        })
+        .await
+        .unwrap();
+
+        let garbage_collector = GarbageCollector::new(
+            Arc::clone(&ds),
+            &noop_meter(),
+            u64::try_from(i64::MAX).unwrap(),
+            u64::try_from(i64::MAX).unwrap(),
+            u64::try_from(i64::MAX).unwrap(),
+            1,
+            Some(1),
+            None,
+        );
+        let task_states = || async {
+            ds.run_unnamed_tx(|tx| {
+                Box::pin(async move {
+                    let mut states: Vec<_> = tx
+                        .get_aggregator_tasks()
+                        .await?
+                        .into_iter()
+                        .map(|task| (*task.id(), *task.state()))
+                        .collect();
+                    states.sort_by_key(|(task_id, _)| *task_id);
+                    Ok(states)
+                })
+            })
+            .await
+            .unwrap()
+        };
+        let expected_states = |states: &[(&task::AggregatorTask, TaskState)]| {
+            let mut states: Vec<_> = states
+                .iter()
+                .map(|(task, state)| (*task.id(), *state))
+                .collect();
+            states.sort_by_key(|(task_id, _)| *task_id);
+            states
+        };
+
+        // Before the task expiration, only the purging task moves.
+        garbage_collector.run().await.unwrap();
+        assert_eq!(
+            task_states().await,
+            expected_states(&[
+                (&provisioned_task, TaskState::Provisioned),
+                (&active_task, TaskState::Active),
+                (&expiring_task, TaskState::Expiring),
+            ])
+        );
+
+        // After the task expiration, the active task starts expiring.
+        clock.advance(&Duration::from_seconds(101));
+        garbage_collector.run().await.unwrap();
+        assert_eq!(
+            task_states().await,
+            expected_states(&[
+                (&provisioned_task, TaskState::Provisioned),
+                (&active_task, TaskState::Expiring),
+                (&expiring_task, TaskState::Expiring),
+            ])
+        );
+
+        // Once the reports it could have accepted have expired, an expiring task becomes expired.
+        clock.advance(&REPORT_EXPIRY_AGE);
+        garbage_collector.run().await.unwrap();
+        assert_eq!(
+            task_states().await,
+            expected_states(&[
+                (&provisioned_task, TaskState::Provisioned),
+                (&active_task, TaskState::Expired),
+                (&expiring_task, TaskState::Expired),
+            ])
+        );
+
+        // Every transition was recorded.
+        let events: HashSet<_> = ds
+            .run_unnamed_tx(|tx| Box::pin(async move { tx.get_task_lifecycle_events(None).await }))
+            .await
+            .unwrap()
+            .into_iter()
+            .filter(|event| event.from_state().is_some())
+            .map(|event| {
+                (
+                    *event.task_id(),
+                    event.from_state().copied(),
+                    *event.to_state(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            events,
+            HashSet::from([
+                (
+                    *purging_task.id(),
+                    Some(TaskState::Purging),
+                    TaskState::Deleted
+                ),
+                (
+                    *active_task.id(),
+                    Some(TaskState::Active),
+                    TaskState::Expiring
+                ),
+                (
+                    *active_task.id(),
+                    Some(TaskState::Expiring),
+                    TaskState::Expired
+                ),
+                (
+                    *expiring_task.id(),
+                    Some(TaskState::Expiring),
+                    TaskState::Expired
+                ),
+            ])
+        );
+    }
 }
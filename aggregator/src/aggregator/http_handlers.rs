@@ -1,8 +1,16 @@
 use super::{
     error::{ArcError, ReportRejectionReason},
-    Aggregator, Config, Error,
+    report_policy::ReportPolicy,
+    response_compression::ResponseCompression,
+    upload_queue::UploadQueue,
+    upload_router::UPLOAD_ROUTED_HEADER,
+    Aggregator, BatchReportCount, BatchReportCountQuery, CollectionPreflight, CollectionSummary,
+    Config, Error, PublicTaskStats,
+};
+use crate::{
+    aggregator::problem_details::{ProblemDetailsConnExt, ProblemDocument},
+    config::ResponseCompressionConfig,
 };
-use crate::aggregator::problem_details::{ProblemDetailsConnExt, ProblemDocument};
 use async_trait::async_trait;
 use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use janus_aggregator_core::{datastore::Datastore, instrumented};
@@ -17,7 +25,7 @@ use janus_messages::{
     codec::Decode, problem_type::DapProblemType, query_type::TimeInterval, taskprov::TaskConfig,
     AggregateShare, AggregateShareReq, AggregationJobContinueReq, AggregationJobId,
     AggregationJobInitializeReq, AggregationJobResp, Collection, CollectionJobId, CollectionReq,
-    HpkeConfigList, Report, TaskId,
+    Duration, HpkeConfigList, Interval, Report, TaskId, Time,
 };
 use opentelemetry::{
     metrics::{Counter, Meter, Unit},
@@ -30,7 +38,7 @@ use std::{borrow::Cow, time::Duration as StdDuration};
 use std::{io::Cursor, sync::Arc};
 use tracing::warn;
 use trillium::{Conn, Handler, KnownHeaderName, Status};
-use trillium_api::{api, State};
+use trillium_api::{api, Json, State};
 use trillium_caching_headers::CacheControlDirective;
 use trillium_opentelemetry::metrics;
 use trillium_router::{Router, RouterConnExt};
@@ -51,16 +59,19 @@ async fn run_error_handler(error: &Error, mut conn: Conn) -> Conn {
         Error::ReportRejected(rejection) => match rejection.reason() {
             ReportRejectionReason::OutdatedHpkeConfig(_) => conn.with_problem_document(
                 &ProblemDocument::new_dap(DapProblemType::OutdatedConfig)
-                    .with_task_id(rejection.task_id()),
+                    .with_task_id(rejection.task_id())
+                    .with_report_rejection_details(rejection.details()),
             ),
             ReportRejectionReason::TooEarly => conn.with_problem_document(
                 &ProblemDocument::new_dap(DapProblemType::ReportTooEarly)
-                    .with_task_id(rejection.task_id()),
+                    .with_task_id(rejection.task_id())
+                    .with_report_rejection_details(rejection.details()),
             ),
             _ => conn.with_problem_document(
                 &ProblemDocument::new_dap(DapProblemType::ReportRejected)
                     .with_task_id(rejection.task_id())
-                    .with_detail(rejection.reason().detail()),
+                    .with_detail(rejection.reason().detail())
+                    .with_report_rejection_details(rejection.details()),
             ),
         },
         Error::InvalidMessage(task_id, _) => {
@@ -120,6 +131,16 @@ async fn run_error_handler(error: &Error, mut conn: Conn) -> Conn {
         Error::BatchInvalid(task_id, _) => conn.with_problem_document(
             &ProblemDocument::new_dap(DapProblemType::BatchInvalid).with_task_id(task_id),
         ),
+        Error::BatchIntervalMisaligned(task_id, _, _) => conn.with_problem_document(
+            &ProblemDocument::new_dap(DapProblemType::BatchInvalid)
+                .with_task_id(task_id)
+                .with_detail(&error.to_string()),
+        ),
+        Error::BatchIntervalTooLong(task_id, _) => conn.with_problem_document(
+            &ProblemDocument::new_dap(DapProblemType::BatchInvalid)
+                .with_task_id(task_id)
+                .with_detail(&error.to_string()),
+        ),
         Error::BatchOverlap(task_id, _) => conn.with_problem_document(
             &ProblemDocument::new_dap(DapProblemType::BatchOverlap).with_task_id(task_id),
         ),
@@ -140,17 +161,36 @@ async fn run_error_handler(error: &Error, mut conn: Conn) -> Conn {
         | Error::Message(_)
         | Error::HttpClient(_)
         | Error::Http { .. }
-        | Error::TaskParameters(_) => conn.with_status(Status::InternalServerError),
+        | Error::AggregationJobInitUnauthorized(_)
+        | Error::TaskParameters(_)
+        | Error::UploadQueue(_) => conn.with_status(Status::InternalServerError),
         Error::AggregateShareRequestRejected(_, _) => conn.with_status(Status::BadRequest),
         Error::EmptyAggregation(task_id) => conn.with_problem_document(
             &ProblemDocument::new_dap(DapProblemType::InvalidMessage).with_task_id(task_id),
         ),
         Error::ForbiddenMutation { .. } => conn.with_status(Status::Conflict),
         Error::BadRequest(_) => conn.with_status(Status::BadRequest),
+        Error::NonConformantRequest(detail) => conn.with_problem_document(
+            &ProblemDocument::new(
+                "https://docs.divviup.org/references/janus-errors#non-conformant-request",
+                "The request does not conform to the DAP specification.",
+                Status::BadRequest,
+            )
+            .with_detail(detail),
+        ),
         Error::InvalidTask(task_id, _) => conn.with_problem_document(
             &ProblemDocument::new_dap(DapProblemType::InvalidTask).with_task_id(task_id),
         ),
         Error::DifferentialPrivacy(_) => conn.with_status(Status::InternalServerError),
+        Error::AggregateRequestTooLarge(task_id, _) => conn.with_problem_document(
+            &ProblemDocument::new(
+                "https://docs.divviup.org/references/janus-errors#aggregate-request-too-large",
+                "The aggregate request is too large to be processed.",
+                Status::PayloadTooLarge,
+            )
+            .with_task_id(task_id)
+            .with_detail(&error.to_string()),
+        ),
     };
 
     if matches!(conn.status(), Some(status) if status.is_server_error()) {
@@ -215,6 +255,84 @@ where
     }
 }
 
+/// A Trillium handler that checks requests to one endpoint against requirements of the DAP
+/// specification that are otherwise tolerated, and rejects non-conforming requests. This is only
+/// installed if [`Config::strict_conformance`] is set.
+#[derive(Clone, Copy)]
+struct StrictConformance {
+    /// Media type of successful responses, which the request's Accept header must allow.
+    response_media_type: Option<&'static str>,
+    /// Names of the query parameters the endpoint accepts.
+    query_parameters: &'static [&'static str],
+    /// Whether the request must carry an authentication token.
+    authenticated: bool,
+}
+
+impl StrictConformance {
+    fn check(&self, conn: &Conn) -> Result<(), Error> {
+        if let (Some(media_type), Some(accept)) = (
+            self.response_media_type,
+            conn.request_headers().get_str(KnownHeaderName::Accept),
+        ) {
+            let (type_, _) = media_type.split_once('/').unwrap_or((media_type, ""));
+            let acceptable = accept.split(',').any(|media_range| {
+                let media_range = media_range
+                    .split(';')
+                    .next()
+                    .unwrap_or_default()
+                    .trim()
+                    .to_ascii_lowercase();
+                media_range == media_type
+                    || media_range == "*/*"
+                    || media_range.strip_suffix("/*") == Some(type_)
+            });
+            if !acceptable {
+                return Err(Error::NonConformantRequest(format!(
+                    "Accept header {accept:?} does not allow the response media type {media_type}"
+                )));
+            }
+        }
+
+        let query: Vec<(String, String)> =
+            serde_urlencoded::from_str(conn.querystring()).map_err(|err| {
+                Error::NonConformantRequest(format!("couldn't parse query string: {err}"))
+            })?;
+        if let Some((name, _)) = query
+            .iter()
+            .find(|(name, _)| !self.query_parameters.contains(&name.as_str()))
+        {
+            return Err(Error::NonConformantRequest(format!(
+                "unknown query parameter {name:?}"
+            )));
+        }
+
+        if self.authenticated
+            && conn
+                .request_headers()
+                .get(KnownHeaderName::Authorization)
+                .is_none()
+            && conn.request_headers().get(DAP_AUTH_HEADER).is_none()
+        {
+            return Err(Error::NonConformantRequest(format!(
+                "missing authentication token, in either the Authorization or {DAP_AUTH_HEADER} \
+                 header"
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Handler for StrictConformance {
+    async fn run(&self, conn: Conn) -> Conn {
+        match self.check(&conn) {
+            Ok(()) => conn,
+            Err(err) => err.run(conn).await.halt(),
+        }
+    }
+}
+
 /// A Trillium handler that checks for state set when sending an error response, and updates an
 /// OpenTelemetry counter accordingly.
 struct StatusCounter(Counter<u64>);
@@ -275,6 +393,8 @@ impl Handler for StatusCounter {
 pub(crate) static AGGREGATION_JOB_ROUTE: &str =
     "tasks/:task_id/aggregation_jobs/:aggregation_job_id";
 pub(crate) static COLLECTION_JOB_ROUTE: &str = "tasks/:task_id/collection_jobs/:collection_job_id";
+pub(crate) static COLLECTION_SUMMARY_ROUTE: &str =
+    "tasks/:task_id/collection_jobs/:collection_job_id/summary";
 pub(crate) static AGGREGATE_SHARES_ROUTE: &str = "tasks/:task_id/aggregate_shares";
 
 /// Constructs a Trillium handler for the aggregator.
@@ -289,7 +409,79 @@ where
     C: Clock,
     R: Runtime + Send + Sync + 'static,
 {
-    let aggregator = Arc::new(Aggregator::new(datastore, clock, runtime, meter, cfg).await?);
+    let aggregator = Arc::new(Aggregator::new(datastore, None, clock, runtime, meter, cfg).await?);
+    aggregator_handler_with_aggregator(aggregator, meter).await
+}
+
+/// Constructs a DAP aggregator server which writes uploaded reports to a secondary datastore as
+/// well as to the primary datastore, while migrating to a new datastore.
+pub async fn aggregator_handler_with_secondary_datastore<C, R>(
+    datastore: Arc<Datastore<C>>,
+    secondary_datastore: Arc<Datastore<C>>,
+    clock: C,
+    runtime: R,
+    meter: &Meter,
+    cfg: Config,
+) -> Result<impl Handler, Error>
+where
+    C: Clock,
+    R: Runtime + Send + Sync + 'static,
+{
+    let aggregator = Arc::new(
+        Aggregator::new(
+            datastore,
+            Some(secondary_datastore),
+            clock,
+            runtime,
+            meter,
+            cfg,
+        )
+        .await?,
+    );
+    aggregator_handler_with_aggregator(aggregator, meter).await
+}
+
+/// Constructs a DAP aggregator server which sends uploaded reports to the provided queue, rather
+/// than writing them to the datastore.
+pub async fn aggregator_handler_with_upload_queue<C, R>(
+    datastore: Arc<Datastore<C>>,
+    clock: C,
+    runtime: R,
+    meter: &Meter,
+    cfg: Config,
+    upload_queue: Arc<dyn UploadQueue>,
+) -> Result<impl Handler, Error>
+where
+    C: Clock,
+    R: Runtime + Send + Sync + 'static,
+{
+    let aggregator = Arc::new(
+        Aggregator::new(datastore, None, clock, runtime, meter, cfg)
+            .await?
+            .with_upload_queue(upload_queue),
+    );
+    aggregator_handler_with_aggregator(aggregator, meter).await
+}
+
+/// Constructs a DAP aggregator server which evaluates each upload against the provided report
+/// policy, flagging or rejecting reports as it decides.
+pub async fn aggregator_handler_with_report_policy<C, R>(
+    datastore: Arc<Datastore<C>>,
+    clock: C,
+    runtime: R,
+    meter: &Meter,
+    cfg: Config,
+    report_policy: Arc<dyn ReportPolicy>,
+) -> Result<impl Handler, Error>
+where
+    C: Clock,
+    R: Runtime + Send + Sync + 'static,
+{
+    let aggregator = Arc::new(
+        Aggregator::new(datastore, None, clock, runtime, meter, cfg)
+            .await?
+            .with_report_policy(report_policy),
+    );
     aggregator_handler_with_aggregator(aggregator, meter).await
 }
 
@@ -297,7 +489,183 @@ async fn aggregator_handler_with_aggregator<C: Clock>(
     aggregator: Arc<Aggregator<C>>,
     meter: &Meter,
 ) -> Result<impl Handler, Error> {
-    Ok((
+    let compression = aggregator.cfg.response_compression;
+    let strict = aggregator.cfg.strict_conformance;
+    let collection_summaries_enabled = !aggregator.cfg.collector_hpke_keypairs.is_empty();
+    let public_task_stats_enabled = aggregator.cfg.public_task_stats;
+    let router = helper_routes::<C>(
+        Router::new().without_options_handling(),
+        compression,
+        strict,
+    )
+    .put(
+        "tasks/:task_id/reports",
+        (
+            strict_conformance(strict, None, &[], false),
+            instrumented(api(upload::<C>)),
+        ),
+    )
+    .with_route(
+        trillium::Method::Options,
+        "tasks/:task_id/reports",
+        upload_cors_preflight,
+    )
+    .put(
+        COLLECTION_JOB_ROUTE,
+        (
+            strict_conformance(strict, None, &[], true),
+            instrumented(api(collection_jobs_put::<C>)),
+        ),
+    )
+    .post(
+        COLLECTION_JOB_ROUTE,
+        (
+            strict_conformance(
+                strict,
+                Some(Collection::<TimeInterval>::MEDIA_TYPE),
+                &[],
+                true,
+            ),
+            instrumented(api(collection_jobs_post::<C>)),
+            ResponseCompression::new(compression.collection_jobs),
+        ),
+    )
+    .delete(
+        COLLECTION_JOB_ROUTE,
+        (
+            strict_conformance(strict, None, &[], true),
+            instrumented(api(collection_jobs_delete::<C>)),
+        ),
+    )
+    .get(
+        "tasks/:task_id/batch_report_count",
+        (
+            strict_conformance(
+                strict,
+                Some("application/json"),
+                &[
+                    "batch_interval_start",
+                    "batch_interval_duration",
+                    "batch_id",
+                ],
+                true,
+            ),
+            instrumented(api(batch_report_count::<C>)),
+        ),
+    )
+    .post(
+        "tasks/:task_id/collection_preflight",
+        (
+            strict_conformance(strict, Some("application/json"), &[], true),
+            instrumented(api(collection_preflight::<C>)),
+        ),
+    )
+    .get(
+        COLLECTION_SUMMARY_ROUTE,
+        collection_summaries_enabled.then(|| {
+            (
+                strict_conformance(strict, Some("application/json"), &[], true),
+                instrumented(api(collection_summary::<C>)),
+            )
+        }),
+    )
+    .get(
+        "tasks/:task_id/stats",
+        public_task_stats_enabled.then(|| {
+            (
+                strict_conformance(strict, Some("application/json"), &[], false),
+                instrumented(api(public_task_stats::<C>)),
+            )
+        }),
+    );
+    Ok(with_middleware(aggregator, meter, router))
+}
+
+/// Constructs a Trillium handler serving only the helper's endpoints of the DAP API. Requests to
+/// the leader's endpoints, i.e. uploads and collections, are not found.
+pub async fn helper_handler<C, R>(
+    datastore: Arc<Datastore<C>>,
+    clock: C,
+    runtime: R,
+    meter: &Meter,
+    cfg: Config,
+) -> Result<impl Handler, Error>
+where
+    C: Clock,
+    R: Runtime + Send + Sync + 'static,
+{
+    let aggregator = Arc::new(Aggregator::new(datastore, None, clock, runtime, meter, cfg).await?);
+    let router = helper_routes::<C>(
+        Router::new().without_options_handling(),
+        aggregator.cfg.response_compression,
+        aggregator.cfg.strict_conformance,
+    );
+    Ok(with_middleware(aggregator, meter, router))
+}
+
+/// Adds the routes for endpoints served by the helper, as well as the `hpke_config` endpoint, to
+/// `router`.
+fn helper_routes<C: Clock>(
+    router: Router,
+    compression: ResponseCompressionConfig,
+    strict: bool,
+) -> Router {
+    router
+        .get(
+            "hpke_config",
+            (
+                strict_conformance(
+                    strict,
+                    Some(HpkeConfigList::MEDIA_TYPE),
+                    &["task_id"],
+                    false,
+                ),
+                instrumented(api(hpke_config::<C>)),
+                ResponseCompression::new(compression.hpke_config),
+            ),
+        )
+        .with_route(
+            trillium::Method::Options,
+            "hpke_config",
+            hpke_config_cors_preflight,
+        )
+        .put(
+            AGGREGATION_JOB_ROUTE,
+            (
+                strict_conformance(strict, Some(AggregationJobResp::MEDIA_TYPE), &[], true),
+                instrumented(api(aggregation_jobs_put::<C>)),
+            ),
+        )
+        .post(
+            AGGREGATION_JOB_ROUTE,
+            (
+                strict_conformance(strict, Some(AggregationJobResp::MEDIA_TYPE), &[], true),
+                instrumented(api(aggregation_jobs_post::<C>)),
+            ),
+        )
+        .delete(
+            AGGREGATION_JOB_ROUTE,
+            (
+                strict_conformance(strict, None, &[], true),
+                instrumented(api(aggregation_jobs_delete::<C>)),
+            ),
+        )
+        .post(
+            AGGREGATE_SHARES_ROUTE,
+            (
+                strict_conformance(strict, Some(AggregateShare::MEDIA_TYPE), &[], true),
+                instrumented(api(aggregate_shares::<C>)),
+            ),
+        )
+}
+
+/// Wraps `router` with the aggregator's state and with handlers recording metrics.
+fn with_middleware<C: Clock>(
+    aggregator: Arc<Aggregator<C>>,
+    meter: &Meter,
+    router: Router,
+) -> impl Handler {
+    (
         State(aggregator),
         metrics(meter)
             .with_route(|conn| {
@@ -308,50 +676,24 @@ async fn aggregator_handler_with_aggregator<C: Clock>(
                 conn.state::<ErrorCode>()
                     .map(|error_code| Cow::Borrowed(error_code.0))
             }),
-        Router::new()
-            .without_options_handling()
-            .get("hpke_config", instrumented(api(hpke_config::<C>)))
-            .with_route(
-                trillium::Method::Options,
-                "hpke_config",
-                hpke_config_cors_preflight,
-            )
-            .put("tasks/:task_id/reports", instrumented(api(upload::<C>)))
-            .with_route(
-                trillium::Method::Options,
-                "tasks/:task_id/reports",
-                upload_cors_preflight,
-            )
-            .put(
-                AGGREGATION_JOB_ROUTE,
-                instrumented(api(aggregation_jobs_put::<C>)),
-            )
-            .post(
-                AGGREGATION_JOB_ROUTE,
-                instrumented(api(aggregation_jobs_post::<C>)),
-            )
-            .delete(
-                AGGREGATION_JOB_ROUTE,
-                instrumented(api(aggregation_jobs_delete::<C>)),
-            )
-            .put(
-                COLLECTION_JOB_ROUTE,
-                instrumented(api(collection_jobs_put::<C>)),
-            )
-            .post(
-                COLLECTION_JOB_ROUTE,
-                instrumented(api(collection_jobs_post::<C>)),
-            )
-            .delete(
-                COLLECTION_JOB_ROUTE,
-                instrumented(api(collection_jobs_delete::<C>)),
-            )
-            .post(
-                AGGREGATE_SHARES_ROUTE,
-                instrumented(api(aggregate_shares::<C>)),
-            ),
+        router,
         StatusCounter::new(meter),
-    ))
+    )
+}
+
+/// Returns a [`StrictConformance`] handler with the given requirements, if strict conformance
+/// checks are `enabled`.
+fn strict_conformance(
+    enabled: bool,
+    response_media_type: Option<&'static str>,
+    query_parameters: &'static [&'static str],
+    authenticated: bool,
+) -> Option<StrictConformance> {
+    enabled.then_some(StrictConformance {
+        response_media_type,
+        query_parameters,
+        authenticated,
+    })
 }
 
 /// Deserialization helper struct to extract a "task_id" parameter from a query string.
@@ -363,10 +705,14 @@ struct HpkeConfigQuery {
 }
 
 /// API handler for the "/hpke_config" GET endpoint.
+///
+/// Responses carry a weak entity tag derived from the HPKE configs, which changes whenever the
+/// advertised configs do, e.g. on key rotation. Requests whose If-None-Match header matches it are
+/// answered with 304 Not Modified and no body.
 async fn hpke_config<C: Clock>(
     conn: &mut Conn,
     State(aggregator): State<Arc<Aggregator<C>>>,
-) -> Result<(CacheControlDirective, EncodedBody<HpkeConfigList>), Error> {
+) -> Result<(CacheControlDirective, Option<EncodedBody<HpkeConfigList>>), Error> {
     let query = serde_urlencoded::from_str::<HpkeConfigQuery>(conn.querystring())
         .map_err(|err| Error::BadRequest(format!("couldn't parse query string: {err}")))?;
     let hpke_config_list = aggregator
@@ -381,12 +727,51 @@ async fn hpke_config<C: Clock>(
             .insert(KnownHeaderName::AccessControlAllowOrigin, origin);
     }
 
+    let cache_control = CacheControlDirective::MaxAge(StdDuration::from_secs(86400));
+    let etag = hpke_config_list_etag(&hpke_config_list)?;
+    let not_modified = if_none_match(conn, &etag);
+    conn.headers_mut().insert(KnownHeaderName::Etag, etag);
+    if not_modified {
+        conn.set_status(Status::NotModified);
+        conn.set_halted(true);
+        return Ok((cache_control, None));
+    }
+
     Ok((
-        CacheControlDirective::MaxAge(StdDuration::from_secs(86400)),
-        EncodedBody::new(hpke_config_list, HpkeConfigList::MEDIA_TYPE),
+        cache_control,
+        Some(EncodedBody::new(
+            hpke_config_list,
+            HpkeConfigList::MEDIA_TYPE,
+        )),
+    ))
+}
+
+/// Computes a weak entity tag for an HPKE config list. The order of the configs in the list is
+/// not significant, so it is ignored.
+fn hpke_config_list_etag(hpke_config_list: &HpkeConfigList) -> Result<String, Error> {
+    let mut hpke_configs = hpke_config_list.hpke_configs().to_vec();
+    hpke_configs.sort_by_key(|hpke_config| *hpke_config.id());
+    let encoded = HpkeConfigList::new(hpke_configs)
+        .get_encoded()
+        .map_err(Error::ResponseEncode)?;
+    Ok(format!(
+        "W/\"{}\"",
+        URL_SAFE_NO_PAD.encode(digest(&SHA256, &encoded))
     ))
 }
 
+/// Returns whether the request's If-None-Match header matches the given entity tag, using the
+/// weak comparison that RFC 9110 requires for If-None-Match.
+fn if_none_match(conn: &Conn, etag: &str) -> bool {
+    let Some(if_none_match) = conn.request_headers().get_str(KnownHeaderName::IfNoneMatch) else {
+        return false;
+    };
+    let opaque_tag = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    if_none_match
+        .split(',')
+        .any(|tag| tag.trim() == "*" || opaque_tag(tag) == opaque_tag(etag))
+}
+
 /// Handler for CORS preflight requests to "/hpke_config".
 async fn hpke_config_cors_preflight(mut conn: Conn) -> Conn {
     conn.headers_mut().insert(KnownHeaderName::Allow, "GET");
@@ -412,17 +797,79 @@ async fn upload<C: Clock>(
     validate_content_type(conn, Report::MEDIA_TYPE).map_err(Arc::new)?;
 
     let task_id = parse_task_id(conn).map_err(Arc::new)?;
-    aggregator.handle_upload(&task_id, &body).await?;
+    // Most tasks don't authenticate uploads, so a malformed token is only an error for tasks that
+    // do, which reject it as missing.
+    let auth_token = parse_auth_token(&task_id, conn).ok().flatten();
+
+    // Forward the upload to another leader, if it is routed elsewhere. Uploads that were already
+    // routed here by another instance are always handled locally.
+    if let Some(upload_router) = aggregator.upload_router.as_ref().filter(|_| {
+        conn.request_headers()
+            .get_str(UPLOAD_ROUTED_HEADER)
+            .is_none()
+    }) {
+        if let Some(response) = upload_router
+            .forward(
+                &task_id,
+                Report::MEDIA_TYPE,
+                conn.request_headers().get_str(KnownHeaderName::UserAgent),
+                auth_token.as_ref(),
+                &body,
+            )
+            .await
+        {
+            let status = Status::try_from(response.status).unwrap_or(Status::BadGateway);
+            if status.is_success() {
+                allow_upload_cors(conn);
+            } else {
+                // Relay the other leader's error, e.g. a problem document rejecting the report.
+                if let Some(content_type) = response.content_type {
+                    conn.headers_mut()
+                        .insert(KnownHeaderName::ContentType, content_type);
+                }
+                conn.set_body(response.body);
+            }
+            return Ok(status);
+        }
+    }
 
-    // Handle CORS, if the request header is present.
+    let result = aggregator
+        .handle_upload(&task_id, &body, auth_token.as_ref(), conn.request_headers())
+        .await;
+
+    // Uploads that fail before their task is found are not counted, so that requests naming
+    // arbitrary task IDs can't inflate the cardinality of the client and label metrics.
+    match result.as_ref().map_err(|err| &**err) {
+        Err(Error::MessageDecode(_) | Error::UnrecognizedTask(_)) => {}
+        result => {
+            aggregator.client_telemetry.record_upload(
+                &task_id,
+                conn.request_headers().get_str(KnownHeaderName::UserAgent),
+                result.is_ok(),
+            );
+            if let Some(upload_labels) = aggregator.upload_labels.as_ref() {
+                upload_labels.record_upload(
+                    &task_id,
+                    conn.request_headers().get_str(upload_labels.header()),
+                    result.is_ok(),
+                );
+            }
+        }
+    }
+    result?;
+
+    allow_upload_cors(conn);
+    Ok(Status::Ok)
+}
+
+/// Handles CORS for a successful upload, if the request header is present.
+fn allow_upload_cors(conn: &mut Conn) {
     if let Some(origin) = conn.request_headers().get(KnownHeaderName::Origin) {
         // Unconditionally allow CORS requests from all origins.
         let origin = origin.clone();
         conn.headers_mut()
             .insert(KnownHeaderName::AccessControlAllowOrigin, origin);
     }
-
-    Ok(Status::Ok)
 }
 
 /// Handler for CORS preflight requests to "/tasks/.../reports".
@@ -552,7 +999,26 @@ async fn collection_jobs_post<C: Clock>(
             conn.set_status(Status::Ok);
             conn.set_body(response_bytes);
         }
-        None => conn.set_status(Status::Accepted),
+        None => {
+            // Advise the collector of when the collection job is expected to be ready. This is
+            // best-effort: failing to produce an estimate should not fail the poll.
+            match aggregator
+                .collection_job_time_to_completion(&task_id, &collection_job_id)
+                .await
+            {
+                Ok(Some(time_to_completion)) => {
+                    conn.headers_mut().insert(
+                        KnownHeaderName::RetryAfter,
+                        time_to_completion.as_seconds().max(1).to_string(),
+                    );
+                }
+                Ok(None) => {}
+                Err(error) => {
+                    warn!(?error, "Couldn't estimate collection job completion time");
+                }
+            }
+            conn.set_status(Status::Accepted)
+        }
     }
     Ok(())
 }
@@ -588,6 +1054,112 @@ async fn aggregate_shares<C: Clock>(
     Ok(EncodedBody::new(share, AggregateShare::MEDIA_TYPE))
 }
 
+/// Deserialization helper struct to extract the batch from the query string of a
+/// "/tasks/.../batch_report_count" request.
+#[derive(Deserialize)]
+struct BatchReportCountParams {
+    /// The start of the batch interval in seconds since the UNIX epoch, for time-interval tasks.
+    #[serde(default)]
+    batch_interval_start: Option<u64>,
+    /// The duration of the batch interval in seconds, for time-interval tasks.
+    #[serde(default)]
+    batch_interval_duration: Option<u64>,
+    /// The batch ID in base64url-encoded form, for fixed-size tasks.
+    #[serde(default)]
+    batch_id: Option<String>,
+}
+
+/// API handler for the "/tasks/.../batch_report_count" GET endpoint.
+async fn batch_report_count<C: Clock>(
+    conn: &mut Conn,
+    State(aggregator): State<Arc<Aggregator<C>>>,
+) -> Result<Json<BatchReportCount>, Error> {
+    let task_id = parse_task_id(conn)?;
+    let auth_token = parse_auth_token(&task_id, conn)?;
+    let params = serde_urlencoded::from_str::<BatchReportCountParams>(conn.querystring())
+        .map_err(|err| Error::BadRequest(format!("couldn't parse query string: {err}")))?;
+    let query = match params {
+        BatchReportCountParams {
+            batch_interval_start: Some(start),
+            batch_interval_duration: Some(duration),
+            batch_id: None,
+        } => BatchReportCountQuery::TimeInterval(
+            Interval::new(
+                Time::from_seconds_since_epoch(start),
+                Duration::from_seconds(duration),
+            )
+            .map_err(|err| Error::BadRequest(format!("invalid batch interval: {err}")))?,
+        ),
+        BatchReportCountParams {
+            batch_interval_start: None,
+            batch_interval_duration: None,
+            batch_id: Some(batch_id),
+        } => BatchReportCountQuery::FixedSize(
+            batch_id
+                .parse()
+                .map_err(|_| Error::BadRequest("invalid BatchId".to_owned()))?,
+        ),
+        _ => {
+            return Err(Error::BadRequest(
+                "query string must contain either batch_interval_start and \
+                 batch_interval_duration, or batch_id"
+                    .to_owned(),
+            ))
+        }
+    };
+
+    let batch_report_count = aggregator
+        .handle_get_batch_report_count(&task_id, query, auth_token)
+        .await?;
+    Ok(Json(batch_report_count))
+}
+
+/// API handler for the "/tasks/.../collection_preflight" POST endpoint. The request body is a
+/// `CollectionReq`, which is checked as it would be by the "/tasks/.../collection_jobs/..." PUT
+/// endpoint, without creating a collection job.
+async fn collection_preflight<C: Clock>(
+    conn: &mut Conn,
+    (State(aggregator), body): (State<Arc<Aggregator<C>>>, Vec<u8>),
+) -> Result<Json<CollectionPreflight>, Error> {
+    validate_content_type(conn, CollectionReq::<TimeInterval>::MEDIA_TYPE)?;
+
+    let task_id = parse_task_id(conn)?;
+    let auth_token = parse_auth_token(&task_id, conn)?;
+    let preflight = aggregator
+        .handle_collection_preflight(&task_id, &body, auth_token)
+        .await?;
+    Ok(Json(preflight))
+}
+
+/// API handler for the "/tasks/.../collection_jobs/.../summary" GET endpoint. This endpoint is only
+/// routed if the aggregator is configured with collector HPKE keypairs.
+async fn collection_summary<C: Clock>(
+    conn: &mut Conn,
+    State(aggregator): State<Arc<Aggregator<C>>>,
+) -> Result<Option<Json<CollectionSummary>>, Error> {
+    let task_id = parse_task_id(conn)?;
+    let collection_job_id = parse_collection_job_id(conn)?;
+    let auth_token = parse_auth_token(&task_id, conn)?;
+    let summary = aggregator
+        .handle_get_collection_summary(&task_id, &collection_job_id, auth_token)
+        .await?;
+    if summary.is_none() {
+        conn.set_status(Status::Accepted);
+    }
+    Ok(summary.map(Json))
+}
+
+/// API handler for the "/tasks/.../stats" GET endpoint. This endpoint is only routed if the
+/// aggregator is configured to serve public task statistics, and requires no authentication.
+async fn public_task_stats<C: Clock>(
+    conn: &mut Conn,
+    State(aggregator): State<Arc<Aggregator<C>>>,
+) -> Result<Json<PublicTaskStats>, Error> {
+    let task_id = parse_task_id(conn)?;
+    let stats = aggregator.handle_get_public_task_stats(&task_id).await?;
+    Ok(Json(stats))
+}
+
 /// Check the request's Content-Type header, and return an error if it is missing or not equal to
 /// the expected value.
 fn validate_content_type(conn: &Conn, expected_media_type: &'static str) -> Result<(), Error> {
@@ -770,8 +1342,11 @@ mod tests {
             empty_batch_aggregations,
             error::{BatchMismatch, ReportRejectionReason},
             http_handlers::{
-                aggregator_handler, aggregator_handler_with_aggregator,
-                test_util::{decode_response_body, setup_http_handler_test, take_problem_details},
+                aggregator_handler, aggregator_handler_with_aggregator, helper_handler,
+                test_util::{
+                    decode_response_body, setup_http_handler_test, take_problem_details,
+                    take_response_body,
+                },
             },
             test_util::{default_aggregator_config, BATCH_AGGREGATION_SHARD_COUNT},
             tests::{
@@ -780,16 +1355,18 @@ mod tests {
             },
             Config,
         },
-        config::TaskprovConfig,
+        config::{ResponseCompressionConfig, TaskprovConfig},
     };
     use assert_matches::assert_matches;
+    use flate2::read::GzDecoder;
     use futures::future::try_join_all;
     use janus_aggregator_core::{
         datastore::{
             models::{
                 merge_batch_aggregations_by_batch, AggregationJob, AggregationJobState,
                 BatchAggregation, BatchAggregationState, CollectionJob, CollectionJobState,
-                HpkeKeyState, ReportAggregation, ReportAggregationState,
+                HpkeKeyState, LeaderStoredReport, ReportAggregation, ReportAggregationState,
+                TaskUploadCounter,
             },
             test_util::EphemeralDatastoreBuilder,
         },
@@ -816,11 +1393,12 @@ mod tests {
         query_type::{FixedSize, TimeInterval},
         AggregateShare as AggregateShareMessage, AggregateShareAad, AggregateShareReq,
         AggregationJobContinueReq, AggregationJobId, AggregationJobInitializeReq,
-        AggregationJobResp, AggregationJobStep, BatchSelector, Collection, CollectionJobId,
-        CollectionReq, Duration, Extension, ExtensionType, HpkeCiphertext, HpkeConfigId,
-        HpkeConfigList, InputShareAad, Interval, PartialBatchSelector, PlaintextInputShare,
-        PrepareContinue, PrepareError, PrepareInit, PrepareResp, PrepareStepResult, Query, Report,
-        ReportId, ReportIdChecksum, ReportMetadata, ReportShare, Role, TaskId, Time,
+        AggregationJobResp, AggregationJobStep, BatchId, BatchSelector, Collection,
+        CollectionJobId, CollectionReq, Duration, Extension, ExtensionType, HpkeCiphertext,
+        HpkeConfigId, HpkeConfigList, InputShareAad, Interval, PartialBatchSelector,
+        PlaintextInputShare, PrepareContinue, PrepareError, PrepareInit, PrepareResp,
+        PrepareStepResult, Query, Report, ReportId, ReportIdChecksum, ReportMetadata, ReportShare,
+        Role, TaskId, Time,
     };
     use prio::{
         codec::{Decode, Encode},
@@ -835,7 +1413,12 @@ mod tests {
     };
     use rand::random;
     use serde_json::json;
-    use std::{collections::HashMap, sync::Arc, time::Duration as StdDuration};
+    use std::{
+        collections::{BTreeSet, HashMap},
+        io::Read,
+        sync::Arc,
+        time::{Duration as StdDuration, Instant},
+    };
     use tokio::time::sleep;
     use trillium::{KnownHeaderName, Status};
     use trillium_testing::{
@@ -898,20 +1481,152 @@ mod tests {
             "content-type" => (HpkeConfigList::MEDIA_TYPE),
         );
 
+        let etag = test_conn
+            .response_headers()
+            .get_str(KnownHeaderName::Etag)
+            .unwrap()
+            .to_string();
+        assert!(etag.starts_with("W/\""), "{etag}");
+
         let hpke_config_list: HpkeConfigList = decode_response_body(&mut test_conn).await;
         assert_eq!(
             hpke_config_list.hpke_configs(),
             &[want_hpke_key.config().clone()]
         );
         check_hpke_config_is_usable(&hpke_config_list, &want_hpke_key);
+
+        // Conditional requests for an unchanged config are answered without a body.
+        for if_none_match in [etag.as_str(), etag.trim_start_matches("W/"), "\"other\", *"] {
+            let mut test_conn = get(&format!("/hpke_config?task_id={}", task.id()))
+                .with_request_header(KnownHeaderName::IfNoneMatch, if_none_match.to_string())
+                .run_async(&handler)
+                .await;
+            assert_eq!(test_conn.status(), Some(Status::NotModified));
+            assert_headers!(
+                &test_conn,
+                "cache-control" => "max-age=86400",
+                "etag" => (etag.as_str()),
+            );
+            assert!(test_conn.take_response_body().is_none());
+        }
+
+        // Conditional requests for another config are answered as usual.
+        let test_conn = get(&format!("/hpke_config?task_id={}", task.id()))
+            .with_request_header(KnownHeaderName::IfNoneMatch, "W/\"other\"")
+            .run_async(&handler)
+            .await;
+        assert_eq!(test_conn.status(), Some(Status::Ok));
     }
 
     #[tokio::test]
-    async fn global_hpke_config() {
-        let (clock, _ephemeral_datastore, datastore, _) = setup_http_handler_test().await;
+    async fn hpke_config_compression() {
+        install_test_trace_subscriber();
+        let clock = MockClock::default();
+        let ephemeral_datastore = EphemeralDatastoreBuilder::new().build().await;
+        let datastore = Arc::new(ephemeral_datastore.datastore(clock.clone()).await);
+        let handler = aggregator_handler(
+            datastore.clone(),
+            clock.clone(),
+            TestRuntime::default(),
+            &noop_meter(),
+            Config {
+                response_compression: ResponseCompressionConfig {
+                    hpke_config: true,
+                    collection_jobs: false,
+                },
+                ..default_aggregator_config()
+            },
+        )
+        .await
+        .unwrap();
 
-        // Insert an HPKE config, i.e. start the application with a keypair already
-        // in the database.
+        let task = TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Prio3Count)
+            .build()
+            .leader_view()
+            .unwrap();
+        datastore.put_aggregator_task(&task).await.unwrap();
+        let want_hpke_config_list =
+            HpkeConfigList::new(Vec::from([task.current_hpke_key().config().clone()]));
+        let url = format!("/hpke_config?task_id={}", task.id());
+
+        // A client accepting zstd gets a zstd-compressed response.
+        let mut test_conn = get(&url)
+            .with_request_header(KnownHeaderName::AcceptEncoding, "gzip, zstd")
+            .run_async(&handler)
+            .await;
+        assert_eq!(test_conn.status(), Some(Status::Ok));
+        assert_headers!(
+            &test_conn,
+            "content-type" => (HpkeConfigList::MEDIA_TYPE),
+            "content-encoding" => "zstd",
+            "vary" => "Accept-Encoding",
+        );
+        let body = zstd::decode_all(take_response_body(&mut test_conn).await.as_ref()).unwrap();
+        assert_eq!(
+            HpkeConfigList::get_decoded(&body).unwrap(),
+            want_hpke_config_list
+        );
+
+        // A client accepting only gzip gets a gzip-compressed response.
+        let mut test_conn = get(&url)
+            .with_request_header(KnownHeaderName::AcceptEncoding, "gzip")
+            .run_async(&handler)
+            .await;
+        assert_eq!(test_conn.status(), Some(Status::Ok));
+        assert_headers!(&test_conn, "content-encoding" => "gzip");
+        let mut body = Vec::new();
+        GzDecoder::new(take_response_body(&mut test_conn).await.as_ref())
+            .read_to_end(&mut body)
+            .unwrap();
+        assert_eq!(
+            HpkeConfigList::get_decoded(&body).unwrap(),
+            want_hpke_config_list
+        );
+
+        // A client accepting neither coding gets an uncompressed response.
+        let mut test_conn = get(&url)
+            .with_request_header(KnownHeaderName::AcceptEncoding, "br")
+            .run_async(&handler)
+            .await;
+        assert_eq!(test_conn.status(), Some(Status::Ok));
+        assert!(test_conn
+            .response_headers()
+            .get(KnownHeaderName::ContentEncoding)
+            .is_none());
+        let hpke_config_list: HpkeConfigList = decode_response_body(&mut test_conn).await;
+        assert_eq!(hpke_config_list, want_hpke_config_list);
+
+        // Error responses are not compressed.
+        let test_conn = get(&format!("/hpke_config?task_id={}", random::<TaskId>()))
+            .with_request_header(KnownHeaderName::AcceptEncoding, "zstd")
+            .run_async(&handler)
+            .await;
+        assert_eq!(test_conn.status(), Some(Status::BadRequest));
+        assert!(test_conn
+            .response_headers()
+            .get(KnownHeaderName::ContentEncoding)
+            .is_none());
+
+        // Responses are not compressed if compression is not enabled for the route.
+        let (_, _ephemeral_datastore, datastore, handler) = setup_http_handler_test().await;
+        datastore.put_aggregator_task(&task).await.unwrap();
+        let test_conn = get(&url)
+            .with_request_header(KnownHeaderName::AcceptEncoding, "zstd")
+            .run_async(&handler)
+            .await;
+        assert_eq!(test_conn.status(), Some(Status::Ok));
+        assert!(test_conn
+            .response_headers()
+            .get(KnownHeaderName::ContentEncoding)
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn global_hpke_config() {
+        let (clock, _ephemeral_datastore, datastore, _) = setup_http_handler_test().await;
+
+        // Insert an HPKE config, i.e. start the application with a keypair already
+        // in the database.
         let first_hpke_keypair = generate_test_hpke_config_and_private_key_with_id(1);
         datastore
             .run_unnamed_tx(|tx| {
@@ -930,6 +1645,7 @@ mod tests {
         let aggregator = Arc::new(
             crate::aggregator::Aggregator::new(
                 datastore.clone(),
+                None,
                 clock.clone(),
                 TestRuntime::default(),
                 &noop_meter(),
@@ -950,6 +1666,11 @@ mod tests {
             "cache-control" => "max-age=86400",
             "content-type" => (HpkeConfigList::MEDIA_TYPE),
         );
+        let first_etag = test_conn
+            .response_headers()
+            .get_str(KnownHeaderName::Etag)
+            .unwrap()
+            .to_string();
         let hpke_config_list: HpkeConfigList = decode_response_body(&mut test_conn).await;
         assert_eq!(
             hpke_config_list.hpke_configs(),
@@ -987,8 +1708,16 @@ mod tests {
             .await
             .unwrap();
         aggregator.refresh_caches().await.unwrap();
-        let mut test_conn = get("/hpke_config").run_async(&handler).await;
+        let mut test_conn = get("/hpke_config")
+            .with_request_header(KnownHeaderName::IfNoneMatch, first_etag.clone())
+            .run_async(&handler)
+            .await;
+        // Cached configs are refetched once another config is advertised.
         assert_eq!(test_conn.status(), Some(Status::Ok));
+        assert_ne!(
+            test_conn.response_headers().get_str(KnownHeaderName::Etag),
+            Some(first_etag.as_str())
+        );
         let hpke_config_list: HpkeConfigList = decode_response_body(&mut test_conn).await;
         // Unordered comparison.
         assert_eq!(
@@ -1083,6 +1812,7 @@ mod tests {
         let aggregator = Arc::new(
             crate::aggregator::Aggregator::new(
                 datastore.clone(),
+                None,
                 clock.clone(),
                 TestRuntime::default(),
                 &noop_meter(),
@@ -1179,6 +1909,7 @@ mod tests {
             desired_title: &str,
             desired_task_id: &TaskId,
             desired_detail: Option<&str>,
+            desired_extensions: serde_json::Value,
         ) {
             let mut desired_response = json!({
                 "status": desired_status as u16,
@@ -1192,6 +1923,10 @@ mod tests {
                     .unwrap()
                     .insert("detail".to_string(), json!(detail));
             }
+            desired_response
+                .as_object_mut()
+                .unwrap()
+                .extend(desired_extensions.as_object().unwrap().clone());
             assert_eq!(test_conn.status(), Some(desired_status));
             assert_eq!(take_problem_details(test_conn).await, desired_response);
         }
@@ -1263,6 +1998,13 @@ mod tests {
             "Report could not be processed.",
             task.id(),
             Some(ReportRejectionReason::Expired.detail()),
+            json!({
+                "earliest_acceptable_time": clock
+                    .now()
+                    .sub(&Duration::from_seconds(REPORT_EXPIRY_AGE))
+                    .unwrap()
+                    .as_seconds_since_epoch(),
+            }),
         )
         .await;
 
@@ -1297,6 +2039,13 @@ mod tests {
             "The message was generated using an outdated configuration.",
             task.id(),
             None,
+            json!({
+                "hpke_config_ids": leader_task
+                    .hpke_keys()
+                    .keys()
+                    .map(|id| u8::from(*id))
+                    .collect::<BTreeSet<_>>(),
+            }),
         )
         .await;
 
@@ -1325,6 +2074,14 @@ mod tests {
             "Report could not be processed because it arrived too early.",
             task.id(),
             None,
+            json!({
+                "tolerable_clock_skew": 600,
+                "latest_acceptable_time": clock
+                    .now()
+                    .add(&Duration::from_minutes(10).unwrap())
+                    .unwrap()
+                    .as_seconds_since_epoch(),
+            }),
         )
         .await;
 
@@ -1353,6 +2110,12 @@ mod tests {
             "Report could not be processed.",
             task_expire_soon.id(),
             Some(ReportRejectionReason::TaskExpired.detail()),
+            json!({
+                "latest_acceptable_time": task_expire_soon
+                    .task_expiration()
+                    .unwrap()
+                    .as_seconds_since_epoch(),
+            }),
         )
         .await;
 
@@ -1381,6 +2144,7 @@ mod tests {
             "Report could not be processed.",
             leader_task.id(),
             Some(ReportRejectionReason::DecodeFailure.detail()),
+            json!({}),
         )
         .await;
 
@@ -1406,6 +2170,7 @@ mod tests {
             "Report could not be processed.",
             leader_task.id(),
             Some(ReportRejectionReason::DecryptFailure.detail()),
+            json!({}),
         )
         .await;
 
@@ -1446,6 +2211,7 @@ mod tests {
             "Report could not be processed.",
             leader_task.id(),
             Some(ReportRejectionReason::DecodeFailure.detail()),
+            json!({}),
         )
         .await;
 
@@ -1660,8 +2426,8 @@ mod tests {
             problem_details,
             json!({
                 "status": 400,
-                "type": "urn:ietf:params:ppm:dap:error:unrecognizedTask",
-                "title": "An endpoint received a message with an unknown task ID.",
+                "type": "urn:ietf:params:ppm:dap:error:unauthorizedRequest",
+                "title": "The request's authorization is not valid.",
                 "taskid": format!("{}", task.id()),
             })
         );
@@ -4524,8 +5290,8 @@ mod tests {
             take_problem_details(&mut test_conn).await,
             json!({
                 "status": Status::BadRequest as u16,
-                "type": "urn:ietf:params:ppm:dap:error:unrecognizedTask",
-                "title": "An endpoint received a message with an unknown task ID.",
+                "type": "urn:ietf:params:ppm:dap:error:unauthorizedRequest",
+                "title": "The request's authorization is not valid.",
                 "taskid": format!("{}", test_case.task.id()),
             })
         );
@@ -4560,6 +5326,99 @@ mod tests {
                 "type": "urn:ietf:params:ppm:dap:error:batchInvalid",
                 "title": "The batch implied by the query is invalid.",
                 "taskid": format!("{}", test_case.task.id()),
+                "detail": format!(
+                    "task {}: invalid batch interval {}: batch interval is shorter than the \
+                     task's time precision",
+                    test_case.task.id(),
+                    request.query().batch_interval(),
+                ),
+            })
+        );
+
+        // A batch interval which does not start on a multiple of the time precision is rejected
+        // with a different explanation.
+        let request = CollectionReq::new(
+            Query::new_time_interval(
+                Interval::new(
+                    Time::from_seconds_since_epoch(1),
+                    *test_case.task.time_precision(),
+                )
+                .unwrap(),
+            ),
+            dummy::AggregationParam::default().get_encoded().unwrap(),
+        );
+
+        let mut test_conn = test_case.put_collection_job(&random(), &request).await;
+
+        assert_eq!(test_conn.status(), Some(Status::BadRequest));
+        assert_eq!(
+            take_problem_details(&mut test_conn).await,
+            json!({
+                "status": Status::BadRequest as u16,
+                "type": "urn:ietf:params:ppm:dap:error:batchInvalid",
+                "title": "The batch implied by the query is invalid.",
+                "taskid": format!("{}", test_case.task.id()),
+                "detail": format!(
+                    "task {}: invalid batch interval {}: batch interval start is not a multiple \
+                     of the task's time precision",
+                    test_case.task.id(),
+                    request.query().batch_interval(),
+                ),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn collection_job_put_request_batch_interval_too_long() {
+        let mut test_case =
+            setup_collection_job_test_case(Role::Leader, QueryType::TimeInterval).await;
+        test_case.handler = Box::new(
+            aggregator_handler(
+                Arc::clone(&test_case.datastore),
+                MockClock::default(),
+                TestRuntime::default(),
+                &noop_meter(),
+                Config {
+                    batch_aggregation_shard_count: BATCH_AGGREGATION_SHARD_COUNT,
+                    max_collection_interval_time_precisions: Some(1),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap(),
+        );
+
+        let collection_job_id: CollectionJobId = random();
+        let request = CollectionReq::new(
+            Query::new_time_interval(
+                Interval::new(
+                    Time::from_seconds_since_epoch(0),
+                    // Collect request will be rejected because batch interval spans two time
+                    // precisions.
+                    Duration::from_seconds(test_case.task.time_precision().as_seconds() * 2),
+                )
+                .unwrap(),
+            ),
+            dummy::AggregationParam::default().get_encoded().unwrap(),
+        );
+
+        let mut test_conn = test_case
+            .put_collection_job(&collection_job_id, &request)
+            .await;
+
+        assert_eq!(test_conn.status(), Some(Status::BadRequest));
+        assert_eq!(
+            take_problem_details(&mut test_conn).await,
+            json!({
+                "status": Status::BadRequest as u16,
+                "type": "urn:ietf:params:ppm:dap:error:batchInvalid",
+                "title": "The batch implied by the query is invalid.",
+                "taskid": format!("{}", test_case.task.id()),
+                "detail": format!(
+                    "task {}: batch interval spans more than 1 time precisions; collect shorter \
+                     batch intervals and combine their results instead",
+                    test_case.task.id()
+                ),
             })
         );
     }
@@ -4609,40 +5468,529 @@ mod tests {
         let leader_task = task.leader_view().unwrap();
         datastore.put_aggregator_task(&leader_task).await.unwrap();
 
-        let collection_job_id: CollectionJobId = random();
-        let request = CollectionReq::new(
-            Query::new_time_interval(
-                Interval::new(
-                    Time::from_seconds_since_epoch(0),
-                    Duration::from_seconds(task.time_precision().as_seconds()),
-                )
-                .unwrap(),
+        let collection_job_id: CollectionJobId = random();
+        let request = CollectionReq::new(
+            Query::new_time_interval(
+                Interval::new(
+                    Time::from_seconds_since_epoch(0),
+                    Duration::from_seconds(task.time_precision().as_seconds()),
+                )
+                .unwrap(),
+            ),
+            dummy::AggregationParam::default().get_encoded().unwrap(),
+        );
+
+        let (header, value) = task.collector_auth_token().request_authentication();
+        let mut test_conn = put(task.collection_job_uri(&collection_job_id).unwrap().path())
+            .with_request_header(header, value)
+            .with_request_header(
+                KnownHeaderName::ContentType,
+                CollectionReq::<TimeInterval>::MEDIA_TYPE,
+            )
+            .with_request_body(request.get_encoded().unwrap())
+            .run_async(&handler)
+            .await;
+
+        // Collect request will be rejected because there are no reports in the batch interval
+        assert_eq!(test_conn.status(), Some(Status::BadRequest));
+        assert_eq!(
+            take_problem_details(&mut test_conn).await,
+            json!({
+                "status": Status::BadRequest as u16,
+                "type": "urn:ietf:params:ppm:dap:error:invalidBatchSize",
+                "title": "The number of reports included in the batch is invalid.",
+                "taskid": format!("{}", task.id()),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn batch_report_count() {
+        let (clock, _ephemeral_datastore, datastore, handler) = setup_http_handler_test().await;
+
+        let task = TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Fake)
+            .with_min_batch_size(2)
+            .build();
+        let leader_task = task.leader_view().unwrap();
+        datastore.put_aggregator_task(&leader_task).await.unwrap();
+
+        let batch_interval = Interval::new(
+            clock
+                .now()
+                .to_batch_interval_start(task.time_precision())
+                .unwrap(),
+            *task.time_precision(),
+        )
+        .unwrap();
+        let uri = format!(
+            "/tasks/{}/batch_report_count?batch_interval_start={}&batch_interval_duration={}",
+            task.id(),
+            batch_interval.start().as_seconds_since_epoch(),
+            batch_interval.duration().as_seconds(),
+        );
+        let (header, value) = task.collector_auth_token().request_authentication();
+
+        for (report_count, valid_batch_size) in [(1, false), (2, true)] {
+            let report = LeaderStoredReport::new_dummy(*task.id(), *batch_interval.start());
+            datastore
+                .run_unnamed_tx(|tx| {
+                    let report = report.clone();
+                    Box::pin(
+                        async move { tx.put_client_report(&dummy::Vdaf::new(1), &report).await },
+                    )
+                })
+                .await
+                .unwrap();
+
+            let mut test_conn = get(&uri)
+                .with_request_header(header, value.clone())
+                .run_async(&handler)
+                .await;
+            assert_eq!(test_conn.status(), Some(Status::Ok));
+            assert_eq!(
+                serde_json::from_slice::<serde_json::Value>(
+                    &take_response_body(&mut test_conn).await
+                )
+                .unwrap(),
+                json!({
+                    "report_count": report_count,
+                    "min_batch_size": 2,
+                    "valid_batch_size": valid_batch_size,
+                })
+            );
+        }
+
+        // Collector authentication is required.
+        let (header, value) = random::<AuthenticationToken>().request_authentication();
+        let mut test_conn = get(&uri)
+            .with_request_header(header, value)
+            .run_async(&handler)
+            .await;
+        assert_eq!(test_conn.status(), Some(Status::BadRequest));
+        assert_eq!(
+            take_problem_details(&mut test_conn).await,
+            json!({
+                "status": Status::BadRequest as u16,
+                "type": "urn:ietf:params:ppm:dap:error:unauthorizedRequest",
+                "title": "The request's authorization is not valid.",
+                "taskid": format!("{}", task.id()),
+            })
+        );
+
+        // The batch must be valid for the task's query type.
+        let (header, value) = task.collector_auth_token().request_authentication();
+        let batch_id: BatchId = random();
+        let test_conn = get(&format!(
+            "/tasks/{}/batch_report_count?batch_id={batch_id}",
+            task.id()
+        ))
+        .with_request_header(header, value.clone())
+        .run_async(&handler)
+        .await;
+        assert_eq!(test_conn.status(), Some(Status::BadRequest));
+
+        let test_conn = get(&format!(
+            "/tasks/{}/batch_report_count?batch_interval_start=1&batch_interval_duration=1",
+            task.id()
+        ))
+        .with_request_header(header, value)
+        .run_async(&handler)
+        .await;
+        assert_eq!(test_conn.status(), Some(Status::BadRequest));
+    }
+
+    #[tokio::test]
+    async fn collection_preflight() {
+        let (clock, _ephemeral_datastore, datastore, handler) = setup_http_handler_test().await;
+
+        let task = TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Fake)
+            .with_min_batch_size(2)
+            .build();
+        let leader_task = task.leader_view().unwrap();
+        datastore.put_aggregator_task(&leader_task).await.unwrap();
+
+        let batch_interval = Interval::new(
+            clock
+                .now()
+                .to_batch_interval_start(task.time_precision())
+                .unwrap(),
+            *task.time_precision(),
+        )
+        .unwrap();
+        let uri = format!("/tasks/{}/collection_preflight", task.id());
+        let (header, value) = task.collector_auth_token().request_authentication();
+        let preflight = |batch_interval: Interval| {
+            post(&uri)
+                .with_request_header(header, value.clone())
+                .with_request_header(
+                    KnownHeaderName::ContentType,
+                    CollectionReq::<TimeInterval>::MEDIA_TYPE,
+                )
+                .with_request_body(
+                    CollectionReq::new(
+                        Query::new_time_interval(batch_interval),
+                        dummy::AggregationParam::default().get_encoded().unwrap(),
+                    )
+                    .get_encoded()
+                    .unwrap(),
+                )
+                .run_async(&handler)
+        };
+
+        for report_count in [1, 2] {
+            let report = LeaderStoredReport::new_dummy(*task.id(), *batch_interval.start());
+            datastore
+                .run_unnamed_tx(|tx| {
+                    let report = report.clone();
+                    Box::pin(
+                        async move { tx.put_client_report(&dummy::Vdaf::new(1), &report).await },
+                    )
+                })
+                .await
+                .unwrap();
+
+            let mut test_conn = preflight(batch_interval).await;
+            assert_eq!(test_conn.status(), Some(Status::Ok));
+            let expected = if report_count < 2 {
+                json!({
+                    "accepted": false,
+                    "error": "invalid_batch_size",
+                    "detail": format!("task {}: invalid number of reports (1)", task.id()),
+                    "report_count": report_count,
+                    "min_batch_size": 2,
+                })
+            } else {
+                json!({
+                    "accepted": true,
+                    "batch": batch_interval.to_string(),
+                    "report_count": report_count,
+                    "min_batch_size": 2,
+                })
+            };
+            assert_eq!(
+                serde_json::from_slice::<serde_json::Value>(
+                    &take_response_body(&mut test_conn).await
+                )
+                .unwrap(),
+                expected
+            );
+        }
+
+        // Batch intervals that aren't valid for the task are reported as such.
+        let misaligned_interval = Interval::new(
+            batch_interval
+                .start()
+                .add(&Duration::from_seconds(1))
+                .unwrap(),
+            *task.time_precision(),
+        )
+        .unwrap();
+        let mut test_conn = preflight(misaligned_interval).await;
+        assert_eq!(test_conn.status(), Some(Status::Ok));
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&take_response_body(&mut test_conn).await)
+                .unwrap(),
+            json!({
+                "accepted": false,
+                "error": "batch_interval_misaligned",
+                "detail": format!(
+                    "task {}: invalid batch interval {misaligned_interval}: batch interval start \
+                     is not a multiple of the task's time precision",
+                    task.id()
+                ),
+                "min_batch_size": 2,
+            })
+        );
+
+        // No collection job is created.
+        let collection_jobs = datastore
+            .run_unnamed_tx(|tx| {
+                let (task_id, batch_interval) = (*task.id(), batch_interval);
+                Box::pin(async move {
+                    tx.get_collection_jobs_intersecting_interval::<0, dummy::Vdaf>(
+                        &dummy::Vdaf::new(1),
+                        &task_id,
+                        &batch_interval,
+                    )
+                    .await
+                })
+            })
+            .await
+            .unwrap();
+        assert!(collection_jobs.is_empty());
+
+        // Collector authentication is required.
+        let (header, value) = random::<AuthenticationToken>().request_authentication();
+        let test_conn = post(&uri)
+            .with_request_header(header, value)
+            .with_request_header(
+                KnownHeaderName::ContentType,
+                CollectionReq::<TimeInterval>::MEDIA_TYPE,
+            )
+            .with_request_body(
+                CollectionReq::new(
+                    Query::new_time_interval(batch_interval),
+                    dummy::AggregationParam::default().get_encoded().unwrap(),
+                )
+                .get_encoded()
+                .unwrap(),
+            )
+            .run_async(&handler)
+            .await;
+        assert_eq!(test_conn.status(), Some(Status::BadRequest));
+    }
+
+    #[tokio::test]
+    async fn public_task_stats() {
+        install_test_trace_subscriber();
+        let clock = MockClock::default();
+        let ephemeral_datastore = EphemeralDatastoreBuilder::new().build().await;
+        let datastore = Arc::new(ephemeral_datastore.datastore(clock.clone()).await);
+        let handler = aggregator_handler(
+            datastore.clone(),
+            clock.clone(),
+            TestRuntime::default(),
+            &noop_meter(),
+            Config {
+                public_task_stats: true,
+                ..default_aggregator_config()
+            },
+        )
+        .await
+        .unwrap();
+
+        let task = TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Fake).build();
+        let leader_task = task.leader_view().unwrap();
+        datastore.put_aggregator_task(&leader_task).await.unwrap();
+        let helper_task = TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Fake)
+            .build()
+            .helper_view()
+            .unwrap();
+        datastore.put_aggregator_task(&helper_task).await.unwrap();
+        let uri = format!("/tasks/{}/stats", task.id());
+
+        // Nothing is known about a new task.
+        let mut test_conn = get(&uri).run_async(&handler).await;
+        assert_eq!(test_conn.status(), Some(Status::Ok));
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&take_response_body(&mut test_conn).await)
+                .unwrap(),
+            json!({
+                "reports_accepted_past_week": null,
+                "last_collection_time": null,
+            })
+        );
+
+        // Reports accepted since the earliest snapshot of the past week are counted, and the time
+        // of the latest finished collection is included.
+        let batch_interval = Interval::new(
+            clock
+                .now()
+                .to_batch_interval_start(task.time_precision())
+                .unwrap(),
+            *task.time_precision(),
+        )
+        .unwrap();
+        datastore
+            .run_unnamed_tx(|tx| {
+                let task_id = *task.id();
+                Box::pin(async move {
+                    tx.increment_task_upload_counter(
+                        &task_id,
+                        0,
+                        &TaskUploadCounter::new_with_values(0, 0, 0, 0, 0, 5, 0, 0),
+                    )
+                    .await?;
+                    tx.put_task_metrics_snapshots(batch_interval.start())
+                        .await?;
+                    tx.increment_task_upload_counter(
+                        &task_id,
+                        1,
+                        &TaskUploadCounter::new_with_values(0, 0, 0, 0, 0, 3, 0, 0),
+                    )
+                    .await?;
+                    tx.put_collection_job(&CollectionJob::<0, TimeInterval, dummy::Vdaf>::new(
+                        task_id,
+                        random(),
+                        Query::new_time_interval(batch_interval),
+                        dummy::AggregationParam(0),
+                        batch_interval,
+                        CollectionJobState::Finished {
+                            report_count: 8,
+                            client_timestamp_interval: batch_interval,
+                            encrypted_helper_aggregate_share: HpkeCiphertext::new(
+                                HpkeConfigId::from(0),
+                                Vec::new(),
+                                Vec::new(),
+                            ),
+                            leader_aggregate_share: dummy::AggregateShare(0),
+                        },
+                    ))
+                    .await
+                })
+            })
+            .await
+            .unwrap();
+
+        let mut test_conn = get(&uri).run_async(&handler).await;
+        assert_eq!(test_conn.status(), Some(Status::Ok));
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&take_response_body(&mut test_conn).await)
+                .unwrap(),
+            json!({
+                "reports_accepted_past_week": 3,
+                "last_collection_time": clock.now().as_seconds_since_epoch(),
+            })
+        );
+
+        // Statistics are only served for tasks in which this aggregator is the leader.
+        let test_conn = get(&format!("/tasks/{}/stats", helper_task.id()))
+            .run_async(&handler)
+            .await;
+        assert_eq!(test_conn.status(), Some(Status::BadRequest));
+
+        // The endpoint is not served unless enabled. Unrouted requests are left unhandled, and the
+        // server responds to them with 404 Not Found.
+        let (_, _ephemeral_datastore, _, handler) = setup_http_handler_test().await;
+        let test_conn = get(&uri).run_async(&handler).await;
+        assert_eq!(test_conn.status(), None);
+    }
+
+    #[tokio::test]
+    async fn auth_failures_are_uniform() {
+        install_test_trace_subscriber();
+        let clock = MockClock::default();
+        let ephemeral_datastore = EphemeralDatastoreBuilder::new().build().await;
+        let datastore = Arc::new(ephemeral_datastore.datastore(clock.clone()).await);
+        let min_auth_failure_response_time = StdDuration::from_millis(250);
+        let handler = aggregator_handler(
+            datastore.clone(),
+            clock.clone(),
+            TestRuntime::default(),
+            &noop_meter(),
+            Config {
+                min_auth_failure_response_time,
+                ..default_aggregator_config()
+            },
+        )
+        .await
+        .unwrap();
+
+        let leader_task = TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Fake).build();
+        let helper_task = TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Fake).build();
+        datastore
+            .put_aggregator_task(&leader_task.leader_view().unwrap())
+            .await
+            .unwrap();
+        datastore
+            .put_aggregator_task(&helper_task.helper_view().unwrap())
+            .await
+            .unwrap();
+        let unrecognized_task_id: TaskId = random();
+
+        // Each failure must produce the same response, naming only the task ID from the request,
+        // and take at least the configured minimum time, whether or not the task exists.
+        let check_response = |label, task_id: TaskId, start: Instant, test_conn: TestConn| async move {
+            let mut test_conn = test_conn;
+            assert!(
+                start.elapsed() >= min_auth_failure_response_time,
+                "{label}: response was too fast"
+            );
+            assert_eq!(test_conn.status(), Some(Status::BadRequest), "{label}");
+            assert_eq!(
+                take_problem_details(&mut test_conn).await,
+                json!({
+                    "status": Status::BadRequest as u16,
+                    "type": "urn:ietf:params:ppm:dap:error:unauthorizedRequest",
+                    "title": "The request's authorization is not valid.",
+                    "taskid": format!("{task_id}"),
+                }),
+                "{label}"
+            );
+        };
+
+        // Collection endpoints are served by the leader and require the collector's token.
+        for (label, task_id, auth_token) in [
+            (
+                "unrecognized task",
+                unrecognized_task_id,
+                Some(leader_task.collector_auth_token().clone()),
             ),
-            dummy::AggregationParam::default().get_encoded().unwrap(),
-        );
-
-        let (header, value) = task.collector_auth_token().request_authentication();
-        let mut test_conn = put(task.collection_job_uri(&collection_job_id).unwrap().path())
-            .with_request_header(header, value)
+            ("invalid token", *leader_task.id(), Some(random())),
+            ("missing token", *leader_task.id(), None),
+            (
+                "aggregator token",
+                *leader_task.id(),
+                Some(leader_task.aggregator_auth_token().clone()),
+            ),
+            (
+                "helper task",
+                *helper_task.id(),
+                Some(helper_task.collector_auth_token().clone()),
+            ),
+        ] {
+            let request = CollectionReq::new(
+                Query::new_time_interval(
+                    Interval::new(
+                        Time::from_seconds_since_epoch(0),
+                        *leader_task.time_precision(),
+                    )
+                    .unwrap(),
+                ),
+                dummy::AggregationParam::default().get_encoded().unwrap(),
+            );
+            let collection_job_id: CollectionJobId = random();
+            let mut test_conn = put(&format!(
+                "/tasks/{task_id}/collection_jobs/{collection_job_id}"
+            ))
             .with_request_header(
                 KnownHeaderName::ContentType,
                 CollectionReq::<TimeInterval>::MEDIA_TYPE,
             )
-            .with_request_body(request.get_encoded().unwrap())
-            .run_async(&handler)
-            .await;
+            .with_request_body(request.get_encoded().unwrap());
+            if let Some(auth_token) = auth_token {
+                let (header, value) = auth_token.request_authentication();
+                test_conn = test_conn.with_request_header(header, value);
+            }
 
-        // Collect request will be rejected because there are no reports in the batch interval
-        assert_eq!(test_conn.status(), Some(Status::BadRequest));
-        assert_eq!(
-            take_problem_details(&mut test_conn).await,
-            json!({
-                "status": Status::BadRequest as u16,
-                "type": "urn:ietf:params:ppm:dap:error:invalidBatchSize",
-                "title": "The number of reports included in the batch is invalid.",
-                "taskid": format!("{}", task.id()),
-            })
-        );
+            let start = Instant::now();
+            let test_conn = test_conn.run_async(&handler).await;
+            check_response(label, task_id, start, test_conn).await;
+        }
+
+        // Aggregation endpoints are served by the helper and require the aggregator's token.
+        for (label, task_id, auth_token) in [
+            (
+                "unrecognized task",
+                unrecognized_task_id,
+                Some(helper_task.aggregator_auth_token().clone()),
+            ),
+            ("invalid token", *helper_task.id(), Some(random())),
+            ("missing token", *helper_task.id(), None),
+            (
+                "collector token",
+                *helper_task.id(),
+                Some(helper_task.collector_auth_token().clone()),
+            ),
+            (
+                "leader task",
+                *leader_task.id(),
+                Some(leader_task.aggregator_auth_token().clone()),
+            ),
+        ] {
+            let aggregation_job_id: AggregationJobId = random();
+            let mut test_conn = delete(&format!(
+                "/tasks/{task_id}/aggregation_jobs/{aggregation_job_id}"
+            ));
+            if let Some(auth_token) = auth_token {
+                let (header, value) = auth_token.request_authentication();
+                test_conn = test_conn.with_request_header(header, value);
+            }
+
+            let start = Instant::now();
+            let test_conn = test_conn.run_async(&handler).await;
+            check_response(label, task_id, start, test_conn).await;
+        }
     }
 
     #[tokio::test]
@@ -4950,6 +6298,145 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn collection_summary() {
+        let test_case = setup_collection_job_test_case(Role::Leader, QueryType::TimeInterval).await;
+        let handler = aggregator_handler(
+            Arc::clone(&test_case.datastore),
+            MockClock::default(),
+            TestRuntime::default(),
+            &noop_meter(),
+            Config {
+                collector_hpke_keypairs: Vec::from([test_case
+                    .task
+                    .collector_hpke_keypair()
+                    .clone()]),
+                ..default_aggregator_config()
+            },
+        )
+        .await
+        .unwrap();
+
+        let batch_interval = TimeInterval::to_batch_identifier(
+            &test_case.task.leader_view().unwrap(),
+            &(),
+            &Time::from_seconds_since_epoch(0),
+        )
+        .unwrap();
+        let aggregation_param = dummy::AggregationParam::default();
+        let collection_job_id: CollectionJobId = random();
+        let request = CollectionReq::new(
+            Query::new_time_interval(batch_interval),
+            aggregation_param.get_encoded().unwrap(),
+        );
+        let test_conn = test_case
+            .put_collection_job(&collection_job_id, &request)
+            .await;
+        assert_eq!(test_conn.status(), Some(Status::Created));
+
+        let summary_path = format!(
+            "/tasks/{}/collection_jobs/{collection_job_id}/summary",
+            test_case.task.id()
+        );
+        let (header, value) = test_case
+            .task
+            .collector_auth_token()
+            .request_authentication();
+
+        // The endpoint is not routed unless collector keypairs are configured.
+        let test_conn = get(&summary_path)
+            .with_request_header(header, value.clone())
+            .run_async(&test_case.handler)
+            .await;
+        assert_eq!(test_conn.status(), None);
+
+        // The collection job is not yet finished.
+        let test_conn = get(&summary_path)
+            .with_request_header(header, value.clone())
+            .run_async(&handler)
+            .await;
+        assert_eq!(test_conn.status(), Some(Status::Accepted));
+
+        test_case
+            .datastore
+            .run_unnamed_tx(|tx| {
+                let task = test_case.task.clone();
+                Box::pin(async move {
+                    let encrypted_helper_aggregate_share = hpke::seal(
+                        task.collector_hpke_keypair().config(),
+                        &HpkeApplicationInfo::new(
+                            &Label::AggregateShare,
+                            &Role::Helper,
+                            &Role::Collector,
+                        ),
+                        &dummy::AggregateShare(7).get_encoded().unwrap(),
+                        &AggregateShareAad::new(
+                            *task.id(),
+                            aggregation_param.get_encoded().unwrap(),
+                            BatchSelector::new_time_interval(batch_interval),
+                        )
+                        .get_encoded()
+                        .unwrap(),
+                    )
+                    .unwrap();
+
+                    let collection_job = tx
+                        .get_collection_job::<0, TimeInterval, dummy::Vdaf>(
+                            &dummy::Vdaf::new(1),
+                            task.id(),
+                            &collection_job_id,
+                        )
+                        .await
+                        .unwrap()
+                        .unwrap()
+                        .with_state(CollectionJobState::Finished {
+                            report_count: 12,
+                            client_timestamp_interval: batch_interval,
+                            encrypted_helper_aggregate_share,
+                            leader_aggregate_share: dummy::AggregateShare(5),
+                        });
+
+                    tx.update_collection_job::<0, TimeInterval, dummy::Vdaf>(&collection_job)
+                        .await
+                        .unwrap();
+                    Ok(())
+                })
+            })
+            .await
+            .unwrap();
+
+        let mut test_conn = get(&summary_path)
+            .with_request_header(header, value)
+            .run_async(&handler)
+            .await;
+        assert_eq!(test_conn.status(), Some(Status::Ok));
+        assert_headers!(&test_conn, "content-type" => "application/json");
+        let body: serde_json::Value =
+            serde_json::from_slice(&take_response_body(&mut test_conn).await).unwrap();
+        assert_eq!(
+            body,
+            json!({
+                "batch": batch_interval.to_string(),
+                "report_count": 12,
+                "interval_start": batch_interval.start().as_seconds_since_epoch(),
+                "interval_duration": batch_interval.duration().as_seconds(),
+                "aggregate_result": 12,
+            })
+        );
+
+        // The collector's auth token is required.
+        let mut test_conn = get(&summary_path).run_async(&handler).await;
+        assert_eq!(
+            take_problem_details(&mut test_conn).await,
+            json!({
+                "status": Status::BadRequest as u16,
+                "type": "urn:ietf:params:ppm:dap:error:unauthorizedRequest",
+                "title": "The request's authorization is not valid.",
+                "taskid": format!("{}", test_case.task.id()),
+            })
+        );
+    }
+
     #[tokio::test]
     async fn collection_job_post_request_no_such_collection_job() {
         let test_case = setup_collection_job_test_case(Role::Leader, QueryType::TimeInterval).await;
@@ -5208,8 +6695,8 @@ mod tests {
             take_problem_details(&mut test_conn).await,
             json!({
                 "status": Status::BadRequest as u16,
-                "type": "urn:ietf:params:ppm:dap:error:unrecognizedTask",
-                "title": "An endpoint received a message with an unknown task ID.",
+                "type": "urn:ietf:params:ppm:dap:error:unauthorizedRequest",
+                "title": "The request's authorization is not valid.",
                 "taskid": format!("{}", task.id()),
             })
         );
@@ -5263,6 +6750,12 @@ mod tests {
                 "type": "urn:ietf:params:ppm:dap:error:batchInvalid",
                 "title": "The batch implied by the query is invalid.",
                 "taskid": format!("{}", task.id()),
+                "detail": format!(
+                    "task {}: invalid batch interval {}: batch interval is shorter than the \
+                     task's time precision",
+                    task.id(),
+                    request.batch_selector().batch_identifier(),
+                ),
             })
         );
 
@@ -5781,4 +7274,151 @@ mod tests {
             );
         }
     }
+
+    #[tokio::test]
+    async fn helper_handler_serves_only_helper_endpoints() {
+        install_test_trace_subscriber();
+        let clock = MockClock::default();
+        let ephemeral_datastore = EphemeralDatastoreBuilder::new().build().await;
+        let datastore = Arc::new(ephemeral_datastore.datastore(clock.clone()).await);
+        let handler = helper_handler(
+            Arc::clone(&datastore),
+            clock.clone(),
+            TestRuntime::default(),
+            &noop_meter(),
+            default_aggregator_config(),
+        )
+        .await
+        .unwrap();
+
+        let task = TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Prio3Count)
+            .build()
+            .helper_view()
+            .unwrap();
+        datastore.put_aggregator_task(&task).await.unwrap();
+
+        let test_conn = get(&format!("/hpke_config?task_id={}", task.id()))
+            .run_async(&handler)
+            .await;
+        assert_eq!(test_conn.status(), Some(Status::Ok));
+
+        // Helper endpoints are routed, even if the requests themselves are bad.
+        for test_conn in [
+            put(&format!(
+                "/tasks/{}/aggregation_jobs/{}",
+                task.id(),
+                random::<AggregationJobId>()
+            ))
+            .run_async(&handler)
+            .await,
+            post(&format!("/tasks/{}/aggregate_shares", task.id()))
+                .run_async(&handler)
+                .await,
+        ] {
+            assert_ne!(test_conn.status(), Some(Status::NotFound));
+        }
+
+        // Leader endpoints are not. Unrouted requests are left unhandled, and the server responds
+        // to them with 404 Not Found.
+        for test_conn in [
+            put(&format!("/tasks/{}/reports", task.id()))
+                .run_async(&handler)
+                .await,
+            put(&format!(
+                "/tasks/{}/collection_jobs/{}",
+                task.id(),
+                random::<CollectionJobId>()
+            ))
+            .run_async(&handler)
+            .await,
+        ] {
+            assert_eq!(test_conn.status(), None);
+        }
+    }
+
+    #[tokio::test]
+    async fn strict_conformance() {
+        install_test_trace_subscriber();
+        let clock = MockClock::default();
+        let ephemeral_datastore = EphemeralDatastoreBuilder::new().build().await;
+        let datastore = Arc::new(ephemeral_datastore.datastore(clock.clone()).await);
+        let handler = aggregator_handler(
+            Arc::clone(&datastore),
+            clock.clone(),
+            TestRuntime::default(),
+            &noop_meter(),
+            Config {
+                strict_conformance: true,
+                ..default_aggregator_config()
+            },
+        )
+        .await
+        .unwrap();
+
+        let task = TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Prio3Count)
+            .build()
+            .helper_view()
+            .unwrap();
+        datastore.put_aggregator_task(&task).await.unwrap();
+        let hpke_config_path = format!("/hpke_config?task_id={}", task.id());
+
+        // Conforming requests are handled as usual.
+        for accept in [
+            None,
+            Some(HpkeConfigList::MEDIA_TYPE),
+            Some("*/*"),
+            Some("text/plain;q=0.5, application/*"),
+        ] {
+            let mut test_conn = get(&hpke_config_path);
+            if let Some(accept) = accept {
+                test_conn = test_conn.with_request_header(KnownHeaderName::Accept, accept);
+            }
+            let test_conn = test_conn.run_async(&handler).await;
+            assert_eq!(test_conn.status(), Some(Status::Ok), "Accept: {accept:?}");
+        }
+
+        for (test_conn, detail) in [
+            (
+                get(&hpke_config_path)
+                    .with_request_header(KnownHeaderName::Accept, "application/json")
+                    .run_async(&handler)
+                    .await,
+                "Accept header \"application/json\" does not allow the response media type \
+                 application/dap-hpke-config-list",
+            ),
+            (
+                get(&format!("{hpke_config_path}&extra=1"))
+                    .run_async(&handler)
+                    .await,
+                "unknown query parameter \"extra\"",
+            ),
+            (
+                put(&format!(
+                    "/tasks/{}/aggregation_jobs/{}",
+                    task.id(),
+                    random::<AggregationJobId>()
+                ))
+                .with_request_header(
+                    KnownHeaderName::ContentType,
+                    AggregationJobInitializeReq::<TimeInterval>::MEDIA_TYPE,
+                )
+                .run_async(&handler)
+                .await,
+                "missing authentication token, in either the Authorization or DAP-Auth-Token \
+                 header",
+            ),
+        ] {
+            let mut test_conn = test_conn;
+            assert_eq!(test_conn.status(), Some(Status::BadRequest));
+            assert_eq!(
+                take_problem_details(&mut test_conn).await,
+                json!({
+                    "status": Status::BadRequest as u16,
+                    "type": "https://docs.divviup.org/references/janus-errors#non-conformant-request",
+                    "title": "The request does not conform to the DAP specification.",
+                    "detail": detail,
+                })
+            );
+        }
+    }
 }
@@ -0,0 +1,357 @@
+//! Election of a single replica to perform a duty which must not be performed concurrently by
+//! multiple replicas, such as garbage collection.
+//!
+//! Replicas compete for a lease row in the datastore. The holder renews its lease each time it
+//! performs the duty; if it stops doing so, for example because it crashed, another replica takes
+//! over once the lease expires.
+//!
+//! The state of each election a replica participates in may be shared via [`LeaderElectionStates`],
+//! which the health check server serves at `/leaderz`.
+
+use crate::aggregator::deployment::DeploymentFence;
+use janus_aggregator_core::datastore::{Datastore, Error};
+use janus_core::time::Clock;
+use opentelemetry::{metrics::Meter, KeyValue};
+use rand::random;
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    env,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use tracing::{debug, error, info};
+
+/// The state of the leader elections in which a binary participates, keyed by lease name.
+#[derive(Clone, Debug, Default)]
+pub struct LeaderElectionStates(Arc<Mutex<BTreeMap<&'static str, LeaderElectionState>>>);
+
+impl LeaderElectionStates {
+    /// Returns the current state of each election.
+    pub fn snapshot(&self) -> BTreeMap<&'static str, LeaderElectionState> {
+        self.0.lock().unwrap().clone()
+    }
+
+    fn update(&self, name: &'static str, state: LeaderElectionState) {
+        self.0.lock().unwrap().insert(name, state);
+    }
+}
+
+/// The state of a single leader election, from the point of view of one replica.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct LeaderElectionState {
+    /// The identity under which this replica participates in the election.
+    pub holder: String,
+    /// Whether this replica holds the lease.
+    pub is_leader: bool,
+    /// The replica holding the lease as of the last attempt to acquire it, if known.
+    pub leader: Option<String>,
+}
+
+pub struct LeaderElection<C: Clock> {
+    datastore: Arc<Datastore<C>>,
+    name: &'static str,
+    holder: Arc<str>,
+    lease_duration: Duration,
+    is_leader: Arc<AtomicBool>,
+    states: Option<LeaderElectionStates>,
+    deployment_fence: DeploymentFence,
+}
+
+impl<C: Clock> LeaderElection<C> {
+    /// Creates a participant in the election for the lease with the given name. Each replica
+    /// participates under a distinct identity, derived from its hostname.
+    pub fn new(
+        datastore: Arc<Datastore<C>>,
+        meter: &Meter,
+        name: &'static str,
+        lease_duration: Duration,
+    ) -> Self {
+        let holder: Arc<str> = format!(
+            "{}-{:08x}",
+            env::var("HOSTNAME").unwrap_or_else(|_| "janus".to_string()),
+            random::<u32>()
+        )
+        .into();
+        let is_leader = Arc::new(AtomicBool::new(false));
+
+        let leader_gauge = meter
+            .u64_observable_gauge("janus_leader_lease_held")
+            .with_description(
+                "Whether this replica currently holds a leader lease (1) or not (0). The holder \
+                 attribute identifies the replica.",
+            )
+            .init();
+        let attributes = [
+            KeyValue::new("lease", name),
+            KeyValue::new("holder", holder.to_string()),
+        ];
+        let result = meter.register_callback(&[leader_gauge.as_any()], {
+            let is_leader = Arc::clone(&is_leader);
+            move |observer| {
+                observer.observe_u64(
+                    &leader_gauge,
+                    is_leader.load(Ordering::Relaxed).into(),
+                    &attributes,
+                )
+            }
+        });
+        if let Err(error) = result {
+            error!(?error, "Couldn't register leader lease metric");
+        }
+
+        Self {
+            datastore,
+            name,
+            holder,
+            lease_duration,
+            is_leader,
+            states: None,
+            deployment_fence: DeploymentFence::default(),
+        }
+    }
+
+    /// Records the state of this election in `states` whenever it changes.
+    pub fn with_states(self, states: &LeaderElectionStates) -> Self {
+        states.update(
+            self.name,
+            LeaderElectionState {
+                holder: self.holder.to_string(),
+                is_leader: false,
+                leader: None,
+            },
+        );
+        Self {
+            states: Some(states.clone()),
+            ..self
+        }
+    }
+
+    /// Only acquire the lease while this replica's deployment is active.
+    pub fn with_deployment_fence(self, deployment_fence: DeploymentFence) -> Self {
+        Self {
+            deployment_fence,
+            ..self
+        }
+    }
+
+    /// The identity under which this replica participates in the election.
+    pub fn holder(&self) -> &str {
+        &self.holder
+    }
+
+    /// Attempts to acquire or renew the lease, returning true if this replica holds it. Errors
+    /// are logged and treated as not holding the lease.
+    pub async fn try_acquire(&self) -> bool {
+        let (name, lease_duration) = (self.name, self.lease_duration);
+        let result = self
+            .datastore
+            .run_tx("leader_election_acquire", |tx| {
+                let (holder, deployment_fence) =
+                    (Arc::clone(&self.holder), self.deployment_fence.clone());
+                Box::pin(async move {
+                    if !deployment_fence.is_active(tx).await? {
+                        return Ok(None);
+                    }
+                    tx.try_acquire_leader_lease(name, &holder, &lease_duration)
+                        .await
+                        .map(Some)
+                })
+            })
+            .await;
+        let lease = match result {
+            Ok(Some(lease)) => lease,
+            Ok(None) => {
+                debug!(
+                    lease = name,
+                    "Deployment is on standby, not acquiring leader lease"
+                );
+                self.set_leader(false, None);
+                return false;
+            }
+            Err(error) => {
+                error!(?error, lease = name, "Couldn't acquire leader lease");
+                self.set_leader(false, None);
+                return false;
+            }
+        };
+
+        let is_leader = lease.holder() == &*self.holder;
+        self.set_leader(is_leader, Some(lease.holder()));
+        is_leader
+    }
+
+    /// Releases the lease, if this replica holds it, so that another replica can take over
+    /// without waiting for the lease to expire.
+    pub async fn release(&self) {
+        if !self.is_leader.load(Ordering::Relaxed) {
+            return;
+        }
+        let name = self.name;
+        let result = self
+            .datastore
+            .run_tx("leader_election_release", |tx| {
+                let holder = Arc::clone(&self.holder);
+                Box::pin(async move { tx.release_leader_lease(name, &holder).await })
+            })
+            .await;
+        match result {
+            // The lease may have expired and been taken over by another replica.
+            Ok(()) | Err(Error::MutationTargetNotFound) => {}
+            Err(error) => error!(?error, lease = name, "Couldn't release leader lease"),
+        }
+        self.set_leader(false, None);
+    }
+
+    fn set_leader(&self, is_leader: bool, leader: Option<&str>) {
+        if let Some(states) = &self.states {
+            states.update(
+                self.name,
+                LeaderElectionState {
+                    holder: self.holder.to_string(),
+                    is_leader,
+                    leader: leader.map(str::to_string),
+                },
+            );
+        }
+        if self.is_leader.swap(is_leader, Ordering::Relaxed) != is_leader {
+            if is_leader {
+                info!(lease = self.name, holder = %self.holder, "Acquired leader lease");
+            } else {
+                info!(
+                    lease = self.name,
+                    holder = %self.holder,
+                    ?leader,
+                    "No longer holding leader lease"
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::aggregator::{
+        deployment::DeploymentFence,
+        leader_election::{LeaderElection, LeaderElectionState, LeaderElectionStates},
+    };
+    use janus_aggregator_core::{datastore::test_util::ephemeral_datastore, test_util::noop_meter};
+    use janus_core::{test_util::install_test_trace_subscriber, time::MockClock};
+    use janus_messages::Duration;
+    use std::{sync::Arc, time::Duration as StdDuration};
+
+    #[tokio::test]
+    async fn takeover() {
+        install_test_trace_subscriber();
+
+        let clock = MockClock::default();
+        let ephemeral_datastore = ephemeral_datastore().await;
+        let ds = Arc::new(ephemeral_datastore.datastore(clock.clone()).await);
+        let lease_duration = StdDuration::from_secs(60);
+
+        let first = LeaderElection::new(Arc::clone(&ds), &noop_meter(), "test", lease_duration);
+        let second = LeaderElection::new(Arc::clone(&ds), &noop_meter(), "test", lease_duration);
+        assert_ne!(first.holder(), second.holder());
+
+        assert!(first.try_acquire().await);
+        assert!(!second.try_acquire().await);
+        assert!(first.try_acquire().await);
+
+        // The first replica stops renewing its lease, so the second takes over once it expires.
+        clock.advance(&Duration::from_seconds(61));
+        assert!(second.try_acquire().await);
+        assert!(!first.try_acquire().await);
+
+        // Releasing the lease allows an immediate takeover.
+        second.release().await;
+        assert!(first.try_acquire().await);
+        assert!(!second.try_acquire().await);
+
+        // Releasing a lease that isn't held has no effect.
+        second.release().await;
+        assert!(!second.try_acquire().await);
+        assert_eq!(
+            ds.run_unnamed_tx(|tx| Box::pin(async move { tx.get_leader_lease("test").await }))
+                .await
+                .unwrap()
+                .unwrap()
+                .holder(),
+            first.holder()
+        );
+    }
+
+    #[tokio::test]
+    async fn states() {
+        install_test_trace_subscriber();
+
+        let clock = MockClock::default();
+        let ephemeral_datastore = ephemeral_datastore().await;
+        let ds = Arc::new(ephemeral_datastore.datastore(clock.clone()).await);
+        let lease_duration = StdDuration::from_secs(60);
+        let states = LeaderElectionStates::default();
+
+        let election = LeaderElection::new(Arc::clone(&ds), &noop_meter(), "test", lease_duration)
+            .with_states(&states);
+        let other = LeaderElection::new(Arc::clone(&ds), &noop_meter(), "test", lease_duration);
+        let state = |is_leader, leader: Option<&str>| LeaderElectionState {
+            holder: election.holder().to_string(),
+            is_leader,
+            leader: leader.map(str::to_string),
+        };
+        assert_eq!(states.snapshot()["test"], state(false, None));
+
+        assert!(other.try_acquire().await);
+        assert!(!election.try_acquire().await);
+        assert_eq!(
+            states.snapshot()["test"],
+            state(false, Some(other.holder()))
+        );
+
+        other.release().await;
+        assert!(election.try_acquire().await);
+        assert_eq!(
+            states.snapshot()["test"],
+            state(true, Some(election.holder()))
+        );
+
+        election.release().await;
+        assert_eq!(states.snapshot()["test"], state(false, None));
+    }
+
+    #[tokio::test]
+    async fn standby_deployment() {
+        install_test_trace_subscriber();
+
+        let clock = MockClock::default();
+        let ephemeral_datastore = ephemeral_datastore().await;
+        let ds = Arc::new(ephemeral_datastore.datastore(clock.clone()).await);
+        let lease_duration = StdDuration::from_secs(60);
+        let promote = |deployment: &'static str| {
+            ds.run_unnamed_tx(move |tx| {
+                Box::pin(async move { tx.promote_deployment(deployment).await })
+            })
+        };
+
+        let east = LeaderElection::new(Arc::clone(&ds), &noop_meter(), "test", lease_duration)
+            .with_deployment_fence(DeploymentFence::new(Some("east")));
+        let west = LeaderElection::new(Arc::clone(&ds), &noop_meter(), "test", lease_duration)
+            .with_deployment_fence(DeploymentFence::new(Some("west")));
+
+        // Neither deployment acquires the lease until one of them is promoted.
+        assert!(!east.try_acquire().await);
+        assert!(!west.try_acquire().await);
+
+        promote("east").await.unwrap();
+        assert!(!west.try_acquire().await);
+        assert!(east.try_acquire().await);
+
+        // Promoting the standby deployment lets it take over the lease immediately, and the
+        // previously active deployment no longer acquires it.
+        promote("west").await.unwrap();
+        assert!(west.try_acquire().await);
+        assert!(!east.try_acquire().await);
+    }
+}
@@ -0,0 +1,173 @@
+//! Hourly snapshots of per-task counters, persisted to the datastore so that historical volume can
+//! be queried long after a metrics backend would have discarded it.
+//!
+//! Snapshots are written idempotently, so every replica may run the snapshotter: the first replica
+//! to snapshot a given hour writes it, and the others have no effect.
+
+use anyhow::{Context, Result};
+use janus_aggregator_core::datastore::Datastore;
+use janus_core::time::{Clock, DurationExt, TimeExt};
+use janus_messages::{Duration, Time};
+use std::sync::Arc;
+use tracing::info;
+
+pub struct MetricsSnapshotter<C: Clock> {
+    // Dependencies.
+    datastore: Arc<Datastore<C>>,
+    clock: C,
+
+    // Configuration.
+    retention: Duration,
+    delete_limit: u64,
+
+    // State.
+    last_snapshot_time: Option<Time>,
+}
+
+impl<C: Clock> MetricsSnapshotter<C> {
+    /// Creates a snapshotter which retains snapshots for `retention`, deleting up to
+    /// `delete_limit` expired snapshots each time it runs.
+    pub fn new(
+        datastore: Arc<Datastore<C>>,
+        clock: C,
+        retention: Duration,
+        delete_limit: u64,
+    ) -> Self {
+        Self {
+            datastore,
+            clock,
+            retention,
+            delete_limit,
+            last_snapshot_time: None,
+        }
+    }
+
+    /// Snapshots every task's counters as of the start of the current hour, unless this
+    /// snapshotter has already done so, and deletes snapshots older than the retention period.
+    pub async fn run(&mut self) -> Result<()> {
+        let one_hour = Duration::from_hours(1)?;
+        let snapshot_time = self.clock.now().to_batch_interval_start(&one_hour)?;
+
+        if self.last_snapshot_time != Some(snapshot_time) {
+            let written = self
+                .datastore
+                .run_tx("put_task_metrics_snapshots", |tx| {
+                    Box::pin(async move { tx.put_task_metrics_snapshots(&snapshot_time).await })
+                })
+                .await
+                .context("couldn't snapshot task metrics")?;
+            info!(?snapshot_time, written, "Snapshotted task metrics");
+            self.last_snapshot_time = Some(snapshot_time);
+        }
+
+        // If the retention period reaches back before the epoch, there is nothing to delete.
+        let Ok(cutoff) = snapshot_time.sub(&self.retention) else {
+            return Ok(());
+        };
+        let delete_limit = self.delete_limit;
+        let deleted = self
+            .datastore
+            .run_tx("delete_task_metrics_snapshots", |tx| {
+                Box::pin(async move {
+                    tx.delete_task_metrics_snapshots_before(&cutoff, delete_limit)
+                        .await
+                })
+            })
+            .await
+            .context("couldn't delete expired task metrics snapshots")?;
+        if deleted > 0 {
+            info!(deleted, "Deleted expired task metrics snapshots");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::aggregator::metrics_snapshotter::MetricsSnapshotter;
+    use janus_aggregator_core::{
+        datastore::{models::TaskMetricsSnapshot, test_util::ephemeral_datastore},
+        task::{test_util::TaskBuilder, QueryType},
+    };
+    use janus_core::{
+        test_util::install_test_trace_subscriber,
+        time::{Clock, DurationExt, MockClock, TimeExt},
+        vdaf::VdafInstance,
+    };
+    use janus_messages::{Duration, Time};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn snapshot_and_expire() {
+        install_test_trace_subscriber();
+
+        let clock = MockClock::default();
+        let ephemeral_datastore = ephemeral_datastore().await;
+        let ds = Arc::new(ephemeral_datastore.datastore(clock.clone()).await);
+        let one_hour = Duration::from_hours(1).unwrap();
+
+        let task = TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Fake)
+            .build()
+            .leader_view()
+            .unwrap();
+        ds.put_aggregator_task(&task).await.unwrap();
+
+        let mut snapshotter = MetricsSnapshotter::new(
+            Arc::clone(&ds),
+            clock.clone(),
+            Duration::from_hours(2).unwrap(),
+            100,
+        );
+        let get_snapshots = || {
+            let task_id = *task.id();
+            ds.run_unnamed_tx(move |tx| {
+                Box::pin(async move {
+                    tx.get_task_metrics_snapshots(&task_id, &Time::from_seconds_since_epoch(0))
+                        .await
+                })
+            })
+        };
+        let first_snapshot_time = clock.now().to_batch_interval_start(&one_hour).unwrap();
+
+        // Running again within the same hour doesn't take another snapshot.
+        snapshotter.run().await.unwrap();
+        snapshotter.run().await.unwrap();
+        assert_eq!(
+            get_snapshots().await.unwrap(),
+            Vec::from([TaskMetricsSnapshot::new(
+                *task.id(),
+                first_snapshot_time,
+                0,
+                0,
+                0
+            )])
+        );
+
+        // Each hour, another snapshot is taken, and snapshots older than the retention period are
+        // deleted.
+        for _ in 0..3 {
+            clock.advance(&one_hour);
+            snapshotter.run().await.unwrap();
+        }
+        let snapshot_times: Vec<_> = get_snapshots()
+            .await
+            .unwrap()
+            .iter()
+            .map(|snapshot| *snapshot.snapshot_time())
+            .collect();
+        assert_eq!(
+            snapshot_times,
+            Vec::from([
+                first_snapshot_time
+                    .add(&Duration::from_hours(1).unwrap())
+                    .unwrap(),
+                first_snapshot_time
+                    .add(&Duration::from_hours(2).unwrap())
+                    .unwrap(),
+                first_snapshot_time
+                    .add(&Duration::from_hours(3).unwrap())
+                    .unwrap(),
+            ])
+        );
+    }
+}
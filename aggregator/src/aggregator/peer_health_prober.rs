@@ -0,0 +1,280 @@
+//! Periodic probing of each task's peer aggregator, so that connectivity problems between
+//! aggregators are noticed before aggregation or collection jobs fail.
+//!
+//! Each probe fetches the peer aggregator's HPKE configuration for the task, which DAP aggregators
+//! serve without authentication. The result of each task's most recent probe is written to the
+//! datastore, where it is served by the aggregator API, and probes are also counted in metrics.
+
+use anyhow::{Context, Result};
+use futures::{stream, StreamExt};
+use janus_aggregator_core::{
+    datastore::{self, Datastore},
+    task::{AggregatorTask, TaskState},
+};
+use janus_core::time::Clock;
+use janus_messages::{HpkeConfigList, Role};
+use opentelemetry::{
+    metrics::{Counter, Histogram, Meter, Unit},
+    KeyValue,
+};
+use reqwest::header::ACCEPT;
+use std::{
+    sync::Arc,
+    time::{Duration as StdDuration, Instant},
+};
+use tracing::{error, warn};
+
+/// The maximum number of peer aggregators probed at once.
+const MAX_CONCURRENT_PROBES: usize = 16;
+
+pub struct PeerHealthProber<C: Clock> {
+    // Dependencies.
+    datastore: Arc<Datastore<C>>,
+    clock: C,
+    http_client: reqwest::Client,
+
+    // Metrics.
+    probe_counter: Counter<u64>,
+    probe_duration_histogram: Histogram<f64>,
+}
+
+impl<C: Clock> PeerHealthProber<C> {
+    /// Creates a prober whose probes time out after `request_timeout`.
+    pub fn new(
+        datastore: Arc<Datastore<C>>,
+        clock: C,
+        meter: &Meter,
+        request_timeout: StdDuration,
+    ) -> Result<Self> {
+        let http_client = reqwest::Client::builder()
+            .timeout(request_timeout)
+            .build()
+            .context("couldn't create HTTP client")?;
+
+        let probe_counter = meter
+            .u64_counter("janus_peer_probes")
+            .with_description(
+                "Number of probes of tasks' peer aggregator endpoints, by task and by whether the \
+                 peer aggregator was reachable.",
+            )
+            .with_unit(Unit::new("{request}"))
+            .init();
+        let probe_duration_histogram = meter
+            .f64_histogram("janus_peer_probe_duration")
+            .with_description("The amount of time elapsed while probing a peer aggregator.")
+            .with_unit(Unit::new("s"))
+            .init();
+
+        Ok(Self {
+            datastore,
+            clock,
+            http_client,
+            probe_counter,
+            probe_duration_histogram,
+        })
+    }
+
+    /// Probes the peer aggregator of every active or expiring task, and records the results.
+    pub async fn run(&self) -> Result<()> {
+        let tasks = self
+            .datastore
+            .run_tx("peer_health_prober_get_tasks", |tx| {
+                Box::pin(async move { tx.get_aggregator_tasks().await })
+            })
+            .await
+            .context("couldn't get tasks")?;
+
+        stream::iter(
+            tasks
+                .iter()
+                .filter(|task| matches!(task.state(), TaskState::Active | TaskState::Expiring)),
+        )
+        .for_each_concurrent(MAX_CONCURRENT_PROBES, |task| async move {
+            if let Err(err) = self.probe_task(task).await {
+                error!(task_id = %task.id(), ?err, "Couldn't record peer probe result");
+            }
+        })
+        .await;
+        Ok(())
+    }
+
+    async fn probe_task(&self, task: &AggregatorTask) -> Result<(), datastore::Error> {
+        let probed_at = self.clock.now();
+        let start = Instant::now();
+        let result = self.probe(task).await;
+        let elapsed = start.elapsed();
+
+        self.probe_duration_histogram.record(
+            elapsed.as_secs_f64(),
+            &[KeyValue::new("task_id", task.id().to_string())],
+        );
+        self.probe_counter.add(
+            1,
+            &[
+                KeyValue::new("task_id", task.id().to_string()),
+                KeyValue::new("result", if result.is_ok() { "success" } else { "error" }),
+            ],
+        );
+        if let Err(error) = &result {
+            warn!(
+                task_id = %task.id(),
+                peer_aggregator_endpoint = %task.peer_aggregator_endpoint(),
+                error,
+                "Peer aggregator is unreachable"
+            );
+        }
+
+        let task_id = *task.id();
+        let result = result.map(|()| u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX));
+        self.datastore
+            .run_tx("put_task_peer_probe_result", |tx| {
+                let result = result.clone();
+                Box::pin(async move {
+                    tx.put_task_peer_probe_result(
+                        &task_id,
+                        &probed_at,
+                        result.as_ref().copied().map_err(String::as_str),
+                    )
+                    .await
+                })
+            })
+            .await
+    }
+
+    /// Fetches the peer aggregator's HPKE configuration for the task, returning a description of
+    /// the problem if this fails.
+    async fn probe(&self, task: &AggregatorTask) -> Result<(), String> {
+        let mut url = task
+            .peer_aggregator_endpoint()
+            .join("hpke_config")
+            .map_err(|err| format!("invalid peer aggregator endpoint: {err}"))?;
+        url.query_pairs_mut()
+            .append_pair("task_id", &task.id().to_string());
+
+        let mut request = self
+            .http_client
+            .get(url)
+            .header(ACCEPT, HpkeConfigList::MEDIA_TYPE);
+        if task.role() == &Role::Leader {
+            for header in task.helper_request_headers() {
+                request = request.header(header.name(), header.value());
+            }
+        }
+
+        let response = request.send().await.map_err(|err| err.to_string())?;
+        if !response.status().is_success() {
+            return Err(format!(
+                "peer aggregator responded with HTTP status {}",
+                response.status()
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::aggregator::peer_health_prober::PeerHealthProber;
+    use janus_aggregator_core::{
+        datastore::test_util::ephemeral_datastore,
+        task::{test_util::TaskBuilder, QueryType, TaskState},
+        test_util::noop_meter,
+    };
+    use janus_core::{
+        test_util::install_test_trace_subscriber, time::MockClock, vdaf::VdafInstance,
+    };
+    use mockito::Matcher;
+    use std::{sync::Arc, time::Duration as StdDuration};
+
+    #[tokio::test]
+    async fn probe_peer_aggregators() {
+        install_test_trace_subscriber();
+
+        let clock = MockClock::default();
+        let ephemeral_datastore = ephemeral_datastore().await;
+        let ds = Arc::new(ephemeral_datastore.datastore(clock.clone()).await);
+        let mut server = mockito::Server::new_async().await;
+
+        let reachable_task = TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Fake)
+            .with_helper_aggregator_endpoint(server.url().parse().unwrap())
+            .build()
+            .leader_view()
+            .unwrap();
+        let unreachable_task = TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Fake)
+            .with_helper_aggregator_endpoint(server.url().parse().unwrap())
+            .build()
+            .leader_view()
+            .unwrap();
+        let provisioned_task = TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Fake)
+            .with_helper_aggregator_endpoint(server.url().parse().unwrap())
+            .build()
+            .leader_view()
+            .unwrap()
+            .with_state(TaskState::Provisioned);
+        for task in [&reachable_task, &unreachable_task, &provisioned_task] {
+            ds.put_aggregator_task(task).await.unwrap();
+        }
+
+        let reachable_mock = server
+            .mock("GET", "/hpke_config")
+            .match_query(Matcher::UrlEncoded(
+                "task_id".into(),
+                reachable_task.id().to_string(),
+            ))
+            .with_status(200)
+            .expect(1)
+            .create_async()
+            .await;
+        let unreachable_mock = server
+            .mock("GET", "/hpke_config")
+            .match_query(Matcher::UrlEncoded(
+                "task_id".into(),
+                unreachable_task.id().to_string(),
+            ))
+            .with_status(503)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let prober = PeerHealthProber::new(
+            Arc::clone(&ds),
+            clock.clone(),
+            &noop_meter(),
+            StdDuration::from_secs(10),
+        )
+        .unwrap();
+        prober.run().await.unwrap();
+        reachable_mock.assert_async().await;
+        unreachable_mock.assert_async().await;
+
+        let (reachable_health, unreachable_health, provisioned_health) = ds
+            .run_unnamed_tx(|tx| {
+                let reachable_task_id = *reachable_task.id();
+                let unreachable_task_id = *unreachable_task.id();
+                let provisioned_task_id = *provisioned_task.id();
+                Box::pin(async move {
+                    Ok((
+                        tx.get_task_peer_health(&reachable_task_id).await?,
+                        tx.get_task_peer_health(&unreachable_task_id).await?,
+                        tx.get_task_peer_health(&provisioned_task_id).await?,
+                    ))
+                })
+            })
+            .await
+            .unwrap();
+
+        let reachable_health = reachable_health.unwrap();
+        assert!(reachable_health.reachable());
+        assert!(reachable_health.latency_ms().is_some());
+        assert_eq!(reachable_health.consecutive_failures(), 0);
+
+        let unreachable_health = unreachable_health.unwrap();
+        assert!(!unreachable_health.reachable());
+        assert!(unreachable_health.error().unwrap().contains("503"));
+        assert_eq!(unreachable_health.last_reachable_at(), None);
+        assert_eq!(unreachable_health.consecutive_failures(), 1);
+
+        // Tasks that aren't yet served aren't probed.
+        assert_eq!(provisioned_health, None);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::aggregator::error::ReportRejectionDetails;
 use janus_messages::{problem_type::DapProblemType, AggregationJobId, CollectionJobId, TaskId};
 use serde::Serialize;
 use trillium::{Conn, KnownHeaderName, Status};
@@ -40,6 +41,17 @@ pub struct ProblemDocument<'a> {
     aggregation_job_id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     collection_job_id: Option<String>,
+    /// Tolerable clock skew, in seconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tolerable_clock_skew: Option<u64>,
+    /// Earliest acceptable report timestamp, in seconds since the UNIX epoch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    earliest_acceptable_time: Option<u64>,
+    /// Latest acceptable report timestamp, in seconds since the UNIX epoch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    latest_acceptable_time: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hpke_config_ids: Option<Vec<u8>>,
 }
 
 impl<'a> ProblemDocument<'a> {
@@ -58,6 +70,10 @@ impl<'a> ProblemDocument<'a> {
             detail: None,
             aggregation_job_id: None,
             collection_job_id: None,
+            tolerable_clock_skew: None,
+            earliest_acceptable_time: None,
+            latest_acceptable_time: None,
+            hpke_config_ids: None,
         }
     }
 
@@ -97,6 +113,25 @@ impl<'a> ProblemDocument<'a> {
             ..self
         }
     }
+
+    /// Adds extension members describing what the aggregator would have accepted in place of a
+    /// rejected report, so that clients can correct their uploads.
+    pub fn with_report_rejection_details(self, details: &ReportRejectionDetails) -> Self {
+        Self {
+            tolerable_clock_skew: details.tolerable_clock_skew.map(|skew| skew.as_seconds()),
+            earliest_acceptable_time: details
+                .earliest_acceptable_time
+                .map(|time| time.as_seconds_since_epoch()),
+            latest_acceptable_time: details
+                .latest_acceptable_time
+                .map(|time| time.as_seconds_since_epoch()),
+            hpke_config_ids: details
+                .hpke_config_ids
+                .as_ref()
+                .map(|ids| ids.iter().map(|id| u8::from(*id)).collect()),
+            ..self
+        }
+    }
 }
 
 pub trait ProblemDetailsConnExt {
@@ -126,14 +161,14 @@ mod tests {
     use bytes::Bytes;
     use futures::future::join_all;
     use http::Method;
-    use janus_aggregator_core::test_util::noop_meter;
+    use janus_aggregator_core::{query_type::BatchIntervalViolation, test_util::noop_meter};
     use janus_core::{
         retries::test_util::LimitedRetryer,
         time::{Clock, RealClock},
     };
     use janus_messages::{
         problem_type::{DapProblemType, DapProblemTypeParseError},
-        Duration, Interval, ReportIdChecksum,
+        Duration, Interval, ReportIdChecksum, Time,
     };
     use opentelemetry::metrics::Unit;
     use rand::random;
@@ -264,6 +299,23 @@ mod tests {
                     }),
                     Some(DapProblemType::BatchInvalid),
                 ),
+                TestCase::new(
+                    Box::new(|| {
+                        Error::BatchIntervalMisaligned(
+                            random(),
+                            format!(
+                                "{}",
+                                Interval::new(
+                                    Time::from_seconds_since_epoch(1),
+                                    Duration::from_seconds(3600)
+                                )
+                                .unwrap()
+                            ),
+                            BatchIntervalViolation::StartMisaligned,
+                        )
+                    }),
+                    Some(DapProblemType::BatchInvalid),
+                ),
                 TestCase::new(
                     Box::new(|| {
                         Error::BatchOverlap(
@@ -325,6 +377,7 @@ mod tests {
                             body: Bytes::new(),
                         }),
                         &random(),
+                        &[],
                         &request_histogram,
                     )
                     .await
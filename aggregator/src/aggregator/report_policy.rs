@@ -0,0 +1,146 @@
+//! Deployment-specific screening of uploaded reports.
+//!
+//! A [`ReportPolicy`] is consulted by the leader for each upload that names a known task and passes
+//! authentication, before the report is validated and written. Operators can implement it to flag
+//! or reject reports using heuristics that only make sense for their deployment, e.g. an unusual
+//! volume of uploads arriving through one gateway, without modifying the upload handler. The
+//! default policy, [`AcceptAllReportPolicy`], accepts every report.
+
+use async_trait::async_trait;
+use janus_aggregator_core::task::AggregatorTask;
+use janus_messages::{ReportMetadata, Time};
+use opentelemetry::{
+    metrics::{Counter, Meter, Unit},
+    KeyValue,
+};
+use std::{fmt::Debug, sync::Arc};
+use tracing::info;
+use trillium::Headers;
+
+/// An upload being evaluated by a [`ReportPolicy`].
+#[derive(Debug, Clone, Copy)]
+pub struct PolicyUpload<'a> {
+    task: &'a AggregatorTask,
+    report_metadata: &'a ReportMetadata,
+    received_at: Time,
+    request_headers: &'a Headers,
+}
+
+impl<'a> PolicyUpload<'a> {
+    pub fn new(
+        task: &'a AggregatorTask,
+        report_metadata: &'a ReportMetadata,
+        received_at: Time,
+        request_headers: &'a Headers,
+    ) -> Self {
+        Self {
+            task,
+            report_metadata,
+            received_at,
+            request_headers,
+        }
+    }
+
+    /// The task the report was uploaded to.
+    pub fn task(&self) -> &AggregatorTask {
+        self.task
+    }
+
+    pub fn report_metadata(&self) -> &ReportMetadata {
+        self.report_metadata
+    }
+
+    /// The time at which the leader received the upload.
+    pub fn received_at(&self) -> &Time {
+        &self.received_at
+    }
+
+    /// The headers of the upload request, including any set by a reverse proxy in front of the
+    /// leader.
+    pub fn request_headers(&self) -> &Headers {
+        self.request_headers
+    }
+}
+
+/// The outcome of evaluating an upload against a [`ReportPolicy`].
+///
+/// Reasons are used as metric labels, so they should be drawn from a small, fixed set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PolicyDecision {
+    /// The report is processed as usual.
+    Accept,
+    /// The report is processed as usual, but is logged and counted as suspicious.
+    Flag(&'static str),
+    /// The report is rejected with a reportRejected problem document.
+    Reject(&'static str),
+}
+
+/// A hook, evaluated for each upload, which decides whether the report should be accepted.
+#[async_trait]
+pub trait ReportPolicy: Debug + Send + Sync {
+    async fn evaluate(&self, upload: &PolicyUpload<'_>) -> PolicyDecision;
+}
+
+/// A [`ReportPolicy`] which accepts every report.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct AcceptAllReportPolicy;
+
+#[async_trait]
+impl ReportPolicy for AcceptAllReportPolicy {
+    async fn evaluate(&self, _: &PolicyUpload<'_>) -> PolicyDecision {
+        PolicyDecision::Accept
+    }
+}
+
+/// Evaluates uploads against a [`ReportPolicy`], recording its decisions.
+#[derive(Debug)]
+pub(crate) struct ReportPolicyEvaluator {
+    policy: Arc<dyn ReportPolicy>,
+    decision_counter: Counter<u64>,
+}
+
+impl ReportPolicyEvaluator {
+    pub(crate) fn new(meter: &Meter, policy: Arc<dyn ReportPolicy>) -> Self {
+        let decision_counter = meter
+            .u64_counter("janus_report_policy_decisions")
+            .with_description(
+                "Number of uploads flagged or rejected by the report policy, by task and reason.",
+            )
+            .with_unit(Unit::new("{report}"))
+            .init();
+        Self {
+            policy,
+            decision_counter,
+        }
+    }
+
+    /// Replaces the policy that uploads are evaluated against.
+    pub(crate) fn with_policy(self, policy: Arc<dyn ReportPolicy>) -> Self {
+        Self { policy, ..self }
+    }
+
+    /// Evaluates an upload, returning the reason it should be rejected, if any.
+    pub(crate) async fn evaluate(&self, upload: &PolicyUpload<'_>) -> Option<&'static str> {
+        let (decision, reason) = match self.policy.evaluate(upload).await {
+            PolicyDecision::Accept => return None,
+            PolicyDecision::Flag(reason) => ("flag", reason),
+            PolicyDecision::Reject(reason) => ("reject", reason),
+        };
+        info!(
+            task_id = %upload.task().id(),
+            report_id = %upload.report_metadata().id(),
+            decision,
+            reason,
+            "Upload was flagged or rejected by the report policy"
+        );
+        self.decision_counter.add(
+            1,
+            &[
+                KeyValue::new("task_id", upload.task().id().to_string()),
+                KeyValue::new("decision", decision),
+                KeyValue::new("reason", reason),
+            ],
+        );
+        (decision == "reject").then_some(reason)
+    }
+}
@@ -0,0 +1,459 @@
+//! Publishing of accepted reports to a message queue, for downstream analytics or auditing.
+//!
+//! When a [`ReportSink`] is configured, each report accepted by the leader is also handed to a
+//! [`ReportSinkPublisher`], which publishes reports to the sink in batches from a background task.
+//! Publishing is best-effort: the upload path never waits for the sink, and reports are dropped
+//! (and counted) if the sink falls too far behind.
+
+use crate::config::{ReportSinkConfig, ReportSinkType};
+use anyhow::Result;
+use async_trait::async_trait;
+use janus_core::Runtime;
+use janus_messages::{ReportId, TaskId, Time};
+use opentelemetry::{
+    metrics::{Counter, Meter, Unit},
+    KeyValue,
+};
+use std::{fmt::Debug, sync::Arc};
+use tokio::sync::mpsc;
+use tracing::warn;
+
+#[cfg(feature = "gcp-pubsub")]
+use {
+    anyhow::Context,
+    base64::{engine::general_purpose::STANDARD, Engine},
+    serde::Serialize,
+    std::collections::BTreeMap,
+    url::Url,
+};
+
+/// A report accepted by the leader, as published to a [`ReportSink`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReportSinkRecord {
+    task_id: TaskId,
+    report_id: ReportId,
+    report_time: Time,
+    received_at: Time,
+    report_bytes: Vec<u8>,
+}
+
+impl ReportSinkRecord {
+    pub fn new(
+        task_id: TaskId,
+        report_id: ReportId,
+        report_time: Time,
+        received_at: Time,
+        report_bytes: Vec<u8>,
+    ) -> Self {
+        Self {
+            task_id,
+            report_id,
+            report_time,
+            received_at,
+            report_bytes,
+        }
+    }
+
+    pub fn task_id(&self) -> &TaskId {
+        &self.task_id
+    }
+
+    pub fn report_id(&self) -> &ReportId {
+        &self.report_id
+    }
+
+    /// The timestamp in the report's metadata.
+    pub fn report_time(&self) -> &Time {
+        &self.report_time
+    }
+
+    /// The time at which the leader accepted the report.
+    pub fn received_at(&self) -> &Time {
+        &self.received_at
+    }
+
+    /// The report, encoded as it was uploaded. Input shares remain encrypted.
+    pub fn report_bytes(&self) -> &[u8] {
+        &self.report_bytes
+    }
+}
+
+/// A message queue to which accepted reports are published. Implementations may be backed by any
+/// message queueing system (e.g. Kafka, Pub/Sub).
+#[async_trait]
+pub trait ReportSink: Debug + Send + Sync {
+    /// Publishes a batch of reports. Once this method returns successfully, the reports must have
+    /// been durably accepted by the message queue.
+    async fn publish(&self, records: &[ReportSinkRecord]) -> Result<()>;
+}
+
+/// Constructs the [`ReportSink`] described by the given configuration.
+pub fn report_sink_from_config(config: &ReportSinkConfig) -> Result<Arc<dyn ReportSink>> {
+    match &config.sink {
+        #[cfg(feature = "gcp-pubsub")]
+        ReportSinkType::GcpPubSub { topic, endpoint } => {
+            let http_client = reqwest::Client::builder()
+                .user_agent(CLIENT_USER_AGENT)
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .context("couldn't create HTTP client")?;
+            Ok(Arc::new(GcpPubSubReportSink::new(
+                http_client,
+                topic,
+                endpoint.clone(),
+                // Unwrap safety: the metadata token URL is a valid URL.
+                crate::cloud_credentials::GCP_METADATA_TOKEN_URL
+                    .parse()
+                    .unwrap(),
+            )?))
+        }
+        #[cfg(not(feature = "gcp-pubsub"))]
+        ReportSinkType::GcpPubSub { .. } => Err(anyhow::anyhow!(
+            "Google Cloud Pub/Sub report sink was enabled in the configuration file, but support \
+             was not enabled at compile time. Rebuild with `--features gcp-pubsub`."
+        )),
+    }
+}
+
+/// Publishes reports to a [`ReportSink`] in batches, from a background task.
+pub(crate) struct ReportSinkPublisher {
+    record_tx: mpsc::Sender<ReportSinkRecord>,
+    dropped_reports_counter: Counter<u64>,
+}
+
+impl ReportSinkPublisher {
+    pub(crate) fn new<R: Runtime>(
+        runtime: &R,
+        meter: &Meter,
+        sink: Arc<dyn ReportSink>,
+        config: &ReportSinkConfig,
+    ) -> Self {
+        let published_reports_counter = meter
+            .u64_counter("janus_report_sink_published_reports")
+            .with_description("Number of accepted reports published to the report sink.")
+            .with_unit(Unit::new("{report}"))
+            .init();
+        for result in ["success", "error"] {
+            published_reports_counter.add(0, &[KeyValue::new("result", result)]);
+        }
+
+        let dropped_reports_counter = meter
+            .u64_counter("janus_report_sink_dropped_reports")
+            .with_description(
+                "Number of accepted reports not published to the report sink because too many \
+                 reports were waiting to be published.",
+            )
+            .with_unit(Unit::new("{report}"))
+            .init();
+        dropped_reports_counter.add(0, &[]);
+
+        let (record_tx, record_rx) = mpsc::channel(config.max_buffered_reports.max(1));
+        runtime.spawn(Self::run(
+            sink,
+            record_rx,
+            config.max_batch_size.max(1),
+            published_reports_counter,
+        ));
+
+        Self {
+            record_tx,
+            dropped_reports_counter,
+        }
+    }
+
+    /// Queues a report to be published, without waiting for it to be published. If too many
+    /// reports are already waiting, the report is dropped.
+    pub(crate) fn publish(&self, record: ReportSinkRecord) {
+        if self.record_tx.try_send(record).is_err() {
+            self.dropped_reports_counter.add(1, &[]);
+        }
+    }
+
+    async fn run(
+        sink: Arc<dyn ReportSink>,
+        mut record_rx: mpsc::Receiver<ReportSinkRecord>,
+        max_batch_size: usize,
+        published_reports_counter: Counter<u64>,
+    ) {
+        let mut batch = Vec::with_capacity(max_batch_size);
+        while let Some(record) = record_rx.recv().await {
+            // Publish whatever has accumulated while the previous batch was being published.
+            batch.push(record);
+            while batch.len() < max_batch_size {
+                match record_rx.try_recv() {
+                    Ok(record) => batch.push(record),
+                    Err(_) => break,
+                }
+            }
+
+            let result = match sink.publish(&batch).await {
+                Ok(()) => "success",
+                Err(err) => {
+                    warn!(
+                        ?err,
+                        reports = batch.len(),
+                        "Couldn't publish reports to sink"
+                    );
+                    "error"
+                }
+            };
+            published_reports_counter.add(
+                u64::try_from(batch.len()).unwrap_or(u64::MAX),
+                &[KeyValue::new("result", result)],
+            );
+            batch.clear();
+        }
+    }
+}
+
+/// User agent sent with requests to message queue APIs.
+#[cfg(feature = "gcp-pubsub")]
+const CLIENT_USER_AGENT: &str = concat!(
+    env!("CARGO_PKG_NAME"),
+    "/",
+    env!("CARGO_PKG_VERSION"),
+    "/report_sink",
+);
+
+/// Timeout applied to each request to a message queue API or metadata service.
+#[cfg(feature = "gcp-pubsub")]
+const REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Default base URL of the Pub/Sub API.
+#[cfg(feature = "gcp-pubsub")]
+const DEFAULT_PUBSUB_ENDPOINT: &str = "https://pubsub.googleapis.com/";
+
+#[cfg(feature = "gcp-pubsub")]
+#[derive(Serialize)]
+struct PublishRequest {
+    messages: Vec<PubsubMessage>,
+}
+
+#[cfg(feature = "gcp-pubsub")]
+#[derive(Serialize)]
+struct PubsubMessage {
+    data: String,
+    attributes: BTreeMap<&'static str, String>,
+}
+
+/// A [`ReportSink`] that publishes to a Google Cloud Pub/Sub topic, using the [publish][1] API.
+/// Each report is published as a message whose data is the encoded report, and whose attributes
+/// carry the task ID, report ID, report timestamp, and time at which the report was accepted.
+///
+/// [1]: https://cloud.google.com/pubsub/docs/reference/rest/v1/projects.topics/publish
+#[cfg(feature = "gcp-pubsub")]
+#[derive(Debug)]
+struct GcpPubSubReportSink {
+    http_client: reqwest::Client,
+    publish_url: Url,
+    token_url: Url,
+}
+
+#[cfg(feature = "gcp-pubsub")]
+impl GcpPubSubReportSink {
+    fn new(
+        http_client: reqwest::Client,
+        topic: &str,
+        endpoint: Option<Url>,
+        token_url: Url,
+    ) -> Result<Self> {
+        let endpoint = match endpoint {
+            Some(endpoint) => endpoint,
+            // Unwrap safety: the default endpoint is a valid URL.
+            None => DEFAULT_PUBSUB_ENDPOINT.parse().unwrap(),
+        };
+        let publish_url = endpoint
+            .join(&format!("v1/{topic}:publish"))
+            .with_context(|| format!("invalid Pub/Sub topic {topic:?}"))?;
+        Ok(Self {
+            http_client,
+            publish_url,
+            token_url,
+        })
+    }
+}
+
+#[cfg(feature = "gcp-pubsub")]
+#[async_trait]
+impl ReportSink for GcpPubSubReportSink {
+    async fn publish(&self, records: &[ReportSinkRecord]) -> Result<()> {
+        let access_token = crate::cloud_credentials::fetch_gcp_access_token(
+            &self.http_client,
+            self.token_url.clone(),
+        )
+        .await?;
+
+        let request = PublishRequest {
+            messages: records
+                .iter()
+                .map(|record| PubsubMessage {
+                    data: STANDARD.encode(record.report_bytes()),
+                    attributes: BTreeMap::from([
+                        ("task_id", record.task_id().to_string()),
+                        ("report_id", record.report_id().to_string()),
+                        ("report_time", record.report_time().to_string()),
+                        ("received_at", record.received_at().to_string()),
+                    ]),
+                })
+                .collect(),
+        };
+
+        self.http_client
+            .post(self.publish_url.clone())
+            .bearer_auth(access_token)
+            .json(&request)
+            .send()
+            .await
+            .context("couldn't send request to Pub/Sub")?
+            .error_for_status()
+            .context("Pub/Sub returned an error")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        aggregator::report_sink::{ReportSink, ReportSinkPublisher, ReportSinkRecord},
+        config::{ReportSinkConfig, ReportSinkType},
+    };
+    use anyhow::Result;
+    use async_trait::async_trait;
+    use janus_aggregator_core::test_util::noop_meter;
+    use janus_core::TokioRuntime;
+    use janus_messages::Time;
+    use rand::random;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    };
+    use tokio::sync::Semaphore;
+
+    /// A fake sink, which records published batches. Each batch waits for a permit to be added to
+    /// the semaphore before it is published.
+    #[derive(Debug)]
+    struct FakeReportSink {
+        batches: Mutex<Vec<Vec<ReportSinkRecord>>>,
+        attempts: AtomicUsize,
+        permits: Semaphore,
+    }
+
+    #[async_trait]
+    impl ReportSink for FakeReportSink {
+        async fn publish(&self, records: &[ReportSinkRecord]) -> Result<()> {
+            self.attempts.fetch_add(1, Ordering::SeqCst);
+            self.permits.acquire().await.unwrap().forget();
+            self.batches.lock().unwrap().push(records.to_vec());
+            Ok(())
+        }
+    }
+
+    fn record() -> ReportSinkRecord {
+        ReportSinkRecord::new(
+            random(),
+            random(),
+            Time::from_seconds_since_epoch(1000),
+            Time::from_seconds_since_epoch(1001),
+            Vec::from(*b"report"),
+        )
+    }
+
+    fn config(max_buffered_reports: usize, max_batch_size: usize) -> ReportSinkConfig {
+        ReportSinkConfig {
+            sink: ReportSinkType::GcpPubSub {
+                topic: "projects/p/topics/t".to_string(),
+                endpoint: None,
+            },
+            max_buffered_reports,
+            max_batch_size,
+        }
+    }
+
+    #[tokio::test]
+    async fn publisher_batches_and_drops() {
+        let sink = Arc::new(FakeReportSink {
+            batches: Mutex::new(Vec::new()),
+            attempts: AtomicUsize::new(0),
+            permits: Semaphore::new(0),
+        });
+        let publisher =
+            ReportSinkPublisher::new(&TokioRuntime, &noop_meter(), sink.clone(), &config(3, 2));
+
+        // The first record is taken by the background task, which then waits to publish it. Three
+        // more records fill the buffer, and the fifth is dropped.
+        let records: Vec<_> = (0..5).map(|_| record()).collect();
+        publisher.publish(records[0].clone());
+        while sink.attempts.load(Ordering::SeqCst) == 0 {
+            tokio::task::yield_now().await;
+        }
+        for record in &records[1..] {
+            publisher.publish(record.clone());
+        }
+
+        sink.permits.add_permits(3);
+        while sink.batches.lock().unwrap().len() < 3 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(
+            *sink.batches.lock().unwrap(),
+            Vec::from([
+                Vec::from([records[0].clone()]),
+                Vec::from([records[1].clone(), records[2].clone()]),
+                Vec::from([records[3].clone()]),
+            ])
+        );
+    }
+
+    #[cfg(feature = "gcp-pubsub")]
+    #[tokio::test]
+    async fn gcp_pubsub_report_sink() {
+        use crate::aggregator::report_sink::GcpPubSubReportSink;
+        use base64::{engine::general_purpose::STANDARD, Engine};
+        use mockito::Matcher;
+        use serde_json::json;
+
+        let mut server = mockito::Server::new_async().await;
+        let token_mock = server
+            .mock("GET", "/token")
+            .match_header("Metadata-Flavor", "Google")
+            .with_status(200)
+            .with_body(
+                json!({"access_token": "token", "expires_in": 3600, "token_type": "Bearer"})
+                    .to_string(),
+            )
+            .expect(1)
+            .create_async()
+            .await;
+        let record = record();
+        let publish_mock = server
+            .mock("POST", "/v1/projects/p/topics/t:publish")
+            .match_header("authorization", "Bearer token")
+            .match_body(Matcher::Json(json!({
+                "messages": [{
+                    "data": STANDARD.encode(b"report"),
+                    "attributes": {
+                        "task_id": record.task_id().to_string(),
+                        "report_id": record.report_id().to_string(),
+                        "report_time": "1000",
+                        "received_at": "1001",
+                    },
+                }],
+            })))
+            .with_status(200)
+            .with_body(json!({"messageIds": ["1"]}).to_string())
+            .expect(1)
+            .create_async()
+            .await;
+
+        let sink = GcpPubSubReportSink::new(
+            reqwest::Client::new(),
+            "projects/p/topics/t",
+            Some(server.url().parse().unwrap()),
+            format!("{}/token", server.url()).parse().unwrap(),
+        )
+        .unwrap();
+        sink.publish(&[record]).await.unwrap();
+        token_mock.assert_async().await;
+        publish_mock.assert_async().await;
+    }
+}
@@ -7,7 +7,9 @@ use async_trait::async_trait;
 use futures::future::{join_all, try_join_all};
 use janus_aggregator_core::datastore::{
     self,
-    models::{LeaderStoredReport, TaskUploadCounter},
+    models::{
+        ClientReportWriteOutcome, EncodedClientReport, LeaderStoredReport, TaskUploadCounter,
+    },
     Datastore, Transaction,
 };
 use janus_core::{time::Clock, Runtime};
@@ -27,7 +29,7 @@ use tokio::{
     sync::{mpsc, oneshot},
     time::{sleep_until, Instant},
 };
-use tracing::{debug, error};
+use tracing::{debug, error, warn};
 
 type ReportResult<C> = Result<Box<dyn ReportWriter<C>>, ReportRejection>;
 
@@ -41,8 +43,12 @@ pub struct ReportWriteBatcher<C> {
 }
 
 impl<C: Clock> ReportWriteBatcher<C> {
+    /// Creates a new batcher writing reports to `ds`. If `secondary_ds` is set, e.g. during a
+    /// datastore migration, each batch of reports is also written to it, and uploads fail unless
+    /// both writes succeed.
     pub fn new<R: Runtime + Send + Sync + 'static>(
         ds: Arc<Datastore<C>>,
+        secondary_ds: Option<Arc<Datastore<C>>>,
         runtime: R,
         counter_shard_count: u64,
         max_batch_size: usize,
@@ -55,6 +61,7 @@ impl<C: Clock> ReportWriteBatcher<C> {
         runtime.spawn(async move {
             Self::run_upload_batcher(
                 ds,
+                secondary_ds,
                 runtime_clone,
                 report_rx,
                 counter_shard_count,
@@ -101,10 +108,11 @@ impl<C: Clock> ReportWriteBatcher<C> {
 
     #[tracing::instrument(
         name = "ReportWriteBatcher::run_upload_batcher",
-        skip(ds, runtime, report_rx)
+        skip(ds, secondary_ds, runtime, report_rx)
     )]
     async fn run_upload_batcher<R: Runtime + Send + Sync>(
         ds: Arc<Datastore<C>>,
+        secondary_ds: Option<Arc<Datastore<C>>>,
         runtime: Arc<R>,
         mut report_rx: ReportWriteBatcherReceiver<C>,
         counter_shard_count: u64,
@@ -146,10 +154,11 @@ impl<C: Clock> ReportWriteBatcher<C> {
             // If the event made us want to write the current batch to storage, do so.
             if write_batch {
                 let ds = Arc::clone(&ds);
+                let secondary_ds = secondary_ds.clone();
                 let report_results =
                     replace(&mut report_results, Vec::with_capacity(max_batch_size));
                 runtime.spawn(async move {
-                    Self::write_batch(ds, counter_shard_count, report_results).await;
+                    Self::write_batch(ds, secondary_ds, counter_shard_count, report_results).await;
                 });
             }
         }
@@ -158,55 +167,39 @@ impl<C: Clock> ReportWriteBatcher<C> {
     #[tracing::instrument(name = "ReportWriteBatcher::write_batch", skip_all)]
     async fn write_batch(
         ds: Arc<Datastore<C>>,
+        secondary_ds: Option<Arc<Datastore<C>>>,
         counter_shard_count: u64,
         report_results: Vec<(ReportResult<C>, Option<ResultSender>)>,
     ) {
-        // Run all report writes concurrently.
         let (report_results, result_senders): (Vec<ReportResult<C>>, Vec<Option<ResultSender>>) =
             report_results.into_iter().unzip();
         let report_results = Arc::new(report_results);
-        let results = ds
-            .run_tx("upload", |tx| {
-                let report_results = Arc::clone(&report_results);
-                Box::pin(async move {
-                    let task_upload_counters = TaskUploadCounters::default();
-                    let results = join_all(report_results.iter().map(|report_result| {
-                        let task_upload_counters = task_upload_counters.clone();
-                        async move {
-                            match report_result {
-                                Ok(report_writer) => {
-                                    report_writer.write_report(tx, &task_upload_counters).await
-                                }
-                                Err(rejection) => {
-                                    task_upload_counters.increment_report_rejection(rejection);
-                                    Ok(())
-                                }
-                            }
-                        }
-                    }))
-                    .await;
-                    Ok((results, task_upload_counters))
-                })
-            })
-            .await;
+        let mut results = Self::write_reports(&ds, counter_shard_count, &report_results).await;
 
-        match results {
-            Ok((results, task_upload_counters)) => {
-                // Write the task upload counters in a separate transaction from uploads. This is
-                // to prevent seralization conflicts causing excess repeated work when INSERTing.
-                //
-                // We're fine with this being non-transactional with the actual report uploads.
-                // If the process dies before being able to write counters, it's not a big deal.
-                let _ = ds
-                    .run_tx("update_task_upload_counters", |tx| {
-                        let task_upload_counters = task_upload_counters.clone();
-                        Box::pin(async move {
-                            task_upload_counters.write(counter_shard_count, tx).await
-                        })
-                    })
-                    .await
-                    .map_err(|err| error!(?err, "Failed to write upload metrics"));
+        // During a datastore migration, the batch is also written to the secondary datastore. If
+        // that fails, the uploads fail too, so that clients retry them and both datastores end up
+        // with every accepted report. Reports already written to the primary datastore are ignored
+        // as duplicates when retried.
+        if let (Ok(_), Some(secondary_ds)) = (&results, secondary_ds) {
+            match Self::write_reports(&secondary_ds, counter_shard_count, &report_results).await {
+                Ok(secondary_results) => {
+                    let failures = secondary_results.iter().filter(|r| r.is_err()).count();
+                    if failures > 0 {
+                        warn!(
+                            failures,
+                            "Some reports were rejected by the secondary datastore"
+                        );
+                    }
+                }
+                Err(err) => {
+                    error!(?err, "Failed to write reports to secondary datastore");
+                    results = Err(err);
+                }
+            }
+        }
 
+        match results {
+            Ok(results) => {
                 // Individual, per-request results.
                 assert_eq!(result_senders.len(), results.len()); // sanity check: should be guaranteed.
                 for (result_tx, result) in result_senders.into_iter().zip(results.into_iter()) {
@@ -232,15 +225,123 @@ impl<C: Clock> ReportWriteBatcher<C> {
             }
         };
     }
+
+    /// Writes a batch of reports, and the resulting task upload counters, to a datastore. Returns
+    /// the result of writing each report.
+    async fn write_reports(
+        ds: &Datastore<C>,
+        counter_shard_count: u64,
+        report_results: &Arc<Vec<ReportResult<C>>>,
+    ) -> Result<Vec<Result<(), Error>>, datastore::Error> {
+        let (results, task_upload_counters) = ds
+            .run_tx("upload", |tx| {
+                let report_results = Arc::clone(report_results);
+                Box::pin(async move {
+                    let task_upload_counters = TaskUploadCounters::default();
+
+                    // Validate all reports concurrently.
+                    let prepared_reports = join_all(report_results.iter().map(|report_result| {
+                        let task_upload_counters = task_upload_counters.clone();
+                        async move {
+                            match report_result {
+                                Ok(report_writer) => {
+                                    let result = report_writer.prepare_report(tx).await;
+                                    if let Err(Error::ReportRejected(rejection)) = &result {
+                                        task_upload_counters.increment_report_rejection(rejection);
+                                    }
+                                    result.map(Some)
+                                }
+                                Err(rejection) => {
+                                    task_upload_counters.increment_report_rejection(rejection);
+                                    Ok(None)
+                                }
+                            }
+                        }
+                    }))
+                    .await;
+
+                    // Then write the valid reports in a single statement.
+                    let (indices, encoded_reports): (Vec<usize>, Vec<EncodedClientReport>) =
+                        prepared_reports
+                            .iter()
+                            .enumerate()
+                            .filter_map(|(index, prepared_report)| {
+                                prepared_report
+                                    .as_ref()
+                                    .ok()
+                                    .and_then(Option::as_ref)
+                                    .map(|report| (index, report.clone()))
+                            })
+                            .unzip();
+                    let outcomes = tx.put_client_reports(&encoded_reports).await?;
+
+                    let mut results: Vec<Result<(), Error>> = prepared_reports
+                        .into_iter()
+                        .map(|prepared_report| prepared_report.map(|_| ()))
+                        .collect();
+                    for ((index, report), outcome) in
+                        indices.into_iter().zip(&encoded_reports).zip(outcomes)
+                    {
+                        match outcome {
+                            ClientReportWriteOutcome::Inserted => {
+                                task_upload_counters.increment_report_success(report.task_id())
+                            }
+                            // This was a duplicate report, return OK but don't increment the
+                            // counter so we avoid double counting successful reports.
+                            ClientReportWriteOutcome::Duplicate => {}
+                            // A different report was already uploaded with this ID. The stored
+                            // report is left in place; accept the upload so as not to reveal
+                            // anything to the client.
+                            ClientReportWriteOutcome::Conflict => debug!(
+                                task_id = ?report.task_id(),
+                                report_id = ?report.report_id(),
+                                "Report ID conflicts with a previously uploaded report"
+                            ),
+                            // The report expired between being validated and being written.
+                            ClientReportWriteOutcome::Expired => {
+                                let rejection = ReportRejection::new(
+                                    *report.task_id(),
+                                    *report.report_id(),
+                                    *report.client_timestamp(),
+                                    ReportRejectionReason::Expired,
+                                );
+                                task_upload_counters.increment_report_rejection(&rejection);
+                                results[index] = Err(Error::ReportRejected(rejection));
+                            }
+                            // The task was deleted between the report being validated and
+                            // being written.
+                            ClientReportWriteOutcome::TaskNotFound => {
+                                results[index] = Err(Error::UnrecognizedTask(*report.task_id()))
+                            }
+                        }
+                    }
+
+                    Ok((results, task_upload_counters))
+                })
+            })
+            .await?;
+
+        // Write the task upload counters in a separate transaction from uploads. This is to
+        // prevent seralization conflicts causing excess repeated work when INSERTing.
+        //
+        // We're fine with this being non-transactional with the actual report uploads. If the
+        // process dies before being able to write counters, it's not a big deal.
+        let _ = ds
+            .run_tx("update_task_upload_counters", |tx| {
+                let task_upload_counters = task_upload_counters.clone();
+                Box::pin(async move { task_upload_counters.write(counter_shard_count, tx).await })
+            })
+            .await
+            .map_err(|err| error!(?err, "Failed to write upload metrics"));
+
+        Ok(results)
+    }
 }
 
 #[async_trait]
 pub trait ReportWriter<C: Clock>: Debug + Send + Sync {
-    async fn write_report(
-        &self,
-        tx: &Transaction<C>,
-        task_upload_counters: &TaskUploadCounters,
-    ) -> Result<(), Error>;
+    /// Validates the report, and encodes it to be written along with the rest of its batch.
+    async fn prepare_report(&self, tx: &Transaction<C>) -> Result<EncodedClientReport, Error>;
 }
 
 #[derive(Debug)]
@@ -287,36 +388,11 @@ where
     C: Clock,
     Q: UploadableQueryType,
 {
-    async fn write_report(
-        &self,
-        tx: &Transaction<C>,
-        task_upload_counter: &TaskUploadCounters,
-    ) -> Result<(), Error> {
+    async fn prepare_report(&self, tx: &Transaction<C>) -> Result<EncodedClientReport, Error> {
         // Some validation requires we query the database. Thus it's still possible to reject a
         // report at this stage.
-        match Q::validate_uploaded_report(tx, self.vdaf.as_ref(), &self.report).await {
-            Ok(_) => {
-                let result = tx
-                    .put_client_report::<SEED_SIZE, A>(&self.vdaf, &self.report)
-                    .await;
-                match result {
-                    Ok(_) => {
-                        task_upload_counter.increment_report_success(self.report.task_id());
-                        Ok(())
-                    }
-                    // Assume this was a duplicate report, return OK but don't increment the counter
-                    // so we avoid double counting successful reports.
-                    Err(datastore::Error::MutationTargetAlreadyExists) => Ok(()),
-                    Err(error) => Err(error.into()),
-                }
-            }
-            Err(error) => {
-                if let Error::ReportRejected(rejection) = error {
-                    task_upload_counter.increment_report_rejection(&rejection);
-                }
-                Err(error)
-            }
-        }
+        Q::validate_uploaded_report(tx, self.vdaf.as_ref(), &self.report).await?;
+        Ok(EncodedClientReport::new(&self.report)?)
     }
 }
 
@@ -349,6 +425,8 @@ impl TaskUploadCounters {
             ReportRejectionReason::Expired => entry.increment_report_expired(),
             ReportRejectionReason::TooEarly => entry.increment_report_too_early(),
             ReportRejectionReason::OutdatedHpkeConfig(_) => entry.increment_report_outdated_key(),
+            // Rejections by the report policy are counted by the report policy's own metrics.
+            ReportRejectionReason::PolicyRejected => {}
         }
     }
 
@@ -0,0 +1,114 @@
+//! Accounting of the memory used by the helper while handling an aggregate request.
+
+use crate::aggregator::Error;
+use janus_messages::TaskId;
+use opentelemetry::{
+    metrics::{Histogram, Meter, Unit},
+    KeyValue,
+};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+pub(crate) fn aggregate_request_memory_histogram(meter: &Meter) -> Histogram<u64> {
+    meter
+        .u64_histogram("janus_aggregate_request_memory")
+        .with_description(
+            "Approximate peak memory held while handling each aggregate request in the helper.",
+        )
+        .with_unit(Unit::new("By"))
+        .init()
+}
+
+/// Tracks an approximation of the memory held while the helper decodes and processes a single
+/// aggregate request, and enforces the configured per-request budget.
+///
+/// Callers charge the tracker for the large buffers they hold: the request body, the decoded
+/// request, decrypted input shares, and preparation states. Charges are never released, so the
+/// tracked value is an upper bound on the request's peak usage.
+#[derive(Debug)]
+pub(crate) struct RequestMemoryBudget {
+    task_id: TaskId,
+    limit: Option<u64>,
+    used: AtomicU64,
+}
+
+impl RequestMemoryBudget {
+    pub(crate) fn new(task_id: TaskId, limit: Option<u64>) -> Self {
+        Self {
+            task_id,
+            limit,
+            used: AtomicU64::new(0),
+        }
+    }
+
+    /// Records that `bytes` more bytes are held on behalf of the request. Fails with
+    /// [`Error::AggregateRequestTooLarge`] if this takes the request over budget.
+    pub(crate) fn charge(&self, bytes: usize) -> Result<(), Error> {
+        let bytes = u64::try_from(bytes).unwrap_or(u64::MAX);
+        let used = self
+            .used
+            .fetch_add(bytes, Ordering::Relaxed)
+            .saturating_add(bytes);
+        match self.limit {
+            Some(limit) if used > limit => {
+                Err(Error::AggregateRequestTooLarge(self.task_id, limit))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the total number of bytes charged so far.
+    pub(crate) fn peak(&self) -> u64 {
+        self.used.load(Ordering::Relaxed)
+    }
+
+    /// Records the request's peak usage in `histogram`, labeled with the aggregation step being
+    /// handled and whether the request went over budget.
+    pub(crate) fn record(&self, histogram: &Histogram<u64>, step: &'static str) {
+        let peak = self.peak();
+        let over_budget = matches!(self.limit, Some(limit) if peak > limit);
+        histogram.record(
+            peak,
+            &[
+                KeyValue::new("step", step),
+                KeyValue::new("over_budget", over_budget),
+            ],
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::aggregator::{request_memory::RequestMemoryBudget, Error};
+    use assert_matches::assert_matches;
+    use janus_messages::TaskId;
+    use rand::random;
+
+    #[test]
+    fn charge_within_budget() {
+        let budget = RequestMemoryBudget::new(random(), Some(100));
+        budget.charge(60).unwrap();
+        budget.charge(40).unwrap();
+        assert_eq!(budget.peak(), 100);
+    }
+
+    #[test]
+    fn charge_over_budget() {
+        let task_id: TaskId = random();
+        let budget = RequestMemoryBudget::new(task_id, Some(100));
+        budget.charge(60).unwrap();
+        assert_matches!(
+            budget.charge(41),
+            Err(Error::AggregateRequestTooLarge(err_task_id, 100)) => {
+                assert_eq!(err_task_id, task_id);
+            }
+        );
+        assert_eq!(budget.peak(), 101);
+    }
+
+    #[test]
+    fn unlimited() {
+        let budget = RequestMemoryBudget::new(random(), None);
+        budget.charge(usize::MAX).unwrap();
+        budget.charge(usize::MAX).unwrap();
+    }
+}
@@ -0,0 +1,187 @@
+//! Compression of response bodies, negotiated with the client via the Accept-Encoding header.
+//!
+//! Compression is enabled per route, by attaching a [`ResponseCompression`] handler alongside the
+//! route's API handler. It is intended for endpoints with potentially large responses, such as
+//! collection results, and reduces the amount of data sent to clients that support it.
+
+use async_trait::async_trait;
+use flate2::{write::GzEncoder, Compression};
+use std::io::{self, Write};
+use tracing::warn;
+use trillium::{Body, Conn, Handler, KnownHeaderName, Status};
+
+/// A content coding supported for response bodies.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum ContentEncoding {
+    Zstd,
+    Gzip,
+}
+
+impl ContentEncoding {
+    /// Selects a content coding acceptable to the client, given the value of its Accept-Encoding
+    /// header. Codings are ranked by their quality values, with ties going to zstd. Returns `None`
+    /// if the client accepts neither supported coding.
+    pub(super) fn negotiate(accept_encoding: &str) -> Option<Self> {
+        let (mut zstd, mut gzip, mut wildcard) = (None, None, None);
+        for element in accept_encoding.split(',') {
+            let mut parts = element.split(';');
+            let coding = parts.next().unwrap_or_default().trim();
+            let quality = parts
+                .find_map(|param| param.trim().strip_prefix("q="))
+                .map_or(1.0, |quality| quality.trim().parse::<f32>().unwrap_or(0.0));
+
+            let slot = if coding.eq_ignore_ascii_case("zstd") {
+                &mut zstd
+            } else if coding.eq_ignore_ascii_case("gzip") || coding.eq_ignore_ascii_case("x-gzip") {
+                &mut gzip
+            } else if coding == "*" {
+                &mut wildcard
+            } else {
+                continue;
+            };
+            *slot = Some(quality);
+        }
+
+        // Codings that are explicitly listed take precedence over the wildcard.
+        let zstd = zstd.or(wildcard).unwrap_or(0.0);
+        let gzip = gzip.or(wildcard).unwrap_or(0.0);
+        if zstd > 0.0 && zstd >= gzip {
+            Some(Self::Zstd)
+        } else if gzip > 0.0 {
+            Some(Self::Gzip)
+        } else {
+            None
+        }
+    }
+
+    /// The name of this coding, as used in the Content-Encoding header.
+    pub(super) fn as_str(&self) -> &'static str {
+        match self {
+            Self::Zstd => "zstd",
+            Self::Gzip => "gzip",
+        }
+    }
+
+    /// Compresses `data` with this coding.
+    pub(super) fn compress(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            Self::Zstd => zstd::bulk::compress(data, zstd::DEFAULT_COMPRESSION_LEVEL),
+            Self::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+        }
+    }
+}
+
+/// A Trillium handler that compresses successful responses for clients that accept a supported
+/// content coding. If `enabled` is false, responses are passed through unmodified.
+pub(super) struct ResponseCompression {
+    enabled: bool,
+}
+
+impl ResponseCompression {
+    pub(super) fn new(enabled: bool) -> Self {
+        Self { enabled }
+    }
+}
+
+#[async_trait]
+impl Handler for ResponseCompression {
+    async fn run(&self, conn: Conn) -> Conn {
+        conn
+    }
+
+    async fn before_send(&self, mut conn: Conn) -> Conn {
+        if !self.enabled
+            || conn.status() != Some(Status::Ok)
+            || conn
+                .response_headers()
+                .has_header(KnownHeaderName::ContentEncoding)
+        {
+            return conn;
+        }
+        // Caches must not serve a response compressed for one client to another client that
+        // doesn't accept that coding.
+        conn.headers_mut()
+            .append(KnownHeaderName::Vary, "Accept-Encoding");
+
+        let encoding = match conn
+            .request_headers()
+            .get_str(KnownHeaderName::AcceptEncoding)
+            .and_then(ContentEncoding::negotiate)
+        {
+            Some(encoding) => encoding,
+            None => return conn,
+        };
+        // Our API handlers always produce bodies that are already in memory.
+        let body = match conn.response_body().and_then(Body::static_bytes) {
+            Some(body) if !body.is_empty() => body,
+            _ => return conn,
+        };
+
+        match encoding.compress(body) {
+            Ok(compressed) => conn
+                .with_header(KnownHeaderName::ContentEncoding, encoding.as_str())
+                .with_body(compressed),
+            Err(error) => {
+                warn!(?error, ?encoding, "Couldn't compress response body");
+                conn
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::aggregator::response_compression::ContentEncoding;
+
+    #[test]
+    fn negotiate() {
+        for (accept_encoding, expected) in [
+            ("", None),
+            ("identity", None),
+            ("br, deflate", None),
+            ("gzip", Some(ContentEncoding::Gzip)),
+            ("x-gzip", Some(ContentEncoding::Gzip)),
+            ("zstd", Some(ContentEncoding::Zstd)),
+            ("gzip, deflate, br, zstd", Some(ContentEncoding::Zstd)),
+            ("GZIP", Some(ContentEncoding::Gzip)),
+            ("gzip;q=1.0, zstd;q=0.5", Some(ContentEncoding::Gzip)),
+            ("gzip; q=0.2, zstd; q=0.8", Some(ContentEncoding::Zstd)),
+            ("zstd;q=0, gzip", Some(ContentEncoding::Gzip)),
+            ("zstd;q=0, gzip;q=0", None),
+            ("*", Some(ContentEncoding::Zstd)),
+            ("*, zstd;q=0", Some(ContentEncoding::Gzip)),
+            ("*;q=0", None),
+            ("*;q=0, gzip", Some(ContentEncoding::Gzip)),
+            ("zstd;q=bogus, gzip", Some(ContentEncoding::Gzip)),
+        ] {
+            assert_eq!(
+                ContentEncoding::negotiate(accept_encoding),
+                expected,
+                "{accept_encoding:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn compress_roundtrip() {
+        let data = b"an aggregate result ".repeat(100);
+
+        let compressed = ContentEncoding::Zstd.compress(&data).unwrap();
+        assert!(compressed.len() < data.len());
+        assert_eq!(zstd::decode_all(compressed.as_slice()).unwrap(), data);
+
+        let compressed = ContentEncoding::Gzip.compress(&data).unwrap();
+        assert!(compressed.len() < data.len());
+        let mut decompressed = Vec::new();
+        std::io::Read::read_to_end(
+            &mut flate2::read::GzDecoder::new(compressed.as_slice()),
+            &mut decompressed,
+        )
+        .unwrap();
+        assert_eq!(decompressed, data);
+    }
+}
@@ -0,0 +1,496 @@
+//! Recording of collection results, for deployments in which the leader also acts as collector.
+//!
+//! When a [`ResultSink`] is configured, the collection job driver decodes the aggregate result of
+//! each collection job it finishes, using the collector HPKE keypair for the job's task, and hands
+//! it to the sink. This allows a single operator to feed aggregates straight into an analytics
+//! store without running a separate collector. Recording is best-effort: a collection job is
+//! finished regardless of whether its result could be recorded.
+
+use crate::{
+    aggregator::{query_type::CollectableQueryType, CollectionSummary},
+    binary_utils::database_pool,
+    config::ResultSinkConfig,
+};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use deadpool_postgres::Pool;
+use janus_aggregator_core::{datastore::models::CollectionJob, task::AggregatorTask};
+use janus_core::hpke::HpkeKeypair;
+use janus_messages::{CollectionJobId, TaskId};
+use opentelemetry::{
+    metrics::{Counter, Meter, Unit},
+    KeyValue,
+};
+use prio::vdaf;
+use serde::Serialize;
+use std::{
+    fmt::Debug,
+    io::{self, Write},
+    sync::Arc,
+};
+use tracing::{debug, warn};
+
+#[cfg(feature = "gcp-bigquery")]
+use url::Url;
+
+/// The decoded result of a finished collection job, as recorded by a [`ResultSink`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CollectionResultRecord {
+    task_id: TaskId,
+    collection_job_id: String,
+    batch: String,
+    report_count: u64,
+    interval_start: u64,
+    interval_duration: u64,
+    aggregate_result: serde_json::Value,
+}
+
+impl CollectionResultRecord {
+    fn new(
+        task_id: TaskId,
+        collection_job_id: &CollectionJobId,
+        summary: CollectionSummary,
+    ) -> Self {
+        Self {
+            task_id,
+            collection_job_id: collection_job_id.to_string(),
+            batch: summary.batch,
+            report_count: summary.report_count,
+            interval_start: summary.interval_start,
+            interval_duration: summary.interval_duration,
+            aggregate_result: summary.aggregate_result,
+        }
+    }
+
+    pub fn task_id(&self) -> &TaskId {
+        &self.task_id
+    }
+
+    pub fn collection_job_id(&self) -> &str {
+        &self.collection_job_id
+    }
+
+    /// The batch that was collected, in human-readable form.
+    pub fn batch(&self) -> &str {
+        &self.batch
+    }
+
+    pub fn report_count(&self) -> u64 {
+        self.report_count
+    }
+
+    /// The start of the smallest interval containing the timestamps of all included reports, in
+    /// seconds since the UNIX epoch.
+    pub fn interval_start(&self) -> u64 {
+        self.interval_start
+    }
+
+    /// The duration of the smallest interval containing the timestamps of all included reports,
+    /// in seconds.
+    pub fn interval_duration(&self) -> u64 {
+        self.interval_duration
+    }
+
+    /// The unsharded aggregate result, as JSON. Its shape depends on the task's VDAF.
+    pub fn aggregate_result(&self) -> &serde_json::Value {
+        &self.aggregate_result
+    }
+}
+
+/// A destination for decoded collection results, e.g. an analytics database.
+#[async_trait]
+pub trait ResultSink: Debug + Send + Sync {
+    /// Records the result of a collection job. Once this method returns successfully, the result
+    /// must have been durably recorded.
+    async fn record(&self, record: &CollectionResultRecord) -> Result<()>;
+}
+
+/// Constructs the [`ResultSink`] described by the given configuration.
+pub async fn result_sink_from_config(config: &ResultSinkConfig) -> Result<Arc<dyn ResultSink>> {
+    match config {
+        ResultSinkConfig::Stdout => Ok(Arc::new(StdoutResultSink)),
+        ResultSinkConfig::Postgres { database, table } => Ok(Arc::new(PostgresResultSink::new(
+            database_pool(database, None)
+                .await
+                .context("couldn't connect to result database")?,
+            table,
+        ))),
+        #[cfg(feature = "gcp-bigquery")]
+        ResultSinkConfig::BigQuery {
+            project,
+            dataset,
+            table,
+            endpoint,
+        } => {
+            let http_client = reqwest::Client::builder()
+                .user_agent(CLIENT_USER_AGENT)
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .context("couldn't create HTTP client")?;
+            Ok(Arc::new(BigQueryResultSink::new(
+                http_client,
+                project,
+                dataset,
+                table,
+                endpoint.clone(),
+                // Unwrap safety: the metadata token URL is a valid URL.
+                crate::cloud_credentials::GCP_METADATA_TOKEN_URL
+                    .parse()
+                    .unwrap(),
+            )?))
+        }
+        #[cfg(not(feature = "gcp-bigquery"))]
+        ResultSinkConfig::BigQuery { .. } => Err(anyhow::anyhow!(
+            "BigQuery result sink was enabled in the configuration file, but support was not \
+             enabled at compile time. Rebuild with `--features gcp-bigquery`."
+        )),
+    }
+}
+
+/// Decodes the results of finished collection jobs and records them to a [`ResultSink`].
+#[derive(Debug)]
+pub(crate) struct ResultRecorder {
+    sink: Arc<dyn ResultSink>,
+    collector_hpke_keypairs: Vec<HpkeKeypair>,
+    recorded_results_counter: Counter<u64>,
+}
+
+impl ResultRecorder {
+    pub(crate) fn new(
+        meter: &Meter,
+        sink: Arc<dyn ResultSink>,
+        collector_hpke_keypairs: Vec<HpkeKeypair>,
+    ) -> Self {
+        let recorded_results_counter = meter
+            .u64_counter("janus_result_sink_recorded_results")
+            .with_description("Number of collection results recorded to the result sink.")
+            .with_unit(Unit::new("{collection}"))
+            .init();
+        for result in ["success", "error"] {
+            recorded_results_counter.add(0, &[KeyValue::new("result", result)]);
+        }
+
+        Self {
+            sink,
+            collector_hpke_keypairs,
+            recorded_results_counter,
+        }
+    }
+
+    /// Records the result of a finished collection job. Jobs belonging to tasks whose collector
+    /// HPKE keypair is not configured are skipped. Failures are logged and counted, but otherwise
+    /// ignored.
+    pub(crate) async fn record<const SEED_SIZE: usize, Q: CollectableQueryType, A>(
+        &self,
+        vdaf: &A,
+        task: &AggregatorTask,
+        collection_job: &CollectionJob<SEED_SIZE, Q, A>,
+    ) where
+        A: vdaf::Aggregator<SEED_SIZE, 16> + vdaf::Collector,
+        A::AggregateResult: Serialize,
+    {
+        let collector_hpke_keypair = match task.collector_hpke_config().and_then(|config| {
+            self.collector_hpke_keypairs
+                .iter()
+                .find(|keypair| keypair.config() == config)
+        }) {
+            Some(keypair) => keypair,
+            None => {
+                debug!(
+                    task_id = %task.id(),
+                    "Not recording collection result: no collector HPKE keypair for task"
+                );
+                return;
+            }
+        };
+
+        let result = match CollectionSummary::decode(vdaf, collection_job, collector_hpke_keypair) {
+            Ok(summary) => {
+                let record = CollectionResultRecord::new(*task.id(), collection_job.id(), summary);
+                self.sink.record(&record).await
+            }
+            Err(err) => Err(err.into()),
+        };
+        let result = match result {
+            Ok(()) => "success",
+            Err(err) => {
+                warn!(
+                    task_id = %task.id(),
+                    collection_job_id = %collection_job.id(),
+                    ?err,
+                    "Couldn't record collection result"
+                );
+                "error"
+            }
+        };
+        self.recorded_results_counter
+            .add(1, &[KeyValue::new("result", result)]);
+    }
+}
+
+/// A [`ResultSink`] which writes each result to standard output, as a line of JSON.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct StdoutResultSink;
+
+#[async_trait]
+impl ResultSink for StdoutResultSink {
+    async fn record(&self, record: &CollectionResultRecord) -> Result<()> {
+        let line = serde_json::to_string(record).context("couldn't serialize result")?;
+        writeln!(io::stdout().lock(), "{line}").context("couldn't write result")
+    }
+}
+
+/// A [`ResultSink`] which inserts each result as a row of a PostgreSQL table. The table must
+/// already exist; see the deployment documentation for its schema. Results for a collection job
+/// that was already recorded are ignored, provided the table has a unique constraint on
+/// `collection_job_id`.
+#[derive(Debug)]
+struct PostgresResultSink {
+    pool: Pool,
+    insert_statement: String,
+}
+
+impl PostgresResultSink {
+    fn new(pool: Pool, table: &str) -> Self {
+        let insert_statement = format!(
+            "INSERT INTO {} (task_id, collection_job_id, batch, report_count, interval_start,
+                interval_duration, aggregate_result)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT DO NOTHING",
+            quote_table_name(table)
+        );
+        Self {
+            pool,
+            insert_statement,
+        }
+    }
+}
+
+/// Quotes a possibly schema-qualified table name, so that it can be interpolated into a statement.
+fn quote_table_name(table: &str) -> String {
+    table
+        .split('.')
+        .map(|part| format!("\"{}\"", part.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+#[async_trait]
+impl ResultSink for PostgresResultSink {
+    async fn record(&self, record: &CollectionResultRecord) -> Result<()> {
+        let client = self
+            .pool
+            .get()
+            .await
+            .context("couldn't get result database connection")?;
+        let statement = client.prepare_cached(&self.insert_statement).await?;
+        client
+            .execute(
+                &statement,
+                &[
+                    /* task_id */ &record.task_id().to_string(),
+                    /* collection_job_id */ &record.collection_job_id(),
+                    /* batch */ &record.batch(),
+                    /* report_count */ &i64::try_from(record.report_count())?,
+                    /* interval_start */ &i64::try_from(record.interval_start())?,
+                    /* interval_duration */ &i64::try_from(record.interval_duration())?,
+                    /* aggregate_result */ record.aggregate_result(),
+                ],
+            )
+            .await
+            .context("couldn't insert result")?;
+        Ok(())
+    }
+}
+
+/// User agent sent with requests to the BigQuery API.
+#[cfg(feature = "gcp-bigquery")]
+const CLIENT_USER_AGENT: &str = concat!(
+    env!("CARGO_PKG_NAME"),
+    "/",
+    env!("CARGO_PKG_VERSION"),
+    "/result_sink",
+);
+
+/// Timeout applied to each request to the BigQuery API or metadata service.
+#[cfg(feature = "gcp-bigquery")]
+const REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Default base URL of the BigQuery API.
+#[cfg(feature = "gcp-bigquery")]
+const DEFAULT_BIGQUERY_ENDPOINT: &str = "https://bigquery.googleapis.com/";
+
+#[cfg(feature = "gcp-bigquery")]
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct InsertAllRequest<'a> {
+    rows: [InsertAllRow<'a>; 1],
+}
+
+#[cfg(feature = "gcp-bigquery")]
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct InsertAllRow<'a> {
+    insert_id: &'a str,
+    json: BigQueryRow<'a>,
+}
+
+/// A result, as inserted into BigQuery. The aggregate result is inserted as a JSON string, so it
+/// may be stored in either a `STRING` or a `JSON` column.
+#[cfg(feature = "gcp-bigquery")]
+#[derive(Serialize)]
+struct BigQueryRow<'a> {
+    task_id: String,
+    collection_job_id: &'a str,
+    batch: &'a str,
+    report_count: u64,
+    interval_start: u64,
+    interval_duration: u64,
+    aggregate_result: String,
+}
+
+#[cfg(feature = "gcp-bigquery")]
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InsertAllResponse {
+    #[serde(default)]
+    insert_errors: Vec<serde_json::Value>,
+}
+
+/// A [`ResultSink`] that streams each result into a BigQuery table, using the [insertAll][1] API.
+/// Each result's collection job ID is used as its insert ID, so BigQuery deduplicates retried
+/// inserts on a best-effort basis.
+///
+/// [1]: https://cloud.google.com/bigquery/docs/reference/rest/v2/tabledata/insertAll
+#[cfg(feature = "gcp-bigquery")]
+#[derive(Debug)]
+struct BigQueryResultSink {
+    http_client: reqwest::Client,
+    insert_all_url: Url,
+    token_url: Url,
+}
+
+#[cfg(feature = "gcp-bigquery")]
+impl BigQueryResultSink {
+    fn new(
+        http_client: reqwest::Client,
+        project: &str,
+        dataset: &str,
+        table: &str,
+        endpoint: Option<Url>,
+        token_url: Url,
+    ) -> Result<Self> {
+        let endpoint = match endpoint {
+            Some(endpoint) => endpoint,
+            // Unwrap safety: the default endpoint is a valid URL.
+            None => DEFAULT_BIGQUERY_ENDPOINT.parse().unwrap(),
+        };
+        let insert_all_url = endpoint
+            .join(&format!(
+                "bigquery/v2/projects/{project}/datasets/{dataset}/tables/{table}/insertAll"
+            ))
+            .with_context(|| format!("invalid BigQuery table {project}.{dataset}.{table}"))?;
+        Ok(Self {
+            http_client,
+            insert_all_url,
+            token_url,
+        })
+    }
+}
+
+#[cfg(feature = "gcp-bigquery")]
+#[async_trait]
+impl ResultSink for BigQueryResultSink {
+    async fn record(&self, record: &CollectionResultRecord) -> Result<()> {
+        let access_token = crate::cloud_credentials::fetch_gcp_access_token(
+            &self.http_client,
+            self.token_url.clone(),
+        )
+        .await?;
+
+        let request = InsertAllRequest {
+            rows: [InsertAllRow {
+                insert_id: record.collection_job_id(),
+                json: BigQueryRow {
+                    task_id: record.task_id().to_string(),
+                    collection_job_id: record.collection_job_id(),
+                    batch: record.batch(),
+                    report_count: record.report_count(),
+                    interval_start: record.interval_start(),
+                    interval_duration: record.interval_duration(),
+                    aggregate_result: record.aggregate_result().to_string(),
+                },
+            }],
+        };
+
+        let response: InsertAllResponse = self
+            .http_client
+            .post(self.insert_all_url.clone())
+            .bearer_auth(access_token)
+            .json(&request)
+            .send()
+            .await
+            .context("couldn't send request to BigQuery")?
+            .error_for_status()
+            .context("BigQuery returned an error")?
+            .json()
+            .await
+            .context("couldn't parse BigQuery response")?;
+        if !response.insert_errors.is_empty() {
+            anyhow::bail!("BigQuery rejected row: {:?}", response.insert_errors);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::aggregator::{
+        result_sink::{quote_table_name, CollectionResultRecord},
+        CollectionSummary,
+    };
+    use rand::random;
+    use serde_json::json;
+
+    #[test]
+    fn quote_table_names() {
+        assert_eq!(quote_table_name("results"), "\"results\"");
+        assert_eq!(
+            quote_table_name("analytics.results"),
+            "\"analytics\".\"results\""
+        );
+        assert_eq!(
+            quote_table_name("results\"; DROP TABLE tasks; --"),
+            "\"results\"\"; DROP TABLE tasks; --\""
+        );
+    }
+
+    #[test]
+    fn record_json() {
+        let task_id = random();
+        let collection_job_id = random();
+        let record = CollectionResultRecord::new(
+            task_id,
+            &collection_job_id,
+            CollectionSummary {
+                batch: "batch".to_string(),
+                report_count: 10,
+                interval_start: 1000,
+                interval_duration: 100,
+                aggregate_result: json!([1, 2, 3]),
+            },
+        );
+        assert_eq!(
+            serde_json::to_value(&record).unwrap(),
+            json!({
+                "task_id": task_id.to_string(),
+                "collection_job_id": collection_job_id.to_string(),
+                "batch": "batch",
+                "report_count": 10,
+                "interval_start": 1000,
+                "interval_duration": 100,
+                "aggregate_result": [1, 2, 3],
+            })
+        );
+    }
+}
@@ -0,0 +1,461 @@
+//! Classification of errors encountered while stepping aggregation or collection jobs into errors
+//! that may succeed if the job is retried, and errors that will never succeed no matter how many
+//! times the job is retried. Errors are also attributed to a [`FailureDomain`], so that job
+//! drivers can log, count, and record failures by where they came from.
+
+use crate::aggregator::Error;
+use janus_aggregator_core::datastore;
+use janus_core::{
+    http::HttpErrorResponse,
+    retries::{is_retryable_http_status, is_retryable_network_error},
+};
+use janus_messages::problem_type::DapProblemType;
+use opentelemetry::{
+    metrics::{Counter, Meter, Unit},
+    KeyValue,
+};
+
+/// Describes whether a job step that failed with some error should be retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorClass {
+    /// The error is transient, and the job step may succeed if it is retried later.
+    Retryable(ErrorSource),
+    /// The error is permanent, and the job should be abandoned.
+    Fatal(ErrorSource),
+}
+
+impl ErrorClass {
+    /// Returns true if the job step should be retried.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, ErrorClass::Retryable(_))
+    }
+
+    fn class_label(&self) -> &'static str {
+        match self {
+            ErrorClass::Retryable(_) => "retryable",
+            ErrorClass::Fatal(_) => "fatal",
+        }
+    }
+
+    /// Returns the part of the error that determined its class.
+    pub fn source(&self) -> ErrorSource {
+        match self {
+            ErrorClass::Retryable(source) | ErrorClass::Fatal(source) => *source,
+        }
+    }
+
+    /// Returns the failure domain of the error.
+    pub fn domain(&self) -> FailureDomain {
+        self.source().domain()
+    }
+}
+
+/// Describes which part of an error determined its [`ErrorClass`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorSource {
+    /// The peer aggregator responded with a DAP problem document.
+    ProblemType,
+    /// The peer aggregator responded with an error HTTP status, without a recognized DAP problem
+    /// document.
+    HttpStatus,
+    /// The request to the peer aggregator failed without receiving a response.
+    Network,
+    /// The datastore returned an error.
+    Datastore,
+    /// A VDAF, HPKE, or message decoding operation failed.
+    Crypto,
+    /// Any other error.
+    Other,
+}
+
+impl ErrorSource {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ErrorSource::ProblemType => "problem_type",
+            ErrorSource::HttpStatus => "http_status",
+            ErrorSource::Network => "network",
+            ErrorSource::Datastore => "datastore",
+            ErrorSource::Crypto => "crypto",
+            ErrorSource::Other => "other",
+        }
+    }
+
+    fn domain(&self) -> FailureDomain {
+        match self {
+            ErrorSource::ProblemType | ErrorSource::HttpStatus | ErrorSource::Network => {
+                FailureDomain::Peer
+            }
+            ErrorSource::Datastore => FailureDomain::Datastore,
+            ErrorSource::Crypto => FailureDomain::Crypto,
+            ErrorSource::Other => FailureDomain::Other,
+        }
+    }
+}
+
+/// Describes where a job step failure came from. Failures in the datastore or at the peer
+/// aggregator are often transient, while cryptographic failures are permanent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureDomain {
+    /// The datastore failed.
+    Datastore,
+    /// The request to the peer aggregator failed, or the peer aggregator rejected it.
+    Peer,
+    /// A VDAF, HPKE, or message decoding operation failed.
+    Crypto,
+    /// Any other failure.
+    Other,
+}
+
+impl FailureDomain {
+    /// Returns the name of this failure domain, as used in metrics, logs, and abandoned jobs.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FailureDomain::Datastore => "datastore",
+            FailureDomain::Peer => "peer",
+            FailureDomain::Crypto => "crypto",
+            FailureDomain::Other => "other",
+        }
+    }
+}
+
+/// Classifies errors encountered while stepping jobs, and records a metric for each classified
+/// error.
+#[derive(Clone)]
+pub struct RetryClassifier {
+    job_step_error_counter: Counter<u64>,
+}
+
+impl RetryClassifier {
+    pub fn new(meter: &Meter) -> Self {
+        let job_step_error_counter = meter
+            .u64_counter("janus_job_step_errors")
+            .with_description("Count of failed job steps, by error class and source.")
+            .with_unit(Unit::new("{error}"))
+            .init();
+
+        // Initialize counters with desired labels. This causes Prometheus to see the first
+        // non-zero value we record.
+        for class in ["retryable", "fatal"] {
+            for source in [
+                ErrorSource::ProblemType,
+                ErrorSource::HttpStatus,
+                ErrorSource::Network,
+                ErrorSource::Datastore,
+                ErrorSource::Crypto,
+                ErrorSource::Other,
+            ] {
+                job_step_error_counter.add(
+                    0,
+                    &[
+                        KeyValue::new("class", class),
+                        KeyValue::new("source", source.as_str()),
+                    ],
+                );
+            }
+        }
+
+        Self {
+            job_step_error_counter,
+        }
+    }
+
+    /// Classifies the given error, recording it in the job step error metric.
+    pub fn classify(&self, error: &Error) -> ErrorClass {
+        let class = classify_error(error);
+        self.job_step_error_counter.add(
+            1,
+            &[
+                KeyValue::new("class", class.class_label()),
+                KeyValue::new("source", class.source().as_str()),
+            ],
+        );
+        class
+    }
+}
+
+/// Determines whether the given [`Error`], encountered while stepping an aggregation job or a
+/// collection job, is retryable.
+pub fn classify_error(error: &Error) -> ErrorClass {
+    match error {
+        Error::Http(http_error_response) => classify_http_error_response(http_error_response),
+        // The helper may not have been provisioned with the task yet. The number of attempts is
+        // still bounded by the job driver's maximum attempts.
+        Error::AggregationJobInitUnauthorized(_) => ErrorClass::Retryable(ErrorSource::ProblemType),
+        Error::HttpClient(error) => {
+            if is_retryable_network_error(error) {
+                ErrorClass::Retryable(ErrorSource::Network)
+            } else {
+                ErrorClass::Fatal(ErrorSource::Network)
+            }
+        }
+        Error::Datastore(error) => match error {
+            datastore::Error::Db(_)
+            | datastore::Error::Pool(_)
+            | datastore::Error::TooManyRetries { .. } => {
+                ErrorClass::Retryable(ErrorSource::Datastore)
+            }
+            datastore::Error::User(error) => match error.downcast_ref::<Error>() {
+                Some(error) => classify_error(error),
+                None => ErrorClass::Fatal(ErrorSource::Datastore),
+            },
+            _ => ErrorClass::Fatal(ErrorSource::Datastore),
+        },
+        Error::Vdaf(_)
+        | Error::DifferentialPrivacy(_)
+        | Error::Hpke(_)
+        | Error::MessageDecode(_) => ErrorClass::Fatal(ErrorSource::Crypto),
+        _ => ErrorClass::Fatal(ErrorSource::Other),
+    }
+}
+
+/// Classifies an error response from the peer aggregator. A recognized DAP problem type takes
+/// precedence over the HTTP status code, since a helper may report a problem that will never be
+/// resolved by retrying with a 5xx status.
+fn classify_http_error_response(http_error_response: &HttpErrorResponse) -> ErrorClass {
+    match http_error_response.dap_problem_type() {
+        Some(problem_type) => match problem_type {
+            // The helper may not have been provisioned with the task yet, may be rotating its
+            // HPKE configurations, or may consider the batch not yet ready.
+            DapProblemType::UnrecognizedTask
+            | DapProblemType::OutdatedConfig
+            | DapProblemType::ReportTooEarly => ErrorClass::Retryable(ErrorSource::ProblemType),
+
+            DapProblemType::InvalidMessage
+            | DapProblemType::StepMismatch
+            | DapProblemType::MissingTaskId
+            | DapProblemType::UnrecognizedAggregationJob
+            | DapProblemType::ReportRejected
+            | DapProblemType::BatchInvalid
+            | DapProblemType::InvalidBatchSize
+            | DapProblemType::BatchQueriedTooManyTimes
+            | DapProblemType::BatchMismatch
+            | DapProblemType::UnauthorizedRequest
+            | DapProblemType::BatchOverlap
+            | DapProblemType::InvalidTask => ErrorClass::Fatal(ErrorSource::ProblemType),
+        },
+        None => {
+            if is_retryable_http_status(http_error_response.status()) {
+                ErrorClass::Retryable(ErrorSource::HttpStatus)
+            } else {
+                ErrorClass::Fatal(ErrorSource::HttpStatus)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::aggregator::{
+        retry_classification::{classify_error, ErrorClass, ErrorSource, FailureDomain},
+        Error,
+    };
+    use assert_matches::assert_matches;
+    use http::StatusCode;
+    use http_api_problem::HttpApiProblem;
+    use janus_aggregator_core::datastore;
+    use janus_core::http::HttpErrorResponse;
+    use janus_messages::problem_type::DapProblemType;
+    use prio::{codec::CodecError, vdaf::VdafError};
+    use std::time::Duration;
+    use tokio::{io::AsyncReadExt, net::TcpListener};
+
+    fn problem_response(status: StatusCode, problem_type: DapProblemType) -> Error {
+        Error::Http(Box::new(
+            HttpApiProblem::new(status)
+                .type_url(problem_type.type_uri())
+                .try_into()
+                .unwrap(),
+        ))
+    }
+
+    #[test]
+    fn classify_http_status() {
+        for (status, expected) in [
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                ErrorClass::Retryable(ErrorSource::HttpStatus),
+            ),
+            (
+                StatusCode::SERVICE_UNAVAILABLE,
+                ErrorClass::Retryable(ErrorSource::HttpStatus),
+            ),
+            (
+                StatusCode::TOO_MANY_REQUESTS,
+                ErrorClass::Retryable(ErrorSource::HttpStatus),
+            ),
+            (
+                StatusCode::NOT_IMPLEMENTED,
+                ErrorClass::Fatal(ErrorSource::HttpStatus),
+            ),
+            (
+                StatusCode::BAD_REQUEST,
+                ErrorClass::Fatal(ErrorSource::HttpStatus),
+            ),
+            (
+                StatusCode::FORBIDDEN,
+                ErrorClass::Fatal(ErrorSource::HttpStatus),
+            ),
+        ] {
+            let error = Error::Http(Box::new(HttpErrorResponse::from(status)));
+            assert_eq!(classify_error(&error), expected, "{status}");
+        }
+    }
+
+    #[test]
+    fn classify_problem_type() {
+        for (status, problem_type, expected) in [
+            (
+                StatusCode::BAD_REQUEST,
+                DapProblemType::UnrecognizedTask,
+                ErrorClass::Retryable(ErrorSource::ProblemType),
+            ),
+            (
+                StatusCode::BAD_REQUEST,
+                DapProblemType::OutdatedConfig,
+                ErrorClass::Retryable(ErrorSource::ProblemType),
+            ),
+            (
+                StatusCode::BAD_REQUEST,
+                DapProblemType::ReportTooEarly,
+                ErrorClass::Retryable(ErrorSource::ProblemType),
+            ),
+            (
+                StatusCode::BAD_REQUEST,
+                DapProblemType::BatchQueriedTooManyTimes,
+                ErrorClass::Fatal(ErrorSource::ProblemType),
+            ),
+            // A fatal problem type is fatal even if the status code is retryable.
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                DapProblemType::UnauthorizedRequest,
+                ErrorClass::Fatal(ErrorSource::ProblemType),
+            ),
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                DapProblemType::StepMismatch,
+                ErrorClass::Fatal(ErrorSource::ProblemType),
+            ),
+        ] {
+            let error = problem_response(status, problem_type);
+            assert_eq!(
+                classify_error(&error),
+                expected,
+                "{status} {problem_type:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn classify_aggregation_job_init_unauthorized() {
+        let error = assert_matches!(
+            problem_response(
+                StatusCode::BAD_REQUEST,
+                DapProblemType::UnauthorizedRequest
+            ),
+            Error::Http(error_response) => error_response
+        );
+        assert_eq!(
+            classify_error(&Error::AggregationJobInitUnauthorized(error)),
+            ErrorClass::Retryable(ErrorSource::ProblemType)
+        );
+    }
+
+    #[test]
+    fn classify_datastore_error() {
+        assert_eq!(
+            classify_error(&Error::Datastore(datastore::Error::User(
+                problem_response(
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    DapProblemType::ReportTooEarly
+                )
+                .into()
+            ))),
+            ErrorClass::Retryable(ErrorSource::ProblemType)
+        );
+        assert_eq!(
+            classify_error(&Error::Datastore(datastore::Error::User(
+                Error::Http(Box::new(HttpErrorResponse::from(StatusCode::BAD_REQUEST))).into()
+            ))),
+            ErrorClass::Fatal(ErrorSource::HttpStatus)
+        );
+        assert_eq!(
+            classify_error(&Error::Datastore(datastore::Error::MutationTargetNotFound)),
+            ErrorClass::Fatal(ErrorSource::Datastore)
+        );
+    }
+
+    #[test]
+    fn classify_crypto_error() {
+        assert_eq!(
+            classify_error(&Error::Vdaf(VdafError::Uncategorized("oops".to_string()))),
+            ErrorClass::Fatal(ErrorSource::Crypto)
+        );
+        assert_eq!(
+            classify_error(&Error::MessageDecode(CodecError::UnexpectedValue)),
+            ErrorClass::Fatal(ErrorSource::Crypto)
+        );
+    }
+
+    #[test]
+    fn failure_domains() {
+        assert_eq!(
+            classify_error(&Error::Datastore(datastore::Error::TooManyRetries {
+                source: None
+            }))
+            .domain(),
+            FailureDomain::Datastore
+        );
+        assert_eq!(
+            classify_error(&problem_response(
+                StatusCode::BAD_REQUEST,
+                DapProblemType::InvalidMessage
+            ))
+            .domain(),
+            FailureDomain::Peer
+        );
+        assert_eq!(
+            classify_error(&Error::Http(Box::new(HttpErrorResponse::from(
+                StatusCode::BAD_GATEWAY
+            ))))
+            .domain(),
+            FailureDomain::Peer
+        );
+        assert_eq!(
+            classify_error(&Error::MessageDecode(CodecError::UnexpectedValue)).domain(),
+            FailureDomain::Crypto
+        );
+        assert_eq!(
+            classify_error(&Error::Internal("oops".to_string())).domain(),
+            FailureDomain::Other
+        );
+    }
+
+    #[tokio::test]
+    async fn classify_connection_reset() {
+        // Accept a connection, read the request, and then reset the connection rather than
+        // responding.
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = [0; 1024];
+            let _ = stream.read(&mut buf).await.unwrap();
+            stream.set_linger(Some(Duration::ZERO)).unwrap();
+        });
+
+        let error = reqwest::get(format!("http://{addr}/")).await.unwrap_err();
+        server.await.unwrap();
+        assert_eq!(
+            classify_error(&Error::HttpClient(error)),
+            ErrorClass::Retryable(ErrorSource::Network)
+        );
+    }
+
+    #[test]
+    fn classify_other_error() {
+        assert_eq!(
+            classify_error(&Error::Internal("oops".to_string())),
+            ErrorClass::Fatal(ErrorSource::Other)
+        );
+    }
+}
@@ -0,0 +1,522 @@
+//! A simulation of the full aggregation pipeline, which runs randomized interleavings of uploads,
+//! aggregation job creation and stepping, collection, and worker crashes against a leader and a
+//! helper in a single process.
+//!
+//! Both aggregators share a [`MockClock`], and the leader reaches the helper over loopback HTTP.
+//! Each aggregator has its own ephemeral datastore, since Janus has no in-memory datastore. The
+//! sequence of operations is drawn from a random number generator seeded per run, and operations
+//! run one at a time, so a failing run can be replayed by setting `JANUS_SIMULATION_SEED` to the
+//! seed it printed. `JANUS_SIMULATION_RUNS` sets how many seeds are tried, e.g. to run thousands
+//! of interleavings locally.
+//!
+//! After the random operations, every job is driven to completion, and each collection must count
+//! exactly the reports that were uploaded to its batch interval.
+
+use crate::aggregator::{
+    aggregation_job_creator::AggregationJobCreator, aggregation_job_driver::AggregationJobDriver,
+    collection_job_driver::CollectionJobDriver, http_handlers::aggregator_handler,
+    test_util::BATCH_AGGREGATION_SHARD_COUNT, Config,
+};
+use janus_aggregator_core::{
+    datastore::{
+        test_util::{ephemeral_datastore, EphemeralDatastore},
+        Datastore,
+    },
+    task::{
+        test_util::{Task, TaskBuilder},
+        AggregatorTask, QueryType,
+    },
+    test_util::noop_meter,
+};
+use janus_client::Client;
+use janus_collector::{CollectionJob, Collector, PollResult};
+use janus_core::{
+    retries::test_util::LimitedRetryer,
+    test_util::{install_test_trace_subscriber, runtime::TestRuntime},
+    time::{Clock, IntervalExt, MockClock, TimeExt},
+    vdaf::VdafInstance,
+};
+use janus_messages::{query_type::TimeInterval, Duration, Interval, Query};
+use prio::vdaf::prio3::{Prio3, Prio3Count};
+use rand::{random, rngs::StdRng, Rng, SeedableRng};
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    fmt::Debug,
+    sync::Arc,
+    time::Duration as StdDuration,
+};
+use tracing::debug;
+use trillium_tokio::Stopper;
+use url::Url;
+
+/// Number of seeds to run, unless overridden by `JANUS_SIMULATION_RUNS`.
+const DEFAULT_SIMULATION_RUNS: u64 = 3;
+
+/// Number of random operations in each run.
+const OPERATIONS_PER_RUN: usize = 150;
+
+/// Maximum number of rounds of driving every job after the random operations.
+const MAX_DRAIN_ROUNDS: usize = 20;
+
+const LEASE_DURATION: StdDuration = StdDuration::from_secs(600);
+
+/// Crashed workers don't count as failed attempts, so jobs are never abandoned for too many
+/// attempts.
+const MAXIMUM_ATTEMPTS_BEFORE_FAILURE: usize = 1000;
+
+/// An operation of the simulation.
+#[derive(Clone, Copy, Debug)]
+enum Operation {
+    Upload,
+    AdvanceClock,
+    CreateAggregationJobs,
+    StepAggregationJob,
+    /// Acquire an aggregation job's lease, then drop it, as if the worker crashed.
+    CrashAggregationJobDriver,
+    StartCollection,
+    StepCollectionJob,
+    /// Acquire a collection job's lease, then drop it, as if the worker crashed.
+    CrashCollectionJobDriver,
+}
+
+impl Operation {
+    /// Operations with their relative weights.
+    const WEIGHTED: [(Operation, u32); 8] = [
+        (Operation::Upload, 30),
+        (Operation::AdvanceClock, 10),
+        (Operation::CreateAggregationJobs, 15),
+        (Operation::StepAggregationJob, 15),
+        (Operation::CrashAggregationJobDriver, 5),
+        (Operation::StartCollection, 10),
+        (Operation::StepCollectionJob, 10),
+        (Operation::CrashCollectionJobDriver, 5),
+    ];
+
+    fn sample(rng: &mut StdRng) -> Operation {
+        let total: u32 = Self::WEIGHTED.iter().map(|(_, weight)| weight).sum();
+        let mut choice = rng.gen_range(0..total);
+        for (operation, weight) in Self::WEIGHTED {
+            if choice < weight {
+                return operation;
+            }
+            choice -= weight;
+        }
+        unreachable!()
+    }
+}
+
+/// Count and sum of the measurements uploaded to a batch interval.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct Tally {
+    report_count: u64,
+    sum: u64,
+}
+
+struct Simulation {
+    seed: u64,
+    rng: StdRng,
+    clock: MockClock,
+    task: Task,
+    leader_task: Arc<AggregatorTask>,
+    leader_datastore: Arc<Datastore<MockClock>>,
+
+    client: Client<Prio3Count>,
+    collector: Collector<Prio3Count>,
+    aggregation_job_creator: Arc<AggregationJobCreator<MockClock>>,
+    aggregation_job_driver: Arc<AggregationJobDriver<LimitedRetryer>>,
+    collection_job_driver: Arc<CollectionJobDriver<LimitedRetryer>>,
+
+    /// Measurements uploaded to each batch interval.
+    uploads: HashMap<Interval, Tally>,
+    /// Batch intervals for which a collection has been started.
+    collected: HashSet<Interval>,
+    /// Collections that have been started, but whose results have not yet been received.
+    pending_collections: Vec<(Interval, CollectionJob<(), TimeInterval>)>,
+
+    /// Stops both aggregators' servers.
+    stopper: Stopper,
+    _leader_ephemeral_datastore: EphemeralDatastore,
+    _helper_ephemeral_datastore: EphemeralDatastore,
+}
+
+impl Simulation {
+    async fn new(seed: u64) -> Self {
+        let clock = MockClock::default();
+        let config = Config {
+            batch_aggregation_shard_count: BATCH_AGGREGATION_SHARD_COUNT,
+            ..Default::default()
+        };
+
+        let leader_ephemeral_datastore = ephemeral_datastore().await;
+        let leader_datastore = Arc::new(leader_ephemeral_datastore.datastore(clock.clone()).await);
+        let helper_ephemeral_datastore = ephemeral_datastore().await;
+        let helper_datastore = Arc::new(helper_ephemeral_datastore.datastore(clock.clone()).await);
+
+        let stopper = Stopper::new();
+        let leader_endpoint = serve(
+            Arc::clone(&leader_datastore),
+            clock.clone(),
+            config.clone(),
+            &stopper,
+        )
+        .await;
+        let helper_endpoint = serve(
+            Arc::clone(&helper_datastore),
+            clock.clone(),
+            config,
+            &stopper,
+        )
+        .await;
+
+        let task = TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Prio3Count)
+            .with_leader_aggregator_endpoint(leader_endpoint.clone())
+            .with_helper_aggregator_endpoint(helper_endpoint.clone())
+            .with_min_batch_size(1)
+            .build();
+        let leader_task = Arc::new(task.leader_view().unwrap());
+        leader_datastore
+            .put_aggregator_task(&leader_task)
+            .await
+            .unwrap();
+        helper_datastore
+            .put_aggregator_task(&task.helper_view().unwrap())
+            .await
+            .unwrap();
+
+        let vdaf = Prio3::new_count(2).unwrap();
+        let client = Client::builder(
+            *task.id(),
+            leader_endpoint.clone(),
+            helper_endpoint,
+            *task.time_precision(),
+            vdaf.clone(),
+        )
+        .build()
+        .await
+        .unwrap();
+        let collector = Collector::new(
+            *task.id(),
+            leader_endpoint,
+            task.collector_auth_token().clone(),
+            task.collector_hpke_keypair().clone(),
+            vdaf,
+        )
+        .unwrap();
+
+        let meter = noop_meter();
+        let aggregation_job_creator = Arc::new(AggregationJobCreator::new(
+            leader_ephemeral_datastore.datastore(clock.clone()).await,
+            meter.clone(),
+            BATCH_AGGREGATION_SHARD_COUNT,
+            StdDuration::from_secs(3600),
+            StdDuration::from_secs(60),
+            1,
+            3,
+            100,
+        ));
+        let aggregation_job_driver = Arc::new(AggregationJobDriver::new(
+            reqwest::Client::new(),
+            LimitedRetryer::new(0),
+            &meter,
+            BATCH_AGGREGATION_SHARD_COUNT,
+        ));
+        let collection_job_driver = Arc::new(CollectionJobDriver::new(
+            reqwest::Client::new(),
+            LimitedRetryer::new(0),
+            &meter,
+            BATCH_AGGREGATION_SHARD_COUNT,
+            StdDuration::ZERO,
+        ));
+
+        Self {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+            clock,
+            task,
+            leader_task,
+            leader_datastore,
+            client,
+            collector,
+            aggregation_job_creator,
+            aggregation_job_driver,
+            collection_job_driver,
+            uploads: HashMap::new(),
+            collected: HashSet::new(),
+            pending_collections: Vec::new(),
+            stopper,
+            _leader_ephemeral_datastore: leader_ephemeral_datastore,
+            _helper_ephemeral_datastore: helper_ephemeral_datastore,
+        }
+    }
+
+    async fn run(mut self) {
+        for _ in 0..OPERATIONS_PER_RUN {
+            let operation = Operation::sample(&mut self.rng);
+            debug!(?operation, "Simulation operation");
+            match operation {
+                Operation::Upload => self.upload().await,
+                Operation::AdvanceClock => {
+                    let seconds = self
+                        .rng
+                        .gen_range(1..=self.task.time_precision().as_seconds());
+                    self.clock.advance(&Duration::from_seconds(seconds));
+                }
+                Operation::CreateAggregationJobs => self.create_aggregation_jobs().await,
+                Operation::StepAggregationJob => {
+                    self.step_aggregation_jobs(1).await;
+                }
+                Operation::CrashAggregationJobDriver => {
+                    self.aggregation_job_driver
+                        .make_incomplete_job_acquirer_callback(
+                            Arc::clone(&self.leader_datastore),
+                            LEASE_DURATION,
+                        )(1)
+                    .await
+                    .unwrap();
+                }
+                Operation::StartCollection => self.start_collection().await,
+                Operation::StepCollectionJob => {
+                    self.step_collection_jobs(1).await;
+                    self.poll_collections().await;
+                }
+                Operation::CrashCollectionJobDriver => {
+                    self.collection_job_driver
+                        .make_incomplete_job_acquirer_callback(
+                            Arc::clone(&self.leader_datastore),
+                            LEASE_DURATION,
+                        )(1)
+                    .await
+                    .unwrap();
+                }
+            }
+        }
+
+        // Drive every job to completion. Advancing the clock past the lease duration releases the
+        // leases held by crashed workers.
+        for _ in 0..MAX_DRAIN_ROUNDS {
+            if self.pending_collections.is_empty() {
+                break;
+            }
+            self.clock
+                .advance(&Duration::from_seconds(LEASE_DURATION.as_secs() + 1));
+            self.create_aggregation_jobs().await;
+            while self.step_aggregation_jobs(10).await > 0 {}
+            while self.step_collection_jobs(10).await > 0 {}
+            self.poll_collections().await;
+        }
+        assert!(
+            self.pending_collections.is_empty(),
+            "seed {}: collections of {:?} did not finish",
+            self.seed,
+            self.pending_collections
+                .iter()
+                .map(|(interval, _)| interval)
+                .collect::<Vec<_>>(),
+        );
+
+        self.stopper.stop();
+    }
+
+    async fn upload(&mut self) {
+        let measurement = self.rng.gen::<bool>();
+        let time = self.clock.now();
+        self.check(
+            self.client.upload_with_time(&measurement, time).await,
+            "couldn't upload report",
+        );
+
+        let time_precision = *self.task.time_precision();
+        let interval = Interval::new(
+            time.to_batch_interval_start(&time_precision).unwrap(),
+            time_precision,
+        )
+        .unwrap();
+        let tally = self.uploads.entry(interval).or_default();
+        tally.report_count += 1;
+        tally.sum += u64::from(measurement);
+    }
+
+    async fn create_aggregation_jobs(&self) {
+        self.check(
+            Arc::clone(&self.aggregation_job_creator)
+                .create_aggregation_jobs_for_task(Arc::clone(&self.leader_task))
+                .await,
+            "couldn't create aggregation jobs",
+        );
+    }
+
+    /// Steps up to `count` aggregation jobs, returning how many were stepped. Failed steps are
+    /// retried once their lease expires, so they are not treated as simulation failures.
+    async fn step_aggregation_jobs(&self, count: usize) -> usize {
+        let leases = self.check(
+            self.aggregation_job_driver
+                .make_incomplete_job_acquirer_callback(
+                    Arc::clone(&self.leader_datastore),
+                    LEASE_DURATION,
+                )(count)
+            .await,
+            "couldn't acquire aggregation jobs",
+        );
+        let stepped = leases.len();
+        let stepper = Arc::clone(&self.aggregation_job_driver).make_job_stepper_callback(
+            Arc::clone(&self.leader_datastore),
+            MAXIMUM_ATTEMPTS_BEFORE_FAILURE,
+        );
+        for lease in leases {
+            if let Err(error) = stepper(lease).await {
+                debug!(?error, "Aggregation job step failed");
+            }
+        }
+        stepped
+    }
+
+    /// Steps up to `count` collection jobs, returning how many were stepped.
+    async fn step_collection_jobs(&self, count: usize) -> usize {
+        let leases = self.check(
+            self.collection_job_driver
+                .make_incomplete_job_acquirer_callback(
+                    Arc::clone(&self.leader_datastore),
+                    LEASE_DURATION,
+                )(count)
+            .await,
+            "couldn't acquire collection jobs",
+        );
+        let stepped = leases.len();
+        let stepper = Arc::clone(&self.collection_job_driver).make_job_stepper_callback(
+            Arc::clone(&self.leader_datastore),
+            MAXIMUM_ATTEMPTS_BEFORE_FAILURE,
+        );
+        for lease in leases {
+            if let Err(error) = stepper(lease).await {
+                debug!(?error, "Collection job step failed");
+            }
+        }
+        stepped
+    }
+
+    /// Starts collecting a random batch interval that has ended, and has not yet been collected.
+    /// Since reports are always uploaded at the current time, no more reports can be uploaded to
+    /// the interval.
+    async fn start_collection(&mut self) {
+        let now = self.clock.now();
+        let mut candidates: Vec<_> = self
+            .uploads
+            .keys()
+            .filter(|interval| !self.collected.contains(interval) && !interval.end().is_after(&now))
+            .copied()
+            .collect();
+        if candidates.is_empty() {
+            return;
+        }
+        // Sort, so that the choice only depends on the seed.
+        candidates.sort();
+        let interval = candidates[self.rng.gen_range(0..candidates.len())];
+
+        let job = self.check(
+            self.collector
+                .start_collection(Query::new_time_interval(interval), &())
+                .await,
+            "couldn't start collection",
+        );
+        self.collected.insert(interval);
+        self.pending_collections.push((interval, job));
+    }
+
+    /// Polls each pending collection, checking the results of those that have finished.
+    async fn poll_collections(&mut self) {
+        let mut pending_collections = Vec::new();
+        for (interval, job) in std::mem::take(&mut self.pending_collections) {
+            match self.check(
+                self.collector.poll_once(&job).await,
+                "couldn't poll collection",
+            ) {
+                PollResult::CollectionResult(collection) => {
+                    let got = Tally {
+                        report_count: collection.report_count(),
+                        sum: *collection.aggregate_result(),
+                    };
+                    let want = self.uploads[&interval];
+                    assert_eq!(
+                        got, want,
+                        "seed {}: wrong collection result for {interval:?}",
+                        self.seed
+                    );
+                }
+                PollResult::NotReady(_) => pending_collections.push((interval, job)),
+            }
+        }
+        self.pending_collections = pending_collections;
+    }
+
+    /// Unwraps the result of an operation that should always succeed, reporting the seed if not.
+    fn check<T, E: Debug>(&self, result: Result<T, E>, message: &str) -> T {
+        result.unwrap_or_else(|error| panic!("seed {}: {message}: {error:?}", self.seed))
+    }
+}
+
+/// Serves an aggregator on a loopback port until `stopper` is stopped, returning its endpoint.
+async fn serve(
+    datastore: Arc<Datastore<MockClock>>,
+    clock: MockClock,
+    config: Config,
+    stopper: &Stopper,
+) -> Url {
+    let handler = aggregator_handler(
+        datastore,
+        clock,
+        TestRuntime::default(),
+        &noop_meter(),
+        config,
+    )
+    .await
+    .unwrap();
+    let server_handle = trillium_tokio::config()
+        .without_signals()
+        .with_host("127.0.0.1")
+        .with_port(0)
+        .with_stopper(stopper.clone())
+        .spawn(handler);
+    let socket_addr = server_handle
+        .info()
+        .await
+        .tcp_socket_addr()
+        .copied()
+        .unwrap();
+    format!("http://{socket_addr}/").parse().unwrap()
+}
+
+#[tokio::test]
+async fn simulate_aggregation_pipeline() {
+    install_test_trace_subscriber();
+
+    let seeds: Vec<u64> = match env::var("JANUS_SIMULATION_SEED") {
+        Ok(seed) => Vec::from([seed.parse().expect("invalid JANUS_SIMULATION_SEED")]),
+        Err(_) => {
+            let runs = env::var("JANUS_SIMULATION_RUNS")
+                .map(|runs| runs.parse().expect("invalid JANUS_SIMULATION_RUNS"))
+                .unwrap_or(DEFAULT_SIMULATION_RUNS);
+            (0..runs).map(|_| random()).collect()
+        }
+    };
+    for seed in seeds {
+        println!("Running simulation with seed {seed}");
+        Simulation::new(seed).await.run().await;
+    }
+}
+
+#[test]
+fn operations_depend_only_on_seed() {
+    let sample = |seed| {
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..100)
+            .map(|_| format!("{:?}", Operation::sample(&mut rng)))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(sample(1), sample(1));
+    assert_ne!(sample(1), sample(2));
+
+    // Every operation is eventually chosen.
+    let operations: HashSet<_> = sample(1).into_iter().collect();
+    assert_eq!(operations.len(), Operation::WEIGHTED.len());
+}
@@ -28,6 +28,7 @@ use janus_aggregator_core::{
     test_util::noop_meter,
 };
 use janus_core::{
+    auth_tokens::AuthenticationToken,
     hpke::{
         self, test_util::generate_test_hpke_config_and_private_key, HpkeApplicationInfo,
         HpkeKeypair, Label,
@@ -67,6 +68,7 @@ use trillium::{Handler, KnownHeaderName, Status};
 use trillium_testing::{
     assert_headers,
     prelude::{post, put},
+    TestConn,
 };
 use url::Url;
 
@@ -88,6 +90,29 @@ pub struct TaskprovTestCase<V: Vdaf, const VERIFY_KEY_SIZE: usize> {
     global_hpke_key: HpkeKeypair,
 }
 
+async fn taskprov_handler(
+    datastore: &Arc<Datastore<MockClock>>,
+    clock: &MockClock,
+) -> Box<dyn Handler> {
+    Box::new(
+        aggregator_handler(
+            Arc::clone(datastore),
+            clock.clone(),
+            TestRuntime::default(),
+            &noop_meter(),
+            Config {
+                taskprov_config: TaskprovConfig {
+                    enabled: true,
+                    ignore_unknown_differential_privacy_mechanism: false,
+                },
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap(),
+    )
+}
+
 impl TaskprovTestCase<TestVdaf, 16> {
     async fn new() -> Self {
         // We use a real VDAF since taskprov doesn't have any allowance for a test VDAF, and we use
@@ -146,21 +171,7 @@ where
             .await
             .unwrap();
 
-        let handler = aggregator_handler(
-            Arc::clone(&datastore),
-            clock.clone(),
-            TestRuntime::default(),
-            &noop_meter(),
-            Config {
-                taskprov_config: TaskprovConfig {
-                    enabled: true,
-                    ignore_unknown_differential_privacy_mechanism: false,
-                },
-                ..Default::default()
-            },
-        )
-        .await
-        .unwrap();
+        let handler = taskprov_handler(&datastore, &clock).await;
 
         let time_precision = Duration::from_seconds(1);
         let max_batch_query_count = 1;
@@ -212,7 +223,7 @@ where
             clock,
             collector_hpke_keypair,
             datastore,
-            handler: Box::new(handler),
+            handler,
             peer_aggregator,
             task,
             task_config,
@@ -224,6 +235,30 @@ where
         }
     }
 
+    /// Replaces the tokens the peer aggregator may authenticate with, and restarts the handler so
+    /// that it loads them, as an operator would when rotating tokens.
+    async fn set_peer_aggregator_auth_tokens(&mut self, tokens: Vec<AuthenticationToken>) {
+        self.peer_aggregator = PeerAggregatorBuilder::from(self.peer_aggregator.clone())
+            .with_aggregator_auth_tokens(tokens)
+            .build();
+        self.datastore
+            .run_unnamed_tx(|tx| {
+                let peer_aggregator = self.peer_aggregator.clone();
+                Box::pin(async move {
+                    tx.delete_taskprov_peer_aggregator(
+                        peer_aggregator.endpoint(),
+                        peer_aggregator.role(),
+                    )
+                    .await
+                    .unwrap();
+                    tx.put_taskprov_peer_aggregator(&peer_aggregator).await
+                })
+            })
+            .await
+            .unwrap();
+        self.handler = taskprov_handler(&self.datastore, &self.clock).await;
+    }
+
     fn next_report_share(
         &self,
     ) -> (
@@ -1261,6 +1296,132 @@ async fn end_to_end_poplar1() {
     );
 }
 
+/// Rotates the leader's aggregator auth token while an aggregation job is in flight: the new token
+/// is added as the primary token alongside the old one, then the old token is retired. The
+/// in-flight job completes with the old token, and new jobs are accepted with the new token.
+#[tokio::test]
+async fn aggregator_auth_token_rotation() {
+    let mut test = TaskprovTestCase::new().await;
+    let old_token = test.peer_aggregator.primary_aggregator_auth_token().clone();
+    let new_token: AuthenticationToken = random();
+
+    async fn init_aggregation_job(
+        test: &TaskprovTestCase<TestVdaf, 16>,
+        token: &AuthenticationToken,
+    ) -> (
+        TestConn,
+        AggregationJobId,
+        VdafTranscript<16, TestVdaf>,
+        ReportShare,
+    ) {
+        let (transcript, report_share, aggregation_param) = test.next_report_share();
+        let request = AggregationJobInitializeReq::new(
+            aggregation_param.get_encoded().unwrap(),
+            PartialBatchSelector::new_fixed_size(random()),
+            Vec::from([PrepareInit::new(
+                report_share.clone(),
+                transcript.leader_prepare_transitions[0].message.clone(),
+            )]),
+        );
+        let aggregation_job_id: AggregationJobId = random();
+        let (auth_header_name, auth_header_value) = token.request_authentication();
+        let test_conn = put(test
+            .task
+            .aggregation_job_uri(&aggregation_job_id)
+            .unwrap()
+            .path())
+        .with_request_header(auth_header_name, auth_header_value)
+        .with_request_header(
+            KnownHeaderName::ContentType,
+            AggregationJobInitializeReq::<FixedSize>::MEDIA_TYPE,
+        )
+        .with_request_header(
+            TASKPROV_HEADER,
+            URL_SAFE_NO_PAD.encode(test.task_config.get_encoded().unwrap()),
+        )
+        .with_request_body(request.get_encoded().unwrap())
+        .run_async(&test.handler)
+        .await;
+        (test_conn, aggregation_job_id, transcript, report_share)
+    }
+
+    // Start an aggregation job with the old token.
+    let (mut test_conn, aggregation_job_id, transcript, report_share) =
+        init_aggregation_job(&test, &old_token).await;
+    assert_eq!(test_conn.status(), Some(Status::Ok));
+    let aggregation_job_resp: AggregationJobResp = decode_response_body(&mut test_conn).await;
+    assert_matches!(
+        aggregation_job_resp.prepare_resps()[0].result(),
+        PrepareStepResult::Continue { .. }
+    );
+
+    // Add the new token as the primary token, keeping the old token.
+    test.set_peer_aggregator_auth_tokens(Vec::from([old_token.clone(), new_token.clone()]))
+        .await;
+    assert_eq!(
+        test.peer_aggregator.primary_aggregator_auth_token(),
+        &new_token
+    );
+
+    // The in-flight job completes with the old token.
+    let continue_request = AggregationJobContinueReq::new(
+        AggregationJobStep::from(1),
+        Vec::from([PrepareContinue::new(
+            *report_share.metadata().id(),
+            transcript.leader_prepare_transitions[1].message.clone(),
+        )]),
+    );
+    let (auth_header_name, auth_header_value) = old_token.request_authentication();
+    let mut test_conn = post(
+        test.task
+            .aggregation_job_uri(&aggregation_job_id)
+            .unwrap()
+            .path(),
+    )
+    .with_request_header(auth_header_name, auth_header_value)
+    .with_request_header(
+        KnownHeaderName::ContentType,
+        AggregationJobContinueReq::MEDIA_TYPE,
+    )
+    .with_request_header(
+        TASKPROV_HEADER,
+        URL_SAFE_NO_PAD.encode(test.task_config.get_encoded().unwrap()),
+    )
+    .with_request_body(continue_request.get_encoded().unwrap())
+    .run_async(&test.handler)
+    .await;
+    assert_eq!(test_conn.status(), Some(Status::Ok));
+    let aggregation_job_resp: AggregationJobResp = decode_response_body(&mut test_conn).await;
+    assert_eq!(
+        aggregation_job_resp,
+        AggregationJobResp::new(Vec::from([PrepareResp::new(
+            *report_share.metadata().id(),
+            PrepareStepResult::Finished
+        )]))
+    );
+
+    // New jobs are accepted with the new primary token.
+    let (test_conn, ..) = init_aggregation_job(&test, &new_token).await;
+    assert_eq!(test_conn.status(), Some(Status::Ok));
+
+    // Once the old token is retired, it is rejected, while the new token is still accepted.
+    test.set_peer_aggregator_auth_tokens(Vec::from([new_token.clone()]))
+        .await;
+    let (mut test_conn, ..) = init_aggregation_job(&test, &old_token).await;
+    assert_eq!(test_conn.status(), Some(Status::BadRequest));
+    assert_eq!(
+        take_problem_details(&mut test_conn).await,
+        json!({
+            "status": Status::BadRequest as u16,
+            "type": "urn:ietf:params:ppm:dap:error:unauthorizedRequest",
+            "title": "The request's authorization is not valid.",
+            "taskid": format!("{}", test.task_id),
+        })
+    );
+    let (test_conn, ..) = init_aggregation_job(&test, &new_token).await;
+    assert_eq!(test_conn.status(), Some(Status::Ok));
+}
+
 #[tokio::test]
 async fn end_to_end_sumvec_hmac() {
     let vdaf = new_prio3_sum_vec_field64_multiproof_hmacsha256_aes128(2, 8, 12, 14).unwrap();
@@ -0,0 +1,112 @@
+//! Metrics counting uploads by a label taken from a request header.
+//!
+//! Operators may configure a header, set by a trusted reverse proxy, whose value labels each
+//! upload, e.g. with the region or application that the upload came through. Uploads are counted
+//! per task and label value, so that report volume can be broken down along that dimension without
+//! examining report contents.
+
+use crate::{aggregator::client_telemetry::sanitize_label, config::UploadLabelConfig};
+use janus_messages::TaskId;
+use opentelemetry::{
+    metrics::{Counter, Meter, Unit},
+    KeyValue,
+};
+use std::{collections::HashSet, sync::Mutex};
+
+#[derive(Debug)]
+pub(crate) struct UploadLabels {
+    header: String,
+    max_distinct_values: usize,
+    upload_counter: Counter<u64>,
+    seen_values: Mutex<HashSet<String>>,
+}
+
+impl UploadLabels {
+    /// Label used for uploads without the configured header.
+    const UNKNOWN: &'static str = "unknown";
+
+    /// Label used for values beyond the first `max_distinct_values`.
+    const OTHER: &'static str = "other";
+
+    pub(crate) fn new(meter: &Meter, config: &UploadLabelConfig) -> Self {
+        let upload_counter = meter
+            .u64_counter("janus_upload_labels")
+            .with_description(
+                "Number of uploads to the tasks/{task-id}/reports endpoint, by task and by the \
+                 value of the configured upload label header.",
+            )
+            .with_unit(Unit::new("{report}"))
+            .init();
+
+        Self {
+            header: config.header.clone(),
+            max_distinct_values: config.max_distinct_values,
+            upload_counter,
+            seen_values: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// The name of the request header whose value labels each upload.
+    pub(crate) fn header(&self) -> &str {
+        &self.header
+    }
+
+    /// Counts an upload for the given task, labelled with `value`, the value of the configured
+    /// header. `succeeded` indicates whether the upload was accepted.
+    pub(crate) fn record_upload(&self, task_id: &TaskId, value: Option<&str>, succeeded: bool) {
+        let value = self.admit(
+            value
+                .map(sanitize_label)
+                .filter(|value| !value.is_empty())
+                .unwrap_or_else(|| Self::UNKNOWN.to_string()),
+        );
+        self.upload_counter.add(
+            1,
+            &[
+                KeyValue::new("task_id", task_id.to_string()),
+                KeyValue::new("label", value),
+                KeyValue::new("result", if succeeded { "success" } else { "error" }),
+            ],
+        );
+    }
+
+    /// Returns the given label value if it has been seen before, or if there is room to report
+    /// another value, and [`Self::OTHER`] otherwise.
+    fn admit(&self, value: String) -> String {
+        let mut seen_values = self.seen_values.lock().unwrap();
+        if seen_values.contains(&value) {
+            return value;
+        }
+        if seen_values.len() < self.max_distinct_values {
+            seen_values.insert(value.clone());
+            return value;
+        }
+        Self::OTHER.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::UploadLabels;
+    use crate::config::UploadLabelConfig;
+    use janus_aggregator_core::test_util::noop_meter;
+
+    #[test]
+    fn distinct_values_are_bounded() {
+        let labels = UploadLabels::new(
+            &noop_meter(),
+            &UploadLabelConfig {
+                header: "X-Janus-Region".to_string(),
+                max_distinct_values: 2,
+            },
+        );
+        assert_eq!(labels.header(), "X-Janus-Region");
+
+        assert_eq!(labels.admit("us-east".to_string()), "us-east");
+        assert_eq!(labels.admit("eu-west".to_string()), "eu-west");
+
+        // Values seen before are still admitted, but new ones are not.
+        assert_eq!(labels.admit("us-east".to_string()), "us-east");
+        assert_eq!(labels.admit("ap-south".to_string()), "other");
+    }
+}
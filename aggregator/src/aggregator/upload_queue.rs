@@ -0,0 +1,357 @@
+//! Durable queueing of uploaded reports, decoupling the upload endpoint from the datastore.
+//!
+//! When an [`UploadQueue`] is configured, the aggregator's upload endpoint performs only cheap
+//! checks on incoming reports before placing them on the queue and responding to the client. A
+//! separate [`UploadIngester`] process drains the queue, runs the full upload validation, and
+//! writes reports to the datastore in batches. This allows uploads to continue to be accepted
+//! while the database is unavailable, e.g. during maintenance windows.
+
+use crate::{
+    aggregator::{Aggregator, Config, Error},
+    config::UploadQueueConfig,
+};
+use async_trait::async_trait;
+use futures::future::join_all;
+use janus_aggregator_core::datastore::Datastore;
+use janus_core::{time::Clock, Runtime};
+use janus_messages::TaskId;
+use opentelemetry::{
+    metrics::{Counter, Meter, Unit},
+    KeyValue,
+};
+use prio::codec::Decode;
+use rand::random;
+use std::{
+    fmt::Debug,
+    io::{Cursor, ErrorKind},
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::{fs, io::AsyncWriteExt, time::sleep};
+use tracing::{debug, error, warn};
+use trillium_tokio::Stopper;
+
+/// A report which has been accepted by the upload endpoint, and is waiting in an [`UploadQueue`] to
+/// be ingested into the datastore.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueuedReport {
+    /// Opaque, queue-specific identifier used to acknowledge this report.
+    receipt: String,
+    task_id: TaskId,
+    report_bytes: Vec<u8>,
+}
+
+impl QueuedReport {
+    pub fn new(receipt: String, task_id: TaskId, report_bytes: Vec<u8>) -> Self {
+        Self {
+            receipt,
+            task_id,
+            report_bytes,
+        }
+    }
+
+    pub fn receipt(&self) -> &str {
+        &self.receipt
+    }
+
+    pub fn task_id(&self) -> &TaskId {
+        &self.task_id
+    }
+
+    pub fn report_bytes(&self) -> &[u8] {
+        &self.report_bytes
+    }
+}
+
+/// A durable queue of uploaded reports. Implementations may be backed by any message queueing
+/// system (e.g. Kafka, SQS).
+///
+/// Delivery is at-least-once: a report that has been received but not yet acknowledged may be
+/// received again, e.g. if an ingester crashes. This is safe, since writing a duplicate report to
+/// the datastore is a no-op.
+#[async_trait]
+pub trait UploadQueue: Debug + Send + Sync {
+    /// Durably enqueues an encoded report for the given task. Once this method returns
+    /// successfully, the report must eventually be delivered by [`UploadQueue::receive`].
+    async fn send(&self, task_id: &TaskId, report_bytes: &[u8]) -> Result<(), Error>;
+
+    /// Receives up to `max_reports` reports from the queue. Received reports remain in the queue
+    /// until they are acknowledged.
+    async fn receive(&self, max_reports: usize) -> Result<Vec<QueuedReport>, Error>;
+
+    /// Acknowledges that a received report has been handled, removing it from the queue.
+    async fn acknowledge(&self, report: &QueuedReport) -> Result<(), Error>;
+}
+
+/// Constructs the [`UploadQueue`] described by the given configuration.
+pub fn upload_queue_from_config(config: &UploadQueueConfig) -> Arc<dyn UploadQueue> {
+    match config {
+        UploadQueueConfig::Directory { path } => Arc::new(DirectoryUploadQueue::new(path.clone())),
+    }
+}
+
+/// An [`UploadQueue`] backed by a spool directory, typically on a persistent volume shared by the
+/// aggregator and the ingester. Each queued report is stored in its own file.
+///
+/// Only a single ingester should receive from a given directory at a time.
+#[derive(Debug, Clone)]
+pub struct DirectoryUploadQueue {
+    path: PathBuf,
+}
+
+impl DirectoryUploadQueue {
+    const FILE_EXTENSION: &'static str = "report";
+
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+}
+
+#[async_trait]
+impl UploadQueue for DirectoryUploadQueue {
+    async fn send(&self, task_id: &TaskId, report_bytes: &[u8]) -> Result<(), Error> {
+        // File names sort by time of arrival, so that reports are ingested roughly in order. The
+        // random suffix avoids collisions between concurrent uploads.
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|err| Error::UploadQueue(format!("system time is before epoch: {err}")))?;
+        let name = format!("{:020}-{:032x}", timestamp.as_nanos(), random::<u128>());
+
+        let mut contents = Vec::with_capacity(TaskId::LEN + report_bytes.len());
+        contents.extend_from_slice(task_id.as_ref());
+        contents.extend_from_slice(report_bytes);
+
+        // Write to a temporary file, then rename it into place, so that the ingester never
+        // observes a partially-written report.
+        let temp_path = self.path.join(format!(".{name}.tmp"));
+        let mut file = fs::File::create(&temp_path)
+            .await
+            .map_err(|err| Error::UploadQueue(format!("couldn't create {temp_path:?}: {err}")))?;
+        file.write_all(&contents)
+            .await
+            .map_err(|err| Error::UploadQueue(format!("couldn't write {temp_path:?}: {err}")))?;
+        file.sync_all()
+            .await
+            .map_err(|err| Error::UploadQueue(format!("couldn't sync {temp_path:?}: {err}")))?;
+        fs::rename(
+            &temp_path,
+            self.path.join(format!("{name}.{}", Self::FILE_EXTENSION)),
+        )
+        .await
+        .map_err(|err| Error::UploadQueue(format!("couldn't rename {temp_path:?}: {err}")))?;
+        Ok(())
+    }
+
+    async fn receive(&self, max_reports: usize) -> Result<Vec<QueuedReport>, Error> {
+        let mut entries = fs::read_dir(&self.path)
+            .await
+            .map_err(|err| Error::UploadQueue(format!("couldn't read {:?}: {err}", self.path)))?;
+        let mut names = Vec::new();
+        while let Some(entry) = entries
+            .next_entry()
+            .await
+            .map_err(|err| Error::UploadQueue(format!("couldn't read {:?}: {err}", self.path)))?
+        {
+            let path = entry.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some(Self::FILE_EXTENSION) {
+                continue;
+            }
+            if let Some(name) = path.file_name().and_then(|name| name.to_str()) {
+                names.push(name.to_string());
+            }
+        }
+        names.sort_unstable();
+        names.truncate(max_reports);
+
+        let mut reports = Vec::with_capacity(names.len());
+        for name in names {
+            let path = self.path.join(&name);
+            let contents = match fs::read(&path).await {
+                Ok(contents) => contents,
+                // Another process may have acknowledged the report concurrently.
+                Err(err) if err.kind() == ErrorKind::NotFound => continue,
+                Err(err) => {
+                    return Err(Error::UploadQueue(format!("couldn't read {path:?}: {err}")))
+                }
+            };
+            let mut cursor = Cursor::new(contents.as_slice());
+            let task_id = TaskId::decode(&mut cursor).map_err(|err| {
+                Error::UploadQueue(format!("corrupt queued report {path:?}: {err}"))
+            })?;
+            let report_bytes = contents[usize::try_from(cursor.position())?..].to_vec();
+            reports.push(QueuedReport::new(name, task_id, report_bytes));
+        }
+        Ok(reports)
+    }
+
+    async fn acknowledge(&self, report: &QueuedReport) -> Result<(), Error> {
+        let path = self.path.join(&report.receipt);
+        match fs::remove_file(&path).await {
+            Ok(()) => Ok(()),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(Error::UploadQueue(format!(
+                "couldn't remove {path:?}: {err}"
+            ))),
+        }
+    }
+}
+
+/// Drains an [`UploadQueue`], validating queued reports and writing them to the datastore.
+pub struct UploadIngester<C: Clock> {
+    aggregator: Aggregator<C>,
+    upload_queue: Arc<dyn UploadQueue>,
+    max_reports_per_receive: usize,
+    poll_interval: Duration,
+    ingested_reports_counter: Counter<u64>,
+}
+
+impl<C: Clock> UploadIngester<C> {
+    /// Creates a new ingester. `cfg` controls how reports are batched into datastore writes, as it
+    /// does for the aggregator's upload endpoint. If `secondary_datastore` is set, ingested reports
+    /// are written to it as well as to `datastore`.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn new<R: Runtime + Send + Sync + 'static>(
+        datastore: Arc<Datastore<C>>,
+        secondary_datastore: Option<Arc<Datastore<C>>>,
+        clock: C,
+        runtime: R,
+        meter: &Meter,
+        cfg: Config,
+        upload_queue: Arc<dyn UploadQueue>,
+        max_reports_per_receive: usize,
+        poll_interval: Duration,
+    ) -> Result<Self, Error> {
+        let aggregator =
+            Aggregator::new(datastore, secondary_datastore, clock, runtime, meter, cfg).await?;
+
+        let ingested_reports_counter = meter
+            .u64_counter("janus_upload_queue_ingested_reports")
+            .with_description("Number of queued reports handled by the upload ingester.")
+            .with_unit(Unit::new("{report}"))
+            .init();
+        for result in ["success", "rejected", "retry"] {
+            ingested_reports_counter.add(0, &[KeyValue::new("result", result)]);
+        }
+
+        Ok(Self {
+            aggregator,
+            upload_queue,
+            max_reports_per_receive,
+            poll_interval,
+            ingested_reports_counter,
+        })
+    }
+
+    /// Runs the ingester until the stopper is signalled.
+    pub async fn run(&self, stopper: Stopper) {
+        loop {
+            let delay = match self.ingest().await {
+                // Keep draining the queue without delay while there is a backlog.
+                Ok(count) if count >= self.max_reports_per_receive => Duration::ZERO,
+                Ok(_) => self.poll_interval,
+                Err(err) => {
+                    error!(?err, "Couldn't receive reports from upload queue");
+                    self.poll_interval
+                }
+            };
+            if stopper.stop_future(sleep(delay)).await.is_none() {
+                break;
+            }
+        }
+    }
+
+    /// Receives a single batch of reports from the queue and ingests them. Returns the number of
+    /// reports received.
+    #[tracing::instrument(name = "UploadIngester::ingest", skip(self), err)]
+    pub async fn ingest(&self) -> Result<usize, Error> {
+        let reports = self
+            .upload_queue
+            .receive(self.max_reports_per_receive)
+            .await?;
+        debug!(count = reports.len(), "Received reports from upload queue");
+
+        // Handle all reports concurrently, so that the report writer can batch them together.
+        join_all(reports.iter().map(|report| async move {
+            let result = match self
+                .aggregator
+                .handle_queued_upload(report.task_id(), report.report_bytes())
+                .await
+            {
+                Ok(()) => "success",
+                Err(err) => match err.as_ref() {
+                    // These errors are a property of the report itself, so retrying it would not
+                    // help. The rejection has already been counted by the report writer.
+                    Error::MessageDecode(_)
+                    | Error::ReportRejected(_)
+                    | Error::UnrecognizedTask(_) => {
+                        debug!(task_id = %report.task_id(), ?err, "Queued report rejected");
+                        "rejected"
+                    }
+                    _ => {
+                        warn!(task_id = %report.task_id(), ?err, "Couldn't ingest queued report");
+                        "retry"
+                    }
+                },
+            };
+            self.ingested_reports_counter
+                .add(1, &[KeyValue::new("result", result)]);
+
+            // Reports that failed for transient reasons are left in the queue, to be received
+            // again later.
+            if result != "retry" {
+                if let Err(err) = self.upload_queue.acknowledge(report).await {
+                    error!(?err, "Couldn't acknowledge queued report");
+                }
+            }
+        }))
+        .await;
+
+        Ok(reports.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DirectoryUploadQueue, UploadQueue};
+    use janus_messages::TaskId;
+    use rand::random;
+
+    #[tokio::test]
+    async fn directory_upload_queue() {
+        let dir = tempfile::tempdir().unwrap();
+        let queue = DirectoryUploadQueue::new(dir.path().to_path_buf());
+
+        assert!(queue.receive(10).await.unwrap().is_empty());
+
+        let task_id_1: TaskId = random();
+        let task_id_2: TaskId = random();
+        queue.send(&task_id_1, b"report 1").await.unwrap();
+        queue.send(&task_id_2, b"report 2").await.unwrap();
+        queue.send(&task_id_1, b"report 3").await.unwrap();
+
+        // Reports are received in the order they were sent, and are limited in number.
+        let reports = queue.receive(2).await.unwrap();
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[0].task_id(), &task_id_1);
+        assert_eq!(reports[0].report_bytes(), b"report 1");
+        assert_eq!(reports[1].task_id(), &task_id_2);
+        assert_eq!(reports[1].report_bytes(), b"report 2");
+
+        // Unacknowledged reports are redelivered.
+        assert_eq!(queue.receive(2).await.unwrap(), reports);
+
+        queue.acknowledge(&reports[0]).await.unwrap();
+        queue.acknowledge(&reports[1]).await.unwrap();
+        // Acknowledging twice is harmless.
+        queue.acknowledge(&reports[1]).await.unwrap();
+
+        let reports = queue.receive(10).await.unwrap();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].task_id(), &task_id_1);
+        assert_eq!(reports[0].report_bytes(), b"report 3");
+        queue.acknowledge(&reports[0]).await.unwrap();
+
+        assert!(queue.receive(10).await.unwrap().is_empty());
+    }
+}
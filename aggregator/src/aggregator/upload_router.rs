@@ -0,0 +1,368 @@
+//! Routing of uploads between several leader instances that share one datastore.
+//!
+//! Each upload is assigned to a leader by rendezvous hashing of its task ID and the bucket that
+//! its report timestamp falls into. Reports that land in the same batch thus tend to be written by
+//! the same instance, which improves datastore locality and the hit rates of per-instance caches.
+//! Uploads assigned to another leader are forwarded to it, marked with [`UPLOAD_ROUTED_HEADER`] so
+//! that they are not routed again. Routing is an optimization only: any leader can handle any
+//! upload, so uploads that can't be routed or forwarded are handled locally.
+
+use crate::config::{UploadRoutingConfig, UploadRoutingLeaderConfig};
+use janus_core::auth_tokens::AuthenticationToken;
+use janus_messages::{codec::Decode, ReportMetadata, TaskId};
+use opentelemetry::{
+    metrics::{Counter, Histogram, Meter, Unit},
+    KeyValue,
+};
+use reqwest::header::{CONTENT_TYPE, USER_AGENT};
+use ring::digest::{digest, SHA256};
+use std::{
+    collections::HashSet,
+    io::Cursor,
+    time::{Duration as StdDuration, Instant},
+};
+use tracing::warn;
+
+/// Header added to forwarded uploads, naming the leader they were routed to. Uploads carrying this
+/// header are always handled by the instance that receives them.
+pub(crate) const UPLOAD_ROUTED_HEADER: &str = "janus-upload-routed";
+
+/// Errors that may occur when constructing an [`UploadRouter`].
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("no leaders configured")]
+    NoLeaders,
+    #[error("duplicate leader name {0}")]
+    DuplicateLeader(String),
+    #[error("local leader {0} is not a configured leader")]
+    UnknownLocalLeader(String),
+    #[error("timestamp bucket width must be positive")]
+    InvalidTimestampBucket,
+    #[error("couldn't create HTTP client: {0}")]
+    HttpClient(#[from] reqwest::Error),
+}
+
+/// The response of another leader to a forwarded upload.
+#[derive(Debug)]
+pub(crate) struct ForwardedResponse {
+    pub(crate) status: u16,
+    pub(crate) content_type: Option<String>,
+    pub(crate) body: Vec<u8>,
+}
+
+/// Routes uploads to the leader instance responsible for their task and timestamp bucket.
+#[derive(Debug)]
+pub(crate) struct UploadRouter {
+    config: UploadRoutingConfig,
+    http_client: reqwest::Client,
+    request_counter: Counter<u64>,
+    forward_duration_histogram: Histogram<f64>,
+}
+
+impl UploadRouter {
+    pub(crate) fn new(meter: &Meter, config: UploadRoutingConfig) -> Result<Self, Error> {
+        if config.leaders.is_empty() {
+            return Err(Error::NoLeaders);
+        }
+        let mut names = HashSet::new();
+        for leader in &config.leaders {
+            if !names.insert(leader.name.as_str()) {
+                return Err(Error::DuplicateLeader(leader.name.clone()));
+            }
+        }
+        if let Some(local_leader) = &config.local_leader {
+            if !names.contains(local_leader.as_str()) {
+                return Err(Error::UnknownLocalLeader(local_leader.clone()));
+            }
+        }
+        if config.timestamp_bucket_secs == 0 {
+            return Err(Error::InvalidTimestampBucket);
+        }
+
+        let http_client = reqwest::Client::builder()
+            .timeout(StdDuration::from_secs(config.request_timeout_secs))
+            .build()?;
+
+        let request_counter = meter
+            .u64_counter("janus_upload_router_requests")
+            .with_description(
+                "Number of uploads seen by the upload router, by the leader they were routed to \
+                 and whether they were handled locally, forwarded, or handled locally after \
+                 forwarding failed.",
+            )
+            .with_unit(Unit::new("{request}"))
+            .init();
+        let forward_duration_histogram = meter
+            .f64_histogram("janus_upload_router_forward_duration")
+            .with_description("The amount of time elapsed while forwarding an upload to a leader.")
+            .with_unit(Unit::new("s"))
+            .init();
+
+        Ok(Self {
+            config,
+            http_client,
+            request_counter,
+            forward_duration_histogram,
+        })
+    }
+
+    /// Returns the leader that the given upload is routed to, or `None` if the upload should be
+    /// handled locally, either because it is routed to this instance, or because its report
+    /// metadata can't be decoded. Malformed uploads are left for the local upload handler to
+    /// reject.
+    fn route(&self, task_id: &TaskId, report_bytes: &[u8]) -> Option<&UploadRoutingLeaderConfig> {
+        let metadata = ReportMetadata::decode(&mut Cursor::new(report_bytes)).ok()?;
+        let bucket = metadata.time().as_seconds_since_epoch() / self.config.timestamp_bucket_secs;
+
+        // Unwrap safety: the constructor checks that there is at least one leader.
+        let leader = self
+            .config
+            .leaders
+            .iter()
+            .max_by_key(|leader| Self::rendezvous_score(&leader.name, task_id, bucket))
+            .unwrap();
+        if self.config.local_leader.as_ref() == Some(&leader.name) {
+            return None;
+        }
+        Some(leader)
+    }
+
+    /// Computes the rendezvous (highest random weight) score of a leader for a timestamp bucket of
+    /// a task.
+    fn rendezvous_score(leader_name: &str, task_id: &TaskId, bucket: u64) -> [u8; 32] {
+        let mut input = Vec::with_capacity(leader_name.len() + 1 + TaskId::LEN + 8);
+        input.extend_from_slice(leader_name.as_bytes());
+        input.push(0);
+        input.extend_from_slice(task_id.as_ref());
+        input.extend_from_slice(&bucket.to_be_bytes());
+        // Unwrap safety: SHA-256 digests are always 32 bytes long.
+        digest(&SHA256, &input).as_ref().try_into().unwrap()
+    }
+
+    /// Forwards the given upload to the leader it is routed to, and returns that leader's response.
+    /// Returns `None` if the upload should instead be handled locally.
+    pub(crate) async fn forward(
+        &self,
+        task_id: &TaskId,
+        content_type: &str,
+        user_agent: Option<&str>,
+        auth_token: Option<&AuthenticationToken>,
+        report_bytes: &[u8],
+    ) -> Option<ForwardedResponse> {
+        let leader = match self.route(task_id, report_bytes) {
+            Some(leader) => leader,
+            None => {
+                self.record(
+                    self.config.local_leader.as_deref().unwrap_or("local"),
+                    "local",
+                );
+                return None;
+            }
+        };
+
+        let start = Instant::now();
+        let result = self
+            .send(
+                leader,
+                task_id,
+                content_type,
+                user_agent,
+                auth_token,
+                report_bytes,
+            )
+            .await;
+        self.forward_duration_histogram.record(
+            start.elapsed().as_secs_f64(),
+            &[KeyValue::new("leader", leader.name.clone())],
+        );
+
+        match result {
+            Ok(response) => {
+                self.record(&leader.name, "forwarded");
+                Some(response)
+            }
+            Err(error) => {
+                warn!(
+                    %task_id,
+                    leader = %leader.name,
+                    ?error,
+                    "Couldn't forward upload, handling it locally"
+                );
+                self.record(&leader.name, "fallback");
+                None
+            }
+        }
+    }
+
+    async fn send(
+        &self,
+        leader: &UploadRoutingLeaderConfig,
+        task_id: &TaskId,
+        content_type: &str,
+        user_agent: Option<&str>,
+        auth_token: Option<&AuthenticationToken>,
+        report_bytes: &[u8],
+    ) -> Result<ForwardedResponse, reqwest::Error> {
+        // Unwrap safety: the path is a valid relative URL.
+        let url = leader
+            .url
+            .join(&format!("tasks/{task_id}/reports"))
+            .unwrap();
+        let mut request = self
+            .http_client
+            .put(url)
+            .header(CONTENT_TYPE, content_type)
+            .header(UPLOAD_ROUTED_HEADER, &leader.name)
+            .body(report_bytes.to_vec());
+        if let Some(user_agent) = user_agent {
+            request = request.header(USER_AGENT, user_agent);
+        }
+        if let Some(auth_token) = auth_token {
+            let (header, value) = auth_token.request_authentication();
+            request = request.header(header, value);
+        }
+
+        let response = request.send().await?;
+        let status = response.status().as_u16();
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        let body = response.bytes().await?.to_vec();
+        Ok(ForwardedResponse {
+            status,
+            content_type,
+            body,
+        })
+    }
+
+    fn record(&self, leader: &str, result: &'static str) {
+        self.request_counter.add(
+            1,
+            &[
+                KeyValue::new("leader", leader.to_string()),
+                KeyValue::new("result", result),
+            ],
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Error, UploadRouter};
+    use crate::config::{UploadRoutingConfig, UploadRoutingLeaderConfig};
+    use assert_matches::assert_matches;
+    use janus_aggregator_core::test_util::noop_meter;
+    use janus_messages::{codec::Encode, ReportMetadata, TaskId, Time};
+    use rand::random;
+    use std::collections::HashMap;
+
+    fn leader(name: &str) -> UploadRoutingLeaderConfig {
+        UploadRoutingLeaderConfig {
+            name: name.to_string(),
+            url: format!("https://{name}.example.com/").parse().unwrap(),
+        }
+    }
+
+    fn config(leaders: &[&str], local_leader: Option<&str>) -> UploadRoutingConfig {
+        UploadRoutingConfig {
+            leaders: leaders.iter().map(|name| leader(name)).collect(),
+            local_leader: local_leader.map(str::to_string),
+            timestamp_bucket_secs: 3600,
+            request_timeout_secs: 10,
+        }
+    }
+
+    fn report_metadata_bytes(time: u64) -> Vec<u8> {
+        ReportMetadata::new(random(), Time::from_seconds_since_epoch(time))
+            .get_encoded()
+            .unwrap()
+    }
+
+    #[test]
+    fn invalid_config() {
+        let meter = noop_meter();
+        assert_matches!(
+            UploadRouter::new(&meter, config(&[], None)),
+            Err(Error::NoLeaders)
+        );
+        assert_matches!(
+            UploadRouter::new(&meter, config(&["a", "a"], None)),
+            Err(Error::DuplicateLeader(name)) => assert_eq!(name, "a")
+        );
+        assert_matches!(
+            UploadRouter::new(&meter, config(&["a"], Some("b"))),
+            Err(Error::UnknownLocalLeader(name)) => assert_eq!(name, "b")
+        );
+        assert_matches!(
+            UploadRouter::new(
+                &meter,
+                UploadRoutingConfig {
+                    timestamp_bucket_secs: 0,
+                    ..config(&["a"], None)
+                }
+            ),
+            Err(Error::InvalidTimestampBucket)
+        );
+    }
+
+    #[test]
+    fn routing_is_stable_and_balanced() {
+        let meter = noop_meter();
+        let task_id: TaskId = random();
+        let three_leaders = UploadRouter::new(&meter, config(&["a", "b", "c"], None)).unwrap();
+        let four_leaders = UploadRouter::new(&meter, config(&["c", "b", "a", "d"], None)).unwrap();
+
+        let mut counts = HashMap::new();
+        for bucket in 0..1000 {
+            // Reports in the same bucket are routed to the same leader.
+            let start = bucket * 3600;
+            let before = &three_leaders
+                .route(&task_id, &report_metadata_bytes(start))
+                .unwrap()
+                .name;
+            assert_eq!(
+                &three_leaders
+                    .route(&task_id, &report_metadata_bytes(start + 3599))
+                    .unwrap()
+                    .name,
+                before
+            );
+            *counts.entry(before.clone()).or_insert(0) += 1;
+
+            // Routing doesn't depend on the order of leaders, and adding a leader only moves
+            // buckets to the new leader.
+            let after = &four_leaders
+                .route(&task_id, &report_metadata_bytes(start))
+                .unwrap()
+                .name;
+            assert!(
+                after == before || after == "d",
+                "bucket {bucket} moved {before} -> {after}"
+            );
+        }
+
+        for name in ["a", "b", "c"] {
+            assert!(counts[name] > 200, "{counts:?}");
+        }
+    }
+
+    #[test]
+    fn local_and_malformed_uploads_are_not_routed() {
+        let meter = noop_meter();
+        let task_id: TaskId = random();
+        let router = UploadRouter::new(&meter, config(&["a", "b"], Some("a"))).unwrap();
+
+        let mut routed = 0;
+        for bucket in 0..100 {
+            if let Some(leader) = router.route(&task_id, &report_metadata_bytes(bucket * 3600)) {
+                assert_eq!(leader.name, "b");
+                routed += 1;
+            }
+        }
+        assert!(routed > 0 && routed < 100, "{routed}");
+
+        assert!(router.route(&task_id, b"malformed").is_none());
+    }
+}
@@ -0,0 +1,72 @@
+//! Sampling of uploaded report metadata, for debugging the behavior of client fleets.
+
+use crate::config::UploadSamplingConfig;
+use janus_aggregator_core::datastore::{models::UploadSample, Datastore};
+use janus_core::time::Clock;
+use janus_messages::{Duration, Extension, Report, TaskId, Time};
+use rand::{thread_rng, Rng};
+use std::sync::Arc;
+use tracing::warn;
+
+/// Records metadata for a configured fraction of each task's accepted uploads.
+#[derive(Debug)]
+pub(crate) struct UploadSampler<C: Clock> {
+    datastore: Arc<Datastore<C>>,
+    samples_per_million: u32,
+    sample_ttl: Duration,
+}
+
+impl<C: Clock> UploadSampler<C> {
+    pub(crate) fn new(datastore: Arc<Datastore<C>>, cfg: &UploadSamplingConfig) -> Self {
+        Self {
+            datastore,
+            samples_per_million: cfg.samples_per_million,
+            sample_ttl: Duration::from_seconds(cfg.sample_ttl_secs),
+        }
+    }
+
+    /// Decides whether to sample an upload, returning the metadata to record if so.
+    pub(crate) fn sample(
+        &self,
+        task_id: &TaskId,
+        report: &Report,
+        received_at: Time,
+        extensions: &[Extension],
+    ) -> Option<UploadSample> {
+        if self.samples_per_million == 0
+            || thread_rng().gen_range(0..1_000_000) >= self.samples_per_million
+        {
+            return None;
+        }
+        Some(UploadSample::new(
+            *task_id,
+            *report.metadata().id(),
+            *report.metadata().time(),
+            received_at,
+            extensions
+                .iter()
+                .map(|extension| u16::from(*extension.extension_type()))
+                .collect(),
+            report.public_share().len() as u64,
+            report.leader_encrypted_input_share().payload().len() as u64,
+            report.helper_encrypted_input_share().payload().len() as u64,
+        ))
+    }
+
+    /// Writes a sample to the datastore. Sampling is best-effort, so failures are logged rather
+    /// than returned.
+    pub(crate) async fn record(&self, sample: UploadSample) {
+        let sample = Arc::new(sample);
+        let sample_ttl = self.sample_ttl;
+        if let Err(err) = self
+            .datastore
+            .run_tx("upload_sample", |tx| {
+                let sample = Arc::clone(&sample);
+                Box::pin(async move { tx.put_upload_sample(&sample, &sample_ttl).await })
+            })
+            .await
+        {
+            warn!(task_id = %sample.task_id(), ?err, "Couldn't record upload sample");
+        }
+    }
+}
@@ -0,0 +1,236 @@
+//! Concurrency limits for upload validation.
+//!
+//! Upload validation is split into two stages. The cheap stage decodes the report, looks up its
+//! task, checks its timestamp, and recognizes reports that were recently accepted, which are
+//! acknowledged without further work. The expensive stage decrypts and decodes the report's shares.
+//! Each stage has its own concurrency limits, so that expensive validations can't hold up the cheap
+//! validations queued behind them.
+
+use crate::config::UploadValidationConfig;
+use janus_messages::{ReportId, TaskId};
+use opentelemetry::{
+    metrics::{Histogram, Meter, Unit},
+    KeyValue,
+};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Permits held by an upload for the duration of a validation stage. Dropping the permit allows
+/// another upload to enter the stage.
+#[derive(Debug)]
+pub(crate) struct UploadValidationPermit {
+    _permits: Vec<OwnedSemaphorePermit>,
+}
+
+#[derive(Debug)]
+pub(crate) struct UploadValidation {
+    cheap_semaphore: Option<Arc<Semaphore>>,
+    expensive_semaphore: Option<Arc<Semaphore>>,
+    max_concurrent_expensive_validations_per_task: Option<usize>,
+    task_expensive_semaphores: Mutex<HashMap<TaskId, Arc<Semaphore>>>,
+    recent_report_id_cache_size: usize,
+    recent_report_ids: Mutex<HashMap<TaskId, RecentReportIds>>,
+    queue_wait_histogram: Histogram<f64>,
+}
+
+impl UploadValidation {
+    pub(crate) fn new(meter: &Meter, cfg: &UploadValidationConfig) -> Self {
+        let queue_wait_histogram = meter
+            .f64_histogram("janus_upload_validation_queue_wait")
+            .with_description(
+                "Time uploads spend waiting to enter each stage of upload validation.",
+            )
+            .with_unit(Unit::new("s"))
+            .init();
+
+        Self {
+            cheap_semaphore: cfg
+                .max_concurrent_cheap_validations
+                .map(|limit| Arc::new(Semaphore::new(limit))),
+            expensive_semaphore: cfg
+                .max_concurrent_expensive_validations
+                .map(|limit| Arc::new(Semaphore::new(limit))),
+            max_concurrent_expensive_validations_per_task: cfg
+                .max_concurrent_expensive_validations_per_task,
+            task_expensive_semaphores: Mutex::new(HashMap::new()),
+            recent_report_id_cache_size: cfg.recent_report_id_cache_size,
+            recent_report_ids: Mutex::new(HashMap::new()),
+            queue_wait_histogram,
+        }
+    }
+
+    /// Waits to enter the cheap validation stage.
+    pub(crate) async fn enter_cheap_stage(&self) -> UploadValidationPermit {
+        let start = Instant::now();
+        let permits = acquire(self.cheap_semaphore.as_ref())
+            .await
+            .into_iter()
+            .collect();
+        self.record_queue_wait("cheap", start);
+        UploadValidationPermit { _permits: permits }
+    }
+
+    /// Leaves the cheap validation stage and waits to enter the expensive validation stage for the
+    /// given task.
+    pub(crate) async fn enter_expensive_stage(
+        &self,
+        task_id: &TaskId,
+        cheap_permit: UploadValidationPermit,
+    ) -> UploadValidationPermit {
+        drop(cheap_permit);
+        let start = Instant::now();
+
+        let task_semaphore = self
+            .max_concurrent_expensive_validations_per_task
+            .map(|limit| {
+                Arc::clone(
+                    self.task_expensive_semaphores
+                        .lock()
+                        .unwrap()
+                        .entry(*task_id)
+                        .or_insert_with(|| Arc::new(Semaphore::new(limit))),
+                )
+            });
+        // Wait for the task's own limit before the global limit, so that uploads for a busy task
+        // don't hold global permits while they wait.
+        let permits = [
+            acquire(task_semaphore.as_ref()).await,
+            acquire(self.expensive_semaphore.as_ref()).await,
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+
+        self.record_queue_wait("expensive", start);
+        UploadValidationPermit { _permits: permits }
+    }
+
+    /// Returns true if the given report was recently accepted for the given task.
+    pub(crate) fn is_recently_accepted(&self, task_id: &TaskId, report_id: &ReportId) -> bool {
+        self.recent_report_ids
+            .lock()
+            .unwrap()
+            .get(task_id)
+            .map_or(false, |recent| recent.contains(report_id))
+    }
+
+    /// Records that the given report was accepted for the given task.
+    pub(crate) fn record_accepted(&self, task_id: &TaskId, report_id: &ReportId) {
+        if self.recent_report_id_cache_size == 0 {
+            return;
+        }
+        self.recent_report_ids
+            .lock()
+            .unwrap()
+            .entry(*task_id)
+            .or_default()
+            .insert(*report_id, self.recent_report_id_cache_size);
+    }
+
+    fn record_queue_wait(&self, stage: &'static str, start: Instant) {
+        self.queue_wait_histogram.record(
+            start.elapsed().as_secs_f64(),
+            &[KeyValue::new("stage", stage)],
+        );
+    }
+}
+
+async fn acquire(semaphore: Option<&Arc<Semaphore>>) -> Option<OwnedSemaphorePermit> {
+    // Unwrap safety: we never close these semaphores.
+    Some(Arc::clone(semaphore?).acquire_owned().await.unwrap())
+}
+
+/// A bounded set of report IDs, which forgets the oldest report ID once it is full.
+#[derive(Debug, Default)]
+struct RecentReportIds {
+    ids: HashSet<ReportId>,
+    order: VecDeque<ReportId>,
+}
+
+impl RecentReportIds {
+    fn contains(&self, report_id: &ReportId) -> bool {
+        self.ids.contains(report_id)
+    }
+
+    fn insert(&mut self, report_id: ReportId, capacity: usize) {
+        if !self.ids.insert(report_id) {
+            return;
+        }
+        self.order.push_back(report_id);
+        while self.order.len() > capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.ids.remove(&oldest);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{aggregator::upload_validation::UploadValidation, config::UploadValidationConfig};
+    use janus_aggregator_core::test_util::noop_meter;
+    use rand::random;
+    use std::time::Duration;
+    use tokio::time::timeout;
+
+    #[tokio::test]
+    async fn per_task_expensive_limit() {
+        let upload_validation = UploadValidation::new(
+            &noop_meter(),
+            &UploadValidationConfig {
+                max_concurrent_expensive_validations: Some(2),
+                max_concurrent_expensive_validations_per_task: Some(1),
+                ..Default::default()
+            },
+        );
+        let (task_id, other_task_id) = (random(), random());
+
+        let _permit = upload_validation
+            .enter_expensive_stage(&task_id, upload_validation.enter_cheap_stage().await)
+            .await;
+
+        // The task is at its limit, but other tasks and the cheap stage are not held up.
+        let cheap_permit = upload_validation.enter_cheap_stage().await;
+        assert!(timeout(
+            Duration::from_millis(100),
+            upload_validation.enter_expensive_stage(&task_id, cheap_permit)
+        )
+        .await
+        .is_err());
+        let _other_permit = timeout(
+            Duration::from_secs(1),
+            upload_validation
+                .enter_expensive_stage(&other_task_id, upload_validation.enter_cheap_stage().await),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[test]
+    fn recent_report_ids() {
+        let upload_validation = UploadValidation::new(
+            &noop_meter(),
+            &UploadValidationConfig {
+                recent_report_id_cache_size: 2,
+                ..Default::default()
+            },
+        );
+        let (task_id, other_task_id) = (random(), random());
+        let report_ids: [_; 3] = random();
+
+        upload_validation.record_accepted(&task_id, &report_ids[0]);
+        upload_validation.record_accepted(&task_id, &report_ids[1]);
+        assert!(upload_validation.is_recently_accepted(&task_id, &report_ids[0]));
+        assert!(!upload_validation.is_recently_accepted(&other_task_id, &report_ids[0]));
+
+        // The oldest report ID is forgotten once the cache is full.
+        upload_validation.record_accepted(&task_id, &report_ids[2]);
+        assert!(!upload_validation.is_recently_accepted(&task_id, &report_ids[0]));
+        assert!(upload_validation.is_recently_accepted(&task_id, &report_ids[1]));
+        assert!(upload_validation.is_recently_accepted(&task_id, &report_ids[2]));
+    }
+}
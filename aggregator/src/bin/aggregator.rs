@@ -1,6 +1,11 @@
 use janus_aggregator::{binaries::aggregator::main_callback, binary_utils::janus_main};
 use janus_core::time::RealClock;
 
+#[cfg(feature = "allocation-tracking")]
+#[global_allocator]
+static ALLOCATOR: janus_aggregator::binary_utils::TrackingAllocator =
+    janus_aggregator::binary_utils::TrackingAllocator;
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     janus_main(RealClock::default(), main_callback).await
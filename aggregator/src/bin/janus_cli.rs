@@ -1,36 +1,78 @@
 use anyhow::{anyhow, Context, Result};
 use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
 use janus_aggregator::{
-    binary_utils::{database_pool, datastore, read_config, CommonBinaryOptions},
-    config::{BinaryConfig, CommonConfig},
+    aggregator::garbage_collector,
+    binaries,
+    binary_utils::{
+        database_pool, datastore_crypter, datastore_with_crypter, migration_databases, read_config,
+        CommonBinaryOptions,
+    },
+    config::{parse_config, BinaryConfig, CommonConfig, DbConfig, MeasurementLengthLimitsConfig},
     git_revision,
     metrics::{install_metrics_exporter, MetricsExporterHandle},
+    sharding::{ShardMap, ShardingConfig},
     trace::{install_trace_subscriber, TraceGuards},
 };
 use janus_aggregator_core::{
-    datastore::{self, Datastore},
-    task::{AggregatorTask, SerializedAggregatorTask},
+    datastore::{
+        self,
+        models::{
+            ActiveDeployment, AdminApiKey, FeatureFlag, TaskHpkeConfig, TaskLifecycleEvent,
+            TaskMetricsSnapshot, TaskUploadCounter, UploadSample,
+        },
+        Crypter, Datastore, SUPPORTED_SCHEMA_VERSIONS,
+    },
+    task::{AggregatorTask, QueryType, SerializedAggregatorTask, TaskState},
+    SecretBytes,
+};
+use janus_core::{
+    auth_tokens::{AuthenticationToken, AuthenticationTokenHash},
+    hpke::{
+        self, generate_hpke_config_and_private_key, HpkeApplicationInfo, HpkeKeypair,
+        HpkePrivateKey, Label,
+    },
+    time::{Clock, RealClock, TimeExt},
+    vdaf::{MeasurementLengthLimits, VdafInstance},
+};
+use janus_messages::{
+    codec::Encode, AggregateShareAad, BatchSelector, Duration, HpkeConfig, HpkeConfigId, Interval,
+    Role, TaskId, Time,
 };
-use janus_core::time::{Clock, RealClock};
 use k8s_openapi::api::core::v1::Secret;
 use kube::api::{ObjectMeta, PostParams};
 use opentelemetry::global::meter;
-use rand::{distributions::Standard, thread_rng, Rng};
+use rand::{distributions::Standard, random, thread_rng, Rng};
 use ring::aead::AES_128_GCM;
+use schemars::{schema::RootSchema, schema_for, JsonSchema};
 use serde::{Deserialize, Serialize};
+use sqlx::{
+    migrate::{Migrate, Migrator},
+    postgres::{PgConnectOptions, PgSslMode},
+    ConnectOptions, PgConnection,
+};
 use std::{
-    collections::BTreeMap,
+    collections::{BTreeMap, HashMap, HashSet},
+    env,
+    io::{stdin, BufRead, IsTerminal},
     path::{Path, PathBuf},
+    str::FromStr,
     sync::{Arc, OnceLock},
+    time::Duration as StdDuration,
 };
-use tokio::fs;
-use tracing::{debug, info};
+use tokio::{fs, try_join};
+use tracing::{debug, info, warn};
+use zeroize::Zeroizing;
 
 #[tokio::main]
 async fn main() -> Result<()> {
     // Parse command-line options, then read & parse config.
     let command_line_options = CommandLineOptions::parse();
+    if let Command::PrintConfigSchema { binary } = &command_line_options.cmd {
+        // This command does not need a configuration file, and should not produce any output
+        // other than the schema.
+        return print_config_schema(*binary);
+    }
     let config_file: ConfigFile = read_config(&command_line_options.common_options)?;
 
     let _guards = install_tracing_and_metrics_handlers(config_file.common_config()).await?;
@@ -73,6 +115,14 @@ enum Command {
         /// Write the YAML representation of the tasks that are written to stdout
         #[clap(long, default_value = "false")]
         echo_tasks: bool,
+
+        /// Prompt for this many shares of each task's VDAF verify key
+        ///
+        /// Each share is entered by a different operator, in unpadded base64url, and the shares
+        /// are XORed together in memory to form the verify key, so that no single operator knows
+        /// the verify key. Tasks in the YAML tasks file must omit vdaf_verify_key.
+        #[clap(long, conflicts_with = "echo_tasks")]
+        verify_key_shares: Option<usize>,
     },
 
     /// Create a datastore key and write it to a Kubernetes secret
@@ -80,6 +130,334 @@ enum Command {
         #[clap(flatten)]
         kubernetes_secret_options: KubernetesSecretOptions,
     },
+
+    /// Apply SQL migrations to the database, up to the latest schema version this version of Janus
+    /// supports
+    ///
+    /// Migrations for later schema versions are not applied, since Janus refuses to run against a
+    /// schema it does not support. Migrations are recorded in the same table as `sqlx migrate run`
+    /// uses, so the two may be used interchangeably.
+    Migrate {
+        /// Directory containing the SQL migration scripts, e.g. `/migrations` in the
+        /// janus_db_migrator container image
+        #[clap(long)]
+        migrations_path: PathBuf,
+    },
+
+    /// Write the JSON Schema of a Janus binary's configuration file to stdout
+    ///
+    /// The schema may be used to validate configuration files before deploying them. The
+    /// configuration file named by --config-file is not read.
+    PrintConfigSchema {
+        /// The binary whose configuration file schema should be written
+        #[clap(value_enum)]
+        binary: ConfigSchemaBinary,
+    },
+
+    /// Move tasks that are not owned by this deployment to the shards that own them
+    ///
+    /// Only task definitions are moved; reports and aggregation state are not copied. Tasks should
+    /// be rebalanced before they receive uploads, or pinned to their current shard with an explicit
+    /// assignment until their data has been collected.
+    RebalanceTasks {
+        #[clap(flatten)]
+        kubernetes_secret_options: KubernetesSecretOptions,
+
+        /// A YAML file containing the sharding configuration
+        ///
+        /// Each shard that tasks are moved to must have a database configured. All shards must use
+        /// the same datastore keys.
+        sharding_config_file: PathBuf,
+
+        /// Name of the shard whose database is configured in the config file
+        #[clap(long)]
+        local_shard: String,
+
+        /// Delete moved tasks from this shard's datastore after writing them to their new shard
+        #[clap(long, default_value = "false")]
+        delete_moved_tasks: bool,
+    },
+
+    /// Check the datastore for inconsistent data, optionally repairing it
+    ///
+    /// Report aggregations without an aggregation job and batch aggregations with impossible
+    /// counts are deleted on repair. Finished collection jobs whose batch aggregations are missing
+    /// are abandoned on repair.
+    Fsck {
+        #[clap(flatten)]
+        kubernetes_secret_options: KubernetesSecretOptions,
+
+        /// Repair any inconsistencies found
+        #[clap(long, default_value = "false")]
+        repair: bool,
+    },
+
+    /// Check that the two datastores named by the datastore_migration configuration hold the same
+    /// tasks, keys, reports and job state
+    ///
+    /// Row counts of each table and a digest of each task's report IDs are compared. Job drivers,
+    /// job creators and the garbage collector should be paused while verifying, since job state is
+    /// only written to one of the datastores.
+    VerifyDatastoreMigration {
+        #[clap(flatten)]
+        kubernetes_secret_options: KubernetesSecretOptions,
+    },
+
+    /// Write the unexpired upload samples for a task to stdout, as YAML
+    ///
+    /// Upload samples are only recorded if the aggregator's upload_sampling configuration is set.
+    ListUploadSamples {
+        #[clap(flatten)]
+        kubernetes_secret_options: KubernetesSecretOptions,
+
+        /// ID of the task whose upload samples are listed
+        #[clap(long)]
+        task_id: TaskId,
+    },
+
+    /// Gather a snapshot of this deployment's state into a single YAML document, to attach to bug
+    /// reports
+    ///
+    /// The snapshot includes the configuration, the database schema version, a summary and the
+    /// upload counters of each task, and counts of aggregation and collection jobs in each state.
+    /// Secrets, such as the database password and task keys and tokens, are left out.
+    SupportBundle {
+        #[clap(flatten)]
+        kubernetes_secret_options: KubernetesSecretOptions,
+
+        /// File to write the support bundle to, instead of stdout
+        #[clap(long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Enable or disable a runtime feature flag, for all tasks or for a single task
+    ///
+    /// Flags stored in the datastore override flags set in configuration files. Running processes
+    /// pick up changes when they next refresh their feature flags.
+    SetFeatureFlag {
+        #[clap(flatten)]
+        kubernetes_secret_options: KubernetesSecretOptions,
+
+        /// Name of the feature flag
+        name: String,
+
+        /// ID of the task the flag applies to. If not set, the flag applies to all tasks
+        #[clap(long)]
+        task_id: Option<TaskId>,
+
+        /// Whether the flag is enabled
+        #[clap(long, action = clap::ArgAction::Set)]
+        enabled: bool,
+    },
+
+    /// Delete a runtime feature flag from the datastore, so that it takes its configured or
+    /// default state
+    DeleteFeatureFlag {
+        #[clap(flatten)]
+        kubernetes_secret_options: KubernetesSecretOptions,
+
+        /// Name of the feature flag
+        name: String,
+
+        /// ID of the task the flag applies to. If not set, the flag for all tasks is deleted
+        #[clap(long)]
+        task_id: Option<TaskId>,
+    },
+
+    /// Write the runtime feature flags stored in the datastore to stdout, as YAML
+    ListFeatureFlags {
+        #[clap(flatten)]
+        kubernetes_secret_options: KubernetesSecretOptions,
+    },
+
+    /// Move a task to another lifecycle state
+    ///
+    /// Operators activate provisioned tasks, and purge tasks that should be deleted. The garbage
+    /// collector moves tasks through the other states, and deletes purged tasks.
+    SetTaskState {
+        #[clap(flatten)]
+        kubernetes_secret_options: KubernetesSecretOptions,
+
+        /// ID of the task to update
+        #[clap(long)]
+        task_id: TaskId,
+
+        /// The task's new state, e.g. `Active` or `Purging`
+        #[clap(long, value_parser = parse_task_state)]
+        state: TaskState,
+    },
+
+    /// Write the recorded task lifecycle transitions to stdout, as YAML
+    ListTaskLifecycleEvents {
+        #[clap(flatten)]
+        kubernetes_secret_options: KubernetesSecretOptions,
+
+        /// ID of the task whose transitions are listed. If not set, transitions of all tasks are
+        /// listed
+        #[clap(long)]
+        task_id: Option<TaskId>,
+    },
+
+    /// Write a task's hourly metrics snapshots to stdout, as YAML
+    ///
+    /// Snapshots are only taken if the aggregator's metrics_snapshots configuration is set. Each
+    /// snapshot holds cumulative counters, along with their increase since the previous snapshot.
+    ListMetricsSnapshots {
+        #[clap(flatten)]
+        kubernetes_secret_options: KubernetesSecretOptions,
+
+        /// ID of the task whose snapshots are listed. Snapshots are kept after a task is deleted
+        #[clap(long)]
+        task_id: TaskId,
+
+        /// How many days of snapshots to list
+        #[clap(long, default_value = "30")]
+        days: u64,
+    },
+
+    /// Check that a collector private key matches a task's collector HPKE config
+    ///
+    /// A synthetic aggregate share is encrypted to the task's collector HPKE config, as it would
+    /// be when serving a collection, and then decrypted with the given private key. This catches
+    /// mismatched collector keys before the first collection.
+    VerifyCollectorKey {
+        #[clap(flatten)]
+        kubernetes_secret_options: KubernetesSecretOptions,
+
+        /// ID of the task whose collector HPKE config is checked
+        #[clap(long)]
+        task_id: TaskId,
+
+        /// The collector's HPKE private key, encoded in unpadded url-safe base64
+        #[clap(long, env = "COLLECTOR_PRIVATE_KEY", hide_env_values = true)]
+        collector_private_key: HpkePrivateKey,
+    },
+
+    /// Make a deployment the active one, among an active/standby pair of leader deployments sharing
+    /// this datastore
+    ///
+    /// Only binaries whose deployment_name matches the active deployment create and step jobs.
+    /// Outstanding job and leader leases are expired, so the promoted deployment takes over
+    /// immediately, and work still in flight in the previously active deployment fails to commit.
+    Promote {
+        #[clap(flatten)]
+        kubernetes_secret_options: KubernetesSecretOptions,
+
+        /// Name of the deployment to promote, matching its binaries' deployment_name
+        deployment: String,
+    },
+
+    /// Manage the HPKE keypairs that clients encrypt a task's report shares to
+    HpkeKeys {
+        #[clap(subcommand)]
+        command: HpkeKeysCommand,
+    },
+
+    /// Manage admin API keys, which grant access to a subset of tasks via the aggregator API until
+    /// they expire
+    AdminApiKeys {
+        #[clap(subcommand)]
+        command: AdminApiKeysCommand,
+    },
+}
+
+/// Janus binaries whose configuration file schemas may be printed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum ConfigSchemaBinary {
+    Aggregator,
+    AggregationJobCreator,
+    AggregationJobDriver,
+    CollectionJobDriver,
+    EdgeHelper,
+    UploadIngester,
+    JanusCli,
+}
+
+impl ConfigSchemaBinary {
+    fn schema(&self) -> RootSchema {
+        match self {
+            Self::Aggregator => schema_for!(binaries::aggregator::Config),
+            Self::AggregationJobCreator => schema_for!(binaries::aggregation_job_creator::Config),
+            Self::AggregationJobDriver => schema_for!(binaries::aggregation_job_driver::Config),
+            Self::CollectionJobDriver => schema_for!(binaries::collection_job_driver::Config),
+            Self::EdgeHelper => schema_for!(binaries::edge_helper::Config),
+            Self::UploadIngester => schema_for!(binaries::upload_ingester::Config),
+            Self::JanusCli => schema_for!(ConfigFile),
+        }
+    }
+}
+
+#[derive(Debug, Subcommand)]
+enum HpkeKeysCommand {
+    /// Generate a new HPKE keypair for a task, and schedule the task's other keypairs for
+    /// retirement
+    ///
+    /// The new keypair is advertised to clients immediately. Old keypairs can still decrypt
+    /// reports until they are retired, giving clients time to fetch the new HPKE config, and are
+    /// deleted by the next rotation after that. The new HPKE config is written to stdout, as YAML.
+    Rotate {
+        #[clap(flatten)]
+        kubernetes_secret_options: KubernetesSecretOptions,
+
+        /// ID of the task whose keypairs are rotated
+        #[clap(long)]
+        task_id: TaskId,
+
+        /// How long old keypairs remain usable after rotation, in seconds
+        #[clap(long, default_value = "604800")]
+        retire_after_secs: u64,
+    },
+
+    /// Write a task's HPKE configs to stdout, as YAML, including those of retired keypairs that
+    /// have not yet been deleted
+    List {
+        #[clap(flatten)]
+        kubernetes_secret_options: KubernetesSecretOptions,
+
+        /// ID of the task whose HPKE configs are listed
+        #[clap(long)]
+        task_id: TaskId,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum AdminApiKeysCommand {
+    /// Create an admin API key, scoped to the given tasks
+    ///
+    /// The key, including its bearer token, is written to stdout as YAML. Only a hash of the token
+    /// is stored, so it can't be retrieved later.
+    Create {
+        #[clap(flatten)]
+        kubernetes_secret_options: KubernetesSecretOptions,
+
+        /// Name identifying the key, e.g. the team it is issued to
+        #[clap(long)]
+        name: String,
+
+        /// ID of a task the key grants access to; may be repeated
+        #[clap(long = "task-id", required = true)]
+        task_ids: Vec<TaskId>,
+
+        /// How long the key remains valid, in seconds
+        #[clap(long, default_value = "7776000")]
+        expires_in_secs: u64,
+    },
+
+    /// Write all admin API keys to stdout, as YAML, including expired keys
+    List {
+        #[clap(flatten)]
+        kubernetes_secret_options: KubernetesSecretOptions,
+    },
+
+    /// Delete an admin API key, revoking it immediately
+    Delete {
+        #[clap(flatten)]
+        kubernetes_secret_options: KubernetesSecretOptions,
+
+        /// Name of the key to delete
+        #[clap(long)]
+        name: String,
+    },
 }
 
 impl Command {
@@ -98,6 +476,7 @@ impl Command {
                 tasks_file,
                 generate_missing_parameters,
                 echo_tasks,
+                verify_key_shares,
             } => {
                 let datastore = datastore_from_opts(
                     kubernetes_secret_options,
@@ -107,10 +486,19 @@ impl Command {
                 )
                 .await?;
 
+                let mut prompt_for_shares = verify_key_shares.map(|share_count| {
+                    move |task_index: usize, task_id: Option<TaskId>| {
+                        prompt_for_verify_key_shares(share_count, task_index, task_id)
+                    }
+                });
                 let written_tasks = provision_tasks(
                     &datastore,
                     tasks_file,
                     *generate_missing_parameters,
+                    &config_file.measurement_length_limits.into(),
+                    prompt_for_shares
+                        .as_mut()
+                        .map(|prompt| prompt as &mut ReadVerifyKeyShares),
                     command_line_options.dry_run,
                 )
                 .await?;
@@ -140,91 +528,1635 @@ impl Command {
                 )
                 .await
             }
-        }
-    }
-}
 
-async fn install_tracing_and_metrics_handlers(
-    config: &CommonConfig,
-) -> Result<(TraceGuards, MetricsExporterHandle)> {
-    // Discard the trace reload handler, since this program is short-lived.
-    let (trace_guard, _) = install_trace_subscriber(&config.logging_config)
-        .context("couldn't install tracing subscriber")?;
+            Command::Migrate { migrations_path } => {
+                migrate(
+                    &config_file.common_config.database,
+                    command_line_options
+                        .common_options
+                        .database_password
+                        .as_deref(),
+                    migrations_path,
+                    command_line_options.dry_run,
+                )
+                .await
+            }
 
-    let metrics_guard = install_metrics_exporter(&config.metrics_config)
-        .await
-        .context("failed to install metrics exporter")?;
-    Ok((trace_guard, metrics_guard))
-}
+            Command::PrintConfigSchema { binary } => print_config_schema(*binary),
 
-async fn provision_tasks<C: Clock>(
-    datastore: &Datastore<C>,
-    tasks_file: &Path,
-    generate_missing_parameters: bool,
-    dry_run: bool,
-) -> Result<Vec<AggregatorTask>> {
-    // Read tasks file.
-    let tasks: Vec<SerializedAggregatorTask> = {
-        let task_file_contents = fs::read_to_string(tasks_file)
-            .await
-            .with_context(|| format!("couldn't read tasks file {tasks_file:?}"))?;
-        serde_yaml::from_str(&task_file_contents)
-            .with_context(|| format!("couldn't parse tasks file {tasks_file:?}"))?
-    };
+            Command::RebalanceTasks {
+                kubernetes_secret_options,
+                sharding_config_file,
+                local_shard,
+                delete_moved_tasks,
+            } => {
+                let sharding_config: ShardingConfig = {
+                    let contents = fs::read_to_string(sharding_config_file)
+                        .await
+                        .with_context(|| {
+                            format!("couldn't read sharding config file {sharding_config_file:?}")
+                        })?;
+                    parse_config(&contents).with_context(|| {
+                        format!("couldn't parse sharding config file {sharding_config_file:?}")
+                    })?
+                };
+                let shard_map =
+                    ShardMap::new(sharding_config).context("invalid sharding config")?;
+
+                let crypter = crypter_from_opts(
+                    kubernetes_secret_options,
+                    command_line_options,
+                    config_file,
+                    &kube_client,
+                )
+                .await?;
+                let local_datastore =
+                    datastore_from_crypter(command_line_options, config_file, crypter.clone())
+                        .await?;
+
+                let mut shard_datastores = HashMap::new();
+                for shard in shard_map.shards() {
+                    if &shard.name == local_shard {
+                        continue;
+                    }
+                    if let Some(db_config) = &shard.database {
+                        let pool = database_pool(db_config, None).await?;
+                        let shard_datastore = datastore_with_crypter(
+                            pool,
+                            RealClock::default(),
+                            &meter("janus_aggregator"),
+                            crypter.clone(),
+                            db_config.check_schema_version,
+                            config_file.common_config().max_transaction_retries,
+                        )
+                        .await
+                        .with_context(|| format!("couldn't connect to shard {}", shard.name))?;
+                        shard_datastores.insert(shard.name.clone(), shard_datastore);
+                    }
+                }
 
-    let tasks: Vec<AggregatorTask> = tasks
-        .into_iter()
-        .map(|mut task| {
-            if generate_missing_parameters {
-                task.generate_missing_fields();
+                let leases = if command_line_options.dry_run {
+                    None
+                } else {
+                    Some(
+                        MaintenanceLeases::acquire(
+                            &local_datastore,
+                            &[garbage_collector::LEADER_LEASE_NAME],
+                        )
+                        .await?,
+                    )
+                };
+                let result = rebalance_tasks(
+                    &local_datastore,
+                    &shard_map,
+                    local_shard,
+                    &shard_datastores,
+                    *delete_moved_tasks,
+                    command_line_options.dry_run,
+                )
+                .await;
+                if let Some(leases) = leases {
+                    leases.release().await;
+                }
+                result?;
+                Ok(())
             }
 
-            AggregatorTask::try_from(task)
-        })
-        .collect::<Result<_, _>>()?;
-
-    if dry_run {
-        info!(task_count = %tasks.len(), "DRY RUN: Not writing tasks");
-        return Ok(tasks);
-    }
-
-    let tasks = Arc::new(tasks);
+            Command::Fsck {
+                kubernetes_secret_options,
+                repair,
+            } => {
+                let datastore = datastore_from_opts(
+                    kubernetes_secret_options,
+                    command_line_options,
+                    config_file,
+                    &kube_client,
+                )
+                .await?;
 
-    // Write all tasks requested.
-    info!(task_count = %tasks.len(), "Writing tasks");
-    let written_tasks = datastore
-        .run_tx("provision-tasks", |tx| {
-            let tasks = Arc::clone(&tasks);
-            Box::pin(async move {
-                let mut written_tasks = Vec::new();
-                for task in tasks.iter() {
-                    // We attempt to delete the task, but ignore "task not found" errors since
-                    // the task not existing is an OK outcome too.
-                    match tx.delete_task(task.id()).await {
-                        Ok(()) => {
-                            info!(task_id = %task.id(), "replacing existing task");
-                        }
-                        Err(datastore::Error::MutationTargetNotFound) => (),
-                        err => err?,
-                    }
+                let repaired = *repair && !command_line_options.dry_run;
+                let leases = if repaired {
+                    Some(
+                        MaintenanceLeases::acquire(
+                            &datastore,
+                            &[garbage_collector::LEADER_LEASE_NAME],
+                        )
+                        .await?,
+                    )
+                } else {
+                    None
+                };
+                let result = fsck(&datastore, *repair, command_line_options.dry_run).await;
+                if let Some(leases) = leases {
+                    leases.release().await;
+                }
+                let report = result?;
+                if !report.is_clean() && !repaired {
+                    return Err(anyhow!("datastore has inconsistencies: {report:?}"));
+                }
+                Ok(())
+            }
 
-                    tx.put_aggregator_task(task).await?;
+            Command::VerifyDatastoreMigration {
+                kubernetes_secret_options,
+            } => {
+                let (primary_database, secondary_database) = migration_databases(
+                    config_file.common_config(),
+                    &command_line_options.common_options,
+                );
+                let secondary_database =
+                    secondary_database.context("datastore_migration is not configured")?;
+                let crypter = crypter_from_opts(
+                    kubernetes_secret_options,
+                    command_line_options,
+                    config_file,
+                    &kube_client,
+                )
+                .await?;
+                let primary_datastore =
+                    datastore_for_database(config_file, primary_database, crypter.clone()).await?;
+                let secondary_datastore =
+                    datastore_for_database(config_file, secondary_database, crypter).await?;
 
-                    written_tasks.push(task.clone());
+                if !verify_datastore_migration(&primary_datastore, &secondary_datastore).await? {
+                    return Err(anyhow!("datastores differ"));
                 }
-                Ok(written_tasks)
-            })
-        })
-        .await
-        .context("couldn't write tasks")?;
-
-    Ok(written_tasks)
-}
+                Ok(())
+            }
 
-async fn fetch_datastore_keys(
-    kube_client: &LazyKubeClient,
-    namespace: &str,
-    secret_name: &str,
+            Command::ListUploadSamples {
+                kubernetes_secret_options,
+                task_id,
+            } => {
+                let datastore = datastore_from_opts(
+                    kubernetes_secret_options,
+                    command_line_options,
+                    config_file,
+                    &kube_client,
+                )
+                .await?;
+
+                let samples = list_upload_samples(&datastore, task_id).await?;
+                let samples_yaml = serde_yaml::to_string(&samples)
+                    .context("couldn't serialize upload samples to YAML")?;
+                println!("{samples_yaml}");
+                Ok(())
+            }
+
+            Command::SupportBundle {
+                kubernetes_secret_options,
+                output,
+            } => {
+                let datastore = datastore_from_opts(
+                    kubernetes_secret_options,
+                    command_line_options,
+                    config_file,
+                    &kube_client,
+                )
+                .await?;
+
+                let bundle = support_bundle(&datastore, config_file).await?;
+                let bundle_yaml = serde_yaml::to_string(&bundle)
+                    .context("couldn't serialize support bundle to YAML")?;
+                match output {
+                    Some(output) => fs::write(output, bundle_yaml)
+                        .await
+                        .with_context(|| format!("couldn't write support bundle to {output:?}"))?,
+                    None => println!("{bundle_yaml}"),
+                }
+                Ok(())
+            }
+
+            Command::SetFeatureFlag {
+                kubernetes_secret_options,
+                name,
+                task_id,
+                enabled,
+            } => {
+                let datastore = datastore_from_opts(
+                    kubernetes_secret_options,
+                    command_line_options,
+                    config_file,
+                    &kube_client,
+                )
+                .await?;
+
+                set_feature_flag(
+                    &datastore,
+                    FeatureFlag::new(name.clone(), *task_id, *enabled),
+                    command_line_options.dry_run,
+                )
+                .await
+            }
+
+            Command::DeleteFeatureFlag {
+                kubernetes_secret_options,
+                name,
+                task_id,
+            } => {
+                let datastore = datastore_from_opts(
+                    kubernetes_secret_options,
+                    command_line_options,
+                    config_file,
+                    &kube_client,
+                )
+                .await?;
+
+                delete_feature_flag(
+                    &datastore,
+                    name,
+                    task_id.as_ref(),
+                    command_line_options.dry_run,
+                )
+                .await
+            }
+
+            Command::ListFeatureFlags {
+                kubernetes_secret_options,
+            } => {
+                let datastore = datastore_from_opts(
+                    kubernetes_secret_options,
+                    command_line_options,
+                    config_file,
+                    &kube_client,
+                )
+                .await?;
+
+                let feature_flags = list_feature_flags(&datastore).await?;
+                let feature_flags_yaml = serde_yaml::to_string(&feature_flags)
+                    .context("couldn't serialize feature flags to YAML")?;
+                println!("{feature_flags_yaml}");
+                Ok(())
+            }
+
+            Command::SetTaskState {
+                kubernetes_secret_options,
+                task_id,
+                state,
+            } => {
+                let datastore = datastore_from_opts(
+                    kubernetes_secret_options,
+                    command_line_options,
+                    config_file,
+                    &kube_client,
+                )
+                .await?;
+
+                set_task_state(&datastore, task_id, state, command_line_options.dry_run).await
+            }
+
+            Command::ListTaskLifecycleEvents {
+                kubernetes_secret_options,
+                task_id,
+            } => {
+                let datastore = datastore_from_opts(
+                    kubernetes_secret_options,
+                    command_line_options,
+                    config_file,
+                    &kube_client,
+                )
+                .await?;
+
+                let events = list_task_lifecycle_events(&datastore, task_id.as_ref()).await?;
+                let events_yaml = serde_yaml::to_string(&events)
+                    .context("couldn't serialize task lifecycle events to YAML")?;
+                println!("{events_yaml}");
+                Ok(())
+            }
+
+            Command::ListMetricsSnapshots {
+                kubernetes_secret_options,
+                task_id,
+                days,
+            } => {
+                let datastore = datastore_from_opts(
+                    kubernetes_secret_options,
+                    command_line_options,
+                    config_file,
+                    &kube_client,
+                )
+                .await?;
+
+                let snapshots = list_metrics_snapshots(&datastore, task_id, *days).await?;
+                let snapshots_yaml = serde_yaml::to_string(&snapshots)
+                    .context("couldn't serialize metrics snapshots to YAML")?;
+                println!("{snapshots_yaml}");
+                Ok(())
+            }
+
+            Command::VerifyCollectorKey {
+                kubernetes_secret_options,
+                task_id,
+                collector_private_key,
+            } => {
+                let datastore = datastore_from_opts(
+                    kubernetes_secret_options,
+                    command_line_options,
+                    config_file,
+                    &kube_client,
+                )
+                .await?;
+
+                verify_collector_key(&datastore, task_id, collector_private_key).await?;
+                println!(
+                    "Collector private key matches the collector HPKE config of task {task_id}"
+                );
+                Ok(())
+            }
+
+            Command::Promote {
+                kubernetes_secret_options,
+                deployment,
+            } => {
+                let datastore = datastore_from_opts(
+                    kubernetes_secret_options,
+                    command_line_options,
+                    config_file,
+                    &kube_client,
+                )
+                .await?;
+
+                promote_deployment(&datastore, deployment, command_line_options.dry_run).await
+            }
+
+            Command::HpkeKeys {
+                command:
+                    HpkeKeysCommand::Rotate {
+                        kubernetes_secret_options,
+                        task_id,
+                        retire_after_secs,
+                    },
+            } => {
+                let datastore = datastore_from_opts(
+                    kubernetes_secret_options,
+                    command_line_options,
+                    config_file,
+                    &kube_client,
+                )
+                .await?;
+
+                let config = rotate_task_hpke_keys(
+                    &datastore,
+                    task_id,
+                    &Duration::from_seconds(*retire_after_secs),
+                    command_line_options.dry_run,
+                )
+                .await?;
+                let config_yaml = serde_yaml::to_string(&config)
+                    .context("couldn't serialize HPKE config to YAML")?;
+                println!("{config_yaml}");
+                Ok(())
+            }
+
+            Command::HpkeKeys {
+                command:
+                    HpkeKeysCommand::List {
+                        kubernetes_secret_options,
+                        task_id,
+                    },
+            } => {
+                let datastore = datastore_from_opts(
+                    kubernetes_secret_options,
+                    command_line_options,
+                    config_file,
+                    &kube_client,
+                )
+                .await?;
+
+                let configs = list_task_hpke_keys(&datastore, task_id).await?;
+                let configs_yaml = serde_yaml::to_string(&configs)
+                    .context("couldn't serialize HPKE configs to YAML")?;
+                println!("{configs_yaml}");
+                Ok(())
+            }
+
+            Command::AdminApiKeys {
+                command:
+                    AdminApiKeysCommand::Create {
+                        kubernetes_secret_options,
+                        name,
+                        task_ids,
+                        expires_in_secs,
+                    },
+            } => {
+                let datastore = datastore_from_opts(
+                    kubernetes_secret_options,
+                    command_line_options,
+                    config_file,
+                    &kube_client,
+                )
+                .await?;
+
+                let admin_api_key = create_admin_api_key(
+                    &datastore,
+                    name,
+                    task_ids,
+                    &Duration::from_seconds(*expires_in_secs),
+                    command_line_options.dry_run,
+                )
+                .await?;
+                let admin_api_key_yaml = serde_yaml::to_string(&admin_api_key)
+                    .context("couldn't serialize admin API key to YAML")?;
+                println!("{admin_api_key_yaml}");
+                Ok(())
+            }
+
+            Command::AdminApiKeys {
+                command:
+                    AdminApiKeysCommand::List {
+                        kubernetes_secret_options,
+                    },
+            } => {
+                let datastore = datastore_from_opts(
+                    kubernetes_secret_options,
+                    command_line_options,
+                    config_file,
+                    &kube_client,
+                )
+                .await?;
+
+                let admin_api_keys = list_admin_api_keys(&datastore).await?;
+                let admin_api_keys_yaml = serde_yaml::to_string(&admin_api_keys)
+                    .context("couldn't serialize admin API keys to YAML")?;
+                println!("{admin_api_keys_yaml}");
+                Ok(())
+            }
+
+            Command::AdminApiKeys {
+                command:
+                    AdminApiKeysCommand::Delete {
+                        kubernetes_secret_options,
+                        name,
+                    },
+            } => {
+                let datastore = datastore_from_opts(
+                    kubernetes_secret_options,
+                    command_line_options,
+                    config_file,
+                    &kube_client,
+                )
+                .await?;
+
+                delete_admin_api_key(&datastore, name, command_line_options.dry_run).await
+            }
+        }
+    }
+}
+
+fn print_config_schema(binary: ConfigSchemaBinary) -> Result<()> {
+    let schema = serde_json::to_string_pretty(&binary.schema())
+        .context("couldn't serialize configuration schema")?;
+    println!("{schema}");
+    Ok(())
+}
+
+async fn install_tracing_and_metrics_handlers(
+    config: &CommonConfig,
+) -> Result<(TraceGuards, MetricsExporterHandle)> {
+    // Discard the trace reload handler, since this program is short-lived.
+    let (trace_guard, _) = install_trace_subscriber(&config.logging_config)
+        .context("couldn't install tracing subscriber")?;
+
+    let metrics_guard = install_metrics_exporter(&config.metrics_config)
+        .await
+        .context("failed to install metrics exporter")?;
+    Ok((trace_guard, metrics_guard))
+}
+
+/// Returns the shares of the VDAF verify key of the task at the given index in the tasks file,
+/// which has the given task ID if one is set.
+type ReadVerifyKeyShares<'a> =
+    dyn FnMut(usize, Option<TaskId>) -> Result<Vec<SecretBytes>> + Send + 'a;
+
+/// Prompts on stderr for `share_count` shares of the VDAF verify key of a task, reading each share
+/// from stdin. If stdin is a terminal, echo is disabled while each share is entered, so that
+/// operators can take turns at the same terminal without seeing one another's shares.
+fn prompt_for_verify_key_shares(
+    share_count: usize,
+    task_index: usize,
+    task_id: Option<TaskId>,
+) -> Result<Vec<SecretBytes>> {
+    let task_description = match task_id {
+        Some(task_id) => format!("task {task_id}"),
+        None => format!("task {task_index} in the tasks file"),
+    };
+    let hide_input = stdin().is_terminal();
+
+    (1..=share_count)
+        .map(|share_number| {
+            eprint!(
+                "Operator {share_number} of {share_count}, enter your verify key share for \
+                 {task_description}: "
+            );
+            let line = read_stdin_line(hide_input)?;
+            let share = URL_SAFE_NO_PAD
+                .decode(line.trim())
+                .context("couldn't decode verify key share")?;
+            Ok(SecretBytes::new(share))
+        })
+        .collect()
+}
+
+/// Reads a line from stdin. If `hide_input` is set, the line is instead read from the terminal with
+/// echo disabled; echo is restored once the line is read, even if reading fails. The line is
+/// zeroized when dropped.
+fn read_stdin_line(hide_input: bool) -> Result<Zeroizing<String>> {
+    if hide_input {
+        let line =
+            Zeroizing::new(rpassword::read_password().context("couldn't read from terminal")?);
+        eprintln!();
+        return Ok(line);
+    }
+
+    let mut line = Zeroizing::new(String::new());
+    if stdin()
+        .lock()
+        .read_line(&mut line)
+        .context("couldn't read from stdin")?
+        == 0
+    {
+        return Err(anyhow!("unexpected end of input"));
+    }
+    Ok(line)
+}
+
+async fn provision_tasks<C: Clock>(
+    datastore: &Datastore<C>,
+    tasks_file: &Path,
+    generate_missing_parameters: bool,
+    measurement_length_limits: &MeasurementLengthLimits,
+    read_verify_key_shares: Option<&mut ReadVerifyKeyShares<'_>>,
+    dry_run: bool,
+) -> Result<Vec<AggregatorTask>> {
+    // Read tasks file.
+    let mut tasks: Vec<SerializedAggregatorTask> = {
+        let task_file_contents = fs::read_to_string(tasks_file)
+            .await
+            .with_context(|| format!("couldn't read tasks file {tasks_file:?}"))?;
+        serde_yaml::from_str(&task_file_contents)
+            .with_context(|| format!("couldn't parse tasks file {tasks_file:?}"))?
+    };
+
+    if let Some(read_verify_key_shares) = read_verify_key_shares {
+        for (task_index, task) in tasks.iter_mut().enumerate() {
+            let shares = read_verify_key_shares(task_index, task.task_id())?;
+            task.combine_vdaf_verify_key_shares(&shares)
+                .with_context(|| {
+                    format!("couldn't combine verify key shares of task {task_index}")
+                })?;
+        }
+    }
+
+    let tasks: Vec<AggregatorTask> = tasks
+        .into_iter()
+        .map(|mut task| {
+            if generate_missing_parameters {
+                task.generate_missing_fields()?;
+            }
+
+            AggregatorTask::try_from(task)
+        })
+        .collect::<Result<_, _>>()?;
+    for task in &tasks {
+        task.vdaf()
+            .check_measurement_length(measurement_length_limits)
+            .with_context(|| format!("task {} has an unsupported VDAF", task.id()))?;
+    }
+
+    if dry_run {
+        info!(task_count = %tasks.len(), "DRY RUN: Not writing tasks");
+        return Ok(tasks);
+    }
+
+    let tasks = Arc::new(tasks);
+
+    // Write all tasks requested.
+    info!(task_count = %tasks.len(), "Writing tasks");
+    let written_tasks = datastore
+        .run_tx("provision-tasks", |tx| {
+            let tasks = Arc::clone(&tasks);
+            Box::pin(async move {
+                let mut written_tasks = Vec::new();
+                for task in tasks.iter() {
+                    // We attempt to delete the task, but ignore "task not found" errors since
+                    // the task not existing is an OK outcome too.
+                    match tx.delete_task(task.id()).await {
+                        Ok(()) => {
+                            info!(task_id = %task.id(), "replacing existing task");
+                        }
+                        Err(datastore::Error::MutationTargetNotFound) => (),
+                        err => err?,
+                    }
+
+                    tx.put_aggregator_task(task).await?;
+
+                    written_tasks.push(task.clone());
+                }
+                Ok(written_tasks)
+            })
+        })
+        .await
+        .context("couldn't write tasks")?;
+
+    Ok(written_tasks)
+}
+
+/// Writes each task in `datastore` that is owned by some shard other than `local_shard` to that
+/// shard's datastore, optionally deleting it from `datastore`. Returns the moved task IDs along
+/// with the name of the shard each was moved to.
+async fn rebalance_tasks<C: Clock>(
+    datastore: &Datastore<C>,
+    shard_map: &ShardMap,
+    local_shard: &str,
+    shard_datastores: &HashMap<String, Datastore<C>>,
+    delete_moved_tasks: bool,
+    dry_run: bool,
+) -> Result<Vec<(TaskId, String)>> {
+    if shard_map.shard(local_shard).is_none() {
+        return Err(anyhow!(
+            "local shard {local_shard} is not in sharding config"
+        ));
+    }
+
+    let tasks = datastore
+        .run_tx("rebalance-tasks-get", |tx| {
+            Box::pin(async move { tx.get_aggregator_tasks().await })
+        })
+        .await
+        .context("couldn't get tasks")?;
+
+    let moves: Vec<_> = tasks
+        .into_iter()
+        .filter_map(|task| {
+            let shard = shard_map.shard_for_task(task.id());
+            (shard.name != local_shard).then(|| (task, shard.name.clone()))
+        })
+        .collect();
+    info!(task_count = %moves.len(), "Found tasks owned by other shards");
+
+    let mut moved_tasks = Vec::new();
+    for (task, shard_name) in moves {
+        let task_id = *task.id();
+        if dry_run {
+            info!(%task_id, shard = %shard_name, "DRY RUN: Not moving task");
+            moved_tasks.push((task_id, shard_name));
+            continue;
+        }
+
+        let shard_datastore = shard_datastores
+            .get(&shard_name)
+            .with_context(|| format!("no database configured for shard {shard_name}"))?;
+
+        info!(%task_id, shard = %shard_name, "Moving task");
+        let task = Arc::new(task);
+        shard_datastore
+            .run_tx("rebalance-tasks-put", |tx| {
+                let task = Arc::clone(&task);
+                Box::pin(async move {
+                    match tx.delete_task(task.id()).await {
+                        Ok(()) => {
+                            warn!(task_id = %task.id(), "replacing existing task on new shard");
+                        }
+                        Err(datastore::Error::MutationTargetNotFound) => (),
+                        err => err?,
+                    }
+                    tx.put_aggregator_task(&task).await
+                })
+            })
+            .await
+            .with_context(|| format!("couldn't write task {task_id} to shard {shard_name}"))?;
+
+        if delete_moved_tasks {
+            datastore
+                .run_tx("rebalance-tasks-delete", |tx| {
+                    Box::pin(async move { tx.delete_task(&task_id).await })
+                })
+                .await
+                .with_context(|| format!("couldn't delete moved task {task_id}"))?;
+        }
+
+        moved_tasks.push((task_id, shard_name));
+    }
+
+    Ok(moved_tasks)
+}
+
+/// The name of the lease held by destructive commands while they run, so that at most one of them
+/// runs against a datastore at a time.
+const MAINTENANCE_LEASE_NAME: &str = "janus_cli_maintenance";
+
+/// How long maintenance leases last if janus_cli exits without releasing them.
+const MAINTENANCE_LEASE_DURATION: StdDuration = StdDuration::from_secs(3600);
+
+/// Leases held by a destructive command while it runs: the maintenance lease, so that it doesn't
+/// run concurrently with another destructive command, and the leader leases of background jobs
+/// that conflict with it, which pause until it finishes. Background jobs only hold their leader
+/// leases if the aggregator is configured to elect a leader to run them.
+struct MaintenanceLeases<'a, C: Clock> {
+    datastore: &'a Datastore<C>,
+    holder: Arc<str>,
+    names: Arc<[&'static str]>,
+}
+
+impl<'a, C: Clock> MaintenanceLeases<'a, C> {
+    /// Acquires the maintenance lease and the given leader leases of conflicting background jobs.
+    /// Fails without acquiring any of them if any is currently held by someone else.
+    async fn acquire(
+        datastore: &'a Datastore<C>,
+        conflicting_leases: &[&'static str],
+    ) -> Result<MaintenanceLeases<'a, C>> {
+        let holder: Arc<str> = format!(
+            "janus_cli-{}-{:08x}",
+            env::var("HOSTNAME").unwrap_or_else(|_| "janus".to_string()),
+            random::<u32>()
+        )
+        .into();
+        let names: Arc<[&'static str]> = [MAINTENANCE_LEASE_NAME]
+            .into_iter()
+            .chain(conflicting_leases.iter().copied())
+            .collect();
+
+        datastore
+            .run_tx("maintenance-leases-acquire", |tx| {
+                let (holder, names) = (Arc::clone(&holder), Arc::clone(&names));
+                Box::pin(async move {
+                    for name in names.iter() {
+                        let lease = tx
+                            .try_acquire_leader_lease(name, &holder, &MAINTENANCE_LEASE_DURATION)
+                            .await?;
+                        if lease.holder() != &*holder {
+                            return Err(datastore::Error::User(
+                                format!(
+                                    "lease {name} is held by {} until {}",
+                                    lease.holder(),
+                                    lease.lease_expiry_time()
+                                )
+                                .into(),
+                            ));
+                        }
+                    }
+                    Ok(())
+                })
+            })
+            .await
+            .context(
+                "couldn't acquire maintenance leases; another maintenance command or a \
+                 conflicting background job may be running",
+            )?;
+        info!(%holder, leases = ?names, "Acquired maintenance leases");
+
+        Ok(Self {
+            datastore,
+            holder,
+            names,
+        })
+    }
+
+    /// Releases the leases. Errors are logged, since the leases expire eventually regardless.
+    async fn release(self) {
+        let result = self
+            .datastore
+            .run_tx("maintenance-leases-release", |tx| {
+                let (holder, names) = (Arc::clone(&self.holder), Arc::clone(&self.names));
+                Box::pin(async move {
+                    for name in names.iter() {
+                        match tx.release_leader_lease(name, &holder).await {
+                            // The lease may have expired and been taken over.
+                            Ok(()) | Err(datastore::Error::MutationTargetNotFound) => {}
+                            Err(err) => return Err(err),
+                        }
+                    }
+                    Ok(())
+                })
+            })
+            .await;
+        if let Err(err) = result {
+            warn!(?err, "Couldn't release maintenance leases");
+        }
+    }
+}
+
+/// Counts of the inconsistencies found by [`fsck`].
+#[derive(Debug, Default, PartialEq, Eq)]
+struct FsckReport {
+    orphaned_report_aggregations: u64,
+    batch_aggregations_with_impossible_counts: u64,
+    collection_jobs_missing_batch_aggregations: u64,
+}
+
+impl FsckReport {
+    fn is_clean(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+/// Checks `datastore` for inconsistent data, repairing it if `repair` is set. Returns the
+/// inconsistencies found before any repair.
+async fn fsck<C: Clock>(
+    datastore: &Datastore<C>,
+    repair: bool,
+    dry_run: bool,
+) -> Result<FsckReport> {
+    let report = datastore
+        .run_tx("fsck-check", |tx| {
+            Box::pin(async move {
+                Ok(FsckReport {
+                    orphaned_report_aggregations: tx.count_orphaned_report_aggregations().await?,
+                    batch_aggregations_with_impossible_counts: tx
+                        .count_batch_aggregations_with_impossible_counts()
+                        .await?,
+                    collection_jobs_missing_batch_aggregations: tx
+                        .count_collection_jobs_missing_batch_aggregations()
+                        .await?,
+                })
+            })
+        })
+        .await
+        .context("couldn't check datastore")?;
+
+    if report.is_clean() {
+        info!("No inconsistencies found");
+        return Ok(report);
+    }
+    warn!(?report, "Found inconsistencies");
+    if !repair {
+        return Ok(report);
+    }
+    if dry_run {
+        info!("DRY RUN: Not repairing inconsistencies");
+        return Ok(report);
+    }
+
+    // Batch aggregations are deleted before collection jobs are checked, so that collection jobs
+    // relying only on deleted batch aggregations are abandoned too.
+    let repaired = datastore
+        .run_tx("fsck-repair", |tx| {
+            Box::pin(async move {
+                Ok(FsckReport {
+                    orphaned_report_aggregations: tx.delete_orphaned_report_aggregations().await?,
+                    batch_aggregations_with_impossible_counts: tx
+                        .delete_batch_aggregations_with_impossible_counts()
+                        .await?,
+                    collection_jobs_missing_batch_aggregations: tx
+                        .abandon_collection_jobs_missing_batch_aggregations()
+                        .await?,
+                })
+            })
+        })
+        .await
+        .context("couldn't repair datastore")?;
+    info!(?repaired, "Repaired inconsistencies");
+
+    Ok(report)
+}
+
+/// The contents of a datastore compared by `verify-datastore-migration`.
+#[derive(Debug, PartialEq, Eq)]
+struct DatastoreSummary {
+    row_counts: Vec<(String, u64)>,
+    client_report_digests: Vec<(TaskId, u64, Vec<u8>)>,
+}
+
+impl DatastoreSummary {
+    async fn read<C: Clock>(datastore: &Datastore<C>) -> Result<Self> {
+        datastore
+            .run_tx("verify-datastore-migration", |tx| {
+                Box::pin(async move {
+                    Ok(Self {
+                        row_counts: tx.count_rows_by_table().await?,
+                        client_report_digests: tx.get_client_report_digests().await?,
+                    })
+                })
+            })
+            .await
+            .context("couldn't summarize datastore")
+    }
+}
+
+/// Compares the contents of the two datastores of a datastore migration, logging each difference
+/// found. Returns whether the datastores hold the same data.
+async fn verify_datastore_migration<C: Clock>(
+    primary_datastore: &Datastore<C>,
+    secondary_datastore: &Datastore<C>,
+) -> Result<bool> {
+    let (primary, secondary) = try_join!(
+        DatastoreSummary::read(primary_datastore),
+        DatastoreSummary::read(secondary_datastore),
+    )?;
+    if primary == secondary {
+        info!("Datastores match");
+        return Ok(true);
+    }
+
+    for ((table, primary_count), (_, secondary_count)) in
+        primary.row_counts.iter().zip(&secondary.row_counts)
+    {
+        if primary_count != secondary_count {
+            warn!(
+                table,
+                primary_count, secondary_count, "Row counts differ between datastores"
+            );
+        }
+    }
+    let primary_digests: HashMap<_, _> = primary
+        .client_report_digests
+        .iter()
+        .map(|(task_id, count, digest)| (task_id, (count, digest)))
+        .collect();
+    let secondary_digests: HashMap<_, _> = secondary
+        .client_report_digests
+        .iter()
+        .map(|(task_id, count, digest)| (task_id, (count, digest)))
+        .collect();
+    for task_id in primary_digests
+        .keys()
+        .chain(secondary_digests.keys())
+        .collect::<HashSet<_>>()
+    {
+        let primary_report_count = primary_digests.get(task_id).map(|(count, _)| **count);
+        let secondary_report_count = secondary_digests.get(task_id).map(|(count, _)| **count);
+        if primary_digests.get(task_id) != secondary_digests.get(task_id) {
+            warn!(
+                %task_id,
+                ?primary_report_count,
+                ?secondary_report_count,
+                "Client reports differ between datastores"
+            );
+        }
+    }
+    Ok(false)
+}
+
+/// The YAML representation of an upload sample written by `list-upload-samples`.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+struct UploadSampleView {
+    report_id: String,
+    client_timestamp: Time,
+    received_at: Time,
+    timestamp_skew_seconds: i128,
+    extension_types: Vec<u16>,
+    public_share_size: u64,
+    leader_encrypted_input_share_size: u64,
+    helper_encrypted_input_share_size: u64,
+}
+
+impl From<UploadSample> for UploadSampleView {
+    fn from(sample: UploadSample) -> Self {
+        Self {
+            report_id: sample.report_id().to_string(),
+            client_timestamp: *sample.client_timestamp(),
+            received_at: *sample.received_at(),
+            timestamp_skew_seconds: sample.timestamp_skew_seconds(),
+            extension_types: sample.extension_types().to_vec(),
+            public_share_size: sample.public_share_size(),
+            leader_encrypted_input_share_size: sample.leader_encrypted_input_share_size(),
+            helper_encrypted_input_share_size: sample.helper_encrypted_input_share_size(),
+        }
+    }
+}
+
+async fn list_upload_samples<C: Clock>(
+    datastore: &Datastore<C>,
+    task_id: &TaskId,
+) -> Result<Vec<UploadSampleView>> {
+    let task_id = *task_id;
+    let samples = datastore
+        .run_tx("list-upload-samples", |tx| {
+            Box::pin(async move {
+                if tx.get_aggregator_task(&task_id).await?.is_none() {
+                    return Ok(None);
+                }
+                tx.get_upload_samples_for_task(&task_id).await.map(Some)
+            })
+        })
+        .await
+        .context("couldn't get upload samples")?
+        .with_context(|| format!("no such task {task_id}"))?;
+    Ok(samples.into_iter().map(UploadSampleView::from).collect())
+}
+
+async fn set_feature_flag<C: Clock>(
+    datastore: &Datastore<C>,
+    feature_flag: FeatureFlag,
+    dry_run: bool,
+) -> Result<()> {
+    if dry_run {
+        info!(?feature_flag, "DRY RUN: Not setting feature flag");
+        return Ok(());
+    }
+
+    let feature_flag = Arc::new(feature_flag);
+    datastore
+        .run_tx("set-feature-flag", |tx| {
+            let feature_flag = Arc::clone(&feature_flag);
+            Box::pin(async move { tx.put_feature_flag(&feature_flag).await })
+        })
+        .await
+        .with_context(|| format!("couldn't set feature flag {feature_flag:?}"))?;
+    info!(?feature_flag, "Set feature flag");
+    Ok(())
+}
+
+async fn delete_feature_flag<C: Clock>(
+    datastore: &Datastore<C>,
+    name: &str,
+    task_id: Option<&TaskId>,
+    dry_run: bool,
+) -> Result<()> {
+    if dry_run {
+        info!(name, ?task_id, "DRY RUN: Not deleting feature flag");
+        return Ok(());
+    }
+
+    let (name, task_id) = (Arc::new(name.to_string()), task_id.copied());
+    datastore
+        .run_tx("delete-feature-flag", |tx| {
+            let name = Arc::clone(&name);
+            Box::pin(async move { tx.delete_feature_flag(&name, task_id.as_ref()).await })
+        })
+        .await
+        .with_context(|| format!("couldn't delete feature flag {name} (task ID {task_id:?})"))?;
+    info!(%name, ?task_id, "Deleted feature flag");
+    Ok(())
+}
+
+/// The YAML representation of a feature flag written by `list-feature-flags`.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+struct FeatureFlagView {
+    name: String,
+    task_id: Option<TaskId>,
+    enabled: bool,
+}
+
+impl From<FeatureFlag> for FeatureFlagView {
+    fn from(feature_flag: FeatureFlag) -> Self {
+        Self {
+            name: feature_flag.name().to_string(),
+            task_id: feature_flag.task_id().copied(),
+            enabled: feature_flag.enabled(),
+        }
+    }
+}
+
+async fn list_feature_flags<C: Clock>(datastore: &Datastore<C>) -> Result<Vec<FeatureFlagView>> {
+    let feature_flags = datastore
+        .run_tx("list-feature-flags", |tx| {
+            Box::pin(async move { tx.get_feature_flags().await })
+        })
+        .await
+        .context("couldn't get feature flags")?;
+    Ok(feature_flags
+        .into_iter()
+        .map(FeatureFlagView::from)
+        .collect())
+}
+
+fn parse_task_state(value: &str) -> Result<TaskState, serde_yaml::Error> {
+    serde_yaml::from_str(value)
+}
+
+async fn set_task_state<C: Clock>(
+    datastore: &Datastore<C>,
+    task_id: &TaskId,
+    state: &TaskState,
+    dry_run: bool,
+) -> Result<()> {
+    let (task_id, state) = (*task_id, *state);
+    let from = datastore
+        .run_tx("set-task-state", |tx| {
+            Box::pin(async move {
+                let from = match tx.get_aggregator_task(&task_id).await? {
+                    Some(task) => *task.state(),
+                    None => return Ok(None),
+                };
+                if !dry_run {
+                    tx.update_task_state(&task_id, &from, &state).await?;
+                }
+                Ok(Some(from))
+            })
+        })
+        .await
+        .with_context(|| format!("couldn't move task {task_id} to state {state:?}"))?
+        .with_context(|| format!("no such task {task_id}"))?;
+
+    if dry_run {
+        if !from.can_transition_to(&state) {
+            return Err(anyhow!(
+                "task {task_id} can't move from state {from:?} to state {state:?}"
+            ));
+        }
+        info!(%task_id, ?from, to = ?state, "DRY RUN: Not setting task state");
+        return Ok(());
+    }
+    info!(%task_id, ?from, to = ?state, "Set task state");
+    Ok(())
+}
+
+/// The YAML representation of a task lifecycle event written by `list-task-lifecycle-events`.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+struct TaskLifecycleEventView {
+    task_id: TaskId,
+    from_state: Option<TaskState>,
+    to_state: TaskState,
+    occurred_at: Time,
+}
+
+impl From<TaskLifecycleEvent> for TaskLifecycleEventView {
+    fn from(event: TaskLifecycleEvent) -> Self {
+        Self {
+            task_id: *event.task_id(),
+            from_state: event.from_state().copied(),
+            to_state: *event.to_state(),
+            occurred_at: *event.occurred_at(),
+        }
+    }
+}
+
+async fn list_task_lifecycle_events<C: Clock>(
+    datastore: &Datastore<C>,
+    task_id: Option<&TaskId>,
+) -> Result<Vec<TaskLifecycleEventView>> {
+    let task_id = task_id.copied();
+    let events = datastore
+        .run_tx("list-task-lifecycle-events", |tx| {
+            Box::pin(async move { tx.get_task_lifecycle_events(task_id.as_ref()).await })
+        })
+        .await
+        .context("couldn't get task lifecycle events")?;
+    Ok(events
+        .into_iter()
+        .map(TaskLifecycleEventView::from)
+        .collect())
+}
+
+/// The YAML representation of a metrics snapshot written by `list-metrics-snapshots`.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+struct MetricsSnapshotView {
+    snapshot_time: Time,
+    reports_accepted: u64,
+    reports_aggregated: u64,
+    reports_collected: u64,
+    /// The increase of each counter since the previous snapshot listed, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    increase: Option<MetricsIncreaseView>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+struct MetricsIncreaseView {
+    reports_accepted: u64,
+    reports_aggregated: u64,
+    reports_collected: u64,
+}
+
+async fn list_metrics_snapshots<C: Clock>(
+    datastore: &Datastore<C>,
+    task_id: &TaskId,
+    days: u64,
+) -> Result<Vec<MetricsSnapshotView>> {
+    let task_id = *task_id;
+    let period = Duration::from_seconds(days.saturating_mul(86400));
+    let snapshots = datastore
+        .run_tx("list-metrics-snapshots", |tx| {
+            Box::pin(async move {
+                let since = tx
+                    .clock()
+                    .now()
+                    .sub(&period)
+                    .unwrap_or_else(|_| Time::from_seconds_since_epoch(0));
+                tx.get_task_metrics_snapshots(&task_id, &since).await
+            })
+        })
+        .await
+        .context("couldn't get metrics snapshots")?;
+
+    let mut previous: Option<&TaskMetricsSnapshot> = None;
+    Ok(snapshots
+        .iter()
+        .map(|snapshot| {
+            let increase = previous.map(|previous| MetricsIncreaseView {
+                reports_accepted: snapshot
+                    .reports_accepted()
+                    .saturating_sub(previous.reports_accepted()),
+                reports_aggregated: snapshot
+                    .reports_aggregated()
+                    .saturating_sub(previous.reports_aggregated()),
+                reports_collected: snapshot
+                    .reports_collected()
+                    .saturating_sub(previous.reports_collected()),
+            });
+            previous = Some(snapshot);
+            MetricsSnapshotView {
+                snapshot_time: *snapshot.snapshot_time(),
+                reports_accepted: snapshot.reports_accepted(),
+                reports_aggregated: snapshot.reports_aggregated(),
+                reports_collected: snapshot.reports_collected(),
+                increase,
+            }
+        })
+        .collect())
+}
+
+async fn promote_deployment<C: Clock>(
+    datastore: &Datastore<C>,
+    deployment: &str,
+    dry_run: bool,
+) -> Result<()> {
+    let deployment = Arc::new(deployment.to_string());
+    let (previous, promoted) = datastore
+        .run_tx("promote-deployment", |tx| {
+            let deployment = Arc::clone(&deployment);
+            Box::pin(async move {
+                let previous = tx.get_active_deployment().await?;
+                let promoted = if dry_run {
+                    None
+                } else {
+                    Some(tx.promote_deployment(&deployment).await?)
+                };
+                Ok((previous, promoted))
+            })
+        })
+        .await
+        .with_context(|| format!("couldn't promote deployment {deployment}"))?;
+
+    let previous = previous.as_ref().map(ActiveDeployment::deployment);
+    match promoted {
+        Some(promoted) => info!(
+            deployment = promoted.deployment(),
+            epoch = promoted.epoch(),
+            ?previous,
+            "Promoted deployment"
+        ),
+        None => info!(
+            deployment = %deployment,
+            ?previous,
+            "DRY RUN: Not promoting deployment"
+        ),
+    }
+    Ok(())
+}
+
+async fn verify_collector_key<C: Clock>(
+    datastore: &Datastore<C>,
+    task_id: &TaskId,
+    collector_private_key: &HpkePrivateKey,
+) -> Result<()> {
+    let task_id = *task_id;
+    let task = datastore
+        .run_tx("verify-collector-key", |tx| {
+            Box::pin(async move { tx.get_aggregator_task(&task_id).await })
+        })
+        .await
+        .context("couldn't get task")?
+        .with_context(|| format!("no such task {task_id}"))?;
+    let collector_hpke_config = task
+        .collector_hpke_config()
+        .with_context(|| format!("task {task_id} has no collector HPKE config"))?;
+
+    // Encrypt a synthetic aggregate share for an arbitrary batch, just as a real aggregate share
+    // is encrypted when serving a collection.
+    let application_info =
+        HpkeApplicationInfo::new(&Label::AggregateShare, task.role(), &Role::Collector);
+    let aad = match task.query_type() {
+        QueryType::TimeInterval => AggregateShareAad::new(
+            task_id,
+            Vec::new(),
+            BatchSelector::new_time_interval(
+                Interval::new(Time::from_seconds_since_epoch(0), *task.time_precision())
+                    .context("couldn't construct batch interval")?,
+            ),
+        )
+        .get_encoded(),
+        QueryType::FixedSize { .. } => {
+            AggregateShareAad::new(task_id, Vec::new(), BatchSelector::new_fixed_size(random()))
+                .get_encoded()
+        }
+    }
+    .context("couldn't encode aggregate share AAD")?;
+    let aggregate_share: [u8; 32] = random();
+    let ciphertext = hpke::seal(
+        collector_hpke_config,
+        &application_info,
+        &aggregate_share,
+        &aad,
+    )
+    .context("couldn't encrypt synthetic aggregate share to the collector HPKE config")?;
+
+    let collector_keypair =
+        HpkeKeypair::new(collector_hpke_config.clone(), collector_private_key.clone());
+    let plaintext =
+        hpke::open(&collector_keypair, &application_info, &ciphertext, &aad).map_err(|err| {
+            anyhow!(
+                "collector private key does not match the collector HPKE config of task \
+                 {task_id}: {err}"
+            )
+        })?;
+    if plaintext != aggregate_share {
+        return Err(anyhow!(
+            "synthetic aggregate share for task {task_id} was decrypted incorrectly"
+        ));
+    }
+    info!(%task_id, config_id = %collector_hpke_config.id(), "Collector private key matches");
+    Ok(())
+}
+
+/// The YAML representation of a task HPKE config written by `hpke-keys`.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+struct TaskHpkeConfigView {
+    config: HpkeConfig,
+    /// The DAP encoding of the config, in unpadded base64url, for distribution to clients.
+    encoded_config: String,
+    retire_at: Option<Time>,
+}
+
+impl TryFrom<TaskHpkeConfig> for TaskHpkeConfigView {
+    type Error = anyhow::Error;
+
+    fn try_from(config: TaskHpkeConfig) -> Result<Self> {
+        Ok(Self {
+            encoded_config: URL_SAFE_NO_PAD.encode(
+                config
+                    .config()
+                    .get_encoded()
+                    .context("couldn't encode HPKE config")?,
+            ),
+            retire_at: config.retire_at().copied(),
+            config: config.config().clone(),
+        })
+    }
+}
+
+async fn rotate_task_hpke_keys<C: Clock>(
+    datastore: &Datastore<C>,
+    task_id: &TaskId,
+    retire_after: &Duration,
+    dry_run: bool,
+) -> Result<TaskHpkeConfigView> {
+    let (task_id, retire_after) = (*task_id, *retire_after);
+    let (config, retire_at) = datastore
+        .run_tx("rotate-task-hpke-keys", |tx| {
+            Box::pin(async move {
+                if tx.get_aggregator_task(&task_id).await?.is_none() {
+                    return Ok(None);
+                }
+                let configs = tx.get_task_hpke_configs(&task_id).await?;
+
+                // Aggregators advertise the keypair with the greatest config ID, so the new
+                // keypair must have the greatest config ID. It uses the same algorithms as the
+                // keypair it replaces.
+                let latest = configs
+                    .iter()
+                    .map(TaskHpkeConfig::config)
+                    .max_by_key(|config| *config.id())
+                    .ok_or(datastore::Error::DbState(format!(
+                        "task {task_id} has no HPKE keys"
+                    )))?;
+                let config_id = u8::from(*latest.id()).checked_add(1).ok_or_else(|| {
+                    datastore::Error::User(
+                        anyhow!(
+                            "task {task_id} already has an HPKE key with the greatest config ID"
+                        )
+                        .into(),
+                    )
+                })?;
+                let keypair = generate_hpke_config_and_private_key(
+                    HpkeConfigId::from(config_id),
+                    *latest.kem_id(),
+                    *latest.kdf_id(),
+                    *latest.aead_id(),
+                )
+                .map_err(|err| datastore::Error::User(err.into()))?;
+                let retire_at = tx.clock().now().add(&retire_after)?;
+
+                if !dry_run {
+                    tx.delete_retired_task_hpke_keypairs(&task_id).await?;
+                    tx.put_task_hpke_keypair(&task_id, &keypair).await?;
+                    tx.retire_task_hpke_keypairs(&task_id, keypair.config().id(), &retire_at)
+                        .await?;
+                }
+                Ok(Some((keypair.config().clone(), retire_at)))
+            })
+        })
+        .await
+        .with_context(|| format!("couldn't rotate HPKE keys of task {task_id}"))?
+        .with_context(|| format!("no such task {task_id}"))?;
+
+    if dry_run {
+        info!(%task_id, config_id = %config.id(), ?retire_at, "DRY RUN: Not rotating HPKE keys");
+    } else {
+        info!(%task_id, config_id = %config.id(), ?retire_at, "Rotated HPKE keys");
+    }
+    TaskHpkeConfigView::try_from(TaskHpkeConfig::new(config, None))
+}
+
+async fn list_task_hpke_keys<C: Clock>(
+    datastore: &Datastore<C>,
+    task_id: &TaskId,
+) -> Result<Vec<TaskHpkeConfigView>> {
+    let task_id = *task_id;
+    let configs = datastore
+        .run_tx("list-task-hpke-keys", |tx| {
+            Box::pin(async move { tx.get_task_hpke_configs(&task_id).await })
+        })
+        .await
+        .context("couldn't get task HPKE configs")?;
+    configs
+        .into_iter()
+        .map(TaskHpkeConfigView::try_from)
+        .collect()
+}
+
+/// The YAML representation of an admin API key written by `admin-api-keys`. The token is only
+/// known when the key is created, since only its hash is stored.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+struct AdminApiKeyView {
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    token: Option<String>,
+    task_ids: Vec<TaskId>,
+    expiry: Time,
+}
+
+impl From<AdminApiKey> for AdminApiKeyView {
+    fn from(admin_api_key: AdminApiKey) -> Self {
+        Self {
+            name: admin_api_key.name().to_string(),
+            token: None,
+            task_ids: admin_api_key.task_ids().to_vec(),
+            expiry: *admin_api_key.expiry(),
+        }
+    }
+}
+
+async fn create_admin_api_key<C: Clock>(
+    datastore: &Datastore<C>,
+    name: &str,
+    task_ids: &[TaskId],
+    expires_in: &Duration,
+    dry_run: bool,
+) -> Result<AdminApiKeyView> {
+    let token = random::<AuthenticationToken>();
+    let expiry = datastore.clock().now().add(expires_in)?;
+    let admin_api_key = Arc::new(AdminApiKey::new(
+        name.to_string(),
+        AuthenticationTokenHash::from(&token),
+        task_ids.to_vec(),
+        expiry,
+    ));
+
+    datastore
+        .run_tx("create-admin-api-key", |tx| {
+            let admin_api_key = Arc::clone(&admin_api_key);
+            Box::pin(async move {
+                for task_id in admin_api_key.task_ids() {
+                    if tx.get_aggregator_task(task_id).await?.is_none() {
+                        return Err(datastore::Error::User(
+                            anyhow!("no such task {task_id}").into(),
+                        ));
+                    }
+                }
+                if !dry_run {
+                    tx.put_admin_api_key(&admin_api_key).await?;
+                }
+                Ok(())
+            })
+        })
+        .await
+        .with_context(|| format!("couldn't create admin API key {name}"))?;
+
+    if dry_run {
+        info!(name, ?task_ids, %expiry, "DRY RUN: Not creating admin API key");
+    } else {
+        info!(name, ?task_ids, %expiry, "Created admin API key");
+    }
+    Ok(AdminApiKeyView {
+        token: Some(token.as_str().to_string()),
+        ..AdminApiKeyView::from(AdminApiKey::clone(&admin_api_key))
+    })
+}
+
+async fn list_admin_api_keys<C: Clock>(datastore: &Datastore<C>) -> Result<Vec<AdminApiKeyView>> {
+    let admin_api_keys = datastore
+        .run_tx("list-admin-api-keys", |tx| {
+            Box::pin(async move { tx.get_admin_api_keys().await })
+        })
+        .await
+        .context("couldn't get admin API keys")?;
+    Ok(admin_api_keys
+        .into_iter()
+        .map(AdminApiKeyView::from)
+        .collect())
+}
+
+async fn delete_admin_api_key<C: Clock>(
+    datastore: &Datastore<C>,
+    name: &str,
+    dry_run: bool,
+) -> Result<()> {
+    if dry_run {
+        info!(name, "DRY RUN: Not deleting admin API key");
+        return Ok(());
+    }
+
+    let name = Arc::new(name.to_string());
+    datastore
+        .run_tx("delete-admin-api-key", |tx| {
+            let name = Arc::clone(&name);
+            Box::pin(async move { tx.delete_admin_api_key(&name).await })
+        })
+        .await
+        .with_context(|| format!("couldn't delete admin API key {name}"))?;
+    info!(%name, "Deleted admin API key");
+    Ok(())
+}
+
+/// The snapshot of a deployment's state written by `support-bundle`.
+#[derive(Debug, Serialize)]
+struct SupportBundle {
+    version: &'static str,
+    git_revision: &'static str,
+    config: ConfigFile,
+    schema_version: i64,
+    schema_description: String,
+    tasks: Vec<TaskSummary>,
+    aggregation_job_states: BTreeMap<String, u64>,
+    collection_job_states: BTreeMap<String, u64>,
+}
+
+/// The non-secret parameters and upload counters of a task, as included in a support bundle.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+struct TaskSummary {
+    task_id: TaskId,
+    role: Role,
+    vdaf: VdafInstance,
+    query_type: QueryType,
+    task_expiration: Option<Time>,
+    upload_counter: TaskUploadCounter,
+}
+
+async fn support_bundle<C: Clock>(
+    datastore: &Datastore<C>,
+    config_file: &ConfigFile,
+) -> Result<SupportBundle> {
+    let mut config = config_file.clone();
+    let database_url = &mut config.common_config.database.url;
+    if database_url.password().is_some() {
+        let _ = database_url.set_password(Some("REDACTED"));
+    }
+
+    datastore
+        .run_tx("support-bundle", |tx| {
+            let config = config.clone();
+            Box::pin(async move {
+                let (schema_version, schema_description) =
+                    tx.get_current_schema_migration_version().await?;
+
+                let mut tasks = Vec::new();
+                for task in tx.get_aggregator_tasks().await? {
+                    let upload_counter = tx
+                        .get_task_upload_counter(task.id())
+                        .await?
+                        .unwrap_or_default();
+                    tasks.push(TaskSummary {
+                        task_id: *task.id(),
+                        role: *task.role(),
+                        vdaf: task.vdaf().clone(),
+                        query_type: *task.query_type(),
+                        task_expiration: task.task_expiration().copied(),
+                        upload_counter,
+                    });
+                }
+                tasks.sort_by_key(|task| task.task_id);
+
+                Ok(SupportBundle {
+                    version: env!("CARGO_PKG_VERSION"),
+                    git_revision: git_revision(),
+                    config,
+                    schema_version,
+                    schema_description,
+                    tasks,
+                    aggregation_job_states: tx
+                        .count_aggregation_jobs_by_state()
+                        .await?
+                        .into_iter()
+                        .map(|(state, count)| (format!("{state:?}"), count))
+                        .collect(),
+                    collection_job_states: tx
+                        .count_collection_jobs_by_state()
+                        .await?
+                        .into_iter()
+                        .map(|(state, count)| (format!("{state:?}"), count))
+                        .collect(),
+                })
+            })
+        })
+        .await
+        .context("couldn't gather support bundle")
+}
+
+async fn fetch_datastore_keys(
+    kube_client: &LazyKubeClient,
+    namespace: &str,
+    secret_name: &str,
     secret_data_key: &str,
 ) -> Result<Vec<String>> {
     debug!(
@@ -232,489 +2164,1616 @@ async fn fetch_datastore_keys(
         secret_data_key, namespace, secret_name,
     );
 
-    let secrets_api: kube::Api<Secret> =
-        kube::Api::namespaced(kube_client.get().await?.clone(), namespace);
+    let secrets_api: kube::Api<Secret> =
+        kube::Api::namespaced(kube_client.get().await?.clone(), namespace);
+
+    let secret = secrets_api
+        .get(secret_name)
+        .await?
+        .data
+        .context(format!("no data on secret {secret_name}"))?;
+    let secret_value = secret.get(secret_data_key).context(format!(
+        "no data key {secret_data_key} on secret {secret_name}"
+    ))?;
+
+    Ok(String::from_utf8(secret_value.0.clone())?
+        .split(',')
+        .map(&str::to_string)
+        .collect())
+}
+
+async fn create_datastore_key(
+    dry_run: bool,
+    kube_client: &LazyKubeClient,
+    k8s_namespace: &str,
+    k8s_secret_name: &str,
+    k8s_secret_data_key: &str,
+) -> Result<()> {
+    info!(
+        namespace = k8s_namespace,
+        secret_name = k8s_secret_name,
+        secret_data_key = k8s_secret_data_key,
+        "Creating datastore key"
+    );
+    let secrets_api: kube::Api<Secret> =
+        kube::Api::namespaced(kube_client.get().await?.clone(), k8s_namespace);
+
+    // Generate a random datastore key & encode it into unpadded base64 as will be expected by
+    // consumers of the secret we are about to write.
+    let key_bytes: Vec<_> = thread_rng()
+        .sample_iter(Standard)
+        .take(AES_128_GCM.key_len())
+        .collect();
+    let secret_content = URL_SAFE_NO_PAD.encode(key_bytes);
+
+    // Write the secret.
+    secrets_api
+        .create(
+            &PostParams {
+                dry_run,
+                ..Default::default()
+            },
+            &Secret {
+                metadata: ObjectMeta {
+                    namespace: Some(k8s_namespace.to_string()),
+                    name: Some(k8s_secret_name.to_string()),
+                    ..ObjectMeta::default()
+                },
+                string_data: Some(BTreeMap::from([(
+                    k8s_secret_data_key.to_string(),
+                    secret_content,
+                )])),
+                ..Secret::default()
+            },
+        )
+        .await
+        .context("couldn't write datastore key secret")?;
+    Ok(())
+}
+
+async fn migrate(
+    db_config: &DbConfig,
+    db_password: Option<&str>,
+    migrations_path: &Path,
+    dry_run: bool,
+) -> Result<()> {
+    let target_version = *SUPPORTED_SCHEMA_VERSIONS.iter().max().unwrap();
+    let mut migrator = Migrator::new(migrations_path)
+        .await
+        .with_context(|| format!("couldn't read migrations from {migrations_path:?}"))?;
+    if !migrator
+        .iter()
+        .any(|migration| migration.version == target_version)
+    {
+        return Err(anyhow!(
+            "{migrations_path:?} has no migration to schema version {target_version}"
+        ));
+    }
+    migrator.migrations = migrator
+        .iter()
+        .filter(|migration| migration.version <= target_version)
+        .cloned()
+        .collect();
+
+    let mut connect_options = PgConnectOptions::from_str(db_config.url.as_str())
+        .context("couldn't parse database connect string")?;
+    if db_config.url.password().is_some() && db_password.is_some() {
+        return Err(anyhow!(
+            "database config & password override are both specified"
+        ));
+    }
+    if let Some(password) = db_password {
+        connect_options = connect_options.password(password);
+    }
+    if let Some(path) = &db_config.tls_trust_store_path {
+        connect_options = connect_options
+            .ssl_mode(PgSslMode::VerifyFull)
+            .ssl_root_cert(path);
+    }
+    let mut connection: PgConnection = connect_options
+        .connect()
+        .await
+        .context("couldn't connect to database")?;
+
+    if dry_run {
+        // Don't create the migrations table if it doesn't exist yet: that would be a change.
+        let migrations_table_exists: bool =
+            sqlx::query_scalar("SELECT to_regclass('_sqlx_migrations') IS NOT NULL")
+                .fetch_one(&mut connection)
+                .await
+                .context("couldn't check for migrations table")?;
+        let applied_versions: HashSet<_> = if migrations_table_exists {
+            connection
+                .list_applied_migrations()
+                .await
+                .context("couldn't list applied migrations")?
+                .into_iter()
+                .map(|migration| migration.version)
+                .collect()
+        } else {
+            HashSet::new()
+        };
+        for migration in migrator.iter().filter(|migration| {
+            migration.migration_type.is_up_migration()
+                && !applied_versions.contains(&migration.version)
+        }) {
+            info!(
+                version = migration.version,
+                description = %migration.description,
+                "DRY RUN: not applying migration"
+            );
+        }
+        return Ok(());
+    }
+
+    migrator
+        .run(&mut connection)
+        .await
+        .context("couldn't apply migrations")?;
+    info!(schema_version = target_version, "Applied migrations");
+    Ok(())
+}
+
+async fn datastore_from_opts(
+    kubernetes_secret_options: &KubernetesSecretOptions,
+    command_line_options: &CommandLineOptions,
+    config_file: &ConfigFile,
+    kube_client: &LazyKubeClient,
+) -> Result<Datastore<RealClock>> {
+    let crypter = crypter_from_opts(
+        kubernetes_secret_options,
+        command_line_options,
+        config_file,
+        kube_client,
+    )
+    .await?;
+    datastore_from_crypter(command_line_options, config_file, crypter).await
+}
+
+/// Constructs a [`Crypter`] from the datastore keys provided in the options, or fetched from the
+/// configured secret store, unwrapping them first if datastore key encryption is configured.
+async fn crypter_from_opts(
+    kubernetes_secret_options: &KubernetesSecretOptions,
+    command_line_options: &CommandLineOptions,
+    config_file: &ConfigFile,
+    kube_client: &LazyKubeClient,
+) -> Result<Crypter> {
+    let datastore_keys = match &config_file.common_config().secrets {
+        Some(secrets_config) if secrets_config.datastore_keys.is_some() => Vec::new(),
+        _ => {
+            kubernetes_secret_options
+                .datastore_keys(&command_line_options.common_options, kube_client)
+                .await?
+        }
+    };
+    let (crypter, _) = datastore_crypter(config_file.common_config(), &datastore_keys).await?;
+    Ok(crypter)
+}
+
+/// Constructs a datastore backed by the primary database, which is the configured database
+/// unless a datastore migration has been cut over.
+async fn datastore_from_crypter(
+    command_line_options: &CommandLineOptions,
+    config_file: &ConfigFile,
+    crypter: Crypter,
+) -> Result<Datastore<RealClock>> {
+    let (primary_database, _) = migration_databases(
+        config_file.common_config(),
+        &command_line_options.common_options,
+    );
+    datastore_for_database(config_file, primary_database, crypter).await
+}
+
+async fn datastore_for_database(
+    config_file: &ConfigFile,
+    (db_config, db_password): (&DbConfig, Option<&str>),
+    crypter: Crypter,
+) -> Result<Datastore<RealClock>> {
+    let pool = database_pool(db_config, db_password).await?;
+
+    datastore_with_crypter(
+        pool,
+        RealClock::default(),
+        &meter("janus_aggregator"),
+        crypter,
+        db_config.check_schema_version,
+        config_file.common_config().max_transaction_retries,
+    )
+    .await
+}
+
+#[derive(Debug, Parser)]
+#[clap(
+    name = "janus_cli",
+    about = "Janus CLI tool",
+    rename_all = "kebab-case",
+    version = env!("CARGO_PKG_VERSION"),
+)]
+struct CommandLineOptions {
+    #[clap(subcommand)]
+    cmd: Command,
+
+    #[clap(flatten)]
+    common_options: CommonBinaryOptions,
+
+    /// Do not make permanent changes
+    ///
+    /// The tool will print out what it would do but will not make any real, permanent changes.
+    #[clap(long, default_value = "false")]
+    dry_run: bool,
+}
+
+#[derive(Debug, Parser)]
+struct KubernetesSecretOptions {
+    /// The Kubernetes namespace where secrets are stored
+    ///
+    /// Required if --datastore-keys is not set or if the command is `create-datastore-key`.
+    #[clap(long, env = "SECRETS_K8S_NAMESPACE", num_args = 1)]
+    secrets_k8s_namespace: Option<String>,
+
+    /// Kubernetes secret containing the datastore key(s)
+    #[clap(
+        long,
+        env = "DATASTORE_KEYS_SECRET_NAME",
+        num_args = 1,
+        default_value = "datastore-key"
+    )]
+    datastore_keys_secret_name: String,
+
+    /// Key into data of datastore key Kubernetes secret
+    #[clap(
+        long,
+        env = "DATASTORE_KEYS_SECRET_KEY",
+        num_args = 1,
+        default_value = "datastore_key"
+    )]
+    datastore_keys_secret_data_key: String,
+}
+
+impl KubernetesSecretOptions {
+    /// Fetch the datastore keys from the options. If --secrets-k8s-namespace is set, keys are fetched
+    /// from a secret therein. Otherwise, returns the keys provided to --datastore-keys. If neither was
+    /// set, returns an error.
+    async fn datastore_keys(
+        &self,
+        options: &CommonBinaryOptions,
+        kube_client: &LazyKubeClient,
+    ) -> Result<Vec<String>> {
+        if let Some(ref secrets_namespace) = &self.secrets_k8s_namespace {
+            fetch_datastore_keys(
+                kube_client,
+                secrets_namespace,
+                &self.datastore_keys_secret_name,
+                &self.datastore_keys_secret_data_key,
+            )
+            .await
+            .context("failed to fetch datastore key(s) from Kubernetes secret")
+        } else if !options.datastore_keys.is_empty() {
+            Ok(options.datastore_keys.clone())
+        } else {
+            Err(anyhow!(
+                "Either --datastore-keys or --secrets-k8s-namespace must be set"
+            ))
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+struct ConfigFile {
+    #[serde(flatten)]
+    common_config: CommonConfig,
+
+    /// Upper bounds on the measurement length of histogram and vector VDAFs of provisioned tasks.
+    #[serde(default)]
+    measurement_length_limits: MeasurementLengthLimitsConfig,
+}
+
+impl BinaryConfig for ConfigFile {
+    fn common_config(&self) -> &CommonConfig {
+        &self.common_config
+    }
+
+    fn common_config_mut(&mut self) -> &mut CommonConfig {
+        &mut self.common_config
+    }
+}
+
+/// A wrapper around [`kube::Client`] adding lazy initialization.
+struct LazyKubeClient {
+    lock: OnceLock<kube::Client>,
+}
+
+impl LazyKubeClient {
+    fn new() -> Self {
+        Self {
+            lock: OnceLock::new(),
+        }
+    }
+
+    /// Return a reference to a client, constructing a client from the default inferred
+    /// configuration if it has not been done yet. This will use the local kubeconfig file if
+    /// present, use in-cluster environment variables if present, or fail.
+    async fn get(&self) -> Result<&kube::Client> {
+        if let Some(client) = self.lock.get() {
+            return Ok(client);
+        }
+        let _ = self.lock.set(
+            kube::Client::try_default()
+                .await
+                .context("couldn't load Kubernetes configuration")?,
+        );
+        Ok(self.lock.get().unwrap())
+    }
+}
+
+impl From<kube::Client> for LazyKubeClient {
+    fn from(value: kube::Client) -> Self {
+        Self {
+            lock: OnceLock::from(value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        fetch_datastore_keys, CommandLineOptions, ConfigFile, ConfigSchemaBinary,
+        KubernetesSecretOptions,
+    };
+    use crate::{LazyKubeClient, URL_SAFE_NO_PAD};
+    use base64::Engine;
+    use clap::{CommandFactory, ValueEnum};
+    use janus_aggregator::{
+        binary_utils::CommonBinaryOptions,
+        config::test_util::{generate_db_config, generate_metrics_config, generate_trace_config},
+        config::{
+            default_max_transaction_retries, parse_config, CommonConfig, DbConfig,
+            MeasurementLengthLimitsConfig,
+        },
+        feature_flags::FeatureFlagsConfig,
+        sharding::{ShardConfig, ShardMap, ShardingConfig},
+    };
+    use janus_aggregator_core::{
+        datastore::{
+            self,
+            models::{
+                AggregationJob, AggregationJobState, BatchAggregation, BatchAggregationState,
+                FeatureFlag, LeaderStoredReport, TaskUploadCounter, UploadSample,
+            },
+            test_util::{ephemeral_datastore, EphemeralDatastoreBuilder},
+            Datastore, SUPPORTED_SCHEMA_VERSIONS,
+        },
+        task::{test_util::TaskBuilder, AggregatorTask, QueryType, TaskState},
+        SecretBytes,
+    };
+    use janus_core::{
+        auth_tokens::{AuthenticationToken, AuthenticationTokenHash},
+        hpke::HpkeCiphersuite,
+        test_util::{kubernetes, roundtrip_encoding},
+        time::{Clock, DurationExt, RealClock, TimeExt},
+        vdaf::{MeasurementLengthLimits, VdafInstance},
+    };
+    use janus_messages::{
+        codec::Decode, query_type::TimeInterval, AggregationJobStep, Duration, HpkeAeadId,
+        HpkeConfig, HpkeKdfId, HpkeKemId, Interval, ReportId, ReportIdChecksum, Role, TaskId, Time,
+    };
+    use prio::vdaf::dummy;
+    use rand::random;
+    use ring::aead::{UnboundKey, AES_128_GCM};
+    use std::{
+        collections::{BTreeMap, HashMap},
+        io::Write,
+        iter,
+        net::{Ipv4Addr, SocketAddr},
+        path::Path,
+    };
+    use tempfile::NamedTempFile;
+    use url::Url;
+
+    #[test]
+    fn verify_app() {
+        CommandLineOptions::command().debug_assert()
+    }
+
+    #[tokio::test]
+    async fn options_datastore_keys() {
+        // Prep: create a Kubernetes cluster and put a secret in it
+        let k8s_cluster = kubernetes::EphemeralCluster::create();
+        let kube_client = k8s_cluster.cluster().client().await.into();
+        super::create_datastore_key(
+            false,
+            &kube_client,
+            "default",
+            "secret-name",
+            "secret-data-key",
+        )
+        .await
+        .unwrap();
+
+        let expected_datastore_keys =
+            Vec::from(["datastore-key-1".to_string(), "datastore-key-2".to_string()]);
+
+        // Keys provided at command line, not present in k8s
+        let common_options = CommonBinaryOptions {
+            datastore_keys: expected_datastore_keys.clone(),
+            ..Default::default()
+        };
+
+        let kubernetes_secret_options = KubernetesSecretOptions {
+            datastore_keys_secret_name: "secret-name".to_string(),
+            datastore_keys_secret_data_key: "secret-data-key".to_string(),
+            secrets_k8s_namespace: None,
+        };
+        let empty_kube_client = LazyKubeClient::new();
+
+        assert_eq!(
+            kubernetes_secret_options
+                .datastore_keys(&common_options, &empty_kube_client)
+                .await
+                .unwrap(),
+            expected_datastore_keys
+        );
+        // Shouldn't have set up a kube Client for this, since no namespace was given.
+        assert!(empty_kube_client.lock.get().is_none());
+
+        // Keys not provided at command line, present in k8s
+        let common_options = CommonBinaryOptions::default();
+        let kubernetes_secret_options = KubernetesSecretOptions {
+            datastore_keys_secret_name: "secret-name".to_string(),
+            datastore_keys_secret_data_key: "secret-data-key".to_string(),
+            secrets_k8s_namespace: Some("default".to_string()),
+        };
+
+        assert_eq!(
+            kubernetes_secret_options
+                .datastore_keys(&common_options, &kube_client)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+
+        // Neither flag provided
+        let common_options = CommonBinaryOptions::default();
+        let kubernetes_secret_options = KubernetesSecretOptions {
+            datastore_keys_secret_name: "secret-name".to_string(),
+            datastore_keys_secret_data_key: "secret-data-key".to_string(),
+            secrets_k8s_namespace: None,
+        };
+
+        kubernetes_secret_options
+            .datastore_keys(&common_options, &kube_client)
+            .await
+            .unwrap_err();
+    }
+
+    fn task_hashmap_from_slice(tasks: Vec<AggregatorTask>) -> HashMap<TaskId, AggregatorTask> {
+        tasks.into_iter().map(|task| (*task.id(), task)).collect()
+    }
+
+    async fn get_tasks(ds: &Datastore<RealClock>) -> HashMap<TaskId, AggregatorTask> {
+        task_hashmap_from_slice(
+            ds.run_unnamed_tx(|tx| Box::pin(async move { tx.get_aggregator_tasks().await }))
+                .await
+                .unwrap(),
+        )
+    }
+
+    async fn run_provision_tasks_testcase(
+        ds: &Datastore<RealClock>,
+        tasks: &[AggregatorTask],
+        dry_run: bool,
+    ) -> Vec<AggregatorTask> {
+        // Write tasks to a temporary file.
+        let mut tasks_file = NamedTempFile::new().unwrap();
+        tasks_file
+            .write_all(serde_yaml::to_string(&tasks).unwrap().as_ref())
+            .unwrap();
+        let tasks_path = tasks_file.into_temp_path();
+
+        // Run the program logic.
+        super::provision_tasks(
+            ds,
+            &tasks_path,
+            false,
+            &MeasurementLengthLimits::default(),
+            None,
+            dry_run,
+        )
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn provision_tasks() {
+        let ephemeral_datastore = ephemeral_datastore().await;
+        let ds = ephemeral_datastore.datastore(RealClock::default()).await;
+
+        let tasks = Vec::from([
+            TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Prio3Count)
+                .with_min_batch_size(1)
+                .build()
+                .leader_view()
+                .unwrap(),
+            TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Prio3Sum { bits: 64 })
+                .with_min_batch_size(1)
+                .build()
+                .helper_view()
+                .unwrap(),
+        ]);
+
+        let written_tasks = run_provision_tasks_testcase(&ds, &tasks, false).await;
+
+        // Verify that the expected tasks were written.
+        let want_tasks = task_hashmap_from_slice(tasks);
+        let written_tasks = task_hashmap_from_slice(written_tasks);
+        let got_tasks = task_hashmap_from_slice(
+            ds.run_unnamed_tx(|tx| Box::pin(async move { tx.get_aggregator_tasks().await }))
+                .await
+                .unwrap(),
+        );
+        assert_eq!(want_tasks, got_tasks);
+        assert_eq!(want_tasks, written_tasks);
+    }
+
+    #[tokio::test]
+    async fn promote_deployment() {
+        let ephemeral_datastore = ephemeral_datastore().await;
+        let ds = ephemeral_datastore.datastore(RealClock::default()).await;
+        let get_active_deployment =
+            || ds.run_unnamed_tx(|tx| Box::pin(async move { tx.get_active_deployment().await }));
+
+        // A dry run doesn't promote the deployment.
+        super::promote_deployment(&ds, "east", true).await.unwrap();
+        assert_eq!(get_active_deployment().await.unwrap(), None);
+
+        super::promote_deployment(&ds, "east", false).await.unwrap();
+        let active_deployment = get_active_deployment().await.unwrap().unwrap();
+        assert_eq!(active_deployment.deployment(), "east");
+        assert_eq!(active_deployment.epoch(), 1);
+
+        // Failing over to the standby deployment advances the epoch.
+        super::promote_deployment(&ds, "west", false).await.unwrap();
+        let active_deployment = get_active_deployment().await.unwrap().unwrap();
+        assert_eq!(active_deployment.deployment(), "west");
+        assert_eq!(active_deployment.epoch(), 2);
+    }
+
+    #[tokio::test]
+    async fn provision_task_dry_run() {
+        let ephemeral_datastore = ephemeral_datastore().await;
+        let ds = ephemeral_datastore.datastore(RealClock::default()).await;
+
+        let tasks =
+            Vec::from([
+                TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Prio3Count)
+                    .with_min_batch_size(1)
+                    .build()
+                    .leader_view()
+                    .unwrap(),
+            ]);
+
+        let written_tasks = run_provision_tasks_testcase(&ds, &tasks, true).await;
+
+        let want_tasks = task_hashmap_from_slice(tasks);
+        let written_tasks = task_hashmap_from_slice(written_tasks);
+        assert_eq!(want_tasks, written_tasks);
+        let got_tasks = task_hashmap_from_slice(
+            ds.run_unnamed_tx(|tx| Box::pin(async move { tx.get_aggregator_tasks().await }))
+                .await
+                .unwrap(),
+        );
+        assert!(got_tasks.is_empty());
+    }
+
+    #[tokio::test]
+    async fn provision_task_measurement_too_long() {
+        let ephemeral_datastore = ephemeral_datastore().await;
+        let ds = ephemeral_datastore.datastore(RealClock::default()).await;
+
+        let tasks = Vec::from([
+            TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Prio3Count)
+                .with_min_batch_size(1)
+                .build()
+                .leader_view()
+                .unwrap(),
+            TaskBuilder::new(
+                QueryType::TimeInterval,
+                VdafInstance::Prio3Histogram {
+                    length: 101,
+                    chunk_length: 10,
+                },
+            )
+            .with_min_batch_size(1)
+            .build()
+            .leader_view()
+            .unwrap(),
+        ]);
+        let mut tasks_file = NamedTempFile::new().unwrap();
+        tasks_file
+            .write_all(serde_yaml::to_string(&tasks).unwrap().as_ref())
+            .unwrap();
+
+        super::provision_tasks(
+            &ds,
+            &tasks_file.into_temp_path(),
+            false,
+            &MeasurementLengthLimits {
+                max_histogram_length: Some(100),
+                max_vector_length: None,
+            },
+            None,
+            false,
+        )
+        .await
+        .unwrap_err();
 
-    let secret = secrets_api
-        .get(secret_name)
-        .await?
-        .data
-        .context(format!("no data on secret {secret_name}"))?;
-    let secret_value = secret.get(secret_data_key).context(format!(
-        "no data key {secret_data_key} on secret {secret_name}"
-    ))?;
+        // No tasks are written if any task is rejected.
+        assert!(get_tasks(&ds).await.is_empty());
+    }
 
-    Ok(String::from_utf8(secret_value.0.clone())?
-        .split(',')
-        .map(&str::to_string)
-        .collect())
-}
+    #[tokio::test]
+    async fn replace_task() {
+        let tasks = Vec::from([
+            TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Prio3Count)
+                .with_min_batch_size(1)
+                .build()
+                .leader_view()
+                .unwrap(),
+            TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Prio3Sum { bits: 64 })
+                .with_min_batch_size(1)
+                .build()
+                .leader_view()
+                .unwrap(),
+        ]);
 
-async fn create_datastore_key(
-    dry_run: bool,
-    kube_client: &LazyKubeClient,
-    k8s_namespace: &str,
-    k8s_secret_name: &str,
-    k8s_secret_data_key: &str,
-) -> Result<()> {
-    info!(
-        namespace = k8s_namespace,
-        secret_name = k8s_secret_name,
-        secret_data_key = k8s_secret_data_key,
-        "Creating datastore key"
-    );
-    let secrets_api: kube::Api<Secret> =
-        kube::Api::namespaced(kube_client.get().await?.clone(), k8s_namespace);
+        let ephemeral_datastore = ephemeral_datastore().await;
+        let ds = ephemeral_datastore.datastore(RealClock::default()).await;
 
-    // Generate a random datastore key & encode it into unpadded base64 as will be expected by
-    // consumers of the secret we are about to write.
-    let key_bytes: Vec<_> = thread_rng()
-        .sample_iter(Standard)
-        .take(AES_128_GCM.key_len())
-        .collect();
-    let secret_content = URL_SAFE_NO_PAD.encode(key_bytes);
+        let mut tasks_file = NamedTempFile::new().unwrap();
+        tasks_file
+            .write_all(serde_yaml::to_string(&tasks).unwrap().as_ref())
+            .unwrap();
 
-    // Write the secret.
-    secrets_api
-        .create(
-            &PostParams {
-                dry_run,
-                ..Default::default()
+        super::provision_tasks(
+            &ds,
+            &tasks_file.into_temp_path(),
+            false,
+            &MeasurementLengthLimits::default(),
+            None,
+            false,
+        )
+        .await
+        .unwrap();
+
+        // Construct a "new" task with a previously existing ID.
+        let replacement_task = TaskBuilder::new(
+            QueryType::FixedSize {
+                max_batch_size: Some(100),
+                batch_time_window_size: None,
             },
-            &Secret {
-                metadata: ObjectMeta {
-                    namespace: Some(k8s_namespace.to_string()),
-                    name: Some(k8s_secret_name.to_string()),
-                    ..ObjectMeta::default()
-                },
-                string_data: Some(BTreeMap::from([(
-                    k8s_secret_data_key.to_string(),
-                    secret_content,
-                )])),
-                ..Secret::default()
+            VdafInstance::Prio3SumVec {
+                bits: 1,
+                length: 4,
+                chunk_length: 2,
             },
         )
+        .with_id(*tasks[0].id())
+        .with_min_batch_size(1)
+        .build()
+        .leader_view()
+        .unwrap();
+
+        let mut replacement_tasks_file = NamedTempFile::new().unwrap();
+        replacement_tasks_file
+            .write_all(
+                serde_yaml::to_string(&[&replacement_task])
+                    .unwrap()
+                    .as_ref(),
+            )
+            .unwrap();
+
+        let written_tasks = super::provision_tasks(
+            &ds,
+            &replacement_tasks_file.into_temp_path(),
+            false,
+            &MeasurementLengthLimits::default(),
+            None,
+            false,
+        )
         .await
-        .context("couldn't write datastore key secret")?;
-    Ok(())
-}
+        .unwrap();
+        assert_eq!(written_tasks.len(), 1);
+        assert_eq!(written_tasks[0].id(), tasks[0].id());
 
-async fn datastore_from_opts(
-    kubernetes_secret_options: &KubernetesSecretOptions,
-    command_line_options: &CommandLineOptions,
-    config_file: &ConfigFile,
-    kube_client: &LazyKubeClient,
-) -> Result<Datastore<RealClock>> {
-    let pool = database_pool(
-        &config_file.common_config.database,
-        command_line_options
-            .common_options
-            .database_password
-            .as_deref(),
-    )
-    .await?;
+        // Verify that the expected tasks were written.
+        let got_tasks = task_hashmap_from_slice(
+            ds.run_unnamed_tx(|tx| Box::pin(async move { tx.get_aggregator_tasks().await }))
+                .await
+                .unwrap(),
+        );
+        let want_tasks = HashMap::from([
+            (*replacement_task.id(), replacement_task),
+            (*tasks[1].id(), tasks[1].clone()),
+        ]);
 
-    datastore(
-        pool,
-        RealClock::default(),
-        &meter("janus_aggregator"),
-        &kubernetes_secret_options
-            .datastore_keys(&command_line_options.common_options, kube_client)
-            .await?,
-        config_file.common_config().database.check_schema_version,
-        config_file.common_config().max_transaction_retries,
-    )
-    .await
-}
+        assert_eq!(want_tasks, got_tasks);
+    }
 
-#[derive(Debug, Parser)]
-#[clap(
-    name = "janus_cli",
-    about = "Janus CLI tool",
-    rename_all = "kebab-case",
-    version = env!("CARGO_PKG_VERSION"),
-)]
-struct CommandLineOptions {
-    #[clap(subcommand)]
-    cmd: Command,
+    #[tokio::test]
+    async fn rebalance_tasks() {
+        let local_ephemeral_datastore = ephemeral_datastore().await;
+        let local_ds = local_ephemeral_datastore
+            .datastore(RealClock::default())
+            .await;
+        let remote_ephemeral_datastore = ephemeral_datastore().await;
+        let remote_ds = remote_ephemeral_datastore
+            .datastore(RealClock::default())
+            .await;
 
-    #[clap(flatten)]
-    common_options: CommonBinaryOptions,
+        let tasks = Vec::from([
+            TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Prio3Count)
+                .with_min_batch_size(1)
+                .build()
+                .leader_view()
+                .unwrap(),
+            TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Prio3Sum { bits: 64 })
+                .with_min_batch_size(1)
+                .build()
+                .leader_view()
+                .unwrap(),
+        ]);
+        run_provision_tasks_testcase(&local_ds, &tasks, false).await;
+
+        // Pin the first task to the local shard, and the second to the remote shard.
+        let shard_map = ShardMap::new(ShardingConfig {
+            shards: Vec::from([
+                ShardConfig {
+                    name: "local".to_string(),
+                    url: "https://local.example.com/".parse().unwrap(),
+                    database: None,
+                },
+                ShardConfig {
+                    name: "remote".to_string(),
+                    url: "https://remote.example.com/".parse().unwrap(),
+                    database: None,
+                },
+            ]),
+            task_assignments: HashMap::from([
+                (*tasks[0].id(), "local".to_string()),
+                (*tasks[1].id(), "remote".to_string()),
+            ]),
+        })
+        .unwrap();
+        let shard_datastores = HashMap::from([("remote".to_string(), remote_ds)]);
+
+        // A dry run reports the move, but doesn't make it.
+        let moved_tasks = super::rebalance_tasks(
+            &local_ds,
+            &shard_map,
+            "local",
+            &shard_datastores,
+            true,
+            true,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            moved_tasks,
+            Vec::from([(*tasks[1].id(), "remote".to_string())])
+        );
+        assert!(get_tasks(&shard_datastores["remote"]).await.is_empty());
 
-    /// Do not make permanent changes
-    ///
-    /// The tool will print out what it would do but will not make any real, permanent changes.
-    #[clap(long, default_value = "false")]
-    dry_run: bool,
-}
+        let moved_tasks = super::rebalance_tasks(
+            &local_ds,
+            &shard_map,
+            "local",
+            &shard_datastores,
+            true,
+            false,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            moved_tasks,
+            Vec::from([(*tasks[1].id(), "remote".to_string())])
+        );
+        assert_eq!(
+            get_tasks(&local_ds).await,
+            HashMap::from([(*tasks[0].id(), tasks[0].clone())])
+        );
+        assert_eq!(
+            get_tasks(&shard_datastores["remote"]).await,
+            HashMap::from([(*tasks[1].id(), tasks[1].clone())])
+        );
 
-#[derive(Debug, Parser)]
-struct KubernetesSecretOptions {
-    /// The Kubernetes namespace where secrets are stored
-    ///
-    /// Required if --datastore-keys is not set or if the command is `create-datastore-key`.
-    #[clap(long, env = "SECRETS_K8S_NAMESPACE", num_args = 1)]
-    secrets_k8s_namespace: Option<String>,
+        // Unknown local shards are rejected.
+        super::rebalance_tasks(
+            &local_ds,
+            &shard_map,
+            "other",
+            &shard_datastores,
+            true,
+            false,
+        )
+        .await
+        .unwrap_err();
+    }
 
-    /// Kubernetes secret containing the datastore key(s)
-    #[clap(
-        long,
-        env = "DATASTORE_KEYS_SECRET_NAME",
-        num_args = 1,
-        default_value = "datastore-key"
-    )]
-    datastore_keys_secret_name: String,
+    #[tokio::test]
+    async fn fsck() {
+        let ephemeral_datastore = ephemeral_datastore().await;
+        let ds = ephemeral_datastore.datastore(RealClock::default()).await;
 
-    /// Key into data of datastore key Kubernetes secret
-    #[clap(
-        long,
-        env = "DATASTORE_KEYS_SECRET_KEY",
-        num_args = 1,
-        default_value = "datastore_key"
-    )]
-    datastore_keys_secret_data_key: String,
-}
+        let task = TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Fake)
+            .with_min_batch_size(1)
+            .build()
+            .leader_view()
+            .unwrap();
+        run_provision_tasks_testcase(&ds, &[task.clone()], false).await;
+        assert!(super::fsck(&ds, true, false).await.unwrap().is_clean());
+
+        // Write a batch aggregation which has terminated more aggregation jobs than it created.
+        ds.run_unnamed_tx(|tx| {
+            let task_id = *task.id();
+            Box::pin(async move {
+                let batch_interval = Interval::new(
+                    Time::from_seconds_since_epoch(0),
+                    Duration::from_seconds(100),
+                )
+                .unwrap();
+                tx.put_batch_aggregation(&BatchAggregation::<0, TimeInterval, dummy::Vdaf>::new(
+                    task_id,
+                    batch_interval,
+                    dummy::AggregationParam(0),
+                    0,
+                    batch_interval,
+                    BatchAggregationState::Aggregating {
+                        aggregate_share: None,
+                        report_count: 0,
+                        checksum: ReportIdChecksum::default(),
+                        aggregation_jobs_created: 1,
+                        aggregation_jobs_terminated: 2,
+                    },
+                ))
+                .await
+            })
+        })
+        .await
+        .unwrap();
+        let want_report = super::FsckReport {
+            batch_aggregations_with_impossible_counts: 1,
+            ..Default::default()
+        };
+
+        // Inconsistencies are left in place unless a repair is requested outside of a dry run.
+        assert_eq!(super::fsck(&ds, false, false).await.unwrap(), want_report);
+        assert_eq!(super::fsck(&ds, true, true).await.unwrap(), want_report);
+        assert_eq!(super::fsck(&ds, true, false).await.unwrap(), want_report);
+        assert!(super::fsck(&ds, false, false).await.unwrap().is_clean());
+    }
+
+    #[tokio::test]
+    async fn verify_datastore_migration() {
+        let primary_ephemeral_datastore = ephemeral_datastore().await;
+        let primary_ds = primary_ephemeral_datastore
+            .datastore(RealClock::default())
+            .await;
+        let secondary_ephemeral_datastore = ephemeral_datastore().await;
+        let secondary_ds = secondary_ephemeral_datastore
+            .datastore(RealClock::default())
+            .await;
+
+        let task = TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Fake)
+            .with_min_batch_size(1)
+            .build()
+            .leader_view()
+            .unwrap();
+        run_provision_tasks_testcase(&primary_ds, &[task.clone()], false).await;
+        run_provision_tasks_testcase(&secondary_ds, &[task.clone()], false).await;
+        assert!(
+            super::verify_datastore_migration(&primary_ds, &secondary_ds)
+                .await
+                .unwrap()
+        );
 
-impl KubernetesSecretOptions {
-    /// Fetch the datastore keys from the options. If --secrets-k8s-namespace is set, keys are fetched
-    /// from a secret therein. Otherwise, returns the keys provided to --datastore-keys. If neither was
-    /// set, returns an error.
-    async fn datastore_keys(
-        &self,
-        options: &CommonBinaryOptions,
-        kube_client: &LazyKubeClient,
-    ) -> Result<Vec<String>> {
-        if let Some(ref secrets_namespace) = &self.secrets_k8s_namespace {
-            fetch_datastore_keys(
-                kube_client,
-                secrets_namespace,
-                &self.datastore_keys_secret_name,
-                &self.datastore_keys_secret_data_key,
-            )
+        async fn put_report(
+            ds: &Datastore<RealClock>,
+            report: LeaderStoredReport<0, dummy::Vdaf>,
+        ) -> Result<(), datastore::Error> {
+            ds.run_unnamed_tx(|tx| {
+                let report = report.clone();
+                Box::pin(async move {
+                    tx.put_client_report(&dummy::Vdaf::default(), &report)
+                        .await
+                        .map(|_| ())
+                })
+            })
             .await
-            .context("failed to fetch datastore key(s) from Kubernetes secret")
-        } else if !options.datastore_keys.is_empty() {
-            Ok(options.datastore_keys.clone())
-        } else {
-            Err(anyhow!(
-                "Either --datastore-keys or --secrets-k8s-namespace must be set"
-            ))
         }
-    }
-}
 
-#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
-struct ConfigFile {
-    #[serde(flatten)]
-    common_config: CommonConfig,
-}
+        // A report written to only one datastore is detected.
+        let report = LeaderStoredReport::new_dummy(*task.id(), RealClock::default().now());
+        put_report(&primary_ds, report.clone()).await.unwrap();
+        assert!(
+            !super::verify_datastore_migration(&primary_ds, &secondary_ds)
+                .await
+                .unwrap()
+        );
 
-impl BinaryConfig for ConfigFile {
-    fn common_config(&self) -> &CommonConfig {
-        &self.common_config
-    }
+        put_report(&secondary_ds, report).await.unwrap();
+        assert!(
+            super::verify_datastore_migration(&primary_ds, &secondary_ds)
+                .await
+                .unwrap()
+        );
 
-    fn common_config_mut(&mut self) -> &mut CommonConfig {
-        &mut self.common_config
+        // Different reports for the same task are detected, even though the row counts match.
+        put_report(
+            &primary_ds,
+            LeaderStoredReport::new_dummy(*task.id(), RealClock::default().now()),
+        )
+        .await
+        .unwrap();
+        put_report(
+            &secondary_ds,
+            LeaderStoredReport::new_dummy(*task.id(), RealClock::default().now()),
+        )
+        .await
+        .unwrap();
+        assert!(
+            !super::verify_datastore_migration(&primary_ds, &secondary_ds)
+                .await
+                .unwrap()
+        );
     }
-}
 
-/// A wrapper around [`kube::Client`] adding lazy initialization.
-struct LazyKubeClient {
-    lock: OnceLock<kube::Client>,
-}
+    #[tokio::test]
+    async fn maintenance_leases() {
+        let ephemeral_datastore = ephemeral_datastore().await;
+        let ds = ephemeral_datastore.datastore(RealClock::default()).await;
 
-impl LazyKubeClient {
-    fn new() -> Self {
-        Self {
-            lock: OnceLock::new(),
-        }
+        // Only one maintenance command may hold the leases at a time.
+        let leases = super::MaintenanceLeases::acquire(&ds, &["conflicting_job"])
+            .await
+            .unwrap();
+        assert!(super::MaintenanceLeases::acquire(&ds, &[]).await.is_err());
+        leases.release().await;
+        super::MaintenanceLeases::acquire(&ds, &[])
+            .await
+            .unwrap()
+            .release()
+            .await;
+
+        // Commands don't run while a conflicting background job holds its lease, and acquire none
+        // of the leases in that case.
+        ds.run_unnamed_tx(|tx| {
+            Box::pin(async move {
+                tx.try_acquire_leader_lease(
+                    "conflicting_job",
+                    "replica",
+                    &std::time::Duration::from_secs(60),
+                )
+                .await
+            })
+        })
+        .await
+        .unwrap();
+        assert!(super::MaintenanceLeases::acquire(&ds, &["conflicting_job"])
+            .await
+            .is_err());
+        super::MaintenanceLeases::acquire(&ds, &[])
+            .await
+            .unwrap()
+            .release()
+            .await;
     }
 
-    /// Return a reference to a client, constructing a client from the default inferred
-    /// configuration if it has not been done yet. This will use the local kubeconfig file if
-    /// present, use in-cluster environment variables if present, or fail.
-    async fn get(&self) -> Result<&kube::Client> {
-        if let Some(client) = self.lock.get() {
-            return Ok(client);
-        }
-        let _ = self.lock.set(
-            kube::Client::try_default()
+    #[tokio::test]
+    async fn migrate() {
+        let ephemeral_datastore = EphemeralDatastoreBuilder::new()
+            .with_schema_version(1)
+            .build()
+            .await;
+        let db_config = DbConfig {
+            url: Url::parse(ephemeral_datastore.connection_string()).unwrap(),
+            ..generate_db_config()
+        };
+        let migrations_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../db");
+
+        let schema_version = || async {
+            ephemeral_datastore
+                .pool()
+                .get()
                 .await
-                .context("couldn't load Kubernetes configuration")?,
+                .unwrap()
+                .query_one(
+                    "SELECT MAX(version) FROM _sqlx_migrations WHERE success",
+                    &[],
+                )
+                .await
+                .unwrap()
+                .get::<_, i64>(0)
+        };
+
+        // Dry runs leave the schema unchanged.
+        super::migrate(&db_config, None, &migrations_path, true)
+            .await
+            .unwrap();
+        assert_eq!(schema_version().await, 1);
+
+        super::migrate(&db_config, None, &migrations_path, false)
+            .await
+            .unwrap();
+        assert_eq!(
+            schema_version().await,
+            *SUPPORTED_SCHEMA_VERSIONS.iter().max().unwrap()
         );
-        Ok(self.lock.get().unwrap())
-    }
-}
 
-impl From<kube::Client> for LazyKubeClient {
-    fn from(value: kube::Client) -> Self {
-        Self {
-            lock: OnceLock::from(value),
-        }
+        // Migrating an up-to-date database is a no-op.
+        super::migrate(&db_config, None, &migrations_path, false)
+            .await
+            .unwrap();
+        ephemeral_datastore.datastore(RealClock::default()).await;
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::{fetch_datastore_keys, CommandLineOptions, ConfigFile, KubernetesSecretOptions};
-    use crate::{LazyKubeClient, URL_SAFE_NO_PAD};
-    use base64::Engine;
-    use clap::CommandFactory;
-    use janus_aggregator::{
-        binary_utils::CommonBinaryOptions,
-        config::test_util::{generate_db_config, generate_metrics_config, generate_trace_config},
-        config::{default_max_transaction_retries, CommonConfig},
-    };
-    use janus_aggregator_core::{
-        datastore::{test_util::ephemeral_datastore, Datastore},
-        task::{test_util::TaskBuilder, AggregatorTask, QueryType},
-    };
-    use janus_core::{
-        test_util::{kubernetes, roundtrip_encoding},
-        time::RealClock,
-        vdaf::VdafInstance,
-    };
-    use janus_messages::{Role, TaskId};
-    use ring::aead::{UnboundKey, AES_128_GCM};
-    use std::{
-        collections::HashMap,
-        io::Write,
-        net::{Ipv4Addr, SocketAddr},
-    };
-    use tempfile::NamedTempFile;
+    #[tokio::test]
+    async fn list_upload_samples() {
+        let ephemeral_datastore = ephemeral_datastore().await;
+        let ds = ephemeral_datastore.datastore(RealClock::default()).await;
 
-    #[test]
-    fn verify_app() {
-        CommandLineOptions::command().debug_assert()
+        let task = TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Fake)
+            .with_min_batch_size(1)
+            .build()
+            .leader_view()
+            .unwrap();
+        run_provision_tasks_testcase(&ds, &[task.clone()], false).await;
+
+        let report_id: ReportId = random();
+        ds.run_unnamed_tx(|tx| {
+            let task_id = *task.id();
+            Box::pin(async move {
+                tx.put_upload_sample(
+                    &UploadSample::new(
+                        task_id,
+                        report_id,
+                        Time::from_seconds_since_epoch(990),
+                        Time::from_seconds_since_epoch(1000),
+                        Vec::from([0xFF00]),
+                        32,
+                        120,
+                        140,
+                    ),
+                    &Duration::from_seconds(3600),
+                )
+                .await
+            })
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(
+            super::list_upload_samples(&ds, task.id()).await.unwrap(),
+            Vec::from([super::UploadSampleView {
+                report_id: report_id.to_string(),
+                client_timestamp: Time::from_seconds_since_epoch(990),
+                received_at: Time::from_seconds_since_epoch(1000),
+                timestamp_skew_seconds: 10,
+                extension_types: Vec::from([0xFF00]),
+                public_share_size: 32,
+                leader_encrypted_input_share_size: 120,
+                helper_encrypted_input_share_size: 140,
+            }])
+        );
+
+        // Unknown tasks are rejected.
+        super::list_upload_samples(&ds, &random())
+            .await
+            .unwrap_err();
     }
 
     #[tokio::test]
-    async fn options_datastore_keys() {
-        // Prep: create a Kubernetes cluster and put a secret in it
-        let k8s_cluster = kubernetes::EphemeralCluster::create();
-        let kube_client = k8s_cluster.cluster().client().await.into();
-        super::create_datastore_key(
+    async fn feature_flags() {
+        let ephemeral_datastore = ephemeral_datastore().await;
+        let ds = ephemeral_datastore.datastore(RealClock::default()).await;
+
+        let task = TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Fake)
+            .with_min_batch_size(1)
+            .build()
+            .leader_view()
+            .unwrap();
+        run_provision_tasks_testcase(&ds, &[task.clone()], false).await;
+
+        // Dry runs make no changes.
+        super::set_feature_flag(&ds, FeatureFlag::new("flag".to_string(), None, true), true)
+            .await
+            .unwrap();
+        assert!(super::list_feature_flags(&ds).await.unwrap().is_empty());
+
+        super::set_feature_flag(&ds, FeatureFlag::new("flag".to_string(), None, true), false)
+            .await
+            .unwrap();
+        super::set_feature_flag(
+            &ds,
+            FeatureFlag::new("flag".to_string(), Some(*task.id()), false),
             false,
-            &kube_client,
-            "default",
-            "secret-name",
-            "secret-data-key",
         )
         .await
         .unwrap();
+        assert_eq!(
+            super::list_feature_flags(&ds).await.unwrap(),
+            Vec::from([
+                super::FeatureFlagView {
+                    name: "flag".to_string(),
+                    task_id: None,
+                    enabled: true,
+                },
+                super::FeatureFlagView {
+                    name: "flag".to_string(),
+                    task_id: Some(*task.id()),
+                    enabled: false,
+                },
+            ])
+        );
 
-        let expected_datastore_keys =
-            Vec::from(["datastore-key-1".to_string(), "datastore-key-2".to_string()]);
+        // Flags cannot be set for unknown tasks.
+        super::set_feature_flag(
+            &ds,
+            FeatureFlag::new("flag".to_string(), Some(random()), true),
+            false,
+        )
+        .await
+        .unwrap_err();
 
-        // Keys provided at command line, not present in k8s
-        let common_options = CommonBinaryOptions {
-            datastore_keys: expected_datastore_keys.clone(),
-            ..Default::default()
-        };
+        super::delete_feature_flag(&ds, "flag", None, true)
+            .await
+            .unwrap();
+        super::delete_feature_flag(&ds, "flag", None, false)
+            .await
+            .unwrap();
+        assert_eq!(
+            super::list_feature_flags(&ds).await.unwrap(),
+            Vec::from([super::FeatureFlagView {
+                name: "flag".to_string(),
+                task_id: Some(*task.id()),
+                enabled: false,
+            }])
+        );
+        super::delete_feature_flag(&ds, "flag", None, false)
+            .await
+            .unwrap_err();
+    }
 
-        let kubernetes_secret_options = KubernetesSecretOptions {
-            datastore_keys_secret_name: "secret-name".to_string(),
-            datastore_keys_secret_data_key: "secret-data-key".to_string(),
-            secrets_k8s_namespace: None,
-        };
-        let empty_kube_client = LazyKubeClient::new();
+    #[tokio::test]
+    async fn admin_api_keys() {
+        let ephemeral_datastore = ephemeral_datastore().await;
+        let ds = ephemeral_datastore.datastore(RealClock::default()).await;
+
+        let task = TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Fake)
+            .with_min_batch_size(1)
+            .build()
+            .leader_view()
+            .unwrap();
+        run_provision_tasks_testcase(&ds, &[task.clone()], false).await;
+        let expires_in = Duration::from_seconds(3600);
 
+        // Dry runs make no changes, but still report the token.
+        let key = super::create_admin_api_key(&ds, "team", &[*task.id()], &expires_in, true)
+            .await
+            .unwrap();
+        assert!(key.token.is_some());
+        assert!(super::list_admin_api_keys(&ds).await.unwrap().is_empty());
+
+        let key = super::create_admin_api_key(&ds, "team", &[*task.id()], &expires_in, false)
+            .await
+            .unwrap();
+        let token = key.token.clone().unwrap();
+        let stored_key = ds
+            .run_unnamed_tx(|tx| {
+                let token = token.clone();
+                Box::pin(async move {
+                    tx.get_unexpired_admin_api_key(&AuthenticationTokenHash::from(
+                        &AuthenticationToken::new_bearer_token_from_string(token).unwrap(),
+                    ))
+                    .await
+                })
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored_key.name(), "team");
         assert_eq!(
-            kubernetes_secret_options
-                .datastore_keys(&common_options, &empty_kube_client)
-                .await
-                .unwrap(),
-            expected_datastore_keys
+            super::list_admin_api_keys(&ds).await.unwrap(),
+            Vec::from([super::AdminApiKeyView { token: None, ..key }])
         );
-        // Shouldn't have set up a kube Client for this, since no namespace was given.
-        assert!(empty_kube_client.lock.get().is_none());
 
-        // Keys not provided at command line, present in k8s
-        let common_options = CommonBinaryOptions::default();
-        let kubernetes_secret_options = KubernetesSecretOptions {
-            datastore_keys_secret_name: "secret-name".to_string(),
-            datastore_keys_secret_data_key: "secret-data-key".to_string(),
-            secrets_k8s_namespace: Some("default".to_string()),
-        };
+        // Names must be unique, and keys cannot be scoped to unknown tasks.
+        super::create_admin_api_key(&ds, "team", &[*task.id()], &expires_in, false)
+            .await
+            .unwrap_err();
+        super::create_admin_api_key(&ds, "other", &[random()], &expires_in, false)
+            .await
+            .unwrap_err();
+
+        super::delete_admin_api_key(&ds, "team", true)
+            .await
+            .unwrap();
+        assert_eq!(super::list_admin_api_keys(&ds).await.unwrap().len(), 1);
+        super::delete_admin_api_key(&ds, "team", false)
+            .await
+            .unwrap();
+        assert!(super::list_admin_api_keys(&ds).await.unwrap().is_empty());
+        super::delete_admin_api_key(&ds, "team", false)
+            .await
+            .unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn task_lifecycle() {
+        let ephemeral_datastore = ephemeral_datastore().await;
+        let ds = ephemeral_datastore.datastore(RealClock::default()).await;
+
+        let task = TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Fake)
+            .with_min_batch_size(1)
+            .build()
+            .leader_view()
+            .unwrap()
+            .with_state(TaskState::Provisioned);
+        run_provision_tasks_testcase(&ds, &[task.clone()], false).await;
 
         assert_eq!(
-            kubernetes_secret_options
-                .datastore_keys(&common_options, &kube_client)
-                .await
-                .unwrap()
-                .len(),
-            1
+            super::parse_task_state("Purging").unwrap(),
+            TaskState::Purging
         );
+        super::parse_task_state("bogus").unwrap_err();
 
-        // Neither flag provided
-        let common_options = CommonBinaryOptions::default();
-        let kubernetes_secret_options = KubernetesSecretOptions {
-            datastore_keys_secret_name: "secret-name".to_string(),
-            datastore_keys_secret_data_key: "secret-data-key".to_string(),
-            secrets_k8s_namespace: None,
-        };
-
-        kubernetes_secret_options
-            .datastore_keys(&common_options, &kube_client)
+        // Dry runs make no changes, but still check the transition.
+        super::set_task_state(&ds, task.id(), &TaskState::Active, true)
+            .await
+            .unwrap();
+        super::set_task_state(&ds, task.id(), &TaskState::Expired, true)
             .await
             .unwrap_err();
-    }
-
-    fn task_hashmap_from_slice(tasks: Vec<AggregatorTask>) -> HashMap<TaskId, AggregatorTask> {
-        tasks.into_iter().map(|task| (*task.id(), task)).collect()
-    }
+        assert_eq!(
+            get_tasks(&ds).await.get(task.id()).unwrap().state(),
+            &TaskState::Provisioned
+        );
 
-    async fn run_provision_tasks_testcase(
-        ds: &Datastore<RealClock>,
-        tasks: &[AggregatorTask],
-        dry_run: bool,
-    ) -> Vec<AggregatorTask> {
-        // Write tasks to a temporary file.
-        let mut tasks_file = NamedTempFile::new().unwrap();
-        tasks_file
-            .write_all(serde_yaml::to_string(&tasks).unwrap().as_ref())
+        super::set_task_state(&ds, task.id(), &TaskState::Active, false)
+            .await
             .unwrap();
-        let tasks_path = tasks_file.into_temp_path();
+        assert_eq!(
+            get_tasks(&ds).await.get(task.id()).unwrap().state(),
+            &TaskState::Active
+        );
 
-        // Run the program logic.
-        super::provision_tasks(ds, &tasks_path, false, dry_run)
+        // Invalid transitions and unknown tasks are rejected.
+        super::set_task_state(&ds, task.id(), &TaskState::Provisioned, false)
+            .await
+            .unwrap_err();
+        super::set_task_state(&ds, &random(), &TaskState::Active, false)
+            .await
+            .unwrap_err();
+
+        let events = super::list_task_lifecycle_events(&ds, Some(task.id()))
+            .await
+            .unwrap();
+        assert_eq!(
+            events
+                .iter()
+                .map(|event| (event.task_id, event.from_state, event.to_state))
+                .collect::<Vec<_>>(),
+            Vec::from([
+                (*task.id(), None, TaskState::Provisioned),
+                (*task.id(), Some(TaskState::Provisioned), TaskState::Active),
+            ])
+        );
+        assert!(super::list_task_lifecycle_events(&ds, Some(&random()))
             .await
             .unwrap()
+            .is_empty());
     }
 
     #[tokio::test]
-    async fn provision_tasks() {
+    async fn list_metrics_snapshots() {
         let ephemeral_datastore = ephemeral_datastore().await;
         let ds = ephemeral_datastore.datastore(RealClock::default()).await;
 
-        let tasks = Vec::from([
-            TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Prio3Count)
-                .build()
-                .leader_view()
-                .unwrap(),
-            TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Prio3Sum { bits: 64 })
-                .build()
-                .helper_view()
-                .unwrap(),
-        ]);
+        let task = TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Fake)
+            .with_min_batch_size(1)
+            .build()
+            .leader_view()
+            .unwrap();
+        run_provision_tasks_testcase(&ds, &[task.clone()], false).await;
 
-        let written_tasks = run_provision_tasks_testcase(&ds, &tasks, false).await;
+        let one_hour = Duration::from_hours(1).unwrap();
+        let first_snapshot_time = RealClock::default()
+            .now()
+            .to_batch_interval_start(&one_hour)
+            .unwrap()
+            .sub(&Duration::from_hours(2).unwrap())
+            .unwrap();
+        let second_snapshot_time = first_snapshot_time.add(&one_hour).unwrap();
+        for (snapshot_time, report_success) in [(first_snapshot_time, 3), (second_snapshot_time, 4)]
+        {
+            let task_id = *task.id();
+            ds.run_unnamed_tx(|tx| {
+                Box::pin(async move {
+                    tx.increment_task_upload_counter(
+                        &task_id,
+                        0,
+                        &TaskUploadCounter::new_with_values(0, 0, 0, 0, 0, report_success, 0, 0),
+                    )
+                    .await?;
+                    tx.put_task_metrics_snapshots(&snapshot_time).await
+                })
+            })
+            .await
+            .unwrap();
+        }
 
-        // Verify that the expected tasks were written.
-        let want_tasks = task_hashmap_from_slice(tasks);
-        let written_tasks = task_hashmap_from_slice(written_tasks);
-        let got_tasks = task_hashmap_from_slice(
-            ds.run_unnamed_tx(|tx| Box::pin(async move { tx.get_aggregator_tasks().await }))
+        assert_eq!(
+            super::list_metrics_snapshots(&ds, task.id(), 30)
                 .await
                 .unwrap(),
+            Vec::from([
+                super::MetricsSnapshotView {
+                    snapshot_time: first_snapshot_time,
+                    reports_accepted: 3,
+                    reports_aggregated: 0,
+                    reports_collected: 0,
+                    increase: None,
+                },
+                super::MetricsSnapshotView {
+                    snapshot_time: second_snapshot_time,
+                    reports_accepted: 7,
+                    reports_aggregated: 0,
+                    reports_collected: 0,
+                    increase: Some(super::MetricsIncreaseView {
+                        reports_accepted: 4,
+                        reports_aggregated: 0,
+                        reports_collected: 0,
+                    }),
+                },
+            ])
         );
-        assert_eq!(want_tasks, got_tasks);
-        assert_eq!(want_tasks, written_tasks);
+
+        // Snapshots from before the requested period are not listed.
+        assert!(super::list_metrics_snapshots(&ds, task.id(), 0)
+            .await
+            .unwrap()
+            .is_empty());
     }
 
     #[tokio::test]
-    async fn provision_task_dry_run() {
+    async fn verify_collector_key() {
         let ephemeral_datastore = ephemeral_datastore().await;
         let ds = ephemeral_datastore.datastore(RealClock::default()).await;
 
-        let tasks =
-            Vec::from([
-                TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Prio3Count)
-                    .build()
-                    .leader_view()
-                    .unwrap(),
-            ]);
+        let time_interval_task = TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Fake)
+            .with_min_batch_size(1)
+            .build();
+        let fixed_size_task = TaskBuilder::new(
+            QueryType::FixedSize {
+                max_batch_size: Some(10),
+                batch_time_window_size: None,
+            },
+            VdafInstance::Fake,
+        )
+        .with_min_batch_size(1)
+        .build();
+        run_provision_tasks_testcase(
+            &ds,
+            &[
+                time_interval_task.leader_view().unwrap(),
+                fixed_size_task.helper_view().unwrap(),
+            ],
+            false,
+        )
+        .await;
 
-        let written_tasks = run_provision_tasks_testcase(&ds, &tasks, true).await;
+        for task in [&time_interval_task, &fixed_size_task] {
+            super::verify_collector_key(
+                &ds,
+                task.id(),
+                task.collector_hpke_keypair().private_key(),
+            )
+            .await
+            .unwrap();
+        }
 
-        let want_tasks = task_hashmap_from_slice(tasks);
-        let written_tasks = task_hashmap_from_slice(written_tasks);
-        assert_eq!(want_tasks, written_tasks);
-        let got_tasks = task_hashmap_from_slice(
-            ds.run_unnamed_tx(|tx| Box::pin(async move { tx.get_aggregator_tasks().await }))
-                .await
-                .unwrap(),
-        );
-        assert!(got_tasks.is_empty());
+        // Another task's collector private key does not match.
+        super::verify_collector_key(
+            &ds,
+            time_interval_task.id(),
+            fixed_size_task.collector_hpke_keypair().private_key(),
+        )
+        .await
+        .unwrap_err();
+
+        // Unknown tasks are rejected.
+        super::verify_collector_key(
+            &ds,
+            &random(),
+            time_interval_task.collector_hpke_keypair().private_key(),
+        )
+        .await
+        .unwrap_err();
     }
 
     #[tokio::test]
-    async fn replace_task() {
-        let tasks = Vec::from([
-            TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Prio3Count)
-                .build()
-                .leader_view()
-                .unwrap(),
-            TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Prio3Sum { bits: 64 })
+    async fn rotate_hpke_keys() {
+        let ephemeral_datastore = ephemeral_datastore().await;
+        let ds = ephemeral_datastore.datastore(RealClock::default()).await;
+
+        // Each rotation needs a greater config ID than the task's existing ones.
+        let task = iter::repeat_with(|| {
+            TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Fake)
+                .with_min_batch_size(1)
                 .build()
                 .leader_view()
+                .unwrap()
+        })
+        .find(|task| {
+            task.hpke_keys()
+                .keys()
+                .all(|id| u8::from(*id) < u8::MAX - 1)
+        })
+        .unwrap();
+        run_provision_tasks_testcase(&ds, &[task.clone()], false).await;
+        let mut initial_configs: Vec<_> = task
+            .hpke_keys()
+            .values()
+            .map(|keypair| keypair.config().clone())
+            .collect();
+        initial_configs.sort_by_key(|config| *config.id());
+        let latest_config_id = u8::from(*initial_configs.last().unwrap().id());
+        let retire_after = Duration::from_hours(1).unwrap();
+
+        // Dry runs make no changes.
+        let config = super::rotate_task_hpke_keys(&ds, task.id(), &retire_after, true)
+            .await
+            .unwrap();
+        assert_eq!(u8::from(*config.config.id()), latest_config_id + 1);
+        let configs = super::list_task_hpke_keys(&ds, task.id()).await.unwrap();
+        assert_eq!(
+            configs
+                .iter()
+                .map(|config| (config.config.clone(), config.retire_at))
+                .collect::<Vec<_>>(),
+            initial_configs
+                .iter()
+                .map(|config| (config.clone(), None))
+                .collect::<Vec<_>>()
+        );
+
+        let config = super::rotate_task_hpke_keys(&ds, task.id(), &retire_after, false)
+            .await
+            .unwrap();
+        assert_eq!(u8::from(*config.config.id()), latest_config_id + 1);
+        assert_eq!(
+            HpkeConfig::get_decoded(&URL_SAFE_NO_PAD.decode(&config.encoded_config).unwrap())
                 .unwrap(),
-        ]);
+            config.config
+        );
+        let configs = super::list_task_hpke_keys(&ds, task.id()).await.unwrap();
+        assert_eq!(configs.len(), initial_configs.len() + 1);
+        let retire_at = configs[0].retire_at.unwrap();
+        assert!(retire_at.is_after(&RealClock::default().now()));
+        for (config, initial_config) in configs.iter().zip(&initial_configs) {
+            assert_eq!(&config.config, initial_config);
+            assert_eq!(config.retire_at, Some(retire_at));
+        }
+        assert_eq!(configs.last(), Some(&config));
 
-        let ephemeral_datastore = ephemeral_datastore().await;
-        let ds = ephemeral_datastore.datastore(RealClock::default()).await;
+        // The new keypair is advertised, and the old ones are still usable until they are retired.
+        let stored_task = get_tasks(&ds).await.remove(task.id()).unwrap();
+        assert_eq!(stored_task.hpke_keys().len(), initial_configs.len() + 1);
 
-        let mut tasks_file = NamedTempFile::new().unwrap();
-        tasks_file
-            .write_all(serde_yaml::to_string(&tasks).unwrap().as_ref())
+        // Rotating again retires the newest keypair, but doesn't postpone the older ones'
+        // retirement.
+        super::rotate_task_hpke_keys(&ds, task.id(), &retire_after, false)
+            .await
             .unwrap();
+        let configs = super::list_task_hpke_keys(&ds, task.id()).await.unwrap();
+        assert_eq!(configs.len(), initial_configs.len() + 2);
+        for config in &configs[..initial_configs.len()] {
+            assert_eq!(config.retire_at, Some(retire_at));
+        }
+        assert!(configs[initial_configs.len()].retire_at.is_some());
+        assert_eq!(configs.last().unwrap().retire_at, None);
 
-        super::provision_tasks(&ds, &tasks_file.into_temp_path(), false, false)
+        // Unknown tasks are rejected.
+        super::rotate_task_hpke_keys(&ds, &random(), &retire_after, false)
             .await
-            .unwrap();
+            .unwrap_err();
+    }
 
-        // Construct a "new" task with a previously existing ID.
-        let replacement_task = TaskBuilder::new(
-            QueryType::FixedSize {
-                max_batch_size: Some(100),
-                batch_time_window_size: None,
-            },
-            VdafInstance::Prio3SumVec {
-                bits: 1,
-                length: 4,
-                chunk_length: 2,
-            },
-        )
-        .with_id(*tasks[0].id())
-        .build()
-        .leader_view()
-        .unwrap();
+    #[tokio::test]
+    async fn support_bundle() {
+        let ephemeral_datastore = ephemeral_datastore().await;
+        let ds = ephemeral_datastore.datastore(RealClock::default()).await;
 
-        let mut replacement_tasks_file = NamedTempFile::new().unwrap();
-        replacement_tasks_file
-            .write_all(
-                serde_yaml::to_string(&[&replacement_task])
-                    .unwrap()
-                    .as_ref(),
-            )
+        let task = TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Fake)
+            .with_min_batch_size(1)
+            .build()
+            .leader_view()
             .unwrap();
+        run_provision_tasks_testcase(&ds, &[task.clone()], false).await;
 
-        let written_tasks =
-            super::provision_tasks(&ds, &replacement_tasks_file.into_temp_path(), false, false)
+        ds.run_unnamed_tx(|tx| {
+            let task_id = *task.id();
+            Box::pin(async move {
+                tx.put_aggregation_job(&AggregationJob::<0, TimeInterval, dummy::Vdaf>::new(
+                    task_id,
+                    random(),
+                    dummy::AggregationParam(0),
+                    (),
+                    Interval::new(
+                        Time::from_seconds_since_epoch(1000),
+                        Duration::from_seconds(1),
+                    )
+                    .unwrap(),
+                    AggregationJobState::InProgress,
+                    AggregationJobStep::from(0),
+                ))
                 .await
-                .unwrap();
-        assert_eq!(written_tasks.len(), 1);
-        assert_eq!(written_tasks[0].id(), tasks[0].id());
+            })
+        })
+        .await
+        .unwrap();
 
-        // Verify that the expected tasks were written.
-        let got_tasks = task_hashmap_from_slice(
-            ds.run_unnamed_tx(|tx| Box::pin(async move { tx.get_aggregator_tasks().await }))
-                .await
-                .unwrap(),
-        );
-        let want_tasks = HashMap::from([
-            (*replacement_task.id(), replacement_task),
-            (*tasks[1].id(), tasks[1].clone()),
-        ]);
+        let config_file = ConfigFile {
+            common_config: CommonConfig {
+                database: generate_db_config(),
+                logging_config: generate_trace_config(),
+                metrics_config: generate_metrics_config(),
+                health_check_listen_address: SocketAddr::from((Ipv4Addr::UNSPECIFIED, 8080)),
+                max_transaction_retries: default_max_transaction_retries(),
+                datastore_key_encryption: None,
+                feature_flags: FeatureFlagsConfig::default(),
+                datastore_migration: None,
+                shutdown_drain_timeout_secs: None,
+                deployment_name: None,
+                secrets: None,
+            },
+            measurement_length_limits: MeasurementLengthLimitsConfig::default(),
+        };
+        let bundle = super::support_bundle(&ds, &config_file).await.unwrap();
 
-        assert_eq!(want_tasks, got_tasks);
+        assert_eq!(
+            bundle.config.common_config.database.url.password(),
+            Some("REDACTED")
+        );
+        assert_eq!(
+            bundle.tasks,
+            Vec::from([super::TaskSummary {
+                task_id: *task.id(),
+                role: Role::Leader,
+                vdaf: VdafInstance::Fake,
+                query_type: QueryType::TimeInterval,
+                task_expiration: task.task_expiration().copied(),
+                upload_counter: TaskUploadCounter::default(),
+            }])
+        );
+        assert_eq!(
+            bundle.aggregation_job_states,
+            BTreeMap::from([("InProgress".to_string(), 1)])
+        );
+        assert!(bundle.collection_job_states.is_empty());
+
+        // Secrets are left out of the bundle.
+        let bundle_yaml = serde_yaml::to_string(&bundle).unwrap();
+        let aggregator_auth_token =
+            std::str::from_utf8(task.aggregator_auth_token().unwrap().as_ref()).unwrap();
+        assert!(!bundle_yaml.contains(aggregator_auth_token));
+        assert!(!bundle_yaml.contains(":postgres@"));
     }
 
     #[tokio::test]
     async fn provision_task_with_generated_values() {
         // YAML contains no task ID, VDAF verify keys, aggregator auth tokens, collector auth tokens
-        // or HPKE keys.
+        // or HPKE keys. The helper's HPKE keypair is generated with a non-default ciphersuite.
         let serialized_task_yaml = r#"
 - peer_aggregator_endpoint: https://helper
   query_type: TimeInterval
@@ -764,6 +3823,10 @@ mod tests {
     hash: MJOoBO_ysLEuG_lv2C37eEOf1Ngetsr-Ers0ZYj4vdQ
   collector_auth_token:
   hpke_keys: []
+  hpke_ciphersuite:
+    kem_id: P256HkdfSha256
+    kdf_id: HkdfSha512
+    aead_id: ChaCha20Poly1305
 "#;
 
         let ephemeral_datastore = ephemeral_datastore().await;
@@ -780,6 +3843,8 @@ mod tests {
             &tasks_file_path,
             // do not generate missing parameters
             false,
+            &MeasurementLengthLimits::default(),
+            None,
             // not a dry-run
             false,
         )
@@ -792,6 +3857,8 @@ mod tests {
             &tasks_file_path,
             // generate missing parameters
             true,
+            &MeasurementLengthLimits::default(),
+            None,
             // not a dry-run
             false,
         )
@@ -807,10 +3874,28 @@ mod tests {
         assert_eq!(got_tasks.len(), 2);
 
         for task in &got_tasks {
-            match task.role() {
-                Role::Leader => assert!(task.collector_auth_token_hash().is_some()),
-                Role::Helper => assert!(task.collector_auth_token_hash().is_none()),
+            let (expected_ciphersuite, has_collector_auth_token_hash) = match task.role() {
+                Role::Leader => (HpkeCiphersuite::default(), true),
+                Role::Helper => (
+                    HpkeCiphersuite::new(
+                        HpkeKemId::P256HkdfSha256,
+                        HpkeKdfId::HkdfSha512,
+                        HpkeAeadId::ChaCha20Poly1305,
+                    ),
+                    false,
+                ),
                 role => panic!("unexpected role {role}"),
+            };
+            assert_eq!(
+                task.collector_auth_token_hash().is_some(),
+                has_collector_auth_token_hash
+            );
+            assert_eq!(task.hpke_keys().len(), 1);
+            for keypair in task.hpke_keys().values() {
+                assert_eq!(
+                    HpkeCiphersuite::from(keypair.config()),
+                    expected_ciphersuite
+                );
             }
         }
 
@@ -820,6 +3905,80 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn provision_task_with_verify_key_shares() {
+        let ephemeral_datastore = ephemeral_datastore().await;
+        let ds = ephemeral_datastore.datastore(RealClock::default()).await;
+
+        let task = TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Prio3Count)
+            .with_min_batch_size(1)
+            .build()
+            .leader_view()
+            .unwrap();
+        let write_tasks_file = |include_verify_key: bool| {
+            let mut task_yaml = serde_yaml::to_value(&task).unwrap();
+            if !include_verify_key {
+                task_yaml
+                    .as_mapping_mut()
+                    .unwrap()
+                    .remove("vdaf_verify_key");
+            }
+            let mut tasks_file = NamedTempFile::new().unwrap();
+            tasks_file
+                .write_all(serde_yaml::to_string(&[task_yaml]).unwrap().as_bytes())
+                .unwrap();
+            tasks_file.into_temp_path()
+        };
+
+        let mut read_shares =
+            |task_index: usize, task_id: Option<TaskId>| -> anyhow::Result<Vec<SecretBytes>> {
+                assert_eq!(task_index, 0);
+                assert_eq!(task_id, Some(*task.id()));
+                Ok(Vec::from([
+                    SecretBytes::new(Vec::from([0x5a; 16])),
+                    SecretBytes::new(Vec::from([0x0f; 16])),
+                ]))
+            };
+
+        // Shares can't be combined into a verify key that is already set.
+        super::provision_tasks(
+            &ds,
+            &write_tasks_file(true),
+            false,
+            &MeasurementLengthLimits::default(),
+            Some(&mut read_shares),
+            false,
+        )
+        .await
+        .unwrap_err();
+
+        let written_tasks = super::provision_tasks(
+            &ds,
+            &write_tasks_file(false),
+            false,
+            &MeasurementLengthLimits::default(),
+            Some(&mut read_shares),
+            false,
+        )
+        .await
+        .unwrap();
+        assert_eq!(written_tasks.len(), 1);
+        assert_eq!(
+            written_tasks[0].opaque_vdaf_verify_key().as_ref(),
+            [0x55; 16]
+        );
+
+        let got_task = ds
+            .run_unnamed_tx(|tx| {
+                let task_id = *task.id();
+                Box::pin(async move { tx.get_aggregator_task(&task_id).await })
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(got_task, written_tasks[0]);
+    }
+
     #[tokio::test]
     async fn create_datastore_key() {
         let k8s_cluster = kubernetes::EphemeralCluster::create();
@@ -884,19 +4043,40 @@ mod tests {
                 metrics_config: generate_metrics_config(),
                 health_check_listen_address: SocketAddr::from((Ipv4Addr::UNSPECIFIED, 8080)),
                 max_transaction_retries: default_max_transaction_retries(),
+                datastore_key_encryption: None,
+                feature_flags: FeatureFlagsConfig::default(),
+                datastore_migration: None,
+                shutdown_drain_timeout_secs: None,
+                deployment_name: None,
+                secrets: None,
+            },
+            measurement_length_limits: MeasurementLengthLimitsConfig {
+                max_histogram_length: Some(10_000),
+                max_vector_length: None,
             },
         })
     }
 
     #[test]
     fn documentation_config_examples() {
-        serde_yaml::from_str::<ConfigFile>(include_str!(
+        parse_config::<ConfigFile>(include_str!(
             "../../../docs/samples/basic_config/janus_cli.yaml"
         ))
         .unwrap();
-        serde_yaml::from_str::<ConfigFile>(include_str!(
+        parse_config::<ConfigFile>(include_str!(
             "../../../docs/samples/advanced_config/janus_cli.yaml"
         ))
         .unwrap();
     }
+
+    #[test]
+    fn config_schemas() {
+        for binary in ConfigSchemaBinary::value_variants() {
+            let schema = serde_json::to_value(binary.schema()).unwrap();
+            assert!(
+                schema["properties"]["database"].is_object(),
+                "{binary:?}: {schema}"
+            );
+        }
+    }
 }
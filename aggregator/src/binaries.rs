@@ -2,3 +2,5 @@ pub mod aggregation_job_creator;
 pub mod aggregation_job_driver;
 pub mod aggregator;
 pub mod collection_job_driver;
+pub mod edge_helper;
+pub mod upload_ingester;
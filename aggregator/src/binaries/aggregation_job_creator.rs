@@ -1,18 +1,19 @@
 use crate::{
-    aggregator::aggregation_job_creator::AggregationJobCreator,
+    aggregator::{aggregation_job_creator::AggregationJobCreator, deployment::DeploymentFence},
     binary_utils::{BinaryContext, BinaryOptions, CommonBinaryOptions},
     config::{BinaryConfig, CommonConfig},
 };
-use anyhow::Result;
+use anyhow::{ensure, Result};
 use clap::Parser;
 use janus_core::time::RealClock;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use std::time::Duration;
 
 pub async fn main_callback(ctx: BinaryContext<RealClock, Options, Config>) -> Result<()> {
     // Start creating aggregation jobs.
-    let aggregation_job_creator = Arc::new(AggregationJobCreator::new(
+    let mut aggregation_job_creator = AggregationJobCreator::new(
         ctx.datastore,
         ctx.meter,
         ctx.config.batch_aggregation_shard_count,
@@ -21,8 +22,35 @@ pub async fn main_callback(ctx: BinaryContext<RealClock, Options, Config>) -> Re
         ctx.config.min_aggregation_job_size,
         ctx.config.max_aggregation_job_size,
         ctx.config.aggregation_job_creation_report_window,
+    )
+    .with_deployment_fence(DeploymentFence::new(
+        ctx.config.common_config.deployment_name.as_deref(),
     ));
-    aggregation_job_creator.run(ctx.stopper).await;
+    if let Some(max_adaptive_aggregation_job_size) = ctx.config.max_adaptive_aggregation_job_size {
+        ensure!(
+            max_adaptive_aggregation_job_size >= ctx.config.max_aggregation_job_size,
+            "max_adaptive_aggregation_job_size must be at least max_aggregation_job_size"
+        );
+        aggregation_job_creator = aggregation_job_creator
+            .with_max_adaptive_aggregation_job_size(max_adaptive_aggregation_job_size);
+    }
+    if let Some(task_discovery_interval_secs) = ctx.config.task_discovery_interval_secs {
+        ensure!(
+            task_discovery_interval_secs > 0,
+            "task_discovery_interval_secs must be greater than zero"
+        );
+        aggregation_job_creator = aggregation_job_creator
+            .with_task_discovery_interval(Duration::from_secs(task_discovery_interval_secs));
+    }
+    if let Some(lease_duration_s) = ctx.config.leader_lease_duration_s {
+        ensure!(
+            lease_duration_s > ctx.config.tasks_update_frequency_secs,
+            "leader_lease_duration_s must be greater than tasks_update_frequency_secs"
+        );
+        aggregation_job_creator = aggregation_job_creator
+            .with_leader_election(Duration::from_secs(lease_duration_s), &ctx.leader_elections);
+    }
+    Arc::new(aggregation_job_creator).run(ctx.stopper).await;
 
     Ok(())
 }
@@ -66,7 +94,7 @@ impl BinaryOptions for Options {
 ///
 /// let _decoded: Config = serde_yaml::from_str(yaml_config).unwrap();
 /// ```
-#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub struct Config {
     #[serde(flatten)]
     pub common_config: CommonConfig,
@@ -77,6 +105,11 @@ pub struct Config {
     pub batch_aggregation_shard_count: u64,
     /// How frequently we look for new tasks to start creating aggregation jobs for, in seconds.
     pub tasks_update_frequency_secs: u64,
+    /// If set, how frequently to check for newly provisioned or deleted tasks between task
+    /// updates, in seconds. Only task IDs are read on each check, so this may be much shorter than
+    /// `tasks_update_frequency_secs`, letting job creation start soon after a task is provisioned.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task_discovery_interval_secs: Option<u64>,
     /// How frequently we attempt to create new aggregation jobs for each task, in seconds.
     pub aggregation_job_creation_interval_secs: u64,
     /// The minimum number of client reports to include in an aggregation job. Applies to the
@@ -88,6 +121,19 @@ pub struct Config {
     /// Maximum number of reports to load at a time when creating aggregation jobs.
     #[serde(default = "default_aggregation_job_creation_report_window")]
     pub aggregation_job_creation_report_window: usize,
+    /// If set, aggregation jobs may include up to this many client reports when a task has more
+    /// unaggregated reports than fit in `aggregation_job_creation_report_window`, reducing the
+    /// number of requests to the helper while upload volume is high. Must be at least
+    /// `max_aggregation_job_size`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_adaptive_aggregation_job_size: Option<usize>,
+    /// If set, replicas elect a leader so that only one replica creates aggregation jobs at a
+    /// time. The leader holds a lease of this many seconds, renewed each time tasks are updated.
+    /// If the leader stops renewing its lease, another replica takes over once it expires. This
+    /// must be greater than `tasks_update_frequency_secs`. Leaving this unset means every replica
+    /// creates aggregation jobs independently.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub leader_lease_duration_s: Option<u64>,
 }
 
 fn default_aggregation_job_creation_report_window() -> usize {
@@ -108,10 +154,11 @@ impl BinaryConfig for Config {
 mod tests {
     use super::{Config, Options};
     use crate::config::{
-        default_max_transaction_retries,
+        default_max_transaction_retries, parse_config,
         test_util::{generate_db_config, generate_metrics_config, generate_trace_config},
         CommonConfig,
     };
+    use crate::feature_flags::FeatureFlagsConfig;
     use clap::CommandFactory;
     use janus_core::test_util::roundtrip_encoding;
     use std::net::{Ipv4Addr, SocketAddr};
@@ -130,23 +177,32 @@ mod tests {
                 metrics_config: generate_metrics_config(),
                 health_check_listen_address: SocketAddr::from((Ipv4Addr::UNSPECIFIED, 8080)),
                 max_transaction_retries: default_max_transaction_retries(),
+                datastore_key_encryption: None,
+                feature_flags: FeatureFlagsConfig::default(),
+                datastore_migration: None,
+                shutdown_drain_timeout_secs: None,
+                deployment_name: None,
+                secrets: None,
             },
             batch_aggregation_shard_count: 32,
             tasks_update_frequency_secs: 3600,
+            task_discovery_interval_secs: Some(5),
             aggregation_job_creation_interval_secs: 60,
             min_aggregation_job_size: 100,
             max_aggregation_job_size: 500,
             aggregation_job_creation_report_window: 5000,
+            max_adaptive_aggregation_job_size: Some(2000),
+            leader_lease_duration_s: Some(300),
         })
     }
 
     #[test]
     fn documentation_config_examples() {
-        serde_yaml::from_str::<Config>(include_str!(
+        parse_config::<Config>(include_str!(
             "../../../docs/samples/basic_config/aggregation_job_creator.yaml"
         ))
         .unwrap();
-        serde_yaml::from_str::<Config>(include_str!(
+        parse_config::<Config>(include_str!(
             "../../../docs/samples/advanced_config/aggregation_job_creator.yaml"
         ))
         .unwrap();
@@ -1,11 +1,12 @@
 use crate::{
-    aggregator::aggregation_job_driver::AggregationJobDriver,
+    aggregator::{aggregation_job_driver::AggregationJobDriver, deployment::DeploymentFence},
     binary_utils::{job_driver::JobDriver, BinaryContext, BinaryOptions, CommonBinaryOptions},
     config::{BinaryConfig, CommonConfig, JobDriverConfig, TaskprovConfig},
 };
 use anyhow::{Context, Result};
 use clap::Parser;
 use janus_core::{time::RealClock, TokioRuntime};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::{fmt::Debug, sync::Arc, time::Duration};
 
@@ -18,7 +19,7 @@ pub async fn main_callback(ctx: BinaryContext<RealClock, Options, Config>) -> Re
     );
 
     let datastore = Arc::new(ctx.datastore);
-    let aggregation_job_driver = Arc::new(AggregationJobDriver::new(
+    let mut aggregation_job_driver = AggregationJobDriver::new(
         reqwest::Client::builder()
             .user_agent(CLIENT_USER_AGENT)
             .timeout(Duration::from_secs(
@@ -34,12 +35,20 @@ pub async fn main_callback(ctx: BinaryContext<RealClock, Options, Config>) -> Re
         ctx.config.job_driver_config.retry_config(),
         &ctx.meter,
         ctx.config.batch_aggregation_shard_count,
+    );
+    aggregation_job_driver = aggregation_job_driver.with_deployment_fence(DeploymentFence::new(
+        ctx.config.common_config.deployment_name.as_deref(),
     ));
+    if let Some(max_concurrent_jobs_per_task) = ctx.config.max_concurrent_jobs_per_task {
+        aggregation_job_driver =
+            aggregation_job_driver.with_max_concurrent_jobs_per_task(max_concurrent_jobs_per_task);
+    }
+    let aggregation_job_driver = Arc::new(aggregation_job_driver);
     let lease_duration =
         Duration::from_secs(ctx.config.job_driver_config.worker_lease_duration_secs);
 
     // Start running.
-    let job_driver = Arc::new(JobDriver::new(
+    let mut job_driver = JobDriver::new(
         ctx.clock,
         TokioRuntime,
         ctx.meter,
@@ -53,12 +62,18 @@ pub async fn main_callback(ctx: BinaryContext<RealClock, Options, Config>) -> Re
         ),
         aggregation_job_driver
             .make_incomplete_job_acquirer_callback(Arc::clone(&datastore), lease_duration),
-        aggregation_job_driver.make_job_stepper_callback(
+        Arc::clone(&aggregation_job_driver).make_job_stepper_callback(
             Arc::clone(&datastore),
             ctx.config.job_driver_config.maximum_attempts_before_failure,
         ),
-    )?);
-    job_driver.run().await;
+    )?;
+    if let Some(drain_timeout_secs) = ctx.config.common_config.shutdown_drain_timeout_secs {
+        job_driver = job_driver.with_shutdown_drain_timeout(
+            Duration::from_secs(drain_timeout_secs),
+            aggregation_job_driver.make_job_releaser_callback(Arc::clone(&datastore)),
+        );
+    }
+    Arc::new(job_driver).run().await;
 
     Ok(())
 }
@@ -102,13 +117,14 @@ impl BinaryOptions for Options {
 /// retry_max_interval_millis: 30000
 /// retry_max_elapsed_time_millis: 300000
 /// batch_aggregation_shard_count: 32
+/// max_concurrent_jobs_per_task: 4
 /// taskprov_config:
 ///   enabled: false
 /// "#;
 ///
 /// let _decoded: Config = serde_yaml::from_str(yaml_config).unwrap();
 /// ```
-#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub struct Config {
     #[serde(flatten)]
     pub common_config: CommonConfig,
@@ -121,6 +137,12 @@ pub struct Config {
     /// will reduce the amount of database contention during leader aggregation, while increasing
     /// the cost of collection.
     pub batch_aggregation_shard_count: u64,
+
+    /// The maximum number of aggregation jobs of a single task that may be leased at once, across
+    /// all aggregation job drivers. If unset, there is no limit beyond
+    /// `max_concurrent_job_workers`.
+    #[serde(default)]
+    pub max_concurrent_jobs_per_task: Option<usize>,
 }
 
 impl BinaryConfig for Config {
@@ -137,10 +159,11 @@ impl BinaryConfig for Config {
 mod tests {
     use super::{Config, Options};
     use crate::config::{
-        default_max_transaction_retries,
+        default_max_transaction_retries, parse_config,
         test_util::{generate_db_config, generate_metrics_config, generate_trace_config},
         CommonConfig, JobDriverConfig, TaskprovConfig,
     };
+    use crate::feature_flags::FeatureFlagsConfig;
     use clap::CommandFactory;
     use janus_core::test_util::roundtrip_encoding;
     use std::net::{Ipv4Addr, SocketAddr};
@@ -159,6 +182,12 @@ mod tests {
                 metrics_config: generate_metrics_config(),
                 health_check_listen_address: SocketAddr::from((Ipv4Addr::UNSPECIFIED, 8080)),
                 max_transaction_retries: default_max_transaction_retries(),
+                datastore_key_encryption: None,
+                feature_flags: FeatureFlagsConfig::default(),
+                datastore_migration: None,
+                shutdown_drain_timeout_secs: None,
+                deployment_name: None,
+                secrets: None,
             },
             job_driver_config: JobDriverConfig {
                 job_discovery_interval_secs: 10,
@@ -174,16 +203,17 @@ mod tests {
             },
             batch_aggregation_shard_count: 32,
             taskprov_config: TaskprovConfig::default(),
+            max_concurrent_jobs_per_task: Some(4),
         })
     }
 
     #[test]
     fn documentation_config_examples() {
-        serde_yaml::from_str::<Config>(include_str!(
+        parse_config::<Config>(include_str!(
             "../../../docs/samples/basic_config/aggregation_job_driver.yaml"
         ))
         .unwrap();
-        serde_yaml::from_str::<Config>(include_str!(
+        parse_config::<Config>(include_str!(
             "../../../docs/samples/advanced_config/aggregation_job_driver.yaml"
         ))
         .unwrap();
@@ -1,13 +1,21 @@
 use crate::{
-    aggregator::collection_job_driver::CollectionJobDriver,
-    binary_utils::{job_driver::JobDriver, BinaryContext, BinaryOptions, CommonBinaryOptions},
-    config::{BinaryConfig, CommonConfig, JobDriverConfig},
+    aggregator::{
+        collection_job_driver::CollectionJobDriver, deployment::DeploymentFence,
+        result_sink::result_sink_from_config,
+    },
+    binary_utils::{
+        job_driver::JobDriver, parse_hpke_keypair, BinaryContext, BinaryOptions,
+        CommonBinaryOptions,
+    },
+    config::{BinaryConfig, CommonConfig, JobDriverConfig, ResultSinkConfig},
 };
 use anyhow::{Context, Result};
 use clap::Parser;
-use janus_core::{time::RealClock, TokioRuntime};
+use janus_core::{hpke::HpkeKeypair, time::RealClock, TokioRuntime};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::{fmt::Debug, sync::Arc, time::Duration};
+use tracing::info;
 
 pub async fn main_callback(ctx: BinaryContext<RealClock, Options, Config>) -> Result<()> {
     const CLIENT_USER_AGENT: &str = concat!(
@@ -18,7 +26,7 @@ pub async fn main_callback(ctx: BinaryContext<RealClock, Options, Config>) -> Re
     );
 
     let datastore = Arc::new(ctx.datastore);
-    let collection_job_driver = Arc::new(CollectionJobDriver::new(
+    let mut collection_job_driver = CollectionJobDriver::new(
         reqwest::Client::builder()
             .user_agent(CLIENT_USER_AGENT)
             .timeout(Duration::from_secs(
@@ -35,12 +43,38 @@ pub async fn main_callback(ctx: BinaryContext<RealClock, Options, Config>) -> Re
         &ctx.meter,
         ctx.config.batch_aggregation_shard_count,
         Duration::from_secs(ctx.config.min_collection_job_retry_delay_secs),
+    );
+    if let Some(result_sink_config) = &ctx.config.result_sink {
+        let collector_hpke_keypairs = ctx
+            .options
+            .collector_hpke_keypairs()
+            .context("invalid collector HPKE keypair")?;
+        if collector_hpke_keypairs.is_empty() {
+            return Err(anyhow::anyhow!(
+                "result_sink is configured, but no collector HPKE keypairs were provided"
+            ));
+        }
+        info!(
+            ?result_sink_config,
+            "Recording collection results to result sink"
+        );
+        collection_job_driver = collection_job_driver.with_result_sink(
+            &ctx.meter,
+            result_sink_from_config(result_sink_config)
+                .await
+                .context("couldn't create result sink")?,
+            collector_hpke_keypairs,
+        );
+    }
+    collection_job_driver = collection_job_driver.with_deployment_fence(DeploymentFence::new(
+        ctx.config.common_config.deployment_name.as_deref(),
     ));
+    let collection_job_driver = Arc::new(collection_job_driver);
     let lease_duration =
         Duration::from_secs(ctx.config.job_driver_config.worker_lease_duration_secs);
 
     // Start running.
-    let job_driver = Arc::new(JobDriver::new(
+    let mut job_driver = JobDriver::new(
         ctx.clock,
         TokioRuntime,
         ctx.meter,
@@ -54,12 +88,18 @@ pub async fn main_callback(ctx: BinaryContext<RealClock, Options, Config>) -> Re
         ),
         collection_job_driver
             .make_incomplete_job_acquirer_callback(Arc::clone(&datastore), lease_duration),
-        collection_job_driver.make_job_stepper_callback(
+        Arc::clone(&collection_job_driver).make_job_stepper_callback(
             Arc::clone(&datastore),
             ctx.config.job_driver_config.maximum_attempts_before_failure,
         ),
-    )?);
-    job_driver.run().await;
+    )?;
+    if let Some(drain_timeout_secs) = ctx.config.common_config.shutdown_drain_timeout_secs {
+        job_driver = job_driver.with_shutdown_drain_timeout(
+            Duration::from_secs(drain_timeout_secs),
+            collection_job_driver.make_job_releaser_callback(Arc::clone(&datastore)),
+        );
+    }
+    Arc::new(job_driver).run().await;
 
     Ok(())
 }
@@ -74,6 +114,32 @@ pub async fn main_callback(ctx: BinaryContext<RealClock, Options, Config>) -> Re
 pub struct Options {
     #[clap(flatten)]
     pub common: CommonBinaryOptions,
+
+    /// Collector HPKE keypairs, used to decode collection results if `result_sink` is configured
+    ///
+    /// Each keypair is an HPKE config DAP message and the corresponding private key, each encoded
+    /// in unpadded url-safe base64 and separated by a colon. Keypairs are comma-separated. This
+    /// should only be set in trusted deployments where the leader and collector are operated by
+    /// the same party.
+    #[clap(
+        long,
+        env = "COLLECTOR_HPKE_KEYPAIRS",
+        hide_env_values = true,
+        num_args = 0..=1,
+        use_value_delimiter = true,
+    )]
+    pub collector_hpke_keypairs: Vec<String>,
+}
+
+impl Options {
+    fn collector_hpke_keypairs(&self) -> Result<Vec<HpkeKeypair>> {
+        self.collector_hpke_keypairs
+            .iter()
+            .filter(|keypair| !keypair.is_empty())
+            .map(String::as_str)
+            .map(parse_hpke_keypair)
+            .collect()
+    }
 }
 
 impl BinaryOptions for Options {
@@ -108,7 +174,7 @@ impl BinaryOptions for Options {
 ///
 /// let _decoded: Config = serde_yaml::from_str(yaml_config).unwrap();
 /// ```
-#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub struct Config {
     #[serde(flatten)]
     pub common_config: CommonConfig,
@@ -123,6 +189,12 @@ pub struct Config {
     /// The minimum duration to wait, in seconds, before retrying a collection job that has been
     /// stepped but was not ready yet because not all included reports had finished aggregation.
     pub min_collection_job_retry_delay_secs: u64,
+
+    /// If set, the decoded result of each finished collection job is recorded to this sink. This
+    /// requires the collector HPKE keypairs of the recorded tasks to be provided, so it should
+    /// only be used in deployments where the leader also acts as collector.
+    #[serde(default)]
+    pub result_sink: Option<ResultSinkConfig>,
 }
 
 impl BinaryConfig for Config {
@@ -139,10 +211,11 @@ impl BinaryConfig for Config {
 mod tests {
     use super::{Config, Options};
     use crate::config::{
-        default_max_transaction_retries,
+        default_max_transaction_retries, parse_config,
         test_util::{generate_db_config, generate_metrics_config, generate_trace_config},
-        CommonConfig, JobDriverConfig,
+        CommonConfig, JobDriverConfig, ResultSinkConfig,
     };
+    use crate::feature_flags::FeatureFlagsConfig;
     use clap::CommandFactory;
     use janus_core::test_util::roundtrip_encoding;
     use std::net::{Ipv4Addr, SocketAddr};
@@ -161,6 +234,12 @@ mod tests {
                 metrics_config: generate_metrics_config(),
                 health_check_listen_address: SocketAddr::from((Ipv4Addr::UNSPECIFIED, 8080)),
                 max_transaction_retries: default_max_transaction_retries(),
+                datastore_key_encryption: None,
+                feature_flags: FeatureFlagsConfig::default(),
+                datastore_migration: None,
+                shutdown_drain_timeout_secs: None,
+                deployment_name: None,
+                secrets: None,
             },
             job_driver_config: JobDriverConfig {
                 job_discovery_interval_secs: 10,
@@ -176,16 +255,17 @@ mod tests {
             },
             batch_aggregation_shard_count: 32,
             min_collection_job_retry_delay_secs: 600,
+            result_sink: Some(ResultSinkConfig::Stdout),
         })
     }
 
     #[test]
     fn documentation_config_examples() {
-        serde_yaml::from_str::<Config>(include_str!(
+        parse_config::<Config>(include_str!(
             "../../../docs/samples/basic_config/collection_job_driver.yaml"
         ))
         .unwrap();
-        serde_yaml::from_str::<Config>(include_str!(
+        parse_config::<Config>(include_str!(
             "../../../docs/samples/advanced_config/collection_job_driver.yaml"
         ))
         .unwrap();
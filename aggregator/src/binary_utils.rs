@@ -2,10 +2,15 @@
 
 pub mod job_driver;
 
+#[cfg(feature = "allocation-tracking")]
+pub use crate::aggregator::allocation_tracking::TrackingAllocator;
 use crate::{
-    config::{BinaryConfig, DbConfig},
+    aggregator::leader_election::{LeaderElectionState, LeaderElectionStates},
+    config::{parse_config, BinaryConfig, CommonConfig, DbConfig, RuntimeConfig},
     git_revision,
+    kms::DatastoreKeyUnwrapper,
     metrics::install_metrics_exporter,
+    secrets::SecretFetcher,
     trace::{install_trace_subscriber, TraceReloadHandle},
 };
 use anyhow::{anyhow, Context as _, Result};
@@ -16,82 +21,81 @@ use deadpool::managed::TimeoutType;
 use deadpool_postgres::{Manager, Pool, PoolError, Runtime, Timeouts};
 use futures::StreamExt;
 use janus_aggregator_core::datastore::{Crypter, Datastore};
-use janus_core::time::Clock;
-use opentelemetry::metrics::{Meter, MetricsError};
-use ring::aead::{LessSafeKey, UnboundKey, AES_128_GCM};
+use janus_core::{hpke::HpkeKeypair, time::Clock};
+use janus_messages::{codec::Decode, HpkeConfig};
+use opentelemetry::{
+    metrics::{Meter, MetricsError, Unit},
+    KeyValue,
+};
+use ring::{
+    aead::{LessSafeKey, UnboundKey, AES_128_GCM},
+    digest::{digest, SHA256},
+};
 use rustls::RootCertStore;
+use serde::Serialize;
 use std::{
-    fmt::{self, Debug, Formatter},
+    collections::BTreeMap,
+    fmt::{self, Debug, Formatter, Write as _},
     fs::{self, File},
-    future::Future,
+    future::{pending, Future},
     io::{self, BufReader},
     net::SocketAddr,
     panic,
     path::{Path, PathBuf},
     str::FromStr,
     sync::Arc,
-    time::Duration,
+    time::{Duration, Instant},
+};
+use tokio::{
+    runtime::Handle,
+    select,
+    sync::oneshot,
+    time::{self, interval, MissedTickBehavior},
 };
-use tokio::sync::oneshot;
 use tokio_postgres::NoTls;
 use tokio_postgres_rustls::MakeRustlsConnect;
-use tracing::{debug, info};
+use tracing::{debug, error, info, warn};
 use tracing_subscriber::EnvFilter;
 use trillium::{Handler, Headers, Info, Init, Status};
-use trillium_api::{api, State};
+use trillium_api::{api, Json, State};
 use trillium_head::Head;
 use trillium_router::Router;
 use trillium_tokio::Stopper;
+use url::Url;
 
 /// Reads, parses, and returns the config referenced by the given options, or None if no config file
 /// path was set.
 pub fn read_config<Config: BinaryConfig>(options: &CommonBinaryOptions) -> Result<Config> {
     let config_content = fs::read_to_string(&options.config_file)
         .with_context(|| format!("couldn't read config file {:?}", options.config_file))?;
-    serde_yaml::from_str(&config_content)
+    parse_config(&config_content)
         .with_context(|| format!("couldn't parse config file {:?}", options.config_file))
 }
 
+/// Parses an HPKE config DAP message and the corresponding private key, each encoded in unpadded
+/// url-safe base64 and separated by a colon.
+pub(crate) fn parse_hpke_keypair(keypair: &str) -> Result<HpkeKeypair> {
+    let (config, private_key) = keypair
+        .split_once(':')
+        .context("keypair must be an HPKE config and private key separated by ':'")?;
+    let config = HpkeConfig::get_decoded(
+        &URL_SAFE_NO_PAD
+            .decode(config)
+            .context("invalid base64url in HPKE config")?,
+    )
+    .context("couldn't decode HPKE config")?;
+    let private_key = private_key
+        .parse()
+        .context("invalid base64url in HPKE private key")?;
+    Ok(HpkeKeypair::new(config, private_key))
+}
+
 /// Connects to a database, given a config. `db_password` is mutually exclusive with the database
 /// password specified in the connection URL in `db_config`.
 pub async fn database_pool(db_config: &DbConfig, db_password: Option<&str>) -> Result<Pool> {
-    let mut database_config = tokio_postgres::Config::from_str(db_config.url.as_str())
-        .with_context(|| {
-            format!(
-                "couldn't parse database connect string: {:?}",
-                db_config.url
-            )
-        })?;
-    if database_config.get_password().is_some() && db_password.is_some() {
-        return Err(anyhow!(
-            "database config & password override are both specified"
-        ));
-    }
-    if let Some(pass) = db_password {
-        database_config.password(pass);
-    }
-
+    let pool = build_database_pool(db_config, &db_config.url, db_password)?;
     let connection_pool_timeout = Duration::from_secs(db_config.connection_pool_timeouts_secs);
 
-    let conn_mgr = if let Some(ref path) = db_config.tls_trust_store_path {
-        let root_store = load_pem_trust_store(path).context("failed to load TLS trust store")?;
-        let rustls_config = rustls::ClientConfig::builder()
-            .with_root_certificates(root_store)
-            .with_no_client_auth();
-        Manager::new(database_config, MakeRustlsConnect::new(rustls_config))
-    } else {
-        Manager::new(database_config, NoTls)
-    };
-    let pool = Pool::builder(conn_mgr)
-        .runtime(Runtime::Tokio1)
-        .timeouts(Timeouts {
-            wait: Some(connection_pool_timeout),
-            create: Some(connection_pool_timeout),
-            recycle: Some(connection_pool_timeout),
-        })
-        .build()
-        .context("failed to create database connection pool")?;
-
     // Attempt to fetch a connection from the connection pool, to check that the database is
     // accessible. This will either create a new database connection or recycle an existing one, and
     // then return the connection back to the pool.
@@ -122,6 +126,55 @@ pub async fn database_pool(db_config: &DbConfig, db_password: Option<&str>) -> R
     Ok(pool)
 }
 
+/// Creates a connection pool for the read replica configured in `db_config`, if any. The replica
+/// is connected to with the same password and TLS settings as the primary database. Unlike
+/// [`database_pool`], this does not check that the replica is reachable, since read-only
+/// transactions fall back to the primary database if it is not.
+pub fn read_replica_database_pool(
+    db_config: &DbConfig,
+    db_password: Option<&str>,
+) -> Result<Option<Pool>> {
+    db_config
+        .read_replica_url
+        .as_ref()
+        .map(|url| build_database_pool(db_config, url, db_password))
+        .transpose()
+}
+
+fn build_database_pool(db_config: &DbConfig, url: &Url, db_password: Option<&str>) -> Result<Pool> {
+    let mut database_config = tokio_postgres::Config::from_str(url.as_str())
+        .with_context(|| format!("couldn't parse database connect string: {url:?}"))?;
+    if database_config.get_password().is_some() && db_password.is_some() {
+        return Err(anyhow!(
+            "database config & password override are both specified"
+        ));
+    }
+    if let Some(pass) = db_password {
+        database_config.password(pass);
+    }
+
+    let connection_pool_timeout = Duration::from_secs(db_config.connection_pool_timeouts_secs);
+
+    let conn_mgr = if let Some(ref path) = db_config.tls_trust_store_path {
+        let root_store = load_pem_trust_store(path).context("failed to load TLS trust store")?;
+        let rustls_config = rustls::ClientConfig::builder()
+            .with_root_certificates(root_store)
+            .with_no_client_auth();
+        Manager::new(database_config, MakeRustlsConnect::new(rustls_config))
+    } else {
+        Manager::new(database_config, NoTls)
+    };
+    Pool::builder(conn_mgr)
+        .runtime(Runtime::Tokio1)
+        .timeouts(Timeouts {
+            wait: Some(connection_pool_timeout),
+            create: Some(connection_pool_timeout),
+            recycle: Some(connection_pool_timeout),
+        })
+        .build()
+        .context("failed to create database connection pool")
+}
+
 /// Connects to a datastore, given a connection pool to the underlying database. `datastore_keys`
 /// is a list of AES-128-GCM keys, encoded in base64 with no padding, used to protect secret values
 /// stored in the datastore; it must not be empty.
@@ -133,6 +186,46 @@ pub async fn datastore<C: Clock>(
     check_schema_version: bool,
     max_transaction_retries: u64,
 ) -> Result<Datastore<C>> {
+    datastore_with_crypter(
+        pool,
+        clock,
+        meter,
+        Crypter::new(parse_datastore_keys(datastore_keys)?),
+        check_schema_version,
+        max_transaction_retries,
+    )
+    .await
+}
+
+/// Connects to a datastore, given a connection pool to the underlying database, and a [`Crypter`]
+/// used to protect secret values stored in the datastore.
+pub async fn datastore_with_crypter<C: Clock>(
+    pool: Pool,
+    clock: C,
+    meter: &Meter,
+    crypter: Crypter,
+    check_schema_version: bool,
+    max_transaction_retries: u64,
+) -> Result<Datastore<C>> {
+    let datastore = if check_schema_version {
+        Datastore::new(pool, crypter, clock, meter, max_transaction_retries).await?
+    } else {
+        Datastore::new_without_supported_versions(
+            pool,
+            crypter,
+            clock,
+            meter,
+            max_transaction_retries,
+        )
+        .await
+    };
+
+    Ok(datastore)
+}
+
+/// Parses a list of AES-128-GCM datastore keys, encoded in base64 with no padding. Empty entries
+/// are ignored, but at least one key must be provided.
+pub fn parse_datastore_keys(datastore_keys: &[String]) -> Result<Vec<LessSafeKey>> {
     let datastore_keys = datastore_keys
         .iter()
         .filter(|k| !k.is_empty())
@@ -140,44 +233,124 @@ pub async fn datastore<C: Clock>(
             URL_SAFE_NO_PAD
                 .decode(k)
                 .context("couldn't base64-decode datastore keys")
-                .and_then(|k| {
-                    Ok(LessSafeKey::new(
-                        UnboundKey::new(&AES_128_GCM, k.as_ref()).map_err(|_| {
-                            anyhow!(
-                                "couldn't parse datastore keys, expected {} bytes, got {}",
-                                AES_128_GCM.key_len(),
-                                k.len()
-                            )
-                        })?,
-                    ))
-                })
+                .and_then(|k| datastore_key_from_bytes(&k))
         })
         .collect::<Result<Vec<LessSafeKey>>>()?;
     if datastore_keys.is_empty() {
         return Err(anyhow!("datastore_keys is empty"));
     }
+    Ok(datastore_keys)
+}
 
-    let datastore = if check_schema_version {
-        Datastore::new(
-            pool,
-            Crypter::new(datastore_keys),
-            clock,
-            meter,
-            max_transaction_retries,
-        )
-        .await?
-    } else {
-        Datastore::new_without_supported_versions(
-            pool,
-            Crypter::new(datastore_keys),
-            clock,
-            meter,
-            max_transaction_retries,
-        )
-        .await
-    };
+/// Constructs an AES-128-GCM datastore key from raw key material.
+pub(crate) fn datastore_key_from_bytes(key: &[u8]) -> Result<LessSafeKey> {
+    Ok(LessSafeKey::new(
+        UnboundKey::new(&AES_128_GCM, key).map_err(|_| {
+            anyhow!(
+                "couldn't parse datastore keys, expected {} bytes, got {}",
+                AES_128_GCM.key_len(),
+                key.len()
+            )
+        })?,
+    ))
+}
 
-    Ok(datastore)
+/// Loads datastore keys from the configured sources. Keys are taken from the secret store, if one
+/// is configured to hold them, and otherwise from the keys provided via command line arguments or
+/// environment variables. If datastore key encryption is configured, the keys (or those in the
+/// configured file) are then unwrapped using the configured KMS.
+pub struct DatastoreKeyLoader {
+    datastore_keys: Vec<String>,
+    secret_fetcher: Option<SecretFetcher>,
+    key_unwrapper: Option<DatastoreKeyUnwrapper>,
+}
+
+impl DatastoreKeyLoader {
+    /// Constructs a loader for the given configuration. `datastore_keys` holds the keys provided
+    /// via command line arguments or environment variables.
+    pub fn new(config: &CommonConfig, datastore_keys: &[String]) -> Result<Self> {
+        let secret_fetcher = match &config.secrets {
+            Some(secrets_config) if secrets_config.datastore_keys.is_some() => {
+                Some(SecretFetcher::new(secrets_config)?)
+            }
+            _ => None,
+        };
+        let key_unwrapper = config
+            .datastore_key_encryption
+            .as_ref()
+            .map(DatastoreKeyUnwrapper::new)
+            .transpose()?;
+        Ok(Self {
+            datastore_keys: datastore_keys.to_vec(),
+            secret_fetcher,
+            key_unwrapper,
+        })
+    }
+
+    /// Loads the datastore keys. The order of the keys is preserved, so the first key remains the
+    /// primary key.
+    pub async fn load_keys(&self) -> Result<Vec<LessSafeKey>> {
+        let fetched_keys = match &self.secret_fetcher {
+            Some(secret_fetcher) => secret_fetcher.datastore_keys().await?,
+            None => None,
+        };
+        let datastore_keys = fetched_keys.as_deref().unwrap_or(&self.datastore_keys);
+        match &self.key_unwrapper {
+            Some(key_unwrapper) => key_unwrapper.unwrap_keys(datastore_keys).await,
+            None => parse_datastore_keys(datastore_keys),
+        }
+    }
+
+    /// Returns true if loading the keys again may produce different keys, i.e. if they are fetched
+    /// from a secret store or unwrapped by a KMS.
+    pub fn is_reloadable(&self) -> bool {
+        self.secret_fetcher.is_some() || self.key_unwrapper.is_some()
+    }
+
+    /// How often the keys should be loaded again, if periodic refresh is configured.
+    pub fn refresh_interval(&self) -> Option<Duration> {
+        self.secret_fetcher
+            .as_ref()
+            .and_then(SecretFetcher::refresh_interval)
+    }
+}
+
+/// Constructs a [`Crypter`] holding the datastore keys loaded from the configured sources, as
+/// described in [`DatastoreKeyLoader`]. The loader is also returned, so that the keys may be loaded
+/// again later.
+pub async fn datastore_crypter(
+    config: &CommonConfig,
+    datastore_keys: &[String],
+) -> Result<(Crypter, DatastoreKeyLoader)> {
+    let key_loader = DatastoreKeyLoader::new(config, datastore_keys)?;
+    let crypter = Crypter::new(key_loader.load_keys().await?);
+    Ok((crypter, key_loader))
+}
+
+/// A database configuration, along with the password override to connect to it with, if any.
+type DatabaseWithPassword<'a> = (&'a DbConfig, Option<&'a str>);
+
+/// Determines which database is primary, and which database (if any) uploaded reports are also
+/// written to, according to the datastore migration configuration.
+pub fn migration_databases<'a>(
+    config: &'a CommonConfig,
+    options: &'a CommonBinaryOptions,
+) -> (DatabaseWithPassword<'a>, Option<DatabaseWithPassword<'a>>) {
+    let database = (&config.database, options.database_password.as_deref());
+    match &config.datastore_migration {
+        Some(migration) => {
+            let new_database = (
+                &migration.database,
+                options.migration_database_password.as_deref(),
+            );
+            if migration.cutover {
+                (new_database, Some(database))
+            } else {
+                (database, Some(new_database))
+            }
+        }
+        None => (database, None),
+    }
 }
 
 /// Loads a series of certificates from a PEM file into a rustls [`RootCertStore`].
@@ -209,6 +382,12 @@ pub struct CommonBinaryOptions {
     #[clap(long, env = "PGPASSWORD", hide_env_values = true)]
     pub database_password: Option<String>,
 
+    /// Password for the PostgreSQL connection to the new database of a datastore migration
+    ///
+    /// If specified, it must not be specified in the connection string.
+    #[clap(long, env = "MIGRATION_DATABASE_PASSWORD", hide_env_values = true)]
+    pub migration_database_password: Option<String>,
+
     /// Datastore encryption keys
     ///
     /// Keys are encoded in unpadded url-safe base64, then comma separated.
@@ -236,8 +415,13 @@ pub struct BinaryContext<C: Clock, Options: BinaryOptions, Config: BinaryConfig>
     pub options: Options,
     pub config: Config,
     pub datastore: Datastore<C>,
+    /// Datastore to which uploaded reports are also written, while a datastore migration is in
+    /// progress.
+    pub secondary_datastore: Option<Datastore<C>>,
     pub meter: Meter,
     pub stopper: Stopper,
+    /// Leader elections registered here are reported by the health check server.
+    pub leader_elections: LeaderElectionStates,
 }
 
 pub async fn janus_main<C, Options, Config, F, Fut>(clock: C, f: F) -> anyhow::Result<()>
@@ -263,7 +447,8 @@ where
 
     // Register signal handler.
     let stopper = Stopper::new();
-    setup_signal_handler(stopper.clone()).context("failed to register SIGTERM signal handler")?;
+    setup_signal_handler(stopper.clone())
+        .context("failed to register SIGTERM and SIGINT signal handlers")?;
 
     info!(
         common_options = ?options.common_options(),
@@ -275,28 +460,83 @@ where
     );
 
     // Connect to database.
-    let pool = database_pool(
-        &config.common_config().database,
-        options.common_options().database_password.as_deref(),
+    let (primary_database, secondary_database) =
+        migration_databases(config.common_config(), options.common_options());
+    let pool = database_pool(primary_database.0, primary_database.1)
+        .await
+        .context("couldn't create database connection pool")?;
+    let (crypter, key_loader) = datastore_crypter(
+        config.common_config(),
+        &options.common_options().datastore_keys,
     )
     .await
-    .context("couldn't create database connection pool")?;
-    let datastore = datastore(
+    .context("couldn't load datastore keys")?;
+    if key_loader.is_reloadable() {
+        setup_datastore_key_reload_handler(key_loader, crypter.clone())
+            .context("failed to register SIGHUP signal handler")?;
+    }
+    let mut datastore = datastore_with_crypter(
         pool.clone(),
         clock.clone(),
         &meter,
-        &options.common_options().datastore_keys,
-        config.common_config().database.check_schema_version,
+        crypter.clone(),
+        primary_database.0.check_schema_version,
         config.common_config().max_transaction_retries,
     )
     .await
     .context("couldn't create datastore")?;
+    if let Some(read_replica_pool) =
+        read_replica_database_pool(primary_database.0, primary_database.1)
+            .context("couldn't create read replica connection pool")?
+    {
+        info!("Directing read-only transactions to the database read replica");
+        datastore = datastore.with_read_replica_pool(read_replica_pool);
+    }
+    let secondary_datastore = match secondary_database {
+        Some((db_config, db_password)) => {
+            info!("Datastore migration in progress, writing uploaded reports to both databases");
+            let pool = database_pool(db_config, db_password)
+                .await
+                .context("couldn't create secondary database connection pool")?;
+            Some(
+                datastore_with_crypter(
+                    pool,
+                    clock.clone(),
+                    &meter,
+                    crypter,
+                    db_config.check_schema_version,
+                    config.common_config().max_transaction_retries,
+                )
+                .await
+                .context("couldn't create secondary datastore")?,
+            )
+        }
+        None => None,
+    };
 
     register_database_pool_status_metrics(pool, &meter)?;
 
+    let version_info = Arc::new(
+        VersionInfo::new::<C, Options>(options.common_options(), &datastore)
+            .await
+            .context("couldn't determine version information")?,
+    );
+    version_info.log_lifecycle_event("startup");
+
+    let leader_elections = LeaderElectionStates::default();
     let health_check_listen_address = config.common_config().health_check_listen_address;
-    let zpages_task_handle = tokio::task::spawn(async move {
-        zpages_server(health_check_listen_address, trace_reload_handle).await
+    let zpages_task_handle = tokio::task::spawn({
+        let version_info = Arc::clone(&version_info);
+        let leader_elections = leader_elections.clone();
+        async move {
+            zpages_server(
+                health_check_listen_address,
+                trace_reload_handle,
+                version_info,
+                leader_elections,
+            )
+            .await
+        }
     });
 
     let result = f(BinaryContext {
@@ -304,16 +544,110 @@ where
         options,
         config,
         datastore,
+        secondary_datastore,
         meter,
         stopper,
+        leader_elections,
     })
     .await;
 
     zpages_task_handle.abort();
 
+    version_info.log_lifecycle_event("shutdown");
+
     result
 }
 
+/// Describes the build and configuration of a running Janus binary, so that version skew across a
+/// deployment can be audited. This is logged at startup and shutdown, and served at `/version` by
+/// the health check server.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct VersionInfo {
+    /// Name of the binary.
+    pub binary: String,
+    /// Version of the Janus crates the binary was built from.
+    pub version: &'static str,
+    /// Git revision the binary was built from.
+    pub git_revision: &'static str,
+    /// Version of the Rust compiler used to build the binary.
+    pub rust_version: &'static str,
+    /// Cargo features enabled at compile time.
+    pub features: Vec<&'static str>,
+    /// SHA-256 hash of the configuration file, encoded in hexadecimal.
+    pub config_hash: String,
+    /// Version of the most recently applied database schema migration.
+    pub schema_version: i64,
+}
+
+impl VersionInfo {
+    async fn new<C: Clock, Options: BinaryOptions>(
+        options: &CommonBinaryOptions,
+        datastore: &Datastore<C>,
+    ) -> Result<Self> {
+        let config_content = fs::read(&options.config_file)
+            .with_context(|| format!("couldn't read config file {:?}", options.config_file))?;
+        let (schema_version, _) = datastore
+            .run_tx("get_schema_version", |tx| {
+                Box::pin(async move { tx.get_current_schema_migration_version().await })
+            })
+            .await
+            .context("couldn't get database schema version")?;
+
+        Ok(Self {
+            binary: Options::command().get_name().to_string(),
+            version: env!("CARGO_PKG_VERSION"),
+            git_revision: git_revision(),
+            rust_version: env!("RUSTC_SEMVER"),
+            features: enabled_features(),
+            config_hash: digest(&SHA256, &config_content).as_ref().iter().fold(
+                String::new(),
+                |mut hash, byte| {
+                    // Unwrap safety: writing to a String never fails.
+                    write!(hash, "{byte:02x}").unwrap();
+                    hash
+                },
+            ),
+            schema_version,
+        })
+    }
+
+    /// Logs a structured event marking a change in the binary's lifecycle.
+    fn log_lifecycle_event(&self, event: &'static str) {
+        info!(
+            lifecycle_event = event,
+            binary = self.binary,
+            version = self.version,
+            git_revision = self.git_revision,
+            rust_version = self.rust_version,
+            features = ?self.features,
+            config_hash = self.config_hash,
+            schema_version = self.schema_version,
+            "Lifecycle event"
+        );
+    }
+}
+
+/// Returns the names of the Cargo features of this crate that were enabled at compile time.
+fn enabled_features() -> Vec<&'static str> {
+    [
+        ("allocation-tracking", cfg!(feature = "allocation-tracking")),
+        ("aws-kms", cfg!(feature = "aws-kms")),
+        ("aws-secrets-manager", cfg!(feature = "aws-secrets-manager")),
+        ("azure-key-vault", cfg!(feature = "azure-key-vault")),
+        ("fpvec_bounded_l2", cfg!(feature = "fpvec_bounded_l2")),
+        ("gcp-kms", cfg!(feature = "gcp-kms")),
+        ("gcp-secret-manager", cfg!(feature = "gcp-secret-manager")),
+        ("hashicorp-vault", cfg!(feature = "hashicorp-vault")),
+        ("otlp", cfg!(feature = "otlp")),
+        ("prometheus", cfg!(feature = "prometheus")),
+        ("test-util", cfg!(feature = "test-util")),
+        ("tokio-console", cfg!(feature = "tokio-console")),
+    ]
+    .into_iter()
+    .filter_map(|(name, enabled)| enabled.then_some(name))
+    .collect()
+}
+
 /// Set up metrics to monitor the database connection pool's status.
 fn register_database_pool_status_metrics(pool: Pool, meter: &Meter) -> Result<(), MetricsError> {
     let available_connections_gauge = meter
@@ -379,8 +713,18 @@ fn register_database_pool_status_metrics(pool: Pool, meter: &Meter) -> Result<()
 ///
 /// `/traceconfigz` responds with the tracing_subscriber configuration, or allows configuring it
 /// with a PUT request.
-async fn zpages_server(address: SocketAddr, trace_reload_handle: TraceReloadHandle) {
-    let handler = zpages_handler(trace_reload_handle);
+///
+/// `/version` responds with a JSON representation of the binary's [`VersionInfo`].
+///
+/// `/leaderz` responds with a JSON object describing the state of each leader election the binary
+/// participates in, keyed by lease name.
+async fn zpages_server(
+    address: SocketAddr,
+    trace_reload_handle: TraceReloadHandle,
+    version_info: Arc<VersionInfo>,
+    leader_elections: LeaderElectionStates,
+) {
+    let handler = zpages_handler(trace_reload_handle, version_info, leader_elections);
     trillium_tokio::config()
         .with_port(address.port())
         .with_host(&address.ip().to_string())
@@ -389,20 +733,42 @@ async fn zpages_server(address: SocketAddr, trace_reload_handle: TraceReloadHand
         .await;
 }
 
-fn zpages_handler(trace_reload_handle: TraceReloadHandle) -> impl Handler {
+fn zpages_handler(
+    trace_reload_handle: TraceReloadHandle,
+    version_info: Arc<VersionInfo>,
+    leader_elections: LeaderElectionStates,
+) -> impl Handler {
     (
         Head::new(),
         State(Arc::new(trace_reload_handle)),
+        State(version_info),
+        State(leader_elections),
         Router::new()
             .get(
                 "/healthz",
                 |conn: trillium::Conn| async move { conn.ok("") },
             )
             .get("/traceconfigz", api(get_traceconfigz))
-            .put("/traceconfigz", api(put_traceconfigz)),
+            .put("/traceconfigz", api(put_traceconfigz))
+            .get("/version", api(get_version))
+            .get("/leaderz", api(get_leaderz)),
     )
 }
 
+async fn get_version(
+    _: &mut trillium::Conn,
+    State(version_info): State<Arc<VersionInfo>>,
+) -> Json<VersionInfo> {
+    Json(VersionInfo::clone(&version_info))
+}
+
+async fn get_leaderz(
+    _: &mut trillium::Conn,
+    State(leader_elections): State<LeaderElectionStates>,
+) -> Json<BTreeMap<&'static str, LeaderElectionState>> {
+    Json(leader_elections.snapshot())
+}
+
 async fn get_traceconfigz(
     conn: &mut trillium::Conn,
     State(trace_reload_handle): State<Arc<TraceReloadHandle>>,
@@ -437,14 +803,18 @@ async fn put_traceconfigz(
         })
 }
 
-/// Register a signal handler for SIGTERM, and stop the [`Stopper`] when a SIGTERM signal is
-/// received.
+/// Register a signal handler for SIGTERM and SIGINT, and stop the [`Stopper`] when either signal
+/// is received.
 pub fn setup_signal_handler(stopper: Stopper) -> Result<(), std::io::Error> {
-    let mut signal_stream = signal_hook_tokio::Signals::new([signal_hook::consts::SIGTERM])?;
+    let mut signal_stream = signal_hook_tokio::Signals::new([
+        signal_hook::consts::SIGTERM,
+        signal_hook::consts::SIGINT,
+    ])?;
     let handle = signal_stream.handle();
     tokio::spawn(async move {
         while let Some(signal) = signal_stream.next().await {
-            if signal == signal_hook::consts::SIGTERM {
+            if signal == signal_hook::consts::SIGTERM || signal == signal_hook::consts::SIGINT {
+                info!(signal, "Received shutdown signal, draining in-flight work");
                 stopper.stop();
                 handle.close();
                 break;
@@ -454,14 +824,69 @@ pub fn setup_signal_handler(stopper: Stopper) -> Result<(), std::io::Error> {
     Ok(())
 }
 
+/// Register a signal handler for SIGHUP, which loads the datastore keys again when a SIGHUP signal
+/// is received, or periodically if the secret store is configured to be refreshed, and replaces
+/// the keys used by `crypter` with the result. If the keys can't be loaded, the existing keys
+/// remain in use.
+fn setup_datastore_key_reload_handler(
+    key_loader: DatastoreKeyLoader,
+    crypter: Crypter,
+) -> Result<(), std::io::Error> {
+    let mut signal_stream = signal_hook_tokio::Signals::new([signal_hook::consts::SIGHUP])?;
+    let mut refresh_interval = key_loader.refresh_interval().map(|period| {
+        let mut refresh_interval = time::interval_at(time::Instant::now() + period, period);
+        refresh_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        refresh_interval
+    });
+    tokio::spawn(async move {
+        loop {
+            select! {
+                signal = signal_stream.next() => {
+                    if signal.is_none() {
+                        break;
+                    }
+                }
+                _ = async {
+                    match &mut refresh_interval {
+                        Some(refresh_interval) => {
+                            refresh_interval.tick().await;
+                        }
+                        None => pending().await,
+                    }
+                } => {}
+            }
+
+            match key_loader.load_keys().await {
+                Ok(keys) => {
+                    let key_count = keys.len();
+                    crypter.replace_keys(keys);
+                    info!(key_count, "Reloaded datastore keys");
+                }
+                Err(error) => {
+                    error!(
+                        ?error,
+                        "Couldn't reload datastore keys, keeping existing keys"
+                    )
+                }
+            }
+        }
+    });
+    Ok(())
+}
+
 /// Construct a server that listens on the provided [`SocketAddr`] and services requests with
 /// `handler`. If the `SocketAddr`'s port is 0, an ephemeral port is used. Returns a `SocketAddr`
 /// representing the address and port the server are listening on and a future that can be `await`ed
 /// to wait until the server shuts down.
+///
+/// Once `stopper` is stopped, the server stops accepting new connections, and waits for in-flight
+/// requests to complete. If `drain_timeout` is given, requests still in flight after it elapses
+/// are dropped.
 pub async fn setup_server(
     listen_address: SocketAddr,
     response_headers: Headers,
     stopper: Stopper,
+    drain_timeout: Option<Duration>,
     handler: impl Handler,
 ) -> anyhow::Result<(SocketAddr, impl Future<Output = ()> + 'static)> {
     let (sender, receiver) = oneshot::channel();
@@ -473,19 +898,37 @@ pub async fn setup_server(
     let server_config = trillium_tokio::config()
         .with_port(listen_address.port())
         .with_host(&listen_address.ip().to_string())
-        .with_stopper(stopper)
+        .with_stopper(stopper.clone())
         .without_signals();
     let handler = (init, response_headers, handler);
 
-    let task_handle = tokio::spawn(server_config.run_async(handler));
+    let mut task_handle = tokio::spawn(server_config.run_async(handler));
 
     let address = receiver
         .await
         .map_err(|err| anyhow!("error waiting for socket address: {err}"))?
         .ok_or_else(|| anyhow!("could not get server's socket address"))?;
 
-    let future = async {
-        if let Err(err) = task_handle.await {
+    let future = async move {
+        let drain_deadline = async {
+            stopper.stop_future(pending::<()>()).await;
+            match drain_timeout {
+                Some(drain_timeout) => time::sleep(drain_timeout).await,
+                None => pending().await,
+            }
+        };
+        let result = select! {
+            result = &mut task_handle => result,
+            _ = drain_deadline => {
+                warn!(
+                    ?listen_address,
+                    "Timed out waiting for in-flight requests to complete, dropping them"
+                );
+                task_handle.abort();
+                return;
+            }
+        };
+        if let Err(err) = result {
             if let Ok(reason) = err.try_into_panic() {
                 panic::resume_unwind(reason);
             }
@@ -495,53 +938,226 @@ pub async fn setup_server(
     Ok((address, future))
 }
 
+/// Builds a dedicated multi-threaded Tokio runtime, whose worker threads are named `name`.
+pub fn build_runtime(name: &str, config: &RuntimeConfig) -> Result<tokio::runtime::Runtime> {
+    if config.worker_threads == 0 {
+        return Err(anyhow!(
+            "runtime {name} must have at least one worker thread"
+        ));
+    }
+    tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(config.worker_threads)
+        .thread_name(name)
+        .enable_all()
+        .build()
+        .with_context(|| format!("couldn't build runtime {name}"))
+}
+
+/// How often [`monitor_runtime`] measures a runtime's scheduling delay.
+const RUNTIME_MONITOR_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Returns a future that periodically spawns a trivial task on the runtime with the given handle,
+/// and records how long it waits to be polled in the `janus_runtime_scheduling_delay` metric,
+/// labeled with `name`. A long delay indicates that the runtime's worker threads are saturated.
+/// The future completes once `stopper` is stopped, or the runtime shuts down.
+pub fn monitor_runtime(
+    handle: Handle,
+    name: &'static str,
+    meter: &Meter,
+    stopper: Stopper,
+) -> impl Future<Output = ()> + Send + 'static {
+    let scheduling_delay_histogram = meter
+        .f64_histogram("janus_runtime_scheduling_delay")
+        .with_description(
+            "Time between spawning a task on a Tokio runtime and the task first being polled.",
+        )
+        .with_unit(Unit::new("s"))
+        .init();
+    let attributes = [KeyValue::new("runtime", name)];
+
+    async move {
+        let mut interval = interval(RUNTIME_MONITOR_INTERVAL);
+        while stopper.stop_future(interval.tick()).await.is_some() {
+            let spawned_at = Instant::now();
+            match handle.spawn(async move { spawned_at.elapsed() }).await {
+                Ok(scheduling_delay) => {
+                    scheduling_delay_histogram.record(scheduling_delay.as_secs_f64(), &attributes)
+                }
+                // The runtime is shutting down.
+                Err(_) => break,
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{
-        aggregator::http_handlers::test_util::take_response_body,
+        aggregator::{
+            http_handlers::test_util::take_response_body,
+            leader_election::{LeaderElection, LeaderElectionStates},
+        },
         binary_utils::{
-            database_pool, register_database_pool_status_metrics, zpages_handler,
-            CommonBinaryOptions,
+            build_runtime, database_pool, monitor_runtime, register_database_pool_status_metrics,
+            zpages_handler, CommonBinaryOptions, VersionInfo,
         },
-        config::DbConfig,
+        config::{DbConfig, RuntimeConfig},
     };
     use clap::CommandFactory;
-    use janus_aggregator_core::datastore::test_util::ephemeral_datastore;
-    use janus_core::test_util::{
-        install_test_trace_subscriber,
-        testcontainers::{container_client, Postgres, Volume},
+    use janus_aggregator_core::{
+        datastore::{test_util::ephemeral_datastore, SUPPORTED_SCHEMA_VERSIONS},
+        test_util::noop_meter,
+    };
+    use janus_core::{
+        test_util::{
+            install_test_trace_subscriber,
+            testcontainers::{container_client, Postgres, Volume},
+        },
+        time::MockClock,
     };
     use opentelemetry::metrics::MeterProvider as _;
     use opentelemetry_sdk::{
-        metrics::{data::Gauge, PeriodicReader, SdkMeterProvider},
+        metrics::{
+            data::{Gauge, Histogram},
+            PeriodicReader, SdkMeterProvider,
+        },
         runtime::Tokio,
         testing::metrics::InMemoryMetricsExporter,
     };
-    use std::{collections::HashMap, fs};
+    use std::{collections::HashMap, fs, io::Write, sync::Arc, time::Duration};
+    use tempfile::NamedTempFile;
     use testcontainers::RunnableImage;
-    use tokio::task::spawn_blocking;
+    use tokio::{task::spawn_blocking, time::sleep};
     use tracing_subscriber::{reload, EnvFilter};
     use trillium::Status;
     use trillium_testing::prelude::*;
+    use trillium_tokio::Stopper;
 
     #[test]
     fn verify_app() {
         CommonBinaryOptions::command().debug_assert()
     }
 
+    fn test_version_info() -> Arc<VersionInfo> {
+        Arc::new(VersionInfo {
+            binary: "janus-test".to_string(),
+            version: "0.0.0",
+            git_revision: "abcdef",
+            rust_version: "1.0.0",
+            features: Vec::from(["otlp"]),
+            config_hash: "00".repeat(32),
+            schema_version: 1,
+        })
+    }
+
+    #[tokio::test]
+    async fn version() {
+        let (_, filter_handle) = reload::Layer::new(EnvFilter::new("info"));
+        let handler = zpages_handler(
+            filter_handle,
+            test_version_info(),
+            LeaderElectionStates::default(),
+        );
+
+        let mut test_conn = get("/version").run_async(&handler).await;
+        assert_eq!(test_conn.status(), Some(Status::Ok));
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&take_response_body(&mut test_conn).await)
+                .unwrap(),
+            serde_json::json!({
+                "binary": "janus-test",
+                "version": "0.0.0",
+                "git_revision": "abcdef",
+                "rust_version": "1.0.0",
+                "features": ["otlp"],
+                "config_hash": "00".repeat(32),
+                "schema_version": 1,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn version_info() {
+        install_test_trace_subscriber();
+
+        let ephemeral_datastore = ephemeral_datastore().await;
+        let datastore = ephemeral_datastore.datastore(MockClock::default()).await;
+        let mut config_file = NamedTempFile::new().unwrap();
+        config_file.write_all(b"database: {}\n").unwrap();
+
+        let version_info = VersionInfo::new::<_, crate::binaries::aggregator::Options>(
+            &CommonBinaryOptions {
+                config_file: config_file.path().to_path_buf(),
+                ..Default::default()
+            },
+            &datastore,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(version_info.binary, "janus-aggregator");
+        assert_eq!(version_info.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(version_info.schema_version, SUPPORTED_SCHEMA_VERSIONS[0]);
+        // SHA-256 of the config file's contents.
+        assert_eq!(
+            version_info.config_hash,
+            "3b01032119c86946539b414afe2a7d42139029794f8d45baf0023ceee8be487b"
+        );
+        // The crate's tests enable the test-util feature.
+        assert!(version_info.features.contains(&"test-util"));
+    }
+
     #[tokio::test]
     async fn healthz() {
         let (_, filter_handle) = reload::Layer::new(EnvFilter::new("info"));
-        let handler = zpages_handler(filter_handle);
+        let handler = zpages_handler(
+            filter_handle,
+            test_version_info(),
+            LeaderElectionStates::default(),
+        );
 
         let test_conn = get("/healthz").run_async(&handler).await;
         assert_eq!(test_conn.status(), Some(Status::Ok));
     }
 
+    #[tokio::test]
+    async fn leaderz() {
+        install_test_trace_subscriber();
+
+        let ephemeral_datastore = ephemeral_datastore().await;
+        let datastore = Arc::new(ephemeral_datastore.datastore(MockClock::default()).await);
+        let leader_elections = LeaderElectionStates::default();
+        let election =
+            LeaderElection::new(datastore, &noop_meter(), "test", Duration::from_secs(60))
+                .with_states(&leader_elections);
+        assert!(election.try_acquire().await);
+
+        let (_, filter_handle) = reload::Layer::new(EnvFilter::new("info"));
+        let handler = zpages_handler(filter_handle, test_version_info(), leader_elections);
+
+        let mut test_conn = get("/leaderz").run_async(&handler).await;
+        assert_eq!(test_conn.status(), Some(Status::Ok));
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&take_response_body(&mut test_conn).await)
+                .unwrap(),
+            serde_json::json!({
+                "test": {
+                    "holder": election.holder(),
+                    "is_leader": true,
+                    "leader": election.holder(),
+                },
+            })
+        );
+    }
+
     #[tokio::test]
     async fn traceconfigz() {
         let (_filter, filter_handle) = reload::Layer::new(EnvFilter::new("info"));
-        let handler = zpages_handler(filter_handle);
+        let handler = zpages_handler(
+            filter_handle,
+            test_version_info(),
+            LeaderElectionStates::default(),
+        );
 
         let mut test_conn = get("/traceconfigz").run_async(&handler).await;
         assert_eq!(test_conn.status(), Some(Status::Ok));
@@ -575,7 +1191,11 @@ mod tests {
     async fn traceconfigz_dropped_filter() {
         // Drop the filter immediately but leave the handle open.
         let (_, filter_handle) = reload::Layer::new(EnvFilter::new("info"));
-        let handler = zpages_handler(filter_handle);
+        let handler = zpages_handler(
+            filter_handle,
+            test_version_info(),
+            LeaderElectionStates::default(),
+        );
 
         let mut test_conn = get("/traceconfigz").run_async(&handler).await;
         assert_eq!(test_conn.status(), Some(Status::InternalServerError));
@@ -659,6 +1279,7 @@ mod tests {
             connection_pool_timeouts_secs: 5,
             check_schema_version: false,
             tls_trust_store_path: Some("tests/tls_files/rootCA.pem".into()),
+            read_replica_url: None,
         };
         let pool = database_pool(&db_config, None).await.unwrap();
         let conn = pool.get().await.unwrap();
@@ -697,6 +1318,64 @@ mod tests {
         .unwrap();
     }
 
+    #[tokio::test]
+    async fn runtime_monitor_metrics() {
+        install_test_trace_subscriber();
+
+        assert!(build_runtime("janus-test", &RuntimeConfig { worker_threads: 0 }).is_err());
+        let runtime = build_runtime("janus-test", &RuntimeConfig { worker_threads: 1 }).unwrap();
+
+        let exporter = InMemoryMetricsExporter::default();
+        let reader = PeriodicReader::builder(exporter.clone(), Tokio).build();
+        let meter_provider = SdkMeterProvider::builder()
+            .with_reader(reader.clone())
+            .build();
+        let meter = meter_provider.meter("tests");
+
+        // The monitor measures the delay immediately, and then once per interval until stopped.
+        let stopper = Stopper::new();
+        let monitor_handle = tokio::spawn(monitor_runtime(
+            runtime.handle().clone(),
+            "test",
+            &meter,
+            stopper.clone(),
+        ));
+        sleep(Duration::from_millis(100)).await;
+        stopper.stop();
+        monitor_handle.await.unwrap();
+        runtime.shutdown_background();
+
+        spawn_blocking({
+            let meter_provider = meter_provider.clone();
+            move || {
+                meter_provider.force_flush().unwrap();
+            }
+        })
+        .await
+        .unwrap();
+        let finished_metrics = exporter.get_finished_metrics().unwrap();
+        let histogram = finished_metrics
+            .into_iter()
+            .flat_map(|rm| rm.scope_metrics.into_iter())
+            .flat_map(|sm| sm.metrics.into_iter())
+            .find(|metric| metric.name == "janus_runtime_scheduling_delay")
+            .unwrap();
+        let data_points = &histogram
+            .data
+            .as_any()
+            .downcast_ref::<Histogram<f64>>()
+            .unwrap()
+            .data_points;
+        assert_eq!(data_points.len(), 1);
+        assert!(data_points[0].count >= 1);
+
+        spawn_blocking(move || {
+            let _ = meter_provider.shutdown();
+        })
+        .await
+        .unwrap();
+    }
+
     async fn check_database_pool_gauges(
         meter_provider: &SdkMeterProvider,
         exporter: &InMemoryMetricsExporter,
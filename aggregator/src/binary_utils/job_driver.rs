@@ -2,6 +2,7 @@
 
 use anyhow::Context as _;
 use chrono::NaiveDateTime;
+use futures::future::BoxFuture;
 use janus_aggregator_core::datastore::{self, models::Lease};
 use janus_core::{time::Clock, Runtime};
 use opentelemetry::{
@@ -19,11 +20,16 @@ use tokio::{
     sync::{Semaphore, SemaphorePermit},
     time::{self, Instant},
 };
-use tracing::{debug, error, info_span, Instrument};
+use tracing::{debug, error, info_span, warn, Instrument};
 use trillium_tokio::Stopper;
 
+/// Releases the lease on a job, so that it may be promptly reacquired.
+type JobReleaser<AcquiredJob> = Box<
+    dyn Fn(Lease<AcquiredJob>) -> BoxFuture<'static, Result<(), datastore::Error>> + Send + Sync,
+>;
+
 /// Periodically seeks incomplete jobs in the datastore and drives them concurrently.
-pub struct JobDriver<C: Clock, R, JobAcquirer, JobStepper> {
+pub struct JobDriver<C: Clock, R, AcquiredJob, JobAcquirer, JobStepper> {
     /// Clock used to determine when to schedule jobs.
     clock: C,
     /// Runtime object used to spawn asynchronous tasks.
@@ -32,6 +38,8 @@ pub struct JobDriver<C: Clock, R, JobAcquirer, JobStepper> {
     meter: Meter,
     /// Stopper to signal when to shut down the job driver.
     stopper: Stopper,
+    /// Stopper used to cancel in-flight jobs, once the shutdown drain timeout elapses.
+    drain_stopper: Stopper,
 
     // Configuration values.
     /// The amount of time to wait between job acquisition attempts.
@@ -41,12 +49,17 @@ pub struct JobDriver<C: Clock, R, JobAcquirer, JobStepper> {
     /// Allowable clock skew between datastore and job driver, used when determining if a lease has
     /// expired.
     worker_lease_clock_skew_allowance: Duration,
+    /// How long to wait for in-flight jobs to complete after being stopped, before cancelling
+    /// them. If unset, in-flight jobs are always run to completion.
+    shutdown_drain_timeout: Option<Duration>,
 
     // Callbacks.
     /// Finds incomplete jobs in the datastore and acquires a lease on them.
     incomplete_job_acquirer: JobAcquirer,
     /// Steps an incomplete job.
     job_stepper: JobStepper,
+    /// Releases the lease on a job whose step was cancelled during shutdown.
+    job_releaser: Option<JobReleaser<AcquiredJob>>,
 }
 
 impl<
@@ -58,7 +71,7 @@ impl<
         JobStepper,
         JobStepperFuture,
         AcquiredJob,
-    > JobDriver<C, R, JobAcquirer, JobStepper>
+    > JobDriver<C, R, AcquiredJob, JobAcquirer, JobStepper>
 where
     C: Clock,
     R: Runtime + Send + Sync + 'static,
@@ -88,14 +101,39 @@ where
             runtime,
             meter,
             stopper,
+            drain_stopper: Stopper::new(),
             job_discovery_interval,
             max_concurrent_job_workers,
             worker_lease_clock_skew_allowance,
+            shutdown_drain_timeout: None,
             incomplete_job_acquirer,
             job_stepper,
+            job_releaser: None,
         })
     }
 
+    /// Bound how long the job driver waits for in-flight jobs to complete once it is stopped.
+    /// Jobs still being stepped when `drain_timeout` elapses are cancelled, and their leases are
+    /// released with `job_releaser`, so that another process can pick them up without waiting for
+    /// the leases to expire.
+    pub fn with_shutdown_drain_timeout<JobReleaserFn>(
+        self,
+        drain_timeout: Duration,
+        job_releaser: JobReleaserFn,
+    ) -> Self
+    where
+        JobReleaserFn: Fn(Lease<AcquiredJob>) -> BoxFuture<'static, Result<(), datastore::Error>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        Self {
+            shutdown_drain_timeout: Some(drain_timeout),
+            job_releaser: Some(Box::new(job_releaser)),
+            ..self
+        }
+    }
+
     /// Run this job driver, periodically seeking incomplete jobs and stepping them.
     pub async fn run(self: Arc<Self>) {
         // Create metric recorders.
@@ -111,6 +149,17 @@ where
             .with_description("Time spent stepping jobs.")
             .with_unit(Unit::new("s"))
             .init();
+        let expired_lease_counter = self
+            .meter
+            .u64_counter("janus_job_expired_leases")
+            .with_description(
+                "Count of jobs acquired after a previous lease on them expired without being \
+                 released, e.g. because stepping the job failed or the process holding the lease \
+                 stopped.",
+            )
+            .with_unit(Unit::new("{job}"))
+            .init();
+        expired_lease_counter.add(0, &[]);
 
         // Set up state for the job driver run.
         let sem = Arc::new(Semaphore::new(self.max_concurrent_job_workers));
@@ -129,16 +178,28 @@ where
                 .is_none()
             {
                 // Shut down when signalled via the stopper. Wait for all in-flight jobs to
-                // complete by acquiring all semaphore permits.
+                // complete by acquiring all semaphore permits. If the drain timeout elapses first,
+                // cancel the remaining jobs, and wait for them to release their leases.
                 //
                 // Unwrap safety: The constructor checks that max_concurrent_job_workers can be
                 // converted to a u32.
                 // Unwrap safety: Semaphore::acquire is documented as only returning an error if the
                 // semaphore is closed, and we never close this semaphore.
-                let _: SemaphorePermit<'_> = sem
-                    .acquire_many(u32::try_from(self.max_concurrent_job_workers).unwrap())
-                    .await
-                    .unwrap();
+                let permit_count = u32::try_from(self.max_concurrent_job_workers).unwrap();
+                if let Some(drain_timeout) = self.shutdown_drain_timeout {
+                    if time::timeout(drain_timeout, sem.acquire_many(permit_count))
+                        .await
+                        .is_err()
+                    {
+                        warn!(
+                            in_flight_jobs =
+                                self.max_concurrent_job_workers - sem.available_permits(),
+                            "Timed out waiting for in-flight jobs to complete, cancelling them"
+                        );
+                        self.drain_stopper.stop();
+                    }
+                }
+                let _: SemaphorePermit<'_> = sem.acquire_many(permit_count).await.unwrap();
                 break;
             }
 
@@ -163,7 +224,11 @@ where
             let max_acquire_count = sem.available_permits();
             let start = Instant::now();
             debug!(%max_acquire_count, "Acquiring jobs");
-            let leases = match (self.incomplete_job_acquirer)(max_acquire_count).await {
+            let acquire_span = info_span!("Job acquirer", %max_acquire_count);
+            let leases = match (self.incomplete_job_acquirer)(max_acquire_count)
+                .instrument(acquire_span.clone())
+                .await
+            {
                 Ok(leases) => {
                     job_acquire_time_histogram.record(
                         start.elapsed().as_secs_f64(),
@@ -182,6 +247,13 @@ where
                             max_acquire_count
                         );
                         debug!(acquired_job_count = leases.len(), "Acquired jobs");
+                        // Releasing a lease resets its attempt count, so any lease on its second
+                        // or later attempt was taken over from a lease that expired.
+                        let expired_lease_count = leases
+                            .iter()
+                            .filter(|lease| lease.lease_attempts() > 1)
+                            .count();
+                        expired_lease_counter.add(u64::try_from(expired_lease_count).unwrap(), &[]);
                         next_run_instant = Instant::now();
                         leases
                     }
@@ -212,7 +284,16 @@ where
                     // Unwrap safety: we have seen that at least `leases.len()` permits are
                     // available previously in the outer loop, and this task is the only task that
                     // acquires permits.
-                    let span = info_span!("Job stepper", acquired_job = ?lease.leased());
+                    //
+                    // Each job is stepped in its own trace, which links back to the acquisition
+                    // shared with the other jobs acquired alongside it.
+                    let span = info_span!(
+                        parent: None,
+                        "Job stepper",
+                        acquired_job = ?lease.leased(),
+                        lease_attempts = lease.lease_attempts(),
+                    );
+                    span.follows_from(&acquire_span);
                     let (this, permit, job_step_time_histogram) = (
                         Arc::clone(&self),
                         Arc::clone(&sem).try_acquire_owned().unwrap(),
@@ -222,21 +303,32 @@ where
                     async move {
                         debug!(lease_expiry = %lease.lease_expiry_time(), "Stepping job");
                         let (start, mut status) = (Instant::now(), "success");
-                        match time::timeout(
-                            this.effective_lease_duration(lease.lease_expiry_time()),
-                            (this.job_stepper)(lease),
-                        )
-                        .await
+                        match this
+                            .drain_stopper
+                            .stop_future(time::timeout(
+                                this.effective_lease_duration(lease.lease_expiry_time()),
+                                (this.job_stepper)(lease.clone()),
+                            ))
+                            .await
                         {
-                            Ok(Ok(_)) => debug!("Job stepped"),
-                            Ok(Err(error)) => {
+                            Some(Ok(Ok(_))) => debug!("Job stepped"),
+                            Some(Ok(Err(error))) => {
                                 error!(?error, "Couldn't step job");
                                 status = "error"
                             }
-                            Err(_err) => {
+                            Some(Err(_err)) => {
                                 error!("Stepping job timed out");
                                 status = "error"
                             }
+                            None => {
+                                warn!("Stepping job cancelled by shutdown");
+                                status = "cancelled";
+                                if let Some(job_releaser) = &this.job_releaser {
+                                    if let Err(error) = job_releaser(lease).await {
+                                        error!(?error, "Couldn't release lease on cancelled job");
+                                    }
+                                }
+                            }
                         }
                         job_step_time_histogram.record(
                             start.elapsed().as_secs_f64(),
@@ -281,8 +373,8 @@ mod tests {
     };
     use janus_messages::{AggregationJobId, TaskId};
     use rand::random;
-    use std::{sync::Arc, time::Duration};
-    use tokio::sync::Mutex;
+    use std::{future::pending, sync::Arc, time::Duration};
+    use tokio::sync::{mpsc, Mutex};
     use trillium_tokio::Stopper;
 
     #[tokio::test]
@@ -473,4 +565,78 @@ mod tests {
             ])
         );
     }
+
+    #[tokio::test]
+    async fn job_driver_shutdown_drain_timeout() {
+        install_test_trace_subscriber();
+        let clock = MockClock::default();
+        let mut runtime_manager = TestRuntimeManager::new();
+        let stopper = Stopper::new();
+
+        let job = (
+            random::<TaskId>(),
+            VdafInstance::Fake,
+            random::<AggregationJobId>(),
+        );
+        let lease_expiry = DateTime::<Utc>::from_timestamp(1_000_003_600, 0)
+            .unwrap()
+            .naive_utc();
+        let (started_sender, mut started_receiver) = mpsc::unbounded_channel();
+        let released_jobs = Arc::new(Mutex::new(Vec::new()));
+
+        let job_driver = Arc::new(
+            JobDriver::new(
+                clock,
+                runtime_manager.with_label("stepper"),
+                noop_meter(),
+                stopper.clone(),
+                Duration::from_secs(1),
+                10,
+                Duration::from_secs(60),
+                {
+                    let (acquired, job) = (Arc::new(Mutex::new(false)), job.clone());
+                    move |_| {
+                        let (acquired, job) = (Arc::clone(&acquired), job.clone());
+                        async move {
+                            // Hand out the job only once.
+                            let mut acquired = acquired.lock().await;
+                            if *acquired {
+                                return Ok(Vec::new());
+                            }
+                            *acquired = true;
+                            Ok(Vec::from([Lease::new_dummy(job, lease_expiry)]))
+                        }
+                    }
+                },
+                move |_| {
+                    let started_sender = started_sender.clone();
+                    async move {
+                        // Simulate a job that never finishes stepping.
+                        started_sender.send(()).unwrap();
+                        pending::<Result<(), datastore::Error>>().await
+                    }
+                },
+            )
+            .unwrap()
+            .with_shutdown_drain_timeout(Duration::from_millis(100), {
+                let released_jobs = Arc::clone(&released_jobs);
+                move |lease| {
+                    let released_jobs = Arc::clone(&released_jobs);
+                    Box::pin(async move {
+                        released_jobs.lock().await.push(lease.leased().clone());
+                        Ok(())
+                    })
+                }
+            }),
+        );
+        let task_handle = runtime_manager.with_label("driver").spawn(job_driver.run());
+
+        // Wait for the job to start being stepped, then stop the job driver. It should give up on
+        // the job once the drain timeout elapses, and release the job's lease.
+        started_receiver.recv().await.unwrap();
+        stopper.stop();
+        task_handle.await.unwrap();
+
+        assert_eq!(*released_jobs.lock().await, Vec::from([job]));
+    }
 }
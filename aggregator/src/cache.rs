@@ -82,7 +82,7 @@ impl GlobalHpkeKeypairCache {
         keypairs: &StdMutex<HpkeKeypairs>,
     ) -> Result<(), Error> {
         let global_keypairs = datastore
-            .run_tx("refresh_global_hpke_keypairs_cache", |tx| {
+            .run_read_only_tx("refresh_global_hpke_keypairs_cache", |tx| {
                 Box::pin(async move { tx.get_global_hpke_keypairs().await })
             })
             .await?;
@@ -0,0 +1,91 @@
+//! Authentication to cloud provider APIs, shared by the KMS clients, secret store clients, and
+//! report and result sinks. Requests to GCP and Azure carry an OAuth access token fetched from the
+//! instance metadata service, while requests to AWS are signed with AWS Signature Version 4.
+
+#[cfg(any(
+    feature = "gcp-kms",
+    feature = "azure-key-vault",
+    feature = "gcp-pubsub",
+    feature = "gcp-bigquery",
+    feature = "gcp-secret-manager"
+))]
+use {
+    anyhow::{Context, Result},
+    serde::Deserialize,
+    url::Url,
+};
+
+#[cfg(any(feature = "aws-kms", feature = "aws-secrets-manager"))]
+pub(crate) mod aws;
+
+/// URL from which access tokens for a GCP instance's default service account are fetched.
+#[cfg(any(
+    feature = "gcp-kms",
+    feature = "gcp-pubsub",
+    feature = "gcp-bigquery",
+    feature = "gcp-secret-manager"
+))]
+pub(crate) const GCP_METADATA_TOKEN_URL: &str =
+    "http://metadata.google.internal/computeMetadata/v1/instance/service-accounts/default/token";
+
+/// URL from which access tokens for Azure Key Vault are fetched for the resource's managed
+/// identity.
+#[cfg(feature = "azure-key-vault")]
+pub(crate) const AZURE_KEY_VAULT_METADATA_TOKEN_URL: &str =
+    "http://169.254.169.254/metadata/identity/oauth2/token\
+     ?api-version=2018-02-01&resource=https%3A%2F%2Fvault.azure.net";
+
+/// Fetches an OAuth access token from the GCP metadata service at `token_url`.
+#[cfg(any(
+    feature = "gcp-kms",
+    feature = "gcp-pubsub",
+    feature = "gcp-bigquery",
+    feature = "gcp-secret-manager"
+))]
+pub(crate) async fn fetch_gcp_access_token(
+    http_client: &reqwest::Client,
+    token_url: Url,
+) -> Result<String> {
+    fetch_metadata_access_token(http_client, token_url, ("Metadata-Flavor", "Google")).await
+}
+
+/// Fetches an OAuth access token from the Azure instance metadata service at `token_url`.
+#[cfg(feature = "azure-key-vault")]
+pub(crate) async fn fetch_azure_access_token(
+    http_client: &reqwest::Client,
+    token_url: Url,
+) -> Result<String> {
+    fetch_metadata_access_token(http_client, token_url, ("Metadata", "true")).await
+}
+
+/// Fetches an OAuth access token from a cloud provider's instance metadata service.
+#[cfg(any(
+    feature = "gcp-kms",
+    feature = "azure-key-vault",
+    feature = "gcp-pubsub",
+    feature = "gcp-bigquery",
+    feature = "gcp-secret-manager"
+))]
+async fn fetch_metadata_access_token(
+    http_client: &reqwest::Client,
+    token_url: Url,
+    header: (&str, &str),
+) -> Result<String> {
+    #[derive(Deserialize)]
+    struct TokenResponse {
+        access_token: String,
+    }
+
+    let response: TokenResponse = http_client
+        .get(token_url)
+        .header(header.0, header.1)
+        .send()
+        .await
+        .context("couldn't fetch access token from metadata service")?
+        .error_for_status()
+        .context("metadata service returned an error")?
+        .json()
+        .await
+        .context("couldn't parse metadata service response")?;
+    Ok(response.access_token)
+}
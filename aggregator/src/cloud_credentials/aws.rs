@@ -0,0 +1,274 @@
+//! Client for AWS services using the JSON protocol. Requests are signed with [AWS Signature
+//! Version 4][1].
+//!
+//! [1]: https://docs.aws.amazon.com/IAM/latest/UserGuide/reference_aws-signing.html
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Datelike, NaiveDateTime, Timelike};
+use ring::{
+    digest::{digest, SHA256},
+    hmac,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    env,
+    fmt::{self, Debug, Formatter},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use url::Url;
+
+/// Credentials used to sign requests to AWS.
+#[derive(Clone)]
+pub(crate) struct AwsCredentials {
+    access_key_id: String,
+    secret_access_key: String,
+    session_token: Option<String>,
+}
+
+impl Debug for AwsCredentials {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AwsCredentials")
+            .field("access_key_id", &self.access_key_id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl AwsCredentials {
+    /// Reads credentials from the standard AWS environment variables.
+    pub(crate) fn from_env() -> Result<Self> {
+        Ok(Self {
+            access_key_id: env::var("AWS_ACCESS_KEY_ID")
+                .context("AWS_ACCESS_KEY_ID must be set to authenticate to AWS")?,
+            secret_access_key: env::var("AWS_SECRET_ACCESS_KEY")
+                .context("AWS_SECRET_ACCESS_KEY must be set to authenticate to AWS")?,
+            session_token: env::var("AWS_SESSION_TOKEN").ok(),
+        })
+    }
+
+    /// Returns the example credentials used in the AWS documentation and signing test suite.
+    #[cfg(test)]
+    pub(crate) fn example() -> Self {
+        Self {
+            access_key_id: "AKIDEXAMPLE".to_string(),
+            secret_access_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".to_string(),
+            session_token: None,
+        }
+    }
+
+    /// Returns these credentials, with the given session token.
+    #[cfg(test)]
+    pub(crate) fn with_session_token(self, session_token: String) -> Self {
+        Self {
+            session_token: Some(session_token),
+            ..self
+        }
+    }
+}
+
+/// A client for an AWS service using the JSON protocol, which signs each request.
+pub(crate) struct AwsClient {
+    http_client: reqwest::Client,
+    service: &'static str,
+    endpoint: Url,
+    host: String,
+    region: String,
+    credentials: AwsCredentials,
+}
+
+impl AwsClient {
+    /// Constructs a client for `service` in `region`. If `endpoint` is not given, the service's
+    /// regional endpoint is used.
+    pub(crate) fn new(
+        http_client: reqwest::Client,
+        service: &'static str,
+        region: String,
+        endpoint: Option<Url>,
+        credentials: AwsCredentials,
+    ) -> Result<Self> {
+        let endpoint = match endpoint {
+            Some(endpoint) => endpoint,
+            None => format!("https://{service}.{region}.amazonaws.com/")
+                .parse()
+                .context("invalid AWS region")?,
+        };
+        let host = match (endpoint.host_str(), endpoint.port()) {
+            (Some(host), Some(port)) => format!("{host}:{port}"),
+            (Some(host), None) => host.to_string(),
+            (None, _) => return Err(anyhow!("AWS {service} endpoint {endpoint} has no host")),
+        };
+        Ok(Self {
+            http_client,
+            service,
+            endpoint,
+            host,
+            region,
+            credentials,
+        })
+    }
+
+    /// Invokes the operation named by `target`, e.g. `TrentService.Decrypt`.
+    pub(crate) async fn call<Req: Serialize, Resp: DeserializeOwned>(
+        &self,
+        target: &str,
+        request: &Req,
+    ) -> Result<Resp> {
+        let body = serde_json::to_vec(request)?;
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?;
+        // Unwrap safety: the current time is representable.
+        let now = DateTime::from_timestamp(now.as_secs().try_into()?, 0)
+            .unwrap()
+            .naive_utc();
+
+        let mut headers = Vec::from([
+            (
+                "content-type".to_string(),
+                "application/x-amz-json-1.1".to_string(),
+            ),
+            ("host".to_string(), self.host.clone()),
+            ("x-amz-date".to_string(), amz_date(&now)),
+            ("x-amz-target".to_string(), target.to_string()),
+        ]);
+        if let Some(session_token) = &self.credentials.session_token {
+            headers.push(("x-amz-security-token".to_string(), session_token.clone()));
+        }
+        let authorization = authorization_header(
+            &self.credentials,
+            &self.region,
+            self.service,
+            "POST",
+            self.endpoint.path(),
+            &headers,
+            &body,
+            &now,
+        );
+
+        let mut request = self.http_client.post(self.endpoint.clone());
+        for (name, value) in headers {
+            // The HTTP client sets the Host header itself.
+            if name != "host" {
+                request = request.header(name, value);
+            }
+        }
+        request
+            .header("authorization", authorization)
+            .body(body)
+            .send()
+            .await
+            .with_context(|| format!("couldn't send request to AWS {}", self.service))?
+            .error_for_status()
+            .with_context(|| format!("AWS {} returned an error", self.service))?
+            .json()
+            .await
+            .with_context(|| format!("couldn't parse AWS {} response", self.service))
+    }
+}
+
+/// Formats a timestamp in the ISO 8601 basic format used by AWS.
+fn amz_date(time: &NaiveDateTime) -> String {
+    format!(
+        "{}T{:02}{:02}{:02}Z",
+        date_stamp(time),
+        time.hour(),
+        time.minute(),
+        time.second()
+    )
+}
+
+/// Formats the date of a timestamp as used in the credential scope.
+fn date_stamp(time: &NaiveDateTime) -> String {
+    format!("{:04}{:02}{:02}", time.year(), time.month(), time.day())
+}
+
+/// Computes the value of the Authorization header for a request, per AWS Signature Version 4.
+/// `headers` must include the Host and X-Amz-Date headers, with lowercase names. The request must
+/// not have a query string.
+#[allow(clippy::too_many_arguments)]
+fn authorization_header(
+    credentials: &AwsCredentials,
+    region: &str,
+    service: &str,
+    method: &str,
+    path: &str,
+    headers: &[(String, String)],
+    payload: &[u8],
+    time: &NaiveDateTime,
+) -> String {
+    let mut headers: Vec<_> = headers
+        .iter()
+        .map(|(name, value)| (name.as_str(), value.trim()))
+        .collect();
+    headers.sort();
+    let mut canonical_headers = String::new();
+    for (name, value) in &headers {
+        canonical_headers.push_str(&format!("{name}:{value}\n"));
+    }
+    let signed_headers = headers
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>()
+        .join(";");
+
+    let canonical_request = format!(
+        "{method}\n{path}\n\n{canonical_headers}\n{signed_headers}\n{}",
+        hex::encode(digest(&SHA256, payload))
+    );
+    let credential_scope = format!("{}/{region}/{service}/aws4_request", date_stamp(time));
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{credential_scope}\n{}",
+        amz_date(time),
+        hex::encode(digest(&SHA256, canonical_request.as_bytes()))
+    );
+
+    let signing_key = [date_stamp(time).as_str(), region, service, "aws4_request"]
+        .iter()
+        .fold(
+            format!("AWS4{}", credentials.secret_access_key).into_bytes(),
+            |key, data| {
+                hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, &key), data.as_bytes())
+                    .as_ref()
+                    .to_vec()
+            },
+        );
+    let signature = hmac::sign(
+        &hmac::Key::new(hmac::HMAC_SHA256, &signing_key),
+        string_to_sign.as_bytes(),
+    );
+
+    format!(
+        "AWS4-HMAC-SHA256 Credential={}/{credential_scope}, SignedHeaders={signed_headers}, \
+         Signature={}",
+        credentials.access_key_id,
+        hex::encode(signature),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cloud_credentials::aws::{authorization_header, AwsCredentials};
+    use chrono::NaiveDateTime;
+
+    #[test]
+    fn signature_test_vector() {
+        // The "get-vanilla" test case from the AWS Signature Version 4 test suite.
+        let time = NaiveDateTime::parse_from_str("20150830T123600Z", "%Y%m%dT%H%M%SZ").unwrap();
+        assert_eq!(
+            authorization_header(
+                &AwsCredentials::example(),
+                "us-east-1",
+                "service",
+                "GET",
+                "/",
+                &[
+                    ("host".to_string(), "example.amazonaws.com".to_string()),
+                    ("x-amz-date".to_string(), "20150830T123600Z".to_string()),
+                ],
+                b"",
+                &time,
+            ),
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, \
+             SignedHeaders=host;x-amz-date, \
+             Signature=5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31"
+        );
+    }
+}
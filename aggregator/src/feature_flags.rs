@@ -0,0 +1,264 @@
+//! Runtime feature flags, which gate risky new behaviors so that they can be enabled gradually,
+//! for a whole deployment or for individual tasks, without redeploying.
+//!
+//! A flag's state is resolved from the following sources, in order of precedence:
+//!
+//! 1. A flag stored in the datastore for the specific task.
+//! 2. A flag stored in the datastore for all tasks.
+//! 3. The `feature_flags` section of the binary's configuration file.
+//! 4. The flag's default.
+//!
+//! Flags stored in the datastore are managed with `janus_cli`, and are cached in memory by each
+//! process, so changes take effect within one refresh interval.
+
+use janus_aggregator_core::datastore::{self, models::FeatureFlag, Datastore};
+use janus_core::time::Clock;
+use janus_messages::TaskId;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex as StdMutex},
+    time::{Duration as StdDuration, Instant},
+};
+use tokio::{spawn, task::JoinHandle, time::sleep};
+use tracing::{debug, error};
+
+/// A feature flag known to Janus, along with its state if it is not set anywhere.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Flag {
+    name: &'static str,
+    default: bool,
+}
+
+impl Flag {
+    /// Whether the garbage collector deletes aggregation jobs once they have been in a terminal
+    /// state for the configured `aggregation_job_ttl_s`.
+    pub const GC_DELETE_TERMINAL_AGGREGATION_JOBS: Self = Self {
+        name: "gc_delete_terminal_aggregation_jobs",
+        default: true,
+    };
+
+    /// Returns the name of the flag.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns the state of the flag if it is not set anywhere.
+    pub fn default(&self) -> bool {
+        self.default
+    }
+}
+
+/// Feature flag configuration.
+///
+/// # Examples
+///
+/// ```
+/// use janus_aggregator::feature_flags::FeatureFlagsConfig;
+///
+/// let yaml_config = r#"
+/// ---
+/// flags:
+///   gc_delete_terminal_aggregation_jobs: false
+/// refresh_interval_secs: 30
+/// "#;
+///
+/// let _decoded: FeatureFlagsConfig = serde_yaml::from_str(yaml_config).unwrap();
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct FeatureFlagsConfig {
+    /// States of flags for all tasks, keyed by flag name. These are overridden by flags stored in
+    /// the datastore.
+    #[serde(default)]
+    pub flags: HashMap<String, bool>,
+
+    /// How often, in seconds, flags stored in the datastore are reloaded.
+    #[serde(default = "FeatureFlagsConfig::default_refresh_interval_secs")]
+    pub refresh_interval_secs: u64,
+}
+
+impl FeatureFlagsConfig {
+    fn default_refresh_interval_secs() -> u64 {
+        60
+    }
+}
+
+impl Default for FeatureFlagsConfig {
+    fn default() -> Self {
+        Self {
+            flags: HashMap::new(),
+            refresh_interval_secs: Self::default_refresh_interval_secs(),
+        }
+    }
+}
+
+/// Flags stored in the datastore, keyed by flag name, and then by the task they apply to, if any.
+type StoredFlags = HashMap<String, HashMap<Option<TaskId>, bool>>;
+
+/// Resolves the state of feature flags. Flags stored in the datastore are cached, and refreshed
+/// periodically.
+#[derive(Debug)]
+pub struct FeatureFlags {
+    /// Flags from the configuration file.
+    configured_flags: HashMap<String, bool>,
+
+    // We use a std::sync::Mutex in this cache because we won't hold locks across `.await`
+    // boundaries.
+    /// Cache of flags stored in the datastore.
+    stored_flags: Arc<StdMutex<StoredFlags>>,
+
+    /// Handle for task responsible for periodically refreshing the cache, if any.
+    refresh_handle: Option<JoinHandle<()>>,
+}
+
+impl FeatureFlags {
+    /// Loads flags from the datastore, and starts a task that reloads them every
+    /// `refresh_interval_secs`.
+    pub async fn new<C: Clock>(
+        datastore: Arc<Datastore<C>>,
+        config: &FeatureFlagsConfig,
+    ) -> Result<Self, datastore::Error> {
+        let stored_flags = Arc::new(StdMutex::new(HashMap::new()));
+
+        // Initial cache load.
+        Self::refresh_inner(&datastore, &stored_flags).await?;
+
+        // Start refresh task.
+        let refresh_interval = StdDuration::from_secs(config.refresh_interval_secs);
+        let refresh_stored_flags = Arc::clone(&stored_flags);
+        let refresh_handle = spawn(async move {
+            loop {
+                sleep(refresh_interval).await;
+
+                let now = Instant::now();
+                let result = Self::refresh_inner(&datastore, &refresh_stored_flags).await;
+                let elapsed = now.elapsed();
+
+                match result {
+                    Ok(_) => debug!(?elapsed, "successfully refreshed feature flag cache"),
+                    Err(err) => error!(?err, ?elapsed, "failed to refresh feature flag cache"),
+                }
+            }
+        });
+
+        Ok(Self {
+            configured_flags: config.flags.clone(),
+            stored_flags,
+            refresh_handle: Some(refresh_handle),
+        })
+    }
+
+    /// Creates a set of feature flags from the given configured flags alone, ignoring the
+    /// datastore. This is intended for tests, and for components that run without a datastore.
+    pub fn from_config(config: &FeatureFlagsConfig) -> Self {
+        Self {
+            configured_flags: config.flags.clone(),
+            stored_flags: Arc::new(StdMutex::new(HashMap::new())),
+            refresh_handle: None,
+        }
+    }
+
+    async fn refresh_inner<C: Clock>(
+        datastore: &Datastore<C>,
+        stored_flags: &StdMutex<StoredFlags>,
+    ) -> Result<(), datastore::Error> {
+        let feature_flags = datastore
+            .run_tx("refresh_feature_flag_cache", |tx| {
+                Box::pin(async move { tx.get_feature_flags().await })
+            })
+            .await?;
+
+        let new_stored_flags = Self::index(feature_flags);
+        *stored_flags.lock().unwrap() = new_stored_flags;
+        Ok(())
+    }
+
+    fn index(feature_flags: Vec<FeatureFlag>) -> StoredFlags {
+        let mut stored_flags = StoredFlags::new();
+        for feature_flag in feature_flags {
+            stored_flags
+                .entry(feature_flag.name().to_string())
+                .or_default()
+                .insert(feature_flag.task_id().copied(), feature_flag.enabled());
+        }
+        stored_flags
+    }
+
+    /// Returns whether the given flag is enabled, either for the given task, or for all tasks if
+    /// `task_id` is `None`.
+    pub fn is_enabled(&self, flag: &Flag, task_id: Option<&TaskId>) -> bool {
+        {
+            let stored_flags = self.stored_flags.lock().unwrap();
+            if let Some(stored_flag) = stored_flags.get(flag.name()) {
+                if let Some(enabled) = task_id
+                    .and_then(|task_id| stored_flag.get(&Some(*task_id)))
+                    .or_else(|| stored_flag.get(&None))
+                {
+                    return *enabled;
+                }
+            }
+        }
+        self.configured_flags
+            .get(flag.name())
+            .copied()
+            .unwrap_or(flag.default())
+    }
+}
+
+impl Drop for FeatureFlags {
+    fn drop(&mut self) {
+        if let Some(refresh_handle) = &self.refresh_handle {
+            refresh_handle.abort()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::feature_flags::{FeatureFlags, FeatureFlagsConfig, Flag};
+    use janus_aggregator_core::datastore::models::FeatureFlag;
+    use janus_messages::TaskId;
+    use rand::random;
+    use std::collections::HashMap;
+
+    #[test]
+    fn flag_precedence() {
+        let flag = Flag {
+            name: "flag",
+            default: false,
+        };
+        let other_flag = Flag {
+            name: "other_flag",
+            default: true,
+        };
+        let task_id: TaskId = random();
+        let other_task_id: TaskId = random();
+
+        // Defaults apply when the flag is not set anywhere.
+        let feature_flags = FeatureFlags::from_config(&FeatureFlagsConfig::default());
+        assert!(!feature_flags.is_enabled(&flag, None));
+        assert!(!feature_flags.is_enabled(&flag, Some(&task_id)));
+        assert!(feature_flags.is_enabled(&other_flag, Some(&task_id)));
+
+        // Configured flags override defaults.
+        let feature_flags = FeatureFlags::from_config(&FeatureFlagsConfig {
+            flags: HashMap::from([("flag".to_string(), true)]),
+            ..Default::default()
+        });
+        assert!(feature_flags.is_enabled(&flag, None));
+        assert!(feature_flags.is_enabled(&flag, Some(&task_id)));
+
+        // Stored global flags override configured flags, and stored per-task flags override
+        // stored global flags.
+        *feature_flags.stored_flags.lock().unwrap() = FeatureFlags::index(Vec::from([
+            FeatureFlag::new("flag".to_string(), None, false),
+            FeatureFlag::new("flag".to_string(), Some(task_id), true),
+        ]));
+        assert!(!feature_flags.is_enabled(&flag, None));
+        assert!(feature_flags.is_enabled(&flag, Some(&task_id)));
+        assert!(!feature_flags.is_enabled(&flag, Some(&other_task_id)));
+        assert!(feature_flags.is_enabled(&other_flag, Some(&task_id)));
+    }
+}
@@ -0,0 +1,371 @@
+//! Support for datastore keys that are encrypted ("wrapped") under a key held by an external key
+//! management service (KMS), so that raw datastore key material need not be present in
+//! configuration files or environment variables. Wrapped keys are decrypted ("unwrapped") by the
+//! KMS at startup, and again whenever the process receives a SIGHUP signal.
+
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use ring::aead::LessSafeKey;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tokio::fs;
+use url::Url;
+
+#[cfg(feature = "aws-kms")]
+mod aws;
+#[cfg(feature = "azure-key-vault")]
+mod azure;
+#[cfg(feature = "gcp-kms")]
+mod gcp;
+
+/// Configuration for datastore keys that are wrapped by a KMS.
+///
+/// # Examples
+///
+/// ```
+/// use janus_aggregator::kms::DatastoreKeyEncryptionConfig;
+///
+/// let yaml_config = r#"
+/// ---
+/// type: gcp_kms
+/// key_name: projects/example/locations/global/keyRings/janus/cryptoKeys/datastore-keys
+/// wrapped_keys_file: /etc/janus/wrapped-datastore-keys
+/// "#;
+///
+/// let _decoded: DatastoreKeyEncryptionConfig = serde_yaml::from_str(yaml_config).unwrap();
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct DatastoreKeyEncryptionConfig {
+    /// The KMS holding the key that wraps the datastore keys.
+    #[serde(flatten)]
+    pub kms: KmsConfig,
+
+    /// Path to a file containing the wrapped datastore keys, encoded in unpadded url-safe base64,
+    /// and separated by commas or whitespace. The file is read again each time the keys are
+    /// unwrapped, so it may be updated in place (e.g. by mounting a Kubernetes secret) to rotate
+    /// keys. If not set, the wrapped keys are taken from the configured secret store, or from the
+    /// `DATASTORE_KEYS` environment variable or `--datastore-keys` command line argument.
+    #[serde(default)]
+    pub wrapped_keys_file: Option<PathBuf>,
+}
+
+/// Selection of a KMS, and of the key within it that wraps the datastore keys.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum KmsConfig {
+    /// AWS Key Management Service. Requests are authenticated with credentials taken from the
+    /// `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY`, and (optionally) `AWS_SESSION_TOKEN`
+    /// environment variables.
+    AwsKms {
+        /// The AWS region of the KMS key, e.g. `us-west-2`.
+        region: String,
+        /// The key ID, key ARN, alias name, or alias ARN of the KMS key.
+        key_id: String,
+        /// Base URL of the KMS API. Defaults to `https://kms.{region}.amazonaws.com/`.
+        #[serde(default)]
+        endpoint: Option<Url>,
+    },
+
+    /// Google Cloud Key Management Service. Requests are authenticated with an access token for
+    /// the instance's default service account, fetched from the GCE metadata server.
+    GcpKms {
+        /// Resource name of the symmetric encryption key, of the form
+        /// `projects/*/locations/*/keyRings/*/cryptoKeys/*`.
+        key_name: String,
+        /// Base URL of the Cloud KMS API. Defaults to `https://cloudkms.googleapis.com/`.
+        #[serde(default)]
+        endpoint: Option<Url>,
+    },
+
+    /// Azure Key Vault. Requests are authenticated with an access token for the resource's
+    /// managed identity, fetched from the Azure Instance Metadata Service.
+    AzureKeyVault {
+        /// URL of the key vault, e.g. `https://example.vault.azure.net/`.
+        vault_url: Url,
+        /// Name of the key in the vault.
+        key_name: String,
+        /// Version of the key that wrapped the datastore keys.
+        key_version: String,
+        /// Key wrapping algorithm used to wrap the datastore keys. Defaults to `RSA-OAEP-256`.
+        #[serde(default = "default_azure_key_wrap_algorithm")]
+        algorithm: String,
+    },
+}
+
+fn default_azure_key_wrap_algorithm() -> String {
+    "RSA-OAEP-256".to_string()
+}
+
+/// User agent sent with requests to KMS APIs.
+#[cfg(any(feature = "aws-kms", feature = "gcp-kms", feature = "azure-key-vault"))]
+const CLIENT_USER_AGENT: &str = concat!(
+    env!("CARGO_PKG_NAME"),
+    "/",
+    env!("CARGO_PKG_VERSION"),
+    "/kms",
+);
+
+/// Timeout applied to each request to a KMS API or metadata service.
+#[cfg(any(feature = "aws-kms", feature = "gcp-kms", feature = "azure-key-vault"))]
+const REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// A client for a KMS, able to unwrap datastore keys.
+#[async_trait]
+trait Kms: Send + Sync {
+    /// Unwraps a single wrapped key, returning the raw key material.
+    async fn unwrap_key(&self, wrapped_key: &[u8]) -> Result<Vec<u8>>;
+}
+
+/// Unwraps datastore keys using a KMS.
+pub struct DatastoreKeyUnwrapper {
+    kms: Box<dyn Kms>,
+    wrapped_keys_file: Option<PathBuf>,
+}
+
+impl DatastoreKeyUnwrapper {
+    /// Constructs an unwrapper for the given configuration.
+    pub fn new(config: &DatastoreKeyEncryptionConfig) -> Result<Self> {
+        #[cfg(any(feature = "aws-kms", feature = "gcp-kms", feature = "azure-key-vault"))]
+        let http_client = reqwest::Client::builder()
+            .user_agent(CLIENT_USER_AGENT)
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .context("couldn't create HTTP client")?;
+
+        let kms: Result<Box<dyn Kms>> = match &config.kms {
+            #[cfg(feature = "aws-kms")]
+            KmsConfig::AwsKms {
+                region,
+                key_id,
+                endpoint,
+            } => Ok(Box::new(aws::AwsKms::new(
+                http_client,
+                region.clone(),
+                key_id.clone(),
+                endpoint.clone(),
+                crate::cloud_credentials::aws::AwsCredentials::from_env()?,
+            )?)),
+            #[cfg(not(feature = "aws-kms"))]
+            KmsConfig::AwsKms { .. } => Err(anyhow!(
+                "AWS KMS datastore key encryption was enabled in the configuration file, but \
+                 support was not enabled at compile time. Rebuild with `--features aws-kms`."
+            )),
+
+            #[cfg(feature = "gcp-kms")]
+            KmsConfig::GcpKms { key_name, endpoint } => Ok(Box::new(gcp::GcpKms::new(
+                http_client,
+                key_name,
+                endpoint.clone(),
+                crate::cloud_credentials::GCP_METADATA_TOKEN_URL
+                    .parse()
+                    .unwrap(),
+            )?)),
+            #[cfg(not(feature = "gcp-kms"))]
+            KmsConfig::GcpKms { .. } => Err(anyhow!(
+                "GCP KMS datastore key encryption was enabled in the configuration file, but \
+                 support was not enabled at compile time. Rebuild with `--features gcp-kms`."
+            )),
+
+            #[cfg(feature = "azure-key-vault")]
+            KmsConfig::AzureKeyVault {
+                vault_url,
+                key_name,
+                key_version,
+                algorithm,
+            } => Ok(Box::new(azure::AzureKeyVault::new(
+                http_client,
+                vault_url,
+                key_name,
+                key_version,
+                algorithm.clone(),
+                crate::cloud_credentials::AZURE_KEY_VAULT_METADATA_TOKEN_URL
+                    .parse()
+                    .unwrap(),
+            )?)),
+            #[cfg(not(feature = "azure-key-vault"))]
+            KmsConfig::AzureKeyVault { .. } => Err(anyhow!(
+                "Azure Key Vault datastore key encryption was enabled in the configuration \
+                 file, but support was not enabled at compile time. Rebuild with `--features \
+                 azure-key-vault`."
+            )),
+        };
+
+        Ok(Self::with_kms(kms?, config.wrapped_keys_file.clone()))
+    }
+
+    fn with_kms(kms: Box<dyn Kms>, wrapped_keys_file: Option<PathBuf>) -> Self {
+        Self {
+            kms,
+            wrapped_keys_file,
+        }
+    }
+
+    /// Unwraps the datastore keys. `wrapped_keys` holds the wrapped keys provided via the secret
+    /// store, command line arguments, or environment variables, and is only used if the
+    /// configuration does not specify a file from which to read wrapped keys. The order of the
+    /// keys is preserved, so the first key remains the primary key.
+    pub async fn unwrap_keys(&self, wrapped_keys: &[String]) -> Result<Vec<LessSafeKey>> {
+        let wrapped_keys = match &self.wrapped_keys_file {
+            Some(path) => fs::read_to_string(path)
+                .await
+                .with_context(|| format!("couldn't read wrapped datastore keys file {path:?}"))?
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .map(str::to_string)
+                .collect(),
+            None => wrapped_keys.to_vec(),
+        };
+
+        let mut keys = Vec::new();
+        for wrapped_key in wrapped_keys.iter().filter(|k| !k.is_empty()) {
+            let wrapped_key = URL_SAFE_NO_PAD
+                .decode(wrapped_key)
+                .context("couldn't base64-decode wrapped datastore keys")?;
+            let key = self
+                .kms
+                .unwrap_key(&wrapped_key)
+                .await
+                .context("couldn't unwrap datastore key")?;
+            keys.push(crate::binary_utils::datastore_key_from_bytes(&key)?);
+        }
+        if keys.is_empty() {
+            return Err(anyhow!("no wrapped datastore keys were provided"));
+        }
+        Ok(keys)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::kms::{DatastoreKeyEncryptionConfig, DatastoreKeyUnwrapper, Kms, KmsConfig};
+    use anyhow::Result;
+    use async_trait::async_trait;
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+    use ring::aead::{Aad, Nonce, AES_128_GCM};
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    /// A fake KMS, which "wraps" keys by reversing them.
+    struct ReversingKms;
+
+    #[async_trait]
+    impl Kms for ReversingKms {
+        async fn unwrap_key(&self, wrapped_key: &[u8]) -> Result<Vec<u8>> {
+            Ok(wrapped_key.iter().rev().copied().collect())
+        }
+    }
+
+    fn wrap(key: &[u8]) -> String {
+        URL_SAFE_NO_PAD.encode(key.iter().rev().copied().collect::<Vec<_>>())
+    }
+
+    /// Checks that `unwrapped_key` is the AES-128-GCM key `expected_key`.
+    fn assert_key_eq(unwrapped_key: &ring::aead::LessSafeKey, expected_key: &[u8]) {
+        let expected_key = ring::aead::LessSafeKey::new(
+            ring::aead::UnboundKey::new(&AES_128_GCM, expected_key).unwrap(),
+        );
+        let mut in_out = b"plaintext".to_vec();
+        expected_key
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key([0; 12]),
+                Aad::empty(),
+                &mut in_out,
+            )
+            .unwrap();
+        unwrapped_key
+            .open_in_place(
+                Nonce::assume_unique_for_key([0; 12]),
+                Aad::empty(),
+                &mut in_out,
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn config_roundtrip() {
+        for config in [
+            DatastoreKeyEncryptionConfig {
+                kms: KmsConfig::AwsKms {
+                    region: "us-west-2".to_string(),
+                    key_id: "alias/janus".to_string(),
+                    endpoint: None,
+                },
+                wrapped_keys_file: None,
+            },
+            DatastoreKeyEncryptionConfig {
+                kms: KmsConfig::GcpKms {
+                    key_name: "projects/p/locations/l/keyRings/r/cryptoKeys/k".to_string(),
+                    endpoint: Some("https://example.com/".parse().unwrap()),
+                },
+                wrapped_keys_file: Some("/etc/janus/wrapped-keys".into()),
+            },
+            DatastoreKeyEncryptionConfig {
+                kms: KmsConfig::AzureKeyVault {
+                    vault_url: "https://example.vault.azure.net/".parse().unwrap(),
+                    key_name: "janus".to_string(),
+                    key_version: "0123456789abcdef".to_string(),
+                    algorithm: "RSA-OAEP-256".to_string(),
+                },
+                wrapped_keys_file: None,
+            },
+        ] {
+            let encoded = serde_yaml::to_string(&config).unwrap();
+            let decoded: DatastoreKeyEncryptionConfig = serde_yaml::from_str(&encoded).unwrap();
+            assert_eq!(config, decoded);
+        }
+    }
+
+    #[tokio::test]
+    async fn unwrap_keys_from_options() {
+        let unwrapper = DatastoreKeyUnwrapper::with_kms(Box::new(ReversingKms), None);
+
+        let keys = unwrapper
+            .unwrap_keys(&[wrap(&[1; 16]), String::new(), wrap(&[2; 16])])
+            .await
+            .unwrap();
+        assert_eq!(keys.len(), 2);
+        assert_key_eq(&keys[0], &[1; 16]);
+        assert_key_eq(&keys[1], &[2; 16]);
+    }
+
+    #[tokio::test]
+    async fn unwrap_keys_from_file() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, "{},{}", wrap(&[3; 16]), wrap(&[4; 16])).unwrap();
+        let unwrapper = DatastoreKeyUnwrapper::with_kms(
+            Box::new(ReversingKms),
+            Some(file.path().to_path_buf()),
+        );
+        let wrapped_keys = [wrap(&[1; 16])];
+
+        let keys = unwrapper.unwrap_keys(&wrapped_keys).await.unwrap();
+        assert_eq!(keys.len(), 2);
+        assert_key_eq(&keys[0], &[3; 16]);
+        assert_key_eq(&keys[1], &[4; 16]);
+
+        // The file is read again on each unwrap, to support rotation.
+        file.as_file().set_len(0).unwrap();
+        std::fs::write(file.path(), format!("{}\n", wrap(&[5; 16]))).unwrap();
+        let keys = unwrapper.unwrap_keys(&wrapped_keys).await.unwrap();
+        assert_eq!(keys.len(), 1);
+        assert_key_eq(&keys[0], &[5; 16]);
+    }
+
+    #[tokio::test]
+    async fn unwrap_keys_invalid() {
+        let unwrapper = DatastoreKeyUnwrapper::with_kms(Box::new(ReversingKms), None);
+
+        // No keys.
+        unwrapper.unwrap_keys(&[]).await.unwrap_err();
+
+        // Invalid base64.
+        unwrapper
+            .unwrap_keys(&["not base64!".to_string()])
+            .await
+            .unwrap_err();
+
+        // Unwrapped key has the wrong length.
+        unwrapper.unwrap_keys(&[wrap(&[1; 15])]).await.unwrap_err();
+    }
+}
@@ -0,0 +1,142 @@
+//! Client for the AWS Key Management Service [Decrypt][1] API.
+//!
+//! [1]: https://docs.aws.amazon.com/kms/latest/APIReference/API_Decrypt.html
+
+use crate::{
+    cloud_credentials::aws::{AwsClient, AwsCredentials},
+    kms::Kms,
+};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+/// Name of the service, for purposes of request signing.
+const SERVICE: &str = "kms";
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct DecryptRequest<'a> {
+    ciphertext_blob: String,
+    key_id: &'a str,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct DecryptResponse {
+    plaintext: String,
+}
+
+pub(super) struct AwsKms {
+    client: AwsClient,
+    key_id: String,
+}
+
+impl AwsKms {
+    pub(super) fn new(
+        http_client: reqwest::Client,
+        region: String,
+        key_id: String,
+        endpoint: Option<Url>,
+        credentials: AwsCredentials,
+    ) -> Result<Self> {
+        Ok(Self {
+            client: AwsClient::new(http_client, SERVICE, region, endpoint, credentials)?,
+            key_id,
+        })
+    }
+}
+
+#[async_trait]
+impl Kms for AwsKms {
+    async fn unwrap_key(&self, wrapped_key: &[u8]) -> Result<Vec<u8>> {
+        let response: DecryptResponse = self
+            .client
+            .call(
+                "TrentService.Decrypt",
+                &DecryptRequest {
+                    ciphertext_blob: STANDARD.encode(wrapped_key),
+                    key_id: &self.key_id,
+                },
+            )
+            .await?;
+
+        STANDARD
+            .decode(response.plaintext)
+            .context("couldn't base64-decode AWS KMS response")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        cloud_credentials::aws::AwsCredentials,
+        kms::{aws::AwsKms, Kms},
+    };
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use mockito::Matcher;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn unwrap_key() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "TrentService.Decrypt")
+            .match_header("content-type", "application/x-amz-json-1.1")
+            .match_header("x-amz-security-token", "session-token")
+            .match_header(
+                "authorization",
+                Matcher::Regex(
+                    "^AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/[0-9]{8}/us-west-2/kms/aws4_request, \
+                     SignedHeaders=content-type;host;x-amz-date;x-amz-security-token;\
+                     x-amz-target, Signature=[0-9a-f]{64}$"
+                        .to_string(),
+                ),
+            )
+            .match_body(Matcher::Json(json!({
+                "CiphertextBlob": STANDARD.encode(b"wrapped"),
+                "KeyId": "alias/janus",
+            })))
+            .with_status(200)
+            .with_body(json!({"Plaintext": STANDARD.encode(b"unwrapped")}).to_string())
+            .expect(1)
+            .create_async()
+            .await;
+
+        let kms = AwsKms::new(
+            reqwest::Client::new(),
+            "us-west-2".to_string(),
+            "alias/janus".to_string(),
+            Some(server.url().parse().unwrap()),
+            AwsCredentials::example().with_session_token("session-token".to_string()),
+        )
+        .unwrap();
+        assert_eq!(kms.unwrap_key(b"wrapped").await.unwrap(), b"unwrapped");
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn unwrap_key_error() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/")
+            .with_status(400)
+            .with_body(r#"{"__type":"InvalidCiphertextException"}"#)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let kms = AwsKms::new(
+            reqwest::Client::new(),
+            "us-west-2".to_string(),
+            "alias/janus".to_string(),
+            Some(server.url().parse().unwrap()),
+            AwsCredentials::example(),
+        )
+        .unwrap();
+        kms.unwrap_key(b"wrapped").await.unwrap_err();
+        mock.assert_async().await;
+    }
+}
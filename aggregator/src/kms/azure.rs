@@ -0,0 +1,171 @@
+//! Client for the Azure Key Vault [Unwrap Key][1] API.
+//!
+//! [1]: https://learn.microsoft.com/en-us/rest/api/keyvault/keys/unwrap-key/unwrap-key
+
+use crate::{cloud_credentials::fetch_azure_access_token, kms::Kms};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+/// Version of the Key Vault API used.
+const API_VERSION: &str = "7.4";
+
+#[derive(Serialize)]
+struct UnwrapKeyRequest<'a> {
+    alg: &'a str,
+    value: String,
+}
+
+#[derive(Deserialize)]
+struct UnwrapKeyResponse {
+    value: String,
+}
+
+pub(super) struct AzureKeyVault {
+    http_client: reqwest::Client,
+    unwrap_key_url: Url,
+    algorithm: String,
+    token_url: Url,
+}
+
+impl AzureKeyVault {
+    pub(super) fn new(
+        http_client: reqwest::Client,
+        vault_url: &Url,
+        key_name: &str,
+        key_version: &str,
+        algorithm: String,
+        token_url: Url,
+    ) -> Result<Self> {
+        let mut unwrap_key_url = vault_url
+            .join(&format!("keys/{key_name}/{key_version}/unwrapkey"))
+            .context("invalid Azure Key Vault key name or version")?;
+        unwrap_key_url
+            .query_pairs_mut()
+            .append_pair("api-version", API_VERSION);
+        Ok(Self {
+            http_client,
+            unwrap_key_url,
+            algorithm,
+            token_url,
+        })
+    }
+}
+
+#[async_trait]
+impl Kms for AzureKeyVault {
+    async fn unwrap_key(&self, wrapped_key: &[u8]) -> Result<Vec<u8>> {
+        let access_token =
+            fetch_azure_access_token(&self.http_client, self.token_url.clone()).await?;
+
+        let response: UnwrapKeyResponse = self
+            .http_client
+            .post(self.unwrap_key_url.clone())
+            .bearer_auth(access_token)
+            .json(&UnwrapKeyRequest {
+                alg: &self.algorithm,
+                value: URL_SAFE_NO_PAD.encode(wrapped_key),
+            })
+            .send()
+            .await
+            .context("couldn't send request to Azure Key Vault")?
+            .error_for_status()
+            .context("Azure Key Vault returned an error")?
+            .json()
+            .await
+            .context("couldn't parse Azure Key Vault response")?;
+
+        URL_SAFE_NO_PAD
+            .decode(response.value)
+            .context("couldn't base64-decode Azure Key Vault response")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::kms::{azure::AzureKeyVault, Kms};
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+    use mockito::Matcher;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn unwrap_key() {
+        let mut server = mockito::Server::new_async().await;
+        let token_mock = server
+            .mock("GET", "/token")
+            .match_header("Metadata", "true")
+            .with_status(200)
+            .with_body(json!({"access_token": "token", "token_type": "Bearer"}).to_string())
+            .expect(1)
+            .create_async()
+            .await;
+        let unwrap_mock = server
+            .mock("POST", "/keys/janus/0123/unwrapkey")
+            .match_query(Matcher::UrlEncoded(
+                "api-version".to_string(),
+                "7.4".to_string(),
+            ))
+            .match_header("authorization", "Bearer token")
+            .match_body(Matcher::Json(json!({
+                "alg": "RSA-OAEP-256",
+                "value": URL_SAFE_NO_PAD.encode(b"wrapped"),
+            })))
+            .with_status(200)
+            .with_body(
+                json!({
+                    "kid": "https://example.vault.azure.net/keys/janus/0123",
+                    "value": URL_SAFE_NO_PAD.encode(b"unwrapped"),
+                })
+                .to_string(),
+            )
+            .expect(1)
+            .create_async()
+            .await;
+
+        let kms = AzureKeyVault::new(
+            reqwest::Client::new(),
+            &server.url().parse().unwrap(),
+            "janus",
+            "0123",
+            "RSA-OAEP-256".to_string(),
+            format!("{}/token", server.url()).parse().unwrap(),
+        )
+        .unwrap();
+        assert_eq!(kms.unwrap_key(b"wrapped").await.unwrap(), b"unwrapped");
+        token_mock.assert_async().await;
+        unwrap_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn unwrap_key_error() {
+        let mut server = mockito::Server::new_async().await;
+        let token_mock = server
+            .mock("GET", "/token")
+            .with_status(200)
+            .with_body(json!({"access_token": "token"}).to_string())
+            .expect(1)
+            .create_async()
+            .await;
+        let unwrap_mock = server
+            .mock("POST", Matcher::Any)
+            .with_status(403)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let kms = AzureKeyVault::new(
+            reqwest::Client::new(),
+            &server.url().parse().unwrap(),
+            "janus",
+            "0123",
+            "RSA-OAEP-256".to_string(),
+            format!("{}/token", server.url()).parse().unwrap(),
+        )
+        .unwrap();
+        kms.unwrap_key(b"wrapped").await.unwrap_err();
+        token_mock.assert_async().await;
+        unwrap_mock.assert_async().await;
+    }
+}
@@ -0,0 +1,149 @@
+//! Client for the Google Cloud KMS [decrypt][1] API.
+//!
+//! [1]: https://cloud.google.com/kms/docs/reference/rest/v1/projects.locations.keyRings.cryptoKeys/decrypt
+
+use crate::{cloud_credentials::fetch_gcp_access_token, kms::Kms};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+/// Default base URL of the Cloud KMS API.
+const DEFAULT_ENDPOINT: &str = "https://cloudkms.googleapis.com/";
+
+#[derive(Serialize)]
+struct DecryptRequest {
+    ciphertext: String,
+}
+
+#[derive(Deserialize)]
+struct DecryptResponse {
+    plaintext: String,
+}
+
+pub(super) struct GcpKms {
+    http_client: reqwest::Client,
+    decrypt_url: Url,
+    token_url: Url,
+}
+
+impl GcpKms {
+    pub(super) fn new(
+        http_client: reqwest::Client,
+        key_name: &str,
+        endpoint: Option<Url>,
+        token_url: Url,
+    ) -> Result<Self> {
+        let endpoint = match endpoint {
+            Some(endpoint) => endpoint,
+            // Unwrap safety: the default endpoint is a valid URL.
+            None => DEFAULT_ENDPOINT.parse().unwrap(),
+        };
+        let decrypt_url = endpoint
+            .join(&format!("v1/{key_name}:decrypt"))
+            .with_context(|| format!("invalid GCP KMS key name {key_name:?}"))?;
+        Ok(Self {
+            http_client,
+            decrypt_url,
+            token_url,
+        })
+    }
+}
+
+#[async_trait]
+impl Kms for GcpKms {
+    async fn unwrap_key(&self, wrapped_key: &[u8]) -> Result<Vec<u8>> {
+        let access_token =
+            fetch_gcp_access_token(&self.http_client, self.token_url.clone()).await?;
+
+        let response: DecryptResponse = self
+            .http_client
+            .post(self.decrypt_url.clone())
+            .bearer_auth(access_token)
+            .json(&DecryptRequest {
+                ciphertext: STANDARD.encode(wrapped_key),
+            })
+            .send()
+            .await
+            .context("couldn't send request to GCP KMS")?
+            .error_for_status()
+            .context("GCP KMS returned an error")?
+            .json()
+            .await
+            .context("couldn't parse GCP KMS response")?;
+
+        STANDARD
+            .decode(response.plaintext)
+            .context("couldn't base64-decode GCP KMS response")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::kms::{gcp::GcpKms, Kms};
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use mockito::Matcher;
+    use serde_json::json;
+
+    const KEY_NAME: &str = "projects/p/locations/global/keyRings/r/cryptoKeys/k";
+
+    #[tokio::test]
+    async fn unwrap_key() {
+        let mut server = mockito::Server::new_async().await;
+        let token_mock = server
+            .mock("GET", "/token")
+            .match_header("Metadata-Flavor", "Google")
+            .with_status(200)
+            .with_body(
+                json!({"access_token": "token", "expires_in": 3600, "token_type": "Bearer"})
+                    .to_string(),
+            )
+            .expect(1)
+            .create_async()
+            .await;
+        let decrypt_mock = server
+            .mock("POST", format!("/v1/{KEY_NAME}:decrypt").as_str())
+            .match_header("authorization", "Bearer token")
+            .match_body(Matcher::Json(
+                json!({"ciphertext": STANDARD.encode(b"wrapped")}),
+            ))
+            .with_status(200)
+            .with_body(json!({"plaintext": STANDARD.encode(b"unwrapped")}).to_string())
+            .expect(1)
+            .create_async()
+            .await;
+
+        let kms = GcpKms::new(
+            reqwest::Client::new(),
+            KEY_NAME,
+            Some(server.url().parse().unwrap()),
+            format!("{}/token", server.url()).parse().unwrap(),
+        )
+        .unwrap();
+        assert_eq!(kms.unwrap_key(b"wrapped").await.unwrap(), b"unwrapped");
+        token_mock.assert_async().await;
+        decrypt_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn unwrap_key_error() {
+        let mut server = mockito::Server::new_async().await;
+        let token_mock = server
+            .mock("GET", "/token")
+            .with_status(404)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let kms = GcpKms::new(
+            reqwest::Client::new(),
+            KEY_NAME,
+            Some(server.url().parse().unwrap()),
+            format!("{}/token", server.url()).parse().unwrap(),
+        )
+        .unwrap();
+        kms.unwrap_key(b"wrapped").await.unwrap_err();
+        token_mock.assert_async().await;
+    }
+}
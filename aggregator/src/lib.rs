@@ -7,8 +7,22 @@ pub mod aggregator;
 pub mod binaries;
 pub mod binary_utils;
 pub mod cache;
+#[cfg(any(
+    feature = "aws-kms",
+    feature = "aws-secrets-manager",
+    feature = "azure-key-vault",
+    feature = "gcp-bigquery",
+    feature = "gcp-kms",
+    feature = "gcp-pubsub",
+    feature = "gcp-secret-manager"
+))]
+mod cloud_credentials;
 pub mod config;
+pub mod feature_flags;
+pub mod kms;
 pub mod metrics;
+pub mod secrets;
+pub mod sharding;
 pub mod trace;
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -2,6 +2,7 @@
 
 #[cfg(any(not(feature = "prometheus"), not(feature = "otlp")))]
 use anyhow::anyhow;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::net::AddrParseError;
 
@@ -58,16 +59,18 @@ pub enum Error {
 }
 
 /// Configuration for collection/exporting of application-level metrics.
-#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct MetricsConfiguration {
     /// Configuration for OpenTelemetry metrics, with a choice of exporters.
     #[serde(default, with = "serde_yaml::with::singleton_map")]
+    #[schemars(with = "Option<MetricsExporterConfiguration>")]
     pub exporter: Option<MetricsExporterConfiguration>,
 }
 
 /// Selection of an exporter for OpenTelemetry metrics.
-#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
-#[serde(rename_all = "lowercase")]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase", deny_unknown_fields)]
 pub enum MetricsExporterConfiguration {
     Prometheus {
         host: Option<String>,
@@ -77,7 +80,8 @@ pub enum MetricsExporterConfiguration {
 }
 
 /// Configuration options specific to the OpenTelemetry OTLP metrics exporter.
-#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct OtlpExporterConfiguration {
     /// gRPC endpoint for OTLP exporter.
     pub endpoint: String,
@@ -156,7 +160,9 @@ impl View for CustomView {
         match (inst.kind, inst.name.as_ref()) {
             (
                 Some(InstrumentKind::Histogram),
-                "http.server.request.body.size" | "http.server.response.body.size",
+                "http.server.request.body.size"
+                | "http.server.response.body.size"
+                | "janus_aggregate_request_memory",
             ) => self.bytes_histogram_view.match_inst(inst),
             (Some(InstrumentKind::Histogram), TRANSACTION_RETRIES_METER_NAME) => {
                 self.uint_histogram_view.match_inst(inst)
@@ -280,7 +286,7 @@ pub async fn install_metrics_exporter(
 
 /// Produces a [`opentelemetry::sdk::Resource`] representing this process.
 #[cfg(any(feature = "otlp", feature = "prometheus"))]
-fn resource() -> Resource {
+pub(crate) fn resource() -> Resource {
     // Note that the implementation of `Default` pulls in attributes set via environment variables.
     let default_resource = Resource::default();
 
@@ -0,0 +1,379 @@
+//! Support for fetching secrets (datastore keys, aggregator API authentication tokens, and
+//! collector HPKE keypairs) from an external secret store, so that they need not be present in
+//! configuration files or environment variables. Secrets are fetched at startup. Datastore keys are
+//! fetched again whenever the process receives a SIGHUP signal, and periodically if so configured,
+//! so that they may be rotated in the secret store without a restart.
+
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use url::Url;
+
+#[cfg(feature = "aws-secrets-manager")]
+mod aws;
+#[cfg(feature = "gcp-secret-manager")]
+mod gcp;
+#[cfg(feature = "hashicorp-vault")]
+mod vault;
+
+/// Configuration for fetching secrets from a secret store.
+///
+/// Each secret holds a list of values, encoded as they would be in the corresponding environment
+/// variable, and separated by commas or whitespace. Secrets that are not named here are taken
+/// from environment variables or command line arguments as usual.
+///
+/// # Examples
+///
+/// ```
+/// use janus_aggregator::secrets::SecretsConfig;
+///
+/// let yaml_config = r#"
+/// ---
+/// type: gcp_secret_manager
+/// project: example
+/// datastore_keys: janus-datastore-keys
+/// aggregator_api_auth_tokens: janus-aggregator-api-auth-tokens
+/// refresh_interval_secs: 300
+/// "#;
+///
+/// let _decoded: SecretsConfig = serde_yaml::from_str(yaml_config).unwrap();
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct SecretsConfig {
+    /// The secret store holding the secrets.
+    #[serde(flatten)]
+    pub store: SecretStoreConfig,
+
+    /// Name of the secret holding the datastore keys. If datastore key encryption is configured,
+    /// these are the wrapped datastore keys.
+    #[serde(default)]
+    pub datastore_keys: Option<String>,
+
+    /// Name of the secret holding the aggregator API authentication tokens. Only used by the
+    /// `aggregator` binary.
+    #[serde(default)]
+    pub aggregator_api_auth_tokens: Option<String>,
+
+    /// Name of the secret holding the collector HPKE keypairs. Only used by the `aggregator`
+    /// binary.
+    #[serde(default)]
+    pub collector_hpke_keypairs: Option<String>,
+
+    /// How often to fetch the datastore keys again, in seconds, in addition to upon receipt of a
+    /// SIGHUP signal. If not set, datastore keys are only fetched again upon receipt of SIGHUP.
+    #[serde(default)]
+    pub refresh_interval_secs: Option<u64>,
+}
+
+/// Selection of a secret store.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SecretStoreConfig {
+    /// AWS Secrets Manager. Secrets are named by their name or ARN, and their `SecretString` is
+    /// used. Requests are authenticated with credentials taken from the `AWS_ACCESS_KEY_ID`,
+    /// `AWS_SECRET_ACCESS_KEY`, and (optionally) `AWS_SESSION_TOKEN` environment variables.
+    AwsSecretsManager {
+        /// The AWS region of the secrets, e.g. `us-west-2`.
+        region: String,
+        /// Base URL of the Secrets Manager API. Defaults to
+        /// `https://secretsmanager.{region}.amazonaws.com/`.
+        #[serde(default)]
+        endpoint: Option<Url>,
+    },
+
+    /// Google Cloud Secret Manager. Secrets are named by their secret ID, and their latest version
+    /// is used. Requests are authenticated with an access token for the instance's default
+    /// service account, fetched from the GCE metadata server.
+    GcpSecretManager {
+        /// ID of the project holding the secrets.
+        project: String,
+        /// Base URL of the Secret Manager API. Defaults to
+        /// `https://secretmanager.googleapis.com/`.
+        #[serde(default)]
+        endpoint: Option<Url>,
+    },
+
+    /// HashiCorp Vault, using a version 2 key/value secrets engine. Secrets are named by their
+    /// path within the engine, and the field named `value` of their latest version is used.
+    /// Requests are authenticated with the token in the `VAULT_TOKEN` environment variable.
+    Vault {
+        /// URL of the Vault server, e.g. `https://vault.example.com:8200/`.
+        address: Url,
+        /// Path at which the key/value secrets engine is mounted. Defaults to `secret`.
+        #[serde(default = "default_vault_mount")]
+        mount: String,
+        /// Vault Enterprise namespace holding the secrets engine, if any.
+        #[serde(default)]
+        namespace: Option<String>,
+    },
+}
+
+fn default_vault_mount() -> String {
+    "secret".to_string()
+}
+
+/// User agent sent with requests to secret store APIs.
+#[cfg(any(
+    feature = "aws-secrets-manager",
+    feature = "gcp-secret-manager",
+    feature = "hashicorp-vault"
+))]
+const CLIENT_USER_AGENT: &str = concat!(
+    env!("CARGO_PKG_NAME"),
+    "/",
+    env!("CARGO_PKG_VERSION"),
+    "/secrets",
+);
+
+/// Timeout applied to each request to a secret store API or metadata service.
+#[cfg(any(
+    feature = "aws-secrets-manager",
+    feature = "gcp-secret-manager",
+    feature = "hashicorp-vault"
+))]
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A client for a secret store.
+#[async_trait]
+trait SecretStore: Send + Sync {
+    /// Fetches the current value of the named secret.
+    async fn fetch_secret(&self, name: &str) -> Result<String>;
+}
+
+/// Fetches secrets from a secret store.
+pub struct SecretFetcher {
+    store: Box<dyn SecretStore>,
+    config: SecretsConfig,
+}
+
+impl SecretFetcher {
+    /// Constructs a fetcher for the given configuration.
+    pub fn new(config: &SecretsConfig) -> Result<Self> {
+        #[cfg(any(
+            feature = "aws-secrets-manager",
+            feature = "gcp-secret-manager",
+            feature = "hashicorp-vault"
+        ))]
+        let http_client = reqwest::Client::builder()
+            .user_agent(CLIENT_USER_AGENT)
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .context("couldn't create HTTP client")?;
+
+        let store: Result<Box<dyn SecretStore>> = match &config.store {
+            #[cfg(feature = "aws-secrets-manager")]
+            SecretStoreConfig::AwsSecretsManager { region, endpoint } => {
+                Ok(Box::new(aws::AwsSecretsManager::new(
+                    http_client,
+                    region.clone(),
+                    endpoint.clone(),
+                    crate::cloud_credentials::aws::AwsCredentials::from_env()?,
+                )?))
+            }
+            #[cfg(not(feature = "aws-secrets-manager"))]
+            SecretStoreConfig::AwsSecretsManager { .. } => Err(anyhow!(
+                "AWS Secrets Manager was enabled in the configuration file, but support was not \
+                 enabled at compile time. Rebuild with `--features aws-secrets-manager`."
+            )),
+
+            #[cfg(feature = "gcp-secret-manager")]
+            SecretStoreConfig::GcpSecretManager { project, endpoint } => {
+                Ok(Box::new(gcp::GcpSecretManager::new(
+                    http_client,
+                    project,
+                    endpoint.clone(),
+                    crate::cloud_credentials::GCP_METADATA_TOKEN_URL
+                        .parse()
+                        .unwrap(),
+                )?))
+            }
+            #[cfg(not(feature = "gcp-secret-manager"))]
+            SecretStoreConfig::GcpSecretManager { .. } => Err(anyhow!(
+                "GCP Secret Manager was enabled in the configuration file, but support was not \
+                 enabled at compile time. Rebuild with `--features gcp-secret-manager`."
+            )),
+
+            #[cfg(feature = "hashicorp-vault")]
+            SecretStoreConfig::Vault {
+                address,
+                mount,
+                namespace,
+            } => Ok(Box::new(vault::Vault::new(
+                http_client,
+                address,
+                mount,
+                namespace.clone(),
+                vault::token_from_env()?,
+            )?)),
+            #[cfg(not(feature = "hashicorp-vault"))]
+            SecretStoreConfig::Vault { .. } => Err(anyhow!(
+                "HashiCorp Vault was enabled in the configuration file, but support was not \
+                 enabled at compile time. Rebuild with `--features hashicorp-vault`."
+            )),
+        };
+
+        Ok(Self::with_store(store?, config.clone()))
+    }
+
+    fn with_store(store: Box<dyn SecretStore>, config: SecretsConfig) -> Self {
+        Self { store, config }
+    }
+
+    /// Fetches the datastore keys, if a secret holding them is configured.
+    pub async fn datastore_keys(&self) -> Result<Option<Vec<String>>> {
+        self.fetch_list(self.config.datastore_keys.as_deref())
+            .await
+            .context("couldn't fetch datastore keys")
+    }
+
+    /// Fetches the aggregator API authentication tokens, if a secret holding them is configured.
+    pub async fn aggregator_api_auth_tokens(&self) -> Result<Option<Vec<String>>> {
+        self.fetch_list(self.config.aggregator_api_auth_tokens.as_deref())
+            .await
+            .context("couldn't fetch aggregator API auth tokens")
+    }
+
+    /// Fetches the collector HPKE keypairs, if a secret holding them is configured.
+    pub async fn collector_hpke_keypairs(&self) -> Result<Option<Vec<String>>> {
+        self.fetch_list(self.config.collector_hpke_keypairs.as_deref())
+            .await
+            .context("couldn't fetch collector HPKE keypairs")
+    }
+
+    /// How often datastore keys should be fetched again, if periodic refresh is configured.
+    pub fn refresh_interval(&self) -> Option<Duration> {
+        self.config.refresh_interval_secs.map(Duration::from_secs)
+    }
+
+    /// Fetches the named secret, if any, and splits it into a list of values. Empty values are
+    /// dropped; a secret with no values is an error.
+    async fn fetch_list(&self, name: Option<&str>) -> Result<Option<Vec<String>>> {
+        let Some(name) = name else {
+            return Ok(None);
+        };
+        let values: Vec<String> = self
+            .store
+            .fetch_secret(name)
+            .await
+            .with_context(|| format!("couldn't fetch secret {name:?}"))?
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|value| !value.is_empty())
+            .map(str::to_string)
+            .collect();
+        if values.is_empty() {
+            return Err(anyhow!("secret {name:?} is empty"));
+        }
+        Ok(Some(values))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::secrets::{SecretFetcher, SecretStore, SecretStoreConfig, SecretsConfig};
+    use anyhow::{anyhow, Result};
+    use async_trait::async_trait;
+    use std::{collections::HashMap, time::Duration};
+
+    /// A fake secret store, holding fixed secrets.
+    struct FakeSecretStore(HashMap<&'static str, &'static str>);
+
+    #[async_trait]
+    impl SecretStore for FakeSecretStore {
+        async fn fetch_secret(&self, name: &str) -> Result<String> {
+            self.0
+                .get(name)
+                .map(|value| value.to_string())
+                .ok_or_else(|| anyhow!("no such secret"))
+        }
+    }
+
+    fn config() -> SecretsConfig {
+        SecretsConfig {
+            store: SecretStoreConfig::Vault {
+                address: "https://vault.example.com:8200/".parse().unwrap(),
+                mount: "secret".to_string(),
+                namespace: None,
+            },
+            datastore_keys: Some("janus/datastore-keys".to_string()),
+            aggregator_api_auth_tokens: Some("janus/aggregator-api-auth-tokens".to_string()),
+            collector_hpke_keypairs: None,
+            refresh_interval_secs: Some(300),
+        }
+    }
+
+    #[test]
+    fn config_roundtrip() {
+        for config in [
+            SecretsConfig {
+                store: SecretStoreConfig::AwsSecretsManager {
+                    region: "us-west-2".to_string(),
+                    endpoint: None,
+                },
+                datastore_keys: Some("janus-datastore-keys".to_string()),
+                aggregator_api_auth_tokens: None,
+                collector_hpke_keypairs: Some("janus-collector-hpke-keypairs".to_string()),
+                refresh_interval_secs: None,
+            },
+            SecretsConfig {
+                store: SecretStoreConfig::GcpSecretManager {
+                    project: "example".to_string(),
+                    endpoint: Some("https://example.com/".parse().unwrap()),
+                },
+                datastore_keys: Some("janus-datastore-keys".to_string()),
+                aggregator_api_auth_tokens: Some("janus-aggregator-api-auth-tokens".to_string()),
+                collector_hpke_keypairs: None,
+                refresh_interval_secs: Some(60),
+            },
+            config(),
+        ] {
+            let encoded = serde_yaml::to_string(&config).unwrap();
+            let decoded: SecretsConfig = serde_yaml::from_str(&encoded).unwrap();
+            assert_eq!(config, decoded);
+        }
+    }
+
+    #[tokio::test]
+    async fn fetch_secrets() {
+        let fetcher = SecretFetcher::with_store(
+            Box::new(FakeSecretStore(HashMap::from([
+                ("janus/datastore-keys", "key-1,key-2\n"),
+                ("janus/aggregator-api-auth-tokens", "token-1, ,token-2"),
+            ]))),
+            config(),
+        );
+
+        assert_eq!(fetcher.refresh_interval(), Some(Duration::from_secs(300)));
+        assert_eq!(
+            fetcher.datastore_keys().await.unwrap(),
+            Some(Vec::from(["key-1".to_string(), "key-2".to_string()]))
+        );
+        assert_eq!(
+            fetcher.aggregator_api_auth_tokens().await.unwrap(),
+            Some(Vec::from(["token-1".to_string(), "token-2".to_string()]))
+        );
+        assert_eq!(fetcher.collector_hpke_keypairs().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn fetch_secrets_invalid() {
+        // Missing secret.
+        SecretFetcher::with_store(Box::new(FakeSecretStore(HashMap::new())), config())
+            .datastore_keys()
+            .await
+            .unwrap_err();
+
+        // Empty secret.
+        SecretFetcher::with_store(
+            Box::new(FakeSecretStore(HashMap::from([(
+                "janus/datastore-keys",
+                " , ",
+            )]))),
+            config(),
+        )
+        .datastore_keys()
+        .await
+        .unwrap_err();
+    }
+}
@@ -0,0 +1,134 @@
+//! Client for the AWS Secrets Manager [GetSecretValue][1] API.
+//!
+//! [1]: https://docs.aws.amazon.com/secretsmanager/latest/apireference/API_GetSecretValue.html
+
+use crate::{
+    cloud_credentials::aws::{AwsClient, AwsCredentials},
+    secrets::SecretStore,
+};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+/// Name of the service, for purposes of request signing.
+const SERVICE: &str = "secretsmanager";
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct GetSecretValueRequest<'a> {
+    secret_id: &'a str,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct GetSecretValueResponse {
+    secret_string: Option<String>,
+}
+
+pub(super) struct AwsSecretsManager {
+    client: AwsClient,
+}
+
+impl AwsSecretsManager {
+    pub(super) fn new(
+        http_client: reqwest::Client,
+        region: String,
+        endpoint: Option<Url>,
+        credentials: AwsCredentials,
+    ) -> Result<Self> {
+        Ok(Self {
+            client: AwsClient::new(http_client, SERVICE, region, endpoint, credentials)?,
+        })
+    }
+}
+
+#[async_trait]
+impl SecretStore for AwsSecretsManager {
+    async fn fetch_secret(&self, name: &str) -> Result<String> {
+        let response: GetSecretValueResponse = self
+            .client
+            .call(
+                "secretsmanager.GetSecretValue",
+                &GetSecretValueRequest { secret_id: name },
+            )
+            .await?;
+        response
+            .secret_string
+            .context("AWS Secrets Manager secret has no string value")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        cloud_credentials::aws::AwsCredentials,
+        secrets::{aws::AwsSecretsManager, SecretStore},
+    };
+    use mockito::Matcher;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn fetch_secret() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "secretsmanager.GetSecretValue")
+            .match_header("content-type", "application/x-amz-json-1.1")
+            .match_header(
+                "authorization",
+                Matcher::Regex(
+                    "^AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/[0-9]{8}/us-west-2/secretsmanager/\
+                     aws4_request, SignedHeaders=content-type;host;x-amz-date;x-amz-target, \
+                     Signature=[0-9a-f]{64}$"
+                        .to_string(),
+                ),
+            )
+            .match_body(Matcher::Json(json!({"SecretId": "janus-datastore-keys"})))
+            .with_status(200)
+            .with_body(
+                json!({"Name": "janus-datastore-keys", "SecretString": "key-1,key-2"}).to_string(),
+            )
+            .expect(1)
+            .create_async()
+            .await;
+
+        let store = AwsSecretsManager::new(
+            reqwest::Client::new(),
+            "us-west-2".to_string(),
+            Some(server.url().parse().unwrap()),
+            AwsCredentials::example(),
+        )
+        .unwrap();
+        assert_eq!(
+            store.fetch_secret("janus-datastore-keys").await.unwrap(),
+            "key-1,key-2"
+        );
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn fetch_secret_error() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/")
+            .with_status(400)
+            .with_body(r#"{"__type":"ResourceNotFoundException"}"#)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let store = AwsSecretsManager::new(
+            reqwest::Client::new(),
+            "us-west-2".to_string(),
+            Some(server.url().parse().unwrap()),
+            AwsCredentials::example(),
+        )
+        .unwrap();
+        store
+            .fetch_secret("janus-datastore-keys")
+            .await
+            .unwrap_err();
+        mock.assert_async().await;
+    }
+}
@@ -0,0 +1,162 @@
+//! Client for the Google Cloud Secret Manager [access][1] API.
+//!
+//! [1]: https://cloud.google.com/secret-manager/docs/reference/rest/v1/projects.secrets.versions/access
+
+use crate::{cloud_credentials::fetch_gcp_access_token, secrets::SecretStore};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::Deserialize;
+use url::Url;
+
+/// Default base URL of the Secret Manager API.
+const DEFAULT_ENDPOINT: &str = "https://secretmanager.googleapis.com/";
+
+#[derive(Deserialize)]
+struct AccessSecretVersionResponse {
+    payload: SecretPayload,
+}
+
+#[derive(Deserialize)]
+struct SecretPayload {
+    data: String,
+}
+
+pub(super) struct GcpSecretManager {
+    http_client: reqwest::Client,
+    secrets_url: Url,
+    token_url: Url,
+}
+
+impl GcpSecretManager {
+    pub(super) fn new(
+        http_client: reqwest::Client,
+        project: &str,
+        endpoint: Option<Url>,
+        token_url: Url,
+    ) -> Result<Self> {
+        let endpoint = match endpoint {
+            Some(endpoint) => endpoint,
+            // Unwrap safety: the default endpoint is a valid URL.
+            None => DEFAULT_ENDPOINT.parse().unwrap(),
+        };
+        let secrets_url = endpoint
+            .join(&format!("v1/projects/{project}/secrets/"))
+            .with_context(|| format!("invalid GCP project {project:?}"))?;
+        Ok(Self {
+            http_client,
+            secrets_url,
+            token_url,
+        })
+    }
+}
+
+#[async_trait]
+impl SecretStore for GcpSecretManager {
+    async fn fetch_secret(&self, name: &str) -> Result<String> {
+        let access_url = self
+            .secrets_url
+            .join(&format!("{name}/versions/latest:access"))
+            .with_context(|| format!("invalid GCP secret name {name:?}"))?;
+        let access_token =
+            fetch_gcp_access_token(&self.http_client, self.token_url.clone()).await?;
+
+        let response: AccessSecretVersionResponse = self
+            .http_client
+            .get(access_url)
+            .bearer_auth(access_token)
+            .send()
+            .await
+            .context("couldn't send request to GCP Secret Manager")?
+            .error_for_status()
+            .context("GCP Secret Manager returned an error")?
+            .json()
+            .await
+            .context("couldn't parse GCP Secret Manager response")?;
+
+        String::from_utf8(
+            STANDARD
+                .decode(response.payload.data)
+                .context("couldn't base64-decode GCP Secret Manager response")?,
+        )
+        .context("GCP Secret Manager secret is not valid UTF-8")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::secrets::{gcp::GcpSecretManager, SecretStore};
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn fetch_secret() {
+        let mut server = mockito::Server::new_async().await;
+        let token_mock = server
+            .mock("GET", "/token")
+            .match_header("Metadata-Flavor", "Google")
+            .with_status(200)
+            .with_body(
+                json!({"access_token": "token", "expires_in": 3600, "token_type": "Bearer"})
+                    .to_string(),
+            )
+            .expect(1)
+            .create_async()
+            .await;
+        let access_mock = server
+            .mock(
+                "GET",
+                "/v1/projects/example/secrets/janus-datastore-keys/versions/latest:access",
+            )
+            .match_header("authorization", "Bearer token")
+            .with_status(200)
+            .with_body(
+                json!({
+                    "name": "projects/123/secrets/janus-datastore-keys/versions/1",
+                    "payload": {"data": STANDARD.encode(b"key-1,key-2")},
+                })
+                .to_string(),
+            )
+            .expect(1)
+            .create_async()
+            .await;
+
+        let store = GcpSecretManager::new(
+            reqwest::Client::new(),
+            "example",
+            Some(server.url().parse().unwrap()),
+            format!("{}/token", server.url()).parse().unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            store.fetch_secret("janus-datastore-keys").await.unwrap(),
+            "key-1,key-2"
+        );
+        token_mock.assert_async().await;
+        access_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn fetch_secret_error() {
+        let mut server = mockito::Server::new_async().await;
+        let token_mock = server
+            .mock("GET", "/token")
+            .with_status(404)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let store = GcpSecretManager::new(
+            reqwest::Client::new(),
+            "example",
+            Some(server.url().parse().unwrap()),
+            format!("{}/token", server.url()).parse().unwrap(),
+        )
+        .unwrap();
+        store
+            .fetch_secret("janus-datastore-keys")
+            .await
+            .unwrap_err();
+        token_mock.assert_async().await;
+    }
+}
@@ -0,0 +1,178 @@
+//! Client for the HashiCorp Vault [key/value version 2][1] secrets engine.
+//!
+//! [1]: https://developer.hashicorp.com/vault/api-docs/secret/kv/kv-v2#read-secret-version
+
+use crate::secrets::SecretStore;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    env,
+    fmt::{self, Debug, Formatter},
+};
+use url::Url;
+
+/// Name of the field of each secret holding its value.
+const VALUE_FIELD: &str = "value";
+
+/// A Vault token, used to authenticate requests.
+pub(super) struct VaultToken(String);
+
+impl Debug for VaultToken {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("VaultToken(..)")
+    }
+}
+
+/// Reads a Vault token from the standard `VAULT_TOKEN` environment variable.
+pub(super) fn token_from_env() -> Result<VaultToken> {
+    Ok(VaultToken(env::var("VAULT_TOKEN").context(
+        "VAULT_TOKEN must be set to use HashiCorp Vault",
+    )?))
+}
+
+#[derive(Deserialize)]
+struct ReadSecretResponse {
+    data: ReadSecretData,
+}
+
+#[derive(Deserialize)]
+struct ReadSecretData {
+    data: HashMap<String, Value>,
+}
+
+pub(super) struct Vault {
+    http_client: reqwest::Client,
+    data_url: Url,
+    namespace: Option<String>,
+    token: VaultToken,
+}
+
+impl Vault {
+    pub(super) fn new(
+        http_client: reqwest::Client,
+        address: &Url,
+        mount: &str,
+        namespace: Option<String>,
+        token: VaultToken,
+    ) -> Result<Self> {
+        let mount = mount.trim_matches('/');
+        let data_url = address
+            .join(&format!("v1/{mount}/data/"))
+            .with_context(|| format!("invalid Vault secrets engine mount {mount:?}"))?;
+        Ok(Self {
+            http_client,
+            data_url,
+            namespace,
+            token,
+        })
+    }
+}
+
+#[async_trait]
+impl SecretStore for Vault {
+    async fn fetch_secret(&self, name: &str) -> Result<String> {
+        let secret_url = self
+            .data_url
+            .join(name.trim_start_matches('/'))
+            .with_context(|| format!("invalid Vault secret path {name:?}"))?;
+
+        let mut request = self
+            .http_client
+            .get(secret_url)
+            .header("X-Vault-Token", &self.token.0);
+        if let Some(namespace) = &self.namespace {
+            request = request.header("X-Vault-Namespace", namespace);
+        }
+        let response: ReadSecretResponse = request
+            .send()
+            .await
+            .context("couldn't send request to Vault")?
+            .error_for_status()
+            .context("Vault returned an error")?
+            .json()
+            .await
+            .context("couldn't parse Vault response")?;
+
+        response
+            .data
+            .data
+            .get(VALUE_FIELD)
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .with_context(|| format!("Vault secret has no string {VALUE_FIELD:?} field"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::secrets::{
+        vault::{Vault, VaultToken},
+        SecretStore,
+    };
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn fetch_secret() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/v1/kv/data/janus/datastore-keys")
+            .match_header("X-Vault-Token", "token")
+            .match_header("X-Vault-Namespace", "janus")
+            .with_status(200)
+            .with_body(
+                json!({
+                    "data": {
+                        "data": {"value": "key-1,key-2"},
+                        "metadata": {"version": 2},
+                    },
+                })
+                .to_string(),
+            )
+            .expect(1)
+            .create_async()
+            .await;
+
+        let store = Vault::new(
+            reqwest::Client::new(),
+            &server.url().parse().unwrap(),
+            "/kv/",
+            Some("janus".to_string()),
+            VaultToken("token".to_string()),
+        )
+        .unwrap();
+        assert_eq!(
+            store.fetch_secret("janus/datastore-keys").await.unwrap(),
+            "key-1,key-2"
+        );
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn fetch_secret_missing_field() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/v1/secret/data/janus/datastore-keys")
+            .with_status(200)
+            .with_body(json!({"data": {"data": {"keys": "key-1,key-2"}}}).to_string())
+            .expect(1)
+            .create_async()
+            .await;
+
+        let store = Vault::new(
+            reqwest::Client::new(),
+            &server.url().parse().unwrap(),
+            "secret",
+            None,
+            VaultToken("token".to_string()),
+        )
+        .unwrap();
+        store
+            .fetch_secret("janus/datastore-keys")
+            .await
+            .unwrap_err();
+        mock.assert_async().await;
+    }
+}
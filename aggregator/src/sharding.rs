@@ -0,0 +1,233 @@
+//! Mapping of tasks to the Janus deployment ("shard") that owns them, for use by front-end routers
+//! that spread tasks across several independent deployments.
+
+use crate::config::DbConfig;
+use janus_messages::TaskId;
+use ring::digest::{digest, SHA256};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use url::Url;
+
+/// Configuration describing a set of Janus deployments that tasks are spread across.
+///
+/// # Examples
+///
+/// ```
+/// use janus_aggregator::sharding::ShardingConfig;
+///
+/// let yaml_config = r#"
+/// ---
+/// shards:
+///   - name: shard-a
+///     url: https://a.example.com/
+///   - name: shard-b
+///     url: https://b.example.com/
+/// task_assignments:
+///   G9YKXjoEjfoU7M_fi_o2H0wmzavRb2sBFHeykeRhDMk: shard-b
+/// "#;
+///
+/// let _decoded: ShardingConfig = serde_yaml::from_str(yaml_config).unwrap();
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ShardingConfig {
+    /// The set of shards. Tasks without an explicit assignment are distributed across these shards
+    /// by rendezvous hashing of the task ID, so adding or removing a shard only moves the tasks
+    /// that are assigned to (or would be assigned to) that shard.
+    pub shards: Vec<ShardConfig>,
+
+    /// Explicit assignments of tasks to shards, by shard name. These take precedence over
+    /// hashing, and may be used to pin a task to a shard, e.g. while its data is being migrated.
+    #[serde(default)]
+    pub task_assignments: HashMap<TaskId, String>,
+}
+
+/// Configuration for a single shard.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ShardConfig {
+    /// A unique, stable name for this shard. Renaming a shard changes which tasks hash to it.
+    pub name: String,
+
+    /// Base URL of this shard's DAP API, to which a router should direct requests for the shard's
+    /// tasks.
+    pub url: Url,
+
+    /// Database used by this shard. This is only needed by tools that move tasks between shards.
+    #[serde(default)]
+    pub database: Option<DbConfig>,
+}
+
+/// Errors that may occur when constructing a [`ShardMap`].
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("no shards configured")]
+    NoShards,
+    #[error("duplicate shard name {0}")]
+    DuplicateShard(String),
+    #[error("task {0} assigned to unknown shard {1}")]
+    UnknownShard(TaskId, String),
+}
+
+/// Determines which shard owns each task.
+#[derive(Clone, Debug)]
+pub struct ShardMap {
+    config: ShardingConfig,
+}
+
+impl ShardMap {
+    /// Validates the provided configuration, and constructs a shard map from it.
+    pub fn new(config: ShardingConfig) -> Result<Self, Error> {
+        if config.shards.is_empty() {
+            return Err(Error::NoShards);
+        }
+        let mut names = HashSet::new();
+        for shard in &config.shards {
+            if !names.insert(shard.name.as_str()) {
+                return Err(Error::DuplicateShard(shard.name.clone()));
+            }
+        }
+        for (task_id, shard_name) in &config.task_assignments {
+            if !names.contains(shard_name.as_str()) {
+                return Err(Error::UnknownShard(*task_id, shard_name.clone()));
+            }
+        }
+        Ok(Self { config })
+    }
+
+    /// Returns all configured shards.
+    pub fn shards(&self) -> &[ShardConfig] {
+        &self.config.shards
+    }
+
+    /// Returns the shard with the given name, if any.
+    pub fn shard(&self, name: &str) -> Option<&ShardConfig> {
+        self.config.shards.iter().find(|shard| shard.name == name)
+    }
+
+    /// Returns the shard that owns the given task.
+    pub fn shard_for_task(&self, task_id: &TaskId) -> &ShardConfig {
+        if let Some(shard) = self
+            .config
+            .task_assignments
+            .get(task_id)
+            .and_then(|name| self.shard(name))
+        {
+            return shard;
+        }
+
+        // Unwrap safety: the constructor checks that there is at least one shard.
+        self.config
+            .shards
+            .iter()
+            .max_by_key(|shard| Self::rendezvous_score(&shard.name, task_id))
+            .unwrap()
+    }
+
+    /// Computes the rendezvous (highest random weight) score of a shard for a task.
+    fn rendezvous_score(shard_name: &str, task_id: &TaskId) -> [u8; 32] {
+        let mut input = Vec::with_capacity(shard_name.len() + 1 + TaskId::LEN);
+        input.extend_from_slice(shard_name.as_bytes());
+        input.push(0);
+        input.extend_from_slice(task_id.as_ref());
+        // Unwrap safety: SHA-256 digests are always 32 bytes long.
+        digest(&SHA256, &input).as_ref().try_into().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sharding::{Error, ShardConfig, ShardMap, ShardingConfig};
+    use assert_matches::assert_matches;
+    use janus_messages::TaskId;
+    use rand::random;
+    use std::collections::HashMap;
+
+    fn shard(name: &str) -> ShardConfig {
+        ShardConfig {
+            name: name.to_string(),
+            url: format!("https://{name}.example.com/").parse().unwrap(),
+            database: None,
+        }
+    }
+
+    #[test]
+    fn documentation_config_example() {
+        ShardMap::new(
+            serde_yaml::from_str(include_str!("../../docs/samples/sharding.yaml")).unwrap(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn invalid_config() {
+        assert_matches!(
+            ShardMap::new(ShardingConfig {
+                shards: Vec::new(),
+                task_assignments: HashMap::new(),
+            }),
+            Err(Error::NoShards)
+        );
+        assert_matches!(
+            ShardMap::new(ShardingConfig {
+                shards: Vec::from([shard("a"), shard("a")]),
+                task_assignments: HashMap::new(),
+            }),
+            Err(Error::DuplicateShard(name)) => assert_eq!(name, "a")
+        );
+        assert_matches!(
+            ShardMap::new(ShardingConfig {
+                shards: Vec::from([shard("a")]),
+                task_assignments: HashMap::from([(random(), "b".to_string())]),
+            }),
+            Err(Error::UnknownShard(_, name)) => assert_eq!(name, "b")
+        );
+    }
+
+    #[test]
+    fn explicit_assignment() {
+        let task_ids: Vec<TaskId> = (0..32).map(|_| random()).collect();
+        let shard_map = ShardMap::new(ShardingConfig {
+            shards: Vec::from([shard("a"), shard("b")]),
+            task_assignments: task_ids.iter().map(|id| (*id, "b".to_string())).collect(),
+        })
+        .unwrap();
+
+        for task_id in &task_ids {
+            assert_eq!(shard_map.shard_for_task(task_id).name, "b");
+        }
+    }
+
+    #[test]
+    fn hashing_is_stable_and_balanced() {
+        let task_ids: Vec<TaskId> = (0..1000).map(|_| random()).collect();
+        let three_shards = ShardMap::new(ShardingConfig {
+            shards: Vec::from([shard("a"), shard("b"), shard("c")]),
+            task_assignments: HashMap::new(),
+        })
+        .unwrap();
+        let four_shards = ShardMap::new(ShardingConfig {
+            shards: Vec::from([shard("c"), shard("b"), shard("a"), shard("d")]),
+            task_assignments: HashMap::new(),
+        })
+        .unwrap();
+
+        let mut counts = HashMap::new();
+        for task_id in &task_ids {
+            let before = &three_shards.shard_for_task(task_id).name;
+            let after = &four_shards.shard_for_task(task_id).name;
+            *counts.entry(before.clone()).or_insert(0) += 1;
+
+            // Assignment doesn't depend on the order of shards, and adding a shard only moves
+            // tasks to the new shard.
+            assert!(
+                after == before || after == "d",
+                "{task_id} moved {before} -> {after}"
+            );
+        }
+
+        for name in ["a", "b", "c"] {
+            assert!(counts[name] > 200, "{counts:?}");
+        }
+    }
+}
@@ -1,5 +1,6 @@
 //! Configures a tracing subscriber for Janus.
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::{
     io::{stdout, IsTerminal},
@@ -13,7 +14,11 @@ use tracing_subscriber::{
 };
 
 #[cfg(feature = "otlp")]
-use opentelemetry_otlp::WithExportConfig;
+use {
+    opentelemetry::KeyValue,
+    opentelemetry_otlp::WithExportConfig,
+    opentelemetry_sdk::{trace::Sampler, Resource},
+};
 
 /// Errors from initializing trace subscriber.
 #[derive(Debug, thiserror::Error)]
@@ -32,7 +37,8 @@ pub enum Error {
 }
 
 /// Configuration for the tracing subscriber.
-#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct TraceConfiguration {
     /// If true, uses a [`tracing_subscriber::fmt::TestWriter`] to capture trace
     /// events when running tests
@@ -54,6 +60,7 @@ pub struct TraceConfiguration {
     pub tokio_console_config: TokioConsoleConfiguration,
     /// Configuration for OpenTelemetry traces, with a choice of exporters.
     #[serde(default, with = "serde_yaml::with::singleton_map")]
+    #[schemars(with = "Option<OpenTelemetryTraceConfiguration>")]
     pub open_telemetry_config: Option<OpenTelemetryTraceConfiguration>,
     /// Flag to write tracing spans and events to JSON files. This is compatible with Chrome's
     /// trace viewer, available at `chrome://tracing`, and [Perfetto](https://ui.perfetto.dev).
@@ -62,7 +69,8 @@ pub struct TraceConfiguration {
 }
 
 /// Configuration related to tokio-console.
-#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct TokioConsoleConfiguration {
     /// If true, a tokio-console tracing subscriber is configured to monitor
     /// the async runtime, and listen for TCP connections. (Requires building
@@ -78,19 +86,31 @@ pub struct TokioConsoleConfiguration {
 }
 
 /// Selection of an exporter for OpenTelemetry spans.
-#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum OpenTelemetryTraceConfiguration {
     Otlp(OtlpTraceConfiguration),
 }
 
 /// Configuration options specific to the OpenTelemetry OTLP exporter.
-#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct OtlpTraceConfiguration {
     /// gRPC endpoint for OTLP exporter.
     pub endpoint: String,
+    /// Value of the `service.name` resource attribute of exported spans. If not set, it is taken
+    /// from the `OTEL_SERVICE_NAME` environment variable, as usual.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service_name: Option<String>,
+    /// Fraction of traces to export, between 0 and 1. Traces are sampled at their root span, and
+    /// child spans follow their parent's sampling decision. If not set, all traces are exported.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sampling_ratio: Option<f64>,
 }
 
+// Configurations are only compared in tests, so the float in `sampling_ratio` doesn't matter.
+impl Eq for OtlpTraceConfiguration {}
+
 /// Create a base tracing layer with configuration used in all subscribers
 fn base_layer<S>() -> tracing_subscriber::fmt::Layer<S> {
     tracing_subscriber::fmt::layer()
@@ -184,6 +204,21 @@ pub fn install_trace_subscriber(
     #[cfg(feature = "otlp")]
     if let Some(OpenTelemetryTraceConfiguration::Otlp(otlp_config)) = &config.open_telemetry_config
     {
+        let sampler = match otlp_config.sampling_ratio {
+            Some(ratio) if (0.0..=1.0).contains(&ratio) => {
+                Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(ratio)))
+            }
+            Some(_) => return Err(Error::Other("OTLP sampling ratio must be between 0 and 1")),
+            None => Sampler::ParentBased(Box::new(Sampler::AlwaysOn)),
+        };
+        let mut resource = crate::metrics::resource();
+        if let Some(service_name) = &otlp_config.service_name {
+            resource = resource.merge(&Resource::new([KeyValue::new(
+                "service.name",
+                service_name.clone(),
+            )]));
+        }
+
         let tracer = opentelemetry_otlp::new_pipeline()
             .tracing()
             .with_exporter(
@@ -191,6 +226,11 @@ pub fn install_trace_subscriber(
                     .tonic()
                     .with_endpoint(otlp_config.endpoint.clone()),
             )
+            .with_trace_config(
+                opentelemetry_sdk::trace::config()
+                    .with_sampler(sampler)
+                    .with_resource(resource),
+            )
             .install_batch(opentelemetry_sdk::runtime::Tokio)?;
 
         let telemetry = tracing_opentelemetry::layer()
@@ -10,11 +10,14 @@ use janus_aggregator::{
         aggregation_job_driver::Config as AggregationJobDriverConfig,
         aggregator::{AggregatorApi, Config as AggregatorConfig},
         collection_job_driver::Config as CollectionJobDriverConfig,
+        upload_ingester::Config as UploadIngesterConfig,
     },
     config::{
         default_max_transaction_retries, BinaryConfig, CommonConfig, DbConfig, JobDriverConfig,
-        TaskprovConfig,
+        MeasurementLengthLimitsConfig, ResponseCompressionConfig, TaskprovConfig,
+        UploadQueueConfig, UploadSamplingConfig, UploadValidationConfig,
     },
+    feature_flags::FeatureFlagsConfig,
     metrics::MetricsConfiguration,
     trace::TraceConfiguration,
 };
@@ -251,14 +254,26 @@ async fn aggregator_shutdown() {
                 connection_pool_timeouts_secs: 60,
                 check_schema_version: true,
                 tls_trust_store_path: None,
+                read_replica_url: None,
             },
             logging_config: TraceConfiguration::default(),
             metrics_config: MetricsConfiguration::default(),
             health_check_listen_address: "127.0.0.1:9001".parse().unwrap(),
             max_transaction_retries: default_max_transaction_retries(),
+            datastore_key_encryption: None,
+            feature_flags: FeatureFlagsConfig::default(),
+            datastore_migration: None,
+            shutdown_drain_timeout_secs: None,
+            deployment_name: None,
+            secrets: None,
         },
         taskprov_config: TaskprovConfig::default(),
+        measurement_length_limits: MeasurementLengthLimitsConfig::default(),
         garbage_collection: None,
+        metrics_snapshots: None,
+        peer_health_probing: None,
+        canary: None,
+        background_runtime: None,
         listen_address: aggregator_listen_address,
         aggregator_api: Some(AggregatorApi {
             listen_address: Some(aggregator_api_listen_address),
@@ -271,6 +286,18 @@ async fn aggregator_shutdown() {
         batch_aggregation_shard_count: 32,
         task_counter_shard_count: 64,
         global_hpke_configs_refresh_interval: None,
+        min_auth_failure_response_time_ms: 0,
+        response_compression: ResponseCompressionConfig::default(),
+        upload_validation: UploadValidationConfig::default(),
+        upload_sampling: UploadSamplingConfig::default(),
+        upload_queue: None,
+        upload_routing: None,
+        upload_label: None,
+        report_sink: None,
+        public_task_stats: false,
+        max_collection_interval_time_precisions: None,
+        max_aggregate_request_memory_bytes: None,
+        strict_conformance: false,
     };
 
     graceful_shutdown(trycmd::cargo::cargo_bin!("aggregator"), config).await;
@@ -286,18 +313,28 @@ async fn aggregation_job_creator_shutdown() {
                 connection_pool_timeouts_secs: 60,
                 check_schema_version: true,
                 tls_trust_store_path: None,
+                read_replica_url: None,
             },
             logging_config: TraceConfiguration::default(),
             metrics_config: MetricsConfiguration::default(),
             health_check_listen_address: "127.0.0.1:9001".parse().unwrap(),
             max_transaction_retries: default_max_transaction_retries(),
+            datastore_key_encryption: None,
+            feature_flags: FeatureFlagsConfig::default(),
+            datastore_migration: None,
+            shutdown_drain_timeout_secs: None,
+            deployment_name: None,
+            secrets: None,
         },
         batch_aggregation_shard_count: 32,
         tasks_update_frequency_secs: 3600,
+        task_discovery_interval_secs: None,
         aggregation_job_creation_interval_secs: 60,
         min_aggregation_job_size: 100,
         max_aggregation_job_size: 100,
         aggregation_job_creation_report_window: 5000,
+        max_adaptive_aggregation_job_size: None,
+        leader_lease_duration_s: None,
     };
 
     graceful_shutdown(trycmd::cargo::cargo_bin!("aggregation_job_creator"), config).await;
@@ -313,11 +350,18 @@ async fn aggregation_job_driver_shutdown() {
                 connection_pool_timeouts_secs: 60,
                 check_schema_version: true,
                 tls_trust_store_path: None,
+                read_replica_url: None,
             },
             logging_config: TraceConfiguration::default(),
             metrics_config: MetricsConfiguration::default(),
             health_check_listen_address: "127.0.0.1:9001".parse().unwrap(),
             max_transaction_retries: default_max_transaction_retries(),
+            datastore_key_encryption: None,
+            feature_flags: FeatureFlagsConfig::default(),
+            datastore_migration: None,
+            shutdown_drain_timeout_secs: None,
+            deployment_name: None,
+            secrets: None,
         },
         job_driver_config: JobDriverConfig {
             job_discovery_interval_secs: 10,
@@ -333,6 +377,7 @@ async fn aggregation_job_driver_shutdown() {
         },
         taskprov_config: TaskprovConfig::default(),
         batch_aggregation_shard_count: 32,
+        max_concurrent_jobs_per_task: None,
     };
 
     graceful_shutdown(trycmd::cargo::cargo_bin!("aggregation_job_driver"), config).await;
@@ -348,11 +393,18 @@ async fn collection_job_driver_shutdown() {
                 connection_pool_timeouts_secs: 60,
                 check_schema_version: true,
                 tls_trust_store_path: None,
+                read_replica_url: None,
             },
             logging_config: TraceConfiguration::default(),
             metrics_config: MetricsConfiguration::default(),
             health_check_listen_address: "127.0.0.1:9001".parse().unwrap(),
             max_transaction_retries: default_max_transaction_retries(),
+            datastore_key_encryption: None,
+            feature_flags: FeatureFlagsConfig::default(),
+            datastore_migration: None,
+            shutdown_drain_timeout_secs: None,
+            deployment_name: None,
+            secrets: None,
         },
         job_driver_config: JobDriverConfig {
             job_discovery_interval_secs: 10,
@@ -368,7 +420,47 @@ async fn collection_job_driver_shutdown() {
         },
         batch_aggregation_shard_count: 32,
         min_collection_job_retry_delay_secs: 1,
+        result_sink: None,
     };
 
     graceful_shutdown(trycmd::cargo::cargo_bin!("collection_job_driver"), config).await;
 }
+
+#[tokio::test(flavor = "multi_thread")]
+#[cfg_attr(not(target_os = "linux"), ignore)]
+async fn upload_ingester_shutdown() {
+    let spool_dir = tempfile::tempdir().unwrap();
+    let config = UploadIngesterConfig {
+        common_config: CommonConfig {
+            database: DbConfig {
+                url: "postgres://localhost".parse().unwrap(),
+                connection_pool_timeouts_secs: 60,
+                check_schema_version: true,
+                tls_trust_store_path: None,
+                read_replica_url: None,
+            },
+            logging_config: TraceConfiguration::default(),
+            metrics_config: MetricsConfiguration::default(),
+            health_check_listen_address: "127.0.0.1:9001".parse().unwrap(),
+            max_transaction_retries: default_max_transaction_retries(),
+            datastore_key_encryption: None,
+            feature_flags: FeatureFlagsConfig::default(),
+            datastore_migration: None,
+            shutdown_drain_timeout_secs: None,
+            deployment_name: None,
+            secrets: None,
+        },
+        upload_queue: UploadQueueConfig::Directory {
+            path: spool_dir.path().to_path_buf(),
+        },
+        max_upload_batch_size: 100,
+        max_upload_batch_write_delay_ms: 250,
+        task_counter_shard_count: 32,
+        max_reports_per_receive: 1000,
+        queue_poll_interval_ms: 1000,
+        upload_sampling: UploadSamplingConfig::default(),
+        report_sink: None,
+    };
+
+    graceful_shutdown(trycmd::cargo::cargo_bin!("upload_ingester"), config).await;
+}
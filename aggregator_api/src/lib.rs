@@ -9,16 +9,22 @@ use janus_aggregator_core::{
     datastore::{self, Datastore},
     instrumented,
 };
-use janus_core::{auth_tokens::AuthenticationToken, hpke, http::extract_bearer_token, time::Clock};
-use janus_messages::{HpkeConfigId, RoleParseError, TaskId};
+use janus_core::{
+    auth_tokens::{AuthenticationToken, AuthenticationTokenHash},
+    hpke,
+    http::extract_bearer_token,
+    time::Clock,
+    vdaf::MeasurementLengthLimits,
+};
+use janus_messages::{CollectionJobId, HpkeConfigId, RoleParseError, TaskId};
 use opentelemetry::metrics::Meter;
 use routes::*;
-use std::{borrow::Cow, str::FromStr, sync::Arc};
+use std::{borrow::Cow, collections::HashSet, str::FromStr, sync::Arc};
 use tracing::error;
 use trillium::{
     Conn, Handler,
     KnownHeaderName::{Accept, ContentType},
-    Status,
+    Method, Status,
     Status::{NotAcceptable, UnsupportedMediaType},
 };
 use trillium_api::{api, Halt, State};
@@ -31,6 +37,8 @@ use url::Url;
 pub struct Config {
     pub auth_tokens: Vec<AuthenticationToken>,
     pub public_dap_url: Url,
+    /// Tasks whose VDAF measurements exceed these limits can't be created.
+    pub measurement_length_limits: MeasurementLengthLimits,
 }
 
 /// Content type
@@ -83,7 +91,7 @@ pub fn aggregator_api_handler<C: Clock>(
                 .map(|route_spec| Cow::Owned(route_spec.to_string()))
         }),
         // Authorization check.
-        api(auth_check),
+        api(auth_check::<C>),
         // Check content type and accept headers
         ReplaceMimeTypes,
         // Main functionality router.
@@ -91,12 +99,29 @@ pub fn aggregator_api_handler<C: Clock>(
             .get("/", instrumented(api(get_config)))
             .get("/task_ids", instrumented(api(get_task_ids::<C>)))
             .post("/tasks", instrumented(api(post_task::<C>)))
+            .post("/tasks/bulk", instrumented(api(post_tasks::<C>)))
             .get("/tasks/:task_id", instrumented(api(get_task::<C>)))
             .delete("/tasks/:task_id", instrumented(api(delete_task::<C>)))
             .get(
                 "/tasks/:task_id/metrics/uploads",
                 instrumented(api(get_task_upload_metrics::<C>)),
             )
+            .get(
+                "/tasks/:task_id/metrics/snapshots",
+                instrumented(api(get_task_metrics_snapshots::<C>)),
+            )
+            .get(
+                "/tasks/:task_id/peer_health",
+                instrumented(api(get_task_peer_health::<C>)),
+            )
+            .get(
+                "/tasks/:task_id/aggregation_job_creator_state",
+                instrumented(api(get_task_aggregation_job_creator_state::<C>)),
+            )
+            .get(
+                "/tasks/:task_id/collection_jobs/:collection_job_id/progress",
+                instrumented(api(get_collection_job_progress::<C>)),
+            )
             .get(
                 "/hpke_configs",
                 instrumented(api(get_global_hpke_configs::<C>)),
@@ -132,22 +157,89 @@ pub fn aggregator_api_handler<C: Clock>(
     )
 }
 
-async fn auth_check(conn: &mut Conn, (): ()) -> impl Handler {
-    let (Some(cfg), Ok(Some(bearer_token))) =
-        (conn.state::<Arc<Config>>(), extract_bearer_token(conn))
-    else {
+/// The tasks that an authenticated request may access.
+#[derive(Clone, Debug)]
+enum AccessScope {
+    /// The request was authenticated with one of the configured tokens, and may access anything.
+    All,
+    /// The request was authenticated with an admin API key stored in the datastore, and may only
+    /// access the given tasks.
+    Tasks(Arc<HashSet<TaskId>>),
+}
+
+impl AccessScope {
+    fn allows(&self, task_id: &TaskId) -> bool {
+        match self {
+            Self::All => true,
+            Self::Tasks(task_ids) => task_ids.contains(task_id),
+        }
+    }
+}
+
+async fn auth_check<C: Clock>(conn: &mut Conn, (): ()) -> impl Handler {
+    let (Some(cfg), Some(ds), Ok(Some(bearer_token))) = (
+        conn.state::<Arc<Config>>(),
+        conn.state::<Arc<Datastore<C>>>(),
+        extract_bearer_token(conn),
+    ) else {
         return Some((Status::Unauthorized, Halt));
     };
 
-    if cfg.auth_tokens.iter().any(|key| bearer_token == *key) {
+    if cfg.auth_tokens.contains(&bearer_token) {
         // Authorization succeeds.
-        None
-    } else {
+        conn.set_state(AccessScope::All);
+        return None;
+    }
+
+    // Otherwise, the token may belong to an admin API key scoped to particular tasks.
+    let ds = Arc::clone(ds);
+    let token_hash = Arc::new(AuthenticationTokenHash::from(&bearer_token));
+    let admin_api_key = match ds
+        .run_tx("auth_check", |tx| {
+            let token_hash = Arc::clone(&token_hash);
+            Box::pin(async move { tx.get_unexpired_admin_api_key(&token_hash).await })
+        })
+        .await
+    {
+        Ok(admin_api_key) => admin_api_key,
+        Err(err) => {
+            error!(?err, "Couldn't look up admin API key");
+            return Some((Status::InternalServerError, Halt));
+        }
+    };
+    match admin_api_key {
+        Some(admin_api_key) if task_scoped_route(conn.method(), conn.path()) => {
+            // Authorization succeeds, but access to individual tasks is checked later.
+            conn.set_state(AccessScope::Tasks(Arc::new(
+                admin_api_key.task_ids().iter().copied().collect(),
+            )));
+            None
+        }
+        // Admin API keys may not be used for operations that affect the whole aggregator.
+        Some(_) => Some((Status::Forbidden, Halt)),
         // Authorization fails.
-        Some((Status::Unauthorized, Halt))
+        None => Some((Status::Unauthorized, Halt)),
     }
 }
 
+/// Returns true if a request authenticated with an admin API key scoped to particular tasks may
+/// be made to the given route. Such requests may only fetch the API configuration, list task IDs,
+/// read or delete individual tasks, and read the progress of their collection jobs.
+fn task_scoped_route(method: Method, path: &str) -> bool {
+    let segments: Vec<_> = path.trim_start_matches('/').split('/').collect();
+    matches!(
+        (method, segments.as_slice()),
+        (Method::Get, [""] | ["task_ids"])
+            | (Method::Get | Method::Delete, ["tasks", _])
+            | (
+                Method::Get,
+                ["tasks", _, "metrics", "uploads" | "snapshots"]
+                    | ["tasks", _, "peer_health" | "aggregation_job_creator_state"]
+                    | ["tasks", _, "collection_jobs", _, "progress"]
+            )
+    )
+}
+
 #[derive(Debug, thiserror::Error)]
 enum Error {
     /// Errors that should never happen under expected behavior.
@@ -219,15 +311,32 @@ impl Handler for Error {
 
 trait ConnExt {
     fn task_id_param(&self) -> Result<TaskId, Error>;
+    fn collection_job_id_param(&self) -> Result<CollectionJobId, Error>;
     fn hpke_config_id_param(&self) -> Result<HpkeConfigId, Error>;
 }
 
 impl ConnExt for Conn {
     fn task_id_param(&self) -> Result<TaskId, Error> {
-        TaskId::from_str(
+        let task_id = TaskId::from_str(
             self.param("task_id")
                 .ok_or_else(|| Error::Internal("Missing task_id parameter".to_string()))?,
         )
+        .map_err(|err| Error::BadRequest(format!("{:?}", err)))?;
+
+        // Tasks outside of the request's access scope are indistinguishable from tasks that don't
+        // exist.
+        match self.state::<AccessScope>() {
+            Some(access_scope) if access_scope.allows(&task_id) => Ok(task_id),
+            _ => Err(Error::NotFound),
+        }
+    }
+
+    fn collection_job_id_param(&self) -> Result<CollectionJobId, Error> {
+        CollectionJobId::from_str(
+            self.param("collection_job_id").ok_or_else(|| {
+                Error::Internal("Missing collection_job_id parameter".to_string())
+            })?,
+        )
         .map_err(|err| Error::BadRequest(format!("{:?}", err)))
     }
 
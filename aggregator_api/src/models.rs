@@ -1,12 +1,16 @@
 use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use derivative::Derivative;
 use janus_aggregator_core::{
-    datastore::models::{GlobalHpkeKeypair, HpkeKeyState, TaskUploadCounter},
+    datastore::models::{
+        AggregationJobCreatorTaskState, CollectionJobProgress, CollectionJobStateCode,
+        GlobalHpkeKeypair, HpkeKeyState, TaskMetricsSnapshot, TaskPeerHealth, TaskUploadCounter,
+    },
     task::{AggregatorTask, QueryType},
     taskprov::{PeerAggregator, VerifyKeyInit},
 };
 use janus_core::{
     auth_tokens::{AuthenticationToken, AuthenticationTokenHash},
+    hpke::HpkeCiphersuite,
     vdaf::VdafInstance,
 };
 use janus_messages::{
@@ -88,6 +92,22 @@ pub(crate) struct PostTaskReq {
     /// sub-protocol requests received from the helper. If this aggregator is the helper, the value
     /// is `None`.
     pub(crate) collector_auth_token_hash: Option<AuthenticationTokenHash>,
+    /// Algorithms used by the HPKE keypair generated for this task. Defaults to X25519,
+    /// HKDF-SHA256, and AES-128-GCM.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) hpke_ciphersuite: Option<HpkeCiphersuite>,
+}
+
+/// Request to create several tasks at once. Either all of the tasks are created, or none are.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct PostTasksReq {
+    pub(crate) tasks: Vec<PostTaskReq>,
+}
+
+/// Response to a [`PostTasksReq`], holding the created tasks in the order they were requested.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct PostTasksResp {
+    pub(crate) tasks: Vec<TaskResp>,
 }
 
 #[derive(Clone, Derivative, PartialEq, Eq, Serialize, Deserialize)]
@@ -169,6 +189,70 @@ impl TryFrom<&AggregatorTask> for TaskResp {
 #[derive(Serialize)]
 pub(crate) struct GetTaskUploadMetricsResp(pub(crate) TaskUploadCounter);
 
+#[derive(Serialize)]
+pub(crate) struct GetTaskMetricsSnapshotsResp(pub(crate) Vec<TaskMetricsSnapshot>);
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct GetTaskPeerHealthResp {
+    pub(crate) last_probed_at: Time,
+    pub(crate) reachable: bool,
+    pub(crate) latency_ms: Option<u64>,
+    pub(crate) error: Option<String>,
+    pub(crate) last_reachable_at: Option<Time>,
+    pub(crate) consecutive_failures: u64,
+}
+
+impl From<TaskPeerHealth> for GetTaskPeerHealthResp {
+    fn from(health: TaskPeerHealth) -> Self {
+        Self {
+            last_probed_at: *health.last_probed_at(),
+            reachable: health.reachable(),
+            latency_ms: health.latency_ms(),
+            error: health.error().map(str::to_string),
+            last_reachable_at: health.last_reachable_at().copied(),
+            consecutive_failures: health.consecutive_failures(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct GetTaskAggregationJobCreatorStateResp {
+    pub(crate) discovered_at: Time,
+    pub(crate) last_run_at: Option<Time>,
+    pub(crate) next_run_at: Time,
+    pub(crate) last_error: Option<String>,
+}
+
+impl From<AggregationJobCreatorTaskState> for GetTaskAggregationJobCreatorStateResp {
+    fn from(state: AggregationJobCreatorTaskState) -> Self {
+        Self {
+            discovered_at: *state.discovered_at(),
+            last_run_at: state.last_run_at().copied(),
+            next_run_at: *state.next_run_at(),
+            last_error: state.last_error().map(str::to_string),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct GetCollectionJobProgressResp {
+    pub(crate) state: CollectionJobStateCode,
+    pub(crate) outstanding_aggregation_jobs: u64,
+    pub(crate) recently_terminated_aggregation_jobs: u64,
+    pub(crate) estimated_completion: Option<Time>,
+}
+
+impl From<CollectionJobProgress> for GetCollectionJobProgressResp {
+    fn from(progress: CollectionJobProgress) -> Self {
+        Self {
+            state: progress.state(),
+            outstanding_aggregation_jobs: progress.outstanding_aggregation_jobs(),
+            recently_terminated_aggregation_jobs: progress.recently_terminated_aggregation_jobs(),
+            estimated_completion: progress.estimated_completion().copied(),
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub(crate) struct GlobalHpkeConfigResp {
     pub(crate) config: HpkeConfig,
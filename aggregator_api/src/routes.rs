@@ -1,31 +1,35 @@
 use crate::{
     models::{
-        AggregatorApiConfig, AggregatorRole, DeleteTaskprovPeerAggregatorReq, GetTaskIdsResp,
-        GetTaskUploadMetricsResp, GlobalHpkeConfigResp, PatchGlobalHpkeConfigReq, PostTaskReq,
-        PostTaskprovPeerAggregatorReq, PutGlobalHpkeConfigReq, SupportedVdaf, TaskResp,
+        AggregatorApiConfig, AggregatorRole, DeleteTaskprovPeerAggregatorReq,
+        GetCollectionJobProgressResp, GetTaskAggregationJobCreatorStateResp, GetTaskIdsResp,
+        GetTaskMetricsSnapshotsResp, GetTaskPeerHealthResp, GetTaskUploadMetricsResp,
+        GlobalHpkeConfigResp, PatchGlobalHpkeConfigReq, PostTaskReq, PostTaskprovPeerAggregatorReq,
+        PostTasksReq, PostTasksResp, PutGlobalHpkeConfigReq, SupportedVdaf, TaskResp,
         TaskprovPeerAggregatorResp,
     },
-    Config, ConnExt, Error,
+    AccessScope, Config, ConnExt, Error,
 };
 use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use janus_aggregator_core::{
-    datastore::{self, Datastore},
+    datastore::{self, models::CollectionJobProgress, Datastore, Transaction},
     task::{AggregatorTask, AggregatorTaskParameters},
     taskprov::PeerAggregator,
     SecretBytes,
 };
 use janus_core::{
-    auth_tokens::AuthenticationTokenHash, hpke::generate_hpke_config_and_private_key, time::Clock,
+    auth_tokens::{AuthenticationToken, AuthenticationTokenHash},
+    hpke::{generate_hpke_config_and_private_key, HpkeKeypair},
+    time::Clock,
 };
 use janus_messages::HpkeConfigId;
 use janus_messages::{
     query_type::Code as SupportedQueryType, Duration, HpkeAeadId, HpkeKdfId, HpkeKemId, Role,
-    TaskId,
+    TaskId, Time,
 };
 use querystring::querify;
 use rand::random;
 use ring::digest::{digest, SHA256};
-use std::{str::FromStr, sync::Arc, unreachable};
+use std::{collections::HashSet, str::FromStr, sync::Arc, unreachable};
 use trillium::{Conn, Status};
 use trillium_api::{Json, State};
 
@@ -65,12 +69,16 @@ pub(super) async fn get_task_ids<C: Clock>(
         .transpose()
         .map_err(|err| Error::BadRequest(format!("Couldn't parse pagination_token: {:?}", err)))?;
 
-    let task_ids = ds
+    let mut task_ids = ds
         .run_tx("get_task_ids", |tx| {
             Box::pin(async move { tx.get_task_ids(lower_bound).await })
         })
         .await?;
+    // Pagination proceeds over all tasks, even if only some of them are returned.
     let pagination_token = task_ids.last().cloned();
+    if let Some(access_scope) = conn.state::<AccessScope>() {
+        task_ids.retain(|task_id| access_scope.allows(task_id));
+    }
 
     Ok(Json(GetTaskIdsResp {
         task_ids,
@@ -78,10 +86,78 @@ pub(super) async fn get_task_ids<C: Clock>(
     }))
 }
 
+/// Extractors for the handlers that create tasks: the datastore, the API configuration, and the
+/// request body.
+type TaskCreationExtractors<C, Req> = (State<Arc<Datastore<C>>>, State<Arc<Config>>, Json<Req>);
+
 pub(super) async fn post_task<C: Clock>(
     _: &mut Conn,
-    (State(ds), Json(req)): (State<Arc<Datastore<C>>>, Json<PostTaskReq>),
+    (State(ds), State(config), Json(req)): TaskCreationExtractors<C, PostTaskReq>,
 ) -> Result<Json<TaskResp>, Error> {
+    let (task, aggregator_auth_token) = task_from_post_task_req(req, &config)?;
+    let task = Arc::new(task);
+
+    ds.run_tx("post_task", |tx| {
+        let task = Arc::clone(&task);
+        Box::pin(async move { put_task_if_absent(tx, &task).await })
+    })
+    .await?;
+
+    Ok(Json(task_resp(&task, aggregator_auth_token)?))
+}
+
+/// Creates all of the tasks in the request in a single transaction, so that either every task is
+/// created or none are. As with [`post_task`], requesting a task that already exists with the same
+/// parameters is not an error.
+pub(super) async fn post_tasks<C: Clock>(
+    _: &mut Conn,
+    (State(ds), State(config), Json(req)): TaskCreationExtractors<C, PostTasksReq>,
+) -> Result<Json<PostTasksResp>, Error> {
+    if req.tasks.is_empty() {
+        return Err(Error::BadRequest("no tasks were provided".to_string()));
+    }
+
+    let tasks = req
+        .tasks
+        .into_iter()
+        .map(|req| task_from_post_task_req(req, &config))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut task_ids = HashSet::new();
+    for (task, _) in &tasks {
+        if !task_ids.insert(*task.id()) {
+            return Err(Error::BadRequest(format!(
+                "task {} was provided more than once",
+                task.id()
+            )));
+        }
+    }
+    let tasks = Arc::new(tasks);
+
+    ds.run_tx("post_tasks", |tx| {
+        let tasks = Arc::clone(&tasks);
+        Box::pin(async move {
+            for (task, _) in tasks.iter() {
+                put_task_if_absent(tx, task).await?;
+            }
+            Ok(())
+        })
+    })
+    .await?;
+
+    Ok(Json(PostTasksResp {
+        tasks: tasks
+            .iter()
+            .map(|(task, aggregator_auth_token)| task_resp(task, aggregator_auth_token.clone()))
+            .collect::<Result<_, _>>()?,
+    }))
+}
+
+/// Validates a task creation request, and constructs the task it describes. If this aggregator is
+/// the helper, an aggregator auth token is generated for the task, and returned alongside it.
+fn task_from_post_task_req(
+    req: PostTaskReq,
+    config: &Config,
+) -> Result<(AggregatorTask, Option<AuthenticationToken>), Error> {
     if !matches!(req.role, Role::Leader | Role::Helper) {
         return Err(Error::BadRequest(format!("invalid role {}", req.role)));
     }
@@ -98,6 +174,9 @@ pub(super) async fn post_task<C: Clock>(
             vdaf_verify_key_bytes.len()
         )));
     }
+    req.vdaf
+        .check_measurement_length(&config.measurement_length_limits)
+        .map_err(|err| Error::BadRequest(format!("Unsupported VDAF: {err}")))?;
 
     // DAP recommends deriving the task ID from the VDAF verify key. We deterministically obtain a
     // 32 byte task ID by taking SHA-256(VDAF verify key).
@@ -107,6 +186,10 @@ pub(super) async fn post_task<C: Clock>(
 
     let vdaf_verify_key = SecretBytes::new(vdaf_verify_key_bytes);
 
+    let hpke_keypair =
+        HpkeKeypair::generate(random(), &req.hpke_ciphersuite.unwrap_or_default())
+            .map_err(|err| Error::BadRequest(format!("Unsupported hpke_ciphersuite: {err}")))?;
+
     let (aggregator_auth_token, aggregator_parameters) = match req.role {
         Role::Leader => {
             let aggregator_auth_token = req.aggregator_auth_token.ok_or_else(|| {
@@ -153,74 +236,72 @@ pub(super) async fn post_task<C: Clock>(
         _ => unreachable!(),
     };
 
-    let task = Arc::new(
-        AggregatorTask::new(
-            task_id,
-            /* peer_aggregator_endpoint */ req.peer_aggregator_endpoint,
-            /* query_type */ req.query_type,
-            /* vdaf */ req.vdaf,
-            vdaf_verify_key,
-            /* max_batch_query_count */ req.max_batch_query_count,
-            /* task_expiration */ req.task_expiration,
-            /* report_expiry_age */
-            Some(Duration::from_seconds(3600 * 24 * 7 * 2)), // 2 weeks
-            /* min_batch_size */ req.min_batch_size,
-            /* time_precision */ req.time_precision,
-            /* tolerable_clock_skew */
-            Duration::from_seconds(60), // 1 minute,
-            // hpke_keys
-            // Unwrap safety: we always use a supported KEM.
-            [generate_hpke_config_and_private_key(
-                random(),
-                HpkeKemId::X25519HkdfSha256,
-                HpkeKdfId::HkdfSha256,
-                HpkeAeadId::Aes128Gcm,
-            )
-            .unwrap()],
-            aggregator_parameters,
-        )
-        .map_err(|err| Error::BadRequest(format!("Error constructing task: {err}")))?,
-    );
+    let task = AggregatorTask::builder(
+        task_id,
+        req.peer_aggregator_endpoint,
+        req.query_type,
+        req.vdaf,
+        vdaf_verify_key,
+        aggregator_parameters,
+    )
+    .with_max_batch_query_count(req.max_batch_query_count)
+    .with_task_expiration(req.task_expiration)
+    .with_report_expiry_age(Some(Duration::from_seconds(3600 * 24 * 7 * 2))) // 2 weeks
+    .with_min_batch_size(req.min_batch_size)
+    .with_time_precision(req.time_precision)
+    .with_tolerable_clock_skew(Duration::from_seconds(60)) // 1 minute
+    .with_hpke_keys([hpke_keypair])
+    .build()
+    .map_err(|err| Error::BadRequest(format!("Error constructing task: {err}")))?;
+
+    Ok((task, aggregator_auth_token))
+}
 
-    ds.run_tx("post_task", |tx| {
-        let task = Arc::clone(&task);
-        Box::pin(async move {
-            if let Some(existing_task) = tx.get_aggregator_task(task.id()).await? {
-            // Check whether the existing task in the DB corresponds to the incoming task, ignoring
-            // those fields that are randomly generated.
-            if existing_task.peer_aggregator_endpoint() == task.peer_aggregator_endpoint()
-                && existing_task.query_type() == task.query_type()
-                && existing_task.vdaf() == task.vdaf()
-                && existing_task.opaque_vdaf_verify_key() == task.opaque_vdaf_verify_key()
-                && existing_task.role() == task.role()
-                && existing_task.max_batch_query_count() == task.max_batch_query_count()
-                && existing_task.task_expiration() == task.task_expiration()
-                && existing_task.min_batch_size() == task.min_batch_size()
-                && existing_task.time_precision() == task.time_precision()
-                && existing_task.collector_hpke_config() == task.collector_hpke_config() {
-                    return Ok(())
-                }
-
-                let err = Error::Conflict(
-                    "task with same VDAF verify key and task ID already exists with different parameters".to_string(),
-                );
-                return Err(datastore::Error::User(err.into()));
-            }
+/// Writes the task to the datastore, unless it already exists. It is an error for the task to
+/// already exist with different parameters.
+async fn put_task_if_absent<C: Clock>(
+    tx: &Transaction<'_, C>,
+    task: &AggregatorTask,
+) -> Result<(), datastore::Error> {
+    if let Some(existing_task) = tx.get_aggregator_task(task.id()).await? {
+        // Check whether the existing task in the DB corresponds to the incoming task, ignoring
+        // those fields that are randomly generated.
+        if existing_task.peer_aggregator_endpoint() == task.peer_aggregator_endpoint()
+            && existing_task.query_type() == task.query_type()
+            && existing_task.vdaf() == task.vdaf()
+            && existing_task.opaque_vdaf_verify_key() == task.opaque_vdaf_verify_key()
+            && existing_task.role() == task.role()
+            && existing_task.max_batch_query_count() == task.max_batch_query_count()
+            && existing_task.task_expiration() == task.task_expiration()
+            && existing_task.min_batch_size() == task.min_batch_size()
+            && existing_task.time_precision() == task.time_precision()
+            && existing_task.collector_hpke_config() == task.collector_hpke_config()
+        {
+            return Ok(());
+        }
 
-            tx.put_aggregator_task(&task).await
-        })
-    })
-    .await?;
+        let err = Error::Conflict(format!(
+            "task {} with same VDAF verify key already exists with different parameters",
+            task.id()
+        ));
+        return Err(datastore::Error::User(err.into()));
+    }
+
+    tx.put_aggregator_task(task).await
+}
 
-    let mut task_resp =
-        TaskResp::try_from(task.as_ref()).map_err(|err| Error::Internal(err.to_string()))?;
+fn task_resp(
+    task: &AggregatorTask,
+    aggregator_auth_token: Option<AuthenticationToken>,
+) -> Result<TaskResp, Error> {
+    let mut task_resp = TaskResp::try_from(task).map_err(|err| Error::Internal(err.to_string()))?;
 
     // When creating a new task in the helper, we must put the unhashed aggregator auth token in the
     // response so that divviup-api can later provide it to the leader, but the helper doesn't store
     // the unhashed token and can't later provide it.
     task_resp.aggregator_auth_token = aggregator_auth_token;
 
-    Ok(Json(task_resp))
+    Ok(task_resp)
 }
 
 pub(super) async fn get_task<C: Clock>(
@@ -263,7 +344,7 @@ pub(super) async fn get_task_upload_metrics<C: Clock>(
 ) -> Result<Json<GetTaskUploadMetricsResp>, Error> {
     let task_id = conn.task_id_param()?;
     Ok(Json(GetTaskUploadMetricsResp(
-        ds.run_tx("get_task_upload_metrics", |tx| {
+        ds.run_read_only_tx("get_task_upload_metrics", |tx| {
             Box::pin(async move { tx.get_task_upload_counter(&task_id).await })
         })
         .await?
@@ -271,6 +352,82 @@ pub(super) async fn get_task_upload_metrics<C: Clock>(
     )))
 }
 
+pub(super) async fn get_task_metrics_snapshots<C: Clock>(
+    conn: &mut Conn,
+    State(ds): State<Arc<Datastore<C>>>,
+) -> Result<Json<GetTaskMetricsSnapshotsResp>, Error> {
+    const SINCE_KEY: &str = "since";
+    let task_id = conn.task_id_param()?;
+    // Snapshots are retained after their task is deleted, so an unknown task is not an error.
+    let since = querify(conn.querystring())
+        .into_iter()
+        .find(|&(k, _)| k == SINCE_KEY)
+        .map(|(_, v)| u64::from_str(v).map(Time::from_seconds_since_epoch))
+        .transpose()
+        .map_err(|err| Error::BadRequest(format!("Couldn't parse since: {:?}", err)))?
+        .unwrap_or_else(|| Time::from_seconds_since_epoch(0));
+
+    Ok(Json(GetTaskMetricsSnapshotsResp(
+        ds.run_read_only_tx("get_task_metrics_snapshots", |tx| {
+            Box::pin(async move { tx.get_task_metrics_snapshots(&task_id, &since).await })
+        })
+        .await?,
+    )))
+}
+
+pub(super) async fn get_task_peer_health<C: Clock>(
+    conn: &mut Conn,
+    State(ds): State<Arc<Datastore<C>>>,
+) -> Result<Json<GetTaskPeerHealthResp>, Error> {
+    let task_id = conn.task_id_param()?;
+    Ok(Json(
+        ds.run_read_only_tx("get_task_peer_health", |tx| {
+            Box::pin(async move { tx.get_task_peer_health(&task_id).await })
+        })
+        .await?
+        .ok_or(Error::NotFound)?
+        .into(),
+    ))
+}
+
+pub(super) async fn get_task_aggregation_job_creator_state<C: Clock>(
+    conn: &mut Conn,
+    State(ds): State<Arc<Datastore<C>>>,
+) -> Result<Json<GetTaskAggregationJobCreatorStateResp>, Error> {
+    let task_id = conn.task_id_param()?;
+    Ok(Json(
+        ds.run_read_only_tx("get_task_aggregation_job_creator_state", |tx| {
+            Box::pin(async move { tx.get_aggregation_job_creator_task_state(&task_id).await })
+        })
+        .await?
+        .ok_or(Error::NotFound)?
+        .into(),
+    ))
+}
+
+pub(super) async fn get_collection_job_progress<C: Clock>(
+    conn: &mut Conn,
+    State(ds): State<Arc<Datastore<C>>>,
+) -> Result<Json<GetCollectionJobProgressResp>, Error> {
+    let task_id = conn.task_id_param()?;
+    let collection_job_id = conn.collection_job_id_param()?;
+    Ok(Json(
+        ds.run_read_only_tx("get_collection_job_progress", |tx| {
+            Box::pin(async move {
+                tx.get_collection_job_progress(
+                    &task_id,
+                    &collection_job_id,
+                    &CollectionJobProgress::DEFAULT_THROUGHPUT_WINDOW,
+                )
+                .await
+            })
+        })
+        .await?
+        .ok_or(Error::NotFound)?
+        .into(),
+    ))
+}
+
 pub(super) async fn get_global_hpke_configs<C: Clock>(
     _: &mut Conn,
     State(ds): State<Arc<Datastore<C>>>,
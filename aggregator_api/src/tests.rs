@@ -1,9 +1,11 @@
 use crate::{
     aggregator_api_handler,
     models::{
-        DeleteTaskprovPeerAggregatorReq, GetTaskIdsResp, GetTaskUploadMetricsResp,
-        GlobalHpkeConfigResp, PatchGlobalHpkeConfigReq, PostTaskReq, PostTaskprovPeerAggregatorReq,
-        PutGlobalHpkeConfigReq, TaskResp, TaskprovPeerAggregatorResp,
+        DeleteTaskprovPeerAggregatorReq, GetCollectionJobProgressResp,
+        GetTaskAggregationJobCreatorStateResp, GetTaskIdsResp, GetTaskMetricsSnapshotsResp,
+        GetTaskPeerHealthResp, GetTaskUploadMetricsResp, GlobalHpkeConfigResp,
+        PatchGlobalHpkeConfigReq, PostTaskReq, PostTaskprovPeerAggregatorReq, PostTasksReq,
+        PostTasksResp, PutGlobalHpkeConfigReq, TaskResp, TaskprovPeerAggregatorResp,
     },
     Config, CONTENT_TYPE,
 };
@@ -12,7 +14,11 @@ use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use futures::future::try_join_all;
 use janus_aggregator_core::{
     datastore::{
-        models::{HpkeKeyState, TaskUploadCounter},
+        models::{
+            AdminApiKey, AggregationJobCreatorTaskState, BatchAggregation, BatchAggregationState,
+            CollectionJob, CollectionJobState, CollectionJobStateCode, HpkeKeyState,
+            TaskMetricsSnapshot, TaskUploadCounter,
+        },
         test_util::{ephemeral_datastore, EphemeralDatastore},
         Datastore,
     },
@@ -29,19 +35,20 @@ use janus_core::{
             generate_test_hpke_config_and_private_key,
             generate_test_hpke_config_and_private_key_with_id,
         },
-        HpkeKeypair, HpkePrivateKey,
+        HpkeCiphersuite, HpkeKeypair, HpkePrivateKey,
     },
     test_util::install_test_trace_subscriber,
     time::MockClock,
-    vdaf::VdafInstance,
+    vdaf::{MeasurementLengthLimits, VdafInstance},
 };
 use janus_messages::{
-    Duration, HpkeAeadId, HpkeConfig, HpkeConfigId, HpkeKdfId, HpkeKemId, HpkePublicKey, Role,
-    TaskId, Time,
+    query_type::TimeInterval, CollectionJobId, Duration, HpkeAeadId, HpkeConfig, HpkeConfigId,
+    HpkeKdfId, HpkeKemId, HpkePublicKey, Interval, Query, ReportIdChecksum, Role, TaskId, Time,
 };
+use prio::vdaf::dummy;
 use rand::{distributions::Standard, random, thread_rng, Rng};
 use serde_test::{assert_ser_tokens, assert_tokens, Token};
-use std::{iter, sync::Arc};
+use std::{collections::HashSet, iter, sync::Arc};
 use trillium::{Handler, Status};
 use trillium_testing::{
     assert_response, assert_status,
@@ -62,6 +69,10 @@ async fn setup_api_test() -> (impl Handler, EphemeralDatastore, Arc<Datastore<Mo
                 AuthenticationToken::new_bearer_token_from_string(AUTH_TOKEN).unwrap(),
             ]),
             public_dap_url: "https://dap.url".parse().unwrap(),
+            measurement_length_limits: MeasurementLengthLimits {
+                max_histogram_length: Some(1000),
+                max_vector_length: None,
+            },
         },
         &noop_meter(),
     );
@@ -183,6 +194,116 @@ async fn get_task_ids() {
     );
 }
 
+#[tokio::test]
+async fn admin_api_key_access() {
+    // Setup: write a few tasks to the datastore, and an admin API key scoped to some of them.
+    let (handler, _ephemeral_datastore, ds) = setup_api_test().await;
+    let admin_api_key_token = random::<AuthenticationToken>();
+
+    let mut task_ids: Vec<_> = ds
+        .run_unnamed_tx(|tx| {
+            let admin_api_key_token = admin_api_key_token.clone();
+            Box::pin(async move {
+                let tasks: Vec<_> = iter::repeat_with(|| {
+                    TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Fake)
+                        .build()
+                        .leader_view()
+                        .unwrap()
+                })
+                .take(3)
+                .collect();
+                try_join_all(tasks.iter().map(|task| tx.put_aggregator_task(task))).await?;
+
+                let mut task_ids: Vec<_> = tasks.into_iter().map(|task| *task.id()).collect();
+                task_ids.sort();
+                tx.put_admin_api_key(&AdminApiKey::new(
+                    "team-a".to_string(),
+                    AuthenticationTokenHash::from(&admin_api_key_token),
+                    task_ids[..2].to_vec(),
+                    Time::from_seconds_since_epoch(1000003600),
+                ))
+                .await?;
+                Ok(task_ids)
+            })
+        })
+        .await
+        .unwrap();
+    let other_task_id = task_ids.pop().unwrap();
+    let (_, authorization) = admin_api_key_token.request_authentication();
+
+    // Verify: only tasks within the key's scope are listed, but pagination covers all tasks.
+    assert_response!(
+        get("/task_ids")
+            .with_request_header("Authorization", authorization.clone())
+            .with_request_header("Accept", CONTENT_TYPE)
+            .run_async(&handler)
+            .await,
+        Status::Ok,
+        serde_json::to_string(&GetTaskIdsResp {
+            task_ids: task_ids.clone(),
+            pagination_token: Some(other_task_id),
+        })
+        .unwrap(),
+    );
+
+    // Verify: tasks within the key's scope can be read, and others appear not to exist.
+    assert_status!(
+        get(&format!("/tasks/{}", task_ids[0]))
+            .with_request_header("Authorization", authorization.clone())
+            .with_request_header("Accept", CONTENT_TYPE)
+            .run_async(&handler)
+            .await,
+        Status::Ok
+    );
+    assert_status!(
+        get(&format!("/tasks/{other_task_id}/metrics/uploads"))
+            .with_request_header("Authorization", authorization.clone())
+            .with_request_header("Accept", CONTENT_TYPE)
+            .run_async(&handler)
+            .await,
+        Status::NotFound
+    );
+    assert_status!(
+        delete(&format!("/tasks/{other_task_id}"))
+            .with_request_header("Authorization", authorization.clone())
+            .with_request_header("Accept", CONTENT_TYPE)
+            .run_async(&handler)
+            .await,
+        Status::NotFound
+    );
+
+    // Verify: operations affecting the whole aggregator are forbidden.
+    assert_status!(
+        get("/hpke_configs")
+            .with_request_header("Authorization", authorization.clone())
+            .with_request_header("Accept", CONTENT_TYPE)
+            .run_async(&handler)
+            .await,
+        Status::Forbidden
+    );
+    assert_status!(
+        post("/tasks/bulk")
+            .with_request_body("{}")
+            .with_request_header("Authorization", authorization.clone())
+            .with_request_header("Accept", CONTENT_TYPE)
+            .with_request_header("Content-Type", CONTENT_TYPE)
+            .run_async(&handler)
+            .await,
+        Status::Forbidden
+    );
+
+    // Verify: expired keys are rejected.
+    ds.clock().advance(&Duration::from_seconds(3600));
+    assert_status!(
+        get(&format!("/tasks/{}", task_ids[0]))
+            .with_request_header("Authorization", authorization)
+            .with_request_header("Accept", CONTENT_TYPE)
+            .run_async(&handler)
+            .await,
+        Status::Unauthorized
+    );
+}
+
 #[tokio::test]
 async fn post_task_bad_role() {
     // Setup: create a datastore & handler.
@@ -212,6 +333,54 @@ async fn post_task_bad_role() {
         .clone(),
         aggregator_auth_token: Some(aggregator_auth_token),
         collector_auth_token_hash: Some(AuthenticationTokenHash::from(&random())),
+        hpke_ciphersuite: None,
+    };
+    assert_response!(
+        post("/tasks")
+            .with_request_body(serde_json::to_vec(&req).unwrap())
+            .with_request_header("Authorization", format!("Bearer {AUTH_TOKEN}"))
+            .with_request_header("Accept", CONTENT_TYPE)
+            .with_request_header("Content-Type", CONTENT_TYPE)
+            .run_async(&handler)
+            .await,
+        Status::BadRequest
+    );
+}
+
+#[tokio::test]
+async fn post_task_unsupported_hpke_ciphersuite() {
+    // Setup: create a datastore & handler.
+    let (handler, _ephemeral_datastore, _) = setup_api_test().await;
+
+    let vdaf_verify_key = SecretBytes::new(thread_rng().sample_iter(Standard).take(16).collect());
+    let aggregator_auth_token = AuthenticationToken::DapAuth(random());
+
+    let req = PostTaskReq {
+        peer_aggregator_endpoint: "http://aggregator.endpoint".try_into().unwrap(),
+        query_type: QueryType::TimeInterval,
+        vdaf: VdafInstance::Prio3Count,
+        role: Role::Leader,
+        vdaf_verify_key: URL_SAFE_NO_PAD.encode(&vdaf_verify_key),
+        max_batch_query_count: 12,
+        task_expiration: Some(Time::from_seconds_since_epoch(12345)),
+        min_batch_size: 223,
+        time_precision: Duration::from_seconds(62),
+        collector_hpke_config: generate_hpke_config_and_private_key(
+            random(),
+            HpkeKemId::X25519HkdfSha256,
+            HpkeKdfId::HkdfSha256,
+            HpkeAeadId::Aes128Gcm,
+        )
+        .unwrap()
+        .config()
+        .clone(),
+        aggregator_auth_token: Some(aggregator_auth_token),
+        collector_auth_token_hash: Some(AuthenticationTokenHash::from(&random())),
+        hpke_ciphersuite: Some(HpkeCiphersuite::new(
+            HpkeKemId::P384HkdfSha384,
+            HpkeKdfId::HkdfSha384,
+            HpkeAeadId::Aes256Gcm,
+        )),
     };
     assert_response!(
         post("/tasks")
@@ -225,6 +394,54 @@ async fn post_task_bad_role() {
     );
 }
 
+#[tokio::test]
+async fn post_task_measurement_too_long() {
+    // Setup: create a datastore & handler.
+    let (handler, _ephemeral_datastore, _) = setup_api_test().await;
+
+    let vdaf_verify_key = SecretBytes::new(thread_rng().sample_iter(Standard).take(16).collect());
+    let aggregator_auth_token = AuthenticationToken::DapAuth(random());
+
+    // The histogram has more buckets than the configured limit.
+    let req = PostTaskReq {
+        peer_aggregator_endpoint: "http://aggregator.endpoint".try_into().unwrap(),
+        query_type: QueryType::TimeInterval,
+        vdaf: VdafInstance::Prio3Histogram {
+            length: 1001,
+            chunk_length: 32,
+        },
+        role: Role::Leader,
+        vdaf_verify_key: URL_SAFE_NO_PAD.encode(&vdaf_verify_key),
+        max_batch_query_count: 12,
+        task_expiration: Some(Time::from_seconds_since_epoch(12345)),
+        min_batch_size: 223,
+        time_precision: Duration::from_seconds(62),
+        collector_hpke_config: generate_hpke_config_and_private_key(
+            random(),
+            HpkeKemId::X25519HkdfSha256,
+            HpkeKdfId::HkdfSha256,
+            HpkeAeadId::Aes128Gcm,
+        )
+        .unwrap()
+        .config()
+        .clone(),
+        aggregator_auth_token: Some(aggregator_auth_token),
+        collector_auth_token_hash: Some(AuthenticationTokenHash::from(&random())),
+        hpke_ciphersuite: None,
+    };
+    assert_response!(
+        post("/tasks")
+            .with_request_body(serde_json::to_vec(&req).unwrap())
+            .with_request_header("Authorization", format!("Bearer {AUTH_TOKEN}"))
+            .with_request_header("Accept", CONTENT_TYPE)
+            .with_request_header("Content-Type", CONTENT_TYPE)
+            .run_async(&handler)
+            .await,
+        Status::BadRequest,
+        "Unsupported VDAF: histogram length 1001 exceeds the maximum of 1000"
+    );
+}
+
 #[tokio::test]
 async fn post_task_unauthorized() {
     // Setup: create a datastore & handler.
@@ -254,6 +471,7 @@ async fn post_task_unauthorized() {
         .clone(),
         aggregator_auth_token: Some(aggregator_auth_token),
         collector_auth_token_hash: Some(AuthenticationTokenHash::from(&random())),
+        hpke_ciphersuite: None,
     };
     assert_response!(
         post("/tasks")
@@ -297,6 +515,7 @@ async fn post_task_helper_no_optional_fields() {
         .clone(),
         aggregator_auth_token: None,
         collector_auth_token_hash: None,
+        hpke_ciphersuite: None,
     };
     let mut conn = post("/tasks")
         .with_request_body(serde_json::to_vec(&req).unwrap())
@@ -387,6 +606,7 @@ async fn post_task_helper_with_aggregator_auth_token() {
         .clone(),
         aggregator_auth_token: Some(aggregator_auth_token),
         collector_auth_token_hash: None,
+        hpke_ciphersuite: None,
     };
     assert_response!(
         post("/tasks")
@@ -432,6 +652,7 @@ async fn post_task_idempotence() {
         aggregator_auth_token: Some(aggregator_auth_token.clone()),
 
         collector_auth_token_hash: Some(AuthenticationTokenHash::from(&random())),
+        hpke_ciphersuite: None,
     };
 
     let post_task = || async {
@@ -514,6 +735,11 @@ async fn post_task_leader_all_optional_fields() {
         .clone(),
         aggregator_auth_token: Some(aggregator_auth_token.clone()),
         collector_auth_token_hash: Some(collector_auth_token_hash.clone()),
+        hpke_ciphersuite: Some(HpkeCiphersuite::new(
+            HpkeKemId::P256HkdfSha256,
+            HpkeKdfId::HkdfSha384,
+            HpkeAeadId::Aes256Gcm,
+        )),
     };
     let mut conn = post("/tasks")
         .with_request_body(serde_json::to_vec(&req).unwrap())
@@ -567,6 +793,13 @@ async fn post_task_leader_all_optional_fields() {
         got_task.collector_auth_token_hash().unwrap(),
         &collector_auth_token_hash
     );
+    assert_eq!(got_task.hpke_keys().len(), 1);
+    for keypair in got_task.hpke_keys().values() {
+        assert_eq!(
+            Some(HpkeCiphersuite::from(keypair.config())),
+            req.hpke_ciphersuite
+        );
+    }
 
     // ...and the response.
     assert_eq!(got_task_resp, TaskResp::try_from(&got_task).unwrap());
@@ -602,6 +835,7 @@ async fn post_task_leader_no_aggregator_auth_token() {
         .clone(),
         aggregator_auth_token: None,
         collector_auth_token_hash: Some(AuthenticationTokenHash::from(&random())),
+        hpke_ciphersuite: None,
     };
 
     assert_response!(
@@ -616,6 +850,132 @@ async fn post_task_leader_no_aggregator_auth_token() {
     );
 }
 
+#[tokio::test]
+async fn post_tasks() {
+    // Setup: create a datastore & handler.
+    let (handler, _ephemeral_datastore, ds) = setup_api_test().await;
+
+    let post_task_req = |role| {
+        let vdaf_verify_key: Vec<u8> = thread_rng().sample_iter(Standard).take(16).collect();
+        PostTaskReq {
+            peer_aggregator_endpoint: "http://aggregator.endpoint".try_into().unwrap(),
+            query_type: QueryType::TimeInterval,
+            vdaf: VdafInstance::Prio3Count,
+            role,
+            vdaf_verify_key: URL_SAFE_NO_PAD.encode(vdaf_verify_key),
+            max_batch_query_count: 12,
+            task_expiration: Some(Time::from_seconds_since_epoch(12345)),
+            min_batch_size: 223,
+            time_precision: Duration::from_seconds(62),
+            collector_hpke_config: generate_test_hpke_config_and_private_key().config().clone(),
+            aggregator_auth_token: (role == Role::Leader)
+                .then(|| AuthenticationToken::DapAuth(random())),
+            collector_auth_token_hash: (role == Role::Leader)
+                .then(|| AuthenticationTokenHash::from(&random())),
+            hpke_ciphersuite: None,
+        }
+    };
+    let post_tasks = |req: &PostTasksReq| {
+        post("/tasks/bulk")
+            .with_request_body(serde_json::to_vec(req).unwrap())
+            .with_request_header("Authorization", format!("Bearer {AUTH_TOKEN}"))
+            .with_request_header("Accept", CONTENT_TYPE)
+            .with_request_header("Content-Type", CONTENT_TYPE)
+            .run_async(&handler)
+    };
+    let get_task_ids = || async {
+        ds.run_unnamed_tx(|tx| {
+            Box::pin(async move {
+                Ok(tx
+                    .get_aggregator_tasks()
+                    .await?
+                    .iter()
+                    .map(|task| *task.id())
+                    .collect::<HashSet<_>>())
+            })
+        })
+        .await
+        .unwrap()
+    };
+
+    // Verify: posting several tasks creates all of them, and responds with each in turn.
+    let req = PostTasksReq {
+        tasks: Vec::from([
+            post_task_req(Role::Leader),
+            post_task_req(Role::Helper),
+            post_task_req(Role::Leader),
+        ]),
+    };
+    let mut conn = post_tasks(&req).await;
+    assert_status!(conn, Status::Ok);
+    let resp: PostTasksResp = serde_json::from_slice(
+        &conn
+            .take_response_body()
+            .unwrap()
+            .into_bytes()
+            .await
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(resp.tasks.len(), req.tasks.len());
+    for (task_req, task_resp) in req.tasks.iter().zip(&resp.tasks) {
+        assert_eq!(task_req.role, task_resp.role);
+        assert_eq!(task_req.vdaf_verify_key, task_resp.vdaf_verify_key);
+    }
+    // The helper's generated aggregator auth token is returned only in this response.
+    assert_matches!(resp.tasks[1].aggregator_auth_token, Some(_));
+    let created_task_ids: HashSet<_> = resp.tasks.iter().map(|task| task.task_id).collect();
+    assert_eq!(get_task_ids().await, created_task_ids);
+
+    // Verify: reposting the same tasks is idempotent.
+    assert_status!(post_tasks(&req).await, Status::Ok);
+    assert_eq!(get_task_ids().await, created_task_ids);
+
+    // Verify: if any task conflicts with an existing task, none of the tasks are created.
+    let mut conflicting_task_req = post_task_req(Role::Leader);
+    conflicting_task_req.vdaf_verify_key = req.tasks[0].vdaf_verify_key.clone();
+    conflicting_task_req.max_batch_query_count = 10;
+    assert_status!(
+        post_tasks(&PostTasksReq {
+            tasks: Vec::from([post_task_req(Role::Helper), conflicting_task_req]),
+        })
+        .await,
+        Status::Conflict
+    );
+    assert_eq!(get_task_ids().await, created_task_ids);
+
+    // Verify: if any task is invalid, none of the tasks are created.
+    let mut invalid_task_req = post_task_req(Role::Helper);
+    invalid_task_req.aggregator_auth_token = Some(random());
+    assert_status!(
+        post_tasks(&PostTasksReq {
+            tasks: Vec::from([post_task_req(Role::Leader), invalid_task_req]),
+        })
+        .await,
+        Status::BadRequest
+    );
+    assert_eq!(get_task_ids().await, created_task_ids);
+
+    // Verify: the same task may not be requested twice.
+    let duplicate_task_req = post_task_req(Role::Leader);
+    let mut same_task_req = post_task_req(Role::Leader);
+    same_task_req.vdaf_verify_key = duplicate_task_req.vdaf_verify_key.clone();
+    assert_status!(
+        post_tasks(&PostTasksReq {
+            tasks: Vec::from([duplicate_task_req, same_task_req]),
+        })
+        .await,
+        Status::BadRequest
+    );
+    assert_eq!(get_task_ids().await, created_task_ids);
+
+    // Verify: at least one task must be requested.
+    assert_status!(
+        post_tasks(&PostTasksReq { tasks: Vec::new() }).await,
+        Status::BadRequest
+    );
+}
+
 #[rstest::rstest]
 #[case::leader(Role::Leader)]
 #[case::helper(Role::Helper)]
@@ -832,6 +1192,356 @@ async fn get_task_upload_metrics() {
     );
 }
 
+#[tokio::test]
+async fn get_task_metrics_snapshots() {
+    let (handler, _ephemeral_datastore, ds) = setup_api_test().await;
+    let task_id = ds
+        .run_unnamed_tx(|tx| {
+            Box::pin(async move {
+                let task = TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Fake)
+                    .build()
+                    .leader_view()
+                    .unwrap();
+                tx.put_aggregator_task(&task).await.unwrap();
+                tx.increment_task_upload_counter(
+                    task.id(),
+                    0,
+                    &TaskUploadCounter::new_with_values(0, 0, 0, 0, 0, 10, 0, 0),
+                )
+                .await
+                .unwrap();
+                tx.put_task_metrics_snapshots(&Time::from_seconds_since_epoch(3600))
+                    .await
+                    .unwrap();
+                tx.put_task_metrics_snapshots(&Time::from_seconds_since_epoch(7200))
+                    .await
+                    .unwrap();
+
+                Ok(*task.id())
+            })
+        })
+        .await
+        .unwrap();
+
+    // Verify: all snapshots are returned by default.
+    assert_response!(
+        get(&format!("/tasks/{}/metrics/snapshots", &task_id))
+            .with_request_header("Authorization", format!("Bearer {AUTH_TOKEN}"))
+            .with_request_header("Accept", CONTENT_TYPE)
+            .run_async(&handler)
+            .await,
+        Status::Ok,
+        serde_json::to_string(&GetTaskMetricsSnapshotsResp(Vec::from([
+            TaskMetricsSnapshot::new(task_id, Time::from_seconds_since_epoch(3600), 10, 0, 0),
+            TaskMetricsSnapshot::new(task_id, Time::from_seconds_since_epoch(7200), 10, 0, 0),
+        ])))
+        .unwrap(),
+    );
+
+    // Verify: snapshots taken before `since` are omitted.
+    assert_response!(
+        get(&format!("/tasks/{}/metrics/snapshots?since=3601", &task_id))
+            .with_request_header("Authorization", format!("Bearer {AUTH_TOKEN}"))
+            .with_request_header("Accept", CONTENT_TYPE)
+            .run_async(&handler)
+            .await,
+        Status::Ok,
+        serde_json::to_string(&GetTaskMetricsSnapshotsResp(Vec::from([
+            TaskMetricsSnapshot::new(task_id, Time::from_seconds_since_epoch(7200), 10, 0, 0),
+        ])))
+        .unwrap(),
+    );
+
+    // Verify: an invalid `since` is rejected.
+    assert_status!(
+        get(&format!(
+            "/tasks/{}/metrics/snapshots?since=yesterday",
+            &task_id
+        ))
+        .with_request_header("Authorization", format!("Bearer {AUTH_TOKEN}"))
+        .with_request_header("Accept", CONTENT_TYPE)
+        .run_async(&handler)
+        .await,
+        Status::BadRequest
+    );
+
+    // Verify: unauthorized requests are denied appropriately.
+    assert_response!(
+        get(&format!("/tasks/{}/metrics/snapshots", &task_id))
+            .with_request_header("Accept", CONTENT_TYPE)
+            .run_async(&handler)
+            .await,
+        Status::Unauthorized,
+        "",
+    );
+}
+
+#[tokio::test]
+async fn get_task_peer_health() {
+    let (handler, _ephemeral_datastore, ds) = setup_api_test().await;
+    let (task_id, unprobed_task_id) = ds
+        .run_unnamed_tx(|tx| {
+            Box::pin(async move {
+                let task = TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Fake)
+                    .build()
+                    .leader_view()
+                    .unwrap();
+                let unprobed_task = TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Fake)
+                    .build()
+                    .leader_view()
+                    .unwrap();
+                tx.put_aggregator_task(&task).await.unwrap();
+                tx.put_aggregator_task(&unprobed_task).await.unwrap();
+                tx.put_task_peer_probe_result(
+                    task.id(),
+                    &Time::from_seconds_since_epoch(3600),
+                    Ok(40),
+                )
+                .await
+                .unwrap();
+                tx.put_task_peer_probe_result(
+                    task.id(),
+                    &Time::from_seconds_since_epoch(3900),
+                    Err("connection refused"),
+                )
+                .await
+                .unwrap();
+
+                Ok((*task.id(), *unprobed_task.id()))
+            })
+        })
+        .await
+        .unwrap();
+
+    // Verify: the result of the latest probe is returned.
+    assert_response!(
+        get(&format!("/tasks/{}/peer_health", &task_id))
+            .with_request_header("Authorization", format!("Bearer {AUTH_TOKEN}"))
+            .with_request_header("Accept", CONTENT_TYPE)
+            .run_async(&handler)
+            .await,
+        Status::Ok,
+        serde_json::to_string(&GetTaskPeerHealthResp {
+            last_probed_at: Time::from_seconds_since_epoch(3900),
+            reachable: false,
+            latency_ms: None,
+            error: Some("connection refused".to_string()),
+            last_reachable_at: Some(Time::from_seconds_since_epoch(3600)),
+            consecutive_failures: 1,
+        })
+        .unwrap(),
+    );
+
+    // Verify: tasks whose peer has not been probed are not found.
+    assert_status!(
+        get(&format!("/tasks/{}/peer_health", &unprobed_task_id))
+            .with_request_header("Authorization", format!("Bearer {AUTH_TOKEN}"))
+            .with_request_header("Accept", CONTENT_TYPE)
+            .run_async(&handler)
+            .await,
+        Status::NotFound
+    );
+
+    // Verify: unauthorized requests are denied appropriately.
+    assert_response!(
+        get(&format!("/tasks/{}/peer_health", &task_id))
+            .with_request_header("Accept", CONTENT_TYPE)
+            .run_async(&handler)
+            .await,
+        Status::Unauthorized,
+        "",
+    );
+}
+
+#[tokio::test]
+async fn get_task_aggregation_job_creator_state() {
+    let (handler, _ephemeral_datastore, ds) = setup_api_test().await;
+    let (task_id, undiscovered_task_id) = ds
+        .run_unnamed_tx(|tx| {
+            Box::pin(async move {
+                let task = TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Fake)
+                    .build()
+                    .leader_view()
+                    .unwrap();
+                let undiscovered_task =
+                    TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Fake)
+                        .build()
+                        .leader_view()
+                        .unwrap();
+                tx.put_aggregator_task(&task).await.unwrap();
+                tx.put_aggregator_task(&undiscovered_task).await.unwrap();
+                tx.put_aggregation_job_creator_task_state(&AggregationJobCreatorTaskState::new(
+                    *task.id(),
+                    Time::from_seconds_since_epoch(3600),
+                    Some(Time::from_seconds_since_epoch(3660)),
+                    Time::from_seconds_since_epoch(3720),
+                    Some("database unavailable".to_string()),
+                ))
+                .await
+                .unwrap();
+
+                Ok((*task.id(), *undiscovered_task.id()))
+            })
+        })
+        .await
+        .unwrap();
+
+    // Verify: the task's scheduling state is returned.
+    assert_response!(
+        get(&format!(
+            "/tasks/{}/aggregation_job_creator_state",
+            &task_id
+        ))
+        .with_request_header("Authorization", format!("Bearer {AUTH_TOKEN}"))
+        .with_request_header("Accept", CONTENT_TYPE)
+        .run_async(&handler)
+        .await,
+        Status::Ok,
+        serde_json::to_string(&GetTaskAggregationJobCreatorStateResp {
+            discovered_at: Time::from_seconds_since_epoch(3600),
+            last_run_at: Some(Time::from_seconds_since_epoch(3660)),
+            next_run_at: Time::from_seconds_since_epoch(3720),
+            last_error: Some("database unavailable".to_string()),
+        })
+        .unwrap(),
+    );
+
+    // Verify: tasks that the aggregation job creator hasn't discovered are not found.
+    assert_status!(
+        get(&format!(
+            "/tasks/{}/aggregation_job_creator_state",
+            &undiscovered_task_id
+        ))
+        .with_request_header("Authorization", format!("Bearer {AUTH_TOKEN}"))
+        .with_request_header("Accept", CONTENT_TYPE)
+        .run_async(&handler)
+        .await,
+        Status::NotFound
+    );
+
+    // Verify: unauthorized requests are denied appropriately.
+    assert_response!(
+        get(&format!(
+            "/tasks/{}/aggregation_job_creator_state",
+            &task_id
+        ))
+        .with_request_header("Accept", CONTENT_TYPE)
+        .run_async(&handler)
+        .await,
+        Status::Unauthorized,
+        "",
+    );
+}
+
+#[tokio::test]
+async fn get_collection_job_progress() {
+    let (handler, _ephemeral_datastore, ds) = setup_api_test().await;
+    let (task_id, collection_job_id) = ds
+        .run_unnamed_tx(|tx| {
+            Box::pin(async move {
+                let task = TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Fake)
+                    .build()
+                    .leader_view()
+                    .unwrap();
+                tx.put_aggregator_task(&task).await.unwrap();
+
+                let batch_interval =
+                    Interval::new(Time::from_seconds_since_epoch(1000), *task.time_precision())
+                        .unwrap();
+                let collection_job = CollectionJob::<0, TimeInterval, dummy::Vdaf>::new(
+                    *task.id(),
+                    random(),
+                    Query::new_time_interval(batch_interval),
+                    dummy::AggregationParam(0),
+                    batch_interval,
+                    CollectionJobState::Start,
+                );
+                tx.put_collection_job(&collection_job).await.unwrap();
+                tx.put_batch_aggregation(&BatchAggregation::<0, TimeInterval, dummy::Vdaf>::new(
+                    *task.id(),
+                    batch_interval,
+                    dummy::AggregationParam(0),
+                    0,
+                    Interval::EMPTY,
+                    BatchAggregationState::Aggregating {
+                        aggregate_share: None,
+                        report_count: 0,
+                        checksum: ReportIdChecksum::default(),
+                        aggregation_jobs_created: 2,
+                        aggregation_jobs_terminated: 0,
+                    },
+                ))
+                .await
+                .unwrap();
+
+                Ok((*task.id(), *collection_job.id()))
+            })
+        })
+        .await
+        .unwrap();
+
+    // Verify: the progress of the collection job is returned. No aggregation jobs have
+    // terminated recently, so no completion estimate is made.
+    assert_response!(
+        get(&format!(
+            "/tasks/{}/collection_jobs/{}/progress",
+            &task_id, &collection_job_id
+        ))
+        .with_request_header("Authorization", format!("Bearer {AUTH_TOKEN}"))
+        .with_request_header("Accept", CONTENT_TYPE)
+        .run_async(&handler)
+        .await,
+        Status::Ok,
+        serde_json::to_string(&GetCollectionJobProgressResp {
+            state: CollectionJobStateCode::Start,
+            outstanding_aggregation_jobs: 2,
+            recently_terminated_aggregation_jobs: 0,
+            estimated_completion: None,
+        })
+        .unwrap(),
+    );
+
+    // Verify: unknown collection jobs are not found.
+    assert_status!(
+        get(&format!(
+            "/tasks/{}/collection_jobs/{}/progress",
+            &task_id,
+            random::<CollectionJobId>()
+        ))
+        .with_request_header("Authorization", format!("Bearer {AUTH_TOKEN}"))
+        .with_request_header("Accept", CONTENT_TYPE)
+        .run_async(&handler)
+        .await,
+        Status::NotFound
+    );
+
+    // Verify: malformed collection job IDs are rejected.
+    assert_status!(
+        get(&format!(
+            "/tasks/{}/collection_jobs/not-a-collection-job-id/progress",
+            &task_id
+        ))
+        .with_request_header("Authorization", format!("Bearer {AUTH_TOKEN}"))
+        .with_request_header("Accept", CONTENT_TYPE)
+        .run_async(&handler)
+        .await,
+        Status::BadRequest
+    );
+
+    // Verify: unauthorized requests are denied appropriately.
+    assert_response!(
+        get(&format!(
+            "/tasks/{}/collection_jobs/{}/progress",
+            &task_id, &collection_job_id
+        ))
+        .with_request_header("Accept", CONTENT_TYPE)
+        .run_async(&handler)
+        .await,
+        Status::Unauthorized,
+        "",
+    );
+}
+
 #[tokio::test]
 async fn get_global_hpke_configs() {
     let (handler, _ephemeral_datastore, ds) = setup_api_test().await;
@@ -1542,6 +2252,7 @@ fn post_task_req_serialization() {
             ),
             aggregator_auth_token: None,
             collector_auth_token_hash: None,
+            hpke_ciphersuite: None,
         },
         &[
             Token::Struct {
@@ -1659,6 +2370,7 @@ fn post_task_req_serialization() {
             collector_auth_token_hash: Some(AuthenticationTokenHash::from(
                 &AuthenticationToken::new_dap_auth_token_from_string("ZW5jb2RlZA").unwrap(),
             )),
+            hpke_ciphersuite: None,
         },
         &[
             Token::Struct {
@@ -1773,7 +2485,7 @@ fn post_task_req_serialization() {
 
 #[test]
 fn task_resp_serialization() {
-    let task = AggregatorTask::new(
+    let task = AggregatorTask::builder(
         TaskId::from([0u8; 32]),
         "https://helper.com/".parse().unwrap(),
         QueryType::FixedSize {
@@ -1786,22 +2498,6 @@ fn task_resp_serialization() {
             chunk_length: 2,
         },
         SecretBytes::new(b"vdaf verify key!".to_vec()),
-        1,
-        None,
-        None,
-        100,
-        Duration::from_seconds(3600),
-        Duration::from_seconds(60),
-        [(HpkeKeypair::new(
-            HpkeConfig::new(
-                HpkeConfigId::from(13),
-                HpkeKemId::X25519HkdfSha256,
-                HpkeKdfId::HkdfSha256,
-                HpkeAeadId::Aes128Gcm,
-                HpkePublicKey::from([0u8; 32].to_vec()),
-            ),
-            HpkePrivateKey::new(b"unused".to_vec()),
-        ))],
         AggregatorTaskParameters::Leader {
             aggregator_auth_token: AuthenticationToken::new_dap_auth_token_from_string(
                 "Y29sbGVjdG9yLWFiY2RlZjAw",
@@ -1820,6 +2516,21 @@ fn task_resp_serialization() {
             ),
         },
     )
+    .with_max_batch_query_count(1)
+    .with_min_batch_size(100)
+    .with_time_precision(Duration::from_seconds(3600))
+    .with_tolerable_clock_skew(Duration::from_seconds(60))
+    .with_hpke_keys([(HpkeKeypair::new(
+        HpkeConfig::new(
+            HpkeConfigId::from(13),
+            HpkeKemId::X25519HkdfSha256,
+            HpkeKdfId::HkdfSha256,
+            HpkeAeadId::Aes128Gcm,
+            HpkePublicKey::from([0u8; 32].to_vec()),
+        ),
+        HpkePrivateKey::new(b"unused".to_vec()),
+    ))])
+    .build()
     .unwrap();
     assert_tokens(
         &TaskResp::try_from(&task).unwrap(),
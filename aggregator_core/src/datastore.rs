@@ -1,23 +1,26 @@
 //! Janus datastore (durable storage) implementation.
 
 use self::models::{
-    AcquiredAggregationJob, AcquiredCollectionJob, AggregateShareJob, AggregationJob,
+    AcquiredAggregationJob, AcquiredCollectionJob, ActiveDeployment, AdminApiKey,
+    AggregateShareJob, AggregationJob, AggregationJobCreatorTaskState, AggregationJobState,
     AggregatorRole, AuthenticationTokenType, BatchAggregation, BatchAggregationState,
-    BatchAggregationStateCode, CollectionJob, CollectionJobState, CollectionJobStateCode,
-    GlobalHpkeKeypair, HpkeKeyState, LeaderStoredReport, Lease, LeaseToken, OutstandingBatch,
-    ReportAggregation, ReportAggregationMetadata, ReportAggregationMetadataState,
-    ReportAggregationState, ReportAggregationStateCode, SqlInterval, TaskUploadCounter,
+    BatchAggregationStateCode, ClientReportWriteOutcome, CollectionJob, CollectionJobProgress,
+    CollectionJobState, CollectionJobStateCode, EncodedClientReport, FeatureFlag,
+    GlobalHpkeKeypair, HpkeKeyState, LeaderLease, LeaderStoredReport, Lease, LeaseToken,
+    OutstandingBatch, ReportAggregation, ReportAggregationMetadata, ReportAggregationMetadataState,
+    ReportAggregationState, ReportAggregationStateCode, SqlInterval, TaskHpkeConfig,
+    TaskLifecycleEvent, TaskMetricsSnapshot, TaskPeerHealth, TaskUploadCounter, UploadSample,
 };
 use crate::{
     query_type::{AccumulableQueryType, CollectableQueryType},
-    task::{self, AggregatorTask, AggregatorTaskParameters},
+    task::{self, AggregationJobSizing, AggregatorTask, AggregatorTaskParameters, TaskState},
     taskprov::PeerAggregator,
     SecretBytes,
 };
 use chrono::NaiveDateTime;
 use futures::future::try_join_all;
 use janus_core::{
-    auth_tokens::AuthenticationToken,
+    auth_tokens::{AuthenticationToken, AuthenticationTokenHash},
     hpke::{HpkeKeypair, HpkePrivateKey},
     time::{Clock, TimeExt},
     vdaf::VdafInstance,
@@ -41,7 +44,7 @@ use prio::{
 use rand::random;
 use ring::aead::{self, LessSafeKey, AES_128_GCM};
 use std::{
-    collections::HashMap,
+    collections::{hash_map::Entry, HashMap, HashSet},
     convert::TryFrom,
     fmt::{Debug, Display},
     future::Future,
@@ -51,13 +54,13 @@ use std::{
     pin::Pin,
     sync::{
         atomic::{AtomicBool, Ordering},
-        Arc, Mutex,
+        Arc, Mutex, RwLock,
     },
     time::{Duration as StdDuration, Instant},
 };
 use tokio::{sync::Barrier, try_join};
 use tokio_postgres::{error::SqlState, row::RowIndex, IsolationLevel, Row, Statement, ToStatement};
-use tracing::{error, Level};
+use tracing::{error, warn, Level};
 use url::Url;
 
 pub mod models;
@@ -74,7 +77,9 @@ mod tests;
 /// [1]: https://docs.rs/rstest_reuse/latest/rstest_reuse/
 macro_rules! supported_schema_versions {
     ( $i_latest:literal $(,)? $( $i:literal ),* ) => {
-        const SUPPORTED_SCHEMA_VERSIONS: &[i64] = &[$i_latest, $($i),*];
+        /// Schema versions supported by this version of Janus. The latest supported version comes
+        /// first.
+        pub const SUPPORTED_SCHEMA_VERSIONS: &[i64] = &[$i_latest, $($i),*];
 
         #[cfg(test)]
         #[rstest_reuse::template]
@@ -100,12 +105,13 @@ macro_rules! supported_schema_versions {
 // version is seen, [`Datastore::new`] fails.
 //
 // Note that the latest supported version must be first in the list.
-supported_schema_versions!(1);
+supported_schema_versions!(17);
 
 /// Datastore represents a datastore for Janus, with support for transactional reads and writes.
 /// In practice, Datastore instances are currently backed by a PostgreSQL database.
 pub struct Datastore<C: Clock> {
     pool: deadpool_postgres::Pool,
+    read_replica_pool: Option<deadpool_postgres::Pool>,
     crypter: Crypter,
     clock: C,
     transaction_status_counter: Counter<u64>,
@@ -208,6 +214,7 @@ impl<C: Clock> Datastore<C> {
 
         Self {
             pool,
+            read_replica_pool: None,
             crypter,
             clock,
             transaction_status_counter,
@@ -218,6 +225,20 @@ impl<C: Clock> Datastore<C> {
         }
     }
 
+    /// Directs read-only transactions, run via [`Datastore::run_read_only_tx`], to a read replica
+    /// of the database, reached through the provided connection pool.
+    pub fn with_read_replica_pool(self, read_replica_pool: deadpool_postgres::Pool) -> Self {
+        Self {
+            read_replica_pool: Some(read_replica_pool),
+            ..self
+        }
+    }
+
+    /// Returns the clock used by this datastore's transactions.
+    pub fn clock(&self) -> &C {
+        &self.clock
+    }
+
     /// run_tx runs a transaction, whose body is determined by the given function. The transaction
     /// is committed if the body returns a successful value, and rolled back if the body returns an
     /// error value.
@@ -230,6 +251,32 @@ impl<C: Clock> Datastore<C> {
     /// This method requires a transaction name for use in database metrics.
     #[tracing::instrument(level = "trace", skip(self, f))]
     pub async fn run_tx<F, T>(&self, name: &'static str, f: F) -> Result<T, Error>
+    where
+        for<'a> F:
+            Fn(&'a Transaction<C>) -> Pin<Box<dyn Future<Output = Result<T, Error>> + Send + 'a>>,
+    {
+        self.run_tx_with_access(name, f, false).await
+    }
+
+    /// Runs a read-only transaction, as in [`Datastore::run_tx`]. If a read replica is configured,
+    /// the transaction runs against it, falling back to the primary database if no connection to
+    /// the replica can be obtained. Since the replica may lag behind the primary, this should only
+    /// be used for reads which tolerate slightly stale data. Any write in the transaction fails.
+    #[tracing::instrument(level = "trace", skip(self, f))]
+    pub async fn run_read_only_tx<F, T>(&self, name: &'static str, f: F) -> Result<T, Error>
+    where
+        for<'a> F:
+            Fn(&'a Transaction<C>) -> Pin<Box<dyn Future<Output = Result<T, Error>> + Send + 'a>>,
+    {
+        self.run_tx_with_access(name, f, true).await
+    }
+
+    async fn run_tx_with_access<F, T>(
+        &self,
+        name: &'static str,
+        f: F,
+        read_only: bool,
+    ) -> Result<T, Error>
     where
         for<'a> F:
             Fn(&'a Transaction<C>) -> Pin<Box<dyn Future<Output = Result<T, Error>> + Send + 'a>>,
@@ -237,7 +284,7 @@ impl<C: Clock> Datastore<C> {
         let mut retry_count = 0;
         loop {
             let before = Instant::now();
-            let (mut rslt, retry) = self.run_tx_once(name, &f).await;
+            let (mut rslt, retry) = self.run_tx_once(name, &f, read_only).await;
             let elapsed = before.elapsed();
             self.transaction_duration_histogram
                 .record(elapsed.as_secs_f64(), &[KeyValue::new("tx", name)]);
@@ -283,19 +330,25 @@ impl<C: Clock> Datastore<C> {
     }
 
     #[tracing::instrument(level = "trace", skip(self, f))]
-    async fn run_tx_once<F, T>(&self, name: &'static str, f: &F) -> (Result<T, Error>, bool)
+    async fn run_tx_once<F, T>(
+        &self,
+        name: &'static str,
+        f: &F,
+        read_only: bool,
+    ) -> (Result<T, Error>, bool)
     where
         for<'a> F:
             Fn(&'a Transaction<C>) -> Pin<Box<dyn Future<Output = Result<T, Error>> + Send + 'a>>,
     {
         // Open transaction.
-        let mut client = match self.pool.get().await {
+        let mut client = match self.get_client(name, read_only).await {
             Ok(client) => client,
             Err(err) => return (Err(err.into()), false),
         };
         let raw_tx = match client
             .build_transaction()
             .isolation_level(IsolationLevel::RepeatableRead)
+            .read_only(read_only)
             .start()
             .await
         {
@@ -346,6 +399,26 @@ impl<C: Clock> Datastore<C> {
         (rslt, retry.load(Ordering::Relaxed))
     }
 
+    /// Gets a database connection for a transaction. Read-only transactions use the read replica,
+    /// if one is configured and reachable, and the primary database otherwise.
+    async fn get_client(
+        &self,
+        name: &'static str,
+        read_only: bool,
+    ) -> Result<deadpool_postgres::Object, deadpool_postgres::PoolError> {
+        if let (true, Some(read_replica_pool)) = (read_only, &self.read_replica_pool) {
+            match read_replica_pool.get().await {
+                Ok(client) => return Ok(client),
+                Err(error) => warn!(
+                    tx = name,
+                    ?error,
+                    "Couldn't connect to read replica, falling back to primary database"
+                ),
+            }
+        }
+        self.pool.get().await
+    }
+
     /// See [`Datastore::run_tx`]. This method provides a placeholder transaction name. It is useful
     /// for tests where the transaction name is not important.
     #[cfg(feature = "test-util")]
@@ -571,7 +644,7 @@ impl<C: Clock> Transaction<'_, C> {
 
     /// Returns the current schema version of the datastore and the description of the migration
     /// script that applied it.
-    async fn get_current_schema_migration_version(&self) -> Result<(i64, String), Error> {
+    pub async fn get_current_schema_migration_version(&self) -> Result<(i64, String), Error> {
         let stmt = self
             .prepare_cached(
                 "SELECT version, description FROM _sqlx_migrations
@@ -602,10 +675,13 @@ impl<C: Clock> Transaction<'_, C> {
                     max_batch_query_count, task_expiration, report_expiry_age, min_batch_size,
                     time_precision, tolerable_clock_skew, collector_hpke_config, vdaf_verify_key,
                     aggregator_auth_token_type, aggregator_auth_token, aggregator_auth_token_hash,
-                    collector_auth_token_type, collector_auth_token_hash, created_at, updated_by)
+                    collector_auth_token_type, collector_auth_token_hash, helper_request_headers,
+                    unknown_extension_policy, upload_auth_token_type, upload_auth_token_hash,
+                    min_aggregation_job_size, max_aggregation_job_size,
+                    aggregation_job_creation_interval, state, created_at, updated_by)
                 VALUES (
                     $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18,
-                    $19, $20
+                    $19, $20, $21, $22, $23, $24, $25, $26, $27, $28
                 )
                 ON CONFLICT DO NOTHING",
             )
@@ -682,6 +758,46 @@ impl<C: Clock> Transaction<'_, C> {
                     &task
                         .collector_auth_token_hash()
                         .map(|token_hash| token_hash.as_ref()),
+                    /* helper_request_headers */
+                    &(!task.helper_request_headers().is_empty())
+                        .then(|| {
+                            self.crypter.encrypt(
+                                "tasks",
+                                task.id().as_ref(),
+                                "helper_request_headers",
+                                &serde_json::to_vec(task.helper_request_headers())?,
+                            )
+                        })
+                        .transpose()?,
+                    /* unknown_extension_policy */ task.unknown_extension_policy(),
+                    /* upload_auth_token_type */
+                    &task
+                        .upload_auth_token_hash()
+                        .map(AuthenticationTokenType::from),
+                    /* upload_auth_token_hash */
+                    &task
+                        .upload_auth_token_hash()
+                        .map(|token_hash| token_hash.as_ref()),
+                    /* min_aggregation_job_size */
+                    &task
+                        .aggregation_job_sizing()
+                        .min_aggregation_job_size()
+                        .map(i64::try_from)
+                        .transpose()?,
+                    /* max_aggregation_job_size */
+                    &task
+                        .aggregation_job_sizing()
+                        .max_aggregation_job_size()
+                        .map(i64::try_from)
+                        .transpose()?,
+                    /* aggregation_job_creation_interval */
+                    &task
+                        .aggregation_job_sizing()
+                        .aggregation_job_creation_interval()
+                        .map(Duration::as_seconds)
+                        .map(i64::try_from)
+                        .transpose()?,
+                    /* state */ task.state(),
                     /* created_at */ &self.clock.now().as_naive_date_time()?,
                     /* updated_by */ &self.name,
                 ],
@@ -731,22 +847,124 @@ impl<C: Clock> Transaction<'_, C> {
         ];
         self.execute(&stmt, hpke_configs_params).await?;
 
-        Ok(())
+        self.put_task_lifecycle_event(task.id(), None, task.state())
+            .await
     }
 
     /// Deletes a task from the datastore, along with all related data (client reports,
-    /// aggregations, etc).
+    /// aggregations, etc), and records its transition to [`TaskState::Deleted`].
     #[tracing::instrument(skip(self))]
     pub async fn delete_task(&self, task_id: &TaskId) -> Result<(), Error> {
         // Deletion of other data implemented via ON DELETE CASCADE.
         let stmt = self
-            .prepare_cached("DELETE FROM tasks WHERE task_id = $1")
+            .prepare_cached("DELETE FROM tasks WHERE task_id = $1 RETURNING state")
+            .await?;
+        let state: TaskState = self
+            .query_opt(&stmt, &[/* task_id */ &task_id.as_ref()])
+            .await?
+            .ok_or(Error::MutationTargetNotFound)?
+            .get("state");
+
+        self.put_task_lifecycle_event(task_id, Some(&state), &TaskState::Deleted)
+            .await
+    }
+
+    /// Moves a task from the state `from` to the state `to`, and records the transition. Returns
+    /// [`Error::MutationTargetNotFound`] if the task does not exist or is no longer in state
+    /// `from`, e.g. because another process moved it concurrently. Tasks are moved to
+    /// [`TaskState::Deleted`] by [`Self::delete_task`].
+    #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
+    pub async fn update_task_state(
+        &self,
+        task_id: &TaskId,
+        from: &TaskState,
+        to: &TaskState,
+    ) -> Result<(), Error> {
+        if !from.can_transition_to(to) || to == &TaskState::Deleted {
+            return Err(Error::InvalidParameter("invalid task state transition"));
+        }
+
+        let stmt = self
+            .prepare_cached(
+                "UPDATE tasks SET state = $1, updated_by = $2
+                WHERE task_id = $3 AND state = $4",
+            )
             .await?;
         check_single_row_mutation(
-            self.execute(&stmt, &[/* task_id */ &task_id.as_ref()])
-                .await?,
+            self.execute(
+                &stmt,
+                &[
+                    /* state */ to,
+                    /* updated_by */ &self.name,
+                    /* task_id */ &task_id.as_ref(),
+                    /* from */ from,
+                ],
+            )
+            .await?,
         )?;
-        Ok(())
+
+        self.put_task_lifecycle_event(task_id, Some(from), to).await
+    }
+
+    /// Records a transition of a task from one lifecycle state to another.
+    async fn put_task_lifecycle_event(
+        &self,
+        task_id: &TaskId,
+        from: Option<&TaskState>,
+        to: &TaskState,
+    ) -> Result<(), Error> {
+        let stmt = self
+            .prepare_cached(
+                "INSERT INTO task_lifecycle_events
+                    (task_id, from_state, to_state, occurred_at, updated_by)
+                VALUES ($1, $2, $3, $4, $5)",
+            )
+            .await?;
+        check_insert(
+            self.execute(
+                &stmt,
+                &[
+                    /* task_id */ &task_id.as_ref(),
+                    /* from_state */ &from,
+                    /* to_state */ to,
+                    /* occurred_at */ &self.clock.now().as_naive_date_time()?,
+                    /* updated_by */ &self.name,
+                ],
+            )
+            .await?,
+        )
+    }
+
+    /// Retrieves recorded task lifecycle transitions, in the order they happened, either for the
+    /// given task, or for all tasks if `task_id` is `None`. Transitions are retained after their
+    /// task is deleted.
+    #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
+    pub async fn get_task_lifecycle_events(
+        &self,
+        task_id: Option<&TaskId>,
+    ) -> Result<Vec<TaskLifecycleEvent>, Error> {
+        let stmt = self
+            .prepare_cached(
+                "SELECT task_id, from_state, to_state, occurred_at FROM task_lifecycle_events
+                WHERE $1::BYTEA IS NULL OR task_id = $1
+                ORDER BY id",
+            )
+            .await?;
+        self.query(
+            &stmt,
+            &[/* task_id */ &task_id.map(|task_id| task_id.as_ref())],
+        )
+        .await?
+        .into_iter()
+        .map(|row| {
+            Ok(TaskLifecycleEvent::new(
+                TaskId::get_decoded(row.get("task_id"))?,
+                row.get("from_state"),
+                row.get("to_state"),
+                Time::from_naive_date_time(&row.get::<_, NaiveDateTime>("occurred_at")),
+            ))
+        })
+        .collect()
     }
 
     /// Fetch the task parameters corresponing to the provided `task_id`.
@@ -762,7 +980,10 @@ impl<C: Clock> Transaction<'_, C> {
                     max_batch_query_count, task_expiration, report_expiry_age, min_batch_size,
                     time_precision, tolerable_clock_skew, collector_hpke_config, vdaf_verify_key,
                     aggregator_auth_token_type, aggregator_auth_token, aggregator_auth_token_hash,
-                    collector_auth_token_type, collector_auth_token_hash
+                    collector_auth_token_type, collector_auth_token_hash, helper_request_headers,
+                    unknown_extension_policy, upload_auth_token_type, upload_auth_token_hash,
+                    min_aggregation_job_size, max_aggregation_job_size,
+                    aggregation_job_creation_interval, state
                 FROM tasks WHERE task_id = $1",
             )
             .await?;
@@ -771,10 +992,14 @@ impl<C: Clock> Transaction<'_, C> {
         let stmt = self
             .prepare_cached(
                 "SELECT config_id, config, private_key FROM task_hpke_keys
-                WHERE task_id = (SELECT id FROM tasks WHERE task_id = $1)",
+                WHERE task_id = (SELECT id FROM tasks WHERE task_id = $1)
+                  AND (retire_at IS NULL OR retire_at > $2)",
             )
             .await?;
-        let hpke_key_rows = self.query(&stmt, params);
+        let now = self.clock.now().as_naive_date_time()?;
+        let hpke_key_params: &[&(dyn ToSql + Sync)] =
+            &[/* task_id */ &task_id.as_ref(), /* now */ &now];
+        let hpke_key_rows = self.query(&stmt, hpke_key_params);
 
         let (task_row, hpke_key_rows) = try_join!(task_row, hpke_key_rows,)?;
         task_row
@@ -791,7 +1016,10 @@ impl<C: Clock> Transaction<'_, C> {
                     max_batch_query_count, task_expiration, report_expiry_age, min_batch_size,
                     time_precision, tolerable_clock_skew, collector_hpke_config, vdaf_verify_key,
                     aggregator_auth_token_type, aggregator_auth_token, aggregator_auth_token_hash,
-                    collector_auth_token_type, collector_auth_token_hash
+                    collector_auth_token_type, collector_auth_token_hash, helper_request_headers,
+                    unknown_extension_policy, upload_auth_token_type, upload_auth_token_hash,
+                    min_aggregation_job_size, max_aggregation_job_size,
+                    aggregation_job_creation_interval, state
                 FROM tasks",
             )
             .await?;
@@ -800,10 +1028,13 @@ impl<C: Clock> Transaction<'_, C> {
         let stmt = self
             .prepare_cached(
                 "SELECT (SELECT tasks.task_id FROM tasks WHERE tasks.id = task_hpke_keys.task_id),
-                config_id, config, private_key FROM task_hpke_keys",
+                config_id, config, private_key FROM task_hpke_keys
+                WHERE retire_at IS NULL OR retire_at > $1",
             )
             .await?;
-        let hpke_config_rows = self.query(&stmt, &[]);
+        let now = self.clock.now().as_naive_date_time()?;
+        let hpke_config_params: &[&(dyn ToSql + Sync)] = &[/* now */ &now];
+        let hpke_config_rows = self.query(&stmt, hpke_config_params);
 
         let (task_rows, hpke_config_rows) = try_join!(task_rows, hpke_config_rows,)?;
 
@@ -904,6 +1135,12 @@ impl<C: Clock> Transaction<'_, C> {
             .map(|(token_hash, token_type)| token_type.as_authentication_token_hash(&token_hash))
             .transpose()?;
 
+        let upload_auth_token_hash = row
+            .get::<_, Option<Vec<u8>>>("upload_auth_token_hash")
+            .zip(row.get::<_, Option<AuthenticationTokenType>>("upload_auth_token_type"))
+            .map(|(token_hash, token_type)| token_type.as_authentication_token_hash(&token_hash))
+            .transpose()?;
+
         // HPKE keys.
         let mut hpke_keys = Vec::new();
         for row in hpke_key_rows {
@@ -963,21 +1200,45 @@ impl<C: Clock> Transaction<'_, C> {
             }
         };
 
-        Ok(AggregatorTask::new(
+        let helper_request_headers = row
+            .get::<_, Option<Vec<u8>>>("helper_request_headers")
+            .map(|encrypted_headers| {
+                Ok::<_, Error>(serde_json::from_slice(&self.crypter.decrypt(
+                    "tasks",
+                    task_id.as_ref(),
+                    "helper_request_headers",
+                    &encrypted_headers,
+                )?)?)
+            })
+            .transpose()?
+            .unwrap_or_default();
+
+        Ok(AggregatorTask::builder(
             *task_id,
             peer_aggregator_endpoint,
             query_type,
             vdaf,
             vdaf_verify_key,
-            max_batch_query_count,
-            task_expiration,
-            report_expiry_age,
-            min_batch_size,
-            time_precision,
-            tolerable_clock_skew,
-            hpke_keys,
             aggregator_parameters,
-        )?)
+        )
+        .with_max_batch_query_count(max_batch_query_count)
+        .with_task_expiration(task_expiration)
+        .with_report_expiry_age(report_expiry_age)
+        .with_min_batch_size(min_batch_size)
+        .with_time_precision(time_precision)
+        .with_tolerable_clock_skew(tolerable_clock_skew)
+        .with_hpke_keys(hpke_keys)
+        .with_helper_request_headers(helper_request_headers)
+        .with_unknown_extension_policy(row.get("unknown_extension_policy"))
+        .with_upload_auth_token_hash(upload_auth_token_hash)
+        .with_aggregation_job_sizing(AggregationJobSizing::new(
+            row.get_nullable_bigint_and_convert("min_aggregation_job_size")?,
+            row.get_nullable_bigint_and_convert("max_aggregation_job_size")?,
+            row.get_nullable_bigint_and_convert("aggregation_job_creation_interval")?
+                .map(Duration::from_seconds),
+        ))
+        .with_state(row.get("state"))
+        .build_stored()?)
     }
 
     /// Retrieves task IDs, optionally after some specified lower bound. This method returns tasks
@@ -1002,6 +1263,26 @@ impl<C: Clock> Transaction<'_, C> {
             .collect()
     }
 
+    /// Retrieves the IDs of all tasks in which this aggregator has the given role. This is much
+    /// cheaper than retrieving the tasks themselves, so it may be used to frequently check for
+    /// newly provisioned or deleted tasks.
+    #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
+    pub async fn get_task_ids_by_role(&self, role: &Role) -> Result<HashSet<TaskId>, Error> {
+        let stmt = self
+            .prepare_cached("SELECT task_id FROM tasks WHERE aggregator_role = $1")
+            .await?;
+        self.query(
+            &stmt,
+            &[
+                /* aggregator_role */ &AggregatorRole::from_role(*role)?,
+            ],
+        )
+        .await?
+        .into_iter()
+        .map(|row| Ok(TaskId::get_decoded(row.get("task_id"))?))
+        .collect()
+    }
+
     /// get_client_report retrieves a client report by ID.
     #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
     pub async fn get_client_report<const SEED_SIZE: usize, A>(
@@ -1200,7 +1481,7 @@ impl<C: Clock> Transaction<'_, C> {
         // client_timestamp DESC` clause by using a reverse index scan, without an intermediate
         // sort.
         let (id, threshold) = self
-            .get_task_primary_key_and_expiry_threshold(task_id)
+            .get_task_primary_key_and_expiry_threshold(task_id, &self.clock.now())
             .await?;
 
         let stmt = self
@@ -1243,6 +1524,76 @@ impl<C: Clock> Transaction<'_, C> {
             .collect::<Result<Vec<_>, Error>>()
     }
 
+    /// `get_unaggregated_client_report_ids_by_collect_for_task` returns some client reports for
+    /// the task identified by the given task ID which fall within the batch interval of a
+    /// not-yet-finished collection job, but which have not yet been included in an aggregation job
+    /// with that collection job's aggregation parameter. Each report is returned along with the
+    /// aggregation parameter it should be aggregated with; a report may be returned once for each
+    /// distinct aggregation parameter requested.
+    ///
+    /// This should be used with VDAFs that have a non-unit aggregation parameter, for which
+    /// reports are aggregated once per aggregation parameter chosen by the Collector. It applies
+    /// only to time-interval queries.
+    #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
+    pub async fn get_unaggregated_client_report_ids_by_collect_for_task<
+        const SEED_SIZE: usize,
+        A: vdaf::Aggregator<SEED_SIZE, 16>,
+    >(
+        &self,
+        task_id: &TaskId,
+        limit: usize,
+    ) -> Result<Vec<(ReportMetadata, A::AggregationParam)>, Error> {
+        let (id, threshold) = self
+            .get_task_primary_key_and_expiry_threshold(task_id, &self.clock.now())
+            .await?;
+
+        let stmt = self
+            .prepare_cached(
+                "SELECT DISTINCT client_reports.report_id, client_reports.client_timestamp,
+                    collection_jobs.aggregation_param
+                FROM collection_jobs
+                JOIN client_reports
+                    ON client_reports.task_id = collection_jobs.task_id
+                    AND client_reports.client_timestamp <@ collection_jobs.batch_interval
+                WHERE collection_jobs.task_id = $1
+                  AND collection_jobs.state = 'START'
+                  AND client_reports.client_timestamp >= $2
+                  AND NOT EXISTS(
+                      SELECT 1 FROM report_aggregations
+                      JOIN aggregation_jobs
+                          ON aggregation_jobs.id = report_aggregations.aggregation_job_id
+                      WHERE report_aggregations.task_id = client_reports.task_id
+                        AND report_aggregations.client_report_id = client_reports.report_id
+                        AND aggregation_jobs.aggregation_param = collection_jobs.aggregation_param
+                  )
+                ORDER BY client_reports.client_timestamp
+                LIMIT $3::BIGINT",
+            )
+            .await?;
+        let rows = self
+            .query(
+                &stmt,
+                &[
+                    /* task_id */ &id,
+                    /* threshold */ &threshold,
+                    /* limit */ &i64::try_from(limit)?,
+                ],
+            )
+            .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok((
+                    ReportMetadata::new(
+                        row.get_bytea_and_convert::<ReportId>("report_id")?,
+                        Time::from_naive_date_time(&row.get("client_timestamp")),
+                    ),
+                    A::AggregationParam::get_decoded(row.get("aggregation_param"))?,
+                ))
+            })
+            .collect::<Result<Vec<_>, Error>>()
+    }
+
     /// `mark_report_unaggregated` resets the aggregation-started flag on the given client report,
     /// so that it may once again be returned by `get_unaggregated_client_report_ids_for_task`. It
     /// should generally only be called on report IDs returned from
@@ -1345,6 +1696,51 @@ impl<C: Clock> Transaction<'_, C> {
         Ok(row.get("unaggregated_report_exists"))
     }
 
+    /// Determines whether the given task includes any client reports in the given interval which
+    /// have not yet been included in an aggregation job with the given aggregation parameter. This
+    /// is the equivalent of `interval_has_unaggregated_reports` for VDAFs that have a non-unit
+    /// aggregation parameter.
+    #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
+    pub async fn interval_has_unaggregated_reports_for_aggregation_param(
+        &self,
+        task_id: &TaskId,
+        batch_interval: &Interval,
+        aggregation_param: &[u8],
+    ) -> Result<bool, Error> {
+        let stmt = self
+            .prepare_cached(
+                "SELECT EXISTS(
+                    SELECT 1 FROM client_reports
+                    JOIN tasks ON tasks.id = client_reports.task_id
+                    WHERE tasks.task_id = $1
+                    AND client_reports.client_timestamp >= LOWER($2::TSRANGE)
+                    AND client_reports.client_timestamp < UPPER($2::TSRANGE)
+                    AND client_reports.client_timestamp >= COALESCE($4::TIMESTAMP - tasks.report_expiry_age * '1 second'::INTERVAL, '-infinity'::TIMESTAMP)
+                    AND NOT EXISTS(
+                        SELECT 1 FROM report_aggregations
+                        JOIN aggregation_jobs
+                            ON aggregation_jobs.id = report_aggregations.aggregation_job_id
+                        WHERE report_aggregations.task_id = client_reports.task_id
+                        AND report_aggregations.client_report_id = client_reports.report_id
+                        AND aggregation_jobs.aggregation_param = $3
+                    )
+                ) AS unaggregated_report_exists",
+            )
+            .await?;
+        let row = self
+            .query_one(
+                &stmt,
+                &[
+                    /* task_id */ task_id.as_ref(),
+                    /* batch_interval */ &SqlInterval::from(batch_interval),
+                    /* aggregation_param */ &aggregation_param,
+                    /* now */ &self.clock.now().as_naive_date_time()?,
+                ],
+            )
+            .await?;
+        Ok(row.get("unaggregated_report_exists"))
+    }
+
     /// Return the number of reports in the provided task whose timestamp falls within the provided
     /// interval, regardless of whether the reports have been aggregated or collected. Applies only
     /// to time-interval queries.
@@ -1417,15 +1813,19 @@ impl<C: Clock> Transaction<'_, C> {
     }
 
     /// `put_client_report` stores a client report, the associated plaintext leader input share and
-    /// the associated encrypted helper share. Returns `Ok(())` if the write succeeds, or if there
-    /// was already a row in the table matching `new_report`. Returns an error if something goes
-    /// wrong or if the report ID is already in use with different values.
+    /// the associated encrypted helper share. The write and the check for an existing report with
+    /// the same ID happen in a single query, so concurrent uploads of the same report to different
+    /// replicas cannot race. Returns whether the report was newly inserted, a duplicate of an
+    /// already-stored report, or conflicts with an already-stored report with the same ID.
+    ///
+    /// A stored report whose shares have already been scrubbed is considered a duplicate if its
+    /// timestamp matches, since its remaining contents can no longer be compared.
     #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
     pub async fn put_client_report<const SEED_SIZE: usize, A>(
         &self,
         vdaf: &A,
         new_report: &LeaderStoredReport<SEED_SIZE, A>,
-    ) -> Result<(), Error>
+    ) -> Result<ClientReportWriteOutcome, Error>
     where
         A: vdaf::Aggregator<SEED_SIZE, 16>,
         A::InputShare: PartialEq,
@@ -1437,25 +1837,49 @@ impl<C: Clock> Transaction<'_, C> {
         let mut encoded_extensions = Vec::new();
         encode_u16_items(&mut encoded_extensions, &(), new_report.leader_extensions())?;
 
+        // If the INSERT hits a conflict, the existing row is visible to the second half of the
+        // UNION, since it reads from the statement's snapshot. (A conflicting row committed by a
+        // concurrent transaction after our snapshot was taken causes a serialization failure
+        // instead, and the transaction is retried.)
         let stmt = self
             .prepare_cached(
-                "INSERT INTO client_reports (
-                    task_id,
-                    report_id,
-                    client_timestamp,
-                    extensions,
-                    public_share,
-                    leader_input_share,
-                    helper_encrypted_input_share,
-                    created_at,
-                    updated_at,
-                    updated_by
-                )
-                VALUES (
-                    (SELECT id FROM tasks WHERE task_id = $1), $2, $3, $4, $5, $6, $7, $8, $9, $10
+                "WITH inserted AS (
+                    INSERT INTO client_reports (
+                        task_id,
+                        report_id,
+                        client_timestamp,
+                        extensions,
+                        public_share,
+                        leader_input_share,
+                        helper_encrypted_input_share,
+                        created_at,
+                        updated_at,
+                        updated_by
+                    )
+                    VALUES (
+                        (SELECT id FROM tasks WHERE task_id = $1), $2, $3, $4, $5, $6, $7, $8, $9,
+                        $10
+                    )
+                    ON CONFLICT DO NOTHING
+                    RETURNING COALESCE(client_timestamp < COALESCE($3::TIMESTAMP - (SELECT report_expiry_age FROM tasks WHERE task_id = $1) * '1 second'::INTERVAL, '-infinity'::TIMESTAMP), FALSE) AS is_expired
                 )
-                ON CONFLICT DO NOTHING
-                RETURNING COALESCE(client_timestamp < COALESCE($3::TIMESTAMP - (SELECT report_expiry_age FROM tasks WHERE task_id = $1) * '1 second'::INTERVAL, '-infinity'::TIMESTAMP), FALSE) AS is_expired",
+                SELECT TRUE AS inserted, is_expired, TRUE AS identical FROM inserted
+                UNION ALL
+                SELECT
+                    FALSE AS inserted,
+                    FALSE AS is_expired,
+                    client_reports.client_timestamp = $3
+                        AND (client_reports.leader_input_share IS NULL
+                            OR (client_reports.extensions IS NOT DISTINCT FROM $4
+                                AND client_reports.public_share IS NOT DISTINCT FROM $5
+                                AND client_reports.leader_input_share = $6
+                                AND client_reports.helper_encrypted_input_share
+                                    IS NOT DISTINCT FROM $7)) AS identical
+                FROM client_reports
+                JOIN tasks ON tasks.id = client_reports.task_id
+                WHERE tasks.task_id = $1
+                  AND client_reports.report_id = $2
+                  AND NOT EXISTS (SELECT 1 FROM inserted)",
             )
             .await?;
         let rows = self
@@ -1478,7 +1902,8 @@ impl<C: Clock> Transaction<'_, C> {
             .await?;
 
         if rows.len() > 1 {
-            // This should never happen, because the INSERT should affect 0 or 1 rows.
+            // This should never happen, because the INSERT should affect 0 or 1 rows, and the
+            // existing row is only selected if nothing was inserted.
             panic!(
                 "INSERT for task ID {} and report ID {} affected multiple rows?",
                 new_report.task_id(),
@@ -1486,47 +1911,247 @@ impl<C: Clock> Transaction<'_, C> {
             );
         }
 
-        if let Some(row) = rows.into_iter().next() {
-            // One row was affected, meaning there was no conflict and we wrote a new report. We
-            // check that the report wasn't expired per the task's report_expiry_age, but otherwise
-            // we are done. (If the report was expired, we need to delete it; we do this in a
-            // separate query rather than the initial insert because the initial insert cannot
-            // discriminate between a row that was skipped due to expiry & a row that was skipped
-            // due to a write conflict.)
-            if row.get("is_expired") {
-                let stmt = self
-                    .prepare_cached(
-                        "DELETE FROM client_reports
-                        USING tasks
-                        WHERE client_reports.task_id = tasks.id
-                          AND tasks.task_id = $1
-                          AND client_reports.report_id = $2",
-                    )
-                    .await?;
-                self.execute(
-                    &stmt,
-                    &[
-                        /* task_id */ new_report.task_id().as_ref(),
-                        /* report_id */ new_report.metadata().id().as_ref(),
-                    ],
+        let row = rows.into_iter().next().ok_or_else(|| {
+            Error::DbState(format!(
+                "INSERT for task ID {} and report ID {} neither wrote nor found a report",
+                new_report.task_id(),
+                new_report.metadata().id()
+            ))
+        })?;
+
+        if !row.get::<_, bool>("inserted") {
+            return Ok(if row.get("identical") {
+                ClientReportWriteOutcome::Duplicate
+            } else {
+                ClientReportWriteOutcome::Conflict
+            });
+        }
+
+        // We wrote a new report. We check that the report wasn't expired per the task's
+        // report_expiry_age, but otherwise we are done. (If the report was expired, we need to
+        // delete it; we do this in a separate query rather than the initial insert because the
+        // initial insert cannot discriminate between a row that was skipped due to expiry & a row
+        // that was skipped due to a write conflict.)
+        if row.get("is_expired") {
+            let stmt = self
+                .prepare_cached(
+                    "DELETE FROM client_reports
+                    USING tasks
+                    WHERE client_reports.task_id = tasks.id
+                      AND tasks.task_id = $1
+                      AND client_reports.report_id = $2",
                 )
                 .await?;
-            }
-
-            return Ok(());
+            self.execute(
+                &stmt,
+                &[
+                    /* task_id */ new_report.task_id().as_ref(),
+                    /* report_id */ new_report.metadata().id().as_ref(),
+                ],
+            )
+            .await?;
         }
 
-        // No rows were affected, meaning a row with the report ID already existed and we hit the
-        // query's ON CONFLICT DO NOTHING clause.
-        Err(Error::MutationTargetAlreadyExists)
+        Ok(ClientReportWriteOutcome::Inserted)
     }
 
-    /// scrub_client_report removes the client report itself from the datastore, retaining only a
-    /// small amount of metadata required to perform duplicate-report detection & garbage
-    /// collection.
-    ///
-    /// This method is intended for use by aggregators acting in the Leader role. Scrubbed reports
-    /// can no longer be read, so this method should only be called once all aggregations over the
+    /// Writes many client reports, possibly for different tasks, in a single statement, returning
+    /// the outcome of each write, in order. Each report is handled as by [`Self::put_client_report`],
+    /// except that reports which have expired per their task's report expiry age, and reports whose
+    /// task doesn't exist, are not written, and are reported as such rather than failing the whole
+    /// batch. If the same report ID appears more than once for a task, only the first occurrence is
+    /// written, and later ones are compared to it.
+    #[tracing::instrument(skip(self, reports), fields(report_count = reports.len()), err(level = Level::DEBUG))]
+    pub async fn put_client_reports(
+        &self,
+        reports: &[EncodedClientReport],
+    ) -> Result<Vec<ClientReportWriteOutcome>, Error> {
+        if reports.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // Reports repeated within the batch can't be written by the same statement, so they are
+        // resolved against the first occurrence here instead.
+        let mut first_occurrences = HashMap::new();
+        let mut repeats = Vec::new();
+        let mut unique_reports = Vec::new();
+        for (index, report) in reports.iter().enumerate() {
+            match first_occurrences.entry((*report.task_id(), *report.report_id())) {
+                Entry::Occupied(entry) => repeats.push((index, *entry.get())),
+                Entry::Vacant(entry) => {
+                    entry.insert(index);
+                    unique_reports.push((index, report));
+                }
+            }
+        }
+
+        let mut task_ids = Vec::with_capacity(unique_reports.len());
+        let mut report_ids = Vec::with_capacity(unique_reports.len());
+        let mut client_timestamps = Vec::with_capacity(unique_reports.len());
+        let mut extensions = Vec::with_capacity(unique_reports.len());
+        let mut public_shares = Vec::with_capacity(unique_reports.len());
+        let mut leader_input_shares = Vec::with_capacity(unique_reports.len());
+        let mut helper_encrypted_input_shares = Vec::with_capacity(unique_reports.len());
+        for (_, report) in &unique_reports {
+            task_ids.push(report.task_id().as_ref().as_slice());
+            report_ids.push(report.report_id().as_ref().as_slice());
+            client_timestamps.push(report.client_timestamp().as_naive_date_time()?);
+            extensions.push(report.extensions());
+            public_shares.push(report.public_share());
+            leader_input_shares.push(report.leader_input_share());
+            helper_encrypted_input_shares.push(report.helper_encrypted_input_share());
+        }
+
+        // As in put_client_report, existing rows which conflict with the INSERT are visible to
+        // the outer SELECT, since it reads from the statement's snapshot. Expired reports are
+        // never inserted, so unlike put_client_report, they need not be deleted afterwards.
+        let stmt = self
+            .prepare_cached(
+                "WITH new_reports AS (
+                    SELECT
+                        new_reports.*,
+                        tasks.id AS task_pkey,
+                        COALESCE(new_reports.client_timestamp < $8::TIMESTAMP
+                            - tasks.report_expiry_age * '1 second'::INTERVAL, FALSE) AS is_expired
+                    FROM UNNEST(
+                        $1::BYTEA[], $2::BYTEA[], $3::TIMESTAMP[], $4::BYTEA[], $5::BYTEA[],
+                        $6::BYTEA[], $7::BYTEA[]
+                    ) WITH ORDINALITY AS new_reports(
+                        task_id, report_id, client_timestamp, extensions, public_share,
+                        leader_input_share, helper_encrypted_input_share, ord
+                    )
+                    LEFT JOIN tasks ON tasks.task_id = new_reports.task_id
+                ),
+                inserted AS (
+                    INSERT INTO client_reports (
+                        task_id,
+                        report_id,
+                        client_timestamp,
+                        extensions,
+                        public_share,
+                        leader_input_share,
+                        helper_encrypted_input_share,
+                        created_at,
+                        updated_at,
+                        updated_by
+                    )
+                    SELECT
+                        new_reports.task_pkey, new_reports.report_id,
+                        new_reports.client_timestamp, new_reports.extensions,
+                        new_reports.public_share, new_reports.leader_input_share,
+                        new_reports.helper_encrypted_input_share, $8, $9, $10
+                    FROM new_reports
+                    WHERE new_reports.task_pkey IS NOT NULL AND NOT new_reports.is_expired
+                    ORDER BY new_reports.ord
+                    ON CONFLICT DO NOTHING
+                    RETURNING task_id, report_id
+                )
+                SELECT
+                    new_reports.ord,
+                    new_reports.task_pkey IS NOT NULL AS task_exists,
+                    new_reports.is_expired,
+                    inserted.report_id IS NOT NULL AS inserted,
+                    client_reports.client_timestamp = new_reports.client_timestamp
+                        AND (client_reports.leader_input_share IS NULL
+                            OR (client_reports.extensions
+                                    IS NOT DISTINCT FROM new_reports.extensions
+                                AND client_reports.public_share
+                                    IS NOT DISTINCT FROM new_reports.public_share
+                                AND client_reports.leader_input_share
+                                    = new_reports.leader_input_share
+                                AND client_reports.helper_encrypted_input_share
+                                    IS NOT DISTINCT FROM new_reports.helper_encrypted_input_share
+                            )) AS identical
+                FROM new_reports
+                LEFT JOIN inserted
+                    ON inserted.task_id = new_reports.task_pkey
+                    AND inserted.report_id = new_reports.report_id
+                LEFT JOIN client_reports
+                    ON inserted.report_id IS NULL
+                    AND client_reports.task_id = new_reports.task_pkey
+                    AND client_reports.report_id = new_reports.report_id",
+            )
+            .await?;
+        let rows = self
+            .query(
+                &stmt,
+                &[
+                    /* task_id */ &task_ids,
+                    /* report_id */ &report_ids,
+                    /* client_timestamp */ &client_timestamps,
+                    /* extensions */ &extensions,
+                    /* public_share */ &public_shares,
+                    /* leader_input_share */ &leader_input_shares,
+                    /* helper_encrypted_input_share */ &helper_encrypted_input_shares,
+                    /* created_at */ &self.clock.now().as_naive_date_time()?,
+                    /* updated_at */ &self.clock.now().as_naive_date_time()?,
+                    /* updated_by */ &self.name,
+                ],
+            )
+            .await?;
+
+        let mut outcomes = vec![None; reports.len()];
+        for row in rows {
+            // ord is 1-based, and indexes the unique reports.
+            let ord: usize = row.get_bigint_and_convert("ord")?;
+            let (index, _) = ord
+                .checked_sub(1)
+                .and_then(|ord| unique_reports.get(ord))
+                .ok_or_else(|| {
+                    Error::DbState(format!(
+                        "INSERT of client reports returned unknown ord {ord}"
+                    ))
+                })?;
+            // If nothing was inserted and no existing report was found, the outcome is left unset,
+            // and reported as an error below.
+            outcomes[*index] = if !row.get::<_, bool>("task_exists") {
+                Some(ClientReportWriteOutcome::TaskNotFound)
+            } else if row.get("is_expired") {
+                Some(ClientReportWriteOutcome::Expired)
+            } else if row.get("inserted") {
+                Some(ClientReportWriteOutcome::Inserted)
+            } else {
+                row.get::<_, Option<bool>>("identical")
+                    .map(|identical| match identical {
+                        true => ClientReportWriteOutcome::Duplicate,
+                        false => ClientReportWriteOutcome::Conflict,
+                    })
+            };
+        }
+        for (index, first_index) in repeats {
+            outcomes[index] = match outcomes[first_index] {
+                // Repeats of a report that couldn't be written can't be written either.
+                outcome @ Some(
+                    ClientReportWriteOutcome::TaskNotFound | ClientReportWriteOutcome::Expired,
+                ) => outcome,
+                _ if reports[index] == reports[first_index] => {
+                    Some(ClientReportWriteOutcome::Duplicate)
+                }
+                _ => Some(ClientReportWriteOutcome::Conflict),
+            };
+        }
+
+        outcomes
+            .into_iter()
+            .zip(reports)
+            .map(|(outcome, report)| {
+                outcome.ok_or_else(|| {
+                    Error::DbState(format!(
+                        "INSERT for task ID {} and report ID {} neither wrote nor found a report",
+                        report.task_id(),
+                        report.report_id()
+                    ))
+                })
+            })
+            .collect()
+    }
+
+    /// scrub_client_report removes the client report itself from the datastore, retaining only a
+    /// small amount of metadata required to perform duplicate-report detection & garbage
+    /// collection.
+    ///
+    /// This method is intended for use by aggregators acting in the Leader role. Scrubbed reports
+    /// can no longer be read, so this method should only be called once all aggregations over the
     /// report have stepped past their START state.
     #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
     pub async fn scrub_client_report(
@@ -1652,8 +2277,8 @@ impl<C: Clock> Transaction<'_, C> {
         let stmt = self
             .prepare_cached(
                 "SELECT
-                    aggregation_param, batch_id, client_timestamp_interval, state, step,
-                    last_request_hash
+                    aggregation_param, batch_id, client_timestamp_interval,
+                    aggregation_jobs.state, step, last_request_hash
                 FROM aggregation_jobs
                 JOIN tasks ON tasks.id = aggregation_jobs.task_id
                 WHERE tasks.task_id = $1
@@ -1690,7 +2315,7 @@ impl<C: Clock> Transaction<'_, C> {
             .prepare_cached(
                 "SELECT
                     aggregation_job_id, aggregation_param, batch_id, client_timestamp_interval,
-                    state, step, last_request_hash
+                    aggregation_jobs.state, step, last_request_hash
                 FROM aggregation_jobs
                 JOIN tasks ON tasks.id = aggregation_jobs.task_id
                 WHERE tasks.task_id = $1
@@ -1822,34 +2447,132 @@ impl<C: Clock> Transaction<'_, C> {
         .collect()
     }
 
+    /// Like [`Self::acquire_incomplete_aggregation_jobs`], but acquires jobs of a task only while
+    /// fewer than `maximum_task_lease_count` of its jobs hold unexpired leases, counting the jobs
+    /// acquired by this call. Jobs are acquired from each task in turn, so that a task with many
+    /// incomplete jobs does not starve other tasks. The limit is not enforced strictly across
+    /// concurrent acquisitions, since each only counts the leases committed before it began.
+    #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
+    pub async fn acquire_incomplete_aggregation_jobs_with_task_limit(
+        &self,
+        lease_duration: &StdDuration,
+        maximum_acquire_count: usize,
+        maximum_task_lease_count: usize,
+    ) -> Result<Vec<Lease<AcquiredAggregationJob>>, Error> {
+        let now = self.clock.now().as_naive_date_time()?;
+        let lease_expiry_time = add_naive_date_time_duration(&now, lease_duration)?;
+        let maximum_acquire_count: i64 = maximum_acquire_count.try_into()?;
+        let maximum_task_lease_count: i64 = maximum_task_lease_count.try_into()?;
+
+        let stmt = self
+            .prepare_cached(
+                "WITH leased_jobs AS (
+                    SELECT task_id, COUNT(*) AS count FROM aggregation_jobs
+                    WHERE state = 'IN_PROGRESS' AND lease_expiry > $2
+                    GROUP BY task_id
+                ),
+                candidate_jobs AS (
+                    SELECT aggregation_jobs.id,
+                        ROW_NUMBER() OVER (
+                            PARTITION BY aggregation_jobs.task_id ORDER BY aggregation_jobs.id
+                        ) + COALESCE(leased_jobs.count, 0) AS task_lease_count
+                    FROM aggregation_jobs
+                    JOIN tasks ON tasks.id = aggregation_jobs.task_id
+                    LEFT JOIN leased_jobs ON leased_jobs.task_id = aggregation_jobs.task_id
+                    WHERE tasks.aggregator_role = 'LEADER'
+                    AND aggregation_jobs.state = 'IN_PROGRESS'
+                    AND aggregation_jobs.lease_expiry <= $2
+                    AND UPPER(aggregation_jobs.client_timestamp_interval) >= COALESCE($2::TIMESTAMP - tasks.report_expiry_age * '1 second'::INTERVAL, '-infinity'::TIMESTAMP)
+                ),
+                incomplete_jobs AS (
+                    SELECT aggregation_jobs.id FROM aggregation_jobs
+                    JOIN candidate_jobs ON candidate_jobs.id = aggregation_jobs.id
+                    WHERE candidate_jobs.task_lease_count <= $6
+                    AND aggregation_jobs.lease_expiry <= $2
+                    ORDER BY candidate_jobs.task_lease_count
+                    FOR UPDATE OF aggregation_jobs SKIP LOCKED LIMIT $3
+                )
+                UPDATE aggregation_jobs SET
+                    lease_expiry = $1,
+                    lease_token = gen_random_bytes(16),
+                    lease_attempts = lease_attempts + 1,
+                    updated_at = $4,
+                    updated_by = $5
+                FROM tasks
+                WHERE tasks.id = aggregation_jobs.task_id
+                AND aggregation_jobs.id IN (SELECT id FROM incomplete_jobs)
+                RETURNING tasks.task_id, tasks.query_type, tasks.vdaf,
+                          aggregation_jobs.aggregation_job_id, aggregation_jobs.lease_token,
+                          aggregation_jobs.lease_attempts",
+            )
+            .await?;
+        self.query(
+            &stmt,
+            &[
+                /* lease_expiry */ &lease_expiry_time,
+                /* now */ &now,
+                /* limit */ &maximum_acquire_count,
+                /* updated_at */ &self.clock.now().as_naive_date_time()?,
+                /* updated_by */ &self.name,
+                /* task_limit */ &maximum_task_lease_count,
+            ],
+        )
+        .await?
+        .into_iter()
+        .map(|row| {
+            let task_id = TaskId::get_decoded(row.get("task_id"))?;
+            let aggregation_job_id =
+                row.get_bytea_and_convert::<AggregationJobId>("aggregation_job_id")?;
+            let query_type = row.try_get::<_, Json<task::QueryType>>("query_type")?.0;
+            let vdaf = row.try_get::<_, Json<VdafInstance>>("vdaf")?.0;
+            let lease_token = row.get_bytea_and_convert::<LeaseToken>("lease_token")?;
+            let lease_attempts = row.get_bigint_and_convert("lease_attempts")?;
+            Ok(Lease::new(
+                AcquiredAggregationJob::new(task_id, aggregation_job_id, query_type, vdaf),
+                lease_expiry_time,
+                lease_token,
+                lease_attempts,
+            ))
+        })
+        .collect()
+    }
+
     /// release_aggregation_job releases an acquired (via e.g. acquire_incomplete_aggregation_jobs)
-    /// aggregation job. It returns an error if the aggregation job has no current lease.
+    /// aggregation job. If given, `reacquire_delay` determines the duration of time that must pass
+    /// before the aggregation job can be reacquired. It returns an error if the aggregation job has
+    /// no current lease.
     #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
     pub async fn release_aggregation_job(
         &self,
         lease: &Lease<AcquiredAggregationJob>,
+        reacquire_delay: Option<&StdDuration>,
     ) -> Result<(), Error> {
+        let lease_expiration = reacquire_delay
+            .map(|rd| add_naive_date_time_duration(&self.clock.now().as_naive_date_time()?, rd))
+            .transpose()?;
+
         let stmt = self
             .prepare_cached(
                 "UPDATE aggregation_jobs
-                SET lease_expiry = TIMESTAMP '-infinity',
+                SET lease_expiry = COALESCE($1, '-infinity'::TIMESTAMP),
                     lease_token = NULL,
                     lease_attempts = 0,
-                    updated_at = $1,
-                    updated_by = $2
+                    updated_at = $2,
+                    updated_by = $3
                 FROM tasks
                 WHERE tasks.id = aggregation_jobs.task_id
-                  AND tasks.task_id = $3
-                  AND aggregation_jobs.aggregation_job_id = $4
-                  AND aggregation_jobs.lease_expiry = $5
-                  AND aggregation_jobs.lease_token = $6
-                  AND UPPER(aggregation_jobs.client_timestamp_interval) >= COALESCE($7::TIMESTAMP - tasks.report_expiry_age * '1 second'::INTERVAL, '-infinity'::TIMESTAMP)",
+                  AND tasks.task_id = $4
+                  AND aggregation_jobs.aggregation_job_id = $5
+                  AND aggregation_jobs.lease_expiry = $6
+                  AND aggregation_jobs.lease_token = $7
+                  AND UPPER(aggregation_jobs.client_timestamp_interval) >= COALESCE($8::TIMESTAMP - tasks.report_expiry_age * '1 second'::INTERVAL, '-infinity'::TIMESTAMP)",
             )
             .await?;
         check_single_row_mutation(
             self.execute(
                 &stmt,
                 &[
+                    /* lease_expiry */ &lease_expiration,
                     /* updated_at */ &self.clock.now().as_naive_date_time()?,
                     /* updated_by */ &self.name,
                     /* task_id */ &lease.leased().task_id().as_ref(),
@@ -1864,6 +2587,71 @@ impl<C: Clock> Transaction<'_, C> {
         )
     }
 
+    /// Records the failure domain (e.g. "datastore", "peer", or "crypto") of the most recent failed
+    /// attempt to step the given aggregation job.
+    #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
+    pub async fn set_aggregation_job_last_failure_domain(
+        &self,
+        task_id: &TaskId,
+        aggregation_job_id: &AggregationJobId,
+        failure_domain: &str,
+    ) -> Result<(), Error> {
+        let stmt = self
+            .prepare_cached(
+                "UPDATE aggregation_jobs
+                SET last_failure_domain = $1,
+                    updated_at = $2,
+                    updated_by = $3
+                FROM tasks
+                WHERE tasks.id = aggregation_jobs.task_id
+                  AND tasks.task_id = $4
+                  AND aggregation_jobs.aggregation_job_id = $5",
+            )
+            .await?;
+        check_single_row_mutation(
+            self.execute(
+                &stmt,
+                &[
+                    /* last_failure_domain */ &failure_domain,
+                    /* updated_at */ &self.clock.now().as_naive_date_time()?,
+                    /* updated_by */ &self.name,
+                    /* task_id */ &task_id.as_ref(),
+                    /* aggregation_job_id */ &aggregation_job_id.as_ref(),
+                ],
+            )
+            .await?,
+        )
+    }
+
+    /// Returns the failure domain of the most recent failed attempt to step the given aggregation job,
+    /// or `None` if the job does not exist or no attempt to step it has failed.
+    #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
+    pub async fn get_aggregation_job_last_failure_domain(
+        &self,
+        task_id: &TaskId,
+        aggregation_job_id: &AggregationJobId,
+    ) -> Result<Option<String>, Error> {
+        let stmt = self
+            .prepare_cached(
+                "SELECT aggregation_jobs.last_failure_domain
+                FROM aggregation_jobs
+                JOIN tasks ON tasks.id = aggregation_jobs.task_id
+                WHERE tasks.task_id = $1
+                  AND aggregation_jobs.aggregation_job_id = $2",
+            )
+            .await?;
+        Ok(self
+            .query_opt(
+                &stmt,
+                &[
+                    /* task_id */ &task_id.as_ref(),
+                    /* aggregation_job_id */ &aggregation_job_id.as_ref(),
+                ],
+            )
+            .await?
+            .and_then(|row| row.get("last_failure_domain")))
+    }
+
     /// put_aggregation_job stores an aggregation job.
     #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
     pub async fn put_aggregation_job<
@@ -2629,6 +3417,112 @@ impl<C: Clock> Transaction<'_, C> {
         .transpose()
     }
 
+    /// Returns the progress of the collection job with the given ID, or `None` if no such collection
+    /// job exists. Completion is estimated by assuming that the collection job's outstanding
+    /// aggregation jobs will terminate at the rate at which the task's aggregation jobs terminated
+    /// over the last `throughput_window`, or since the task's earliest aggregation job was created,
+    /// if that is more recent.
+    #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
+    pub async fn get_collection_job_progress(
+        &self,
+        task_id: &TaskId,
+        collection_job_id: &CollectionJobId,
+        throughput_window: &Duration,
+    ) -> Result<Option<CollectionJobProgress>, Error> {
+        let now = self.clock.now();
+        let window_start = now.sub(throughput_window)?;
+        let stmt = self
+            .prepare_cached(
+                "WITH collection_job AS (
+                    SELECT collection_jobs.task_id, collection_jobs.aggregation_param,
+                        collection_jobs.batch_identifier, collection_jobs.batch_interval,
+                        collection_jobs.state
+                    FROM collection_jobs
+                    JOIN tasks ON tasks.id = collection_jobs.task_id
+                    WHERE tasks.task_id = $1
+                      AND collection_jobs.collection_job_id = $2
+                )
+                SELECT
+                    collection_job.state,
+                    (SELECT COALESCE(SUM(batch_aggregations.aggregation_jobs_created
+                            - batch_aggregations.aggregation_jobs_terminated), 0)::BIGINT
+                        FROM batch_aggregations
+                        WHERE batch_aggregations.task_id = collection_job.task_id
+                          AND batch_aggregations.aggregation_param = collection_job.aggregation_param
+                          AND (batch_aggregations.batch_identifier = collection_job.batch_identifier
+                            OR batch_aggregations.batch_interval <@ collection_job.batch_interval)
+                    ) AS outstanding_aggregation_jobs,
+                    (SELECT COUNT(*)
+                        FROM aggregation_jobs
+                        WHERE aggregation_jobs.task_id = collection_job.task_id
+                          AND aggregation_jobs.state IN ('FINISHED', 'ABANDONED', 'DELETED')
+                          AND aggregation_jobs.updated_at >= $3
+                    ) AS recently_terminated_aggregation_jobs,
+                    (SELECT MIN(aggregation_jobs.created_at)
+                        FROM aggregation_jobs
+                        WHERE aggregation_jobs.task_id = collection_job.task_id
+                    ) AS earliest_aggregation_job_created_at
+                FROM collection_job",
+            )
+            .await?;
+        let row = match self
+            .query_opt(
+                &stmt,
+                &[
+                    /* task_id */ task_id.as_ref(),
+                    /* collection_job_id */ &collection_job_id.as_ref(),
+                    /* window_start */ &window_start.as_naive_date_time()?,
+                ],
+            )
+            .await?
+        {
+            Some(row) => row,
+            None => return Ok(None),
+        };
+
+        let state: CollectionJobStateCode = row.get("state");
+        let outstanding_aggregation_jobs: u64 =
+            row.get_bigint_and_convert("outstanding_aggregation_jobs")?;
+        let recently_terminated_aggregation_jobs: u64 =
+            row.get_bigint_and_convert("recently_terminated_aggregation_jobs")?;
+
+        // Throughput is only observed over the part of the window during which the task had
+        // aggregation jobs, so that the estimate is not inflated for newly created tasks.
+        let observed_window = match row
+            .get::<_, Option<NaiveDateTime>>("earliest_aggregation_job_created_at")
+            .as_ref()
+            .map(Time::from_naive_date_time)
+        {
+            Some(earliest_created_at) if earliest_created_at.is_after(&window_start) => now
+                .difference(&earliest_created_at)
+                .unwrap_or(Duration::ZERO),
+            _ => *throughput_window,
+        }
+        .as_seconds()
+        .max(1);
+
+        let estimated_completion = match state {
+            CollectionJobStateCode::Start
+                if outstanding_aggregation_jobs == 0
+                    || recently_terminated_aggregation_jobs > 0 =>
+            {
+                let remaining = Duration::from_seconds(
+                    outstanding_aggregation_jobs.saturating_mul(observed_window)
+                        / recently_terminated_aggregation_jobs.max(1),
+                );
+                Some(now.add(&remaining)?)
+            }
+            _ => None,
+        };
+
+        Ok(Some(CollectionJobProgress::new(
+            state,
+            outstanding_aggregation_jobs,
+            recently_terminated_aggregation_jobs,
+            estimated_completion,
+        )))
+    }
+
     /// Returns a collection job in state FINISHED with the given parameters, or `None` if no such
     /// collection job exists.
     pub async fn get_finished_collection_job<
@@ -2687,6 +3581,31 @@ impl<C: Clock> Transaction<'_, C> {
         .transpose()
     }
 
+    /// Returns the time at which the most recently finished collection job of the given task
+    /// finished, or `None` if none of the task's collection jobs have finished.
+    #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
+    pub async fn get_latest_finished_collection_time(
+        &self,
+        task_id: &TaskId,
+    ) -> Result<Option<Time>, Error> {
+        let stmt = self
+            .prepare_cached(
+                "SELECT MAX(collection_jobs.updated_at) AS latest_finished_at
+                FROM collection_jobs
+                JOIN tasks ON tasks.id = collection_jobs.task_id
+                WHERE tasks.task_id = $1
+                  AND collection_jobs.state = 'FINISHED'",
+            )
+            .await?;
+        let row = self
+            .query_one(&stmt, &[/* task_id */ task_id.as_ref()])
+            .await?;
+        Ok(row
+            .get::<_, Option<NaiveDateTime>>("latest_finished_at")
+            .as_ref()
+            .map(Time::from_naive_date_time))
+    }
+
     /// Returns all collection jobs for the given task which include the given timestamp. Applies
     /// only to time-interval tasks.
     #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
@@ -3161,28 +4080,93 @@ impl<C: Clock> Transaction<'_, C> {
         )
     }
 
-    /// Updates an existing collection job.
+    /// Records the failure domain (e.g. "datastore", "peer", or "crypto") of the most recent failed
+    /// attempt to step the given collection job.
     #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
-    pub async fn update_collection_job<
-        const SEED_SIZE: usize,
-        Q: QueryType,
-        A: vdaf::Aggregator<SEED_SIZE, 16>,
-    >(
+    pub async fn set_collection_job_last_failure_domain(
         &self,
-        collection_job: &CollectionJob<SEED_SIZE, Q, A>,
+        task_id: &TaskId,
+        collection_job_id: &CollectionJobId,
+        failure_domain: &str,
     ) -> Result<(), Error> {
-        let (
-            report_count,
-            client_timestamp_interval,
-            leader_aggregate_share,
-            helper_aggregate_share,
-        ) = match collection_job.state() {
-            CollectionJobState::Start => {
-                return Err(Error::InvalidParameter(
-                    "cannot update collection job into START state",
-                ));
-            }
-
+        let stmt = self
+            .prepare_cached(
+                "UPDATE collection_jobs
+                SET last_failure_domain = $1,
+                    updated_at = $2,
+                    updated_by = $3
+                FROM tasks
+                WHERE tasks.id = collection_jobs.task_id
+                  AND tasks.task_id = $4
+                  AND collection_jobs.collection_job_id = $5",
+            )
+            .await?;
+        check_single_row_mutation(
+            self.execute(
+                &stmt,
+                &[
+                    /* last_failure_domain */ &failure_domain,
+                    /* updated_at */ &self.clock.now().as_naive_date_time()?,
+                    /* updated_by */ &self.name,
+                    /* task_id */ &task_id.as_ref(),
+                    /* collection_job_id */ &collection_job_id.as_ref(),
+                ],
+            )
+            .await?,
+        )
+    }
+
+    /// Returns the failure domain of the most recent failed attempt to step the given collection job,
+    /// or `None` if the job does not exist or no attempt to step it has failed.
+    #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
+    pub async fn get_collection_job_last_failure_domain(
+        &self,
+        task_id: &TaskId,
+        collection_job_id: &CollectionJobId,
+    ) -> Result<Option<String>, Error> {
+        let stmt = self
+            .prepare_cached(
+                "SELECT collection_jobs.last_failure_domain
+                FROM collection_jobs
+                JOIN tasks ON tasks.id = collection_jobs.task_id
+                WHERE tasks.task_id = $1
+                  AND collection_jobs.collection_job_id = $2",
+            )
+            .await?;
+        Ok(self
+            .query_opt(
+                &stmt,
+                &[
+                    /* task_id */ &task_id.as_ref(),
+                    /* collection_job_id */ &collection_job_id.as_ref(),
+                ],
+            )
+            .await?
+            .and_then(|row| row.get("last_failure_domain")))
+    }
+
+    /// Updates an existing collection job.
+    #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
+    pub async fn update_collection_job<
+        const SEED_SIZE: usize,
+        Q: QueryType,
+        A: vdaf::Aggregator<SEED_SIZE, 16>,
+    >(
+        &self,
+        collection_job: &CollectionJob<SEED_SIZE, Q, A>,
+    ) -> Result<(), Error> {
+        let (
+            report_count,
+            client_timestamp_interval,
+            leader_aggregate_share,
+            helper_aggregate_share,
+        ) = match collection_job.state() {
+            CollectionJobState::Start => {
+                return Err(Error::InvalidParameter(
+                    "cannot update collection job into START state",
+                ));
+            }
+
             CollectionJobState::Finished {
                 report_count,
                 client_timestamp_interval,
@@ -4252,12 +5236,13 @@ impl<C: Clock> Transaction<'_, C> {
     }
 
     /// Deletes old client reports for a given task, that is, client reports whose timestamp is
-    /// older than the task's report expiry age. Up to `limit` client reports will be deleted.
-    /// Returns the number of client reports deleted.
+    /// older than the task's report expiry age plus `retention_period`. Up to `limit` client
+    /// reports will be deleted. Returns the number of client reports deleted.
     #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
     pub async fn delete_expired_client_reports(
         &self,
         task_id: &TaskId,
+        retention_period: &Duration,
         limit: u64,
     ) -> Result<u64, Error> {
         // Calculation of a report timestamp threshold is split apart from the main body of the
@@ -4266,7 +5251,10 @@ impl<C: Clock> Transaction<'_, C> {
         // threshold is determined in a single query via a join, the query planner is not able to
         // predict the task's report_expiry_age, and the accuracy of the row count estimate suffers.
         let (id, threshold) = self
-            .get_task_primary_key_and_expiry_threshold(task_id)
+            .get_task_primary_key_and_expiry_threshold(
+                task_id,
+                &self.clock.now().sub(retention_period)?,
+            )
             .await?;
 
         let stmt = self
@@ -4295,10 +5283,11 @@ impl<C: Clock> Transaction<'_, C> {
     }
 
     /// Helper function to look up a task's primary key, and compute a garbage collection visibility
-    /// threshold timestamp from its report expiry duration.
+    /// threshold timestamp from its report expiry duration, as of `now`.
     async fn get_task_primary_key_and_expiry_threshold(
         &self,
         task_id: &TaskId,
+        now: &Time,
     ) -> Result<(i64, Timestamp<NaiveDateTime>), Error> {
         let stmt = self
             .prepare_cached(
@@ -4316,7 +5305,7 @@ impl<C: Clock> Transaction<'_, C> {
                 &stmt,
                 &[
                     /* task_id */ &task_id.get_encoded()?,
-                    /* now */ &self.clock.now().as_naive_date_time()?,
+                    /* now */ &now.as_naive_date_time()?,
                 ],
             )
             .await?;
@@ -4327,13 +5316,14 @@ impl<C: Clock> Transaction<'_, C> {
 
     /// Deletes old aggregation artifacts (aggregation jobs/report aggregations) for a given task,
     /// that is, aggregation artifacts for which the aggregation job's maximum client timestamp is
-    /// older than the task's report expiry age. Up to `limit` aggregation jobs will be deleted,
-    /// along with all related aggregation artifacts. Returns the number of aggregation jobs
-    /// deleted.
+    /// older than the task's report expiry age plus `retention_period`. Up to `limit` aggregation
+    /// jobs will be deleted, along with all related aggregation artifacts. Returns the number of
+    /// aggregation jobs deleted.
     #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
     pub async fn delete_expired_aggregation_artifacts(
         &self,
         task_id: &TaskId,
+        retention_period: &Duration,
         limit: u64,
     ) -> Result<u64, Error> {
         let stmt = self
@@ -4355,11 +5345,59 @@ impl<C: Clock> Transaction<'_, C> {
                 WHERE id IN (SELECT id FROM aggregation_jobs_to_delete)",
             )
             .await?;
+        self.execute(
+            &stmt,
+            &[
+                /* task_id */ &task_id.get_encoded()?,
+                /* now */
+                &self
+                    .clock
+                    .now()
+                    .sub(retention_period)?
+                    .as_naive_date_time()?,
+                /* limit */ &i64::try_from(limit)?,
+            ],
+        )
+        .await
+        .map_err(Into::into)
+    }
+
+    /// Deletes aggregation jobs (along with their report aggregations) for a given task that have
+    /// been in a terminal state (finished, abandoned, or deleted) for at least `ttl`, regardless of
+    /// the task's report expiry age. Up to `limit` aggregation jobs will be deleted. Returns the
+    /// number of aggregation jobs deleted.
+    #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
+    pub async fn delete_terminal_aggregation_jobs(
+        &self,
+        task_id: &TaskId,
+        ttl: &Duration,
+        limit: u64,
+    ) -> Result<u64, Error> {
+        let stmt = self
+            .prepare_cached(
+                "WITH task_id AS (SELECT id FROM tasks WHERE task_id = $1),
+                aggregation_jobs_to_delete AS (
+                    SELECT aggregation_jobs.id FROM aggregation_jobs
+                    WHERE aggregation_jobs.task_id IN (SELECT id FROM task_id)
+                      AND aggregation_jobs.state IN ('FINISHED', 'ABANDONED', 'DELETED')
+                      AND aggregation_jobs.updated_at < $2::TIMESTAMP - $3::BIGINT * '1 second'::INTERVAL
+                    LIMIT $4
+                ),
+                deleted_report_aggregations AS (
+                    DELETE FROM report_aggregations
+                    WHERE aggregation_job_id IN (SELECT id FROM aggregation_jobs_to_delete)
+                    AND task_id IN (SELECT id FROM task_id)
+                )
+                DELETE FROM aggregation_jobs
+                WHERE id IN (SELECT id FROM aggregation_jobs_to_delete)",
+            )
+            .await?;
         self.execute(
             &stmt,
             &[
                 /* task_id */ &task_id.get_encoded()?,
                 /* now */ &self.clock.now().as_naive_date_time()?,
+                /* ttl */ &i64::try_from(ttl.as_seconds())?,
                 /* limit */ &i64::try_from(limit)?,
             ],
         )
@@ -4384,13 +5422,15 @@ impl<C: Clock> Transaction<'_, C> {
     ///   * For fixed-size tasks, collection_jobs and aggregate_share_jobs are considered eligible
     ///     for GC if the related batch is eligible for GC, based on the `batch_aggregations` rows.
     ///
-    /// Up to `limit` batches will be deleted, along with all related collection artifacts.
+    /// Each of these ages is extended by `retention_period`. Up to `limit` batches will be deleted,
+    /// along with all related collection artifacts.
     ///
     /// Returns the number of batches deleted.
     #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
     pub async fn delete_expired_collection_artifacts(
         &self,
         task_id: &TaskId,
+        retention_period: &Duration,
         limit: u64,
     ) -> Result<u64, Error> {
         // `MAX(tasks.report_expiry_age)` below should become `ANY_VALUE(tasks.report_expiry_age)`
@@ -4440,7 +5480,12 @@ impl<C: Clock> Transaction<'_, C> {
             &stmt,
             &[
                 /* task_id */ &task_id.get_encoded()?,
-                /* now */ &self.clock.now().as_naive_date_time()?,
+                /* now */
+                &self
+                    .clock
+                    .now()
+                    .sub(retention_period)?
+                    .as_naive_date_time()?,
                 /* limit */ &i64::try_from(limit)?,
             ],
         )
@@ -4448,6 +5493,230 @@ impl<C: Clock> Transaction<'_, C> {
         .map_err(Into::into)
     }
 
+    /// Counts report aggregations whose aggregation job does not exist. The schema's foreign key
+    /// constraints normally prevent such report aggregations from existing.
+    #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
+    pub async fn count_orphaned_report_aggregations(&self) -> Result<u64, Error> {
+        let stmt = self
+            .prepare_cached(
+                "SELECT COUNT(*) AS count FROM report_aggregations
+                WHERE NOT EXISTS (
+                    SELECT 1 FROM aggregation_jobs
+                    WHERE aggregation_jobs.id = report_aggregations.aggregation_job_id
+                )",
+            )
+            .await?;
+        let row = self.query_one(&stmt, &[]).await?;
+        Ok(u64::try_from(row.get::<_, i64>("count"))?)
+    }
+
+    /// Deletes report aggregations whose aggregation job does not exist. Returns the number of
+    /// report aggregations deleted.
+    #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
+    pub async fn delete_orphaned_report_aggregations(&self) -> Result<u64, Error> {
+        let stmt = self
+            .prepare_cached(
+                "DELETE FROM report_aggregations
+                WHERE NOT EXISTS (
+                    SELECT 1 FROM aggregation_jobs
+                    WHERE aggregation_jobs.id = report_aggregations.aggregation_job_id
+                )",
+            )
+            .await?;
+        self.execute(&stmt, &[]).await.map_err(Into::into)
+    }
+
+    /// Counts unscrubbed batch aggregations whose counters could not have been produced by
+    /// aggregation: missing or negative counts, more terminated aggregation jobs than created
+    /// aggregation jobs, or a nonzero report count without an aggregate share.
+    #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
+    pub async fn count_batch_aggregations_with_impossible_counts(&self) -> Result<u64, Error> {
+        let stmt = self
+            .prepare_cached(
+                "SELECT COUNT(*) AS count FROM batch_aggregations
+                WHERE state != 'SCRUBBED'
+                  AND (report_count IS NULL OR report_count < 0
+                    OR aggregation_jobs_created IS NULL OR aggregation_jobs_terminated IS NULL
+                    OR aggregation_jobs_terminated < 0
+                    OR aggregation_jobs_terminated > aggregation_jobs_created
+                    OR (report_count > 0 AND aggregate_share IS NULL))",
+            )
+            .await?;
+        let row = self.query_one(&stmt, &[]).await?;
+        Ok(u64::try_from(row.get::<_, i64>("count"))?)
+    }
+
+    /// Deletes the batch aggregations counted by
+    /// [`Self::count_batch_aggregations_with_impossible_counts`]. Returns the number of batch
+    /// aggregations deleted.
+    #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
+    pub async fn delete_batch_aggregations_with_impossible_counts(&self) -> Result<u64, Error> {
+        let stmt = self
+            .prepare_cached(
+                "DELETE FROM batch_aggregations
+                WHERE state != 'SCRUBBED'
+                  AND (report_count IS NULL OR report_count < 0
+                    OR aggregation_jobs_created IS NULL OR aggregation_jobs_terminated IS NULL
+                    OR aggregation_jobs_terminated < 0
+                    OR aggregation_jobs_terminated > aggregation_jobs_created
+                    OR (report_count > 0 AND aggregate_share IS NULL))",
+            )
+            .await?;
+        self.execute(&stmt, &[]).await.map_err(Into::into)
+    }
+
+    /// Counts finished, nonempty collection jobs for which no batch aggregation exists. For
+    /// time-interval tasks, any batch aggregation within the collection job's batch interval
+    /// counts.
+    #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
+    pub async fn count_collection_jobs_missing_batch_aggregations(&self) -> Result<u64, Error> {
+        let stmt = self
+            .prepare_cached(
+                "SELECT COUNT(*) AS count FROM collection_jobs
+                WHERE collection_jobs.state = 'FINISHED'
+                  AND collection_jobs.report_count > 0
+                  AND NOT EXISTS (
+                    SELECT 1 FROM batch_aggregations
+                    WHERE batch_aggregations.task_id = collection_jobs.task_id
+                      AND batch_aggregations.aggregation_param = collection_jobs.aggregation_param
+                      AND (batch_aggregations.batch_identifier = collection_jobs.batch_identifier
+                        OR batch_aggregations.batch_interval <@ collection_jobs.batch_interval)
+                  )",
+            )
+            .await?;
+        let row = self.query_one(&stmt, &[]).await?;
+        Ok(u64::try_from(row.get::<_, i64>("count"))?)
+    }
+
+    /// Abandons the collection jobs counted by
+    /// [`Self::count_collection_jobs_missing_batch_aggregations`], discarding their results.
+    /// Returns the number of collection jobs abandoned.
+    #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
+    pub async fn abandon_collection_jobs_missing_batch_aggregations(&self) -> Result<u64, Error> {
+        let stmt = self
+            .prepare_cached(
+                "UPDATE collection_jobs SET
+                    state = 'ABANDONED',
+                    report_count = NULL,
+                    client_timestamp_interval = NULL,
+                    leader_aggregate_share = NULL,
+                    helper_aggregate_share = NULL,
+                    updated_at = $1,
+                    updated_by = $2
+                WHERE collection_jobs.state = 'FINISHED'
+                  AND collection_jobs.report_count > 0
+                  AND NOT EXISTS (
+                    SELECT 1 FROM batch_aggregations
+                    WHERE batch_aggregations.task_id = collection_jobs.task_id
+                      AND batch_aggregations.aggregation_param = collection_jobs.aggregation_param
+                      AND (batch_aggregations.batch_identifier = collection_jobs.batch_identifier
+                        OR batch_aggregations.batch_interval <@ collection_jobs.batch_interval)
+                  )",
+            )
+            .await?;
+        self.execute(
+            &stmt,
+            &[
+                /* updated_at */ &self.clock.now().as_naive_date_time()?,
+                /* updated_by */ &self.name,
+            ],
+        )
+        .await
+        .map_err(Into::into)
+    }
+
+    /// Counts the aggregation jobs of all tasks in each state. States without any aggregation jobs
+    /// are omitted.
+    #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
+    pub async fn count_aggregation_jobs_by_state(
+        &self,
+    ) -> Result<Vec<(AggregationJobState, u64)>, Error> {
+        let stmt = self
+            .prepare_cached(
+                "SELECT state, COUNT(*) AS count FROM aggregation_jobs
+                GROUP BY state ORDER BY state",
+            )
+            .await?;
+        self.query(&stmt, &[])
+            .await?
+            .into_iter()
+            .map(|row| Ok((row.get("state"), row.get_bigint_and_convert("count")?)))
+            .collect()
+    }
+
+    /// Counts the collection jobs of all tasks in each state. States without any collection jobs
+    /// are omitted.
+    #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
+    pub async fn count_collection_jobs_by_state(
+        &self,
+    ) -> Result<Vec<(CollectionJobStateCode, u64)>, Error> {
+        let stmt = self
+            .prepare_cached(
+                "SELECT state, COUNT(*) AS count FROM collection_jobs
+                GROUP BY state ORDER BY state",
+            )
+            .await?;
+        self.query(&stmt, &[])
+            .await?
+            .into_iter()
+            .map(|row| Ok((row.get("state"), row.get_bigint_and_convert("count")?)))
+            .collect()
+    }
+
+    /// Counts the rows of each table holding tasks, keys, reports or job state, in a fixed order
+    /// of tables. Used to compare two datastores during a datastore migration.
+    #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
+    pub async fn count_rows_by_table(&self) -> Result<Vec<(String, u64)>, Error> {
+        let stmt = self
+            .prepare_cached(
+                "SELECT 'tasks' AS name, COUNT(*) AS count FROM tasks
+                UNION ALL SELECT 'task_hpke_keys', COUNT(*) FROM task_hpke_keys
+                UNION ALL SELECT 'global_hpke_keys', COUNT(*) FROM global_hpke_keys
+                UNION ALL SELECT 'taskprov_peer_aggregators', COUNT(*)
+                    FROM taskprov_peer_aggregators
+                UNION ALL SELECT 'client_reports', COUNT(*) FROM client_reports
+                UNION ALL SELECT 'aggregation_jobs', COUNT(*) FROM aggregation_jobs
+                UNION ALL SELECT 'report_aggregations', COUNT(*) FROM report_aggregations
+                UNION ALL SELECT 'batch_aggregations', COUNT(*) FROM batch_aggregations
+                UNION ALL SELECT 'collection_jobs', COUNT(*) FROM collection_jobs
+                UNION ALL SELECT 'aggregate_share_jobs', COUNT(*) FROM aggregate_share_jobs
+                UNION ALL SELECT 'outstanding_batches', COUNT(*) FROM outstanding_batches",
+            )
+            .await?;
+        self.query(&stmt, &[])
+            .await?
+            .into_iter()
+            .map(|row| Ok((row.get("name"), row.get_bigint_and_convert("count")?)))
+            .collect()
+    }
+
+    /// Computes, for each task with client reports, the number of client reports and a SHA-256
+    /// digest over the task's sorted report IDs. Tasks are returned in order of task ID. Used to
+    /// check that two datastores hold the same reports during a datastore migration.
+    #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
+    pub async fn get_client_report_digests(&self) -> Result<Vec<(TaskId, u64, Vec<u8>)>, Error> {
+        let stmt = self
+            .prepare_cached(
+                "SELECT tasks.task_id, COUNT(*) AS count,
+                    sha256(string_agg(client_reports.report_id, ''::BYTEA
+                        ORDER BY client_reports.report_id)) AS digest
+                FROM client_reports JOIN tasks ON tasks.id = client_reports.task_id
+                GROUP BY tasks.task_id ORDER BY tasks.task_id",
+            )
+            .await?;
+        self.query(&stmt, &[])
+            .await?
+            .into_iter()
+            .map(|row| {
+                Ok((
+                    TaskId::get_decoded(row.get("task_id"))?,
+                    row.get_bigint_and_convert("count")?,
+                    row.get("digest"),
+                ))
+            })
+            .collect()
+    }
+
     /// Retrieve all global HPKE keypairs.
     #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
     pub async fn get_global_hpke_keypairs(&self) -> Result<Vec<GlobalHpkeKeypair>, Error> {
@@ -4576,36 +5845,161 @@ impl<C: Clock> Transaction<'_, C> {
         )
     }
 
+    /// Retrieve the HPKE configs of the given task, including those of keypairs which have been
+    /// retired but not yet deleted, ordered by config ID.
     #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
-    pub async fn get_taskprov_peer_aggregators(&self) -> Result<Vec<PeerAggregator>, Error> {
+    pub async fn get_task_hpke_configs(
+        &self,
+        task_id: &TaskId,
+    ) -> Result<Vec<TaskHpkeConfig>, Error> {
         let stmt = self
             .prepare_cached(
-                "SELECT id, endpoint, role, verify_key_init, collector_hpke_config,
-                        report_expiry_age, tolerable_clock_skew
-                    FROM taskprov_peer_aggregators",
+                "SELECT config, retire_at FROM task_hpke_keys
+                WHERE task_id = (SELECT id FROM tasks WHERE task_id = $1)
+                ORDER BY config_id",
             )
             .await?;
-        let peer_aggregator_rows = self.query(&stmt, &[]);
+        self.query(&stmt, &[/* task_id */ &task_id.as_ref()])
+            .await?
+            .into_iter()
+            .map(|row| {
+                Ok(TaskHpkeConfig::new(
+                    HpkeConfig::get_decoded(row.get("config"))?,
+                    row.get::<_, Option<NaiveDateTime>>("retire_at")
+                        .as_ref()
+                        .map(Time::from_naive_date_time),
+                ))
+            })
+            .collect()
+    }
+
+    /// Inserts a new HPKE keypair for the given task. It returns an error if the task already has
+    /// a keypair with the same config ID.
+    #[tracing::instrument(skip(self, hpke_keypair), err(level = Level::DEBUG))]
+    pub async fn put_task_hpke_keypair(
+        &self,
+        task_id: &TaskId,
+        hpke_keypair: &HpkeKeypair,
+    ) -> Result<(), Error> {
+        let config_id = u8::from(*hpke_keypair.config().id());
+        let mut row_id = [0u8; TaskId::LEN + size_of::<u8>()];
+        row_id[..TaskId::LEN].copy_from_slice(task_id.as_ref());
+        row_id[TaskId::LEN..].copy_from_slice(&config_id.to_be_bytes());
+        let encrypted_hpke_private_key = self.crypter.encrypt(
+            "task_hpke_keys",
+            &row_id,
+            "private_key",
+            hpke_keypair.private_key().as_ref(),
+        )?;
 
         let stmt = self
             .prepare_cached(
-                "SELECT (SELECT p.id FROM taskprov_peer_aggregators AS p
-                    WHERE p.id = a.peer_aggregator_id) AS peer_id,
-                ord, type, token FROM taskprov_aggregator_auth_tokens AS a
-                    ORDER BY ord ASC",
+                "INSERT INTO task_hpke_keys (
+                    task_id, config_id, config, private_key, created_at, updated_by
+                )
+                SELECT id, $2, $3, $4, $5, $6 FROM tasks WHERE task_id = $1
+                ON CONFLICT DO NOTHING",
             )
             .await?;
-        let aggregator_auth_token_rows = self.query(&stmt, &[]);
+        check_insert(
+            self.execute(
+                &stmt,
+                &[
+                    /* task_id */ &task_id.as_ref(),
+                    /* config_id */ &(config_id as i16),
+                    /* config */ &hpke_keypair.config().get_encoded()?,
+                    /* private_key */ &encrypted_hpke_private_key,
+                    /* created_at */ &self.clock.now().as_naive_date_time()?,
+                    /* updated_by */ &self.name,
+                ],
+            )
+            .await?,
+        )
+    }
 
+    /// Schedules each HPKE keypair of the given task, other than the one with config ID `keep`,
+    /// to be retired at `retire_at`, unless it is already scheduled for retirement. Returns the
+    /// number of keypairs scheduled.
+    #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
+    pub async fn retire_task_hpke_keypairs(
+        &self,
+        task_id: &TaskId,
+        keep: &HpkeConfigId,
+        retire_at: &Time,
+    ) -> Result<u64, Error> {
         let stmt = self
             .prepare_cached(
-                "SELECT (SELECT p.id FROM taskprov_peer_aggregators AS p
-                    WHERE p.id = a.peer_aggregator_id) AS peer_id,
-                ord, type, token FROM taskprov_collector_auth_tokens AS a
-                    ORDER BY ord ASC",
+                "UPDATE task_hpke_keys SET retire_at = $1, updated_by = $2
+                WHERE task_id = (SELECT id FROM tasks WHERE task_id = $3)
+                  AND config_id != $4
+                  AND retire_at IS NULL",
             )
             .await?;
-        let collector_auth_token_rows = self.query(&stmt, &[]);
+        Ok(self
+            .execute(
+                &stmt,
+                &[
+                    /* retire_at */ &retire_at.as_naive_date_time()?,
+                    /* updated_by */ &self.name,
+                    /* task_id */ &task_id.as_ref(),
+                    /* keep */ &(u8::from(*keep) as i16),
+                ],
+            )
+            .await?)
+    }
+
+    /// Deletes the HPKE keypairs of the given task which have been retired. Returns the number of
+    /// keypairs deleted.
+    #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
+    pub async fn delete_retired_task_hpke_keypairs(&self, task_id: &TaskId) -> Result<u64, Error> {
+        let stmt = self
+            .prepare_cached(
+                "DELETE FROM task_hpke_keys
+                WHERE task_id = (SELECT id FROM tasks WHERE task_id = $1)
+                  AND retire_at <= $2",
+            )
+            .await?;
+        Ok(self
+            .execute(
+                &stmt,
+                &[
+                    /* task_id */ &task_id.as_ref(),
+                    /* now */ &self.clock.now().as_naive_date_time()?,
+                ],
+            )
+            .await?)
+    }
+
+    #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
+    pub async fn get_taskprov_peer_aggregators(&self) -> Result<Vec<PeerAggregator>, Error> {
+        let stmt = self
+            .prepare_cached(
+                "SELECT id, endpoint, role, verify_key_init, collector_hpke_config,
+                        report_expiry_age, tolerable_clock_skew
+                    FROM taskprov_peer_aggregators",
+            )
+            .await?;
+        let peer_aggregator_rows = self.query(&stmt, &[]);
+
+        let stmt = self
+            .prepare_cached(
+                "SELECT (SELECT p.id FROM taskprov_peer_aggregators AS p
+                    WHERE p.id = a.peer_aggregator_id) AS peer_id,
+                ord, type, token FROM taskprov_aggregator_auth_tokens AS a
+                    ORDER BY ord ASC",
+            )
+            .await?;
+        let aggregator_auth_token_rows = self.query(&stmt, &[]);
+
+        let stmt = self
+            .prepare_cached(
+                "SELECT (SELECT p.id FROM taskprov_peer_aggregators AS p
+                    WHERE p.id = a.peer_aggregator_id) AS peer_id,
+                ord, type, token FROM taskprov_collector_auth_tokens AS a
+                    ORDER BY ord ASC",
+            )
+            .await?;
+        let collector_auth_token_rows = self.query(&stmt, &[]);
 
         let (peer_aggregator_rows, aggregator_auth_token_rows, collector_auth_token_rows) = try_join!(
             peer_aggregator_rows,
@@ -5005,6 +6399,839 @@ impl<C: Clock> Transaction<'_, C> {
             .await?,
         )
     }
+
+    /// Attempts to acquire or renew the leader lease with the given name on behalf of `holder`,
+    /// for the given duration. The lease is granted if it is unheld, expired, or already held by
+    /// `holder`. Returns the state of the lease after the attempt, so the caller can determine
+    /// whether it holds the lease and, if not, which replica does.
+    #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
+    pub async fn try_acquire_leader_lease(
+        &self,
+        name: &str,
+        holder: &str,
+        lease_duration: &StdDuration,
+    ) -> Result<LeaderLease, Error> {
+        let now = self.clock.now().as_naive_date_time()?;
+        let lease_expiry_time = add_naive_date_time_duration(&now, lease_duration)?;
+
+        let stmt = self
+            .prepare_cached(
+                "INSERT INTO leader_leases
+                    (name, holder, lease_expiry, acquired_at, created_at, updated_at, updated_by)
+                VALUES ($1, $2, $3, $4, $4, $4, $5)
+                ON CONFLICT (name) DO UPDATE SET
+                    holder = excluded.holder,
+                    lease_expiry = excluded.lease_expiry,
+                    acquired_at = CASE
+                        WHEN leader_leases.holder = excluded.holder
+                            AND leader_leases.lease_expiry > excluded.updated_at
+                        THEN leader_leases.acquired_at
+                        ELSE excluded.acquired_at
+                    END,
+                    updated_at = excluded.updated_at,
+                    updated_by = excluded.updated_by
+                WHERE leader_leases.holder = excluded.holder
+                   OR leader_leases.lease_expiry <= excluded.updated_at",
+            )
+            .await?;
+        self.execute(
+            &stmt,
+            &[
+                /* name */ &name,
+                /* holder */ &holder,
+                /* lease_expiry */ &lease_expiry_time,
+                /* now */ &now,
+                /* updated_by */ &self.name,
+            ],
+        )
+        .await?;
+
+        // Unwrap safety: the row was either inserted or already existed.
+        Ok(self.get_leader_lease(name).await?.unwrap())
+    }
+
+    /// Releases the leader lease with the given name, if it is held by `holder`, allowing another
+    /// replica to acquire it immediately rather than waiting for it to expire.
+    #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
+    pub async fn release_leader_lease(&self, name: &str, holder: &str) -> Result<(), Error> {
+        let stmt = self
+            .prepare_cached(
+                "UPDATE leader_leases SET lease_expiry = $1, updated_at = $1, updated_by = $2
+                WHERE name = $3 AND holder = $4",
+            )
+            .await?;
+        check_single_row_mutation(
+            self.execute(
+                &stmt,
+                &[
+                    /* now */ &self.clock.now().as_naive_date_time()?,
+                    /* updated_by */ &self.name,
+                    /* name */ &name,
+                    /* holder */ &holder,
+                ],
+            )
+            .await?,
+        )
+    }
+
+    /// Retrieves the state of the leader lease with the given name, or `None` if no replica has
+    /// ever attempted to acquire it.
+    #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
+    pub async fn get_leader_lease(&self, name: &str) -> Result<Option<LeaderLease>, Error> {
+        let stmt = self
+            .prepare_cached(
+                "SELECT holder, lease_expiry, acquired_at FROM leader_leases WHERE name = $1",
+            )
+            .await?;
+        self.query_opt(&stmt, &[&name])
+            .await?
+            .map(|row| {
+                Ok(LeaderLease::new(
+                    name.to_string(),
+                    row.get("holder"),
+                    row.get("lease_expiry"),
+                    row.get("acquired_at"),
+                ))
+            })
+            .transpose()
+    }
+
+    /// Retrieves the active deployment, or `None` if no deployment has been promoted. The active
+    /// deployment is locked against promotion until the transaction completes, so work guarded by
+    /// this check can't commit after another deployment is promoted.
+    #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
+    pub async fn get_active_deployment(&self) -> Result<Option<ActiveDeployment>, Error> {
+        let stmt = self
+            .prepare_cached(
+                "SELECT deployment, epoch, promoted_at FROM active_deployment FOR SHARE",
+            )
+            .await?;
+        self.query_opt(&stmt, &[])
+            .await?
+            .map(|row| {
+                Ok(ActiveDeployment::new(
+                    row.get("deployment"),
+                    row.get_bigint_and_convert("epoch")?,
+                    row.get("promoted_at"),
+                ))
+            })
+            .transpose()
+    }
+
+    /// Makes the named deployment the active deployment, incrementing the epoch. Outstanding job
+    /// and leader leases are expired, so that the newly active deployment can acquire them
+    /// immediately. Expired job leases have their lease tokens cleared, so that any work still in
+    /// flight under them, e.g. in the previously active deployment, fails to commit.
+    #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
+    pub async fn promote_deployment(&self, deployment: &str) -> Result<ActiveDeployment, Error> {
+        let now = self.clock.now().as_naive_date_time()?;
+
+        let stmt = self
+            .prepare_cached(
+                "INSERT INTO active_deployment
+                    (deployment, epoch, promoted_at, created_at, updated_at, updated_by)
+                VALUES ($1, 1, $2, $2, $2, $3)
+                ON CONFLICT (id) DO UPDATE SET
+                    deployment = excluded.deployment,
+                    epoch = active_deployment.epoch + 1,
+                    promoted_at = excluded.promoted_at,
+                    updated_at = excluded.updated_at,
+                    updated_by = excluded.updated_by
+                RETURNING deployment, epoch, promoted_at",
+            )
+            .await?;
+        let row = self
+            .query_one(
+                &stmt,
+                &[
+                    /* deployment */ &deployment,
+                    /* now */ &now,
+                    /* updated_by */ &self.name,
+                ],
+            )
+            .await?;
+        let active_deployment = ActiveDeployment::new(
+            row.get("deployment"),
+            row.get_bigint_and_convert("epoch")?,
+            row.get("promoted_at"),
+        );
+
+        let (aggregation_jobs_stmt, collection_jobs_stmt, leader_leases_stmt) = try_join!(
+            self.prepare_cached(
+                "UPDATE aggregation_jobs SET
+                    lease_expiry = '-infinity'::TIMESTAMP,
+                    lease_token = NULL,
+                    updated_at = $1,
+                    updated_by = $2
+                WHERE lease_token IS NOT NULL AND lease_expiry > $1",
+            ),
+            self.prepare_cached(
+                "UPDATE collection_jobs SET
+                    lease_expiry = '-infinity'::TIMESTAMP,
+                    lease_token = NULL,
+                    updated_at = $1,
+                    updated_by = $2
+                WHERE lease_token IS NOT NULL AND lease_expiry > $1",
+            ),
+            self.prepare_cached(
+                "UPDATE leader_leases SET lease_expiry = $1, updated_at = $1, updated_by = $2
+                WHERE lease_expiry > $1",
+            ),
+        )?;
+        let params: &[&(dyn ToSql + Sync)] =
+            &[/* now */ &now, /* updated_by */ &self.name];
+        try_join!(
+            self.execute(&aggregation_jobs_stmt, params),
+            self.execute(&collection_jobs_stmt, params),
+            self.execute(&leader_leases_stmt, params),
+        )?;
+
+        Ok(active_deployment)
+    }
+
+    /// Writes a new admin API key. Returns [`Error::MutationTargetAlreadyExists`] if a key with the
+    /// same name or token already exists.
+    #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
+    pub async fn put_admin_api_key(&self, admin_api_key: &AdminApiKey) -> Result<(), Error> {
+        let now = self.clock.now().as_naive_date_time()?;
+        let stmt = self
+            .prepare_cached(
+                "INSERT INTO admin_api_keys
+                    (name, token_hash, task_ids, expiry, created_at, updated_at, updated_by)
+                VALUES ($1, $2, $3, $4, $5, $5, $6)
+                ON CONFLICT DO NOTHING",
+            )
+            .await?;
+        check_insert(
+            self.execute(
+                &stmt,
+                &[
+                    /* name */ &admin_api_key.name(),
+                    /* token_hash */ &admin_api_key.token_hash().as_ref(),
+                    /* task_ids */
+                    &admin_api_key
+                        .task_ids()
+                        .iter()
+                        .map(TaskId::as_ref)
+                        .collect::<Vec<_>>(),
+                    /* expiry */ &admin_api_key.expiry().as_naive_date_time()?,
+                    /* now */ &now,
+                    /* updated_by */ &self.name,
+                ],
+            )
+            .await?,
+        )
+    }
+
+    /// Retrieves all admin API keys, including expired keys, ordered by name.
+    #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
+    pub async fn get_admin_api_keys(&self) -> Result<Vec<AdminApiKey>, Error> {
+        let stmt = self
+            .prepare_cached(
+                "SELECT name, token_hash, task_ids, expiry FROM admin_api_keys ORDER BY name",
+            )
+            .await?;
+        self.query(&stmt, &[])
+            .await?
+            .iter()
+            .map(Self::admin_api_key_from_row)
+            .collect()
+    }
+
+    /// Retrieves the unexpired admin API key whose bearer token has the given hash, if any.
+    #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
+    pub async fn get_unexpired_admin_api_key(
+        &self,
+        token_hash: &AuthenticationTokenHash,
+    ) -> Result<Option<AdminApiKey>, Error> {
+        let stmt = self
+            .prepare_cached(
+                "SELECT name, token_hash, task_ids, expiry FROM admin_api_keys
+                WHERE token_hash = $1 AND expiry > $2",
+            )
+            .await?;
+        self.query_opt(
+            &stmt,
+            &[
+                /* token_hash */ &token_hash.as_ref(),
+                /* now */ &self.clock.now().as_naive_date_time()?,
+            ],
+        )
+        .await?
+        .as_ref()
+        .map(Self::admin_api_key_from_row)
+        .transpose()
+    }
+
+    fn admin_api_key_from_row(row: &Row) -> Result<AdminApiKey, Error> {
+        Ok(AdminApiKey::new(
+            row.get("name"),
+            AuthenticationTokenType::AuthorizationBearerToken
+                .as_authentication_token_hash(row.get("token_hash"))?,
+            row.get::<_, Vec<Vec<u8>>>("task_ids")
+                .iter()
+                .map(|task_id| TaskId::get_decoded(task_id))
+                .collect::<Result<_, _>>()?,
+            Time::from_naive_date_time(&row.get("expiry")),
+        ))
+    }
+
+    /// Deletes the named admin API key. Returns [`Error::MutationTargetNotFound`] if there is no
+    /// such key.
+    #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
+    pub async fn delete_admin_api_key(&self, name: &str) -> Result<(), Error> {
+        let stmt = self
+            .prepare_cached("DELETE FROM admin_api_keys WHERE name = $1")
+            .await?;
+        check_single_row_mutation(self.execute(&stmt, &[/* name */ &name]).await?)
+    }
+
+    /// Writes metadata for a sampled upload. The sample expires after `ttl`.
+    #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
+    pub async fn put_upload_sample(
+        &self,
+        sample: &UploadSample,
+        ttl: &Duration,
+    ) -> Result<(), Error> {
+        let now = self.clock.now();
+        let expiry = now.add(ttl)?;
+        let extension_types: Vec<i32> = sample
+            .extension_types()
+            .iter()
+            .copied()
+            .map(i32::from)
+            .collect();
+
+        let stmt = self
+            .prepare_cached(
+                "INSERT INTO upload_samples
+                    (task_id, report_id, client_timestamp, received_at, extension_types,
+                    public_share_size, leader_encrypted_input_share_size,
+                    helper_encrypted_input_share_size, expiry, created_at, updated_by)
+                VALUES (
+                    (SELECT id FROM tasks WHERE task_id = $1),
+                    $2, $3, $4, $5, $6, $7, $8, $9, $10, $11
+                )",
+            )
+            .await?;
+        check_insert(
+            self.execute(
+                &stmt,
+                &[
+                    /* task_id */ &sample.task_id().as_ref(),
+                    /* report_id */ &sample.report_id().as_ref(),
+                    /* client_timestamp */
+                    &sample.client_timestamp().as_naive_date_time()?,
+                    /* received_at */ &sample.received_at().as_naive_date_time()?,
+                    /* extension_types */ &extension_types,
+                    /* public_share_size */ &i64::try_from(sample.public_share_size())?,
+                    /* leader_encrypted_input_share_size */
+                    &i64::try_from(sample.leader_encrypted_input_share_size())?,
+                    /* helper_encrypted_input_share_size */
+                    &i64::try_from(sample.helper_encrypted_input_share_size())?,
+                    /* expiry */ &expiry.as_naive_date_time()?,
+                    /* created_at */ &now.as_naive_date_time()?,
+                    /* updated_by */ &self.name,
+                ],
+            )
+            .await?,
+        )
+    }
+
+    /// Retrieves the unexpired upload samples for the given task, most recently received first.
+    #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
+    pub async fn get_upload_samples_for_task(
+        &self,
+        task_id: &TaskId,
+    ) -> Result<Vec<UploadSample>, Error> {
+        let stmt = self
+            .prepare_cached(
+                "SELECT report_id, client_timestamp, received_at, extension_types,
+                    public_share_size, leader_encrypted_input_share_size,
+                    helper_encrypted_input_share_size
+                FROM upload_samples
+                JOIN tasks ON tasks.id = upload_samples.task_id
+                WHERE tasks.task_id = $1 AND upload_samples.expiry > $2
+                ORDER BY received_at DESC, upload_samples.id DESC",
+            )
+            .await?;
+        self.query(
+            &stmt,
+            &[
+                /* task_id */ &task_id.as_ref(),
+                /* now */ &self.clock.now().as_naive_date_time()?,
+            ],
+        )
+        .await?
+        .into_iter()
+        .map(|row| {
+            Ok(UploadSample::new(
+                *task_id,
+                row.get_bytea_and_convert::<ReportId>("report_id")?,
+                Time::from_naive_date_time(&row.get("client_timestamp")),
+                Time::from_naive_date_time(&row.get("received_at")),
+                row.get::<_, Vec<i32>>("extension_types")
+                    .into_iter()
+                    .map(u16::try_from)
+                    .collect::<Result<_, _>>()?,
+                row.get_bigint_and_convert("public_share_size")?,
+                row.get_bigint_and_convert("leader_encrypted_input_share_size")?,
+                row.get_bigint_and_convert("helper_encrypted_input_share_size")?,
+            ))
+        })
+        .collect()
+    }
+
+    /// Deletes expired upload samples for the given task. Up to `limit` upload samples will be
+    /// deleted. Returns the number of upload samples deleted.
+    #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
+    pub async fn delete_expired_upload_samples(
+        &self,
+        task_id: &TaskId,
+        limit: u64,
+    ) -> Result<u64, Error> {
+        let stmt = self
+            .prepare_cached(
+                "WITH upload_samples_to_delete AS (
+                    SELECT upload_samples.id FROM upload_samples
+                    JOIN tasks ON tasks.id = upload_samples.task_id
+                    WHERE tasks.task_id = $1 AND upload_samples.expiry <= $2
+                    LIMIT $3
+                )
+                DELETE FROM upload_samples
+                USING upload_samples_to_delete
+                WHERE upload_samples.id = upload_samples_to_delete.id",
+            )
+            .await?;
+        self.execute(
+            &stmt,
+            &[
+                /* task_id */ &task_id.as_ref(),
+                /* now */ &self.clock.now().as_naive_date_time()?,
+                /* limit */ &i64::try_from(limit)?,
+            ],
+        )
+        .await
+        .map_err(Into::into)
+    }
+
+    /// Retrieves all feature flags, both global and per-task.
+    #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
+    pub async fn get_feature_flags(&self) -> Result<Vec<FeatureFlag>, Error> {
+        let stmt = self
+            .prepare_cached(
+                "SELECT feature_flags.name, tasks.task_id, feature_flags.enabled
+                FROM feature_flags
+                LEFT JOIN tasks ON tasks.id = feature_flags.task_id
+                ORDER BY feature_flags.name, tasks.task_id NULLS FIRST",
+            )
+            .await?;
+        self.query(&stmt, &[])
+            .await?
+            .into_iter()
+            .map(|row| {
+                Ok(FeatureFlag::new(
+                    row.get("name"),
+                    row.get::<_, Option<Vec<u8>>>("task_id")
+                        .map(|task_id| TaskId::get_decoded(&task_id))
+                        .transpose()?,
+                    row.get("enabled"),
+                ))
+            })
+            .collect()
+    }
+
+    /// Sets the state of a feature flag, creating it if it does not already exist. Returns
+    /// [`Error::MutationTargetNotFound`] if the flag applies to a task that does not exist.
+    #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
+    pub async fn put_feature_flag(&self, feature_flag: &FeatureFlag) -> Result<(), Error> {
+        let now = self.clock.now().as_naive_date_time()?;
+        match feature_flag.task_id() {
+            Some(task_id) => {
+                let stmt = self
+                    .prepare_cached(
+                        "INSERT INTO feature_flags
+                            (name, task_id, enabled, created_at, updated_at, updated_by)
+                        SELECT $1, tasks.id, $3, $4, $4, $5 FROM tasks WHERE tasks.task_id = $2
+                        ON CONFLICT (task_id, name) WHERE task_id IS NOT NULL DO UPDATE SET
+                            enabled = excluded.enabled,
+                            updated_at = excluded.updated_at,
+                            updated_by = excluded.updated_by",
+                    )
+                    .await?;
+                check_single_row_mutation(
+                    self.execute(
+                        &stmt,
+                        &[
+                            /* name */ &feature_flag.name(),
+                            /* task_id */ &task_id.as_ref(),
+                            /* enabled */ &feature_flag.enabled(),
+                            /* now */ &now,
+                            /* updated_by */ &self.name,
+                        ],
+                    )
+                    .await?,
+                )
+            }
+            None => {
+                let stmt = self
+                    .prepare_cached(
+                        "INSERT INTO feature_flags
+                            (name, task_id, enabled, created_at, updated_at, updated_by)
+                        VALUES ($1, NULL, $2, $3, $3, $4)
+                        ON CONFLICT (name) WHERE task_id IS NULL DO UPDATE SET
+                            enabled = excluded.enabled,
+                            updated_at = excluded.updated_at,
+                            updated_by = excluded.updated_by",
+                    )
+                    .await?;
+                check_single_row_mutation(
+                    self.execute(
+                        &stmt,
+                        &[
+                            /* name */ &feature_flag.name(),
+                            /* enabled */ &feature_flag.enabled(),
+                            /* now */ &now,
+                            /* updated_by */ &self.name,
+                        ],
+                    )
+                    .await?,
+                )
+            }
+        }
+    }
+
+    /// Deletes a feature flag. If `task_id` is `None`, the global flag with the given name is
+    /// deleted, and flags for individual tasks are left alone.
+    #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
+    pub async fn delete_feature_flag(
+        &self,
+        name: &str,
+        task_id: Option<&TaskId>,
+    ) -> Result<(), Error> {
+        match task_id {
+            Some(task_id) => {
+                let stmt = self
+                    .prepare_cached(
+                        "DELETE FROM feature_flags
+                        USING tasks
+                        WHERE tasks.id = feature_flags.task_id
+                            AND feature_flags.name = $1 AND tasks.task_id = $2",
+                    )
+                    .await?;
+                check_single_row_mutation(
+                    self.execute(&stmt, &[/* name */ &name, /* task_id */ &task_id.as_ref()])
+                        .await?,
+                )
+            }
+            None => {
+                let stmt = self
+                    .prepare_cached("DELETE FROM feature_flags WHERE name = $1 AND task_id IS NULL")
+                    .await?;
+                check_single_row_mutation(self.execute(&stmt, &[/* name */ &name]).await?)
+            }
+        }
+    }
+
+    /// Snapshots the counters of every task as of `snapshot_time`, which should be the start of an
+    /// hour. Counters for aggregated and collected reports are accumulated from the task's previous
+    /// snapshot, counting report aggregations and collections which finished since then, so they
+    /// are not affected by garbage collection of those rows once they have been snapshotted. Tasks
+    /// which already have a snapshot at `snapshot_time` are skipped. Returns the number of
+    /// snapshots written.
+    #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
+    pub async fn put_task_metrics_snapshots(&self, snapshot_time: &Time) -> Result<u64, Error> {
+        let stmt = self
+            .prepare_cached(
+                "INSERT INTO task_metrics_snapshots
+                    (task_id, snapshot_time, reports_accepted, reports_aggregated,
+                    reports_collected, created_at, updated_by)
+                SELECT
+                    tasks.task_id,
+                    $1,
+                    COALESCE((
+                        SELECT SUM(report_success) FROM task_upload_counters
+                        WHERE task_upload_counters.task_id = tasks.id
+                    ), 0)::BIGINT,
+                    COALESCE(previous.reports_aggregated, 0) + (
+                        SELECT COUNT(*) FROM report_aggregations
+                        WHERE report_aggregations.task_id = tasks.id
+                          AND report_aggregations.state = 'FINISHED'
+                          AND report_aggregations.updated_at >= previous_snapshot_time.value
+                          AND report_aggregations.updated_at < $1
+                    ),
+                    COALESCE(previous.reports_collected, 0) + COALESCE((
+                        SELECT SUM(report_count) FROM collection_jobs
+                        WHERE collection_jobs.task_id = tasks.id
+                          AND collection_jobs.state = 'FINISHED'
+                          AND collection_jobs.updated_at >= previous_snapshot_time.value
+                          AND collection_jobs.updated_at < $1
+                    ), 0)::BIGINT + COALESCE((
+                        SELECT SUM(report_count) FROM aggregate_share_jobs
+                        WHERE aggregate_share_jobs.task_id = tasks.id
+                          AND aggregate_share_jobs.created_at >= previous_snapshot_time.value
+                          AND aggregate_share_jobs.created_at < $1
+                    ), 0)::BIGINT,
+                    $2,
+                    $3
+                FROM tasks
+                LEFT JOIN LATERAL (
+                    SELECT snapshot_time, reports_aggregated, reports_collected
+                    FROM task_metrics_snapshots
+                    WHERE task_metrics_snapshots.task_id = tasks.task_id
+                      AND task_metrics_snapshots.snapshot_time < $1
+                    ORDER BY task_metrics_snapshots.snapshot_time DESC
+                    LIMIT 1
+                ) AS previous ON TRUE
+                CROSS JOIN LATERAL (
+                    SELECT COALESCE(previous.snapshot_time, '-infinity'::TIMESTAMP) AS value
+                ) AS previous_snapshot_time
+                ON CONFLICT (task_id, snapshot_time) DO NOTHING",
+            )
+            .await?;
+        self.execute(
+            &stmt,
+            &[
+                /* snapshot_time */ &snapshot_time.as_naive_date_time()?,
+                /* created_at */ &self.clock.now().as_naive_date_time()?,
+                /* updated_by */ &self.name,
+            ],
+        )
+        .await
+        .map_err(Into::into)
+    }
+
+    /// Retrieves the metrics snapshots of the given task taken at or after `since`, oldest first.
+    /// Snapshots are retained after their task is deleted.
+    #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
+    pub async fn get_task_metrics_snapshots(
+        &self,
+        task_id: &TaskId,
+        since: &Time,
+    ) -> Result<Vec<TaskMetricsSnapshot>, Error> {
+        let stmt = self
+            .prepare_cached(
+                "SELECT snapshot_time, reports_accepted, reports_aggregated, reports_collected
+                FROM task_metrics_snapshots
+                WHERE task_id = $1 AND snapshot_time >= $2
+                ORDER BY snapshot_time",
+            )
+            .await?;
+        self.query(
+            &stmt,
+            &[
+                /* task_id */ &task_id.as_ref(),
+                /* since */ &since.as_naive_date_time()?,
+            ],
+        )
+        .await?
+        .into_iter()
+        .map(|row| {
+            Ok(TaskMetricsSnapshot::new(
+                *task_id,
+                Time::from_naive_date_time(&row.get("snapshot_time")),
+                row.get_bigint_and_convert("reports_accepted")?,
+                row.get_bigint_and_convert("reports_aggregated")?,
+                row.get_bigint_and_convert("reports_collected")?,
+            ))
+        })
+        .collect()
+    }
+
+    /// Deletes metrics snapshots, of any task, taken before `cutoff`. Up to `limit` snapshots will
+    /// be deleted. Returns the number of snapshots deleted.
+    #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
+    pub async fn delete_task_metrics_snapshots_before(
+        &self,
+        cutoff: &Time,
+        limit: u64,
+    ) -> Result<u64, Error> {
+        let stmt = self
+            .prepare_cached(
+                "WITH task_metrics_snapshots_to_delete AS (
+                    SELECT id FROM task_metrics_snapshots
+                    WHERE snapshot_time < $1
+                    LIMIT $2
+                )
+                DELETE FROM task_metrics_snapshots
+                USING task_metrics_snapshots_to_delete
+                WHERE task_metrics_snapshots.id = task_metrics_snapshots_to_delete.id",
+            )
+            .await?;
+        self.execute(
+            &stmt,
+            &[
+                /* cutoff */ &cutoff.as_naive_date_time()?,
+                /* limit */ &i64::try_from(limit)?,
+            ],
+        )
+        .await
+        .map_err(Into::into)
+    }
+
+    /// Records the result of probing the given task's peer aggregator at `probed_at`. `result` is
+    /// the probe's latency in milliseconds if it succeeded, or a description of why it failed.
+    #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
+    pub async fn put_task_peer_probe_result(
+        &self,
+        task_id: &TaskId,
+        probed_at: &Time,
+        result: Result<u64, &str>,
+    ) -> Result<(), Error> {
+        let (latency_ms, error) = match result {
+            Ok(latency_ms) => (Some(i64::try_from(latency_ms)?), None),
+            Err(error) => (None, Some(error)),
+        };
+        let now = self.clock.now().as_naive_date_time()?;
+
+        let stmt = self
+            .prepare_cached(
+                "INSERT INTO task_peer_health
+                    (task_id, last_probed_at, latency_ms, error, last_reachable_at,
+                    consecutive_failures, created_at, updated_at, updated_by)
+                SELECT id, $2::TIMESTAMP, $3::BIGINT, $4::TEXT,
+                    CASE WHEN $4 IS NULL THEN $2::TIMESTAMP END,
+                    CASE WHEN $4 IS NULL THEN 0 ELSE 1 END, $5::TIMESTAMP, $5::TIMESTAMP,
+                    $6::TEXT
+                FROM tasks WHERE task_id = $1
+                ON CONFLICT (task_id) DO UPDATE SET
+                    last_probed_at = excluded.last_probed_at,
+                    latency_ms = excluded.latency_ms,
+                    error = excluded.error,
+                    last_reachable_at = COALESCE(
+                        excluded.last_reachable_at, task_peer_health.last_reachable_at),
+                    consecutive_failures = CASE WHEN excluded.error IS NULL THEN 0
+                        ELSE task_peer_health.consecutive_failures + 1 END,
+                    updated_at = excluded.updated_at,
+                    updated_by = excluded.updated_by",
+            )
+            .await?;
+        check_single_row_mutation(
+            self.execute(
+                &stmt,
+                &[
+                    /* task_id */ &task_id.as_ref(),
+                    /* last_probed_at */ &probed_at.as_naive_date_time()?,
+                    /* latency_ms */ &latency_ms,
+                    /* error */ &error,
+                    /* now */ &now,
+                    /* updated_by */ &self.name,
+                ],
+            )
+            .await?,
+        )
+    }
+
+    /// Retrieves the result of the most recent probe of the given task's peer aggregator, or
+    /// `None` if it has not been probed.
+    #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
+    pub async fn get_task_peer_health(
+        &self,
+        task_id: &TaskId,
+    ) -> Result<Option<TaskPeerHealth>, Error> {
+        let stmt = self
+            .prepare_cached(
+                "SELECT last_probed_at, latency_ms, error, last_reachable_at, consecutive_failures
+                FROM task_peer_health
+                JOIN tasks ON tasks.id = task_peer_health.task_id
+                WHERE tasks.task_id = $1",
+            )
+            .await?;
+        self.query_opt(&stmt, &[/* task_id */ &task_id.as_ref()])
+            .await?
+            .map(|row| {
+                Ok(TaskPeerHealth::new(
+                    *task_id,
+                    Time::from_naive_date_time(&row.get("last_probed_at")),
+                    row.get::<_, Option<i64>>("latency_ms")
+                        .map(u64::try_from)
+                        .transpose()?,
+                    row.get("error"),
+                    row.get::<_, Option<NaiveDateTime>>("last_reachable_at")
+                        .as_ref()
+                        .map(Time::from_naive_date_time),
+                    row.get_bigint_and_convert("consecutive_failures")?,
+                ))
+            })
+            .transpose()
+    }
+
+    /// Writes the aggregation job creator's scheduling state for a task, replacing any existing
+    /// state for the task.
+    #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
+    pub async fn put_aggregation_job_creator_task_state(
+        &self,
+        state: &AggregationJobCreatorTaskState,
+    ) -> Result<(), Error> {
+        let now = self.clock.now().as_naive_date_time()?;
+
+        let stmt = self
+            .prepare_cached(
+                "INSERT INTO aggregation_job_creator_task_state
+                    (task_id, discovered_at, last_run_at, next_run_at, last_error, created_at,
+                    updated_at, updated_by)
+                SELECT id, $2, $3, $4, $5, $6, $6, $7
+                FROM tasks WHERE task_id = $1
+                ON CONFLICT (task_id) DO UPDATE SET
+                    discovered_at = excluded.discovered_at,
+                    last_run_at = excluded.last_run_at,
+                    next_run_at = excluded.next_run_at,
+                    last_error = excluded.last_error,
+                    updated_at = excluded.updated_at,
+                    updated_by = excluded.updated_by",
+            )
+            .await?;
+        check_single_row_mutation(
+            self.execute(
+                &stmt,
+                &[
+                    /* task_id */ &state.task_id().as_ref(),
+                    /* discovered_at */ &state.discovered_at().as_naive_date_time()?,
+                    /* last_run_at */
+                    &state
+                        .last_run_at()
+                        .map(Time::as_naive_date_time)
+                        .transpose()?,
+                    /* next_run_at */ &state.next_run_at().as_naive_date_time()?,
+                    /* last_error */ &state.last_error(),
+                    /* now */ &now,
+                    /* updated_by */ &self.name,
+                ],
+            )
+            .await?,
+        )
+    }
+
+    /// Retrieves the aggregation job creator's scheduling state for the given task, or `None` if
+    /// the aggregation job creator has not started working on the task.
+    #[tracing::instrument(skip(self), err(level = Level::DEBUG))]
+    pub async fn get_aggregation_job_creator_task_state(
+        &self,
+        task_id: &TaskId,
+    ) -> Result<Option<AggregationJobCreatorTaskState>, Error> {
+        let stmt = self
+            .prepare_cached(
+                "SELECT discovered_at, last_run_at, next_run_at, last_error
+                FROM aggregation_job_creator_task_state
+                JOIN tasks ON tasks.id = aggregation_job_creator_task_state.task_id
+                WHERE tasks.task_id = $1",
+            )
+            .await?;
+        Ok(self
+            .query_opt(&stmt, &[/* task_id */ &task_id.as_ref()])
+            .await?
+            .map(|row| {
+                AggregationJobCreatorTaskState::new(
+                    *task_id,
+                    Time::from_naive_date_time(&row.get("discovered_at")),
+                    row.get::<_, Option<NaiveDateTime>>("last_run_at")
+                        .as_ref()
+                        .map(Time::from_naive_date_time),
+                    Time::from_naive_date_time(&row.get("next_run_at")),
+                    row.get("last_error"),
+                )
+            }))
+    }
 }
 
 fn check_insert(row_count: u64) -> Result<(), Error> {
@@ -5130,8 +7357,12 @@ impl RowExt for Row {
 /// A Crypter allows a Datastore to encrypt/decrypt sensitive values stored to the datastore. Values
 /// are cryptographically bound to the specific location in the datastore in which they are stored.
 /// Rollback protection is not provided.
+///
+/// Clones of a Crypter share the same set of keys, so that the keys of a Crypter owned by a
+/// [`Datastore`] may be replaced via [`Crypter::replace_keys`] while the datastore is in use.
+#[derive(Clone)]
 pub struct Crypter {
-    keys: Vec<LessSafeKey>,
+    keys: Arc<RwLock<Vec<LessSafeKey>>>,
 }
 
 impl Crypter {
@@ -5145,11 +7376,24 @@ impl Crypter {
     ///
     /// The keys must be for the AES-128-GCM algorithm.
     pub fn new(keys: Vec<LessSafeKey>) -> Self {
+        Self::check_keys(&keys);
+        Self {
+            keys: Arc::new(RwLock::new(keys)),
+        }
+    }
+
+    /// Replaces the set of keys used by this Crypter, and by all of its clones. The new keys are
+    /// subject to the same requirements as those provided to [`Crypter::new`].
+    pub fn replace_keys(&self, keys: Vec<LessSafeKey>) {
+        Self::check_keys(&keys);
+        *self.keys.write().unwrap() = keys;
+    }
+
+    fn check_keys(keys: &[LessSafeKey]) {
         assert!(!keys.is_empty());
-        for key in &keys {
+        for key in keys {
             assert_eq!(key.algorithm(), &AES_128_GCM);
         }
-        Self { keys }
     }
 
     fn encrypt(
@@ -5161,7 +7405,13 @@ impl Crypter {
     ) -> Result<Vec<u8>, Error> {
         // It is safe to unwrap the key because we have already validated that keys is nonempty
         // in Crypter::new.
-        Self::encrypt_with_key(self.keys.first().unwrap(), table, row, column, value)
+        Self::encrypt_with_key(
+            self.keys.read().unwrap().first().unwrap(),
+            table,
+            row,
+            column,
+            value,
+        )
     }
 
     fn encrypt_with_key(
@@ -5199,7 +7449,7 @@ impl Crypter {
         let nonce_bytes: [u8; aead::NONCE_LEN] = nonce_bytes.try_into().unwrap();
         let aad_bytes = Self::aad_bytes_for(table, row, column)?;
 
-        for key in &self.keys {
+        for key in self.keys.read().unwrap().iter() {
             let mut ciphertext_and_tag = ciphertext_and_tag.to_vec();
             if let Ok(plaintext) = key.open_in_place(
                 aead::Nonce::assume_unique_for_key(nonce_bytes),
@@ -5258,6 +7508,8 @@ pub enum Error {
     Decode(#[from] CodecError),
     #[error("base64 decoding error: {0}")]
     Base64(#[from] base64::DecodeError),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
     /// An arbitrary error returned from the user callback; unrelated to DB internals. This error
     /// will never be generated by the datastore library itself.
     #[error(transparent)]
@@ -14,8 +14,8 @@ use janus_core::{
 use janus_messages::{
     query_type::{FixedSize, QueryType, TimeInterval},
     AggregationJobId, AggregationJobStep, BatchId, CollectionJobId, Duration, Extension,
-    HpkeCiphertext, Interval, PrepareError, PrepareResp, Query, ReportId, ReportIdChecksum,
-    ReportMetadata, Role, TaskId, Time,
+    HpkeCiphertext, HpkeConfig, Interval, PrepareError, PrepareResp, Query, ReportId,
+    ReportIdChecksum, ReportMetadata, Role, TaskId, Time,
 };
 use postgres_protocol::types::{
     range_from_sql, range_to_sql, timestamp_from_sql, timestamp_to_sql, Range, RangeBound,
@@ -320,6 +320,94 @@ impl LeaderStoredReport<0, prio::vdaf::dummy::Vdaf> {
     }
 }
 
+/// A [`LeaderStoredReport`] encoded for storage, independently of its VDAF, so that reports for
+/// many tasks may be written together by [`Transaction::put_client_reports`].
+///
+/// [`Transaction::put_client_reports`]: crate::datastore::Transaction::put_client_reports
+#[derive(Clone, Derivative, PartialEq, Eq)]
+#[derivative(Debug)]
+pub struct EncodedClientReport {
+    task_id: TaskId,
+    report_id: ReportId,
+    client_timestamp: Time,
+    #[derivative(Debug = "ignore")]
+    extensions: Vec<u8>,
+    #[derivative(Debug = "ignore")]
+    public_share: Vec<u8>,
+    #[derivative(Debug = "ignore")]
+    leader_input_share: Vec<u8>,
+    #[derivative(Debug = "ignore")]
+    helper_encrypted_input_share: Vec<u8>,
+}
+
+impl EncodedClientReport {
+    pub fn new<const SEED_SIZE: usize, A>(
+        report: &LeaderStoredReport<SEED_SIZE, A>,
+    ) -> Result<Self, Error>
+    where
+        A: vdaf::Aggregator<SEED_SIZE, 16>,
+    {
+        let mut extensions = Vec::new();
+        encode_u16_items(&mut extensions, &(), report.leader_extensions())?;
+        Ok(Self {
+            task_id: *report.task_id(),
+            report_id: *report.metadata().id(),
+            client_timestamp: *report.metadata().time(),
+            extensions,
+            public_share: report.public_share().get_encoded()?,
+            leader_input_share: report.leader_input_share().get_encoded()?,
+            helper_encrypted_input_share: report.helper_encrypted_input_share().get_encoded()?,
+        })
+    }
+
+    pub fn task_id(&self) -> &TaskId {
+        &self.task_id
+    }
+
+    pub fn report_id(&self) -> &ReportId {
+        &self.report_id
+    }
+
+    pub fn client_timestamp(&self) -> &Time {
+        &self.client_timestamp
+    }
+
+    pub(crate) fn extensions(&self) -> &[u8] {
+        &self.extensions
+    }
+
+    pub(crate) fn public_share(&self) -> &[u8] {
+        &self.public_share
+    }
+
+    pub(crate) fn leader_input_share(&self) -> &[u8] {
+        &self.leader_input_share
+    }
+
+    pub(crate) fn helper_encrypted_input_share(&self) -> &[u8] {
+        &self.helper_encrypted_input_share
+    }
+}
+
+/// The result of writing a [`LeaderStoredReport`] to the datastore.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ClientReportWriteOutcome {
+    /// The report was new, and has been written.
+    Inserted,
+    /// A report with the same ID and contents was already stored, e.g. because the client retried
+    /// its upload or the upload was handled by another replica. Nothing was written.
+    Duplicate,
+    /// A report with the same ID but different contents was already stored. Nothing was written.
+    Conflict,
+    /// The report's timestamp is older than its task's report expiry age. Nothing was written.
+    /// Only reported by [`Transaction::put_client_reports`](crate::datastore::Transaction::put_client_reports).
+    Expired,
+    /// The report's task does not exist, e.g. because it was deleted after the report was
+    /// validated. Nothing was written. Only reported by
+    /// [`Transaction::put_client_reports`](crate::datastore::Transaction::put_client_reports).
+    TaskNotFound,
+}
+
 /// AggregatorRole corresponds to the `AGGREGATOR_ROLE` enum in the schema.
 #[derive(Clone, Debug, ToSql, FromSql)]
 #[postgres(name = "aggregator_role")]
@@ -1820,8 +1908,9 @@ where
 {
 }
 
-#[derive(Debug, Copy, Clone, FromSql, ToSql)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, FromSql, ToSql, Serialize, Deserialize)]
 #[postgres(name = "collection_job_state")]
+#[serde(rename_all = "snake_case")]
 pub enum CollectionJobStateCode {
     #[postgres(name = "START")]
     Start,
@@ -2230,6 +2319,11 @@ impl TaskUploadCounter {
         }
     }
 
+    /// Returns the number of reports that were successfully uploaded.
+    pub fn report_success(&self) -> u64 {
+        self.report_success
+    }
+
     pub fn increment_interval_collected(&mut self) {
         self.interval_collected += 1
     }
@@ -2262,3 +2356,559 @@ impl TaskUploadCounter {
         self.task_expired += 1
     }
 }
+
+/// The state of a lease electing a single replica to perform some duty, such as garbage collection.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LeaderLease {
+    name: String,
+    holder: String,
+    lease_expiry_time: NaiveDateTime,
+    acquired_at: NaiveDateTime,
+}
+
+impl LeaderLease {
+    /// Creates a new [`LeaderLease`].
+    pub fn new(
+        name: String,
+        holder: String,
+        lease_expiry_time: NaiveDateTime,
+        acquired_at: NaiveDateTime,
+    ) -> Self {
+        Self {
+            name,
+            holder,
+            lease_expiry_time,
+            acquired_at,
+        }
+    }
+
+    /// Returns the name of the duty which this lease grants.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the identity of the replica which most recently held this lease.
+    pub fn holder(&self) -> &str {
+        &self.holder
+    }
+
+    /// Returns the time at which this lease expires.
+    pub fn lease_expiry_time(&self) -> &NaiveDateTime {
+        &self.lease_expiry_time
+    }
+
+    /// Returns the time at which the holder most recently acquired this lease.
+    pub fn acquired_at(&self) -> &NaiveDateTime {
+        &self.acquired_at
+    }
+}
+
+/// The deployment which is active, among an active/standby pair of leader deployments sharing a
+/// datastore.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ActiveDeployment {
+    deployment: String,
+    epoch: u64,
+    promoted_at: NaiveDateTime,
+}
+
+impl ActiveDeployment {
+    /// Creates a new [`ActiveDeployment`].
+    pub fn new(deployment: String, epoch: u64, promoted_at: NaiveDateTime) -> Self {
+        Self {
+            deployment,
+            epoch,
+            promoted_at,
+        }
+    }
+
+    /// Returns the name of the active deployment.
+    pub fn deployment(&self) -> &str {
+        &self.deployment
+    }
+
+    /// Returns the number of times a deployment has been promoted. This increases with every
+    /// promotion, so it may be used to tell which of two observations of the active deployment is
+    /// more recent.
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    /// Returns the time at which the active deployment was most recently promoted.
+    pub fn promoted_at(&self) -> &NaiveDateTime {
+        &self.promoted_at
+    }
+}
+
+/// A credential for the aggregator API which only grants access to a subset of tasks, until it
+/// expires. Only a hash of the key's bearer token is stored.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AdminApiKey {
+    name: String,
+    token_hash: AuthenticationTokenHash,
+    task_ids: Vec<TaskId>,
+    expiry: Time,
+}
+
+impl AdminApiKey {
+    /// Creates a new [`AdminApiKey`].
+    pub fn new(
+        name: String,
+        token_hash: AuthenticationTokenHash,
+        task_ids: Vec<TaskId>,
+        expiry: Time,
+    ) -> Self {
+        Self {
+            name,
+            token_hash,
+            task_ids,
+            expiry,
+        }
+    }
+
+    /// Returns the name identifying the key.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the hash of the key's bearer token.
+    pub fn token_hash(&self) -> &AuthenticationTokenHash {
+        &self.token_hash
+    }
+
+    /// Returns the IDs of the tasks the key grants access to.
+    pub fn task_ids(&self) -> &[TaskId] {
+        &self.task_ids
+    }
+
+    /// Returns the time at which the key stops being accepted.
+    pub fn expiry(&self) -> &Time {
+        &self.expiry
+    }
+}
+
+/// Metadata recorded for a sampled upload, for debugging the behavior of client fleets. This never
+/// includes the contents of the report's shares.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UploadSample {
+    task_id: TaskId,
+    report_id: ReportId,
+    client_timestamp: Time,
+    received_at: Time,
+    extension_types: Vec<u16>,
+    public_share_size: u64,
+    leader_encrypted_input_share_size: u64,
+    helper_encrypted_input_share_size: u64,
+}
+
+impl UploadSample {
+    /// Creates a new [`UploadSample`].
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        task_id: TaskId,
+        report_id: ReportId,
+        client_timestamp: Time,
+        received_at: Time,
+        extension_types: Vec<u16>,
+        public_share_size: u64,
+        leader_encrypted_input_share_size: u64,
+        helper_encrypted_input_share_size: u64,
+    ) -> Self {
+        Self {
+            task_id,
+            report_id,
+            client_timestamp,
+            received_at,
+            extension_types,
+            public_share_size,
+            leader_encrypted_input_share_size,
+            helper_encrypted_input_share_size,
+        }
+    }
+
+    /// Returns the ID of the task the report was uploaded for.
+    pub fn task_id(&self) -> &TaskId {
+        &self.task_id
+    }
+
+    /// Returns the ID of the report.
+    pub fn report_id(&self) -> &ReportId {
+        &self.report_id
+    }
+
+    /// Returns the client timestamp of the report.
+    pub fn client_timestamp(&self) -> &Time {
+        &self.client_timestamp
+    }
+
+    /// Returns the time at which the aggregator received the report.
+    pub fn received_at(&self) -> &Time {
+        &self.received_at
+    }
+
+    /// Returns the number of seconds by which the report's client timestamp precedes the time it
+    /// was received. This is negative if the client timestamp is in the future.
+    pub fn timestamp_skew_seconds(&self) -> i128 {
+        i128::from(self.received_at.as_seconds_since_epoch())
+            - i128::from(self.client_timestamp.as_seconds_since_epoch())
+    }
+
+    /// Returns the codepoints of the extensions in the leader's input share, in order.
+    pub fn extension_types(&self) -> &[u16] {
+        &self.extension_types
+    }
+
+    /// Returns the size of the report's encoded public share, in bytes.
+    pub fn public_share_size(&self) -> u64 {
+        self.public_share_size
+    }
+
+    /// Returns the size of the leader's encrypted input share payload, in bytes.
+    pub fn leader_encrypted_input_share_size(&self) -> u64 {
+        self.leader_encrypted_input_share_size
+    }
+
+    /// Returns the size of the helper's encrypted input share payload, in bytes.
+    pub fn helper_encrypted_input_share_size(&self) -> u64 {
+        self.helper_encrypted_input_share_size
+    }
+}
+
+/// A runtime feature flag, which enables or disables a behavior for a whole deployment, or for a
+/// single task.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FeatureFlag {
+    name: String,
+    task_id: Option<TaskId>,
+    enabled: bool,
+}
+
+impl FeatureFlag {
+    /// Creates a new [`FeatureFlag`]. If `task_id` is `None`, the flag applies to all tasks.
+    pub fn new(name: String, task_id: Option<TaskId>, enabled: bool) -> Self {
+        Self {
+            name,
+            task_id,
+            enabled,
+        }
+    }
+
+    /// Returns the name of the flag.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the ID of the task the flag applies to, or `None` if it applies to all tasks.
+    pub fn task_id(&self) -> Option<&TaskId> {
+        self.task_id.as_ref()
+    }
+
+    /// Returns whether the flag is enabled.
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+}
+
+/// A transition of a task from one lifecycle state to another, recorded for auditing.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TaskLifecycleEvent {
+    task_id: TaskId,
+    from_state: Option<task::TaskState>,
+    to_state: task::TaskState,
+    occurred_at: Time,
+}
+
+impl TaskLifecycleEvent {
+    /// Creates a new [`TaskLifecycleEvent`]. `from_state` is `None` if the task was just created.
+    pub fn new(
+        task_id: TaskId,
+        from_state: Option<task::TaskState>,
+        to_state: task::TaskState,
+        occurred_at: Time,
+    ) -> Self {
+        Self {
+            task_id,
+            from_state,
+            to_state,
+            occurred_at,
+        }
+    }
+
+    /// Returns the ID of the task that changed state.
+    pub fn task_id(&self) -> &TaskId {
+        &self.task_id
+    }
+
+    /// Returns the task's previous state, or `None` if the task was just created.
+    pub fn from_state(&self) -> Option<&task::TaskState> {
+        self.from_state.as_ref()
+    }
+
+    /// Returns the task's new state.
+    pub fn to_state(&self) -> &task::TaskState {
+        &self.to_state
+    }
+
+    /// Returns when the transition happened.
+    pub fn occurred_at(&self) -> &Time {
+        &self.occurred_at
+    }
+}
+
+/// A snapshot of a task's cumulative counters, taken hourly and retained for longer than metrics
+/// backends typically retain data. The volume during a period is the difference between the
+/// counters of the snapshots at its start and end.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaskMetricsSnapshot {
+    task_id: TaskId,
+    snapshot_time: Time,
+    reports_accepted: u64,
+    reports_aggregated: u64,
+    reports_collected: u64,
+}
+
+impl TaskMetricsSnapshot {
+    /// Creates a new [`TaskMetricsSnapshot`].
+    pub fn new(
+        task_id: TaskId,
+        snapshot_time: Time,
+        reports_accepted: u64,
+        reports_aggregated: u64,
+        reports_collected: u64,
+    ) -> Self {
+        Self {
+            task_id,
+            snapshot_time,
+            reports_accepted,
+            reports_aggregated,
+            reports_collected,
+        }
+    }
+
+    /// Returns the ID of the task whose counters were snapshotted.
+    pub fn task_id(&self) -> &TaskId {
+        &self.task_id
+    }
+
+    /// Returns the time as of which the counters were taken.
+    pub fn snapshot_time(&self) -> &Time {
+        &self.snapshot_time
+    }
+
+    /// Returns the number of reports successfully uploaded to the task.
+    pub fn reports_accepted(&self) -> u64 {
+        self.reports_accepted
+    }
+
+    /// Returns the number of the task's report aggregations that finished successfully.
+    pub fn reports_aggregated(&self) -> u64 {
+        self.reports_aggregated
+    }
+
+    /// Returns the number of reports included in the task's finished collection jobs, for the
+    /// leader, or aggregate share jobs, for the helper.
+    pub fn reports_collected(&self) -> u64 {
+        self.reports_collected
+    }
+}
+
+/// The result of the most recent probe of a task's peer aggregator endpoint.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaskPeerHealth {
+    task_id: TaskId,
+    last_probed_at: Time,
+    latency_ms: Option<u64>,
+    error: Option<String>,
+    last_reachable_at: Option<Time>,
+    consecutive_failures: u64,
+}
+
+impl TaskPeerHealth {
+    /// Creates a new [`TaskPeerHealth`].
+    pub fn new(
+        task_id: TaskId,
+        last_probed_at: Time,
+        latency_ms: Option<u64>,
+        error: Option<String>,
+        last_reachable_at: Option<Time>,
+        consecutive_failures: u64,
+    ) -> Self {
+        Self {
+            task_id,
+            last_probed_at,
+            latency_ms,
+            error,
+            last_reachable_at,
+            consecutive_failures,
+        }
+    }
+
+    /// Returns the ID of the task whose peer aggregator was probed.
+    pub fn task_id(&self) -> &TaskId {
+        &self.task_id
+    }
+
+    /// Returns when the peer aggregator was last probed.
+    pub fn last_probed_at(&self) -> &Time {
+        &self.last_probed_at
+    }
+
+    /// Returns whether the last probe succeeded.
+    pub fn reachable(&self) -> bool {
+        self.error.is_none()
+    }
+
+    /// Returns the latency of the last probe in milliseconds, or `None` if it failed.
+    pub fn latency_ms(&self) -> Option<u64> {
+        self.latency_ms
+    }
+
+    /// Returns a description of why the last probe failed, or `None` if it succeeded.
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// Returns when a probe of the peer aggregator last succeeded, or `None` if none has.
+    pub fn last_reachable_at(&self) -> Option<&Time> {
+        self.last_reachable_at.as_ref()
+    }
+
+    /// Returns the number of probes that have failed since the last successful probe.
+    pub fn consecutive_failures(&self) -> u64 {
+        self.consecutive_failures
+    }
+}
+
+/// The aggregation job creator's scheduling state for a leader task.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AggregationJobCreatorTaskState {
+    task_id: TaskId,
+    discovered_at: Time,
+    last_run_at: Option<Time>,
+    next_run_at: Time,
+    last_error: Option<String>,
+}
+
+impl AggregationJobCreatorTaskState {
+    /// Creates a new [`AggregationJobCreatorTaskState`].
+    pub fn new(
+        task_id: TaskId,
+        discovered_at: Time,
+        last_run_at: Option<Time>,
+        next_run_at: Time,
+        last_error: Option<String>,
+    ) -> Self {
+        Self {
+            task_id,
+            discovered_at,
+            last_run_at,
+            next_run_at,
+            last_error,
+        }
+    }
+
+    /// Returns the ID of the task that aggregation jobs are created for.
+    pub fn task_id(&self) -> &TaskId {
+        &self.task_id
+    }
+
+    /// Returns when the aggregation job creator started working on the task.
+    pub fn discovered_at(&self) -> &Time {
+        &self.discovered_at
+    }
+
+    /// Returns when aggregation jobs were last created for the task, or `None` if they have not
+    /// been yet.
+    pub fn last_run_at(&self) -> Option<&Time> {
+        self.last_run_at.as_ref()
+    }
+
+    /// Returns when aggregation jobs are next scheduled to be created for the task.
+    pub fn next_run_at(&self) -> &Time {
+        &self.next_run_at
+    }
+
+    /// Returns a description of why the last attempt to create aggregation jobs failed, or `None`
+    /// if it succeeded.
+    pub fn last_error(&self) -> Option<&str> {
+        self.last_error.as_deref()
+    }
+}
+
+/// The progress of a collection job, along with an estimate of when it will complete.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CollectionJobProgress {
+    state: CollectionJobStateCode,
+    outstanding_aggregation_jobs: u64,
+    recently_terminated_aggregation_jobs: u64,
+    estimated_completion: Option<Time>,
+}
+
+impl CollectionJobProgress {
+    /// The default window over which the task's recent aggregation throughput is measured.
+    pub const DEFAULT_THROUGHPUT_WINDOW: Duration = Duration::from_seconds(600);
+
+    /// Creates a new [`CollectionJobProgress`].
+    pub fn new(
+        state: CollectionJobStateCode,
+        outstanding_aggregation_jobs: u64,
+        recently_terminated_aggregation_jobs: u64,
+        estimated_completion: Option<Time>,
+    ) -> Self {
+        Self {
+            state,
+            outstanding_aggregation_jobs,
+            recently_terminated_aggregation_jobs,
+            estimated_completion,
+        }
+    }
+
+    /// Returns the state of the collection job.
+    pub fn state(&self) -> CollectionJobStateCode {
+        self.state
+    }
+
+    /// Returns the number of aggregation jobs contributing to the collection job's batch which
+    /// have not yet terminated.
+    pub fn outstanding_aggregation_jobs(&self) -> u64 {
+        self.outstanding_aggregation_jobs
+    }
+
+    /// Returns the number of the task's aggregation jobs which terminated within the throughput
+    /// window.
+    pub fn recently_terminated_aggregation_jobs(&self) -> u64 {
+        self.recently_terminated_aggregation_jobs
+    }
+
+    /// Returns when the collection job is estimated to complete, or `None` if it is no longer in
+    /// progress or if its outstanding aggregation jobs have made no recent progress.
+    pub fn estimated_completion(&self) -> Option<&Time> {
+        self.estimated_completion.as_ref()
+    }
+}
+
+/// An HPKE config of a task, along with when its keypair is retired, if it has been scheduled for
+/// retirement.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TaskHpkeConfig {
+    config: HpkeConfig,
+    retire_at: Option<Time>,
+}
+
+impl TaskHpkeConfig {
+    /// Creates a new [`TaskHpkeConfig`].
+    pub fn new(config: HpkeConfig, retire_at: Option<Time>) -> Self {
+        Self { config, retire_at }
+    }
+
+    /// Returns the HPKE config.
+    pub fn config(&self) -> &HpkeConfig {
+        &self.config
+    }
+
+    /// Returns when the keypair is retired, after which it is no longer used to decrypt reports,
+    /// or `None` if it has not been scheduled for retirement.
+    pub fn retire_at(&self) -> Option<&Time> {
+        self.retire_at.as_ref()
+    }
+}
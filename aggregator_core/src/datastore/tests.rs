@@ -1,22 +1,28 @@
 use crate::{
     datastore::{
         models::{
-            AcquiredAggregationJob, AcquiredCollectionJob, AggregateShareJob, AggregationJob,
-            AggregationJobState, BatchAggregation, BatchAggregationState, CollectionJob,
-            CollectionJobState, CollectionJobStateCode, GlobalHpkeKeypair, HpkeKeyState,
-            LeaderStoredReport, Lease, OutstandingBatch, ReportAggregation,
-            ReportAggregationMetadata, ReportAggregationMetadataState, ReportAggregationState,
-            SqlInterval, TaskUploadCounter,
+            AcquiredAggregationJob, AcquiredCollectionJob, ActiveDeployment, AdminApiKey,
+            AggregateShareJob, AggregationJob, AggregationJobCreatorTaskState, AggregationJobState,
+            BatchAggregation, BatchAggregationState, ClientReportWriteOutcome, CollectionJob,
+            CollectionJobProgress, CollectionJobState, CollectionJobStateCode, EncodedClientReport,
+            FeatureFlag, GlobalHpkeKeypair, HpkeKeyState, LeaderStoredReport, Lease,
+            OutstandingBatch, ReportAggregation, ReportAggregationMetadata,
+            ReportAggregationMetadataState, ReportAggregationState, SqlInterval, TaskHpkeConfig,
+            TaskLifecycleEvent, TaskMetricsSnapshot, TaskPeerHealth, TaskUploadCounter,
+            UploadSample,
         },
         schema_versions_template,
         test_util::{
-            ephemeral_datastore_schema_version, generate_aead_key, EphemeralDatastore,
-            EphemeralDatastoreBuilder, TEST_DATASTORE_MAX_TRANSACTION_RETRIES,
+            ephemeral_datastore_schema_version, generate_aead_key, DatastoreFixtureBuilder,
+            EphemeralDatastore, EphemeralDatastoreBuilder, TEST_DATASTORE_MAX_TRANSACTION_RETRIES,
         },
         Crypter, Datastore, Error, RowExt, Transaction, SUPPORTED_SCHEMA_VERSIONS,
     },
     query_type::CollectableQueryType,
-    task::{self, test_util::TaskBuilder, AggregatorTask},
+    task::{
+        self, test_util::TaskBuilder, AggregationJobSizing, AggregatorTask, HelperRequestHeader,
+        TaskState, UnknownExtensionPolicy,
+    },
     taskprov::test_util::PeerAggregatorBuilder,
     test_util::noop_meter,
 };
@@ -25,8 +31,14 @@ use async_trait::async_trait;
 use chrono::NaiveDate;
 use futures::future::try_join_all;
 use janus_core::{
+    auth_tokens::{AuthenticationToken, AuthenticationTokenHash},
     hpke::{
-        self, test_util::generate_test_hpke_config_and_private_key, HpkeApplicationInfo, Label,
+        self,
+        test_util::{
+            generate_test_hpke_config_and_private_key,
+            generate_test_hpke_config_and_private_key_with_id,
+        },
+        HpkeApplicationInfo, Label,
     },
     test_util::{install_test_trace_subscriber, run_vdaf},
     time::{Clock, DurationExt, IntervalExt, MockClock, TimeExt},
@@ -185,10 +197,49 @@ async fn roundtrip_task(ephemeral_datastore: EphemeralDatastore) {
         (VdafInstance::Poplar1 { bits: 8 }, Role::Helper),
         (VdafInstance::Poplar1 { bits: 64 }, Role::Helper),
     ] {
+        // Give one of the leader tasks headers to send to the helper.
+        let helper_request_headers =
+            if role == Role::Leader && matches!(vdaf, VdafInstance::Prio3SumVec { .. }) {
+                Vec::from([HelperRequestHeader::new(
+                    "X-Api-Key".to_string(),
+                    "gateway-key".to_string(),
+                )
+                .unwrap()])
+            } else {
+                Vec::new()
+            };
+        // Require upload authentication for one of the leader tasks.
+        let upload_auth_token_hash =
+            if role == Role::Leader && matches!(vdaf, VdafInstance::Prio3Histogram { .. }) {
+                Some(AuthenticationTokenHash::from(
+                    &random::<AuthenticationToken>(),
+                ))
+            } else {
+                None
+            };
+        // Override aggregation job sizing for one of the leader tasks.
+        let aggregation_job_sizing =
+            if role == Role::Leader && matches!(vdaf, VdafInstance::Prio3Count) {
+                AggregationJobSizing::new(Some(100), Some(1000), Some(Duration::from_seconds(30)))
+            } else {
+                AggregationJobSizing::default()
+            };
         let task = TaskBuilder::new(task::QueryType::TimeInterval, vdaf)
             .with_report_expiry_age(Some(Duration::from_seconds(3600)))
             .build()
             .view_for_role(role)
+            .unwrap()
+            .with_helper_request_headers(helper_request_headers)
+            .unwrap()
+            .with_unknown_extension_policy(if role == Role::Leader {
+                UnknownExtensionPolicy::AcceptWithMetric
+            } else {
+                UnknownExtensionPolicy::Reject
+            })
+            .unwrap()
+            .with_upload_auth_token_hash(upload_auth_token_hash)
+            .unwrap()
+            .with_aggregation_job_sizing(aggregation_job_sizing)
             .unwrap();
         want_tasks.insert(*task.id(), task.clone());
 
@@ -357,6 +408,48 @@ async fn put_task_invalid_collector_auth_tokens(ephemeral_datastore: EphemeralDa
     }
 }
 
+#[rstest_reuse::apply(schema_versions_template)]
+#[tokio::test]
+async fn get_task_skips_new_task_validation(ephemeral_datastore: EphemeralDatastore) {
+    install_test_trace_subscriber();
+    let ds = ephemeral_datastore.datastore(MockClock::default()).await;
+
+    let task = TaskBuilder::new(task::QueryType::TimeInterval, VdafInstance::Prio3Count)
+        .build()
+        .leader_view()
+        .unwrap();
+    ds.put_aggregator_task(&task).await.unwrap();
+
+    // Tasks written before provisioning rejected these zero values must still be readable.
+    ds.run_unnamed_tx(|tx| {
+        Box::pin(async move {
+            tx.execute(
+                "UPDATE tasks SET report_expiry_age = 0, max_batch_query_count = 0,
+                    min_batch_size = 0, time_precision = 0",
+                &[],
+            )
+            .await
+            .unwrap();
+            Ok(())
+        })
+    })
+    .await
+    .unwrap();
+
+    let got_task = ds
+        .run_unnamed_tx(|tx| {
+            let task = task.clone();
+            Box::pin(async move { tx.get_aggregator_task(task.id()).await })
+        })
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(got_task.report_expiry_age(), Some(&Duration::ZERO));
+    assert_eq!(got_task.max_batch_query_count(), 0);
+    assert_eq!(got_task.min_batch_size(), 0);
+    assert_eq!(got_task.time_precision(), &Duration::ZERO);
+}
+
 #[rstest_reuse::apply(schema_versions_template)]
 #[tokio::test]
 async fn get_task_ids(ephemeral_datastore: EphemeralDatastore) {
@@ -397,6 +490,56 @@ async fn get_task_ids(ephemeral_datastore: EphemeralDatastore) {
     .unwrap();
 }
 
+#[rstest_reuse::apply(schema_versions_template)]
+#[tokio::test]
+async fn get_task_ids_by_role(ephemeral_datastore: EphemeralDatastore) {
+    install_test_trace_subscriber();
+    let ds = ephemeral_datastore.datastore(MockClock::default()).await;
+
+    ds.run_unnamed_tx(|tx| {
+        Box::pin(async move {
+            let leader_tasks: Vec<_> = iter::repeat_with(|| {
+                TaskBuilder::new(task::QueryType::TimeInterval, VdafInstance::Fake)
+                    .build()
+                    .leader_view()
+                    .unwrap()
+            })
+            .take(3)
+            .collect();
+            let helper_task = TaskBuilder::new(task::QueryType::TimeInterval, VdafInstance::Fake)
+                .build()
+                .helper_view()
+                .unwrap();
+
+            try_join_all(
+                leader_tasks
+                    .iter()
+                    .chain(iter::once(&helper_task))
+                    .map(|task| tx.put_aggregator_task(task)),
+            )
+            .await
+            .unwrap();
+
+            assert_eq!(
+                tx.get_task_ids_by_role(&Role::Leader).await.unwrap(),
+                leader_tasks
+                    .iter()
+                    .map(AggregatorTask::id)
+                    .cloned()
+                    .collect()
+            );
+            assert_eq!(
+                tx.get_task_ids_by_role(&Role::Helper).await.unwrap(),
+                HashSet::from([*helper_task.id()])
+            );
+
+            Ok(())
+        })
+    })
+    .await
+    .unwrap();
+}
+
 #[rstest_reuse::apply(schema_versions_template)]
 #[tokio::test]
 async fn roundtrip_report(ephemeral_datastore: EphemeralDatastore) {
@@ -465,7 +608,18 @@ async fn roundtrip_report(ephemeral_datastore: EphemeralDatastore) {
 
     assert_eq!(report, retrieved_report);
 
-    // Try to write a different report with the same ID, and verify we get the expected error.
+    // Write the same report again, and verify it is recognized as a duplicate.
+    let result = ds
+        .run_unnamed_tx(|tx| {
+            let report = report.clone();
+            Box::pin(async move { tx.put_client_report(&dummy::Vdaf::default(), &report).await })
+        })
+        .await
+        .unwrap();
+    assert_eq!(result, ClientReportWriteOutcome::Duplicate);
+
+    // Try to write a different report with the same ID, and verify it is recognized as a
+    // conflict.
     let result = ds
         .run_unnamed_tx(|tx| {
             let task_id = *report.task_id();
@@ -492,8 +646,9 @@ async fn roundtrip_report(ephemeral_datastore: EphemeralDatastore) {
                 .await
             })
         })
-        .await;
-    assert_matches!(result, Err(Error::MutationTargetAlreadyExists));
+        .await
+        .unwrap();
+    assert_eq!(result, ClientReportWriteOutcome::Conflict);
 
     ds.run_unnamed_tx(|tx| {
         Box::pin(async move {
@@ -531,6 +686,16 @@ async fn roundtrip_report(ephemeral_datastore: EphemeralDatastore) {
     .await
     .unwrap();
 
+    // Writing the report again after it has been scrubbed is still recognized as a duplicate.
+    let result = ds
+        .run_unnamed_tx(|tx| {
+            let report = report.clone();
+            Box::pin(async move { tx.put_client_report(&dummy::Vdaf::default(), &report).await })
+        })
+        .await
+        .unwrap();
+    assert_eq!(result, ClientReportWriteOutcome::Duplicate);
+
     // Advance the clock so that the report is expired, and verify that it does not exist.
     clock.advance(&Duration::from_seconds(1));
     let retrieved_report = ds
@@ -550,6 +715,251 @@ async fn roundtrip_report(ephemeral_datastore: EphemeralDatastore) {
     assert_eq!(None, retrieved_report);
 }
 
+#[rstest_reuse::apply(schema_versions_template)]
+#[tokio::test]
+async fn put_client_reports(ephemeral_datastore: EphemeralDatastore) {
+    install_test_trace_subscriber();
+    let clock = MockClock::default();
+    let ds = ephemeral_datastore.datastore(clock.clone()).await;
+
+    let task = TaskBuilder::new(task::QueryType::TimeInterval, VdafInstance::Fake)
+        .build()
+        .leader_view()
+        .unwrap();
+    let other_task = TaskBuilder::new(task::QueryType::TimeInterval, VdafInstance::Fake)
+        .build()
+        .leader_view()
+        .unwrap();
+    ds.put_aggregator_task(&task).await.unwrap();
+    ds.put_aggregator_task(&other_task).await.unwrap();
+
+    let time = clock.now();
+    let existing_report = LeaderStoredReport::new_dummy(*task.id(), time);
+    let conflicting_report = LeaderStoredReport::new(
+        *task.id(),
+        ReportMetadata::new(*existing_report.metadata().id(), time),
+        (), // public share
+        Vec::from([Extension::new(
+            ExtensionType::Tbd,
+            Vec::from("extension_data"),
+        )]),
+        dummy::InputShare::default(), // leader input share
+        /* Dummy ciphertext for the helper share */
+        HpkeCiphertext::new(
+            HpkeConfigId::from(14),
+            Vec::from("encapsulated_context_1"),
+            Vec::from("payload_1"),
+        ),
+    );
+    let new_report = LeaderStoredReport::new_dummy(*task.id(), time);
+    let other_task_report = LeaderStoredReport::new_dummy(*other_task.id(), time);
+    let repeated_report = LeaderStoredReport::new_dummy(*task.id(), time);
+    let conflicting_repeated_report = LeaderStoredReport::new(
+        *task.id(),
+        ReportMetadata::new(
+            *repeated_report.metadata().id(),
+            Time::from_seconds_since_epoch(54321),
+        ),
+        (), // public share
+        Vec::new(),
+        dummy::InputShare::default(), // leader input share
+        repeated_report.helper_encrypted_input_share().clone(),
+    );
+
+    ds.run_tx("test-put-client-reports", |tx| {
+        let existing_report = existing_report.clone();
+        Box::pin(async move {
+            tx.put_client_report(&dummy::Vdaf::default(), &existing_report)
+                .await
+        })
+    })
+    .await
+    .unwrap();
+
+    let outcomes = ds
+        .run_tx("test-put-client-reports", |tx| {
+            let reports = [
+                &new_report,
+                &existing_report,
+                &conflicting_report,
+                &other_task_report,
+                &repeated_report,
+                &repeated_report,
+                &conflicting_repeated_report,
+            ]
+            .into_iter()
+            .map(EncodedClientReport::new)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+            Box::pin(async move { tx.put_client_reports(&reports).await })
+        })
+        .await
+        .unwrap();
+    assert_eq!(
+        outcomes,
+        Vec::from([
+            ClientReportWriteOutcome::Inserted,
+            ClientReportWriteOutcome::Duplicate,
+            ClientReportWriteOutcome::Conflict,
+            ClientReportWriteOutcome::Inserted,
+            ClientReportWriteOutcome::Inserted,
+            ClientReportWriteOutcome::Duplicate,
+            ClientReportWriteOutcome::Conflict,
+        ])
+    );
+
+    // The inserted reports can be read back, and the existing report is left in place.
+    for report in [
+        &new_report,
+        &existing_report,
+        &other_task_report,
+        &repeated_report,
+    ] {
+        let retrieved_report = ds
+            .run_unnamed_tx(|tx| {
+                let (task_id, report_id) = (*report.task_id(), *report.metadata().id());
+                Box::pin(async move {
+                    tx.get_client_report::<0, dummy::Vdaf>(
+                        &dummy::Vdaf::default(),
+                        &task_id,
+                        &report_id,
+                    )
+                    .await
+                })
+            })
+            .await
+            .unwrap();
+        assert_eq!(retrieved_report.as_ref(), Some(report));
+    }
+
+    ds.run_unnamed_tx(|tx| {
+        Box::pin(async move {
+            tx.check_timestamp_columns("client_reports", "test-put-client-reports", false)
+                .await;
+            Ok(())
+        })
+    })
+    .await
+    .unwrap();
+}
+
+#[rstest_reuse::apply(schema_versions_template)]
+#[tokio::test]
+async fn put_client_reports_expired(ephemeral_datastore: EphemeralDatastore) {
+    install_test_trace_subscriber();
+    let clock = MockClock::default();
+    let ds = ephemeral_datastore.datastore(clock.clone()).await;
+
+    let task = TaskBuilder::new(task::QueryType::TimeInterval, VdafInstance::Fake)
+        .with_report_expiry_age(Some(Duration::from_seconds(3600)))
+        .build()
+        .leader_view()
+        .unwrap();
+    ds.put_aggregator_task(&task).await.unwrap();
+
+    let fresh_report = LeaderStoredReport::new_dummy(*task.id(), clock.now());
+    let expired_report = LeaderStoredReport::new_dummy(
+        *task.id(),
+        clock.now().sub(&Duration::from_seconds(7200)).unwrap(),
+    );
+
+    let outcomes = ds
+        .run_unnamed_tx(|tx| {
+            let reports = [&expired_report, &fresh_report, &expired_report]
+                .into_iter()
+                .map(EncodedClientReport::new)
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            Box::pin(async move { tx.put_client_reports(&reports).await })
+        })
+        .await
+        .unwrap();
+    assert_eq!(
+        outcomes,
+        Vec::from([
+            ClientReportWriteOutcome::Expired,
+            ClientReportWriteOutcome::Inserted,
+            ClientReportWriteOutcome::Expired,
+        ])
+    );
+
+    // Only the fresh report was written. (Expired reports can't be read back with
+    // get_client_report, so the table is checked directly.)
+    let report_ids = ds
+        .run_unnamed_tx(|tx| {
+            let task_id = *task.id();
+            Box::pin(async move {
+                Ok(tx
+                    .query(
+                        "SELECT client_reports.report_id
+                        FROM client_reports JOIN tasks ON tasks.id = client_reports.task_id
+                        WHERE tasks.task_id = $1",
+                        &[/* task_id */ &task_id.as_ref()],
+                    )
+                    .await?
+                    .into_iter()
+                    .map(|row| ReportId::get_decoded(row.get("report_id")).unwrap())
+                    .collect::<Vec<_>>())
+            })
+        })
+        .await
+        .unwrap();
+    assert_eq!(report_ids, Vec::from([*fresh_report.metadata().id()]));
+}
+
+#[rstest_reuse::apply(schema_versions_template)]
+#[tokio::test]
+async fn put_client_reports_task_not_found(ephemeral_datastore: EphemeralDatastore) {
+    install_test_trace_subscriber();
+    let clock = MockClock::default();
+    let ds = ephemeral_datastore.datastore(clock.clone()).await;
+
+    let task = TaskBuilder::new(task::QueryType::TimeInterval, VdafInstance::Fake)
+        .build()
+        .leader_view()
+        .unwrap();
+    ds.put_aggregator_task(&task).await.unwrap();
+
+    let report = LeaderStoredReport::new_dummy(*task.id(), clock.now());
+    let unknown_task_report = LeaderStoredReport::new_dummy(random(), clock.now());
+
+    // A report for a task that doesn't exist doesn't fail the rest of the batch.
+    let outcomes = ds
+        .run_unnamed_tx(|tx| {
+            let reports = [&unknown_task_report, &report]
+                .into_iter()
+                .map(EncodedClientReport::new)
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            Box::pin(async move { tx.put_client_reports(&reports).await })
+        })
+        .await
+        .unwrap();
+    assert_eq!(
+        outcomes,
+        Vec::from([
+            ClientReportWriteOutcome::TaskNotFound,
+            ClientReportWriteOutcome::Inserted,
+        ])
+    );
+
+    let retrieved_report = ds
+        .run_unnamed_tx(|tx| {
+            let (task_id, report_id) = (*task.id(), *report.metadata().id());
+            Box::pin(async move {
+                tx.get_client_report::<0, dummy::Vdaf>(
+                    &dummy::Vdaf::default(),
+                    &task_id,
+                    &report_id,
+                )
+                .await
+            })
+        })
+        .await
+        .unwrap();
+    assert_eq!(retrieved_report, Some(report));
+}
+
 #[rstest_reuse::apply(schema_versions_template)]
 #[tokio::test]
 async fn report_not_found(ephemeral_datastore: EphemeralDatastore) {
@@ -1654,7 +2064,7 @@ async fn aggregation_job_acquire_release(ephemeral_datastore: EphemeralDatastore
         let leases_to_release = leases_to_release.clone();
         Box::pin(async move {
             for lease in leases_to_release {
-                tx.release_aggregation_job(&lease).await.unwrap();
+                tx.release_aggregation_job(&lease, None).await.unwrap();
             }
             Ok(())
         })
@@ -1749,7 +2159,10 @@ async fn aggregation_job_acquire_release(ephemeral_datastore: EphemeralDatastore
     );
     ds.run_unnamed_tx(|tx| {
         let lease_with_random_token = lease_with_random_token.clone();
-        Box::pin(async move { tx.release_aggregation_job(&lease_with_random_token).await })
+        Box::pin(async move {
+            tx.release_aggregation_job(&lease_with_random_token, None)
+                .await
+        })
     })
     .await
     .unwrap_err();
@@ -1758,7 +2171,7 @@ async fn aggregation_job_acquire_release(ephemeral_datastore: EphemeralDatastore
     // place.
     ds.run_unnamed_tx(|tx| {
         let lease = lease.clone();
-        Box::pin(async move { tx.release_aggregation_job(&lease).await })
+        Box::pin(async move { tx.release_aggregation_job(&lease, None).await })
     })
     .await
     .unwrap();
@@ -1766,15 +2179,160 @@ async fn aggregation_job_acquire_release(ephemeral_datastore: EphemeralDatastore
 
 #[rstest_reuse::apply(schema_versions_template)]
 #[tokio::test]
-async fn aggregation_job_not_found(ephemeral_datastore: EphemeralDatastore) {
+async fn aggregation_job_release_with_reacquire_delay(ephemeral_datastore: EphemeralDatastore) {
     install_test_trace_subscriber();
-    let ds = ephemeral_datastore.datastore(MockClock::default()).await;
 
-    let rslt = ds
-        .run_unnamed_tx(|tx| {
-            Box::pin(async move {
-                tx.get_aggregation_job::<VERIFY_KEY_LENGTH, TimeInterval, Prio3Count>(
-                    &random(),
+    const LEASE_DURATION: StdDuration = StdDuration::from_secs(300);
+    let clock = MockClock::new(OLDEST_ALLOWED_REPORT_TIMESTAMP);
+    let ds = ephemeral_datastore.datastore(clock.clone()).await;
+
+    let task = TaskBuilder::new(task::QueryType::TimeInterval, VdafInstance::Fake)
+        .build()
+        .leader_view()
+        .unwrap();
+    ds.run_unnamed_tx(|tx| {
+        let task = task.clone();
+        let clock = clock.clone();
+        Box::pin(async move {
+            tx.put_aggregator_task(&task).await.unwrap();
+            for _ in 0..2 {
+                tx.put_aggregation_job(&AggregationJob::<0, TimeInterval, dummy::Vdaf>::new(
+                    *task.id(),
+                    random(),
+                    dummy::AggregationParam(0),
+                    (),
+                    Interval::new(clock.now(), Duration::from_seconds(1)).unwrap(),
+                    AggregationJobState::InProgress,
+                    AggregationJobStep::from(0),
+                ))
+                .await
+                .unwrap();
+            }
+            Ok(())
+        })
+    })
+    .await
+    .unwrap();
+
+    let acquire = || {
+        ds.run_unnamed_tx(|tx| {
+            Box::pin(async move {
+                tx.acquire_incomplete_aggregation_jobs(&LEASE_DURATION, 10)
+                    .await
+            })
+        })
+    };
+
+    // Run: acquire both jobs, then release one of them with a reacquire delay longer than the
+    // lease duration.
+    let lease = acquire().await.unwrap().remove(0);
+    ds.run_unnamed_tx(|tx| {
+        let lease = lease.clone();
+        Box::pin(async move {
+            tx.release_aggregation_job(&lease, Some(&(LEASE_DURATION * 2)))
+                .await
+        })
+    })
+    .await
+    .unwrap();
+
+    // Verify: once the other job's lease expires, only the other job is reacquired.
+    clock.advance(&Duration::from_seconds(LEASE_DURATION.as_secs()));
+    let got_leases = acquire().await.unwrap();
+    assert_eq!(got_leases.len(), 1);
+    assert_ne!(got_leases[0].leased(), lease.leased());
+
+    // Verify: once the reacquire delay has passed, the released job is reacquired.
+    clock.advance(&Duration::from_seconds(LEASE_DURATION.as_secs()));
+    let got_leases = acquire().await.unwrap();
+    assert!(got_leases
+        .iter()
+        .any(|got_lease| got_lease.leased() == lease.leased()));
+}
+
+#[rstest_reuse::apply(schema_versions_template)]
+#[tokio::test]
+async fn aggregation_job_acquire_with_task_limit(ephemeral_datastore: EphemeralDatastore) {
+    install_test_trace_subscriber();
+
+    const LEASE_DURATION: StdDuration = StdDuration::from_secs(300);
+    let clock = MockClock::new(OLDEST_ALLOWED_REPORT_TIMESTAMP);
+    let ds = ephemeral_datastore.datastore(clock.clone()).await;
+
+    // One task has three incomplete aggregation jobs, and the other has one.
+    let busy_task = TaskBuilder::new(task::QueryType::TimeInterval, VdafInstance::Fake)
+        .build()
+        .leader_view()
+        .unwrap();
+    let quiet_task = TaskBuilder::new(task::QueryType::TimeInterval, VdafInstance::Fake)
+        .build()
+        .leader_view()
+        .unwrap();
+    ds.run_unnamed_tx(|tx| {
+        let (busy_task, quiet_task) = (busy_task.clone(), quiet_task.clone());
+        let clock = clock.clone();
+        Box::pin(async move {
+            for (task, job_count) in [(&busy_task, 3), (&quiet_task, 1)] {
+                tx.put_aggregator_task(task).await.unwrap();
+                for _ in 0..job_count {
+                    tx.put_aggregation_job(&AggregationJob::<0, TimeInterval, dummy::Vdaf>::new(
+                        *task.id(),
+                        random(),
+                        dummy::AggregationParam(0),
+                        (),
+                        Interval::new(clock.now(), Duration::from_seconds(1)).unwrap(),
+                        AggregationJobState::InProgress,
+                        AggregationJobStep::from(0),
+                    ))
+                    .await
+                    .unwrap();
+                }
+            }
+            Ok(())
+        })
+    })
+    .await
+    .unwrap();
+
+    let acquire = || {
+        ds.run_unnamed_tx(|tx| {
+            Box::pin(async move {
+                tx.acquire_incomplete_aggregation_jobs_with_task_limit(&LEASE_DURATION, 10, 2)
+                    .await
+            })
+        })
+    };
+    let count_by_task = |leases: Vec<Lease<AcquiredAggregationJob>>| {
+        let mut counts = HashMap::new();
+        for lease in leases {
+            *counts.entry(*lease.leased().task_id()).or_insert(0) += 1;
+        }
+        counts
+    };
+
+    // At most two jobs of each task are acquired.
+    let want_counts = HashMap::from([(*busy_task.id(), 2), (*quiet_task.id(), 1)]);
+    assert_eq!(count_by_task(acquire().await.unwrap()), want_counts);
+
+    // While those leases are live, the busy task's remaining job is not acquired.
+    assert!(acquire().await.unwrap().is_empty());
+
+    // Once the leases expire, the jobs may be acquired again, still subject to the limit.
+    clock.advance(&Duration::from_seconds(LEASE_DURATION.as_secs()));
+    assert_eq!(count_by_task(acquire().await.unwrap()), want_counts);
+}
+
+#[rstest_reuse::apply(schema_versions_template)]
+#[tokio::test]
+async fn aggregation_job_not_found(ephemeral_datastore: EphemeralDatastore) {
+    install_test_trace_subscriber();
+    let ds = ephemeral_datastore.datastore(MockClock::default()).await;
+
+    let rslt = ds
+        .run_unnamed_tx(|tx| {
+            Box::pin(async move {
+                tx.get_aggregation_job::<VERIFY_KEY_LENGTH, TimeInterval, Prio3Count>(
+                    &random(),
                     &random(),
                 )
                 .await
@@ -1806,6 +2364,106 @@ async fn aggregation_job_not_found(ephemeral_datastore: EphemeralDatastore) {
     assert_matches!(rslt, Err(Error::MutationTargetNotFound));
 }
 
+#[rstest_reuse::apply(schema_versions_template)]
+#[tokio::test]
+async fn job_last_failure_domain(ephemeral_datastore: EphemeralDatastore) {
+    install_test_trace_subscriber();
+    let ds = ephemeral_datastore.datastore(MockClock::default()).await;
+
+    let task = TaskBuilder::new(task::QueryType::TimeInterval, VdafInstance::Fake)
+        .build()
+        .leader_view()
+        .unwrap();
+    let batch_interval = Interval::new(
+        Time::from_seconds_since_epoch(0),
+        Duration::from_seconds(100),
+    )
+    .unwrap();
+    let aggregation_job = AggregationJob::<0, TimeInterval, dummy::Vdaf>::new(
+        *task.id(),
+        random(),
+        dummy::AggregationParam(0),
+        (),
+        batch_interval,
+        AggregationJobState::InProgress,
+        AggregationJobStep::from(0),
+    );
+    let collection_job = CollectionJob::<0, TimeInterval, dummy::Vdaf>::new(
+        *task.id(),
+        random(),
+        Query::new_time_interval(batch_interval),
+        dummy::AggregationParam(0),
+        batch_interval,
+        CollectionJobState::Start,
+    );
+
+    ds.run_unnamed_tx(|tx| {
+        let (task, aggregation_job, collection_job) = (
+            task.clone(),
+            aggregation_job.clone(),
+            collection_job.clone(),
+        );
+        Box::pin(async move {
+            tx.put_aggregator_task(&task).await.unwrap();
+            tx.put_aggregation_job(&aggregation_job).await.unwrap();
+            tx.put_collection_job(&collection_job).await.unwrap();
+
+            // No failures have been recorded yet.
+            assert_eq!(
+                tx.get_aggregation_job_last_failure_domain(task.id(), aggregation_job.id())
+                    .await
+                    .unwrap(),
+                None
+            );
+            assert_eq!(
+                tx.get_collection_job_last_failure_domain(task.id(), collection_job.id())
+                    .await
+                    .unwrap(),
+                None
+            );
+
+            // The most recent failure domain is kept.
+            for failure_domain in ["peer", "crypto"] {
+                tx.set_aggregation_job_last_failure_domain(
+                    task.id(),
+                    aggregation_job.id(),
+                    failure_domain,
+                )
+                .await
+                .unwrap();
+            }
+            tx.set_collection_job_last_failure_domain(task.id(), collection_job.id(), "datastore")
+                .await
+                .unwrap();
+            assert_eq!(
+                tx.get_aggregation_job_last_failure_domain(task.id(), aggregation_job.id())
+                    .await
+                    .unwrap()
+                    .as_deref(),
+                Some("crypto")
+            );
+            assert_eq!(
+                tx.get_collection_job_last_failure_domain(task.id(), collection_job.id())
+                    .await
+                    .unwrap()
+                    .as_deref(),
+                Some("datastore")
+            );
+
+            // Recording a failure for a job that doesn't exist fails.
+            assert_matches!(
+                tx.set_aggregation_job_last_failure_domain(task.id(), &random(), "peer")
+                    .await,
+                Err(Error::MutationTargetNotFound)
+            );
+
+            Ok(())
+        })
+    })
+    .await
+    .unwrap();
+}
+
 #[rstest_reuse::apply(schema_versions_template)]
 #[tokio::test]
 async fn get_aggregation_jobs_for_task(ephemeral_datastore: EphemeralDatastore) {
@@ -2702,9 +3360,14 @@ async fn crypter() {
     assert_eq!(PLAINTEXT, &plaintext);
 
     // Roundtripping encryption works even if a non-primary key was used for encryption.
-    let ciphertext =
-        Crypter::encrypt_with_key(crypter.keys.last().unwrap(), TABLE, ROW, COLUMN, PLAINTEXT)
-            .unwrap();
+    let ciphertext = Crypter::encrypt_with_key(
+        crypter.keys.read().unwrap().last().unwrap(),
+        TABLE,
+        ROW,
+        COLUMN,
+        PLAINTEXT,
+    )
+    .unwrap();
     let plaintext = crypter.decrypt(TABLE, ROW, COLUMN, &ciphertext).unwrap();
     assert_eq!(PLAINTEXT, &plaintext);
 
@@ -2723,6 +3386,19 @@ async fn crypter() {
     assert!(crypter
         .decrypt(TABLE, ROW, "wrong_column", &ciphertext)
         .is_err());
+
+    // Replacing the keys of a crypter also replaces the keys of its clones.
+    let ciphertext = crypter.encrypt(TABLE, ROW, COLUMN, PLAINTEXT).unwrap();
+    let cloned_crypter = crypter.clone();
+    crypter.replace_keys(Vec::from([bad_key]));
+    assert!(cloned_crypter
+        .decrypt(TABLE, ROW, COLUMN, &ciphertext)
+        .is_err());
+    let ciphertext = cloned_crypter
+        .encrypt(TABLE, ROW, COLUMN, PLAINTEXT)
+        .unwrap();
+    let plaintext = crypter.decrypt(TABLE, ROW, COLUMN, &ciphertext).unwrap();
+    assert_eq!(PLAINTEXT, &plaintext);
 }
 
 #[rstest_reuse::apply(schema_versions_template)]
@@ -2947,54 +3623,340 @@ async fn get_collection_job(ephemeral_datastore: EphemeralDatastore) {
 
 #[rstest_reuse::apply(schema_versions_template)]
 #[tokio::test]
-async fn update_collection_jobs(ephemeral_datastore: EphemeralDatastore) {
-    // Setup: write collection jobs to the datastore.
+async fn get_collection_job_progress(ephemeral_datastore: EphemeralDatastore) {
     install_test_trace_subscriber();
 
-    let ds = ephemeral_datastore.datastore(MockClock::default()).await;
+    let clock = MockClock::new(Time::from_seconds_since_epoch(10000));
+    let ds = ephemeral_datastore.datastore(clock.clone()).await;
 
+    let time_precision = Duration::from_seconds(100);
     let task = TaskBuilder::new(task::QueryType::TimeInterval, VdafInstance::Fake)
+        .with_time_precision(time_precision)
         .build()
         .leader_view()
         .unwrap();
-    let abandoned_batch_interval = Interval::new(
-        Time::from_seconds_since_epoch(100),
-        Duration::from_seconds(100),
-    )
-    .unwrap();
-    let deleted_batch_interval = Interval::new(
-        Time::from_seconds_since_epoch(200),
-        Duration::from_seconds(100),
+    let batch_interval = Interval::new(
+        Time::from_seconds_since_epoch(1000),
+        Duration::from_seconds(200),
     )
     .unwrap();
+    let future_batch_interval =
+        Interval::new(Time::from_seconds_since_epoch(20000), time_precision).unwrap();
+    let aggregation_param = dummy::AggregationParam(7);
+    let throughput_window = Duration::from_seconds(600);
 
-    ds.run_tx("test-update-collection-jobs", |tx| {
-        let task = task.clone();
-        Box::pin(async move {
-            tx.put_aggregator_task(&task).await.unwrap();
+    let (collection_job_id, future_collection_job_id) = ds
+        .run_unnamed_tx(|tx| {
+            let task = task.clone();
+            Box::pin(async move {
+                tx.put_aggregator_task(&task).await.unwrap();
 
-            let vdaf = dummy::Vdaf::default();
-            let aggregation_param = dummy::AggregationParam(10);
-            let abandoned_collection_job = CollectionJob::<0, TimeInterval, dummy::Vdaf>::new(
-                *task.id(),
-                random(),
-                Query::new_time_interval(abandoned_batch_interval),
-                aggregation_param,
-                abandoned_batch_interval,
-                CollectionJobState::Start,
-            );
-            tx.put_collection_job(&abandoned_collection_job)
+                let collection_job = CollectionJob::<0, TimeInterval, dummy::Vdaf>::new(
+                    *task.id(),
+                    random(),
+                    Query::new_time_interval(batch_interval),
+                    aggregation_param,
+                    batch_interval,
+                    CollectionJobState::Start,
+                );
+                tx.put_collection_job(&collection_job).await.unwrap();
+                let future_collection_job = CollectionJob::<0, TimeInterval, dummy::Vdaf>::new(
+                    *task.id(),
+                    random(),
+                    Query::new_time_interval(future_batch_interval),
+                    aggregation_param,
+                    future_batch_interval,
+                    CollectionJobState::Start,
+                );
+                tx.put_collection_job(&future_collection_job).await.unwrap();
+
+                // Two of these batch aggregations fall within the collection job's batch
+                // interval, with four aggregation jobs outstanding between them.
+                for (start, ord, aggregation_jobs_created, aggregation_jobs_terminated) in
+                    [(1000, 0, 3, 1), (1100, 0, 2, 0), (1200, 0, 5, 0)]
+                {
+                    tx.put_batch_aggregation(
+                        &BatchAggregation::<0, TimeInterval, dummy::Vdaf>::new(
+                            *task.id(),
+                            Interval::new(Time::from_seconds_since_epoch(start), time_precision)
+                                .unwrap(),
+                            aggregation_param,
+                            ord,
+                            Interval::EMPTY,
+                            BatchAggregationState::Aggregating {
+                                aggregate_share: None,
+                                report_count: 0,
+                                checksum: ReportIdChecksum::default(),
+                                aggregation_jobs_created,
+                                aggregation_jobs_terminated,
+                            },
+                        ),
+                    )
+                    .await
+                    .unwrap();
+                }
+
+                // This aggregation job terminates before the throughput window.
+                tx.put_aggregation_job(&AggregationJob::<0, TimeInterval, dummy::Vdaf>::new(
+                    *task.id(),
+                    random(),
+                    aggregation_param,
+                    (),
+                    batch_interval,
+                    AggregationJobState::Finished,
+                    AggregationJobStep::from(1),
+                ))
                 .await
                 .unwrap();
 
-            let deleted_collection_job = CollectionJob::<0, TimeInterval, dummy::Vdaf>::new(
-                *task.id(),
-                random(),
-                Query::new_time_interval(deleted_batch_interval),
-                aggregation_param,
-                deleted_batch_interval,
-                CollectionJobState::Start,
-            );
+                Ok((*collection_job.id(), *future_collection_job.id()))
+            })
+        })
+        .await
+        .unwrap();
+
+    clock.advance(&Duration::from_seconds(1000));
+
+    ds.run_unnamed_tx(|tx| {
+        let task = task.clone();
+        Box::pin(async move {
+            for state in [
+                AggregationJobState::Finished,
+                AggregationJobState::Abandoned,
+                AggregationJobState::InProgress,
+            ] {
+                tx.put_aggregation_job(&AggregationJob::<0, TimeInterval, dummy::Vdaf>::new(
+                    *task.id(),
+                    random(),
+                    aggregation_param,
+                    (),
+                    batch_interval,
+                    state,
+                    AggregationJobStep::from(1),
+                ))
+                .await
+                .unwrap();
+            }
+
+            // Four outstanding aggregation jobs, at two aggregation jobs per ten minutes.
+            assert_eq!(
+                tx.get_collection_job_progress(task.id(), &collection_job_id, &throughput_window)
+                    .await
+                    .unwrap(),
+                Some(CollectionJobProgress::new(
+                    CollectionJobStateCode::Start,
+                    4,
+                    2,
+                    Some(Time::from_seconds_since_epoch(11000 + 1200)),
+                ))
+            );
+
+            // Collection jobs with no outstanding aggregation jobs are estimated to complete now,
+            // even if their batch interval has not yet ended.
+            assert_eq!(
+                tx.get_collection_job_progress(
+                    task.id(),
+                    &future_collection_job_id,
+                    &throughput_window
+                )
+                .await
+                .unwrap(),
+                Some(CollectionJobProgress::new(
+                    CollectionJobStateCode::Start,
+                    0,
+                    2,
+                    Some(Time::from_seconds_since_epoch(11000)),
+                ))
+            );
+
+            assert_eq!(
+                tx.get_collection_job_progress(task.id(), &random(), &throughput_window)
+                    .await
+                    .unwrap(),
+                None
+            );
+
+            Ok(())
+        })
+    })
+    .await
+    .unwrap();
+
+    // With no recent progress, no completion estimate is made.
+    clock.advance(&Duration::from_seconds(1000));
+
+    ds.run_unnamed_tx(|tx| {
+        let task = task.clone();
+        Box::pin(async move {
+            assert_eq!(
+                tx.get_collection_job_progress(task.id(), &collection_job_id, &throughput_window)
+                    .await
+                    .unwrap(),
+                Some(CollectionJobProgress::new(
+                    CollectionJobStateCode::Start,
+                    4,
+                    0,
+                    None,
+                ))
+            );
+            Ok(())
+        })
+    })
+    .await
+    .unwrap();
+}
+
+#[rstest_reuse::apply(schema_versions_template)]
+#[tokio::test]
+async fn get_collection_job_progress_new_task(ephemeral_datastore: EphemeralDatastore) {
+    install_test_trace_subscriber();
+
+    let clock = MockClock::new(Time::from_seconds_since_epoch(10000));
+    let ds = ephemeral_datastore.datastore(clock.clone()).await;
+
+    let time_precision = Duration::from_seconds(100);
+    let task = TaskBuilder::new(task::QueryType::TimeInterval, VdafInstance::Fake)
+        .with_time_precision(time_precision)
+        .build()
+        .leader_view()
+        .unwrap();
+    let batch_interval =
+        Interval::new(Time::from_seconds_since_epoch(9900), time_precision).unwrap();
+    let aggregation_param = dummy::AggregationParam(7);
+    let throughput_window = Duration::from_seconds(600);
+
+    let collection_job_id = ds
+        .run_unnamed_tx(|tx| {
+            let task = task.clone();
+            Box::pin(async move {
+                tx.put_aggregator_task(&task).await.unwrap();
+
+                let collection_job = CollectionJob::<0, TimeInterval, dummy::Vdaf>::new(
+                    *task.id(),
+                    random(),
+                    Query::new_time_interval(batch_interval),
+                    aggregation_param,
+                    batch_interval,
+                    CollectionJobState::Start,
+                );
+                tx.put_collection_job(&collection_job).await.unwrap();
+
+                tx.put_batch_aggregation(&BatchAggregation::<0, TimeInterval, dummy::Vdaf>::new(
+                    *task.id(),
+                    batch_interval,
+                    aggregation_param,
+                    0,
+                    Interval::EMPTY,
+                    BatchAggregationState::Aggregating {
+                        aggregate_share: None,
+                        report_count: 0,
+                        checksum: ReportIdChecksum::default(),
+                        aggregation_jobs_created: 3,
+                        aggregation_jobs_terminated: 1,
+                    },
+                ))
+                .await
+                .unwrap();
+
+                for state in [
+                    AggregationJobState::Finished,
+                    AggregationJobState::InProgress,
+                ] {
+                    tx.put_aggregation_job(&AggregationJob::<0, TimeInterval, dummy::Vdaf>::new(
+                        *task.id(),
+                        random(),
+                        aggregation_param,
+                        (),
+                        batch_interval,
+                        state,
+                        AggregationJobStep::from(0),
+                    ))
+                    .await
+                    .unwrap();
+                }
+
+                Ok(*collection_job.id())
+            })
+        })
+        .await
+        .unwrap();
+
+    // The task's first aggregation job was created twenty seconds ago, so throughput is observed
+    // over those twenty seconds rather than the whole throughput window: two outstanding
+    // aggregation jobs, at one aggregation job per twenty seconds.
+    clock.advance(&Duration::from_seconds(20));
+
+    ds.run_unnamed_tx(|tx| {
+        let task = task.clone();
+        Box::pin(async move {
+            assert_eq!(
+                tx.get_collection_job_progress(task.id(), &collection_job_id, &throughput_window)
+                    .await
+                    .unwrap(),
+                Some(CollectionJobProgress::new(
+                    CollectionJobStateCode::Start,
+                    2,
+                    1,
+                    Some(Time::from_seconds_since_epoch(10020 + 40)),
+                ))
+            );
+            Ok(())
+        })
+    })
+    .await
+    .unwrap();
+}
+
+#[rstest_reuse::apply(schema_versions_template)]
+#[tokio::test]
+async fn update_collection_jobs(ephemeral_datastore: EphemeralDatastore) {
+    // Setup: write collection jobs to the datastore.
+    install_test_trace_subscriber();
+
+    let ds = ephemeral_datastore.datastore(MockClock::default()).await;
+
+    let task = TaskBuilder::new(task::QueryType::TimeInterval, VdafInstance::Fake)
+        .build()
+        .leader_view()
+        .unwrap();
+    let abandoned_batch_interval = Interval::new(
+        Time::from_seconds_since_epoch(100),
+        Duration::from_seconds(100),
+    )
+    .unwrap();
+    let deleted_batch_interval = Interval::new(
+        Time::from_seconds_since_epoch(200),
+        Duration::from_seconds(100),
+    )
+    .unwrap();
+
+    ds.run_tx("test-update-collection-jobs", |tx| {
+        let task = task.clone();
+        Box::pin(async move {
+            tx.put_aggregator_task(&task).await.unwrap();
+
+            let vdaf = dummy::Vdaf::default();
+            let aggregation_param = dummy::AggregationParam(10);
+            let abandoned_collection_job = CollectionJob::<0, TimeInterval, dummy::Vdaf>::new(
+                *task.id(),
+                random(),
+                Query::new_time_interval(abandoned_batch_interval),
+                aggregation_param,
+                abandoned_batch_interval,
+                CollectionJobState::Start,
+            );
+            tx.put_collection_job(&abandoned_collection_job)
+                .await
+                .unwrap();
+
+            let deleted_collection_job = CollectionJob::<0, TimeInterval, dummy::Vdaf>::new(
+                *task.id(),
+                random(),
+                Query::new_time_interval(deleted_batch_interval),
+                aggregation_param,
+                deleted_batch_interval,
+                CollectionJobState::Start,
+            );
             tx.put_collection_job(&deleted_collection_job)
                 .await
                 .unwrap();
@@ -5291,8 +6253,12 @@ async fn delete_expired_client_reports(ephemeral_datastore: EphemeralDatastore)
     let deleted_report_count = ds
         .run_unnamed_tx(|tx| {
             Box::pin(async move {
-                tx.delete_expired_client_reports(&task_id, u64::try_from(i64::MAX).unwrap())
-                    .await
+                tx.delete_expired_client_reports(
+                    &task_id,
+                    &Duration::ZERO,
+                    u64::try_from(i64::MAX).unwrap(),
+                )
+                .await
             })
         })
         .await
@@ -5375,8 +6341,12 @@ async fn delete_expired_client_reports_noop(ephemeral_datastore: EphemeralDatast
     let deleted_report_count = ds
         .run_unnamed_tx(|tx| {
             Box::pin(async move {
-                tx.delete_expired_client_reports(&task_id, u64::try_from(i64::MAX).unwrap())
-                    .await
+                tx.delete_expired_client_reports(
+                    &task_id,
+                    &Duration::ZERO,
+                    u64::try_from(i64::MAX).unwrap(),
+                )
+                .await
             })
         })
         .await
@@ -5759,18 +6729,22 @@ async fn delete_expired_aggregation_artifacts(ephemeral_datastore: EphemeralData
                 try_join!(
                     tx.delete_expired_aggregation_artifacts(
                         &leader_time_interval_task_id,
+                        &Duration::ZERO,
                         u64::try_from(i64::MAX).unwrap(),
                     ),
                     tx.delete_expired_aggregation_artifacts(
                         &helper_time_interval_task_id,
+                        &Duration::ZERO,
                         u64::try_from(i64::MAX).unwrap(),
                     ),
                     tx.delete_expired_aggregation_artifacts(
                         &leader_fixed_size_task_id,
+                        &Duration::ZERO,
                         u64::try_from(i64::MAX).unwrap(),
                     ),
                     tx.delete_expired_aggregation_artifacts(
                         &helper_fixed_size_task_id,
+                        &Duration::ZERO,
                         u64::try_from(i64::MAX).unwrap(),
                     )
                 )
@@ -5874,53 +6848,155 @@ async fn delete_expired_aggregation_artifacts(ephemeral_datastore: EphemeralData
 
 #[rstest_reuse::apply(schema_versions_template)]
 #[tokio::test]
-async fn delete_expired_collection_artifacts(ephemeral_datastore: EphemeralDatastore) {
+async fn delete_terminal_aggregation_jobs(ephemeral_datastore: EphemeralDatastore) {
     install_test_trace_subscriber();
 
     let clock = MockClock::new(OLDEST_ALLOWED_REPORT_TIMESTAMP);
     let ds = ephemeral_datastore.datastore(clock.clone()).await;
+    let ttl = Duration::from_seconds(60);
 
-    // Setup.
-    async fn write_collect_artifacts<Q: TestQueryTypeExt>(
-        tx: &Transaction<'_, MockClock>,
-        task: &AggregatorTask,
-        client_timestamps: &[Time],
-    ) -> (
-        Option<CollectionJobId>,   // collection job ID
-        Option<(TaskId, Vec<u8>)>, // aggregate share job ID (task ID, encoded batch identifier)
-        Option<(TaskId, Vec<u8>)>, // batch ID (task ID, encoded batch identifier)
-        Option<(TaskId, BatchId)>, // outstanding batch ID
-        Option<(TaskId, Vec<u8>)>, // batch aggregation ID (task ID, encoded batch identifier)
-        Option<Time>,              // time bucket start
-    ) {
-        let batch_identifier = Q::batch_identifier_for_client_timestamps(client_timestamps);
-        let client_timestamp_interval = client_timestamps
-            .iter()
-            .fold(Interval::EMPTY, |left, right| {
-                left.merged_with(right).unwrap()
-            });
-
-        let batch_aggregation = BatchAggregation::<0, Q, dummy::Vdaf>::new(
+    let task = TaskBuilder::new(task::QueryType::TimeInterval, VdafInstance::Fake)
+        .build()
+        .leader_view()
+        .unwrap();
+    let aggregation_jobs = [
+        AggregationJobState::InProgress,
+        AggregationJobState::Finished,
+        AggregationJobState::Abandoned,
+        AggregationJobState::Deleted,
+    ]
+    .map(|state| {
+        AggregationJob::<0, TimeInterval, dummy::Vdaf>::new(
             *task.id(),
-            batch_identifier.clone(),
+            random(),
             dummy::AggregationParam(0),
-            0,
-            client_timestamp_interval,
-            BatchAggregationState::Aggregating {
-                aggregate_share: None,
-                report_count: 0,
-                checksum: ReportIdChecksum::default(),
-                aggregation_jobs_created: 3,
-                aggregation_jobs_terminated: 2,
-            },
-        );
-        tx.put_batch_aggregation(&batch_aggregation).await.unwrap();
+            (),
+            Interval::new(OLDEST_ALLOWED_REPORT_TIMESTAMP, Duration::from_seconds(1)).unwrap(),
+            state,
+            AggregationJobStep::from(0),
+        )
+    });
+    let report = LeaderStoredReport::new_dummy(*task.id(), OLDEST_ALLOWED_REPORT_TIMESTAMP);
 
-        if task.role() == &Role::Leader {
-            let collection_job = CollectionJob::<0, Q, dummy::Vdaf>::new(
-                *task.id(),
-                random(),
-                Q::query_for_batch_identifier(&batch_identifier),
+    ds.run_unnamed_tx(|tx| {
+        let (task, aggregation_jobs, report) =
+            (task.clone(), aggregation_jobs.clone(), report.clone());
+        Box::pin(async move {
+            tx.put_aggregator_task(&task).await.unwrap();
+            tx.put_client_report(&dummy::Vdaf::default(), &report)
+                .await
+                .unwrap();
+            for aggregation_job in &aggregation_jobs {
+                tx.put_aggregation_job(aggregation_job).await.unwrap();
+            }
+            tx.put_report_aggregation(
+                &report.as_start_leader_report_aggregation(*aggregation_jobs[1].id(), 0),
+            )
+            .await
+            .unwrap();
+            Ok(())
+        })
+    })
+    .await
+    .unwrap();
+
+    // Jobs are kept until they have been in a terminal state for the TTL.
+    clock.advance(&ttl);
+    let deleted = ds
+        .run_unnamed_tx(|tx| {
+            let task_id = *task.id();
+            Box::pin(async move {
+                tx.delete_terminal_aggregation_jobs(&task_id, &ttl, 10)
+                    .await
+            })
+        })
+        .await
+        .unwrap();
+    assert_eq!(deleted, 0);
+
+    clock.advance(&Duration::from_seconds(1));
+    let (deleted, remaining_aggregation_jobs, report_aggregations) = ds
+        .run_unnamed_tx(|tx| {
+            let task_id = *task.id();
+            let finished_aggregation_job_id = *aggregation_jobs[1].id();
+            Box::pin(async move {
+                Ok((
+                    tx.delete_terminal_aggregation_jobs(&task_id, &ttl, 10)
+                        .await
+                        .unwrap(),
+                    tx.get_aggregation_jobs_for_task::<0, TimeInterval, dummy::Vdaf>(&task_id)
+                        .await
+                        .unwrap(),
+                    tx.get_report_aggregations_for_aggregation_job(
+                        &dummy::Vdaf::default(),
+                        &Role::Leader,
+                        &task_id,
+                        &finished_aggregation_job_id,
+                    )
+                    .await
+                    .unwrap(),
+                ))
+            })
+        })
+        .await
+        .unwrap();
+    assert_eq!(deleted, 3);
+    assert_eq!(
+        remaining_aggregation_jobs,
+        Vec::from([aggregation_jobs[0].clone()])
+    );
+    assert!(report_aggregations.is_empty());
+}
+
+#[rstest_reuse::apply(schema_versions_template)]
+#[tokio::test]
+async fn delete_expired_collection_artifacts(ephemeral_datastore: EphemeralDatastore) {
+    install_test_trace_subscriber();
+
+    let clock = MockClock::new(OLDEST_ALLOWED_REPORT_TIMESTAMP);
+    let ds = ephemeral_datastore.datastore(clock.clone()).await;
+
+    // Setup.
+    async fn write_collect_artifacts<Q: TestQueryTypeExt>(
+        tx: &Transaction<'_, MockClock>,
+        task: &AggregatorTask,
+        client_timestamps: &[Time],
+    ) -> (
+        Option<CollectionJobId>,   // collection job ID
+        Option<(TaskId, Vec<u8>)>, // aggregate share job ID (task ID, encoded batch identifier)
+        Option<(TaskId, Vec<u8>)>, // batch ID (task ID, encoded batch identifier)
+        Option<(TaskId, BatchId)>, // outstanding batch ID
+        Option<(TaskId, Vec<u8>)>, // batch aggregation ID (task ID, encoded batch identifier)
+        Option<Time>,              // time bucket start
+    ) {
+        let batch_identifier = Q::batch_identifier_for_client_timestamps(client_timestamps);
+        let client_timestamp_interval = client_timestamps
+            .iter()
+            .fold(Interval::EMPTY, |left, right| {
+                left.merged_with(right).unwrap()
+            });
+
+        let batch_aggregation = BatchAggregation::<0, Q, dummy::Vdaf>::new(
+            *task.id(),
+            batch_identifier.clone(),
+            dummy::AggregationParam(0),
+            0,
+            client_timestamp_interval,
+            BatchAggregationState::Aggregating {
+                aggregate_share: None,
+                report_count: 0,
+                checksum: ReportIdChecksum::default(),
+                aggregation_jobs_created: 3,
+                aggregation_jobs_terminated: 2,
+            },
+        );
+        tx.put_batch_aggregation(&batch_aggregation).await.unwrap();
+
+        if task.role() == &Role::Leader {
+            let collection_job = CollectionJob::<0, Q, dummy::Vdaf>::new(
+                *task.id(),
+                random(),
+                Q::query_for_batch_identifier(&batch_identifier),
                 dummy::AggregationParam(0),
                 batch_identifier.clone(),
                 CollectionJobState::Start,
@@ -6477,22 +7553,27 @@ async fn delete_expired_collection_artifacts(ephemeral_datastore: EphemeralDatas
                 try_join!(
                     tx.delete_expired_collection_artifacts(
                         &leader_time_interval_task_id,
+                        &Duration::ZERO,
                         u64::try_from(i64::MAX).unwrap(),
                     ),
                     tx.delete_expired_collection_artifacts(
                         &helper_time_interval_task_id,
+                        &Duration::ZERO,
                         u64::try_from(i64::MAX).unwrap(),
                     ),
                     tx.delete_expired_collection_artifacts(
                         &leader_fixed_size_task_id,
+                        &Duration::ZERO,
                         u64::try_from(i64::MAX).unwrap(),
                     ),
                     tx.delete_expired_collection_artifacts(
                         &helper_fixed_size_task_id,
+                        &Duration::ZERO,
                         u64::try_from(i64::MAX).unwrap(),
                     ),
                     tx.delete_expired_collection_artifacts(
                         &leader_fixed_size_time_bucketed_task_id,
+                        &Duration::ZERO,
                         u64::try_from(i64::MAX).unwrap(),
                     )
                 )
@@ -6834,472 +7915,1926 @@ async fn delete_expired_collection_artifacts(ephemeral_datastore: EphemeralDatas
 
 #[rstest_reuse::apply(schema_versions_template)]
 #[tokio::test]
-async fn roundtrip_interval_sql(ephemeral_datastore: EphemeralDatastore) {
+async fn roundtrip_upload_samples(ephemeral_datastore: EphemeralDatastore) {
     install_test_trace_subscriber();
-    let datastore = ephemeral_datastore.datastore(MockClock::default()).await;
-
-    datastore
-        .run_unnamed_tx(|tx| {
-            Box::pin(async move {
-                let interval = tx
-                    .query_one(
-                        "SELECT '[2020-01-01 10:00, 2020-01-01 10:30)'::tsrange AS interval",
-                        &[],
-                    )
-                    .await
-                    .unwrap()
-                    .get::<_, SqlInterval>("interval");
-                let ref_interval = Interval::new(
-                    Time::from_naive_date_time(
-                        &NaiveDate::from_ymd_opt(2020, 1, 1)
-                            .unwrap()
-                            .and_hms_opt(10, 0, 0)
-                            .unwrap(),
-                    ),
-                    Duration::from_minutes(30).unwrap(),
-                )
-                .unwrap();
-                assert_eq!(interval.as_interval(), ref_interval);
 
-                let interval = tx
-                    .query_one(
-                        "SELECT '[1970-02-03 23:00, 1970-02-04 00:00)'::tsrange AS interval",
-                        &[],
-                    )
-                    .await
-                    .unwrap()
-                    .get::<_, SqlInterval>("interval");
-                let ref_interval = Interval::new(
-                    Time::from_naive_date_time(
-                        &NaiveDate::from_ymd_opt(1970, 2, 3)
-                            .unwrap()
-                            .and_hms_opt(23, 0, 0)
-                            .unwrap(),
-                    ),
-                    Duration::from_hours(1).unwrap(),
-                )
-                .unwrap();
-                assert_eq!(interval.as_interval(), ref_interval);
+    let clock = MockClock::new(OLDEST_ALLOWED_REPORT_TIMESTAMP);
+    let ds = ephemeral_datastore.datastore(clock.clone()).await;
+    let ttl = Duration::from_seconds(60);
 
-                let res = tx
-                    .query_one(
-                        "SELECT '[1969-01-01 00:00, 1970-01-01 00:00)'::tsrange AS interval",
-                        &[],
-                    )
-                    .await
-                    .unwrap()
-                    .try_get::<_, SqlInterval>("interval");
-                assert!(res.is_err());
+    let task = TaskBuilder::new(task::QueryType::TimeInterval, VdafInstance::Fake)
+        .build()
+        .leader_view()
+        .unwrap();
+    let other_task = TaskBuilder::new(task::QueryType::TimeInterval, VdafInstance::Fake)
+        .build()
+        .leader_view()
+        .unwrap();
+    let samples = Vec::from([
+        UploadSample::new(
+            *task.id(),
+            random(),
+            clock.now().add(&Duration::from_seconds(5)).unwrap(),
+            clock.now(),
+            Vec::from([0xFF00, 0]),
+            32,
+            120,
+            140,
+        ),
+        UploadSample::new(
+            *task.id(),
+            random(),
+            clock.now().sub(&Duration::from_seconds(5)).unwrap(),
+            clock.now().sub(&Duration::from_seconds(1)).unwrap(),
+            Vec::new(),
+            0,
+            100,
+            100,
+        ),
+    ]);
+    let other_sample = UploadSample::new(
+        *other_task.id(),
+        random(),
+        clock.now(),
+        clock.now(),
+        Vec::new(),
+        0,
+        100,
+        100,
+    );
+    assert_eq!(samples[0].timestamp_skew_seconds(), -5);
+    assert_eq!(samples[1].timestamp_skew_seconds(), 4);
 
-                let ok = tx
-                    .query_one(
-                        "SELECT (lower(interval) = '1972-07-21 05:30:00' AND
-                            upper(interval) = '1972-07-21 06:00:00' AND
-                            lower_inc(interval) AND
-                            NOT upper_inc(interval)) AS ok
-                            FROM (VALUES ($1::tsrange)) AS temp (interval)",
-                        &[&SqlInterval::from(
-                            Interval::new(
-                                Time::from_naive_date_time(
-                                    &NaiveDate::from_ymd_opt(1972, 7, 21)
-                                        .unwrap()
-                                        .and_hms_opt(5, 30, 0)
-                                        .unwrap(),
-                                ),
-                                Duration::from_minutes(30).unwrap(),
-                            )
-                            .unwrap(),
-                        )],
-                    )
-                    .await
-                    .unwrap()
-                    .get::<_, bool>("ok");
-                assert!(ok);
+    ds.run_unnamed_tx(|tx| {
+        let (task, other_task) = (task.clone(), other_task.clone());
+        let (samples, other_sample) = (samples.clone(), other_sample.clone());
+        Box::pin(async move {
+            tx.put_aggregator_task(&task).await.unwrap();
+            tx.put_aggregator_task(&other_task).await.unwrap();
+            for sample in samples.iter().rev() {
+                tx.put_upload_sample(sample, &ttl).await.unwrap();
+            }
+            tx.put_upload_sample(&other_sample, &ttl).await.unwrap();
+            Ok(())
+        })
+    })
+    .await
+    .unwrap();
 
-                let ok = tx
-                    .query_one(
-                        "SELECT (lower(interval) = '2021-10-05 00:00:00' AND
-                            upper(interval) = '2021-10-06 00:00:00' AND
-                            lower_inc(interval) AND
-                            NOT upper_inc(interval)) AS ok
-                            FROM (VALUES ($1::tsrange)) AS temp (interval)",
-                        &[&SqlInterval::from(
-                            Interval::new(
-                                Time::from_naive_date_time(
-                                    &NaiveDate::from_ymd_opt(2021, 10, 5)
-                                        .unwrap()
-                                        .and_hms_opt(0, 0, 0)
-                                        .unwrap(),
-                                ),
-                                Duration::from_hours(24).unwrap(),
-                            )
-                            .unwrap(),
-                        )],
-                    )
-                    .await
-                    .unwrap()
-                    .get::<_, bool>("ok");
-                assert!(ok);
+    let got_samples = ds
+        .run_unnamed_tx(|tx| {
+            let task_id = *task.id();
+            Box::pin(async move { tx.get_upload_samples_for_task(&task_id).await })
+        })
+        .await
+        .unwrap();
+    assert_eq!(got_samples, samples);
 
-                Ok(())
+    // Once the samples expire, they can no longer be read, and are deleted by garbage collection.
+    clock.advance(&ttl);
+    let (got_samples, deleted) = ds
+        .run_unnamed_tx(|tx| {
+            let task_id = *task.id();
+            Box::pin(async move {
+                Ok((
+                    tx.get_upload_samples_for_task(&task_id).await.unwrap(),
+                    tx.delete_expired_upload_samples(&task_id, 10)
+                        .await
+                        .unwrap(),
+                ))
             })
         })
         .await
         .unwrap();
+    assert!(got_samples.is_empty());
+    assert_eq!(deleted, 2);
 }
 
 #[rstest_reuse::apply(schema_versions_template)]
 #[tokio::test]
-async fn roundtrip_global_hpke_keypair(ephemeral_datastore: EphemeralDatastore) {
+async fn roundtrip_feature_flags(ephemeral_datastore: EphemeralDatastore) {
     install_test_trace_subscriber();
-    let datastore = ephemeral_datastore.datastore(MockClock::default()).await;
-    let clock = datastore.clock.clone();
-    let keypair = generate_test_hpke_config_and_private_key();
-
-    datastore
-        .run_tx("test-put-keys", |tx| {
-            let keypair = keypair.clone();
-            let clock = clock.clone();
-            Box::pin(async move {
-                assert_eq!(tx.get_global_hpke_keypairs().await.unwrap(), Vec::new());
-                tx.put_global_hpke_keypair(&keypair).await.unwrap();
 
-                let expected_keypair =
+    let ds = ephemeral_datastore
+        .datastore(MockClock::new(OLDEST_ALLOWED_REPORT_TIMESTAMP))
+        .await;
+    let task = TaskBuilder::new(task::QueryType::TimeInterval, VdafInstance::Fake)
+        .build()
+        .leader_view()
+        .unwrap();
+
+    ds.run_unnamed_tx(|tx| {
+        let task = task.clone();
+        Box::pin(async move {
+            tx.put_aggregator_task(&task).await.unwrap();
+
+            tx.put_feature_flag(&FeatureFlag::new("flag".to_string(), None, true))
+                .await
+                .unwrap();
+            tx.put_feature_flag(&FeatureFlag::new(
+                "flag".to_string(),
+                Some(*task.id()),
+                true,
+            ))
+            .await
+            .unwrap();
+
+            // Putting an existing flag overwrites its state.
+            tx.put_feature_flag(&FeatureFlag::new(
+                "flag".to_string(),
+                Some(*task.id()),
+                false,
+            ))
+            .await
+            .unwrap();
+
+            // Flags cannot be set for tasks that do not exist.
+            assert_matches!(
+                tx.put_feature_flag(&FeatureFlag::new("flag".to_string(), Some(random()), true))
+                    .await,
+                Err(Error::MutationTargetNotFound)
+            );
+            Ok(())
+        })
+    })
+    .await
+    .unwrap();
+
+    let got_flags = ds
+        .run_unnamed_tx(|tx| Box::pin(async move { tx.get_feature_flags().await }))
+        .await
+        .unwrap();
+    assert_eq!(
+        got_flags,
+        Vec::from([
+            FeatureFlag::new("flag".to_string(), None, true),
+            FeatureFlag::new("flag".to_string(), Some(*task.id()), false),
+        ])
+    );
+
+    // Deleting the global flag leaves the task's flag alone.
+    let got_flags = ds
+        .run_unnamed_tx(|tx| {
+            Box::pin(async move {
+                tx.delete_feature_flag("flag", None).await.unwrap();
+                assert_matches!(
+                    tx.delete_feature_flag("flag", None).await,
+                    Err(Error::MutationTargetNotFound)
+                );
+                tx.get_feature_flags().await
+            })
+        })
+        .await
+        .unwrap();
+    assert_eq!(
+        got_flags,
+        Vec::from([FeatureFlag::new(
+            "flag".to_string(),
+            Some(*task.id()),
+            false
+        )])
+    );
+
+    // Deleting the task deletes its flags.
+    let got_flags = ds
+        .run_unnamed_tx(|tx| {
+            let task_id = *task.id();
+            Box::pin(async move {
+                tx.delete_task(&task_id).await.unwrap();
+                tx.get_feature_flags().await
+            })
+        })
+        .await
+        .unwrap();
+    assert!(got_flags.is_empty());
+}
+
+#[rstest_reuse::apply(schema_versions_template)]
+#[tokio::test]
+async fn roundtrip_admin_api_keys(ephemeral_datastore: EphemeralDatastore) {
+    install_test_trace_subscriber();
+    let clock = MockClock::new(OLDEST_ALLOWED_REPORT_TIMESTAMP);
+    let ds = ephemeral_datastore.datastore(clock.clone()).await;
+
+    let token = random::<AuthenticationToken>();
+    let key = AdminApiKey::new(
+        "team-a".to_string(),
+        AuthenticationTokenHash::from(&token),
+        Vec::from([random(), random()]),
+        clock.now().add(&Duration::from_seconds(3600)).unwrap(),
+    );
+    let other_key = AdminApiKey::new(
+        "team-b".to_string(),
+        AuthenticationTokenHash::from(&random::<AuthenticationToken>()),
+        Vec::new(),
+        clock.now().add(&Duration::from_seconds(60)).unwrap(),
+    );
+
+    ds.run_unnamed_tx(|tx| {
+        let (key, other_key) = (key.clone(), other_key.clone());
+        Box::pin(async move {
+            tx.put_admin_api_key(&key).await.unwrap();
+            tx.put_admin_api_key(&other_key).await.unwrap();
+
+            // Names and tokens are unique.
+            assert_matches!(
+                tx.put_admin_api_key(&AdminApiKey::new(
+                    "team-a".to_string(),
+                    AuthenticationTokenHash::from(&random::<AuthenticationToken>()),
+                    Vec::new(),
+                    *key.expiry(),
+                ))
+                .await,
+                Err(Error::MutationTargetAlreadyExists)
+            );
+            assert_matches!(
+                tx.put_admin_api_key(&AdminApiKey::new(
+                    "team-c".to_string(),
+                    key.token_hash().clone(),
+                    Vec::new(),
+                    *key.expiry(),
+                ))
+                .await,
+                Err(Error::MutationTargetAlreadyExists)
+            );
+            Ok(())
+        })
+    })
+    .await
+    .unwrap();
+
+    let (got_keys, got_key) = ds
+        .run_unnamed_tx(|tx| {
+            let token_hash = key.token_hash().clone();
+            Box::pin(async move {
+                Ok((
+                    tx.get_admin_api_keys().await.unwrap(),
+                    tx.get_unexpired_admin_api_key(&token_hash).await.unwrap(),
+                ))
+            })
+        })
+        .await
+        .unwrap();
+    assert_eq!(got_keys, Vec::from([key.clone(), other_key.clone()]));
+    assert_eq!(got_key, Some(key.clone()));
+
+    // Expired keys are still listed, but are not returned by token.
+    clock.advance(&Duration::from_seconds(3600));
+    let (got_keys, got_key) = ds
+        .run_unnamed_tx(|tx| {
+            let token_hash = key.token_hash().clone();
+            Box::pin(async move {
+                Ok((
+                    tx.get_admin_api_keys().await.unwrap(),
+                    tx.get_unexpired_admin_api_key(&token_hash).await.unwrap(),
+                ))
+            })
+        })
+        .await
+        .unwrap();
+    assert_eq!(got_keys, Vec::from([key.clone(), other_key.clone()]));
+    assert_eq!(got_key, None);
+
+    let got_keys = ds
+        .run_unnamed_tx(|tx| {
+            Box::pin(async move {
+                tx.delete_admin_api_key("team-a").await.unwrap();
+                assert_matches!(
+                    tx.delete_admin_api_key("team-a").await,
+                    Err(Error::MutationTargetNotFound)
+                );
+                tx.get_admin_api_keys().await
+            })
+        })
+        .await
+        .unwrap();
+    assert_eq!(got_keys, Vec::from([other_key]));
+}
+
+#[rstest_reuse::apply(schema_versions_template)]
+#[tokio::test]
+async fn integrity_checks(ephemeral_datastore: EphemeralDatastore) {
+    install_test_trace_subscriber();
+
+    let ds = ephemeral_datastore
+        .datastore(MockClock::new(OLDEST_ALLOWED_REPORT_TIMESTAMP))
+        .await;
+    let vdaf = dummy::Vdaf::new(1);
+    let task = TaskBuilder::new(task::QueryType::TimeInterval, VdafInstance::Fake)
+        .build()
+        .leader_view()
+        .unwrap();
+    let interval = |start| {
+        Interval::new(
+            Time::from_seconds_since_epoch(start),
+            Duration::from_seconds(100),
+        )
+        .unwrap()
+    };
+    let finished = |report_count| CollectionJobState::Finished {
+        report_count,
+        client_timestamp_interval: interval(0),
+        encrypted_helper_aggregate_share: HpkeCiphertext::new(
+            HpkeConfigId::from(0),
+            Vec::new(),
+            Vec::new(),
+        ),
+        leader_aggregate_share: dummy::AggregateShare(1),
+    };
+    let healthy_collection_job = CollectionJob::<0, TimeInterval, dummy::Vdaf>::new(
+        *task.id(),
+        random(),
+        Query::new_time_interval(interval(0)),
+        dummy::AggregationParam(0),
+        interval(0),
+        finished(1),
+    );
+    let broken_collection_job = CollectionJob::<0, TimeInterval, dummy::Vdaf>::new(
+        *task.id(),
+        random(),
+        Query::new_time_interval(interval(200)),
+        dummy::AggregationParam(0),
+        interval(200),
+        finished(5),
+    );
+
+    ds.run_unnamed_tx(|tx| {
+        let (vdaf, task) = (vdaf.clone(), task.clone());
+        let (healthy_collection_job, broken_collection_job) = (
+            healthy_collection_job.clone(),
+            broken_collection_job.clone(),
+        );
+        Box::pin(async move {
+            tx.put_aggregator_task(&task).await.unwrap();
+
+            // Report aggregations, one of which loses its aggregation job behind the back of the
+            // foreign key constraints.
+            let orphaned_aggregation_job_id: AggregationJobId = random();
+            for aggregation_job_id in [random(), orphaned_aggregation_job_id] {
+                let report =
+                    LeaderStoredReport::new_dummy(*task.id(), Time::from_seconds_since_epoch(0));
+                tx.put_client_report(&vdaf, &report).await.unwrap();
+                tx.put_aggregation_job(&AggregationJob::<0, TimeInterval, dummy::Vdaf>::new(
+                    *task.id(),
+                    aggregation_job_id,
+                    dummy::AggregationParam(0),
+                    (),
+                    interval(0),
+                    AggregationJobState::InProgress,
+                    AggregationJobStep::from(0),
+                ))
+                .await
+                .unwrap();
+                tx.put_report_aggregation(
+                    &report.as_start_leader_report_aggregation(aggregation_job_id, 0),
+                )
+                .await
+                .unwrap();
+            }
+            tx.execute("SET LOCAL session_replication_role = replica", &[])
+                .await
+                .unwrap();
+            tx.execute(
+                "DELETE FROM aggregation_jobs WHERE aggregation_job_id = $1",
+                &[&orphaned_aggregation_job_id.as_ref()],
+            )
+            .await
+            .unwrap();
+            tx.execute("SET LOCAL session_replication_role = DEFAULT", &[])
+                .await
+                .unwrap();
+
+            // Batch aggregations, one of which has terminated more aggregation jobs than it
+            // created.
+            for (batch_interval, aggregation_jobs_terminated) in
+                [(interval(0), 1), (interval(100), 2)]
+            {
+                tx.put_batch_aggregation(&BatchAggregation::<0, TimeInterval, dummy::Vdaf>::new(
+                    *task.id(),
+                    batch_interval,
+                    dummy::AggregationParam(0),
+                    0,
+                    batch_interval,
+                    BatchAggregationState::Collected {
+                        aggregate_share: Some(dummy::AggregateShare(1)),
+                        report_count: 1,
+                        checksum: ReportIdChecksum::default(),
+                        aggregation_jobs_created: 1,
+                        aggregation_jobs_terminated,
+                    },
+                ))
+                .await
+                .unwrap();
+            }
+
+            // Collection jobs, one of which was finished over a batch with no batch aggregations.
+            // Unfinished collection jobs are not checked.
+            for collection_job in [&healthy_collection_job, &broken_collection_job] {
+                tx.put_collection_job(collection_job).await.unwrap();
+                tx.update_collection_job(collection_job).await.unwrap();
+            }
+            tx.put_collection_job(&CollectionJob::<0, TimeInterval, dummy::Vdaf>::new(
+                *task.id(),
+                random(),
+                Query::new_time_interval(interval(300)),
+                dummy::AggregationParam(0),
+                interval(300),
+                CollectionJobState::Start,
+            ))
+            .await
+            .unwrap();
+
+            Ok(())
+        })
+    })
+    .await
+    .unwrap();
+
+    ds.run_unnamed_tx(|tx| {
+        Box::pin(async move {
+            assert_eq!(tx.count_orphaned_report_aggregations().await.unwrap(), 1);
+            assert_eq!(
+                tx.count_batch_aggregations_with_impossible_counts()
+                    .await
+                    .unwrap(),
+                1
+            );
+            assert_eq!(
+                tx.count_collection_jobs_missing_batch_aggregations()
+                    .await
+                    .unwrap(),
+                1
+            );
+
+            assert_eq!(tx.delete_orphaned_report_aggregations().await.unwrap(), 1);
+            assert_eq!(
+                tx.delete_batch_aggregations_with_impossible_counts()
+                    .await
+                    .unwrap(),
+                1
+            );
+            assert_eq!(
+                tx.abandon_collection_jobs_missing_batch_aggregations()
+                    .await
+                    .unwrap(),
+                1
+            );
+
+            assert_eq!(tx.count_orphaned_report_aggregations().await.unwrap(), 0);
+            assert_eq!(
+                tx.count_batch_aggregations_with_impossible_counts()
+                    .await
+                    .unwrap(),
+                0
+            );
+            assert_eq!(
+                tx.count_collection_jobs_missing_batch_aggregations()
+                    .await
+                    .unwrap(),
+                0
+            );
+            Ok(())
+        })
+    })
+    .await
+    .unwrap();
+
+    let (healthy, broken) = ds
+        .run_unnamed_tx(|tx| {
+            let (vdaf, task_id) = (vdaf.clone(), *task.id());
+            let collection_job_ids = (*healthy_collection_job.id(), *broken_collection_job.id());
+            Box::pin(async move {
+                Ok((
+                    tx.get_collection_job::<0, TimeInterval, dummy::Vdaf>(
+                        &vdaf,
+                        &task_id,
+                        &collection_job_ids.0,
+                    )
+                    .await
+                    .unwrap()
+                    .unwrap(),
+                    tx.get_collection_job::<0, TimeInterval, dummy::Vdaf>(
+                        &vdaf,
+                        &task_id,
+                        &collection_job_ids.1,
+                    )
+                    .await
+                    .unwrap()
+                    .unwrap(),
+                ))
+            })
+        })
+        .await
+        .unwrap();
+    assert_eq!(healthy, healthy_collection_job);
+    assert_eq!(broken.state(), &CollectionJobState::Abandoned);
+}
+
+#[rstest_reuse::apply(schema_versions_template)]
+#[tokio::test]
+async fn roundtrip_interval_sql(ephemeral_datastore: EphemeralDatastore) {
+    install_test_trace_subscriber();
+    let datastore = ephemeral_datastore.datastore(MockClock::default()).await;
+
+    datastore
+        .run_unnamed_tx(|tx| {
+            Box::pin(async move {
+                let interval = tx
+                    .query_one(
+                        "SELECT '[2020-01-01 10:00, 2020-01-01 10:30)'::tsrange AS interval",
+                        &[],
+                    )
+                    .await
+                    .unwrap()
+                    .get::<_, SqlInterval>("interval");
+                let ref_interval = Interval::new(
+                    Time::from_naive_date_time(
+                        &NaiveDate::from_ymd_opt(2020, 1, 1)
+                            .unwrap()
+                            .and_hms_opt(10, 0, 0)
+                            .unwrap(),
+                    ),
+                    Duration::from_minutes(30).unwrap(),
+                )
+                .unwrap();
+                assert_eq!(interval.as_interval(), ref_interval);
+
+                let interval = tx
+                    .query_one(
+                        "SELECT '[1970-02-03 23:00, 1970-02-04 00:00)'::tsrange AS interval",
+                        &[],
+                    )
+                    .await
+                    .unwrap()
+                    .get::<_, SqlInterval>("interval");
+                let ref_interval = Interval::new(
+                    Time::from_naive_date_time(
+                        &NaiveDate::from_ymd_opt(1970, 2, 3)
+                            .unwrap()
+                            .and_hms_opt(23, 0, 0)
+                            .unwrap(),
+                    ),
+                    Duration::from_hours(1).unwrap(),
+                )
+                .unwrap();
+                assert_eq!(interval.as_interval(), ref_interval);
+
+                let res = tx
+                    .query_one(
+                        "SELECT '[1969-01-01 00:00, 1970-01-01 00:00)'::tsrange AS interval",
+                        &[],
+                    )
+                    .await
+                    .unwrap()
+                    .try_get::<_, SqlInterval>("interval");
+                assert!(res.is_err());
+
+                let ok = tx
+                    .query_one(
+                        "SELECT (lower(interval) = '1972-07-21 05:30:00' AND
+                            upper(interval) = '1972-07-21 06:00:00' AND
+                            lower_inc(interval) AND
+                            NOT upper_inc(interval)) AS ok
+                            FROM (VALUES ($1::tsrange)) AS temp (interval)",
+                        &[&SqlInterval::from(
+                            Interval::new(
+                                Time::from_naive_date_time(
+                                    &NaiveDate::from_ymd_opt(1972, 7, 21)
+                                        .unwrap()
+                                        .and_hms_opt(5, 30, 0)
+                                        .unwrap(),
+                                ),
+                                Duration::from_minutes(30).unwrap(),
+                            )
+                            .unwrap(),
+                        )],
+                    )
+                    .await
+                    .unwrap()
+                    .get::<_, bool>("ok");
+                assert!(ok);
+
+                let ok = tx
+                    .query_one(
+                        "SELECT (lower(interval) = '2021-10-05 00:00:00' AND
+                            upper(interval) = '2021-10-06 00:00:00' AND
+                            lower_inc(interval) AND
+                            NOT upper_inc(interval)) AS ok
+                            FROM (VALUES ($1::tsrange)) AS temp (interval)",
+                        &[&SqlInterval::from(
+                            Interval::new(
+                                Time::from_naive_date_time(
+                                    &NaiveDate::from_ymd_opt(2021, 10, 5)
+                                        .unwrap()
+                                        .and_hms_opt(0, 0, 0)
+                                        .unwrap(),
+                                ),
+                                Duration::from_hours(24).unwrap(),
+                            )
+                            .unwrap(),
+                        )],
+                    )
+                    .await
+                    .unwrap()
+                    .get::<_, bool>("ok");
+                assert!(ok);
+
+                Ok(())
+            })
+        })
+        .await
+        .unwrap();
+}
+
+#[rstest_reuse::apply(schema_versions_template)]
+#[tokio::test]
+async fn roundtrip_global_hpke_keypair(ephemeral_datastore: EphemeralDatastore) {
+    install_test_trace_subscriber();
+    let datastore = ephemeral_datastore.datastore(MockClock::default()).await;
+    let clock = datastore.clock.clone();
+    let keypair = generate_test_hpke_config_and_private_key();
+
+    datastore
+        .run_tx("test-put-keys", |tx| {
+            let keypair = keypair.clone();
+            let clock = clock.clone();
+            Box::pin(async move {
+                assert_eq!(tx.get_global_hpke_keypairs().await.unwrap(), Vec::new());
+                tx.put_global_hpke_keypair(&keypair).await.unwrap();
+
+                let expected_keypair =
                     GlobalHpkeKeypair::new(keypair.clone(), HpkeKeyState::Pending, clock.now());
                 assert_eq!(
-                    tx.get_global_hpke_keypairs().await.unwrap(),
-                    Vec::from([expected_keypair.clone()])
+                    tx.get_global_hpke_keypairs().await.unwrap(),
+                    Vec::from([expected_keypair.clone()])
+                );
+                assert_eq!(
+                    tx.get_global_hpke_keypair(keypair.config().id())
+                        .await
+                        .unwrap()
+                        .unwrap(),
+                    expected_keypair
+                );
+
+                // Try modifying state.
+                clock.advance(&Duration::from_seconds(100));
+                tx.set_global_hpke_keypair_state(keypair.config().id(), &HpkeKeyState::Active)
+                    .await
+                    .unwrap();
+                assert_eq!(
+                    tx.get_global_hpke_keypair(keypair.config().id())
+                        .await
+                        .unwrap()
+                        .unwrap(),
+                    GlobalHpkeKeypair::new(keypair.clone(), HpkeKeyState::Active, clock.now(),)
+                );
+
+                clock.advance(&Duration::from_seconds(100));
+                tx.set_global_hpke_keypair_state(keypair.config().id(), &HpkeKeyState::Expired)
+                    .await
+                    .unwrap();
+                assert_eq!(
+                    tx.get_global_hpke_keypair(keypair.config().id())
+                        .await
+                        .unwrap()
+                        .unwrap(),
+                    GlobalHpkeKeypair::new(keypair.clone(), HpkeKeyState::Expired, clock.now(),)
+                );
+
+                Ok(())
+            })
+        })
+        .await
+        .unwrap();
+
+    // Should not be able to set keypair with the same id.
+    assert_matches!(
+        datastore
+            .run_unnamed_tx(|tx| {
+                let keypair = keypair.clone();
+                Box::pin(async move { tx.put_global_hpke_keypair(&keypair).await })
+            })
+            .await,
+        Err(Error::Db(_))
+    );
+
+    datastore
+        .run_unnamed_tx(|tx| {
+            let keypair = keypair.clone();
+            Box::pin(async move {
+                tx.delete_global_hpke_keypair(keypair.config().id())
+                    .await
+                    .unwrap();
+                assert_eq!(tx.get_global_hpke_keypairs().await.unwrap(), Vec::new());
+                assert_matches!(
+                    tx.get_global_hpke_keypair(keypair.config().id())
+                        .await
+                        .unwrap(),
+                    None
+                );
+
+                tx.check_timestamp_columns("global_hpke_keys", "test-put-keys", true)
+                    .await;
+
+                Ok(())
+            })
+        })
+        .await
+        .unwrap();
+}
+
+#[rstest_reuse::apply(schema_versions_template)]
+#[tokio::test]
+async fn rotate_task_hpke_keypairs(ephemeral_datastore: EphemeralDatastore) {
+    install_test_trace_subscriber();
+    let datastore = ephemeral_datastore.datastore(MockClock::default()).await;
+    let clock = datastore.clock.clone();
+
+    let task = TaskBuilder::new(task::QueryType::TimeInterval, VdafInstance::Fake)
+        .build()
+        .leader_view()
+        .unwrap();
+    datastore.put_aggregator_task(&task).await.unwrap();
+    let old_configs: Vec<_> = task
+        .hpke_keys()
+        .values()
+        .map(|keypair| keypair.config().clone())
+        .collect();
+    let new_keypair = generate_test_hpke_config_and_private_key_with_id(
+        (0..=u8::MAX)
+            .find(|id| !task.hpke_keys().contains_key(&HpkeConfigId::from(*id)))
+            .unwrap(),
+    );
+    let retire_at = clock.now().add(&Duration::from_seconds(100)).unwrap();
+
+    datastore
+        .run_unnamed_tx(|tx| {
+            let task_id = *task.id();
+            let old_configs = old_configs.clone();
+            let new_keypair = new_keypair.clone();
+            Box::pin(async move {
+                tx.put_task_hpke_keypair(&task_id, &new_keypair)
+                    .await
+                    .unwrap();
+                assert_eq!(
+                    tx.retire_task_hpke_keypairs(&task_id, new_keypair.config().id(), &retire_at)
+                        .await
+                        .unwrap(),
+                    old_configs.len() as u64
+                );
+
+                let mut expected_configs: Vec<_> = old_configs
+                    .iter()
+                    .map(|config| TaskHpkeConfig::new(config.clone(), Some(retire_at)))
+                    .chain(iter::once(TaskHpkeConfig::new(
+                        new_keypair.config().clone(),
+                        None,
+                    )))
+                    .collect();
+                expected_configs.sort_by_key(|config| *config.config().id());
+                assert_eq!(
+                    tx.get_task_hpke_configs(&task_id).await.unwrap(),
+                    expected_configs
+                );
+
+                // The old keypairs are still used until they are retired.
+                assert_eq!(
+                    tx.get_aggregator_task(&task_id)
+                        .await
+                        .unwrap()
+                        .unwrap()
+                        .hpke_keys()
+                        .len(),
+                    old_configs.len() + 1
+                );
+                assert_eq!(
+                    tx.delete_retired_task_hpke_keypairs(&task_id)
+                        .await
+                        .unwrap(),
+                    0
+                );
+
+                Ok(())
+            })
+        })
+        .await
+        .unwrap();
+
+    clock.advance(&Duration::from_seconds(100));
+
+    datastore
+        .run_unnamed_tx(|tx| {
+            let task_id = *task.id();
+            let old_configs = old_configs.clone();
+            let new_keypair = new_keypair.clone();
+            Box::pin(async move {
+                let task = tx.get_aggregator_task(&task_id).await.unwrap().unwrap();
+                assert_eq!(
+                    task.hpke_keys(),
+                    &HashMap::from([(*new_keypair.config().id(), new_keypair.clone())])
+                );
+
+                assert_eq!(
+                    tx.delete_retired_task_hpke_keypairs(&task_id)
+                        .await
+                        .unwrap(),
+                    old_configs.len() as u64
+                );
+                assert_eq!(
+                    tx.get_task_hpke_configs(&task_id).await.unwrap(),
+                    Vec::from([TaskHpkeConfig::new(new_keypair.config().clone(), None)])
+                );
+
+                Ok(())
+            })
+        })
+        .await
+        .unwrap();
+
+    // Should not be able to put a keypair with the same id.
+    assert_matches!(
+        datastore
+            .run_unnamed_tx(|tx| {
+                let task_id = *task.id();
+                let new_keypair = new_keypair.clone();
+                Box::pin(async move { tx.put_task_hpke_keypair(&task_id, &new_keypair).await })
+            })
+            .await,
+        Err(Error::MutationTargetAlreadyExists)
+    );
+}
+
+#[rstest_reuse::apply(schema_versions_template)]
+#[tokio::test]
+async fn roundtrip_taskprov_peer_aggregator(ephemeral_datastore: EphemeralDatastore) {
+    install_test_trace_subscriber();
+    let datastore = ephemeral_datastore.datastore(MockClock::default()).await;
+
+    // Basic aggregator.
+    let example_leader_peer_aggregator =
+        PeerAggregatorBuilder::new().with_role(Role::Leader).build();
+    let example_helper_peer_aggregator = PeerAggregatorBuilder::new()
+        .with_role(Role::Helper)
+        .with_aggregator_auth_tokens(Vec::from([random(), random()]))
+        .with_collector_auth_tokens(Vec::new())
+        .build();
+    let another_example_leader_peer_aggregator = PeerAggregatorBuilder::new()
+        .with_endpoint(Url::parse("https://another.example.com/").unwrap())
+        .with_aggregator_auth_tokens(Vec::new())
+        .with_collector_auth_tokens(Vec::from([random(), random()]))
+        .build();
+
+    datastore
+        .run_tx("test-put-peer-aggregator", |tx| {
+            let example_leader_peer_aggregator = example_leader_peer_aggregator.clone();
+            let example_helper_peer_aggregator = example_helper_peer_aggregator.clone();
+            let another_example_leader_peer_aggregator =
+                another_example_leader_peer_aggregator.clone();
+            Box::pin(async move {
+                tx.put_taskprov_peer_aggregator(&example_leader_peer_aggregator)
+                    .await
+                    .unwrap();
+                tx.put_taskprov_peer_aggregator(&example_helper_peer_aggregator)
+                    .await
+                    .unwrap();
+                tx.put_taskprov_peer_aggregator(&another_example_leader_peer_aggregator)
+                    .await
+                    .unwrap();
+                Ok(())
+            })
+        })
+        .await
+        .unwrap();
+
+    // Should not be able to put an aggregator with the same endpoint and role.
+    assert_matches!(
+        datastore
+            .run_unnamed_tx(|tx| {
+                Box::pin(async move {
+                    let colliding_peer_aggregator = PeerAggregatorBuilder::new().build();
+                    tx.put_taskprov_peer_aggregator(&colliding_peer_aggregator)
+                        .await
+                })
+            })
+            .await,
+        Err(Error::MutationTargetAlreadyExists)
+    );
+
+    datastore
+        .run_unnamed_tx(|tx| {
+            let example_leader_peer_aggregator = example_leader_peer_aggregator.clone();
+            let example_helper_peer_aggregator = example_helper_peer_aggregator.clone();
+            let another_example_leader_peer_aggregator =
+                another_example_leader_peer_aggregator.clone();
+            Box::pin(async move {
+                for peer in [
+                    example_leader_peer_aggregator.clone(),
+                    example_helper_peer_aggregator.clone(),
+                    another_example_leader_peer_aggregator.clone(),
+                ] {
+                    assert_eq!(
+                        tx.get_taskprov_peer_aggregator(peer.endpoint(), peer.role())
+                            .await
+                            .unwrap(),
+                        Some(peer.clone()),
+                    );
+                }
+
+                assert_eq!(
+                    tx.get_taskprov_peer_aggregators().await.unwrap(),
+                    Vec::from([
+                        example_leader_peer_aggregator.clone(),
+                        example_helper_peer_aggregator.clone(),
+                        another_example_leader_peer_aggregator.clone(),
+                    ])
+                );
+
+                for peer in [
+                    example_leader_peer_aggregator.clone(),
+                    example_helper_peer_aggregator.clone(),
+                    another_example_leader_peer_aggregator.clone(),
+                ] {
+                    tx.delete_taskprov_peer_aggregator(peer.endpoint(), peer.role())
+                        .await
+                        .unwrap();
+                }
+                assert_eq!(
+                    tx.get_taskprov_peer_aggregators().await.unwrap(),
+                    Vec::new()
+                );
+
+                tx.check_timestamp_columns(
+                    "taskprov_peer_aggregators",
+                    "test-put-peer-aggregator",
+                    false,
+                )
+                .await;
+
+                tx.check_timestamp_columns(
+                    "taskprov_aggregator_auth_tokens",
+                    "test-put-peer-aggregator",
+                    false,
+                )
+                .await;
+                tx.check_timestamp_columns(
+                    "taskprov_collector_auth_tokens",
+                    "test-put-peer-aggregator",
+                    false,
+                )
+                .await;
+
+                Ok(())
+            })
+        })
+        .await
+        .unwrap();
+}
+
+#[rstest_reuse::apply(schema_versions_template)]
+#[tokio::test]
+async fn reject_expired_reports_with_same_id(ephemeral_datastore: EphemeralDatastore) {
+    install_test_trace_subscriber();
+    let clock = MockClock::default();
+    let datastore = ephemeral_datastore.datastore(clock.clone()).await;
+
+    let report_expiry_age = Duration::from_seconds(60);
+    let task = TaskBuilder::new(task::QueryType::TimeInterval, VdafInstance::Fake)
+        .with_report_expiry_age(Some(report_expiry_age))
+        .build()
+        .leader_view()
+        .unwrap();
+
+    datastore.put_aggregator_task(&task).await.unwrap();
+
+    // Use same ID for each report.
+    let report_id = random();
+
+    datastore
+        .run_unnamed_tx(|tx| {
+            let report = LeaderStoredReport::<0, dummy::Vdaf>::new(
+                *task.id(),
+                ReportMetadata::new(report_id, clock.now()),
+                (),
+                Vec::new(),
+                dummy::InputShare::default(),
+                HpkeCiphertext::new(
+                    HpkeConfigId::from(13),
+                    Vec::from("encapsulated_context_0"),
+                    Vec::from("payload_0"),
+                ),
+            );
+            Box::pin(async move { tx.put_client_report(&dummy::Vdaf::default(), &report).await })
+        })
+        .await
+        .unwrap();
+
+    // Advance the clock well past the report expiry age.
+    clock.advance(&report_expiry_age.add(&report_expiry_age).unwrap());
+
+    // Insert a client report with the same ID, but a more current timestamp.
+    let result = datastore
+        .run_unnamed_tx(|tx| {
+            let report = LeaderStoredReport::<0, dummy::Vdaf>::new(
+                *task.id(),
+                ReportMetadata::new(report_id, clock.now()),
+                (),
+                Vec::new(),
+                dummy::InputShare::default(),
+                HpkeCiphertext::new(
+                    HpkeConfigId::from(13),
+                    Vec::from("encapsulated_context_0"),
+                    Vec::from("payload_0"),
+                ),
+            );
+            Box::pin(async move { tx.put_client_report(&dummy::Vdaf::default(), &report).await })
+        })
+        .await
+        .unwrap();
+    assert_eq!(result, ClientReportWriteOutcome::Conflict);
+}
+
+#[rstest_reuse::apply(schema_versions_template)]
+#[tokio::test]
+async fn roundtrip_task_upload_counter(ephemeral_datastore: EphemeralDatastore) {
+    install_test_trace_subscriber();
+    let clock = MockClock::default();
+    let datastore = ephemeral_datastore.datastore(clock.clone()).await;
+
+    let task = TaskBuilder::new(task::QueryType::TimeInterval, VdafInstance::Fake)
+        .build()
+        .leader_view()
+        .unwrap();
+
+    datastore.put_aggregator_task(&task).await.unwrap();
+
+    datastore
+        .run_unnamed_tx(|tx| {
+            let task_id = *task.id();
+            Box::pin(async move {
+                // Returns None for non-existent task.
+                let counter = tx.get_task_upload_counter(&random()).await.unwrap();
+                assert_eq!(counter, None);
+
+                // Returns Some for a task that has just been created and has no counters.
+                let counter = tx.get_task_upload_counter(&task_id).await.unwrap();
+                assert_eq!(counter, Some(TaskUploadCounter::default()));
+
+                let ord = thread_rng().gen_range(0..32);
+                tx.increment_task_upload_counter(
+                    &task_id,
+                    ord,
+                    &TaskUploadCounter::new_with_values(2, 4, 6, 8, 10, 100, 25, 12),
+                )
+                .await
+                .unwrap();
+
+                let ord = thread_rng().gen_range(0..32);
+                tx.increment_task_upload_counter(
+                    &task_id,
+                    ord,
+                    &TaskUploadCounter::new_with_values(0, 0, 0, 0, 0, 0, 0, 8),
+                )
+                .await
+                .unwrap();
+
+                let ord = thread_rng().gen_range(0..32);
+                tx.increment_task_upload_counter(&task_id, ord, &TaskUploadCounter::default())
+                    .await
+                    .unwrap();
+
+                let counter = tx.get_task_upload_counter(&task_id).await.unwrap();
+                assert_eq!(
+                    counter,
+                    Some(TaskUploadCounter {
+                        interval_collected: 2,
+                        report_decode_failure: 4,
+                        report_decrypt_failure: 6,
+                        report_expired: 8,
+                        report_outdated_key: 10,
+                        report_success: 100,
+                        report_too_early: 25,
+                        task_expired: 20,
+                    })
                 );
-                assert_eq!(
-                    tx.get_global_hpke_keypair(keypair.config().id())
-                        .await
-                        .unwrap()
+
+                Ok(())
+            })
+        })
+        .await
+        .unwrap();
+}
+
+#[rstest_reuse::apply(schema_versions_template)]
+#[tokio::test]
+async fn leader_lease_acquire_release(ephemeral_datastore: EphemeralDatastore) {
+    install_test_trace_subscriber();
+    let clock = MockClock::default();
+    let datastore = ephemeral_datastore.datastore(clock.clone()).await;
+    const LEASE_DURATION: StdDuration = StdDuration::from_secs(100);
+
+    let try_acquire = |holder: &'static str| {
+        datastore.run_unnamed_tx(move |tx| {
+            Box::pin(async move {
+                tx.try_acquire_leader_lease("gc", holder, &LEASE_DURATION)
+                    .await
+            })
+        })
+    };
+
+    assert_eq!(
+        datastore
+            .run_unnamed_tx(|tx| Box::pin(async move { tx.get_leader_lease("gc").await }))
+            .await
+            .unwrap(),
+        None
+    );
+
+    // The first replica acquires the unheld lease.
+    let start = clock.now().as_naive_date_time().unwrap();
+    let lease = try_acquire("replica-1").await.unwrap();
+    assert_eq!(lease.name(), "gc");
+    assert_eq!(lease.holder(), "replica-1");
+    assert_eq!(*lease.acquired_at(), start);
+    assert_eq!(
+        *lease.lease_expiry_time(),
+        start + chrono::Duration::try_seconds(100).unwrap()
+    );
+
+    // Another replica can't acquire the lease while it is held.
+    clock.advance(&Duration::from_seconds(50));
+    let lease = try_acquire("replica-2").await.unwrap();
+    assert_eq!(lease.holder(), "replica-1");
+
+    // The holder can renew the lease, which retains the original acquisition time.
+    let lease = try_acquire("replica-1").await.unwrap();
+    assert_eq!(lease.holder(), "replica-1");
+    assert_eq!(*lease.acquired_at(), start);
+    assert_eq!(
+        *lease.lease_expiry_time(),
+        start + chrono::Duration::try_seconds(150).unwrap()
+    );
+
+    // Once the lease expires, another replica takes it over.
+    clock.advance(&Duration::from_seconds(100));
+    let takeover = clock.now().as_naive_date_time().unwrap();
+    let lease = try_acquire("replica-2").await.unwrap();
+    assert_eq!(lease.holder(), "replica-2");
+    assert_eq!(*lease.acquired_at(), takeover);
+
+    // Only the holder can release the lease, after which another replica can acquire it.
+    let result = datastore
+        .run_unnamed_tx(|tx| {
+            Box::pin(async move { tx.release_leader_lease("gc", "replica-1").await })
+        })
+        .await;
+    assert_matches!(result, Err(Error::MutationTargetNotFound));
+    datastore
+        .run_unnamed_tx(|tx| {
+            Box::pin(async move { tx.release_leader_lease("gc", "replica-2").await })
+        })
+        .await
+        .unwrap();
+    let lease = try_acquire("replica-1").await.unwrap();
+    assert_eq!(lease.holder(), "replica-1");
+    assert_eq!(*lease.acquired_at(), takeover);
+}
+
+#[rstest_reuse::apply(schema_versions_template)]
+#[tokio::test]
+async fn promote_deployment(ephemeral_datastore: EphemeralDatastore) {
+    install_test_trace_subscriber();
+    let clock = MockClock::new(OLDEST_ALLOWED_REPORT_TIMESTAMP);
+    let ds = ephemeral_datastore.datastore(clock.clone()).await;
+    const LEASE_DURATION: StdDuration = StdDuration::from_secs(300);
+
+    let task = TaskBuilder::new(task::QueryType::TimeInterval, VdafInstance::Prio3Count)
+        .with_report_expiry_age(Some(REPORT_EXPIRY_AGE))
+        .build()
+        .leader_view()
+        .unwrap();
+    ds.run_unnamed_tx(|tx| {
+        let task = task.clone();
+        Box::pin(async move {
+            tx.put_aggregator_task(&task).await.unwrap();
+            tx.put_aggregation_job(
+                &AggregationJob::<VERIFY_KEY_LENGTH, TimeInterval, Prio3Count>::new(
+                    *task.id(),
+                    random(),
+                    (),
+                    (),
+                    Interval::new(OLDEST_ALLOWED_REPORT_TIMESTAMP, Duration::from_seconds(1))
                         .unwrap(),
-                    expected_keypair
-                );
+                    AggregationJobState::InProgress,
+                    AggregationJobStep::from(0),
+                ),
+            )
+            .await
+            .unwrap();
+            assert_eq!(tx.get_active_deployment().await.unwrap(), None);
+            Ok(())
+        })
+    })
+    .await
+    .unwrap();
 
-                // Try modifying state.
-                clock.advance(&Duration::from_seconds(100));
-                tx.set_global_hpke_keypair_state(keypair.config().id(), &HpkeKeyState::Active)
+    // The first promotion starts the first epoch.
+    let promoted_at = clock.now().as_naive_date_time().unwrap();
+    let active_deployment = ds
+        .run_unnamed_tx(|tx| Box::pin(async move { tx.promote_deployment("east").await }))
+        .await
+        .unwrap();
+    assert_eq!(
+        active_deployment,
+        ActiveDeployment::new("east".to_string(), 1, promoted_at)
+    );
+
+    // The active deployment takes out a job lease and a leader lease.
+    let leases = ds
+        .run_unnamed_tx(|tx| {
+            Box::pin(async move {
+                let leases = tx
+                    .acquire_incomplete_aggregation_jobs(&LEASE_DURATION, 10)
                     .await
                     .unwrap();
-                assert_eq!(
-                    tx.get_global_hpke_keypair(keypair.config().id())
+                tx.try_acquire_leader_lease("gc", "east-1", &LEASE_DURATION)
+                    .await
+                    .unwrap();
+                Ok(leases)
+            })
+        })
+        .await
+        .unwrap();
+    assert_eq!(leases.len(), 1);
+
+    // Promoting the standby deployment advances the epoch, and expires the outstanding leases
+    // without waiting for them to expire.
+    clock.advance(&Duration::from_seconds(10));
+    let promoted_at = clock.now().as_naive_date_time().unwrap();
+    let active_deployment = ds
+        .run_unnamed_tx(|tx| Box::pin(async move { tx.promote_deployment("west").await }))
+        .await
+        .unwrap();
+    assert_eq!(
+        active_deployment,
+        ActiveDeployment::new("west".to_string(), 2, promoted_at)
+    );
+    assert_eq!(
+        ds.run_unnamed_tx(|tx| Box::pin(async move { tx.get_active_deployment().await }))
+            .await
+            .unwrap(),
+        Some(active_deployment)
+    );
+
+    let (reacquired_leases, leader_lease) = ds
+        .run_unnamed_tx(|tx| {
+            Box::pin(async move {
+                Ok((
+                    tx.acquire_incomplete_aggregation_jobs(&LEASE_DURATION, 10)
+                        .await
+                        .unwrap(),
+                    tx.try_acquire_leader_lease("gc", "west-1", &LEASE_DURATION)
                         .await
-                        .unwrap()
                         .unwrap(),
-                    GlobalHpkeKeypair::new(keypair.clone(), HpkeKeyState::Active, clock.now(),)
-                );
+                ))
+            })
+        })
+        .await
+        .unwrap();
+    assert_eq!(reacquired_leases.len(), 1);
+    assert_eq!(reacquired_leases[0].leased(), leases[0].leased());
+    assert_eq!(leader_lease.holder(), "west-1");
 
-                clock.advance(&Duration::from_seconds(100));
-                tx.set_global_hpke_keypair_state(keypair.config().id(), &HpkeKeyState::Expired)
+    // The previously active deployment's lease is fenced: work committed under it fails.
+    let lease = Arc::new(leases.into_iter().next().unwrap());
+    let result = ds
+        .run_unnamed_tx(|tx| {
+            let lease = Arc::clone(&lease);
+            Box::pin(async move { tx.release_aggregation_job(&lease, None).await })
+        })
+        .await;
+    assert_matches!(result, Err(Error::MutationTargetNotFound));
+}
+
+#[rstest_reuse::apply(schema_versions_template)]
+#[tokio::test]
+async fn task_lifecycle(ephemeral_datastore: EphemeralDatastore) {
+    install_test_trace_subscriber();
+
+    let clock = MockClock::new(OLDEST_ALLOWED_REPORT_TIMESTAMP);
+    let ds = ephemeral_datastore.datastore(clock.clone()).await;
+    let task = TaskBuilder::new(task::QueryType::TimeInterval, VdafInstance::Fake)
+        .build()
+        .leader_view()
+        .unwrap()
+        .with_state(TaskState::Provisioned);
+    let other_task = TaskBuilder::new(task::QueryType::TimeInterval, VdafInstance::Fake)
+        .build()
+        .leader_view()
+        .unwrap();
+
+    ds.run_unnamed_tx(|tx| {
+        let (task, other_task) = (task.clone(), other_task.clone());
+        Box::pin(async move {
+            tx.put_aggregator_task(&task).await.unwrap();
+            tx.put_aggregator_task(&other_task).await.unwrap();
+            assert_eq!(
+                tx.get_aggregator_task(task.id())
                     .await
-                    .unwrap();
-                assert_eq!(
-                    tx.get_global_hpke_keypair(keypair.config().id())
-                        .await
-                        .unwrap()
-                        .unwrap(),
-                    GlobalHpkeKeypair::new(keypair.clone(), HpkeKeyState::Expired, clock.now(),)
-                );
+                    .unwrap()
+                    .unwrap()
+                    .state(),
+                &TaskState::Provisioned
+            );
 
-                Ok(())
+            tx.update_task_state(task.id(), &TaskState::Provisioned, &TaskState::Active)
+                .await
+                .unwrap();
+
+            // Transitions must start from the task's current state, and must be allowed.
+            assert_matches!(
+                tx.update_task_state(task.id(), &TaskState::Provisioned, &TaskState::Active)
+                    .await,
+                Err(Error::MutationTargetNotFound)
+            );
+            assert_matches!(
+                tx.update_task_state(task.id(), &TaskState::Active, &TaskState::Expired)
+                    .await,
+                Err(Error::InvalidParameter(_))
+            );
+            assert_matches!(
+                tx.update_task_state(task.id(), &TaskState::Purging, &TaskState::Deleted)
+                    .await,
+                Err(Error::InvalidParameter(_))
+            );
+            assert_matches!(
+                tx.update_task_state(&random(), &TaskState::Active, &TaskState::Expiring)
+                    .await,
+                Err(Error::MutationTargetNotFound)
+            );
+
+            tx.update_task_state(task.id(), &TaskState::Active, &TaskState::Purging)
+                .await
+                .unwrap();
+            assert_eq!(
+                tx.get_aggregator_task(task.id())
+                    .await
+                    .unwrap()
+                    .unwrap()
+                    .state(),
+                &TaskState::Purging
+            );
+            tx.delete_task(task.id()).await.unwrap();
+            Ok(())
+        })
+    })
+    .await
+    .unwrap();
+
+    let now = clock.now();
+    let (task_events, all_events) = ds
+        .run_unnamed_tx(|tx| {
+            let task_id = *task.id();
+            Box::pin(async move {
+                Ok((
+                    tx.get_task_lifecycle_events(Some(&task_id)).await.unwrap(),
+                    tx.get_task_lifecycle_events(None).await.unwrap(),
+                ))
             })
         })
         .await
         .unwrap();
 
-    // Should not be able to set keypair with the same id.
-    assert_matches!(
-        datastore
-            .run_unnamed_tx(|tx| {
-                let keypair = keypair.clone();
-                Box::pin(async move { tx.put_global_hpke_keypair(&keypair).await })
+    // Events are retained after the task is deleted.
+    assert_eq!(
+        task_events,
+        Vec::from([
+            TaskLifecycleEvent::new(*task.id(), None, TaskState::Provisioned, now),
+            TaskLifecycleEvent::new(
+                *task.id(),
+                Some(TaskState::Provisioned),
+                TaskState::Active,
+                now
+            ),
+            TaskLifecycleEvent::new(*task.id(), Some(TaskState::Active), TaskState::Purging, now),
+            TaskLifecycleEvent::new(
+                *task.id(),
+                Some(TaskState::Purging),
+                TaskState::Deleted,
+                now
+            ),
+        ])
+    );
+    assert_eq!(all_events.len(), 5);
+    assert!(all_events.contains(&TaskLifecycleEvent::new(
+        *other_task.id(),
+        None,
+        TaskState::Active,
+        now
+    )));
+}
+
+#[rstest_reuse::apply(schema_versions_template)]
+#[tokio::test]
+async fn datastore_fixture(ephemeral_datastore: EphemeralDatastore) {
+    install_test_trace_subscriber();
+    let ds = ephemeral_datastore.datastore(MockClock::default()).await;
+
+    let fixture = DatastoreFixtureBuilder::new()
+        .with_reports(7)
+        .with_batch_units(3)
+        .with_failed_aggregations(2)
+        .build(&ds)
+        .await;
+
+    assert_eq!(fixture.batch_intervals.len(), 3);
+    assert_eq!(fixture.reports.len(), 7);
+    assert_eq!(fixture.aggregation_jobs.len(), 3);
+    assert_eq!(fixture.batch_aggregations.len(), 3);
+
+    let task_id = *fixture.task.id();
+    let vdaf = fixture.vdaf.clone();
+    let (aggregation_jobs, report_aggregations, batch_aggregations) = ds
+        .run_unnamed_tx(|tx| {
+            let vdaf = vdaf.clone();
+            Box::pin(async move {
+                Ok((
+                    tx.get_aggregation_jobs_for_task::<VERIFY_KEY_LENGTH, TimeInterval, Prio3Count>(
+                        &task_id,
+                    )
+                    .await
+                    .unwrap(),
+                    tx.get_report_aggregations_for_task(&vdaf, &Role::Leader, &task_id)
+                        .await
+                        .unwrap(),
+                    tx.get_batch_aggregations_for_task::<VERIFY_KEY_LENGTH, TimeInterval, Prio3Count>(
+                        &vdaf, &task_id,
+                    )
+                    .await
+                    .unwrap(),
+                ))
             })
-            .await,
-        Err(Error::Db(_))
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(aggregation_jobs.len(), 3);
+    assert_eq!(
+        report_aggregations
+            .iter()
+            .filter(|report_aggregation| matches!(
+                report_aggregation.state(),
+                ReportAggregationState::Failed { .. }
+            ))
+            .count(),
+        2
     );
+    assert_eq!(report_aggregations.len(), 7);
 
-    datastore
+    // Reports are distributed round-robin, and the first two reports failed, so the batch units
+    // aggregated 2, 1, and 2 reports.
+    let mut report_counts: Vec<_> = batch_aggregations
+        .iter()
+        .map(|batch_aggregation| {
+            assert_matches!(
+                batch_aggregation.state(),
+                BatchAggregationState::Aggregating { report_count, .. } => {
+                    (*batch_aggregation.batch_interval(), *report_count)
+                }
+            )
+        })
+        .collect();
+    report_counts.sort();
+    assert_eq!(
+        report_counts,
+        Vec::from([
+            (fixture.batch_intervals[0], 2),
+            (fixture.batch_intervals[1], 1),
+            (fixture.batch_intervals[2], 2),
+        ])
+    );
+}
+
+#[rstest_reuse::apply(schema_versions_template)]
+#[tokio::test]
+async fn task_metrics_snapshots(ephemeral_datastore: EphemeralDatastore) {
+    install_test_trace_subscriber();
+    let clock = MockClock::default();
+    let ds = ephemeral_datastore.datastore(clock.clone()).await;
+    let one_hour = Duration::from_hours(1).unwrap();
+
+    // Five reports are uploaded, and three of them are aggregated successfully.
+    let fixture = DatastoreFixtureBuilder::new()
+        .with_reports(5)
+        .with_failed_aggregations(2)
+        .build(&ds)
+        .await;
+    let task_id = *fixture.task.id();
+    ds.run_unnamed_tx(|tx| {
+        Box::pin(async move {
+            tx.increment_task_upload_counter(
+                &task_id,
+                0,
+                &TaskUploadCounter::new_with_values(0, 0, 0, 0, 0, 5, 0, 0),
+            )
+            .await
+        })
+    })
+    .await
+    .unwrap();
+
+    clock.advance(&one_hour);
+    let first_snapshot_time = clock.now().to_batch_interval_start(&one_hour).unwrap();
+    let second_snapshot_time = first_snapshot_time.add(&one_hour).unwrap();
+
+    let written = ds
         .run_unnamed_tx(|tx| {
-            let keypair = keypair.clone();
             Box::pin(async move {
-                tx.delete_global_hpke_keypair(keypair.config().id())
+                Ok((
+                    tx.put_task_metrics_snapshots(&first_snapshot_time)
+                        .await
+                        .unwrap(),
+                    // Snapshotting the same time again has no effect.
+                    tx.put_task_metrics_snapshots(&first_snapshot_time)
+                        .await
+                        .unwrap(),
+                ))
+            })
+        })
+        .await
+        .unwrap();
+    assert_eq!(written, (1, 0));
+
+    // Aggregated reports are accumulated from the previous snapshot, so the next snapshot doesn't
+    // count them again, and the snapshots are retained after the task is deleted.
+    clock.advance(&one_hour);
+    let snapshots = ds
+        .run_unnamed_tx(|tx| {
+            Box::pin(async move {
+                tx.put_task_metrics_snapshots(&second_snapshot_time)
                     .await
                     .unwrap();
-                assert_eq!(tx.get_global_hpke_keypairs().await.unwrap(), Vec::new());
-                assert_matches!(
-                    tx.get_global_hpke_keypair(keypair.config().id())
+                tx.delete_task(&task_id).await.unwrap();
+                Ok((
+                    tx.get_task_metrics_snapshots(&task_id, &Time::from_seconds_since_epoch(0))
                         .await
                         .unwrap(),
-                    None
-                );
-
-                tx.check_timestamp_columns("global_hpke_keys", "test-put-keys", true)
-                    .await;
+                    tx.get_task_metrics_snapshots(&task_id, &second_snapshot_time)
+                        .await
+                        .unwrap(),
+                ))
+            })
+        })
+        .await
+        .unwrap();
+    assert_eq!(
+        snapshots,
+        (
+            Vec::from([
+                TaskMetricsSnapshot::new(task_id, first_snapshot_time, 5, 3, 0),
+                TaskMetricsSnapshot::new(task_id, second_snapshot_time, 5, 3, 0),
+            ]),
+            Vec::from([TaskMetricsSnapshot::new(
+                task_id,
+                second_snapshot_time,
+                5,
+                3,
+                0
+            )]),
+        )
+    );
 
-                Ok(())
+    // Snapshots older than the retention period are deleted.
+    let (deleted, snapshots) = ds
+        .run_unnamed_tx(|tx| {
+            Box::pin(async move {
+                Ok((
+                    tx.delete_task_metrics_snapshots_before(&second_snapshot_time, 10)
+                        .await
+                        .unwrap(),
+                    tx.get_task_metrics_snapshots(&task_id, &Time::from_seconds_since_epoch(0))
+                        .await
+                        .unwrap(),
+                ))
             })
         })
         .await
         .unwrap();
+    assert_eq!(deleted, 1);
+    assert_eq!(
+        snapshots,
+        Vec::from([TaskMetricsSnapshot::new(
+            task_id,
+            second_snapshot_time,
+            5,
+            3,
+            0
+        )])
+    );
 }
 
 #[rstest_reuse::apply(schema_versions_template)]
 #[tokio::test]
-async fn roundtrip_taskprov_peer_aggregator(ephemeral_datastore: EphemeralDatastore) {
+async fn task_peer_health(ephemeral_datastore: EphemeralDatastore) {
     install_test_trace_subscriber();
-    let datastore = ephemeral_datastore.datastore(MockClock::default()).await;
+    let clock = MockClock::default();
+    let ds = ephemeral_datastore.datastore(clock.clone()).await;
 
-    // Basic aggregator.
-    let example_leader_peer_aggregator =
-        PeerAggregatorBuilder::new().with_role(Role::Leader).build();
-    let example_helper_peer_aggregator = PeerAggregatorBuilder::new()
-        .with_role(Role::Helper)
-        .with_aggregator_auth_tokens(Vec::from([random(), random()]))
-        .with_collector_auth_tokens(Vec::new())
-        .build();
-    let another_example_leader_peer_aggregator = PeerAggregatorBuilder::new()
-        .with_endpoint(Url::parse("https://another.example.com/").unwrap())
-        .with_aggregator_auth_tokens(Vec::new())
-        .with_collector_auth_tokens(Vec::from([random(), random()]))
-        .build();
+    let task = TaskBuilder::new(task::QueryType::TimeInterval, VdafInstance::Fake)
+        .build()
+        .leader_view()
+        .unwrap();
+    ds.put_aggregator_task(&task).await.unwrap();
+    let task_id = *task.id();
+
+    let first_probe_time = clock.now();
+    let second_probe_time = first_probe_time.add(&Duration::from_seconds(60)).unwrap();
+    let third_probe_time = second_probe_time.add(&Duration::from_seconds(60)).unwrap();
+    let put_probe_result = |probed_at: Time, result: Result<u64, &'static str>| {
+        ds.run_unnamed_tx(move |tx| {
+            Box::pin(async move {
+                tx.put_task_peer_probe_result(&task_id, &probed_at, result)
+                    .await
+            })
+        })
+    };
+    let get_health = || {
+        ds.run_unnamed_tx(move |tx| {
+            Box::pin(async move { tx.get_task_peer_health(&task_id).await })
+        })
+    };
+
+    // The peer hasn't been probed yet.
+    assert_eq!(get_health().await.unwrap(), None);
+
+    put_probe_result(first_probe_time, Ok(25)).await.unwrap();
+    assert_eq!(
+        get_health().await.unwrap(),
+        Some(TaskPeerHealth::new(
+            task_id,
+            first_probe_time,
+            Some(25),
+            None,
+            Some(first_probe_time),
+            0
+        ))
+    );
+
+    // Failures are counted, and the last successful probe is remembered.
+    put_probe_result(second_probe_time, Err("connection refused"))
+        .await
+        .unwrap();
+    put_probe_result(third_probe_time, Err("timed out"))
+        .await
+        .unwrap();
+    let health = get_health().await.unwrap().unwrap();
+    assert!(!health.reachable());
+    assert_eq!(
+        health,
+        TaskPeerHealth::new(
+            task_id,
+            third_probe_time,
+            None,
+            Some("timed out".to_string()),
+            Some(first_probe_time),
+            2
+        )
+    );
+
+    // A successful probe resets the failure count.
+    put_probe_result(third_probe_time, Ok(30)).await.unwrap();
+    assert_eq!(
+        get_health().await.unwrap(),
+        Some(TaskPeerHealth::new(
+            task_id,
+            third_probe_time,
+            Some(30),
+            None,
+            Some(third_probe_time),
+            0
+        ))
+    );
 
-    datastore
-        .run_tx("test-put-peer-aggregator", |tx| {
-            let example_leader_peer_aggregator = example_leader_peer_aggregator.clone();
-            let example_helper_peer_aggregator = example_helper_peer_aggregator.clone();
-            let another_example_leader_peer_aggregator =
-                another_example_leader_peer_aggregator.clone();
+    // Probe results can't be recorded for unknown tasks, and are deleted along with their task.
+    assert_matches!(
+        ds.run_unnamed_tx(|tx| {
             Box::pin(async move {
-                tx.put_taskprov_peer_aggregator(&example_leader_peer_aggregator)
-                    .await
-                    .unwrap();
-                tx.put_taskprov_peer_aggregator(&example_helper_peer_aggregator)
-                    .await
-                    .unwrap();
-                tx.put_taskprov_peer_aggregator(&another_example_leader_peer_aggregator)
+                tx.put_task_peer_probe_result(&random(), &first_probe_time, Ok(1))
                     .await
-                    .unwrap();
-                Ok(())
             })
         })
+        .await,
+        Err(Error::MutationTargetNotFound)
+    );
+    ds.run_unnamed_tx(|tx| Box::pin(async move { tx.delete_task(&task_id).await }))
         .await
         .unwrap();
+    assert_eq!(get_health().await.unwrap(), None);
+}
 
-    // Should not be able to put an aggregator with the same endpoint and role.
-    assert_matches!(
-        datastore
-            .run_unnamed_tx(|tx| {
-                Box::pin(async move {
-                    let colliding_peer_aggregator = PeerAggregatorBuilder::new().build();
-                    tx.put_taskprov_peer_aggregator(&colliding_peer_aggregator)
-                        .await
-                })
-            })
-            .await,
-        Err(Error::MutationTargetAlreadyExists)
-    );
-
-    datastore
-        .run_unnamed_tx(|tx| {
-            let example_leader_peer_aggregator = example_leader_peer_aggregator.clone();
-            let example_helper_peer_aggregator = example_helper_peer_aggregator.clone();
-            let another_example_leader_peer_aggregator =
-                another_example_leader_peer_aggregator.clone();
-            Box::pin(async move {
-                for peer in [
-                    example_leader_peer_aggregator.clone(),
-                    example_helper_peer_aggregator.clone(),
-                    another_example_leader_peer_aggregator.clone(),
-                ] {
-                    assert_eq!(
-                        tx.get_taskprov_peer_aggregator(peer.endpoint(), peer.role())
-                            .await
-                            .unwrap(),
-                        Some(peer.clone()),
-                    );
-                }
+#[rstest_reuse::apply(schema_versions_template)]
+#[tokio::test]
+async fn aggregation_job_creator_task_state(ephemeral_datastore: EphemeralDatastore) {
+    install_test_trace_subscriber();
+    let clock = MockClock::default();
+    let ds = ephemeral_datastore.datastore(clock.clone()).await;
 
-                assert_eq!(
-                    tx.get_taskprov_peer_aggregators().await.unwrap(),
-                    Vec::from([
-                        example_leader_peer_aggregator.clone(),
-                        example_helper_peer_aggregator.clone(),
-                        another_example_leader_peer_aggregator.clone(),
-                    ])
-                );
+    let task = TaskBuilder::new(task::QueryType::TimeInterval, VdafInstance::Fake)
+        .build()
+        .leader_view()
+        .unwrap();
+    ds.put_aggregator_task(&task).await.unwrap();
+    let task_id = *task.id();
+
+    let discovered_at = clock.now();
+    let first_run_at = discovered_at.add(&Duration::from_seconds(10)).unwrap();
+    let second_run_at = first_run_at.add(&Duration::from_seconds(60)).unwrap();
+    let put_state = |state: AggregationJobCreatorTaskState| {
+        ds.run_unnamed_tx(move |tx| {
+            let state = state.clone();
+            Box::pin(async move { tx.put_aggregation_job_creator_task_state(&state).await })
+        })
+    };
+    let get_state = || {
+        ds.run_unnamed_tx(move |tx| {
+            Box::pin(async move { tx.get_aggregation_job_creator_task_state(&task_id).await })
+        })
+    };
 
-                for peer in [
-                    example_leader_peer_aggregator.clone(),
-                    example_helper_peer_aggregator.clone(),
-                    another_example_leader_peer_aggregator.clone(),
-                ] {
-                    tx.delete_taskprov_peer_aggregator(peer.endpoint(), peer.role())
-                        .await
-                        .unwrap();
-                }
-                assert_eq!(
-                    tx.get_taskprov_peer_aggregators().await.unwrap(),
-                    Vec::new()
-                );
+    // The task hasn't been discovered yet.
+    assert_eq!(get_state().await.unwrap(), None);
 
-                tx.check_timestamp_columns(
-                    "taskprov_peer_aggregators",
-                    "test-put-peer-aggregator",
-                    false,
-                )
-                .await;
+    let discovered_state =
+        AggregationJobCreatorTaskState::new(task_id, discovered_at, None, first_run_at, None);
+    put_state(discovered_state.clone()).await.unwrap();
+    assert_eq!(get_state().await.unwrap(), Some(discovered_state));
 
-                tx.check_timestamp_columns(
-                    "taskprov_aggregator_auth_tokens",
-                    "test-put-peer-aggregator",
-                    false,
-                )
-                .await;
-                tx.check_timestamp_columns(
-                    "taskprov_collector_auth_tokens",
-                    "test-put-peer-aggregator",
-                    false,
-                )
-                .await;
+    // Later writes replace the task's state.
+    let failed_state = AggregationJobCreatorTaskState::new(
+        task_id,
+        discovered_at,
+        Some(first_run_at),
+        second_run_at,
+        Some("database unavailable".to_string()),
+    );
+    put_state(failed_state.clone()).await.unwrap();
+    assert_eq!(get_state().await.unwrap(), Some(failed_state));
 
-                Ok(())
-            })
-        })
+    // State can't be recorded for unknown tasks, and is deleted along with its task.
+    assert_matches!(
+        put_state(AggregationJobCreatorTaskState::new(
+            random(),
+            discovered_at,
+            None,
+            first_run_at,
+            None
+        ))
+        .await,
+        Err(Error::MutationTargetNotFound)
+    );
+    ds.run_unnamed_tx(|tx| Box::pin(async move { tx.delete_task(&task_id).await }))
         .await
         .unwrap();
+    assert_eq!(get_state().await.unwrap(), None);
 }
 
 #[rstest_reuse::apply(schema_versions_template)]
 #[tokio::test]
-async fn reject_expired_reports_with_same_id(ephemeral_datastore: EphemeralDatastore) {
+async fn datastore_migration_verification(ephemeral_datastore: EphemeralDatastore) {
     install_test_trace_subscriber();
-    let clock = MockClock::default();
-    let datastore = ephemeral_datastore.datastore(clock.clone()).await;
+    let clock = MockClock::new(OLDEST_ALLOWED_REPORT_TIMESTAMP);
+    let ds = ephemeral_datastore.datastore(clock.clone()).await;
 
-    let report_expiry_age = Duration::from_seconds(60);
     let task = TaskBuilder::new(task::QueryType::TimeInterval, VdafInstance::Fake)
-        .with_report_expiry_age(Some(report_expiry_age))
         .build()
         .leader_view()
         .unwrap();
+    let empty_task = TaskBuilder::new(task::QueryType::TimeInterval, VdafInstance::Fake)
+        .build()
+        .leader_view()
+        .unwrap();
+    ds.put_aggregator_task(&task).await.unwrap();
+    ds.put_aggregator_task(&empty_task).await.unwrap();
 
-    datastore.put_aggregator_task(&task).await.unwrap();
-
-    // Use same ID for each report.
-    let report_id = random();
+    let mut reports: Vec<_> = iter::repeat_with(|| {
+        LeaderStoredReport::new_dummy(*task.id(), OLDEST_ALLOWED_REPORT_TIMESTAMP)
+    })
+    .take(3)
+    .collect();
+    ds.run_unnamed_tx(|tx| {
+        let reports = reports.clone();
+        Box::pin(async move {
+            for report in &reports {
+                tx.put_client_report(&dummy::Vdaf::default(), report)
+                    .await
+                    .unwrap();
+            }
+            Ok(())
+        })
+    })
+    .await
+    .unwrap();
 
-    datastore
+    let (row_counts, digests) = ds
         .run_unnamed_tx(|tx| {
-            let report = LeaderStoredReport::<0, dummy::Vdaf>::new(
-                *task.id(),
-                ReportMetadata::new(report_id, clock.now()),
-                (),
-                Vec::new(),
-                dummy::InputShare::default(),
-                HpkeCiphertext::new(
-                    HpkeConfigId::from(13),
-                    Vec::from("encapsulated_context_0"),
-                    Vec::from("payload_0"),
-                ),
-            );
-            Box::pin(async move { tx.put_client_report(&dummy::Vdaf::default(), &report).await })
+            Box::pin(async move {
+                Ok((
+                    tx.count_rows_by_table().await.unwrap(),
+                    tx.get_client_report_digests().await.unwrap(),
+                ))
+            })
         })
         .await
         .unwrap();
 
-    // Advance the clock well past the report expiry age.
-    clock.advance(&report_expiry_age.add(&report_expiry_age).unwrap());
+    let row_counts: HashMap<_, _> = row_counts.into_iter().collect();
+    assert_eq!(row_counts["tasks"], 2);
+    assert_eq!(row_counts["client_reports"], 3);
+    assert_eq!(row_counts["aggregation_jobs"], 0);
 
-    // Insert a client report with the same ID, but a more current timestamp.
-    let result = datastore
-        .run_unnamed_tx(|tx| {
-            let report = LeaderStoredReport::<0, dummy::Vdaf>::new(
-                *task.id(),
-                ReportMetadata::new(report_id, clock.now()),
-                (),
-                Vec::new(),
-                dummy::InputShare::default(),
-                HpkeCiphertext::new(
-                    HpkeConfigId::from(13),
-                    Vec::from("encapsulated_context_0"),
-                    Vec::from("payload_0"),
-                ),
-            );
-            Box::pin(async move { tx.put_client_report(&dummy::Vdaf::default(), &report).await })
-        })
-        .await;
-    assert_matches!(result, Err(Error::MutationTargetAlreadyExists));
+    // Tasks without reports are omitted, and the digest does not depend on insertion order.
+    reports.sort_by_key(|report| *report.metadata().id());
+    let report_ids: Vec<u8> = reports
+        .iter()
+        .flat_map(|report| report.metadata().id().as_ref().to_vec())
+        .collect();
+    assert_eq!(
+        digests,
+        Vec::from([(
+            *task.id(),
+            3,
+            ring::digest::digest(&ring::digest::SHA256, &report_ids)
+                .as_ref()
+                .to_vec(),
+        )])
+    );
 }
 
 #[rstest_reuse::apply(schema_versions_template)]
 #[tokio::test]
-async fn roundtrip_task_upload_counter(ephemeral_datastore: EphemeralDatastore) {
+async fn run_read_only_tx(ephemeral_datastore: EphemeralDatastore) {
     install_test_trace_subscriber();
-    let clock = MockClock::default();
-    let datastore = ephemeral_datastore.datastore(clock.clone()).await;
 
+    // Use the database as its own read replica.
+    let ds = ephemeral_datastore
+        .datastore(MockClock::default())
+        .await
+        .with_read_replica_pool(ephemeral_datastore.pool());
     let task = TaskBuilder::new(task::QueryType::TimeInterval, VdafInstance::Fake)
         .build()
         .leader_view()
         .unwrap();
+    ds.put_aggregator_task(&task).await.unwrap();
 
-    datastore.put_aggregator_task(&task).await.unwrap();
-
-    datastore
-        .run_unnamed_tx(|tx| {
+    let got_task = ds
+        .run_read_only_tx("test-read-only", |tx| {
             let task_id = *task.id();
-            Box::pin(async move {
-                // Returns None for non-existent task.
-                let counter = tx.get_task_upload_counter(&random()).await.unwrap();
-                assert_eq!(counter, None);
-
-                // Returns Some for a task that has just been created and has no counters.
-                let counter = tx.get_task_upload_counter(&task_id).await.unwrap();
-                assert_eq!(counter, Some(TaskUploadCounter::default()));
-
-                let ord = thread_rng().gen_range(0..32);
-                tx.increment_task_upload_counter(
-                    &task_id,
-                    ord,
-                    &TaskUploadCounter::new_with_values(2, 4, 6, 8, 10, 100, 25, 12),
-                )
-                .await
-                .unwrap();
+            Box::pin(async move { tx.get_aggregator_task(&task_id).await })
+        })
+        .await
+        .unwrap();
+    assert_eq!(got_task.as_ref(), Some(&task));
 
-                let ord = thread_rng().gen_range(0..32);
-                tx.increment_task_upload_counter(
-                    &task_id,
-                    ord,
-                    &TaskUploadCounter::new_with_values(0, 0, 0, 0, 0, 0, 0, 8),
+    // Writes are rejected in read-only transactions.
+    let result = ds
+        .run_read_only_tx("test-read-only", |tx| {
+            Box::pin(async move {
+                tx.put_aggregator_task(
+                    &TaskBuilder::new(task::QueryType::TimeInterval, VdafInstance::Fake)
+                        .build()
+                        .leader_view()
+                        .unwrap(),
                 )
                 .await
-                .unwrap();
-
-                let ord = thread_rng().gen_range(0..32);
-                tx.increment_task_upload_counter(&task_id, ord, &TaskUploadCounter::default())
-                    .await
-                    .unwrap();
-
-                let counter = tx.get_task_upload_counter(&task_id).await.unwrap();
-                assert_eq!(
-                    counter,
-                    Some(TaskUploadCounter {
-                        interval_collected: 2,
-                        report_decode_failure: 4,
-                        report_decrypt_failure: 6,
-                        report_expired: 8,
-                        report_outdated_key: 10,
-                        report_success: 100,
-                        report_too_early: 25,
-                        task_expired: 20,
-                    })
-                );
-
-                Ok(())
             })
         })
+        .await;
+    assert_matches!(result, Err(Error::Db(_)));
+
+    // If the read replica can't be reached, read-only transactions fall back to the primary.
+    let unreachable_pool = deadpool_postgres::Pool::builder(deadpool_postgres::Manager::new(
+        "postgres://postgres@127.0.0.1:1/postgres"
+            .parse::<tokio_postgres::Config>()
+            .unwrap(),
+        tokio_postgres::NoTls,
+    ))
+    .runtime(deadpool::Runtime::Tokio1)
+    .timeouts(deadpool_postgres::Timeouts {
+        wait: Some(StdDuration::from_secs(1)),
+        create: Some(StdDuration::from_secs(1)),
+        recycle: Some(StdDuration::from_secs(1)),
+    })
+    .build()
+    .unwrap();
+    let ds = ephemeral_datastore
+        .datastore(MockClock::default())
+        .await
+        .with_read_replica_pool(unreachable_pool);
+    let got_task = ds
+        .run_read_only_tx("test-read-only", |tx| {
+            let task_id = *task.id();
+            Box::pin(async move { tx.get_aggregator_task(&task_id).await })
+        })
         .await
         .unwrap();
+    assert_eq!(got_task, Some(task));
 }
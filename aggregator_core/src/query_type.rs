@@ -16,6 +16,20 @@ use janus_messages::{
 use prio::vdaf;
 use std::iter;
 
+/// A boundary check on a batch interval which a collection identifier failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum BatchIntervalViolation {
+    /// The batch interval is shorter than the task's time precision.
+    #[error("batch interval is shorter than the task's time precision")]
+    TooShort,
+    /// The start of the batch interval is not a multiple of the task's time precision.
+    #[error("batch interval start is not a multiple of the task's time precision")]
+    StartMisaligned,
+    /// The duration of the batch interval is not a multiple of the task's time precision.
+    #[error("batch interval duration is not a multiple of the task's time precision")]
+    DurationMisaligned,
+}
+
 #[async_trait]
 pub trait AccumulableQueryType: QueryType {
     /// This method converts various values related to a client report into a batch identifier. The
@@ -194,11 +208,12 @@ pub trait CollectableQueryType: AccumulableQueryType {
     ) -> Self::Iter;
 
     /// Validates a collection identifier, per the boundary checks in
-    /// <https://www.ietf.org/archive/id/draft-ietf-ppm-dap-02.html#section-4.5.6>.
+    /// <https://www.ietf.org/archive/id/draft-ietf-ppm-dap-02.html#section-4.5.6>. On failure,
+    /// returns the check that was violated.
     fn validate_collection_identifier(
         task: &AggregatorTask,
         collection_identifier: &Self::BatchIdentifier,
-    ) -> bool;
+    ) -> Result<(), BatchIntervalViolation>;
 
     /// Returns the number of client reports included in the given collection identifier, whether
     /// they have been aggregated or not.
@@ -270,15 +285,23 @@ impl CollectableQueryType for TimeInterval {
     fn validate_collection_identifier(
         task: &AggregatorTask,
         collection_identifier: &Self::BatchIdentifier,
-    ) -> bool {
+    ) -> Result<(), BatchIntervalViolation> {
         // https://www.ietf.org/archive/id/draft-ietf-ppm-dap-02.html#section-4.5.6.1.1
+        let time_precision = task.time_precision().as_seconds();
 
         // Batch interval should be greater than task's time precision
-        collection_identifier.duration().as_seconds() >= task.time_precision().as_seconds()
-                // Batch interval start must be a multiple of time precision
-                && collection_identifier.start().as_seconds_since_epoch() % task.time_precision().as_seconds() == 0
-                // Batch interval duration must be a multiple of time precision
-                && collection_identifier.duration().as_seconds() % task.time_precision().as_seconds() == 0
+        if collection_identifier.duration().as_seconds() < time_precision {
+            return Err(BatchIntervalViolation::TooShort);
+        }
+        // Batch interval start must be a multiple of time precision
+        if collection_identifier.start().as_seconds_since_epoch() % time_precision != 0 {
+            return Err(BatchIntervalViolation::StartMisaligned);
+        }
+        // Batch interval duration must be a multiple of time precision
+        if collection_identifier.duration().as_seconds() % time_precision != 0 {
+            return Err(BatchIntervalViolation::DurationMisaligned);
+        }
+        Ok(())
     }
 
     async fn count_client_reports<C: Clock>(
@@ -371,8 +394,11 @@ impl CollectableQueryType for FixedSize {
         iter::once(*batch_id)
     }
 
-    fn validate_collection_identifier(_: &AggregatorTask, _: &Self::BatchIdentifier) -> bool {
-        true
+    fn validate_collection_identifier(
+        _: &AggregatorTask,
+        _: &Self::BatchIdentifier,
+    ) -> Result<(), BatchIntervalViolation> {
+        Ok(())
     }
 
     async fn count_client_reports<C: Clock>(
@@ -388,7 +414,7 @@ impl CollectableQueryType for FixedSize {
 #[cfg(test)]
 mod tests {
     use crate::{
-        query_type::CollectableQueryType,
+        query_type::{BatchIntervalViolation, CollectableQueryType},
         task::{test_util::TaskBuilder, QueryType},
     };
     use janus_core::vdaf::VdafInstance;
@@ -406,7 +432,7 @@ mod tests {
         struct TestCase {
             name: &'static str,
             input: Interval,
-            expected: bool,
+            expected: Result<(), BatchIntervalViolation>,
         }
 
         for test_case in Vec::from([
@@ -417,7 +443,7 @@ mod tests {
                     Duration::from_seconds(time_precision_secs),
                 )
                 .unwrap(),
-                expected: true,
+                expected: Ok(()),
             },
             TestCase {
                 name: "interval too short",
@@ -426,7 +452,7 @@ mod tests {
                     Duration::from_seconds(time_precision_secs - 1),
                 )
                 .unwrap(),
-                expected: false,
+                expected: Err(BatchIntervalViolation::TooShort),
             },
             TestCase {
                 name: "interval larger than minimum",
@@ -435,7 +461,7 @@ mod tests {
                     Duration::from_seconds(time_precision_secs * 2),
                 )
                 .unwrap(),
-                expected: true,
+                expected: Ok(()),
             },
             TestCase {
                 name: "interval duration not aligned with minimum",
@@ -444,7 +470,7 @@ mod tests {
                     Duration::from_seconds(time_precision_secs + 1800),
                 )
                 .unwrap(),
-                expected: false,
+                expected: Err(BatchIntervalViolation::DurationMisaligned),
             },
             TestCase {
                 name: "interval start not aligned with minimum",
@@ -453,7 +479,7 @@ mod tests {
                     Duration::from_seconds(time_precision_secs),
                 )
                 .unwrap(),
-                expected: false,
+                expected: Err(BatchIntervalViolation::StartMisaligned),
             },
         ]) {
             assert_eq!(
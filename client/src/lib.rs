@@ -5,10 +5,16 @@ use derivative::Derivative;
 use http::header::CONTENT_TYPE;
 use itertools::Itertools;
 use janus_core::{
-    hpke::{self, is_hpke_config_supported, HpkeApplicationInfo, Label},
+    aggregator_endpoint_join,
+    auth_tokens::AuthenticationToken,
+    hpke::{self, is_hpke_config_supported, HpkeApplicationInfo, HpkeCiphersuite, Label},
     http::HttpErrorResponse,
-    retries::{http_request_exponential_backoff, retry_http_request},
+    retries::{
+        http_request_exponential_backoff, is_retryable_http_status, is_retryable_network_error,
+        retry_http_request,
+    },
     time::{Clock, RealClock, TimeExt},
+    tls::{self, SpkiPin, SpkiPins},
     url_ensure_trailing_slash,
 };
 use janus_messages::{
@@ -17,7 +23,8 @@ use janus_messages::{
 };
 use prio::{
     codec::{Decode, Encode},
-    vdaf,
+    flp::Type,
+    vdaf::{self, prio3::Prio3, xof::Xof},
 };
 use rand::random;
 use std::{convert::Infallible, fmt::Debug, time::SystemTimeError};
@@ -44,6 +51,27 @@ pub enum Error {
     UnexpectedServerResponse(&'static str),
     #[error("time conversion error: {0}")]
     TimeConversion(#[from] SystemTimeError),
+    #[error("TLS pinning error: {0}")]
+    Tls(#[from] tls::Error),
+    #[error("measurement length {length} exceeds the maximum of {max_length}")]
+    MeasurementTooLong { length: usize, max_length: usize },
+}
+
+impl Error {
+    /// Returns true if this error is a transient failure, such as a timeout, a connection failure,
+    /// or a server error, such that the same operation may succeed if attempted again later.
+    /// Requests to the aggregators are already retried with the client's backoff parameters, so a
+    /// retryable error means those retries were exhausted. Other errors are permanent, and will
+    /// recur if the operation is repeated.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::Http(http_error_response) => {
+                is_retryable_http_status(http_error_response.status())
+            }
+            Error::HttpClient(err) => is_retryable_network_error(err),
+            _ => false,
+        }
+    }
 }
 
 impl From<Infallible> for Error {
@@ -77,6 +105,16 @@ struct ClientParameters {
     time_precision: Duration,
     /// Parameters to use when retrying HTTP requests.
     http_request_retry_parameters: ExponentialBackoff,
+    /// Token presented to the leader when uploading reports, for tasks that require upload
+    /// authentication.
+    #[derivative(Debug = "ignore")]
+    upload_auth_token: Option<AuthenticationToken>,
+    /// HPKE ciphersuites the client is willing to encrypt to, in order of preference. If empty,
+    /// any supported ciphersuite is accepted.
+    hpke_ciphersuites: Vec<HpkeCiphersuite>,
+    /// Granularity to which report timestamps are rounded down, if coarser than the time
+    /// precision. Always a multiple of the time precision.
+    report_time_granularity: Option<Duration>,
 }
 
 impl ClientParameters {
@@ -93,9 +131,19 @@ impl ClientParameters {
             helper_aggregator_endpoint: url_ensure_trailing_slash(helper_aggregator_endpoint),
             time_precision,
             http_request_retry_parameters: http_request_exponential_backoff(),
+            upload_auth_token: None,
+            hpke_ciphersuites: Vec::new(),
+            report_time_granularity: None,
         }
     }
 
+    /// The granularity to which report timestamps are rounded down.
+    fn report_time_granularity(&self) -> &Duration {
+        self.report_time_granularity
+            .as_ref()
+            .unwrap_or(&self.time_precision)
+    }
+
     /// The URL relative to which the API endpoints for the aggregator may be found, if the role is
     /// an aggregator, or an error otherwise.
     fn aggregator_endpoint(&self, role: &Role) -> Result<&Url, Error> {
@@ -111,14 +159,18 @@ impl ClientParameters {
     ///
     /// [1]: https://www.ietf.org/archive/id/draft-ietf-ppm-dap-07.html#name-hpke-configuration-request
     fn hpke_config_endpoint(&self, role: &Role) -> Result<Url, Error> {
-        Ok(self.aggregator_endpoint(role)?.join("hpke_config")?)
+        Ok(aggregator_endpoint_join(
+            self.aggregator_endpoint(role)?,
+            "hpke_config",
+        )?)
     }
 
     // URI to which reports may be uploaded for the provided task.
     fn reports_resource_uri(&self, task_id: &TaskId) -> Result<Url, Error> {
-        Ok(self
-            .leader_aggregator_endpoint
-            .join(&format!("tasks/{task_id}/reports"))?)
+        Ok(aggregator_endpoint_join(
+            &self.leader_aggregator_endpoint,
+            &format!("tasks/{task_id}/reports"),
+        )?)
     }
 }
 
@@ -147,16 +199,44 @@ async fn aggregator_hpke_config(
     }
 
     let hpke_configs = HpkeConfigList::get_decoded(hpke_config_response.body())?;
+    select_hpke_config(
+        hpke_configs.hpke_configs(),
+        &client_parameters.hpke_ciphersuites,
+    )
+}
 
-    if hpke_configs.hpke_configs().is_empty() {
+/// Selects the HPKE configuration to encrypt to from those advertised by an aggregator. If
+/// `hpke_ciphersuites` is empty, the first supported configuration is taken, in the aggregator's
+/// order of preference. Otherwise, the configuration whose ciphersuite comes first in
+/// `hpke_ciphersuites` is taken.
+fn select_hpke_config(
+    hpke_configs: &[HpkeConfig],
+    hpke_ciphersuites: &[HpkeCiphersuite],
+) -> Result<HpkeConfig, Error> {
+    if hpke_configs.is_empty() {
         return Err(Error::UnexpectedServerResponse(
             "aggregator provided empty HpkeConfigList",
         ));
     }
 
+    if !hpke_ciphersuites.is_empty() {
+        return hpke_ciphersuites
+            .iter()
+            .find_map(|ciphersuite| {
+                hpke_configs.iter().find(|config| {
+                    &HpkeCiphersuite::from(*config) == ciphersuite
+                        && is_hpke_config_supported(config).is_ok()
+                })
+            })
+            .cloned()
+            .ok_or(Error::UnexpectedServerResponse(
+                "aggregator provided no HpkeConfig with an acceptable ciphersuite",
+            ));
+    }
+
     // Take the first supported HpkeConfig from the list. Return the first error otherwise.
     let mut first_error = None;
-    for config in hpke_configs.hpke_configs() {
+    for config in hpke_configs {
         match is_hpke_config_supported(config) {
             Ok(()) => return Ok(config.clone()),
             Err(e) => {
@@ -173,13 +253,16 @@ async fn aggregator_hpke_config(
 
 /// Construct a [`reqwest::Client`] suitable for use in a DAP [`Client`].
 pub fn default_http_client() -> Result<reqwest::Client, Error> {
-    Ok(reqwest::Client::builder()
+    Ok(default_http_client_builder().build()?)
+}
+
+fn default_http_client_builder() -> reqwest::ClientBuilder {
+    reqwest::Client::builder()
         // Clients wishing to override these timeouts may provide their own
         // values using ClientBuilder::with_http_client.
         .timeout(std::time::Duration::from_secs(30))
         .connect_timeout(std::time::Duration::from_secs(10))
         .user_agent(CLIENT_USER_AGENT)
-        .build()?)
 }
 
 /// Builder for configuring a [`Client`].
@@ -187,6 +270,8 @@ pub struct ClientBuilder<V: vdaf::Client<16>> {
     parameters: ClientParameters,
     vdaf: V,
     http_client: Option<reqwest::Client>,
+    leader_spki_pins: Vec<SpkiPin>,
+    helper_spki_pins: Vec<SpkiPin>,
 }
 
 impl<V: vdaf::Client<16>> ClientBuilder<V> {
@@ -207,17 +292,15 @@ impl<V: vdaf::Client<16>> ClientBuilder<V> {
             ),
             vdaf,
             http_client: None,
+            leader_spki_pins: Vec::new(),
+            helper_spki_pins: Vec::new(),
         }
     }
 
     /// Finalize construction of a [`Client`]. This will fetch HPKE configurations from each
     /// aggregator via HTTPS.
     pub async fn build(self) -> Result<Client<V>, Error> {
-        let http_client = if let Some(http_client) = self.http_client {
-            http_client
-        } else {
-            default_http_client()?
-        };
+        let http_client = self.http_client()?;
         let (leader_hpke_config, helper_hpke_config) = try_join!(
             aggregator_hpke_config(&self.parameters, &Role::Leader, &http_client),
             aggregator_hpke_config(&self.parameters, &Role::Helper, &http_client)
@@ -238,11 +321,7 @@ impl<V: vdaf::Client<16>> ClientBuilder<V> {
         leader_hpke_config: HpkeConfig,
         helper_hpke_config: HpkeConfig,
     ) -> Result<Client<V>, Error> {
-        let http_client = if let Some(http_client) = self.http_client {
-            http_client
-        } else {
-            default_http_client()?
-        };
+        let http_client = self.http_client()?;
         Ok(Client {
             parameters: self.parameters,
             vdaf: self.vdaf,
@@ -263,6 +342,103 @@ impl<V: vdaf::Client<16>> ClientBuilder<V> {
         self.parameters.http_request_retry_parameters = http_request_retry_parameters;
         self
     }
+
+    /// Authenticate uploads to the leader with the given token. This is only needed for tasks whose
+    /// leader requires upload authentication, which is not part of DAP.
+    pub fn with_upload_auth_token(mut self, upload_auth_token: AuthenticationToken) -> Self {
+        self.parameters.upload_auth_token = Some(upload_auth_token);
+        self
+    }
+
+    /// Restrict the HPKE configurations the client encrypts to, to those using one of the given
+    /// ciphersuites. When an aggregator advertises several acceptable configurations, the one whose
+    /// ciphersuite comes first in `hpke_ciphersuites` is used. By default, the first configuration
+    /// advertised by the aggregator that the client supports is used.
+    pub fn with_hpke_ciphersuites(mut self, hpke_ciphersuites: Vec<HpkeCiphersuite>) -> Self {
+        self.parameters.hpke_ciphersuites = hpke_ciphersuites;
+        self
+    }
+
+    /// Round report timestamps down to a multiple of `granularity`, such as the task's minimum
+    /// batch duration, rather than to a multiple of the task's time precision. Coarser timestamps
+    /// reveal less about when each measurement was taken, at the cost of reports landing in earlier
+    /// batches, which may already have been collected. `granularity` must be a nonzero multiple of
+    /// the time precision.
+    pub fn with_report_time_granularity(mut self, granularity: Duration) -> Result<Self, Error> {
+        let time_precision = self.parameters.time_precision.as_seconds();
+        if granularity.as_seconds() == 0
+            || time_precision == 0
+            || granularity.as_seconds() % time_precision != 0
+        {
+            return Err(Error::InvalidParameter(
+                "report time granularity must be a nonzero multiple of time_precision",
+            ));
+        }
+        self.parameters.report_time_granularity = Some(granularity);
+        Ok(self)
+    }
+
+    /// Pin the public keys that the leader may present. Requests to the leader fail unless its
+    /// certificate chain validates as usual and includes one of these keys. Pins can't be combined
+    /// with [`Self::with_http_client`].
+    pub fn with_leader_spki_pins(mut self, pins: Vec<SpkiPin>) -> Self {
+        self.leader_spki_pins = pins;
+        self
+    }
+
+    /// Pin the public keys that the helper may present. Requests to the helper fail unless its
+    /// certificate chain validates as usual and includes one of these keys. Pins can't be combined
+    /// with [`Self::with_http_client`].
+    pub fn with_helper_spki_pins(mut self, pins: Vec<SpkiPin>) -> Self {
+        self.helper_spki_pins = pins;
+        self
+    }
+
+    /// Returns the HTTP client to use, checking any pinned keys.
+    fn http_client(&self) -> Result<reqwest::Client, Error> {
+        let mut pins = SpkiPins::default();
+        if !self.leader_spki_pins.is_empty() {
+            pins.add_endpoint(
+                &self.parameters.leader_aggregator_endpoint,
+                self.leader_spki_pins.iter().copied(),
+            )?;
+        }
+        if !self.helper_spki_pins.is_empty() {
+            pins.add_endpoint(
+                &self.parameters.helper_aggregator_endpoint,
+                self.helper_spki_pins.iter().copied(),
+            )?;
+        }
+
+        match &self.http_client {
+            // Pins can't be checked by a client configured elsewhere, so refuse to run without them.
+            Some(_) if !pins.is_empty() => Err(Error::InvalidParameter(
+                "SPKI pins can't be used with a custom HTTP client",
+            )),
+            Some(http_client) => Ok(http_client.clone()),
+            None if pins.is_empty() => default_http_client(),
+            None => Ok(pins.apply(default_http_client_builder()).build()?),
+        }
+    }
+}
+
+impl<T, P, const SEED_SIZE: usize> ClientBuilder<Prio3<T, P, SEED_SIZE>>
+where
+    T: Type,
+    P: Xof<SEED_SIZE>,
+    Prio3<T, P, SEED_SIZE>: vdaf::Client<16>,
+{
+    /// Refuse to encode measurements for a Prio3 VDAF whose measurements are longer than
+    /// `max_length`, such as a histogram with more buckets. Extremely long measurements make
+    /// reports large, and expensive for the aggregators to prepare, so this guards against
+    /// misconfigured tasks.
+    pub fn with_max_measurement_length(self, max_length: usize) -> Result<Self, Error> {
+        let length = self.vdaf.output_len();
+        if length > max_length {
+            return Err(Error::MeasurementTooLong { length, max_length });
+        }
+        Ok(self)
+    }
 }
 
 /// A DAP client.
@@ -342,7 +518,7 @@ impl<V: vdaf::Client<16>> Client<V> {
         assert_eq!(input_shares.len(), 2); // DAP only supports VDAFs using two aggregators.
 
         let time = time
-            .to_batch_interval_start(&self.parameters.time_precision)
+            .to_batch_interval_start(self.parameters.report_time_granularity())
             .map_err(|_| Error::InvalidParameter("couldn't round time down to time_precision"))?;
         let report_metadata = ReportMetadata::new(report_id, time);
         let encoded_public_share = public_share.get_encoded()?;
@@ -439,12 +615,16 @@ impl<V: vdaf::Client<16>> Client<V> {
         let upload_response = retry_http_request(
             self.parameters.http_request_retry_parameters.clone(),
             || async {
-                self.http_client
+                let mut request = self
+                    .http_client
                     .put(upload_endpoint.clone())
                     .header(CONTENT_TYPE, Report::MEDIA_TYPE)
-                    .body(report.clone())
-                    .send()
-                    .await
+                    .body(report.clone());
+                if let Some(upload_auth_token) = &self.parameters.upload_auth_token {
+                    let (header, value) = upload_auth_token.request_authentication();
+                    request = request.header(header, value);
+                }
+                request.send().await
             },
         )
         .await
@@ -464,16 +644,24 @@ impl<V: vdaf::Client<16>> Client<V> {
 
 #[cfg(test)]
 mod tests {
-    use crate::{aggregator_hpke_config, default_http_client, Client, ClientParameters, Error};
+    use crate::{
+        aggregator_hpke_config, default_http_client, select_hpke_config, Client, ClientParameters,
+        Error,
+    };
     use assert_matches::assert_matches;
     use hex_literal::hex;
     use http::{header::CONTENT_TYPE, StatusCode};
     use janus_core::{
-        hpke::test_util::generate_test_hpke_config_and_private_key,
+        auth_tokens::AuthenticationToken,
+        hpke::{
+            test_util::generate_test_hpke_config_and_private_key, HpkeCiphersuite, HpkeKeypair,
+        },
         retries::test_util::test_http_request_exponential_backoff,
         test_util::install_test_trace_subscriber,
     };
-    use janus_messages::{Duration, HpkeConfigList, Report, Role, Time};
+    use janus_messages::{
+        Duration, HpkeAeadId, HpkeConfigList, HpkeKdfId, HpkeKemId, Report, Role, Time,
+    };
     use prio::{
         codec::Encode,
         vdaf::{self, prio3::Prio3},
@@ -539,6 +727,45 @@ mod tests {
         mocked_upload.assert_async().await;
     }
 
+    #[tokio::test]
+    async fn upload_auth_token() {
+        install_test_trace_subscriber();
+        let mut server = mockito::Server::new_async().await;
+        let server_url = Url::parse(&server.url()).unwrap();
+        let upload_auth_token: AuthenticationToken = random();
+        let client = Client::builder(
+            random(),
+            server_url.clone(),
+            server_url,
+            Duration::from_seconds(1),
+            Prio3::new_count(2).unwrap(),
+        )
+        .with_backoff(test_http_request_exponential_backoff())
+        .with_upload_auth_token(upload_auth_token.clone())
+        .build_with_hpke_configs(
+            generate_test_hpke_config_and_private_key().config().clone(),
+            generate_test_hpke_config_and_private_key().config().clone(),
+        )
+        .unwrap();
+
+        let (auth_header, auth_value) = upload_auth_token.request_authentication();
+        let mocked_upload = server
+            .mock(
+                "PUT",
+                format!("/tasks/{}/reports", client.parameters.task_id).as_str(),
+            )
+            .match_header(CONTENT_TYPE.as_str(), Report::MEDIA_TYPE)
+            .match_header(auth_header, auth_value.as_str())
+            .with_status(200)
+            .expect(1)
+            .create_async()
+            .await;
+
+        client.upload(&true).await.unwrap();
+
+        mocked_upload.assert_async().await;
+    }
+
     #[tokio::test]
     async fn upload_prio3_invalid_measurement() {
         install_test_trace_subscriber();
@@ -571,8 +798,43 @@ mod tests {
 
         assert_matches!(
             client.upload(&true).await,
-            Err(Error::Http(error_response)) => {
-                assert_eq!(error_response.status(), StatusCode::NOT_IMPLEMENTED);
+            Err(err @ Error::Http(_)) => {
+                assert_matches!(&err, Error::Http(error_response) => {
+                    assert_eq!(error_response.status(), StatusCode::NOT_IMPLEMENTED);
+                });
+                assert!(!err.is_retryable());
+            }
+        );
+
+        mocked_upload.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn upload_retryable_http_status_code() {
+        install_test_trace_subscriber();
+        let mut server = mockito::Server::new_async().await;
+        let client = setup_client(&server, Prio3::new_count(2).unwrap());
+
+        let mocked_upload = server
+            .mock(
+                "PUT",
+                format!("/tasks/{}/reports", client.parameters.task_id).as_str(),
+            )
+            .match_header(CONTENT_TYPE.as_str(), Report::MEDIA_TYPE)
+            .with_status(503)
+            .expect_at_least(2)
+            .create_async()
+            .await;
+
+        // The upload is retried until the backoff is exhausted, and the final error is reported as
+        // retryable.
+        assert_matches!(
+            client.upload(&true).await,
+            Err(err @ Error::Http(_)) => {
+                assert_matches!(&err, Error::Http(error_response) => {
+                    assert_eq!(error_response.status(), StatusCode::SERVICE_UNAVAILABLE);
+                });
+                assert!(err.is_retryable());
             }
         );
 
@@ -676,6 +938,57 @@ mod tests {
         );
     }
 
+    #[test]
+    fn report_timestamp_granularity() {
+        install_test_trace_subscriber();
+        let server = mockito::Server::new();
+        let server_url = Url::parse(&server.url()).unwrap();
+        let builder = || {
+            Client::builder(
+                random(),
+                server_url.clone(),
+                server_url.clone(),
+                Duration::from_seconds(100),
+                Prio3::new_count(2).unwrap(),
+            )
+        };
+
+        let client = builder()
+            .with_report_time_granularity(Duration::from_seconds(3600))
+            .unwrap()
+            .build_with_hpke_configs(
+                generate_test_hpke_config_and_private_key().config().clone(),
+                generate_test_hpke_config_and_private_key().config().clone(),
+            )
+            .unwrap();
+        assert_eq!(
+            client
+                .prepare_report(&true, &Time::from_seconds_since_epoch(7300))
+                .unwrap()
+                .metadata()
+                .time(),
+            &Time::from_seconds_since_epoch(7200),
+        );
+        assert_eq!(
+            client
+                .prepare_report(&true, &Time::from_seconds_since_epoch(10799))
+                .unwrap()
+                .metadata()
+                .time(),
+            &Time::from_seconds_since_epoch(7200),
+        );
+
+        // The granularity must be a nonzero multiple of the time precision.
+        for granularity in [0, 150, 50] {
+            assert_matches!(
+                builder()
+                    .with_report_time_granularity(Duration::from_seconds(granularity))
+                    .err(),
+                Some(Error::InvalidParameter(_))
+            );
+        }
+    }
+
     #[tokio::test]
     async fn aggregator_hpke() {
         install_test_trace_subscriber();
@@ -769,4 +1082,102 @@ mod tests {
 
         mock.assert_async().await;
     }
+
+    #[test]
+    fn select_preferred_hpke_ciphersuite() {
+        let x25519 = HpkeCiphersuite::default();
+        let p256 = HpkeCiphersuite::new(
+            HpkeKemId::P256HkdfSha256,
+            HpkeKdfId::HkdfSha256,
+            HpkeAeadId::Aes128Gcm,
+        );
+        let chacha = HpkeCiphersuite::new(
+            HpkeKemId::X25519HkdfSha256,
+            HpkeKdfId::HkdfSha256,
+            HpkeAeadId::ChaCha20Poly1305,
+        );
+        let hpke_configs = Vec::from([
+            HpkeKeypair::generate(1.into(), &x25519)
+                .unwrap()
+                .config()
+                .clone(),
+            HpkeKeypair::generate(2.into(), &p256)
+                .unwrap()
+                .config()
+                .clone(),
+        ]);
+
+        // With no preference, the aggregator's first supported config is taken.
+        assert_eq!(
+            select_hpke_config(&hpke_configs, &[]).unwrap(),
+            hpke_configs[0]
+        );
+
+        // Otherwise, the client's preference order wins.
+        assert_eq!(
+            select_hpke_config(&hpke_configs, &[chacha, p256, x25519]).unwrap(),
+            hpke_configs[1]
+        );
+
+        assert_matches!(
+            select_hpke_config(&hpke_configs, &[chacha]),
+            Err(Error::UnexpectedServerResponse(_))
+        );
+    }
+
+    #[test]
+    fn max_measurement_length() {
+        let builder = |length| {
+            Client::builder(
+                random(),
+                "https://leader.example.com/".parse().unwrap(),
+                "https://helper.example.com/".parse().unwrap(),
+                Duration::from_seconds(1),
+                Prio3::new_histogram(2, length, 10).unwrap(),
+            )
+        };
+
+        builder(100).with_max_measurement_length(100).unwrap();
+        assert_matches!(
+            builder(101).with_max_measurement_length(100).err(),
+            Some(Error::MeasurementTooLong {
+                length: 101,
+                max_length: 100
+            })
+        );
+    }
+
+    #[test]
+    fn spki_pins() {
+        let build = |leader: &str, helper: &str| {
+            Client::builder(
+                random(),
+                leader.parse().unwrap(),
+                helper.parse().unwrap(),
+                Duration::from_seconds(1),
+                Prio3::new_count(2).unwrap(),
+            )
+            .with_leader_spki_pins(Vec::from([random::<[u8; 32]>().into()]))
+        };
+        let hpke_config = || generate_test_hpke_config_and_private_key().config().clone();
+
+        build("https://leader.example.com/", "https://helper.example.com/")
+            .build_with_hpke_configs(hpke_config(), hpke_config())
+            .unwrap();
+
+        // Pins can't be checked for plain HTTP endpoints.
+        assert_matches!(
+            build("http://leader.example.com/", "https://helper.example.com/")
+                .build_with_hpke_configs(hpke_config(), hpke_config()),
+            Err(Error::Tls(_))
+        );
+
+        // Pins can't be checked by HTTP clients configured elsewhere.
+        assert_matches!(
+            build("https://leader.example.com/", "https://helper.example.com/")
+                .with_http_client(default_http_client().unwrap())
+                .build_with_hpke_configs(hpke_config(), hpke_config()),
+            Err(Error::InvalidParameter(_))
+        );
+    }
 }
@@ -0,0 +1,305 @@
+//! A blocking DAP collector.
+//!
+//! This wraps the asynchronous [`Collector`](crate::Collector), driving it with a private
+//! current-thread Tokio runtime, for use in programs that don't otherwise use async Rust, such as
+//! simple scripts or bindings for data science environments. Its methods block the calling thread
+//! until the corresponding asynchronous operation completes, and must not be called from within an
+//! asynchronous runtime.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use janus_collector::{blocking::Collector, AuthenticationToken};
+//! use janus_messages::{
+//!     Duration, HpkeAeadId, HpkeConfigId, HpkeKdfId, HpkeKemId, Interval, Query, Time,
+//! };
+//! use prio::vdaf::prio3::Prio3;
+//! use rand::random;
+//!
+//! let hpke_keypair = janus_core::hpke::generate_hpke_config_and_private_key(
+//!     HpkeConfigId::from(0),
+//!     HpkeKemId::X25519HkdfSha256,
+//!     HpkeKdfId::HkdfSha256,
+//!     HpkeAeadId::Aes128Gcm,
+//! )
+//! .unwrap();
+//! let collector = Collector::new(
+//!     random(),
+//!     "https://example.com/dap/".parse().unwrap(),
+//!     AuthenticationToken::new_bearer_token_from_string("Y29sbGVjdG9yIHRva2Vu").unwrap(),
+//!     hpke_keypair,
+//!     Prio3::new_count(2).unwrap(),
+//! )
+//! .unwrap();
+//!
+//! let interval = Interval::new(
+//!     Time::from_seconds_since_epoch(1_656_000_000),
+//!     Duration::from_seconds(3600),
+//! )
+//! .unwrap();
+//! let aggregation_result = collector
+//!     .collect(Query::new_time_interval(interval), &())
+//!     .unwrap();
+//! ```
+
+use crate::{Collection, CollectionJob, Error, ExponentialBackoff, PollResult, SpkiPin};
+use janus_core::{auth_tokens::AuthenticationToken, hpke::HpkeKeypair};
+use janus_messages::{query_type::QueryType, CollectionJobId, Query, TaskId};
+use prio::vdaf;
+use tokio::runtime::{Builder, Runtime};
+use url::Url;
+
+/// Builder for configuring a blocking [`Collector`].
+pub struct CollectorBuilder<V: vdaf::Collector> {
+    inner: crate::CollectorBuilder<V>,
+}
+
+impl<V: vdaf::Collector> CollectorBuilder<V> {
+    /// Construct a [`CollectorBuilder`] from required DAP task parameters and an implementation of
+    /// the task's VDAF.
+    pub fn new(
+        task_id: TaskId,
+        leader_endpoint: Url,
+        authentication: AuthenticationToken,
+        hpke_keypair: HpkeKeypair,
+        vdaf: V,
+    ) -> Self {
+        Self {
+            inner: crate::CollectorBuilder::new(
+                task_id,
+                leader_endpoint,
+                authentication,
+                hpke_keypair,
+                vdaf,
+            ),
+        }
+    }
+
+    /// Finalize construction of a [`Collector`].
+    pub fn build(self) -> Result<Collector<V>, Error> {
+        let runtime = Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(Error::Runtime)?;
+        Ok(Collector {
+            inner: self.inner.build()?,
+            runtime,
+        })
+    }
+
+    /// Provide an HTTPS client for the collector.
+    pub fn with_http_client(mut self, http_client: reqwest::Client) -> Self {
+        self.inner = self.inner.with_http_client(http_client);
+        self
+    }
+
+    /// Pin the public keys that the leader may present. See
+    /// [`crate::CollectorBuilder::with_leader_spki_pins`].
+    pub fn with_leader_spki_pins(mut self, pins: Vec<SpkiPin>) -> Self {
+        self.inner = self.inner.with_leader_spki_pins(pins);
+        self
+    }
+
+    /// Replace the exponential backoff settings used for HTTP requests.
+    pub fn with_http_request_backoff(mut self, backoff: ExponentialBackoff) -> Self {
+        self.inner = self.inner.with_http_request_backoff(backoff);
+        self
+    }
+
+    /// Replace the exponential backoff settings used while polling for aggregate shares.
+    pub fn with_collect_poll_backoff(mut self, backoff: ExponentialBackoff) -> Self {
+        self.inner = self.inner.with_collect_poll_backoff(backoff);
+        self
+    }
+}
+
+/// A blocking DAP collector. See [`crate::Collector`] for details of each operation.
+#[derive(Debug)]
+pub struct Collector<V: vdaf::Collector> {
+    inner: crate::Collector<V>,
+    runtime: Runtime,
+}
+
+impl<V: vdaf::Collector> Collector<V> {
+    /// Construct a new collector. This requires certain DAP task parameters and an implementation of
+    /// the task's VDAF.
+    pub fn new(
+        task_id: TaskId,
+        leader_endpoint: Url,
+        authentication: AuthenticationToken,
+        hpke_keypair: HpkeKeypair,
+        vdaf: V,
+    ) -> Result<Collector<V>, Error> {
+        Self::builder(task_id, leader_endpoint, authentication, hpke_keypair, vdaf).build()
+    }
+
+    /// Construct a [`CollectorBuilder`] from required DAP task parameters and an implementation of
+    /// the task's VDAF.
+    pub fn builder(
+        task_id: TaskId,
+        leader_endpoint: Url,
+        authentication: AuthenticationToken,
+        hpke_keypair: HpkeKeypair,
+        vdaf: V,
+    ) -> CollectorBuilder<V> {
+        CollectorBuilder::new(task_id, leader_endpoint, authentication, hpke_keypair, vdaf)
+    }
+
+    /// Send a collection request to the leader aggregator, wait for it to complete, and return the
+    /// result of the aggregation.
+    pub fn collect<Q: QueryType>(
+        &self,
+        query: Query<Q>,
+        aggregation_parameter: &V::AggregationParam,
+    ) -> Result<Collection<V::AggregateResult, Q>, Error> {
+        self.runtime
+            .block_on(self.inner.collect(query, aggregation_parameter))
+    }
+
+    /// Send a collection request to the leader aggregator, using a randomly generated
+    /// [`CollectionJobId`]. See [`crate::Collector::start_collection`].
+    pub fn start_collection<Q: QueryType>(
+        &self,
+        query: Query<Q>,
+        aggregation_parameter: &V::AggregationParam,
+    ) -> Result<CollectionJob<V::AggregationParam, Q>, Error> {
+        self.runtime
+            .block_on(self.inner.start_collection(query, aggregation_parameter))
+    }
+
+    /// Send a collection request to the leader aggregator, with the given [`CollectionJobId`].
+    pub fn start_collection_with_id<Q: QueryType>(
+        &self,
+        collection_job_id: CollectionJobId,
+        query: Query<Q>,
+        aggregation_parameter: &V::AggregationParam,
+    ) -> Result<CollectionJob<V::AggregationParam, Q>, Error> {
+        self.runtime.block_on(self.inner.start_collection_with_id(
+            collection_job_id,
+            query,
+            aggregation_parameter,
+        ))
+    }
+
+    /// Request the results of an in-progress collection from the leader aggregator.
+    pub fn poll_once<Q: QueryType>(
+        &self,
+        job: &CollectionJob<V::AggregationParam, Q>,
+    ) -> Result<PollResult<V::AggregateResult, Q>, Error> {
+        self.runtime.block_on(self.inner.poll_once(job))
+    }
+
+    /// Repeatedly request the result of an in-progress collection job until it completes.
+    pub fn poll_until_complete<Q: QueryType>(
+        &self,
+        job: &CollectionJob<V::AggregationParam, Q>,
+    ) -> Result<Collection<V::AggregateResult, Q>, Error> {
+        self.runtime.block_on(self.inner.poll_until_complete(job))
+    }
+
+    /// Tell the leader aggregator to abandon an in-progress collection job, and delete all related
+    /// state.
+    pub fn delete_collection_job<Q: QueryType>(
+        &self,
+        collection_job: &CollectionJob<V::AggregationParam, Q>,
+    ) -> Result<(), Error> {
+        self.runtime
+            .block_on(self.inner.delete_collection_job(collection_job))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        blocking::Collector,
+        tests::{build_collect_response_time, collection_uri_regex_matcher},
+        Collection,
+    };
+    use chrono::{DateTime, Utc};
+    use janus_core::{
+        auth_tokens::AuthenticationToken,
+        hpke::test_util::generate_test_hpke_config_and_private_key,
+        retries::test_util::test_http_request_exponential_backoff,
+        test_util::{install_test_trace_subscriber, run_vdaf},
+    };
+    use janus_messages::{
+        query_type::TimeInterval, Collection as CollectionMessage, CollectionReq, Duration,
+        Interval, PartialBatchSelector, Query, Time,
+    };
+    use prio::{codec::Encode, vdaf::prio3::Prio3};
+    use rand::random;
+    use reqwest::{header::CONTENT_TYPE, Url};
+
+    #[test]
+    fn successful_collect_prio3_count() {
+        install_test_trace_subscriber();
+        let mut server = mockito::Server::new();
+        let vdaf = Prio3::new_count(2).unwrap();
+        let transcript = run_vdaf(&vdaf, &random(), &(), &random(), &true);
+        let collector = Collector::builder(
+            random(),
+            Url::parse(&server.url()).unwrap(),
+            AuthenticationToken::new_bearer_token_from_string("Y29sbGVjdG9yIHRva2Vu").unwrap(),
+            generate_test_hpke_config_and_private_key(),
+            vdaf,
+        )
+        .with_http_request_backoff(test_http_request_exponential_backoff())
+        .with_collect_poll_backoff(test_http_request_exponential_backoff())
+        .build()
+        .unwrap();
+
+        let batch_interval = Interval::new(
+            Time::from_seconds_since_epoch(1_000_000),
+            Duration::from_seconds(3600),
+        )
+        .unwrap();
+        let collect_resp =
+            build_collect_response_time(&transcript, &collector.inner, &(), batch_interval);
+        let matcher = collection_uri_regex_matcher(&collector.inner.task_id);
+
+        let mocked_collect_start = server
+            .mock("PUT", matcher.clone())
+            .match_header(
+                CONTENT_TYPE.as_str(),
+                CollectionReq::<TimeInterval>::MEDIA_TYPE,
+            )
+            .with_status(201)
+            .expect(1)
+            .create();
+        let mocked_collect_accepted = server
+            .mock("POST", matcher.clone())
+            .with_status(202)
+            .expect(1)
+            .create();
+        let mocked_collect_complete = server
+            .mock("POST", matcher)
+            .with_status(200)
+            .with_header(
+                CONTENT_TYPE.as_str(),
+                CollectionMessage::<TimeInterval>::MEDIA_TYPE,
+            )
+            .with_body(collect_resp.get_encoded().unwrap())
+            .expect(1)
+            .create();
+
+        let collection = collector
+            .collect(Query::new_time_interval(batch_interval), &())
+            .unwrap();
+        assert_eq!(
+            collection,
+            Collection::new(
+                PartialBatchSelector::new_time_interval(),
+                1,
+                (
+                    DateTime::<Utc>::from_timestamp(1_000_000, 0).unwrap(),
+                    chrono::Duration::try_seconds(3600).unwrap(),
+                ),
+                1,
+            ),
+        );
+
+        mocked_collect_start.assert();
+        mocked_collect_accepted.assert();
+        mocked_collect_complete.assert();
+    }
+}
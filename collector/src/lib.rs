@@ -48,9 +48,12 @@
 //! let aggregation_result = collector.collect(Query::new_time_interval(interval), &()).await.unwrap();
 //! # }
 //! ```
+//!
+//! Programs that don't use async Rust may use the equivalent [`blocking::Collector`] instead.
 
 #![cfg_attr(docsrs, feature(doc_cfg))]
 
+pub mod blocking;
 mod credential;
 
 use backoff::backoff::Backoff;
@@ -58,14 +61,17 @@ pub use backoff::ExponentialBackoff;
 use chrono::{DateTime, Duration, TimeZone, Utc};
 pub use credential::PrivateCollectorCredential;
 use derivative::Derivative;
-pub use janus_core::auth_tokens::AuthenticationToken;
 use janus_core::{
+    aggregator_endpoint_join,
     hpke::{self, HpkeApplicationInfo, HpkeKeypair},
     http::HttpErrorResponse,
+    ids::generate_collection_job_id,
     retries::{http_request_exponential_backoff, retry_http_request},
     time::{DurationExt, TimeExt},
+    tls::{self, SpkiPins},
     url_ensure_trailing_slash,
 };
+pub use janus_core::{auth_tokens::AuthenticationToken, tls::SpkiPin};
 use janus_messages::{
     query_type::{QueryType, TimeInterval},
     AggregateShareAad, BatchSelector, Collection as CollectionMessage, CollectionJobId,
@@ -75,7 +81,6 @@ use prio::{
     codec::{Decode, Encode, ParameterizedDecode},
     vdaf,
 };
-use rand::random;
 use reqwest::{
     header::{HeaderValue, ToStrError, CONTENT_TYPE, RETRY_AFTER},
     StatusCode,
@@ -121,6 +126,12 @@ pub enum Error {
     ReportCountOverflow,
     #[error("message error: {0}")]
     Message(#[from] janus_messages::Error),
+    #[error("couldn't start async runtime: {0}")]
+    Runtime(std::io::Error),
+    #[error("invalid parameter {0}")]
+    InvalidParameter(&'static str),
+    #[error("TLS pinning error: {0}")]
+    Tls(#[from] tls::Error),
 }
 
 impl From<HttpErrorResponse> for Error {
@@ -139,13 +150,16 @@ static COLLECTOR_USER_AGENT: &str = concat!(
 
 /// Construct a [`reqwest::Client`] suitable for use in a DAP [`Collector`].
 pub fn default_http_client() -> Result<reqwest::Client, Error> {
-    Ok(reqwest::Client::builder()
+    Ok(default_http_client_builder().build()?)
+}
+
+fn default_http_client_builder() -> reqwest::ClientBuilder {
+    reqwest::Client::builder()
         // Clients may override default timeouts using
         // CollectorBuilder::with_http_client
         .timeout(StdDuration::from_secs(30))
         .connect_timeout(StdDuration::from_secs(10))
         .user_agent(COLLECTOR_USER_AGENT)
-        .build()?)
 }
 
 /// Collector state related to a collection job that is in progress.
@@ -303,6 +317,8 @@ pub struct CollectorBuilder<V: vdaf::Collector> {
 
     /// HTTPS client.
     http_client: Option<reqwest::Client>,
+    /// Public keys that the leader may present.
+    leader_spki_pins: Vec<SpkiPin>,
     /// Parameters to use when retrying HTTP requests.
     http_request_retry_parameters: ExponentialBackoff,
     /// Parameters to use when waiting for a collection job to be processed.
@@ -326,6 +342,7 @@ impl<V: vdaf::Collector> CollectorBuilder<V> {
             hpke_keypair,
             vdaf,
             http_client: None,
+            leader_spki_pins: Vec::new(),
             http_request_retry_parameters: http_request_exponential_backoff(),
             collect_poll_wait_parameters: ExponentialBackoff {
                 initial_interval: StdDuration::from_secs(15),
@@ -339,10 +356,20 @@ impl<V: vdaf::Collector> CollectorBuilder<V> {
 
     /// Finalize construction of a [`Collector`].
     pub fn build(self) -> Result<Collector<V>, Error> {
-        let http_client = if let Some(http_client) = self.http_client {
-            http_client
-        } else {
-            default_http_client()?
+        let http_client = match self.http_client {
+            // Pins can't be checked by a client configured elsewhere, so refuse to run without them.
+            Some(_) if !self.leader_spki_pins.is_empty() => {
+                return Err(Error::InvalidParameter(
+                    "SPKI pins can't be used with a custom HTTP client",
+                ))
+            }
+            Some(http_client) => http_client,
+            None if self.leader_spki_pins.is_empty() => default_http_client()?,
+            None => {
+                let mut pins = SpkiPins::default();
+                pins.add_endpoint(&self.leader_endpoint, self.leader_spki_pins)?;
+                pins.apply(default_http_client_builder()).build()?
+            }
         };
         Ok(Collector {
             task_id: self.task_id,
@@ -362,6 +389,14 @@ impl<V: vdaf::Collector> CollectorBuilder<V> {
         self
     }
 
+    /// Pin the public keys that the leader may present. Requests to the leader fail unless its
+    /// certificate chain validates as usual and includes one of these keys. Pins can't be combined
+    /// with [`Self::with_http_client`].
+    pub fn with_leader_spki_pins(mut self, pins: Vec<SpkiPin>) -> Self {
+        self.leader_spki_pins = pins;
+        self
+    }
+
     /// Replace the exponential backoff settings used for HTTP requests.
     pub fn with_http_request_backoff(mut self, backoff: ExponentialBackoff) -> Self {
         self.http_request_retry_parameters = backoff;
@@ -428,10 +463,10 @@ impl<V: vdaf::Collector> Collector<V> {
 
     /// Construct a URI for a collection.
     fn collection_job_uri(&self, collection_job_id: CollectionJobId) -> Result<Url, Error> {
-        Ok(self.leader_endpoint.join(&format!(
-            "tasks/{}/collection_jobs/{collection_job_id}",
-            self.task_id
-        ))?)
+        Ok(aggregator_endpoint_join(
+            &self.leader_endpoint,
+            &format!("tasks/{}/collection_jobs/{collection_job_id}", self.task_id),
+        )?)
     }
 
     /// Send a collection request to the leader aggregator, wait for it to complete, and return the
@@ -453,15 +488,16 @@ impl<V: vdaf::Collector> Collector<V> {
     ///
     /// Since the collection job ID is randomly generated in this function, it is at risk of being
     /// lost if the program crashes before the generated ID is returned to the caller. It is
-    /// recommended that collectors instead generate an ID themselves, store it to non-volatile
-    /// storage, then invoke [`Self::start_collection_with_id`].
+    /// recommended that collectors instead generate an ID themselves (e.g. with
+    /// [`generate_collection_job_id`]), store it to non-volatile storage, then invoke
+    /// [`Self::start_collection_with_id`].
     #[tracing::instrument(skip(aggregation_parameter), err)]
     pub async fn start_collection<Q: QueryType>(
         &self,
         query: Query<Q>,
         aggregation_parameter: &V::AggregationParam,
     ) -> Result<CollectionJob<V::AggregationParam, Q>, Error> {
-        self.start_collection_with_id(random(), query, aggregation_parameter)
+        self.start_collection_with_id(generate_collection_job_id(), query, aggregation_parameter)
             .await
     }
 
@@ -793,7 +829,7 @@ mod tests {
         .unwrap()
     }
 
-    fn collection_uri_regex_matcher(task_id: &TaskId) -> Matcher {
+    pub(crate) fn collection_uri_regex_matcher(task_id: &TaskId) -> Matcher {
         // Matches on the relative path for a collection job resource. The Base64 URL-safe encoding
         // of a collection ID is always 22 characters.
         Matcher::Regex(format!(
@@ -801,7 +837,7 @@ mod tests {
         ))
     }
 
-    fn build_collect_response_time<
+    pub(crate) fn build_collect_response_time<
         const SEED_SIZE: usize,
         V: vdaf::Aggregator<SEED_SIZE, 16> + vdaf::Collector,
     >(
@@ -871,6 +907,36 @@ mod tests {
         )
     }
 
+    #[test]
+    fn leader_spki_pins() {
+        let builder = |leader_endpoint: &str| {
+            Collector::builder(
+                random(),
+                leader_endpoint.parse().unwrap(),
+                AuthenticationToken::new_bearer_token_from_string("Y29sbGVjdG9yIHRva2Vu").unwrap(),
+                generate_test_hpke_config_and_private_key(),
+                dummy::Vdaf::new(1),
+            )
+            .with_leader_spki_pins(Vec::from([random::<[u8; 32]>().into()]))
+        };
+
+        builder("https://leader.example.com/").build().unwrap();
+
+        // Pins can't be checked for plain HTTP endpoints.
+        assert_matches!(
+            builder("http://leader.example.com/").build(),
+            Err(Error::Tls(_))
+        );
+
+        // Pins can't be checked by HTTP clients configured elsewhere.
+        assert_matches!(
+            builder("https://leader.example.com/")
+                .with_http_client(reqwest::Client::new())
+                .build(),
+            Err(Error::InvalidParameter(_))
+        );
+    }
+
     #[test]
     fn leader_endpoint_end_in_slash() {
         let hpke_keypair = generate_test_hpke_config_and_private_key();
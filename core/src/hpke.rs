@@ -235,6 +235,89 @@ pub fn generate_hpke_config_and_private_key(
     ))
 }
 
+/// A combination of HPKE algorithms: a KEM, a KDF, and an AEAD.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HpkeCiphersuite {
+    kem_id: HpkeKemId,
+    kdf_id: HpkeKdfId,
+    aead_id: HpkeAeadId,
+}
+
+impl HpkeCiphersuite {
+    /// The KEMs supported by Janus.
+    const SUPPORTED_KEMS: [HpkeKemId; 2] = [HpkeKemId::X25519HkdfSha256, HpkeKemId::P256HkdfSha256];
+    /// The KDFs supported by Janus.
+    const SUPPORTED_KDFS: [HpkeKdfId; 3] = [
+        HpkeKdfId::HkdfSha256,
+        HpkeKdfId::HkdfSha384,
+        HpkeKdfId::HkdfSha512,
+    ];
+    /// The AEADs supported by Janus.
+    const SUPPORTED_AEADS: [HpkeAeadId; 3] = [
+        HpkeAeadId::Aes128Gcm,
+        HpkeAeadId::Aes256Gcm,
+        HpkeAeadId::ChaCha20Poly1305,
+    ];
+
+    pub const fn new(kem_id: HpkeKemId, kdf_id: HpkeKdfId, aead_id: HpkeAeadId) -> Self {
+        Self {
+            kem_id,
+            kdf_id,
+            aead_id,
+        }
+    }
+
+    /// Returns every ciphersuite supported by Janus.
+    pub fn all_supported() -> Vec<Self> {
+        Self::SUPPORTED_KEMS
+            .iter()
+            .flat_map(|kem_id| {
+                Self::SUPPORTED_KDFS.iter().flat_map(move |kdf_id| {
+                    Self::SUPPORTED_AEADS
+                        .iter()
+                        .map(move |aead_id| Self::new(*kem_id, *kdf_id, *aead_id))
+                })
+            })
+            .collect()
+    }
+
+    pub fn kem_id(&self) -> &HpkeKemId {
+        &self.kem_id
+    }
+
+    pub fn kdf_id(&self) -> &HpkeKdfId {
+        &self.kdf_id
+    }
+
+    pub fn aead_id(&self) -> &HpkeAeadId {
+        &self.aead_id
+    }
+
+    /// Returns true if Janus can encrypt and decrypt messages using this ciphersuite.
+    pub fn is_supported(&self) -> bool {
+        Self::SUPPORTED_KEMS.contains(&self.kem_id)
+            && Self::SUPPORTED_KDFS.contains(&self.kdf_id)
+            && Self::SUPPORTED_AEADS.contains(&self.aead_id)
+    }
+}
+
+/// The default ciphersuite is DHKEM(X25519, HKDF-SHA256), HKDF-SHA256, and AES-128-GCM.
+impl Default for HpkeCiphersuite {
+    fn default() -> Self {
+        Self::new(
+            HpkeKemId::X25519HkdfSha256,
+            HpkeKdfId::HkdfSha256,
+            HpkeAeadId::Aes128Gcm,
+        )
+    }
+}
+
+impl From<&HpkeConfig> for HpkeCiphersuite {
+    fn from(config: &HpkeConfig) -> Self {
+        Self::new(*config.kem_id(), *config.kdf_id(), *config.aead_id())
+    }
+}
+
 /// An HPKE configuration and its corresponding private key.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct HpkeKeypair {
@@ -260,6 +343,19 @@ impl HpkeKeypair {
     pub fn private_key(&self) -> &HpkePrivateKey {
         &self.private_key
     }
+
+    /// Generate a new keypair using the algorithms in the given ciphersuite.
+    pub fn generate(
+        hpke_config_id: HpkeConfigId,
+        ciphersuite: &HpkeCiphersuite,
+    ) -> Result<HpkeKeypair, Error> {
+        generate_hpke_config_and_private_key(
+            hpke_config_id,
+            ciphersuite.kem_id,
+            ciphersuite.kdf_id,
+            ciphersuite.aead_id,
+        )
+    }
 }
 
 #[cfg(feature = "test-util")]
@@ -294,7 +390,9 @@ pub mod test_util {
 mod tests {
     use super::{test_util::generate_test_hpke_config_and_private_key, HpkeApplicationInfo, Label};
     #[allow(deprecated)]
-    use crate::hpke::{open, seal, HpkeKeypair, HpkePrivateKey};
+    use crate::hpke::{
+        is_hpke_config_supported, open, seal, HpkeCiphersuite, HpkeKeypair, HpkePrivateKey,
+    };
     use hpke_dispatch::{Kem, Keypair};
     use janus_messages::{
         HpkeAeadId, HpkeCiphertext, HpkeConfig, HpkeConfigId, HpkeKdfId, HpkeKemId, HpkePublicKey,
@@ -466,6 +564,46 @@ mod tests {
         }
     }
 
+    #[test]
+    fn generate_all_supported_ciphersuites() {
+        let application_info =
+            HpkeApplicationInfo::new(&Label::InputShare, &Role::Client, &Role::Leader);
+        let message = b"a message that is secret";
+        let associated_data = b"message associated data";
+
+        let ciphersuites = HpkeCiphersuite::all_supported();
+        assert_eq!(ciphersuites.len(), 18);
+        assert_eq!(ciphersuites[0], HpkeCiphersuite::default());
+        for ciphersuite in ciphersuites {
+            assert!(ciphersuite.is_supported());
+            let keypair = HpkeKeypair::generate(HpkeConfigId::from(1), &ciphersuite).unwrap();
+            assert_eq!(HpkeCiphersuite::from(keypair.config()), ciphersuite);
+            is_hpke_config_supported(keypair.config()).unwrap();
+
+            let ciphertext = seal(
+                keypair.config(),
+                &application_info,
+                message,
+                associated_data,
+            )
+            .unwrap();
+            let plaintext =
+                open(&keypair, &application_info, &ciphertext, associated_data).unwrap();
+            assert_eq!(plaintext, message);
+        }
+    }
+
+    #[test]
+    fn unsupported_ciphersuite() {
+        let ciphersuite = HpkeCiphersuite::new(
+            HpkeKemId::P384HkdfSha384,
+            HpkeKdfId::HkdfSha384,
+            HpkeAeadId::Aes256Gcm,
+        );
+        assert!(!ciphersuite.is_supported());
+        HpkeKeypair::generate(HpkeConfigId::from(1), &ciphersuite).unwrap_err();
+    }
+
     #[derive(Deserialize)]
     struct EncryptionRecord {
         #[serde(with = "hex")]
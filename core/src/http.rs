@@ -3,8 +3,14 @@ use anyhow::{anyhow, Context};
 use http::StatusCode;
 use http_api_problem::{HttpApiProblem, PROBLEM_JSON_MEDIA_TYPE};
 use janus_messages::problem_type::DapProblemType;
-use reqwest::{header::CONTENT_TYPE, Response};
-use std::fmt::{self, Display, Formatter};
+use reqwest::{
+    header::{CONTENT_TYPE, RETRY_AFTER},
+    Response,
+};
+use std::{
+    fmt::{self, Display, Formatter},
+    time::Duration,
+};
 use tracing::warn;
 use trillium::Conn;
 
@@ -13,6 +19,7 @@ use trillium::Conn;
 pub struct HttpErrorResponse {
     problem_details: HttpApiProblem,
     dap_problem_type: Option<DapProblemType>,
+    retry_after: Option<Duration>,
 }
 
 impl HttpErrorResponse {
@@ -21,19 +28,29 @@ impl HttpErrorResponse {
     /// the response's status code. (see [RFC 7807](https://www.rfc-editor.org/rfc/rfc7807.html))
     pub async fn from_response(response: Response) -> Self {
         let status = response.status();
+        let retry_after = parse_retry_after(response.headers().get(RETRY_AFTER));
         if let Some(content_type) = response.headers().get(CONTENT_TYPE) {
             if content_type == PROBLEM_JSON_MEDIA_TYPE {
                 match response.json::<HttpApiProblem>().await {
                     Ok(mut problem) => {
                         problem.status = Some(status);
                         // Unwrap safety: the conversion always succeeds if the status is populated.
-                        return problem.try_into().unwrap();
+                        let response: Self = problem.try_into().unwrap();
+                        return response.with_retry_after(retry_after);
                     }
                     Err(error) => warn!(%error, "Failed to parse problem details"),
                 }
             }
         }
-        status.into()
+        Self::from(status).with_retry_after(retry_after)
+    }
+
+    /// Sets how long the server asked clients to wait before retrying the request.
+    pub fn with_retry_after(self, retry_after: Option<Duration>) -> Self {
+        Self {
+            retry_after,
+            ..self
+        }
     }
 
     /// The HTTP status code returned by the server.
@@ -62,6 +79,18 @@ impl HttpErrorResponse {
     pub fn dap_problem_type(&self) -> Option<&DapProblemType> {
         self.dap_problem_type.as_ref()
     }
+
+    /// How long the server asked clients to wait before retrying the request, from the
+    /// `Retry-After` header, if present. Only the delay-seconds form of the header is recognized.
+    pub fn retry_after(&self) -> Option<Duration> {
+        self.retry_after
+    }
+}
+
+/// Parses the delay-seconds form of a `Retry-After` header value.
+fn parse_retry_after(value: Option<&reqwest::header::HeaderValue>) -> Option<Duration> {
+    let seconds = value?.to_str().ok()?.trim().parse().ok()?;
+    Some(Duration::from_secs(seconds))
 }
 
 /// The error type returned when converting an [`HttpApiProblem`] without a status code into an
@@ -83,6 +112,7 @@ impl TryFrom<HttpApiProblem> for HttpErrorResponse {
         Ok(Self {
             problem_details,
             dap_problem_type,
+            retry_after: None,
         })
     }
 }
@@ -92,6 +122,7 @@ impl From<StatusCode> for HttpErrorResponse {
         Self {
             problem_details: HttpApiProblem::new(value),
             dap_problem_type: None,
+            retry_after: None,
         }
     }
 }
@@ -0,0 +1,24 @@
+//! Generation of the random identifiers which Janus assigns to protocol objects.
+//!
+//! All such identifiers are drawn from the operating system's CSPRNG, via this module, so that
+//! they can't be predicted by other protocol participants and are vanishingly unlikely to collide.
+//! Code which stores a generated identifier should still treat a collision as possible, and retry
+//! with a fresh identifier rather than failing.
+
+use janus_messages::{AggregationJobId, BatchId, CollectionJobId};
+use rand::{rngs::OsRng, Rng};
+
+/// Generates a new aggregation job ID.
+pub fn generate_aggregation_job_id() -> AggregationJobId {
+    OsRng.gen()
+}
+
+/// Generates a new batch ID, for fixed-size tasks.
+pub fn generate_batch_id() -> BatchId {
+    OsRng.gen()
+}
+
+/// Generates a new collection job ID.
+pub fn generate_collection_job_id() -> CollectionJobId {
+    OsRng.gen()
+}
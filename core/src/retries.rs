@@ -150,21 +150,11 @@ where
         rslt: Result<T, reqwest::Error>,
     ) -> Result<T, backoff::Error<Result<HttpErrorResponse, reqwest::Error>>> {
         rslt.map_err(|err| {
-            if err.is_timeout() || err.is_connect() {
+            if is_retryable_network_error(&err) {
                 warn!(?err, "Encountered retryable network error");
                 return backoff::Error::transient(Err(err));
             }
 
-            if let Some(io_error) = find_io_error(&err) {
-                if let std::io::ErrorKind::ConnectionRefused
-                | std::io::ErrorKind::ConnectionReset
-                | std::io::ErrorKind::ConnectionAborted = io_error.kind()
-                {
-                    warn!(?err, "Encountered retryable network error");
-                    return backoff::Error::transient(Err(err));
-                }
-            }
-
             debug!("Encountered non-retryable network error");
             backoff::Error::permanent(Err(err))
         })
@@ -202,6 +192,22 @@ where
     .await
 }
 
+/// Returns true if the given network error is transient, i.e. a timeout or a problem establishing
+/// or maintaining a connection, such that the request may succeed if retried.
+pub fn is_retryable_network_error(err: &reqwest::Error) -> bool {
+    if err.is_timeout() || err.is_connect() {
+        return true;
+    }
+    matches!(
+        find_io_error(err).map(std::io::Error::kind),
+        Some(
+            std::io::ErrorKind::ConnectionRefused
+                | std::io::ErrorKind::ConnectionReset
+                | std::io::ErrorKind::ConnectionAborted
+        )
+    )
+}
+
 pub fn is_retryable_http_status(status: StatusCode) -> bool {
     (status.is_server_error() && status != StatusCode::NOT_IMPLEMENTED)
         || status == StatusCode::TOO_MANY_REQUESTS
@@ -0,0 +1,400 @@
+//! Pinning of the public keys that HTTPS servers may present, for deployments that don't rely on
+//! the public web PKI alone to authenticate aggregators.
+//!
+//! A pin is the SHA-256 digest of a certificate's DER-encoded SubjectPublicKeyInfo, in the same
+//! form as the `pin-sha256` directive of [RFC 7469][1]. A connection to a pinned host succeeds
+//! only if the server's certificate chain passes the usual validation against the web PKI roots,
+//! and some certificate in the chain carries a pinned public key.
+//!
+//! [1]: https://datatracker.ietf.org/doc/html/rfc7469#section-2.4
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ring::digest::{digest, SHA256};
+use rustls::{
+    client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier},
+    Certificate, ClientConfig, OwnedTrustAnchor, RootCertStore, ServerName,
+};
+use std::{
+    collections::HashMap,
+    fmt::{self, Debug, Display, Formatter},
+    str::FromStr,
+    sync::Arc,
+    time::SystemTime,
+};
+use url::{Host, Url};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("invalid SPKI pin: {0}")]
+    InvalidPin(&'static str),
+    #[error("base64 decode failure: {0}")]
+    Base64Decode(#[from] base64::DecodeError),
+    #[error("SPKI pins can only be used with https endpoints, not {0}")]
+    NotHttps(Url),
+}
+
+/// The SHA-256 digest of a DER-encoded SubjectPublicKeyInfo.
+///
+/// Pins are written, and parsed from strings, as `sha256/` followed by the standard base64
+/// encoding of the digest, as output by e.g.
+///
+/// ```text
+/// openssl x509 -in cert.pem -pubkey -noout | openssl pkey -pubin -outform der \
+///     | openssl dgst -sha256 -binary | base64
+/// ```
+///
+/// The `sha256/` prefix may be omitted when parsing.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SpkiPin([u8; 32]);
+
+impl SpkiPin {
+    const PREFIX: &'static str = "sha256/";
+
+    /// Computes the pin of a DER-encoded SubjectPublicKeyInfo.
+    pub fn from_spki_der(spki: &[u8]) -> Self {
+        // Unwrap safety: SHA-256 digests are always 32 bytes long.
+        Self(digest(&SHA256, spki).as_ref().try_into().unwrap())
+    }
+
+    /// Computes the pin of the public key in a DER-encoded X.509 certificate, or returns `None` if
+    /// the certificate can't be parsed.
+    pub fn from_certificate_der(certificate: &[u8]) -> Option<Self> {
+        subject_public_key_info(certificate).map(Self::from_spki_der)
+    }
+}
+
+impl From<[u8; 32]> for SpkiPin {
+    fn from(digest: [u8; 32]) -> Self {
+        Self(digest)
+    }
+}
+
+impl AsRef<[u8; 32]> for SpkiPin {
+    fn as_ref(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl FromStr for SpkiPin {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let encoded = s.strip_prefix(Self::PREFIX).unwrap_or(s);
+        let digest = STANDARD.decode(encoded)?;
+        Ok(Self(digest.try_into().map_err(|_| {
+            Error::InvalidPin("SHA-256 digest must be 32 bytes long")
+        })?))
+    }
+}
+
+impl Display for SpkiPin {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", Self::PREFIX, STANDARD.encode(self.0))
+    }
+}
+
+impl Debug for SpkiPin {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "SpkiPin({self})")
+    }
+}
+
+/// The public keys pinned for each of a set of hosts.
+#[derive(Clone, Debug, Default)]
+pub struct SpkiPins {
+    hosts: HashMap<String, Vec<SpkiPin>>,
+}
+
+impl SpkiPins {
+    /// Pins the public keys that the host of `endpoint` may present. Connections to the host will
+    /// fail unless its certificate chain includes one of the given keys. Fails if `endpoint` isn't
+    /// an https URL, since its server could then not be checked.
+    pub fn add_endpoint(
+        &mut self,
+        endpoint: &Url,
+        pins: impl IntoIterator<Item = SpkiPin>,
+    ) -> Result<(), Error> {
+        let host = match (endpoint.scheme(), endpoint.host()) {
+            ("https", Some(host)) => host_key(&host),
+            _ => return Err(Error::NotHttps(endpoint.clone())),
+        };
+        self.hosts.entry(host).or_default().extend(pins);
+        Ok(())
+    }
+
+    /// Returns true if no keys are pinned.
+    pub fn is_empty(&self) -> bool {
+        self.hosts.values().all(Vec::is_empty)
+    }
+
+    /// Configures an HTTP client to check pinned keys. Hosts without pinned keys are validated
+    /// against the web PKI roots only.
+    pub fn apply(self, builder: reqwest::ClientBuilder) -> reqwest::ClientBuilder {
+        let mut roots = RootCertStore::empty();
+        roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|anchor| {
+            OwnedTrustAnchor::from_subject_spki_name_constraints(
+                anchor.subject,
+                anchor.spki,
+                anchor.name_constraints,
+            )
+        }));
+        let verifier = PinningVerifier {
+            inner: WebPkiVerifier::new(roots, None),
+            pins: self,
+        };
+        builder.use_preconfigured_tls(
+            ClientConfig::builder()
+                .with_safe_defaults()
+                .with_custom_certificate_verifier(Arc::new(verifier))
+                .with_no_client_auth(),
+        )
+    }
+
+    /// Checks that some certificate in the chain presented by `host` has a pinned key, if any keys
+    /// are pinned for it.
+    fn check(&self, host: &str, certificates: &[&Certificate]) -> Result<(), rustls::Error> {
+        let pins = match self.hosts.get(host) {
+            Some(pins) if !pins.is_empty() => pins,
+            _ => return Ok(()),
+        };
+        if certificates
+            .iter()
+            .filter_map(|certificate| SpkiPin::from_certificate_der(&certificate.0))
+            .any(|pin| pins.contains(&pin))
+        {
+            Ok(())
+        } else {
+            Err(rustls::Error::General(format!(
+                "certificate chain presented by {host} has no pinned public key"
+            )))
+        }
+    }
+}
+
+/// Validates certificate chains against the web PKI roots, then checks pinned keys.
+struct PinningVerifier {
+    inner: WebPkiVerifier,
+    pins: SpkiPins,
+}
+
+impl ServerCertVerifier for PinningVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verified = self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            scts,
+            ocsp_response,
+            now,
+        )?;
+        let host = match server_name {
+            ServerName::DnsName(name) => name.as_ref().to_ascii_lowercase(),
+            ServerName::IpAddress(address) => address.to_string(),
+            _ => {
+                return Err(rustls::Error::General(
+                    "unsupported server name type".to_string(),
+                ))
+            }
+        };
+        let certificates: Vec<_> = [end_entity].into_iter().chain(intermediates).collect();
+        self.pins.check(&host, &certificates)?;
+        Ok(verified)
+    }
+}
+
+/// The form of a URL's host that matches the server name of TLS connections to it.
+fn host_key(host: &Host<&str>) -> String {
+    match host {
+        Host::Domain(domain) => domain.to_ascii_lowercase(),
+        Host::Ipv4(address) => address.to_string(),
+        Host::Ipv6(address) => address.to_string(),
+    }
+}
+
+/// Returns the DER-encoded SubjectPublicKeyInfo of a DER-encoded X.509 certificate.
+///
+/// ```text
+/// Certificate ::= SEQUENCE { tbsCertificate TBSCertificate, ... }
+/// TBSCertificate ::= SEQUENCE {
+///     version [0] EXPLICIT Version DEFAULT v1,
+///     serialNumber, signature, issuer, validity, subject,
+///     subjectPublicKeyInfo SubjectPublicKeyInfo,
+///     ... }
+/// ```
+fn subject_public_key_info(certificate: &[u8]) -> Option<&[u8]> {
+    const SEQUENCE: u8 = 0x30;
+    const VERSION: u8 = 0xa0;
+
+    let certificate = der_element(certificate)?;
+    if certificate.tag != SEQUENCE {
+        return None;
+    }
+    let tbs_certificate = der_element(certificate.contents)?;
+    if tbs_certificate.tag != SEQUENCE {
+        return None;
+    }
+
+    let mut rest = tbs_certificate.contents;
+    if rest.first() == Some(&VERSION) {
+        rest = der_element(rest)?.rest;
+    }
+    // Skip the serial number, signature algorithm, issuer, validity, and subject.
+    for _ in 0..5 {
+        rest = der_element(rest)?.rest;
+    }
+    let spki = der_element(rest)?;
+    (spki.tag == SEQUENCE).then_some(spki.encoding)
+}
+
+/// A DER element split off the front of some input by [`der_element`].
+struct DerElement<'a> {
+    /// The element's tag.
+    tag: u8,
+    /// The element's whole encoding, including its tag and length.
+    encoding: &'a [u8],
+    /// The element's contents.
+    contents: &'a [u8],
+    /// The input following the element.
+    rest: &'a [u8],
+}
+
+/// Splits the first DER element off `input`. Only single-byte tags are supported, which suffices
+/// for certificates.
+fn der_element(input: &[u8]) -> Option<DerElement<'_>> {
+    let (&tag, rest) = input.split_first()?;
+    let (&first_length_byte, mut rest) = rest.split_first()?;
+    let length = if first_length_byte < 0x80 {
+        usize::from(first_length_byte)
+    } else {
+        let length_bytes = usize::from(first_length_byte & 0x7f);
+        if length_bytes == 0 || length_bytes > 4 || rest.len() < length_bytes {
+            return None;
+        }
+        let (length, after_length) = rest.split_at(length_bytes);
+        rest = after_length;
+        length
+            .iter()
+            .fold(0usize, |length, &byte| (length << 8) | usize::from(byte))
+    };
+    if rest.len() < length {
+        return None;
+    }
+    let header_length = input.len() - rest.len();
+    let (contents, rest) = rest.split_at(length);
+    Some(DerElement {
+        tag,
+        encoding: &input[..header_length + length],
+        contents,
+        rest,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SpkiPin, SpkiPins};
+    use assert_matches::assert_matches;
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use rustls::Certificate;
+
+    /// A certificate for 127.0.0.1, issued by an mkcert development CA.
+    const CERTIFICATE: &str = "\
+MIIB/DCCAaKgAwIBAgIRAMR+9sWCX0K8SkVgyAzf15MwCgYIKoZIzj0EAwIwXzEe
+MBwGA1UEChMVbWtjZXJ0IGRldmVsb3BtZW50IENBMRowGAYDVQQLDBFyb290QGQ2
+YTI2ZDQ4NGE1YTEhMB8GA1UEAwwYbWtjZXJ0IHJvb3RAZDZhMjZkNDg0YTVhMB4X
+DTIzMTAyNjIxNDU1MVoXDTI2MDEyNjIxNDU1MVowRTEnMCUGA1UEChMebWtjZXJ0
+IGRldmVsb3BtZW50IGNlcnRpZmljYXRlMRowGAYDVQQLDBFyb290QGQ2YTI2ZDQ4
+NGE1YTBZMBMGByqGSM49AgEGCCqGSM49AwEHA0IABJxyZpYo6fbp7UDwaY3bWA+8
+C52RI9DekCQohKNK+PAHCjrqz3iCmhz/ItbC6hsXPQ2vfa6ZY+Z5brGDUMS4diKj
+WTBXMA4GA1UdDwEB/wQEAwIFoDATBgNVHSUEDDAKBggrBgEFBQcDATAfBgNVHSME
+GDAWgBTH3WElEQHKMtGc3xtBSXZW5T8k3DAPBgNVHREECDAGhwR/AAABMAoGCCqG
+SM49BAMCA0gAMEUCIC9eMvRLHg9LckkzDjaVQiRogHX9oZsUCaDP8BLmzvynAiEA
+wS1LDaPxQAtNJYRAQFtEAglOE598uelEh4xacKDR2IM=";
+
+    /// The pin of the certificate's public key, computed with openssl.
+    const CERTIFICATE_PIN: &str = "sha256/SOU472F0Y35SNCXk2bTc0dxMRzH9Gqio3FUl6iv7GEY=";
+
+    /// The pin of the issuing CA's public key.
+    const CA_PIN: &str = "sha256/SawYXqOZUTXWLTHvlzhH1ldfkKidUiDKUi5hV17bA5w=";
+
+    fn certificate() -> Certificate {
+        Certificate(STANDARD.decode(CERTIFICATE.replace('\n', "")).unwrap())
+    }
+
+    #[test]
+    fn pin_roundtrip() {
+        let pin: SpkiPin = CERTIFICATE_PIN.parse().unwrap();
+        assert_eq!(pin.to_string(), CERTIFICATE_PIN);
+        assert_eq!(
+            CERTIFICATE_PIN
+                .strip_prefix("sha256/")
+                .unwrap()
+                .parse::<SpkiPin>()
+                .unwrap(),
+            pin
+        );
+
+        assert!("sha256/not base64".parse::<SpkiPin>().is_err());
+        assert!("sha256/AAAA".parse::<SpkiPin>().is_err());
+    }
+
+    #[test]
+    fn pin_of_certificate() {
+        assert_eq!(
+            SpkiPin::from_certificate_der(&certificate().0).unwrap(),
+            CERTIFICATE_PIN.parse().unwrap()
+        );
+
+        // Truncated certificates can't be parsed.
+        let certificate = certificate();
+        assert_eq!(
+            SpkiPin::from_certificate_der(&certificate.0[..certificate.0.len() / 2]),
+            None
+        );
+        assert_eq!(SpkiPin::from_certificate_der(&[]), None);
+    }
+
+    #[test]
+    fn check_pins() {
+        let certificate = certificate();
+        let mut pins = SpkiPins::default();
+        assert!(pins.is_empty());
+        pins.add_endpoint(
+            &"https://127.0.0.1:8443/".parse().unwrap(),
+            [CA_PIN.parse().unwrap(), CERTIFICATE_PIN.parse().unwrap()],
+        )
+        .unwrap();
+        pins.add_endpoint(
+            &"https://Helper.Example.com/dap/".parse().unwrap(),
+            [CA_PIN.parse().unwrap()],
+        )
+        .unwrap();
+        assert!(!pins.is_empty());
+
+        // A chain including a pinned key is accepted.
+        pins.check("127.0.0.1", &[&certificate]).unwrap();
+
+        // A chain without any pinned key is rejected, even if another host pins its key.
+        assert_matches!(
+            pins.check("helper.example.com", &[&certificate]),
+            Err(rustls::Error::General(_))
+        );
+
+        // Hosts without pins are not checked.
+        pins.check("leader.example.com", &[&certificate]).unwrap();
+
+        // Pins can't be checked for plain HTTP endpoints.
+        pins.add_endpoint(
+            &"http://leader.example.com/".parse().unwrap(),
+            [CERTIFICATE_PIN.parse().unwrap()],
+        )
+        .unwrap_err();
+    }
+}
@@ -4,9 +4,13 @@ use prio::{
     field::Field64,
     flp::{
         gadgets::{Mul, ParallelSum},
-        types::SumVec,
+        types::{Count, Histogram, Sum, SumVec},
+    },
+    vdaf::{
+        prio3::{Prio3, Prio3Count, Prio3Histogram, Prio3Sum, Prio3SumVecMultithreaded},
+        xof::XofHmacSha256Aes128,
+        VdafError,
     },
-    vdaf::{prio3::Prio3, xof::XofHmacSha256Aes128, VdafError},
 };
 use serde::{Deserialize, Serialize};
 use std::str;
@@ -19,6 +23,12 @@ pub const VERIFY_KEY_LENGTH: usize = 16;
 /// interoperability with Daphne.
 const ALGORITHM_ID_PRIO3_SUM_VEC_FIELD64_MULTIPROOF_HMACSHA256_AES128: u32 = 0xFFFF_1003;
 
+/// Base of the private use algorithm IDs for Prio3 instantiations with a configurable number of
+/// proofs. The algorithm ID of the corresponding standard VDAF is placed in the second lowest
+/// byte, and the number of proofs in the lowest byte, so that aggregators which disagree on the
+/// number of proofs fail to prepare reports, rather than silently computing something different.
+const ALGORITHM_ID_PRIO3_MULTIPROOF_BASE: u32 = 0xFFFF_2000;
+
 /// The length of the verify key parameter when using [`XofHmacSha256Aes128`]. This XOF is not part
 /// of the VDAF specification.
 pub const VERIFY_KEY_LENGTH_HMACSHA256_AES128: usize = 32;
@@ -83,6 +93,24 @@ pub enum VdafInstance {
     },
     /// A `Prio3` histogram with `length` buckets in it.
     Prio3Histogram { length: usize, chunk_length: usize },
+    /// A `Prio3` counter, using `proofs` proofs for stronger robustness.
+    Prio3CountMultiproof { proofs: u8 },
+    /// A `Prio3` sum, using `proofs` proofs for stronger robustness.
+    Prio3SumMultiproof { proofs: u8, bits: usize },
+    /// A vector of `Prio3` sums, using `proofs` proofs for stronger robustness.
+    Prio3SumVecMultiproof {
+        proofs: u8,
+        bits: usize,
+        length: usize,
+        chunk_length: usize,
+    },
+    /// A `Prio3` histogram with `length` buckets in it, using `proofs` proofs for stronger
+    /// robustness.
+    Prio3HistogramMultiproof {
+        proofs: u8,
+        length: usize,
+        chunk_length: usize,
+    },
     /// A `Prio3` fixed point vector sum with bounded L2 norm.
     #[cfg(feature = "fpvec_bounded_l2")]
     Prio3FixedPointBoundedL2VecSum {
@@ -107,6 +135,17 @@ pub enum VdafInstance {
     FakeFailsPrepStep,
 }
 
+/// Groups of [`VdafInstance`] variants, each handled by one of the internal macros backing
+/// [`vdaf_dispatch`](crate::vdaf_dispatch).
+#[doc(hidden)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VdafDispatchGroup {
+    Prio3,
+    Prio3FixedPointBoundedL2VecSum,
+    Poplar1,
+    Fake,
+}
+
 impl VdafInstance {
     /// Returns the expected length of a VDAF verification key for a VDAF of this type.
     pub fn verify_key_length(&self) -> usize {
@@ -121,9 +160,103 @@ impl VdafInstance {
             }
 
             // All other VDAFs (Prio3 as-specified and Poplar1) have the same verify key length.
-            _ => VERIFY_KEY_LENGTH,
+            VdafInstance::Prio3Count
+            | VdafInstance::Prio3Sum { .. }
+            | VdafInstance::Prio3SumVec { .. }
+            | VdafInstance::Prio3Histogram { .. }
+            | VdafInstance::Prio3CountMultiproof { .. }
+            | VdafInstance::Prio3SumMultiproof { .. }
+            | VdafInstance::Prio3SumVecMultiproof { .. }
+            | VdafInstance::Prio3HistogramMultiproof { .. }
+            | VdafInstance::Poplar1 { .. } => VERIFY_KEY_LENGTH,
+            #[cfg(feature = "fpvec_bounded_l2")]
+            VdafInstance::Prio3FixedPointBoundedL2VecSum { .. } => VERIFY_KEY_LENGTH,
         }
     }
+
+    /// Returns which of the macros backing [`vdaf_dispatch`](crate::vdaf_dispatch) handles this
+    /// VDAF.
+    ///
+    /// This match (unlike any outside this crate) is exhaustive, so adding a variant to
+    /// [`VdafInstance`] won't compile until it's assigned to a group here. Give the new variant an
+    /// arm in that group's `vdaf_dispatch_impl_*` macro, and every dispatch site picks it up.
+    #[doc(hidden)]
+    pub fn dispatch_group(&self) -> VdafDispatchGroup {
+        match self {
+            VdafInstance::Prio3Count
+            | VdafInstance::Prio3Sum { .. }
+            | VdafInstance::Prio3SumVec { .. }
+            | VdafInstance::Prio3SumVecField64MultiproofHmacSha256Aes128 { .. }
+            | VdafInstance::Prio3Histogram { .. }
+            | VdafInstance::Prio3CountMultiproof { .. }
+            | VdafInstance::Prio3SumMultiproof { .. }
+            | VdafInstance::Prio3SumVecMultiproof { .. }
+            | VdafInstance::Prio3HistogramMultiproof { .. } => VdafDispatchGroup::Prio3,
+            #[cfg(feature = "fpvec_bounded_l2")]
+            VdafInstance::Prio3FixedPointBoundedL2VecSum { .. } => {
+                VdafDispatchGroup::Prio3FixedPointBoundedL2VecSum
+            }
+            VdafInstance::Poplar1 { .. } => VdafDispatchGroup::Poplar1,
+            #[cfg(feature = "test-util")]
+            VdafInstance::Fake
+            | VdafInstance::FakeFailsPrepInit
+            | VdafInstance::FakeFailsPrepStep => VdafDispatchGroup::Fake,
+        }
+    }
+
+    /// Checks that the length of this VDAF's measurements is within `limits`. Extremely long
+    /// histograms or vectors make report shares large and expensive for the aggregators to
+    /// prepare, so deployments may refuse to provision such tasks, or to upload to them.
+    pub fn check_measurement_length(
+        &self,
+        limits: &MeasurementLengthLimits,
+    ) -> Result<(), MeasurementLengthError> {
+        let (kind, length, max_length) = match self {
+            VdafInstance::Prio3Histogram { length, .. }
+            | VdafInstance::Prio3HistogramMultiproof { length, .. } => {
+                ("histogram", *length, limits.max_histogram_length)
+            }
+            VdafInstance::Prio3SumVec { length, .. }
+            | VdafInstance::Prio3SumVecField64MultiproofHmacSha256Aes128 { length, .. }
+            | VdafInstance::Prio3SumVecMultiproof { length, .. } => {
+                ("vector", *length, limits.max_vector_length)
+            }
+            #[cfg(feature = "fpvec_bounded_l2")]
+            VdafInstance::Prio3FixedPointBoundedL2VecSum { length, .. } => {
+                ("vector", *length, limits.max_vector_length)
+            }
+            _ => return Ok(()),
+        };
+        match max_length {
+            Some(max_length) if length > max_length => Err(MeasurementLengthError {
+                kind,
+                length,
+                max_length,
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Upper bounds on the length of VDAF measurements. Unset bounds are not enforced.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MeasurementLengthLimits {
+    /// The maximum number of buckets of a histogram.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_histogram_length: Option<usize>,
+    /// The maximum length of a vector of sums (including a vector of counts) or of fixed point
+    /// numbers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_vector_length: Option<usize>,
+}
+
+/// Error returned by [`VdafInstance::check_measurement_length`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("{kind} length {length} exceeds the maximum of {max_length}")]
+pub struct MeasurementLengthError {
+    kind: &'static str,
+    length: usize,
+    max_length: usize,
 }
 
 impl TryFrom<&taskprov::VdafType> for VdafInstance {
@@ -194,10 +327,70 @@ pub fn new_prio3_sum_vec_field64_multiproof_hmacsha256_aes128(
     )
 }
 
+/// Computes the private use algorithm ID for a Prio3 instantiation with multiple proofs, given the
+/// algorithm ID of the corresponding standard VDAF.
+fn prio3_multiproof_algorithm_id(standard_algorithm_id: u32, proofs: u8) -> Result<u32, VdafError> {
+    if proofs < 2 {
+        return Err(VdafError::Uncategorized(
+            "Must use at least two proofs; use the standard VDAF for a single proof".into(),
+        ));
+    }
+    Ok(ALGORITHM_ID_PRIO3_MULTIPROOF_BASE | (standard_algorithm_id << 8) | u32::from(proofs))
+}
+
+/// Construct a Prio3Count VDAF using multiple proofs.
+pub fn new_prio3_count_multiproof(proofs: u8) -> Result<Prio3Count, VdafError> {
+    Prio3::new(
+        2,
+        proofs,
+        prio3_multiproof_algorithm_id(0x0000_0000, proofs)?,
+        Count::new(),
+    )
+}
+
+/// Construct a Prio3Sum VDAF using multiple proofs.
+pub fn new_prio3_sum_multiproof(proofs: u8, bits: usize) -> Result<Prio3Sum, VdafError> {
+    Prio3::new(
+        2,
+        proofs,
+        prio3_multiproof_algorithm_id(0x0000_0001, proofs)?,
+        Sum::new(bits)?,
+    )
+}
+
+/// Construct a Prio3SumVec VDAF using multiple proofs.
+pub fn new_prio3_sum_vec_multiproof(
+    proofs: u8,
+    bits: usize,
+    length: usize,
+    chunk_length: usize,
+) -> Result<Prio3SumVecMultithreaded, VdafError> {
+    Prio3::new(
+        2,
+        proofs,
+        prio3_multiproof_algorithm_id(0x0000_0002, proofs)?,
+        SumVec::new(bits, length, chunk_length)?,
+    )
+}
+
+/// Construct a Prio3Histogram VDAF using multiple proofs.
+pub fn new_prio3_histogram_multiproof(
+    proofs: u8,
+    length: usize,
+    chunk_length: usize,
+) -> Result<Prio3Histogram, VdafError> {
+    Prio3::new(
+        2,
+        proofs,
+        prio3_multiproof_algorithm_id(0x0000_0003, proofs)?,
+        Histogram::new(length, chunk_length)?,
+    )
+}
+
 /// Internal implementation details of [`vdaf_dispatch`](crate::vdaf_dispatch).
 #[macro_export]
-macro_rules! vdaf_dispatch_impl_base {
-    (impl match base $vdaf_instance:expr, ($vdaf:ident, $Vdaf:ident, $VERIFY_KEY_LEN:ident, $dp_strategy:ident, $DpStrategy:ident) => $body:tt) => {
+macro_rules! vdaf_dispatch_impl_prio3 {
+    (impl match prio3 $vdaf_instance:expr, ($vdaf:tt, $Vdaf:ident, $VERIFY_KEY_LEN:ident, $dp_strategy:ident, $DpStrategy:ident) => $body:tt) => {
         match $vdaf_instance {
             ::janus_core::vdaf::VdafInstance::Prio3Count => {
                 let $vdaf = ::prio::vdaf::prio3::Prio3::new_count(2)?;
@@ -268,6 +461,70 @@ macro_rules! vdaf_dispatch_impl_base {
                 $body
             }
 
+            ::janus_core::vdaf::VdafInstance::Prio3CountMultiproof { proofs } => {
+                let $vdaf = janus_core::vdaf::new_prio3_count_multiproof(*proofs)?;
+                type $Vdaf = ::prio::vdaf::prio3::Prio3Count;
+                const $VERIFY_KEY_LEN: usize = ::janus_core::vdaf::VERIFY_KEY_LENGTH;
+                type $DpStrategy = janus_core::dp::NoDifferentialPrivacy;
+                let $dp_strategy = janus_core::dp::NoDifferentialPrivacy;
+                $body
+            }
+
+            ::janus_core::vdaf::VdafInstance::Prio3SumMultiproof { proofs, bits } => {
+                let $vdaf = janus_core::vdaf::new_prio3_sum_multiproof(*proofs, *bits)?;
+                type $Vdaf = ::prio::vdaf::prio3::Prio3Sum;
+                const $VERIFY_KEY_LEN: usize = ::janus_core::vdaf::VERIFY_KEY_LENGTH;
+                type $DpStrategy = janus_core::dp::NoDifferentialPrivacy;
+                let $dp_strategy = janus_core::dp::NoDifferentialPrivacy;
+                $body
+            }
+
+            ::janus_core::vdaf::VdafInstance::Prio3SumVecMultiproof {
+                proofs,
+                bits,
+                length,
+                chunk_length,
+            } => {
+                let $vdaf = janus_core::vdaf::new_prio3_sum_vec_multiproof(
+                    *proofs,
+                    *bits,
+                    *length,
+                    *chunk_length,
+                )?;
+                type $Vdaf = ::prio::vdaf::prio3::Prio3SumVecMultithreaded;
+                const $VERIFY_KEY_LEN: usize = ::janus_core::vdaf::VERIFY_KEY_LENGTH;
+                type $DpStrategy = janus_core::dp::NoDifferentialPrivacy;
+                let $dp_strategy = janus_core::dp::NoDifferentialPrivacy;
+                $body
+            }
+
+            ::janus_core::vdaf::VdafInstance::Prio3HistogramMultiproof {
+                proofs,
+                length,
+                chunk_length,
+            } => {
+                let $vdaf = janus_core::vdaf::new_prio3_histogram_multiproof(
+                    *proofs,
+                    *length,
+                    *chunk_length,
+                )?;
+                type $Vdaf = ::prio::vdaf::prio3::Prio3Histogram;
+                const $VERIFY_KEY_LEN: usize = ::janus_core::vdaf::VERIFY_KEY_LENGTH;
+                type $DpStrategy = janus_core::dp::NoDifferentialPrivacy;
+                let $dp_strategy = janus_core::dp::NoDifferentialPrivacy;
+                $body
+            }
+
+            _ => unreachable!(),
+        }
+    };
+}
+
+/// Internal implementation details of [`vdaf_dispatch`](crate::vdaf_dispatch).
+#[macro_export]
+macro_rules! vdaf_dispatch_impl_poplar1 {
+    (impl match poplar1 $vdaf_instance:expr, ($vdaf:tt, $Vdaf:ident, $VERIFY_KEY_LEN:ident, $dp_strategy:ident, $DpStrategy:ident) => $body:tt) => {
+        match $vdaf_instance {
             ::janus_core::vdaf::VdafInstance::Poplar1 { bits } => {
                 let $vdaf = ::prio::vdaf::poplar1::Poplar1::new_turboshake128(*bits);
                 type $Vdaf =
@@ -287,7 +544,7 @@ macro_rules! vdaf_dispatch_impl_base {
 #[cfg(feature = "fpvec_bounded_l2")]
 #[macro_export]
 macro_rules! vdaf_dispatch_impl_fpvec_bounded_l2 {
-    (impl match fpvec_bounded_l2 $vdaf_instance:expr, ($vdaf:ident, $Vdaf:ident, $VERIFY_KEY_LEN:ident, $dp_strategy:ident, $DpStrategy:ident) => $body:tt) => {
+    (impl match fpvec_bounded_l2 $vdaf_instance:expr, ($vdaf:tt, $Vdaf:ident, $VERIFY_KEY_LEN:ident, $dp_strategy:ident, $DpStrategy:ident) => $body:tt) => {
         match $vdaf_instance {
             ::janus_core::vdaf::VdafInstance::Prio3FixedPointBoundedL2VecSum { bitsize, dp_strategy, length } => {
                 const $VERIFY_KEY_LEN: usize = ::janus_core::vdaf::VERIFY_KEY_LENGTH;
@@ -310,10 +567,10 @@ macro_rules! vdaf_dispatch_impl_fpvec_bounded_l2 {
         }
     };
 
-    (@dispatch_bitsize $bitsize:ident, $Vdaf:ident, $vdaf:ident, $length:ident => $body:tt) => {
+    (@dispatch_bitsize $bitsize:ident, $Vdaf:ident, $vdaf:tt, $length:ident => $body:tt) => {
         match $bitsize {
             janus_core::vdaf::Prio3FixedPointBoundedL2VecSumBitSize::BitSize16 => {
-                let $vdaf =
+                let $vdaf: $Vdaf =
                     ::prio::vdaf::prio3::Prio3::new_fixedpoint_boundedl2_vec_sum_multithreaded(
                         2, *$length,
                     )?;
@@ -323,7 +580,7 @@ macro_rules! vdaf_dispatch_impl_fpvec_bounded_l2 {
                 $body
             },
             janus_core::vdaf::Prio3FixedPointBoundedL2VecSumBitSize::BitSize32 => {
-                let $vdaf =
+                let $vdaf: $Vdaf =
                     ::prio::vdaf::prio3::Prio3::new_fixedpoint_boundedl2_vec_sum_multithreaded(
                         2, *$length,
                     )?;
@@ -332,15 +589,25 @@ macro_rules! vdaf_dispatch_impl_fpvec_bounded_l2 {
                 >;
                 $body
             },
-        };
+        }
     }
 }
 
+/// Internal implementation details of [`vdaf_dispatch`](crate::vdaf_dispatch). Without the
+/// `fpvec_bounded_l2` feature, no [`VdafInstance`] is in this group.
+#[cfg(not(feature = "fpvec_bounded_l2"))]
+#[macro_export]
+macro_rules! vdaf_dispatch_impl_fpvec_bounded_l2 {
+    (impl match fpvec_bounded_l2 $($tt:tt)*) => {
+        unreachable!()
+    };
+}
+
 /// Internal implementation details of [`vdaf_dispatch`](crate::vdaf_dispatch).
 #[cfg(feature = "test-util")]
 #[macro_export]
 macro_rules! vdaf_dispatch_impl_test_util {
-    (impl match test_util $vdaf_instance:expr, ($vdaf:ident, $Vdaf:ident, $VERIFY_KEY_LEN:ident, $dp_strategy:ident, $DpStrategy:ident) => $body:tt) => {
+    (impl match test_util $vdaf_instance:expr, ($vdaf:tt, $Vdaf:ident, $VERIFY_KEY_LEN:ident, $dp_strategy:ident, $DpStrategy:ident) => $body:tt) => {
         match $vdaf_instance {
             ::janus_core::vdaf::VdafInstance::Fake => {
                 let $vdaf = ::prio::vdaf::dummy::Vdaf::new(1);
@@ -389,102 +656,72 @@ macro_rules! vdaf_dispatch_impl_test_util {
     };
 }
 
-/// Internal implementation details of [`vdaf_dispatch`](crate::vdaf_dispatch).
-#[cfg(all(feature = "fpvec_bounded_l2", feature = "test-util"))]
+/// Internal implementation details of [`vdaf_dispatch`](crate::vdaf_dispatch). Without the
+/// `test-util` feature, no [`VdafInstance`] is in this group.
+#[cfg(not(feature = "test-util"))]
 #[macro_export]
-macro_rules! vdaf_dispatch_impl {
-    (impl match all $vdaf_instance:expr, ($vdaf:ident, $Vdaf:ident, $VERIFY_KEY_LEN:ident, $dp_strategy:ident, $DpStrategy:ident) => $body:tt) => {
-        match $vdaf_instance {
-            ::janus_core::vdaf::VdafInstance::Prio3Count
-            | ::janus_core::vdaf::VdafInstance::Prio3Sum { .. }
-            | ::janus_core::vdaf::VdafInstance::Prio3SumVec { .. }
-            | ::janus_core::vdaf::VdafInstance::Prio3SumVecField64MultiproofHmacSha256Aes128 { .. }
-            | ::janus_core::vdaf::VdafInstance::Prio3Histogram { .. }
-            | ::janus_core::vdaf::VdafInstance::Poplar1 { .. } => {
-                ::janus_core::vdaf_dispatch_impl_base!(impl match base $vdaf_instance, ($vdaf, $Vdaf, $VERIFY_KEY_LEN, $dp_strategy, $DpStrategy) => $body)
-            }
-
-            ::janus_core::vdaf::VdafInstance::Prio3FixedPointBoundedL2VecSum { .. } => {
-                ::janus_core::vdaf_dispatch_impl_fpvec_bounded_l2!(impl match fpvec_bounded_l2 $vdaf_instance, ($vdaf, $Vdaf, $VERIFY_KEY_LEN, $dp_strategy, $DpStrategy) => $body)
-            }
-
-            ::janus_core::vdaf::VdafInstance::Fake
-            | ::janus_core::vdaf::VdafInstance::FakeFailsPrepInit
-            | ::janus_core::vdaf::VdafInstance::FakeFailsPrepStep => {
-                ::janus_core::vdaf_dispatch_impl_test_util!(impl match test_util $vdaf_instance, ($vdaf, $Vdaf, $VERIFY_KEY_LEN, $dp_strategy, $DpStrategy) => $body)
-            }
+macro_rules! vdaf_dispatch_impl_test_util {
+    (impl match test_util $($tt:tt)*) => {
+        unreachable!()
+    };
+}
 
-            _ => panic!("VDAF {:?} is not yet supported", $vdaf_instance),
-        }
+/// Expands to the given items only if this crate was built with the `test-util` feature, i.e.
+/// only if [`vdaf_dispatch`](crate::vdaf_dispatch) can produce [`prio::vdaf::dummy::Vdaf`].
+/// Dependent crates may use this to provide trait implementations for every VDAF type produced by
+/// [`vdaf_dispatch`](crate::vdaf_dispatch), even if their own `test-util` feature is disabled.
+#[doc(hidden)]
+#[cfg(feature = "test-util")]
+#[macro_export]
+macro_rules! if_vdaf_dispatch_test_util {
+    ($($item:item)*) => {
+        $($item)*
     };
 }
 
+/// Expands to the given items only if this crate was built with the `test-util` feature.
+#[doc(hidden)]
+#[cfg(not(feature = "test-util"))]
+#[macro_export]
+macro_rules! if_vdaf_dispatch_test_util {
+    ($($item:item)*) => {};
+}
+
 /// Internal implementation details of [`vdaf_dispatch`](crate::vdaf_dispatch).
-#[cfg(all(feature = "fpvec_bounded_l2", not(feature = "test-util")))]
 #[macro_export]
 macro_rules! vdaf_dispatch_impl {
-    (impl match all $vdaf_instance:expr, ($vdaf:ident, $Vdaf:ident, $VERIFY_KEY_LEN:ident, $dp_strategy:ident, $DpStrategy:ident) => $body:tt) => {
-        match $vdaf_instance {
-            ::janus_core::vdaf::VdafInstance::Prio3Count
-            | ::janus_core::vdaf::VdafInstance::Prio3Sum { .. }
-            | ::janus_core::vdaf::VdafInstance::Prio3SumVec { .. }
-            | ::janus_core::vdaf::VdafInstance::Prio3SumVecField64MultiproofHmacSha256Aes128 { .. }
-            | ::janus_core::vdaf::VdafInstance::Prio3Histogram { .. }
-            | ::janus_core::vdaf::VdafInstance::Poplar1 { .. } => {
-                ::janus_core::vdaf_dispatch_impl_base!(impl match base $vdaf_instance, ($vdaf, $Vdaf, $VERIFY_KEY_LEN, $dp_strategy, $DpStrategy) => $body)
+    (impl match all $vdaf_instance:expr, ($vdaf:tt, $Vdaf:ident, $VERIFY_KEY_LEN:ident, $dp_strategy:ident, $DpStrategy:ident) => $body:tt) => {
+        match ($vdaf_instance).dispatch_group() {
+            ::janus_core::vdaf::VdafDispatchGroup::Prio3 => {
+                ::janus_core::vdaf_dispatch_impl_prio3!(impl match prio3 $vdaf_instance, ($vdaf, $Vdaf, $VERIFY_KEY_LEN, $dp_strategy, $DpStrategy) => $body)
             }
 
-            ::janus_core::vdaf::VdafInstance::Prio3FixedPointBoundedL2VecSum { .. } => {
+            ::janus_core::vdaf::VdafDispatchGroup::Prio3FixedPointBoundedL2VecSum => {
                 ::janus_core::vdaf_dispatch_impl_fpvec_bounded_l2!(impl match fpvec_bounded_l2 $vdaf_instance, ($vdaf, $Vdaf, $VERIFY_KEY_LEN, $dp_strategy, $DpStrategy) => $body)
             }
 
-            _ => panic!("VDAF {:?} is not yet supported", $vdaf_instance),
-        }
-    };
-}
-
-/// Internal implementation details of [`vdaf_dispatch`](crate::vdaf_dispatch).
-#[cfg(all(not(feature = "fpvec_bounded_l2"), feature = "test-util"))]
-#[macro_export]
-macro_rules! vdaf_dispatch_impl {
-    (impl match all $vdaf_instance:expr, ($vdaf:ident, $Vdaf:ident, $VERIFY_KEY_LEN:ident, $dp_strategy:ident, $DpStrategy:ident) => $body:tt) => {
-        match $vdaf_instance {
-            ::janus_core::vdaf::VdafInstance::Prio3Count
-            | ::janus_core::vdaf::VdafInstance::Prio3Sum { .. }
-            | ::janus_core::vdaf::VdafInstance::Prio3SumVec { .. }
-            | ::janus_core::vdaf::VdafInstance::Prio3SumVecField64MultiproofHmacSha256Aes128 { .. }
-            | ::janus_core::vdaf::VdafInstance::Prio3Histogram { .. }
-            | ::janus_core::vdaf::VdafInstance::Poplar1 { .. } => {
-                ::janus_core::vdaf_dispatch_impl_base!(impl match base $vdaf_instance, ($vdaf, $Vdaf, $VERIFY_KEY_LEN, $dp_strategy, $DpStrategy) => $body)
+            ::janus_core::vdaf::VdafDispatchGroup::Poplar1 => {
+                ::janus_core::vdaf_dispatch_impl_poplar1!(impl match poplar1 $vdaf_instance, ($vdaf, $Vdaf, $VERIFY_KEY_LEN, $dp_strategy, $DpStrategy) => $body)
             }
 
-            ::janus_core::vdaf::VdafInstance::Fake
-            | ::janus_core::vdaf::VdafInstance::FakeFailsPrepInit
-            | ::janus_core::vdaf::VdafInstance::FakeFailsPrepStep => {
+            ::janus_core::vdaf::VdafDispatchGroup::Fake => {
                 ::janus_core::vdaf_dispatch_impl_test_util!(impl match test_util $vdaf_instance, ($vdaf, $Vdaf, $VERIFY_KEY_LEN, $dp_strategy, $DpStrategy) => $body)
             }
-
-            _ => panic!("VDAF {:?} is not yet supported", $vdaf_instance),
         }
     };
-}
 
-/// Internal implementation details of [`vdaf_dispatch`](crate::vdaf_dispatch).
-#[cfg(all(not(feature = "fpvec_bounded_l2"), not(feature = "test-util")))]
-#[macro_export]
-macro_rules! vdaf_dispatch_impl {
-    (impl match all $vdaf_instance:expr, ($vdaf:ident, $Vdaf:ident, $VERIFY_KEY_LEN:ident, $dp_strategy:ident, $DpStrategy:ident) => $body:tt) => {
-        match $vdaf_instance {
-            ::janus_core::vdaf::VdafInstance::Prio3Count
-            | ::janus_core::vdaf::VdafInstance::Prio3Sum { .. }
-            | ::janus_core::vdaf::VdafInstance::Prio3SumVec { .. }
-            | ::janus_core::vdaf::VdafInstance::Prio3SumVecField64MultiproofHmacSha256Aes128 { .. }
-            | ::janus_core::vdaf::VdafInstance::Prio3Histogram { .. }
-            | ::janus_core::vdaf::VdafInstance::Poplar1 { .. } => {
-                ::janus_core::vdaf_dispatch_impl_base!(impl match base $vdaf_instance, ($vdaf, $Vdaf, $VERIFY_KEY_LEN, $dp_strategy, $DpStrategy) => $body)
+    (impl match no_aggregation_parameter $vdaf_instance:expr, ($vdaf:tt, $Vdaf:ident, $VERIFY_KEY_LEN:ident, $dp_strategy:ident, $DpStrategy:ident) => $body:tt, _ => $fallback:tt) => {
+        match ($vdaf_instance).dispatch_group() {
+            ::janus_core::vdaf::VdafDispatchGroup::Prio3 => {
+                ::janus_core::vdaf_dispatch_impl_prio3!(impl match prio3 $vdaf_instance, ($vdaf, $Vdaf, $VERIFY_KEY_LEN, $dp_strategy, $DpStrategy) => $body)
+            }
+
+            ::janus_core::vdaf::VdafDispatchGroup::Prio3FixedPointBoundedL2VecSum => {
+                ::janus_core::vdaf_dispatch_impl_fpvec_bounded_l2!(impl match fpvec_bounded_l2 $vdaf_instance, ($vdaf, $Vdaf, $VERIFY_KEY_LEN, $dp_strategy, $DpStrategy) => $body)
             }
 
-            _ => panic!("VDAF {:?} is not yet supported", $vdaf_instance),
+            ::janus_core::vdaf::VdafDispatchGroup::Poplar1
+            | ::janus_core::vdaf::VdafDispatchGroup::Fake => $fallback,
         }
     };
 }
@@ -496,6 +733,12 @@ macro_rules! vdaf_dispatch_impl {
 /// can use to explicitly specify the VDAF's type, and the name of a const that will be set to the
 /// VDAF's verify key length, also for explicitly specifying type parameters.
 ///
+/// This is the single place that maps [`VdafInstance`] variants to VDAF implementations. Code that
+/// must handle every VDAF should dispatch through this macro (or
+/// [`vdaf_dispatch_no_aggregation_parameter`](crate::vdaf_dispatch_no_aggregation_parameter))
+/// rather than matching on [`VdafInstance`] itself, so that a new VDAF is picked up everywhere, and
+/// any code that can't handle its type fails to compile.
+///
 /// # Example:
 ///
 /// ```
@@ -517,7 +760,7 @@ macro_rules! vdaf_dispatch_impl {
 macro_rules! vdaf_dispatch {
     // Provide the dispatched type only, don't construct a VDAF instance.
     ($vdaf_instance:expr, (_, $Vdaf:ident, $VERIFY_KEY_LEN:ident) => $body:tt) => {
-        ::janus_core::vdaf_dispatch_impl!(impl match all $vdaf_instance, (_, $Vdaf, $VERIFY_KEY_LEN) => $body)
+        ::janus_core::vdaf_dispatch_impl!(impl match all $vdaf_instance, (_, $Vdaf, $VERIFY_KEY_LEN, _unused, _Unused) => $body)
     };
 
     // Construct a VDAF instance, and provide that to the block as well.
@@ -531,9 +774,39 @@ macro_rules! vdaf_dispatch {
     };
 }
 
+/// Like [`vdaf_dispatch`](crate::vdaf_dispatch), but only dispatches to VDAFs that take no
+/// aggregation parameter (i.e. the Prio3 family), for code that can't handle any others. The
+/// fallback expression after `_ =>` is evaluated for any other VDAF.
+///
+/// # Example:
+///
+/// ```
+/// # use janus_core::vdaf_dispatch_no_aggregation_parameter;
+/// # fn handle_request_generic<A, const SEED_SIZE: usize>(_vdaf: &A) -> Result<(), prio::vdaf::VdafError>
+/// # where
+/// #     A: prio::vdaf::Aggregator<SEED_SIZE, 16, AggregationParam = ()>,
+/// # {
+/// #     Ok(())
+/// # }
+/// # fn test() -> Result<(), prio::vdaf::VdafError> {
+/// #     let vdaf = janus_core::vdaf::VdafInstance::Prio3Count;
+/// vdaf_dispatch_no_aggregation_parameter!(&vdaf, (vdaf, VdafType, VERIFY_KEY_LEN) => {
+///     handle_request_generic::<VdafType, VERIFY_KEY_LEN>(&vdaf)
+/// }, _ => {
+///     panic!("unsupported VDAF")
+/// })
+/// # }
+/// ```
+#[macro_export]
+macro_rules! vdaf_dispatch_no_aggregation_parameter {
+    ($vdaf_instance:expr, ($vdaf:ident, $Vdaf:ident, $VERIFY_KEY_LEN:ident) => $body:tt, _ => $fallback:tt) => {
+        ::janus_core::vdaf_dispatch_impl!(impl match no_aggregation_parameter $vdaf_instance, ($vdaf, $Vdaf, $VERIFY_KEY_LEN, _unused, _Unused) => $body, _ => $fallback)
+    };
+}
+
 #[cfg(test)]
 mod tests {
-    use super::VdafInstance;
+    use super::{MeasurementLengthLimits, VdafInstance};
     use serde_test::{assert_tokens, Token};
 
     #[test]
@@ -623,6 +896,40 @@ mod tests {
                 Token::StructVariantEnd,
             ],
         );
+        assert_tokens(
+            &VdafInstance::Prio3CountMultiproof { proofs: 3 },
+            &[
+                Token::StructVariant {
+                    name: "VdafInstance",
+                    variant: "Prio3CountMultiproof",
+                    len: 1,
+                },
+                Token::Str("proofs"),
+                Token::U8(3),
+                Token::StructVariantEnd,
+            ],
+        );
+        assert_tokens(
+            &VdafInstance::Prio3HistogramMultiproof {
+                proofs: 2,
+                length: 6,
+                chunk_length: 2,
+            },
+            &[
+                Token::StructVariant {
+                    name: "VdafInstance",
+                    variant: "Prio3HistogramMultiproof",
+                    len: 3,
+                },
+                Token::Str("proofs"),
+                Token::U8(2),
+                Token::Str("length"),
+                Token::U64(6),
+                Token::Str("chunk_length"),
+                Token::U64(2),
+                Token::StructVariantEnd,
+            ],
+        );
         assert_tokens(
             &VdafInstance::Poplar1 { bits: 64 },
             &[
@@ -658,4 +965,193 @@ mod tests {
             }],
         );
     }
+
+    #[test]
+    fn dispatch() {
+        // Use the dispatch macros via the crate's public interface, as other crates would.
+        use janus_core::vdaf::VdafInstance;
+        use prio::vdaf::VdafError;
+
+        fn dispatch_all(vdaf_instance: &VdafInstance) -> Result<(usize, &'static str), VdafError> {
+            janus_core::vdaf_dispatch!(vdaf_instance, (_, VdafType, VERIFY_KEY_LEN) => {
+                let type_name = std::any::type_name::<VdafType>();
+                Ok((VERIFY_KEY_LEN, type_name))
+            })
+        }
+
+        fn dispatch_no_aggregation_parameter(
+            vdaf_instance: &VdafInstance,
+        ) -> Result<Option<&'static str>, VdafError> {
+            janus_core::vdaf_dispatch_no_aggregation_parameter!(vdaf_instance, (vdaf, VdafType, VERIFY_KEY_LEN) => {
+                let _: &VdafType = &vdaf;
+                assert_eq!(VERIFY_KEY_LEN, vdaf_instance.verify_key_length());
+                Ok(Some(std::any::type_name::<VdafType>()))
+            }, _ => {
+                Ok(None)
+            })
+        }
+
+        #[allow(unused_mut)]
+        let mut vdaf_instances = Vec::from([
+            (VdafInstance::Prio3Count, true),
+            (VdafInstance::Prio3Sum { bits: 8 }, true),
+            (
+                VdafInstance::Prio3SumVec {
+                    bits: 1,
+                    length: 8,
+                    chunk_length: 3,
+                },
+                true,
+            ),
+            (
+                VdafInstance::Prio3SumVecField64MultiproofHmacSha256Aes128 {
+                    proofs: 2,
+                    bits: 1,
+                    length: 8,
+                    chunk_length: 3,
+                },
+                true,
+            ),
+            (
+                VdafInstance::Prio3Histogram {
+                    length: 6,
+                    chunk_length: 2,
+                },
+                true,
+            ),
+            (VdafInstance::Prio3CountMultiproof { proofs: 2 }, true),
+            (
+                VdafInstance::Prio3SumMultiproof { proofs: 3, bits: 8 },
+                true,
+            ),
+            (
+                VdafInstance::Prio3SumVecMultiproof {
+                    proofs: 2,
+                    bits: 1,
+                    length: 8,
+                    chunk_length: 3,
+                },
+                true,
+            ),
+            (
+                VdafInstance::Prio3HistogramMultiproof {
+                    proofs: 4,
+                    length: 6,
+                    chunk_length: 2,
+                },
+                true,
+            ),
+            (VdafInstance::Poplar1 { bits: 64 }, false),
+            (VdafInstance::Fake, false),
+            (VdafInstance::FakeFailsPrepInit, false),
+            (VdafInstance::FakeFailsPrepStep, false),
+        ]);
+        #[cfg(feature = "fpvec_bounded_l2")]
+        vdaf_instances.extend([
+            (
+                VdafInstance::Prio3FixedPointBoundedL2VecSum {
+                    bitsize: janus_core::vdaf::Prio3FixedPointBoundedL2VecSumBitSize::BitSize16,
+                    dp_strategy: Default::default(),
+                    length: 4,
+                },
+                true,
+            ),
+            (
+                VdafInstance::Prio3FixedPointBoundedL2VecSum {
+                    bitsize: janus_core::vdaf::Prio3FixedPointBoundedL2VecSumBitSize::BitSize32,
+                    dp_strategy: Default::default(),
+                    length: 4,
+                },
+                true,
+            ),
+        ]);
+
+        for (vdaf_instance, has_no_aggregation_parameter) in vdaf_instances {
+            let (verify_key_length, type_name) = dispatch_all(&vdaf_instance).unwrap();
+            assert_eq!(verify_key_length, vdaf_instance.verify_key_length());
+            assert_eq!(
+                dispatch_no_aggregation_parameter(&vdaf_instance).unwrap(),
+                has_no_aggregation_parameter.then_some(type_name),
+                "{vdaf_instance:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn multiproof() {
+        use janus_core::vdaf::{
+            new_prio3_count_multiproof, new_prio3_histogram_multiproof, new_prio3_sum_multiproof,
+            new_prio3_sum_vec_multiproof,
+        };
+        use prio::vdaf::Vdaf;
+
+        // The algorithm ID binds both the VDAF type and the number of proofs, so aggregators that
+        // disagree on either fail to prepare reports.
+        assert_eq!(
+            new_prio3_count_multiproof(3).unwrap().algorithm_id(),
+            0xFFFF_2003
+        );
+        assert_eq!(
+            new_prio3_sum_multiproof(2, 8).unwrap().algorithm_id(),
+            0xFFFF_2102
+        );
+        assert_eq!(
+            new_prio3_sum_vec_multiproof(2, 1, 8, 3)
+                .unwrap()
+                .algorithm_id(),
+            0xFFFF_2202
+        );
+        assert_eq!(
+            new_prio3_histogram_multiproof(4, 6, 2)
+                .unwrap()
+                .algorithm_id(),
+            0xFFFF_2304
+        );
+
+        // A single proof is only permitted via the standard VDAFs.
+        new_prio3_count_multiproof(1).unwrap_err();
+        new_prio3_sum_multiproof(0, 8).unwrap_err();
+    }
+
+    #[test]
+    fn check_measurement_length() {
+        let limits = MeasurementLengthLimits {
+            max_histogram_length: Some(100),
+            max_vector_length: Some(10),
+        };
+        let histogram = |length| VdafInstance::Prio3Histogram {
+            length,
+            chunk_length: 10,
+        };
+        let sum_vec = |length| VdafInstance::Prio3SumVec {
+            bits: 1,
+            length,
+            chunk_length: 1,
+        };
+
+        histogram(100).check_measurement_length(&limits).unwrap();
+        assert_eq!(
+            histogram(101)
+                .check_measurement_length(&limits)
+                .unwrap_err()
+                .to_string(),
+            "histogram length 101 exceeds the maximum of 100"
+        );
+        sum_vec(10).check_measurement_length(&limits).unwrap();
+        assert_eq!(
+            sum_vec(11)
+                .check_measurement_length(&limits)
+                .unwrap_err()
+                .to_string(),
+            "vector length 11 exceeds the maximum of 10"
+        );
+        VdafInstance::Prio3Count
+            .check_measurement_length(&limits)
+            .unwrap();
+
+        // Unset limits are not enforced.
+        histogram(1_000_000)
+            .check_measurement_length(&MeasurementLengthLimits::default())
+            .unwrap();
+    }
 }
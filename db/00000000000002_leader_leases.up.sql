@@ -0,0 +1,17 @@
+-- Leases used to elect a single replica to perform a duty that must not be performed concurrently
+-- by multiple replicas, such as garbage collection. A replica holds the lease until it releases it
+-- or the lease expires, at which point any other replica may take it over.
+CREATE TABLE leader_leases(
+    id           BIGINT GENERATED ALWAYS AS IDENTITY PRIMARY KEY,  -- artificial ID, internal-only
+    name         TEXT NOT NULL,       -- the duty which the lease grants
+    holder       TEXT NOT NULL,       -- identifies the replica holding the lease
+    lease_expiry TIMESTAMP NOT NULL,  -- when the lease expires; a released lease expires immediately
+    acquired_at  TIMESTAMP NOT NULL,  -- when the current holder most recently acquired the lease
+
+    -- creation/update records
+    created_at TIMESTAMP NOT NULL,  -- when the row was created
+    updated_at TIMESTAMP NOT NULL,  -- when the row was last changed
+    updated_by TEXT NOT NULL,       -- the name of the transaction that last updated the row
+
+    CONSTRAINT leader_leases_unique_name UNIQUE(name)
+);
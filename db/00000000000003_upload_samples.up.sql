@@ -0,0 +1,22 @@
+-- Metadata recorded for a small sample of uploaded reports, to help debug the behavior of client
+-- fleets. Only metadata is recorded, never report shares. Samples expire quickly and are removed
+-- by the garbage collector.
+CREATE TABLE upload_samples(
+    id                                 BIGINT GENERATED ALWAYS AS IDENTITY PRIMARY KEY,  -- artificial ID, internal-only
+    task_id                            BIGINT NOT NULL,      -- the task ID the report was uploaded for
+    report_id                          BYTEA NOT NULL,       -- 16-byte ReportID as defined by the DAP specification
+    client_timestamp                   TIMESTAMP NOT NULL,   -- the client timestamp of the report
+    received_at                        TIMESTAMP NOT NULL,   -- when the aggregator received the report
+    extension_types                    INTEGER[] NOT NULL,   -- codepoints of the extensions in the leader's input share, in order
+    public_share_size                  BIGINT NOT NULL,      -- size of the encoded public share, in bytes
+    leader_encrypted_input_share_size  BIGINT NOT NULL,      -- size of the leader's encrypted input share payload, in bytes
+    helper_encrypted_input_share_size  BIGINT NOT NULL,      -- size of the helper's encrypted input share payload, in bytes
+    expiry                             TIMESTAMP NOT NULL,   -- when the sample expires
+
+    -- creation/update records
+    created_at TIMESTAMP NOT NULL,  -- when the row was created
+    updated_by TEXT NOT NULL,       -- the name of the transaction that last updated the row
+
+    CONSTRAINT fk_task_id FOREIGN KEY(task_id) REFERENCES tasks(id) ON DELETE CASCADE
+);
+CREATE INDEX upload_samples_task_id_and_expiry ON upload_samples(task_id, expiry);
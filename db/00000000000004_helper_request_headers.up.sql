@@ -0,0 +1,3 @@
+-- Static headers added to every request the leader sends to the helper, as an encrypted JSON array
+-- of name/value objects. Only set for leader tasks which have such headers.
+ALTER TABLE tasks ADD COLUMN helper_request_headers BYTEA;
@@ -0,0 +1,2 @@
+ALTER TABLE tasks DROP COLUMN unknown_extension_policy;
+DROP TYPE UNKNOWN_EXTENSION_POLICY;
@@ -0,0 +1,8 @@
+-- Identifies how the leader handles uploaded reports carrying extensions of an unknown type.
+CREATE TYPE UNKNOWN_EXTENSION_POLICY AS ENUM(
+    'REJECT',             -- reject the report
+    'ACCEPT',             -- accept the report, ignoring the unknown extensions
+    'ACCEPT_WITH_METRIC'  -- accept the report, and count it in a metric
+);
+
+ALTER TABLE tasks ADD COLUMN unknown_extension_policy UNKNOWN_EXTENSION_POLICY NOT NULL DEFAULT 'REJECT';
@@ -0,0 +1 @@
+DROP INDEX aggregation_jobs_terminal_updated_at;
@@ -0,0 +1,3 @@
+-- Supports garbage collection of aggregation jobs a configurable time after they reach a terminal
+-- state, independently of the task's report expiry age.
+CREATE INDEX aggregation_jobs_terminal_updated_at ON aggregation_jobs(task_id, updated_at) WHERE state IN ('FINISHED', 'ABANDONED', 'DELETED');
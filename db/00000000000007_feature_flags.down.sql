@@ -0,0 +1,3 @@
+DROP INDEX feature_flags_task_id_and_name;
+DROP INDEX feature_flags_global_name;
+DROP TABLE feature_flags;
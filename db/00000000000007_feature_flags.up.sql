@@ -0,0 +1,18 @@
+-- Runtime feature flags, which gate risky behaviors so that they can be enabled gradually, for a
+-- whole deployment or for individual tasks, without redeploying. A flag with a NULL task_id
+-- applies to all tasks; a flag with a task_id overrides it for that task.
+CREATE TABLE feature_flags(
+    id       BIGINT GENERATED ALWAYS AS IDENTITY PRIMARY KEY,  -- artificial ID, internal-only
+    name     TEXT NOT NULL,     -- the name of the flag
+    task_id  BIGINT,            -- the task the flag applies to, or NULL if it applies to all tasks
+    enabled  BOOLEAN NOT NULL,  -- whether the flag is enabled
+
+    -- creation/update records
+    created_at TIMESTAMP NOT NULL,  -- when the row was created
+    updated_at TIMESTAMP NOT NULL,  -- when the row was last changed
+    updated_by TEXT NOT NULL,       -- the name of the transaction that last updated the row
+
+    CONSTRAINT fk_task_id FOREIGN KEY(task_id) REFERENCES tasks(id) ON DELETE CASCADE
+);
+CREATE UNIQUE INDEX feature_flags_global_name ON feature_flags(name) WHERE task_id IS NULL;
+CREATE UNIQUE INDEX feature_flags_task_id_and_name ON feature_flags(task_id, name) WHERE task_id IS NOT NULL;
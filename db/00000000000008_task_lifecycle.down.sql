@@ -0,0 +1,4 @@
+DROP INDEX task_lifecycle_events_task_id;
+DROP TABLE task_lifecycle_events;
+ALTER TABLE tasks DROP COLUMN state;
+DROP TYPE TASK_STATE;
@@ -0,0 +1,23 @@
+-- Identifies where a task is in its lifecycle.
+CREATE TYPE TASK_STATE AS ENUM(
+    'PROVISIONED',  -- the task has been provisioned, but is not yet served
+    'ACTIVE',       -- the task is served, and accepts uploads
+    'EXPIRING',     -- the task has expired, but its reports may still be aggregated and collected
+    'EXPIRED',      -- all of the task's reports have expired
+    'PURGING',      -- the task is no longer served, and is being deleted
+    'DELETED'       -- the task has been deleted (only appears in task_lifecycle_events)
+);
+
+ALTER TABLE tasks ADD COLUMN state TASK_STATE NOT NULL DEFAULT 'ACTIVE';
+
+-- Audit log of task lifecycle transitions. Rows are not deleted along with their task, so that
+-- downstream systems can observe deletions.
+CREATE TABLE task_lifecycle_events(
+    id          BIGINT GENERATED ALWAYS AS IDENTITY PRIMARY KEY,  -- artificial ID, internal-only
+    task_id     BYTEA NOT NULL,       -- 32-byte TaskID as defined by the DAP specification
+    from_state  TASK_STATE,           -- the task's previous state, or NULL if it was just created
+    to_state    TASK_STATE NOT NULL,  -- the task's new state
+    occurred_at TIMESTAMP NOT NULL,   -- when the transition happened
+    updated_by  TEXT NOT NULL         -- the name of the transaction that made the transition
+);
+CREATE INDEX task_lifecycle_events_task_id ON task_lifecycle_events(task_id, id);
@@ -0,0 +1,2 @@
+DROP INDEX task_metrics_snapshots_snapshot_time;
+DROP TABLE task_metrics_snapshots;
@@ -0,0 +1,20 @@
+-- Hourly snapshots of per-task counters, kept for much longer than a typical metrics backend
+-- retains data, so that operators can answer questions about historical volume. Counters are
+-- cumulative, so the volume in a period is the difference between two snapshots. Rows are keyed by
+-- the DAP task ID rather than referencing the tasks table, so that they outlive their task, and are
+-- removed once they are older than the configured retention period.
+CREATE TABLE task_metrics_snapshots(
+    id                  BIGINT GENERATED ALWAYS AS IDENTITY PRIMARY KEY,  -- artificial ID, internal-only
+    task_id             BYTEA NOT NULL,      -- 32-byte TaskID as defined by the DAP specification
+    snapshot_time       TIMESTAMP NOT NULL,  -- the time as of which the counters were taken, at the start of an hour
+    reports_accepted    BIGINT NOT NULL,     -- number of reports successfully uploaded to the task
+    reports_aggregated  BIGINT NOT NULL,     -- number of report aggregations that finished successfully
+    reports_collected   BIGINT NOT NULL,     -- number of reports included in finished collection jobs or aggregate share jobs
+
+    -- creation/update records
+    created_at TIMESTAMP NOT NULL,  -- when the row was created
+    updated_by TEXT NOT NULL,       -- the name of the transaction that last updated the row
+
+    CONSTRAINT task_metrics_snapshots_unique_task_id_and_snapshot_time UNIQUE(task_id, snapshot_time)
+);
+CREATE INDEX task_metrics_snapshots_snapshot_time ON task_metrics_snapshots(snapshot_time);
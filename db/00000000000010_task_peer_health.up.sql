@@ -0,0 +1,18 @@
+-- The result of the most recent probe of each task's peer aggregator endpoint, recorded by the
+-- aggregator's peer health prober, so that connectivity problems between aggregators are noticed
+-- before aggregation or collection jobs fail.
+CREATE TABLE task_peer_health(
+    task_id               BIGINT PRIMARY KEY,  -- the task whose peer aggregator was probed
+    last_probed_at        TIMESTAMP NOT NULL,  -- when the peer aggregator was last probed
+    latency_ms            BIGINT,              -- latency of the last probe in milliseconds, or NULL if it failed
+    error                 TEXT,                -- why the last probe failed, or NULL if it succeeded
+    last_reachable_at     TIMESTAMP,           -- when a probe last succeeded, or NULL if none has
+    consecutive_failures  BIGINT NOT NULL,     -- number of probes that have failed since the last success
+
+    -- creation/update records
+    created_at TIMESTAMP NOT NULL,  -- when the row was created
+    updated_at TIMESTAMP NOT NULL,  -- when the row was last changed
+    updated_by TEXT NOT NULL,       -- the name of the transaction that last updated the row
+
+    CONSTRAINT fk_task_id FOREIGN KEY(task_id) REFERENCES tasks(id) ON DELETE CASCADE
+);
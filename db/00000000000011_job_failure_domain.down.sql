@@ -0,0 +1,2 @@
+ALTER TABLE collection_jobs DROP COLUMN last_failure_domain;
+ALTER TABLE aggregation_jobs DROP COLUMN last_failure_domain;
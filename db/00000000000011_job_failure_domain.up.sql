@@ -0,0 +1,4 @@
+-- The failure domain (e.g. "datastore", "peer", or "crypto") of the most recent failed attempt to
+-- step each job, or NULL if no attempt has failed. Once a job is abandoned, this records why.
+ALTER TABLE aggregation_jobs ADD COLUMN last_failure_domain TEXT;
+ALTER TABLE collection_jobs ADD COLUMN last_failure_domain TEXT;
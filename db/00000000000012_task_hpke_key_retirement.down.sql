@@ -0,0 +1 @@
+ALTER TABLE task_hpke_keys DROP COLUMN retire_at;
@@ -0,0 +1,4 @@
+-- When each task HPKE keypair is retired, or NULL if it has not been scheduled for retirement.
+-- Retired keypairs are no longer used to decrypt reports, and are deleted when the task's HPKE
+-- keys are next rotated.
+ALTER TABLE task_hpke_keys ADD COLUMN retire_at TIMESTAMP;
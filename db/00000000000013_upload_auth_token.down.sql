@@ -0,0 +1,3 @@
+ALTER TABLE tasks DROP CONSTRAINT upload_auth_token_null;
+ALTER TABLE tasks DROP COLUMN upload_auth_token_hash;
+ALTER TABLE tasks DROP COLUMN upload_auth_token_type;
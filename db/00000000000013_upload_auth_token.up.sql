@@ -0,0 +1,6 @@
+-- Optional authentication of clients uploading reports to the leader, for closed deployments where
+-- only known gateways submit reports. Uploads are unauthenticated if these columns are NULL.
+ALTER TABLE tasks ADD COLUMN upload_auth_token_type AUTH_TOKEN_TYPE;  -- the type of the authentication token
+ALTER TABLE tasks ADD COLUMN upload_auth_token_hash BYTEA;            -- hash of the token
+ALTER TABLE tasks ADD CONSTRAINT upload_auth_token_null
+    CHECK ((upload_auth_token_type IS NULL) = (upload_auth_token_hash IS NULL));
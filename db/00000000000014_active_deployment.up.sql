@@ -0,0 +1,14 @@
+-- Records which of an active/standby pair of leader deployments sharing this datastore is active.
+-- Only the active deployment creates and steps jobs. There is at most one row; if there is none,
+-- no named deployment is active.
+CREATE TABLE active_deployment(
+    id          BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),  -- constrains the table to one row
+    deployment  TEXT NOT NULL,       -- the name of the active deployment
+    epoch       BIGINT NOT NULL,     -- incremented each time a deployment is promoted
+    promoted_at TIMESTAMP NOT NULL,  -- when the active deployment was most recently promoted
+
+    -- creation/update records
+    created_at TIMESTAMP NOT NULL,  -- when the row was created
+    updated_at TIMESTAMP NOT NULL,  -- when the row was last changed
+    updated_by TEXT NOT NULL        -- the name of the transaction that last updated the row
+);
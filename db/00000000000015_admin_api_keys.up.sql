@@ -0,0 +1,15 @@
+-- Credentials for the aggregator API which only grant access to a subset of tasks, until they
+-- expire, so that teams can manage their own tasks without holding a deployment-wide token. Only
+-- a hash of each key's bearer token is stored.
+CREATE TABLE admin_api_keys(
+    id          BIGINT GENERATED ALWAYS AS IDENTITY PRIMARY KEY,  -- artificial ID, internal-only
+    name        TEXT NOT NULL UNIQUE,   -- operator-chosen name identifying the key
+    token_hash  BYTEA NOT NULL UNIQUE,  -- SHA-256 hash of the key's bearer token
+    task_ids    BYTEA[] NOT NULL,       -- DAP task IDs of the tasks the key grants access to
+    expiry      TIMESTAMP NOT NULL,     -- when the key stops being accepted
+
+    -- creation/update records
+    created_at TIMESTAMP NOT NULL,  -- when the row was created
+    updated_at TIMESTAMP NOT NULL,  -- when the row was last changed
+    updated_by TEXT NOT NULL        -- the name of the transaction that last updated the row
+);
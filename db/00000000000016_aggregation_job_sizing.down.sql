@@ -0,0 +1,3 @@
+ALTER TABLE tasks DROP COLUMN aggregation_job_creation_interval;
+ALTER TABLE tasks DROP COLUMN max_aggregation_job_size;
+ALTER TABLE tasks DROP COLUMN min_aggregation_job_size;
@@ -0,0 +1,5 @@
+-- Per-task overrides of how the leader groups reports into aggregation jobs. NULL values fall back
+-- to the aggregation job creator's configuration.
+ALTER TABLE tasks ADD COLUMN min_aggregation_job_size BIGINT;           -- minimum number of reports per aggregation job
+ALTER TABLE tasks ADD COLUMN max_aggregation_job_size BIGINT;           -- maximum number of reports per aggregation job
+ALTER TABLE tasks ADD COLUMN aggregation_job_creation_interval BIGINT;  -- how often to create aggregation jobs, in seconds
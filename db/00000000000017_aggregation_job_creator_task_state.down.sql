@@ -0,0 +1 @@
+DROP TABLE aggregation_job_creator_task_state;
@@ -0,0 +1,17 @@
+-- The aggregation job creator's scheduling state for each leader task it has discovered, so that
+-- operators can tell when a newly provisioned task was picked up and when jobs will next be
+-- created for it.
+CREATE TABLE aggregation_job_creator_task_state(
+    task_id        BIGINT PRIMARY KEY,  -- the task that aggregation jobs are created for
+    discovered_at  TIMESTAMP NOT NULL,  -- when the aggregation job creator started working on the task
+    last_run_at    TIMESTAMP,           -- when aggregation jobs were last created, or NULL if they have not been
+    next_run_at    TIMESTAMP NOT NULL,  -- when aggregation jobs are next scheduled to be created
+    last_error     TEXT,                -- why the last attempt to create aggregation jobs failed, or NULL if it succeeded
+
+    -- creation/update records
+    created_at TIMESTAMP NOT NULL,  -- when the row was created
+    updated_at TIMESTAMP NOT NULL,  -- when the row was last changed
+    updated_by TEXT NOT NULL,       -- the name of the transaction that last updated the row
+
+    CONSTRAINT fk_task_id FOREIGN KEY(task_id) REFERENCES tasks(id) ON DELETE CASCADE
+);
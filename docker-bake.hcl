@@ -24,6 +24,7 @@ group "janus" {
     "janus_aggregation_job_creator",
     "janus_aggregation_job_driver",
     "janus_collection_job_driver",
+    "janus_upload_ingester",
     "janus_cli",
     "janus_db_migrator",
   ]
@@ -47,6 +48,7 @@ group "janus_release" {
     "janus_aggregation_job_creator_release",
     "janus_aggregation_job_driver_release",
     "janus_collection_job_driver_release",
+    "janus_upload_ingester_release",
     "janus_cli_release",
     "janus_db_migrator_release",
   ]
@@ -152,6 +154,27 @@ target "janus_collection_job_driver_release" {
   ]
 }
 
+target "janus_upload_ingester" {
+  args = {
+    BINARY       = "upload_ingester"
+    GIT_REVISION = "${GIT_REVISION}"
+  }
+  cache-from = [
+    "type=gha,scope=main-janus",
+    "type=gha,scope=${GITHUB_BASE_REF}-janus",
+    "type=gha,scope=${GITHUB_REF_NAME}-janus",
+  ]
+  tags = ["janus_upload_ingester:${VERSION}"]
+}
+
+target "janus_upload_ingester_release" {
+  inherits = ["janus_upload_ingester"]
+  tags = [
+    "us-west2-docker.pkg.dev/janus-artifacts/janus/janus_upload_ingester:${VERSION}",
+    "us-west2-docker.pkg.dev/divviup-artifacts-public/janus/janus_upload_ingester:${VERSION}",
+  ]
+}
+
 target "janus_cli" {
   args = {
     BINARY       = "janus_cli"
@@ -0,0 +1,110 @@
+use anyhow::{anyhow, Result};
+use clap::{Parser, ValueEnum};
+use janus_core::auth_tokens::AuthenticationToken;
+use janus_integration_tests::traffic_capture::{replay, TrafficCapture};
+use std::{path::PathBuf, process::ExitCode};
+use url::Url;
+
+#[tokio::main]
+async fn main() -> Result<ExitCode> {
+    let options = Options::parse();
+
+    let capture = TrafficCapture::read(&options.capture_file)?;
+    let auth_token = options
+        .auth_token
+        .map(|token| match options.auth_token_type {
+            AuthTokenType::Bearer => AuthenticationToken::new_bearer_token_from_string(token),
+            AuthTokenType::DapAuth => AuthenticationToken::new_dap_auth_token_from_string(token),
+        })
+        .transpose()?;
+    let indices = match options.exchange {
+        Some(index) if index >= capture.exchanges.len() => {
+            return Err(anyhow!(
+                "capture only has {} exchanges",
+                capture.exchanges.len()
+            ))
+        }
+        Some(index) => Vec::from([index]),
+        None => (0..capture.exchanges.len()).collect(),
+    };
+
+    let outcomes = replay(
+        &capture,
+        indices,
+        &options.aggregator_url,
+        auth_token.as_ref(),
+    )
+    .await?;
+
+    let mut all_match = true;
+    for outcome in &outcomes {
+        all_match &= outcome.matches();
+        println!(
+            "{} {} {}: captured status {}, replayed status {}, body {}",
+            outcome.index,
+            outcome.method,
+            outcome.path,
+            outcome.captured_status,
+            outcome.replayed_status,
+            if outcome.body_matches {
+                "matches"
+            } else {
+                "differs"
+            },
+        );
+    }
+
+    Ok(if all_match {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+enum AuthTokenType {
+    /// Presented in the "Authorization" header, as a bearer token
+    Bearer,
+    /// Presented in the "DAP-Auth-Token" header
+    DapAuth,
+}
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "replay_dap_traffic",
+    about = "Re-sends DAP requests captured by the integration test harness to an aggregator, \
+             reporting whether its responses match the captured ones",
+    version,
+    rename_all = "kebab-case"
+)]
+struct Options {
+    /// Path to a traffic capture, as written when JANUS_E2E_CAPTURE_PATH is set.
+    capture_file: PathBuf,
+
+    /// Origin of the aggregator to send requests to, e.g. a local helper.
+    #[arg(long, default_value = "http://127.0.0.1:8080/")]
+    aggregator_url: Url,
+
+    /// Index of a single exchange to replay. By default, every exchange is replayed, in order.
+    #[arg(long)]
+    exchange: Option<usize>,
+
+    /// Authentication token to present in place of the redacted one in captured requests.
+    #[arg(long, env = "AUTH_TOKEN", hide_env_values = true)]
+    auth_token: Option<String>,
+
+    /// Type of the authentication token.
+    #[arg(long, value_enum, default_value = "bearer")]
+    auth_token_type: AuthTokenType,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Options;
+    use clap::CommandFactory;
+
+    #[test]
+    fn verify_clap_app() {
+        Options::command().debug_assert();
+    }
+}
@@ -5,14 +5,94 @@ use janus_aggregator_core::task::test_util::{Task, TaskBuilder};
 use janus_interop_binaries::{
     get_rust_log_level, test_util::await_http_server, ContainerLogsDropGuard, ContainerLogsSource,
 };
-use janus_messages::{Role, Time};
+use janus_messages::{
+    codec::Encode, query_type::QueryType, CollectionJobId, CollectionReq, Role, TaskId, Time,
+};
+use serde_json::json;
+use std::time::Duration;
 use testcontainers::{clients::Cli, GenericImage, RunnableImage};
+use tokio::{task::JoinHandle, time::interval};
+use url::Url;
 
+// The leader image is assumed to be published alongside the helper image for the same Daphne
+// commit. It has not been run yet, so tests using a Daphne leader remain ignored.
+const DAPHNE_LEADER_IMAGE_NAME_AND_TAG: &str = "cloudflare/daphne-worker-leader:sha-f6b3ef1";
 const DAPHNE_HELPER_IMAGE_NAME_AND_TAG: &str = "cloudflare/daphne-worker-helper:sha-f6b3ef1";
 
+/// The path prefix under which Daphne serves DAP requests.
+pub const DAPHNE_PATH_PREFIX: &str = "/v04/";
+
+/// Returns the given aggregator endpoint, with its path replaced by Daphne's DAP path prefix.
+pub fn daphne_aggregator_endpoint(endpoint: &Url) -> Url {
+    let mut endpoint = endpoint.clone();
+    endpoint.set_path(DAPHNE_PATH_PREFIX);
+    endpoint
+}
+
+/// Returns the URI of a collection job at a Daphne leader.
+pub fn daphne_collection_job_uri(
+    leader_endpoint: &Url,
+    task_id: &TaskId,
+    collection_job_id: &CollectionJobId,
+) -> Url {
+    daphne_aggregator_endpoint(leader_endpoint)
+        .join(&format!(
+            "tasks/{task_id}/collection_jobs/{collection_job_id}"
+        ))
+        .unwrap()
+}
+
+/// Interprets a collection job URI issued by a Daphne leader, returning the IDs of the task and
+/// collection job it refers to, or `None` if it is not a Daphne collection job URI.
+pub fn parse_daphne_collection_job_uri(uri: &Url) -> Option<(TaskId, CollectionJobId)> {
+    let mut segments = uri.path().strip_prefix(DAPHNE_PATH_PREFIX)?.split('/');
+    match (
+        segments.next(),
+        segments.next(),
+        segments.next(),
+        segments.next(),
+        segments.next(),
+    ) {
+        (Some("tasks"), Some(task_id), Some("collection_jobs"), Some(collection_job_id), None) => {
+            Some((task_id.parse().ok()?, collection_job_id.parse().ok()?))
+        }
+        _ => None,
+    }
+}
+
+/// A collection request, translated into the form in which a Daphne leader expects to receive it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DaphneCollectionRequest {
+    /// The collection job URI, to which the request should be sent with the PUT method.
+    pub uri: Url,
+    /// The value of the Content-Type header.
+    pub content_type: &'static str,
+    /// The encoded request body.
+    pub body: Vec<u8>,
+}
+
+impl DaphneCollectionRequest {
+    /// Translates a Janus collection request for the given collection job into a request to a
+    /// Daphne leader.
+    pub fn new<Q: QueryType>(
+        leader_endpoint: &Url,
+        task_id: &TaskId,
+        collection_job_id: &CollectionJobId,
+        request: &CollectionReq<Q>,
+    ) -> Self {
+        Self {
+            uri: daphne_collection_job_uri(leader_endpoint, task_id, collection_job_id),
+            content_type: CollectionReq::<Q>::MEDIA_TYPE,
+            body: request.get_encoded().unwrap(),
+        }
+    }
+}
+
 /// Represents a running Daphne test instance.
 pub struct Daphne<'a> {
     daphne_container: ContainerLogsDropGuard<'a, GenericImage>,
+    /// Drives a Daphne leader's aggregation and collection jobs, if this instance is a leader.
+    process_loop: Option<JoinHandle<()>>,
 }
 
 impl<'a> Daphne<'a> {
@@ -28,7 +108,10 @@ impl<'a> Daphne<'a> {
         role: Role,
     ) -> Daphne<'a> {
         let (endpoint, image_name_and_tag) = match role {
-            Role::Leader => panic!("A leader container image for Daphne is not yet available"),
+            Role::Leader => (
+                task.leader_aggregator_endpoint(),
+                DAPHNE_LEADER_IMAGE_NAME_AND_TAG,
+            ),
             Role::Helper => (
                 task.helper_aggregator_endpoint(),
                 DAPHNE_HELPER_IMAGE_NAME_AND_TAG,
@@ -66,7 +149,15 @@ impl<'a> Daphne<'a> {
         // Write the given task to the Daphne instance we started.
         interop_api::aggregator_add_task(port, task, role).await;
 
-        Self { daphne_container }
+        // A Daphne leader has no job drivers of its own. Instead, it aggregates uploaded reports
+        // and steps collection jobs each time its internal process endpoint is called, so keep
+        // calling it for as long as the container is running.
+        let process_loop = (role == Role::Leader).then(|| tokio::spawn(process_loop(port)));
+
+        Self {
+            daphne_container,
+            process_loop,
+        }
     }
 
     /// Returns the port of the aggregator on the host.
@@ -75,3 +166,34 @@ impl<'a> Daphne<'a> {
             .get_host_port_ipv4(Self::INTERNAL_SERVING_PORT)
     }
 }
+
+/// How often a Daphne leader is asked to make progress on its pending work.
+const PROCESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Repeatedly asks the Daphne leader listening on the given port to process its pending
+/// aggregation and collection work.
+async fn process_loop(port: u16) {
+    let http_client = reqwest::Client::default();
+    let url = Url::parse(&format!("http://127.0.0.1:{port}/internal/process")).unwrap();
+    let mut interval = interval(PROCESS_INTERVAL);
+    loop {
+        interval.tick().await;
+        // Errors are ignored, since the next iteration will try again.
+        let _ = http_client
+            .post(url.clone())
+            .json(&json!({
+                "max_buckets": 1000,
+                "max_reports": 1000,
+            }))
+            .send()
+            .await;
+    }
+}
+
+impl<'a> Drop for Daphne<'a> {
+    fn drop(&mut self) {
+        if let Some(process_loop) = self.process_loop.take() {
+            process_loop.abort();
+        }
+    }
+}
@@ -2,8 +2,10 @@
 
 #[cfg(feature = "testcontainer")]
 use crate::interop_api;
+use crate::traffic_capture::{traffic_capture_path, CapturingProxy, TrafficCapture};
 use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use janus_aggregator::{
+    aggregator::leader_election::LeaderElectionStates,
     binaries::{
         aggregation_job_creator::{
             self, Config as AggregationJobCreatorConfig, Options as AggregationJobCreatorOptions,
@@ -11,15 +13,20 @@ use janus_aggregator::{
         aggregation_job_driver::{
             self, Config as AggregationJobDriverConfig, Options as AggregationJobDriverOptions,
         },
-        aggregator::{self, Config as AggregatorConfig, Options as AggregatorOptions},
+        aggregator::{
+            self, Config as AggregatorConfig, GarbageCollectorConfig, Options as AggregatorOptions,
+        },
         collection_job_driver::{
             self, Config as CollectionJobDriverConfig, Options as CollectionJobDriverOptions,
         },
     },
     binary_utils::{BinaryContext, CommonBinaryOptions},
     config::{
-        default_max_transaction_retries, CommonConfig, DbConfig, JobDriverConfig, TaskprovConfig,
+        default_max_transaction_retries, CommonConfig, DbConfig, JobDriverConfig,
+        MeasurementLengthLimitsConfig, ResponseCompressionConfig, TaskprovConfig,
+        UploadSamplingConfig, UploadValidationConfig,
     },
+    feature_flags::FeatureFlagsConfig,
     metrics::MetricsConfiguration,
     trace::{TokioConsoleConfiguration, TraceConfiguration},
 };
@@ -35,10 +42,14 @@ use janus_interop_binaries::{
     ContainerLogsDropGuard,
 };
 use janus_messages::Role;
-use std::net::{Ipv4Addr, SocketAddr};
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    path::PathBuf,
+};
 #[cfg(feature = "testcontainer")]
 use testcontainers::{clients::Cli, RunnableImage};
 use trillium_tokio::Stopper;
+use url::Url;
 
 /// Represents a running Janus test instance in a container.
 #[cfg(feature = "testcontainer")]
@@ -94,6 +105,7 @@ impl<'a> JanusContainer<'a> {
 pub struct JanusInProcess {
     socket_address: SocketAddr,
     stopper: Stopper,
+    capturing_proxy: Option<CapturingProxy>,
     _ephemeral_datastore: EphemeralDatastore,
 }
 
@@ -101,6 +113,35 @@ impl JanusInProcess {
     /// Start a new Janus instance in the current process, using a separate ephemeral database,
     /// configured to service the given task.
     pub async fn new(task: &Task, role: Role) -> Self {
+        Self::new_with_garbage_collection(task, role, None).await
+    }
+
+    /// Like [`Self::new`], but the aggregator also runs the garbage collector with the given
+    /// configuration, if any.
+    ///
+    /// If the JANUS_E2E_CAPTURE_PATH environment variable is set, requests to the aggregator pass
+    /// through a [`CapturingProxy`], as in [`Self::new_with_traffic_capture`].
+    pub async fn new_with_garbage_collection(
+        task: &Task,
+        role: Role,
+        garbage_collection: Option<GarbageCollectorConfig>,
+    ) -> Self {
+        Self::new_inner(task, role, garbage_collection, traffic_capture_path()).await
+    }
+
+    /// Like [`Self::new`], but requests to the aggregator pass through a [`CapturingProxy`]. When
+    /// this instance is dropped, the captured traffic is written to a file in `capture_path` named
+    /// after the task ID and the aggregator's role.
+    pub async fn new_with_traffic_capture(task: &Task, role: Role, capture_path: PathBuf) -> Self {
+        Self::new_inner(task, role, None, Some(capture_path)).await
+    }
+
+    async fn new_inner(
+        task: &Task,
+        role: Role,
+        garbage_collection: Option<GarbageCollectorConfig>,
+        capture_path: Option<PathBuf>,
+    ) -> Self {
         // Set up common utilities.
         let stopper = Stopper::new();
         let clock = RealClock::default();
@@ -142,20 +183,36 @@ impl JanusInProcess {
                 connection_pool_timeouts_secs: 60,
                 check_schema_version: false,
                 tls_trust_store_path: None,
+                read_replica_url: None,
             },
             logging_config,
             metrics_config: MetricsConfiguration { exporter: None },
             health_check_listen_address: (Ipv4Addr::LOCALHOST, 0).into(),
             max_transaction_retries: default_max_transaction_retries(),
+            datastore_key_encryption: None,
+            feature_flags: FeatureFlagsConfig::default(),
+            datastore_migration: None,
+            shutdown_drain_timeout_secs: None,
+            deployment_name: None,
+            secrets: None,
         };
         let aggregator_options = AggregatorOptions {
             common: common_binary_options.clone(),
             aggregator_api_auth_tokens: Vec::new(),
+            collector_hpke_keypairs: Vec::new(),
+            canary_collector_auth_token: None,
+            canary_collector_hpke_keypair: None,
+            retain_terminal_aggregation_jobs: false,
         };
         let aggregator_config = AggregatorConfig {
             common_config: common_config.clone(),
             taskprov_config: TaskprovConfig::default(),
-            garbage_collection: None,
+            measurement_length_limits: MeasurementLengthLimitsConfig::default(),
+            garbage_collection,
+            metrics_snapshots: None,
+            peer_health_probing: None,
+            canary: None,
+            background_runtime: None,
             listen_address: (Ipv4Addr::LOCALHOST, 0).into(),
             aggregator_api: None,
             response_headers: Vec::new(),
@@ -164,6 +221,18 @@ impl JanusInProcess {
             batch_aggregation_shard_count: 32,
             task_counter_shard_count: 64,
             global_hpke_configs_refresh_interval: None,
+            min_auth_failure_response_time_ms: 0,
+            response_compression: ResponseCompressionConfig::default(),
+            upload_validation: UploadValidationConfig::default(),
+            upload_sampling: UploadSamplingConfig::default(),
+            upload_queue: None,
+            upload_routing: None,
+            upload_label: None,
+            report_sink: None,
+            public_task_stats: false,
+            max_collection_interval_time_precisions: None,
+            max_aggregate_request_memory_bytes: None,
+            strict_conformance: false,
         };
         let aggregation_job_creator_options = AggregationJobCreatorOptions {
             common: common_binary_options.clone(),
@@ -172,10 +241,13 @@ impl JanusInProcess {
             common_config: common_config.clone(),
             batch_aggregation_shard_count: 32,
             tasks_update_frequency_secs: 2,
+            task_discovery_interval_secs: None,
             aggregation_job_creation_interval_secs: 1,
             min_aggregation_job_size: 1,
             max_aggregation_job_size: 100,
             aggregation_job_creation_report_window: 5000,
+            max_adaptive_aggregation_job_size: None,
+            leader_lease_duration_s: None,
         };
         let aggregation_job_driver_options = AggregationJobDriverOptions {
             common: common_binary_options.clone(),
@@ -196,9 +268,11 @@ impl JanusInProcess {
             },
             taskprov_config: TaskprovConfig::default(),
             batch_aggregation_shard_count: 32,
+            max_concurrent_jobs_per_task: None,
         };
         let collection_job_driver_options = CollectionJobDriverOptions {
             common: common_binary_options.clone(),
+            collector_hpke_keypairs: Vec::new(),
         };
         let collection_job_driver_config = CollectionJobDriverConfig {
             common_config,
@@ -216,6 +290,7 @@ impl JanusInProcess {
             },
             batch_aggregation_shard_count: 32,
             min_collection_job_retry_delay_secs: 1,
+            result_sink: None,
         };
 
         // Spawn each component.
@@ -225,8 +300,10 @@ impl JanusInProcess {
                 options: aggregator_options,
                 config: aggregator_config,
                 datastore: ephemeral_datastore.datastore(clock).await,
+                secondary_datastore: None,
                 meter: noop_meter(),
                 stopper: stopper.clone(),
+                leader_elections: LeaderElectionStates::default(),
             });
         tokio::spawn(aggregator_future);
         tokio::spawn(aggregation_job_creator::main_callback(BinaryContext {
@@ -234,24 +311,30 @@ impl JanusInProcess {
             options: aggregation_job_creator_options,
             config: aggregation_job_creator_config,
             datastore: ephemeral_datastore.datastore(clock).await,
+            secondary_datastore: None,
             meter: noop_meter(),
             stopper: stopper.clone(),
+            leader_elections: LeaderElectionStates::default(),
         }));
         tokio::spawn(aggregation_job_driver::main_callback(BinaryContext {
             clock,
             options: aggregation_job_driver_options,
             config: aggregation_job_driver_config,
             datastore: ephemeral_datastore.datastore(clock).await,
+            secondary_datastore: None,
             meter: noop_meter(),
             stopper: stopper.clone(),
+            leader_elections: LeaderElectionStates::default(),
         }));
         tokio::spawn(collection_job_driver::main_callback(BinaryContext {
             clock,
             options: collection_job_driver_options,
             config: collection_job_driver_config,
             datastore: ephemeral_datastore.datastore(clock).await,
+            secondary_datastore: None,
             meter: noop_meter(),
             stopper: stopper.clone(),
+            leader_elections: LeaderElectionStates::default(),
         }));
 
         // Wait for the aggregator's socket address.
@@ -265,16 +348,37 @@ impl JanusInProcess {
                 .expect("aggregator task shut down before sending socket address");
         };
 
+        let capturing_proxy = match capture_path {
+            Some(capture_path) => Some(
+                CapturingProxy::new(
+                    Url::parse(&format!("http://{socket_address}/")).unwrap(),
+                    Some(capture_path.join(format!("{}-{role}.json", task.id()))),
+                )
+                .await,
+            ),
+            None => None,
+        };
+
         Self {
             socket_address,
             stopper,
+            capturing_proxy,
             _ephemeral_datastore: ephemeral_datastore,
         }
     }
 
-    /// Returns the aggregator's port.
+    /// Returns the aggregator's port, or that of the proxy in front of it if traffic is being
+    /// captured.
     pub fn port(&self) -> u16 {
-        self.socket_address.port()
+        match &self.capturing_proxy {
+            Some(capturing_proxy) => capturing_proxy.port(),
+            None => self.socket_address.port(),
+        }
+    }
+
+    /// Returns the traffic captured so far, if traffic is being captured.
+    pub fn traffic_capture(&self) -> Option<TrafficCapture> {
+        self.capturing_proxy.as_ref().map(CapturingProxy::capture)
     }
 }
 
@@ -0,0 +1,145 @@
+//! Functionality for simulating network latency between containerized aggregators, via a
+//! [Toxiproxy](https://github.com/Shopify/toxiproxy) container.
+
+use janus_interop_binaries::{
+    test_util::await_http_server, testcontainer::Aggregator, ContainerLogsDropGuard,
+    ContainerLogsSource,
+};
+use reqwest::header::CONTENT_TYPE;
+use serde_json::{json, Value};
+use std::time::Duration;
+use testcontainers::{clients::Cli, core::WaitFor, Image, RunnableImage};
+use url::Url;
+
+/// Simulated network conditions imposed on responses from an aggregator.
+#[derive(Debug, Clone, Copy)]
+pub struct NetworkLatency {
+    /// Latency added to every response.
+    pub latency: Duration,
+    /// Maximum random variation of the added latency, in either direction.
+    pub jitter: Duration,
+}
+
+/// A [`testcontainers::Image`] that provides a Toxiproxy server.
+#[derive(Debug, Default)]
+pub struct Toxiproxy;
+
+impl Toxiproxy {
+    const NAME: &'static str = "ghcr.io/shopify/toxiproxy";
+    const TAG: &'static str = "2.9.0";
+
+    /// The internal port that Toxiproxy's HTTP API is served on.
+    const API_PORT: u16 = 8474;
+}
+
+impl Image for Toxiproxy {
+    type Args = ();
+
+    fn name(&self) -> String {
+        Self::NAME.to_owned()
+    }
+
+    fn tag(&self) -> String {
+        Self::TAG.to_owned()
+    }
+
+    fn ready_conditions(&self) -> Vec<WaitFor> {
+        Vec::new()
+    }
+}
+
+/// Represents a running proxy container, which stands in for an aggregator in a Docker network,
+/// forwarding connections to it and delaying its responses.
+pub struct LatencyProxy<'a> {
+    container: ContainerLogsDropGuard<'a, Toxiproxy>,
+    name: String,
+}
+
+impl<'a> LatencyProxy<'a> {
+    /// The internal port that the proxy listens on. This matches the port that aggregators serve
+    /// on, so that the proxy's container name can replace an aggregator's host name in URLs.
+    pub const INTERNAL_SERVING_PORT: u16 = Aggregator::INTERNAL_SERVING_PORT;
+
+    /// Create and start a new proxy in the given Docker network, with the given container name,
+    /// forwarding to the aggregator container with the given host name.
+    pub async fn new(
+        test_name: &str,
+        container_client: &'a Cli,
+        network: &str,
+        name: &str,
+        upstream_host: &str,
+        network_latency: NetworkLatency,
+    ) -> LatencyProxy<'a> {
+        let container = ContainerLogsDropGuard::new(
+            test_name,
+            container_client.run(
+                RunnableImage::from(Toxiproxy)
+                    .with_network(network)
+                    .with_container_name(name),
+            ),
+            ContainerLogsSource::Docker,
+        );
+        let api_port = container.get_host_port_ipv4(Toxiproxy::API_PORT);
+
+        // Wait for the container to start listening on its port.
+        await_http_server(api_port).await;
+
+        // Configure the proxy, and the latency it adds to the upstream's responses.
+        let api_url = Url::parse(&format!("http://127.0.0.1:{api_port}/")).unwrap();
+        post_json(
+            api_url.join("proxies").unwrap(),
+            json!({
+                "name": "aggregator",
+                "listen": format!("0.0.0.0:{}", Self::INTERNAL_SERVING_PORT),
+                "upstream": format!("{upstream_host}:{}", Aggregator::INTERNAL_SERVING_PORT),
+            }),
+        )
+        .await;
+        post_json(
+            api_url.join("proxies/aggregator/toxics").unwrap(),
+            json!({
+                "name": "latency",
+                "type": "latency",
+                "stream": "downstream",
+                "attributes": {
+                    "latency": network_latency.latency.as_millis(),
+                    "jitter": network_latency.jitter.as_millis(),
+                },
+            }),
+        )
+        .await;
+
+        Self {
+            container,
+            name: name.to_owned(),
+        }
+    }
+
+    /// Returns the URL at which the proxied aggregator can be reached from within the Docker
+    /// network.
+    pub fn endpoint_for_virtual_network(&self) -> Url {
+        Url::parse(&format!(
+            "http://{}:{}/",
+            self.name,
+            Self::INTERNAL_SERVING_PORT
+        ))
+        .unwrap()
+    }
+
+    /// Returns the port of the proxy's HTTP API on the host.
+    pub fn api_port(&self) -> u16 {
+        self.container.get_host_port_ipv4(Toxiproxy::API_PORT)
+    }
+}
+
+async fn post_json(url: Url, body: Value) {
+    reqwest::Client::new()
+        .post(url)
+        .header(CONTENT_TYPE, "application/json")
+        .body(body.to_string())
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+}
@@ -11,6 +11,9 @@ pub mod client;
 pub mod daphne;
 pub mod interop_api;
 pub mod janus;
+#[cfg(feature = "testcontainer")]
+pub mod latency_proxy;
+pub mod traffic_capture;
 
 /// Task parameters needed for an integration test. This encompasses the parameters used by either
 /// the client or collector.
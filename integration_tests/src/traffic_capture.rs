@@ -0,0 +1,364 @@
+//! Capture and replay of the DAP HTTP traffic exchanged with an aggregator, for offline debugging
+//! of interop failures.
+//!
+//! A [`CapturingProxy`] stands in front of an aggregator, forwarding each request to it and
+//! recording the request and response, with authentication secrets redacted. The resulting
+//! [`TrafficCapture`] may be written to a JSON artifact, and later re-sent to a local aggregator
+//! with [`replay`], or with the `replay_dap_traffic` tool.
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use janus_core::auth_tokens::AuthenticationToken;
+use serde::{Deserialize, Serialize};
+use std::{
+    env::{self, VarError},
+    fs::{create_dir_all, File},
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+use trillium::{Conn, Handler, Status};
+use trillium_tokio::Stopper;
+use url::Url;
+
+/// Placeholder that replaces the values of headers carrying secrets in captured traffic.
+pub const REDACTED: &str = "REDACTED";
+
+/// Headers whose values are never written to a capture.
+const REDACTED_HEADERS: [&str; 4] = ["authorization", "dap-auth-token", "cookie", "set-cookie"];
+
+/// Headers which describe a single hop of an HTTP exchange, and are thus not forwarded or
+/// replayed.
+const HOP_BY_HOP_HEADERS: [&str; 5] = [
+    "connection",
+    "content-length",
+    "host",
+    "keep-alive",
+    "transfer-encoding",
+];
+
+/// Returns the directory to write traffic captures to, or None if traffic should not be captured.
+///
+/// The resulting value is based directly on the JANUS_E2E_CAPTURE_PATH environment variable.
+pub fn traffic_capture_path() -> Option<PathBuf> {
+    match env::var("JANUS_E2E_CAPTURE_PATH") {
+        Ok(capture_path) => Some(PathBuf::from_str(&capture_path).unwrap()),
+        Err(VarError::NotPresent) => None,
+        Err(err) => panic!("Failed to parse JANUS_E2E_CAPTURE_PATH: {err}"),
+    }
+}
+
+/// The DAP HTTP exchanges sent to one aggregator, in the order they completed.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrafficCapture {
+    pub exchanges: Vec<CapturedExchange>,
+}
+
+impl TrafficCapture {
+    /// Reads a capture from a JSON artifact.
+    pub fn read(path: &Path) -> anyhow::Result<Self> {
+        Ok(serde_json::from_reader(File::open(path)?)?)
+    }
+
+    /// Writes the capture to a JSON artifact.
+    pub fn write(&self, path: &Path) -> anyhow::Result<()> {
+        Ok(serde_json::to_writer_pretty(File::create(path)?, self)?)
+    }
+}
+
+/// One captured HTTP request and the aggregator's response to it. Bodies are encoded in base64url
+/// with no padding, and the values of headers carrying secrets are replaced with [`REDACTED`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CapturedExchange {
+    pub method: String,
+    /// The request's path, including its query string, if any.
+    pub path: String,
+    pub request_headers: Vec<(String, String)>,
+    pub request_body: String,
+    pub status: u16,
+    pub response_headers: Vec<(String, String)>,
+    pub response_body: String,
+}
+
+impl CapturedExchange {
+    /// Decodes the request body.
+    pub fn request_body(&self) -> anyhow::Result<Vec<u8>> {
+        Ok(URL_SAFE_NO_PAD.decode(&self.request_body)?)
+    }
+
+    /// Decodes the response body.
+    pub fn response_body(&self) -> anyhow::Result<Vec<u8>> {
+        Ok(URL_SAFE_NO_PAD.decode(&self.response_body)?)
+    }
+}
+
+fn is_redacted_header(name: &str) -> bool {
+    REDACTED_HEADERS
+        .iter()
+        .any(|redacted| redacted.eq_ignore_ascii_case(name))
+}
+
+fn is_hop_by_hop_header(name: &str) -> bool {
+    HOP_BY_HOP_HEADERS
+        .iter()
+        .any(|hop_by_hop| hop_by_hop.eq_ignore_ascii_case(name))
+}
+
+fn redact_headers(headers: &[(String, String)]) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            if is_redacted_header(name) {
+                (name.clone(), REDACTED.to_owned())
+            } else {
+                (name.clone(), value.clone())
+            }
+        })
+        .collect()
+}
+
+/// Represents a running proxy in this process, which stands in for an aggregator on localhost,
+/// forwarding requests to it and recording each exchange. If an output path was provided, the
+/// capture is written there when the proxy is dropped.
+pub struct CapturingProxy {
+    socket_address: SocketAddr,
+    stopper: Stopper,
+    capture: Arc<Mutex<TrafficCapture>>,
+    output_path: Option<PathBuf>,
+}
+
+impl CapturingProxy {
+    /// Start a new proxy forwarding to the aggregator at `upstream`.
+    pub async fn new(upstream: Url, output_path: Option<PathBuf>) -> Self {
+        let stopper = Stopper::new();
+        let capture = Arc::new(Mutex::new(TrafficCapture::default()));
+        let server_handle = trillium_tokio::config()
+            .without_signals()
+            .with_host("127.0.0.1")
+            .with_port(0)
+            .with_stopper(stopper.clone())
+            .spawn(CapturingHandler {
+                upstream,
+                http_client: reqwest::Client::new(),
+                capture: Arc::clone(&capture),
+            });
+        let socket_address = server_handle
+            .info()
+            .await
+            .tcp_socket_addr()
+            .copied()
+            .unwrap();
+
+        Self {
+            socket_address,
+            stopper,
+            capture,
+            output_path,
+        }
+    }
+
+    /// Returns the proxy's port.
+    pub fn port(&self) -> u16 {
+        self.socket_address.port()
+    }
+
+    /// Returns the exchanges captured so far.
+    pub fn capture(&self) -> TrafficCapture {
+        self.capture.lock().unwrap().clone()
+    }
+}
+
+impl Drop for CapturingProxy {
+    fn drop(&mut self) {
+        self.stopper.stop();
+
+        // As with container logs, a failure to write the capture is surfaced by panicking, since
+        // this only happens in test code.
+        if let Some(output_path) = &self.output_path {
+            if let Some(parent) = output_path.parent() {
+                create_dir_all(parent).expect("could not create traffic capture directory");
+            }
+            self.capture()
+                .write(output_path)
+                .expect("could not write traffic capture");
+        }
+    }
+}
+
+struct CapturingHandler {
+    upstream: Url,
+    http_client: reqwest::Client,
+    capture: Arc<Mutex<TrafficCapture>>,
+}
+
+#[trillium::async_trait]
+impl Handler for CapturingHandler {
+    async fn run(&self, mut conn: Conn) -> Conn {
+        let method = conn.method().as_ref().to_owned();
+        let path = match conn.querystring() {
+            "" => conn.path().to_owned(),
+            querystring => format!("{}?{querystring}", conn.path()),
+        };
+        let request_headers: Vec<(String, String)> = conn
+            .request_headers()
+            .iter()
+            .flat_map(|(name, values)| {
+                values
+                    .iter()
+                    .map(move |value| (name.to_string(), value.to_string()))
+            })
+            .collect();
+        let request_body = match conn.request_body().await.read_bytes().await {
+            Ok(request_body) => request_body,
+            Err(_) => return conn.with_status(Status::BadRequest).halt(),
+        };
+
+        let response = match send_request(
+            &self.http_client,
+            &self.upstream,
+            &method,
+            &path,
+            &request_headers,
+            request_body.clone(),
+        )
+        .await
+        {
+            Ok(response) => response,
+            Err(_) => return conn.with_status(Status::BadGateway).halt(),
+        };
+
+        self.capture
+            .lock()
+            .unwrap()
+            .exchanges
+            .push(CapturedExchange {
+                method,
+                path,
+                request_headers: redact_headers(&request_headers),
+                request_body: URL_SAFE_NO_PAD.encode(&request_body),
+                status: response.status,
+                response_headers: redact_headers(&response.headers),
+                response_body: URL_SAFE_NO_PAD.encode(&response.body),
+            });
+
+        for (name, value) in response.headers {
+            conn.response_headers_mut().append(name, value);
+        }
+        conn.with_status(response.status)
+            .with_body(response.body)
+            .halt()
+    }
+}
+
+/// An aggregator's response to a forwarded or replayed request.
+struct UpstreamResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+async fn send_request(
+    http_client: &reqwest::Client,
+    base_url: &Url,
+    method: &str,
+    path: &str,
+    headers: &[(String, String)],
+    body: Vec<u8>,
+) -> anyhow::Result<UpstreamResponse> {
+    let mut request = http_client.request(reqwest::Method::from_str(method)?, base_url.join(path)?);
+    for (name, value) in headers {
+        if !is_hop_by_hop_header(name) {
+            request = request.header(name, value);
+        }
+    }
+    let response = request.body(body).send().await?;
+
+    let status = response.status().as_u16();
+    let headers = response
+        .headers()
+        .iter()
+        .filter(|(name, _)| !is_hop_by_hop_header(name.as_str()))
+        .map(|(name, value)| Ok((name.to_string(), value.to_str()?.to_owned())))
+        .collect::<anyhow::Result<_>>()?;
+    let body = response.bytes().await?.to_vec();
+    Ok(UpstreamResponse {
+        status,
+        headers,
+        body,
+    })
+}
+
+/// The result of replaying one captured exchange.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayOutcome {
+    /// Index of the exchange within the capture.
+    pub index: usize,
+    pub method: String,
+    pub path: String,
+    pub captured_status: u16,
+    pub replayed_status: u16,
+    /// Whether the replayed response body is identical to the captured one. Responses containing
+    /// fresh HPKE ciphertexts, such as aggregate shares, never match.
+    pub body_matches: bool,
+}
+
+impl ReplayOutcome {
+    /// Returns true if the aggregator responded to the replayed request as it did originally.
+    pub fn matches(&self) -> bool {
+        self.captured_status == self.replayed_status && self.body_matches
+    }
+}
+
+/// Re-sends captured requests, in order, to the aggregator at `base_url`, comparing its responses
+/// to the captured ones. Only the exchanges whose indices are yielded by `indices` are replayed.
+/// Since secrets are redacted from captures, `auth_token`, if provided, is substituted for the
+/// redacted value of the header it is presented in.
+pub async fn replay(
+    capture: &TrafficCapture,
+    indices: impl IntoIterator<Item = usize>,
+    base_url: &Url,
+    auth_token: Option<&AuthenticationToken>,
+) -> anyhow::Result<Vec<ReplayOutcome>> {
+    let http_client = reqwest::Client::new();
+    let auth_header = auth_token.map(AuthenticationToken::request_authentication);
+
+    let mut outcomes = Vec::new();
+    for index in indices {
+        let exchange = capture
+            .exchanges
+            .get(index)
+            .ok_or_else(|| anyhow::anyhow!("capture has no exchange {index}"))?;
+        let headers: Vec<(String, String)> = exchange
+            .request_headers
+            .iter()
+            .filter_map(|(name, value)| match &auth_header {
+                Some((auth_name, auth_value))
+                    if value == REDACTED && name.eq_ignore_ascii_case(auth_name) =>
+                {
+                    Some((name.clone(), auth_value.clone()))
+                }
+                _ if value == REDACTED => None,
+                _ => Some((name.clone(), value.clone())),
+            })
+            .collect();
+
+        let response = send_request(
+            &http_client,
+            base_url,
+            &exchange.method,
+            &exchange.path,
+            &headers,
+            exchange.request_body()?,
+        )
+        .await?;
+        outcomes.push(ReplayOutcome {
+            index,
+            method: exchange.method.clone(),
+            path: exchange.path.clone(),
+            captured_status: exchange.status,
+            replayed_status: response.status,
+            body_matches: exchange.response_body()? == response.body,
+        });
+    }
+    Ok(outcomes)
+}
@@ -118,9 +118,9 @@ pub struct AggregationTestCase<V>
 where
     V: vdaf::Client<16> + vdaf::Collector,
 {
-    measurements: Vec<V::Measurement>,
-    aggregation_parameter: V::AggregationParam,
-    aggregate_result: V::AggregateResult,
+    pub measurements: Vec<V::Measurement>,
+    pub aggregation_parameter: V::AggregationParam,
+    pub aggregate_result: V::AggregateResult,
 }
 
 pub async fn collect_generic<'a, V, Q>(
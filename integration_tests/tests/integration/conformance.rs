@@ -0,0 +1,179 @@
+//! Conformance tests, which run the test vectors from the VDAF specification through the full
+//! leader and helper aggregation pipeline, and check that the collected aggregates match.
+//!
+//! The test vectors are taken from draft-irtf-cfrg-vdaf-08, restricted to those with two shares.
+//! Since clients shard measurements with fresh randomness, only the VDAF parameters, verify key,
+//! measurements, and aggregate result are used, and the fields describing intermediate values
+//! (input shares, preparation messages, and aggregate shares) have been omitted.
+
+use crate::common::{
+    build_test_task, submit_measurements_and_verify_aggregate_generic, AggregationTestCase,
+    TestContext,
+};
+use janus_aggregator_core::{
+    task::{test_util::TaskBuilder, QueryType},
+    SecretBytes,
+};
+use janus_core::{test_util::install_test_trace_subscriber, vdaf::VdafInstance};
+use janus_integration_tests::{
+    client::{ClientBackend, InteropClientEncoding},
+    janus::JanusInProcess,
+};
+use janus_messages::Role;
+use prio::vdaf::{self, prio3::Prio3};
+use serde::Deserialize;
+use std::time::Duration;
+
+/// A VDAF test vector. Fields that are not needed to check the aggregate result are ignored.
+#[derive(Deserialize)]
+struct TestVector<M, R> {
+    verify_key: String,
+    #[serde(default)]
+    bits: Option<usize>,
+    #[serde(default)]
+    length: Option<usize>,
+    #[serde(default)]
+    chunk_length: Option<usize>,
+    prep: Vec<PrepTestVector<M>>,
+    agg_result: R,
+}
+
+/// The preparation of a single report in a VDAF test vector.
+#[derive(Deserialize)]
+struct PrepTestVector<M> {
+    measurement: M,
+}
+
+/// Provisions a task using the given VDAF and the test vector's verify key in a pair of in-process
+/// Janus instances, then uploads the test case's measurements, collects them, and checks the
+/// aggregate result.
+async fn run_test_vector<V>(
+    test_name: &str,
+    vdaf_instance: VdafInstance,
+    vdaf: V,
+    verify_key: &str,
+    test_case: AggregationTestCase<V>,
+) where
+    V: vdaf::Client<16> + vdaf::Collector + InteropClientEncoding,
+    V::AggregateResult: PartialEq,
+{
+    let (mut task_parameters, task_builder) = build_test_task(
+        TaskBuilder::new(QueryType::TimeInterval, vdaf_instance)
+            .with_vdaf_verify_key(SecretBytes::new(hex::decode(verify_key).unwrap())),
+        TestContext::Host,
+        Duration::from_millis(500),
+        Duration::from_secs(60),
+    );
+
+    // The test vectors have fewer reports than the usual minimum batch size, so the whole test
+    // vector is collected as one batch.
+    let min_batch_size = u64::try_from(test_case.measurements.len()).unwrap();
+    task_parameters.min_batch_size = min_batch_size;
+    let mut task_builder = task_builder.with_min_batch_size(min_batch_size);
+
+    // Start servers.
+    let helper = JanusInProcess::new(&task_builder.clone().build(), Role::Helper).await;
+    let helper_url = task_parameters
+        .endpoint_fragments
+        .helper
+        .endpoint_for_host(helper.port());
+    task_builder = task_builder.with_helper_aggregator_endpoint(helper_url);
+    let leader = JanusInProcess::new(&task_builder.build(), Role::Leader).await;
+
+    let client_implementation = ClientBackend::InProcess
+        .build(
+            test_name,
+            &task_parameters,
+            (leader.port(), helper.port()),
+            vdaf.clone(),
+        )
+        .await
+        .unwrap();
+
+    submit_measurements_and_verify_aggregate_generic(
+        &task_parameters,
+        leader.port(),
+        vdaf,
+        &test_case,
+        &client_implementation,
+    )
+    .await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn conformance_prio3_count() {
+    install_test_trace_subscriber();
+    let test_vector: TestVector<u64, u64> =
+        serde_json::from_str(include_str!("test_vectors/Prio3Count_0.json")).unwrap();
+
+    run_test_vector(
+        "conformance_prio3_count",
+        VdafInstance::Prio3Count,
+        Prio3::new_count(2).unwrap(),
+        &test_vector.verify_key,
+        AggregationTestCase {
+            measurements: test_vector
+                .prep
+                .iter()
+                .map(|prep| prep.measurement != 0)
+                .collect(),
+            aggregation_parameter: (),
+            aggregate_result: test_vector.agg_result,
+        },
+    )
+    .await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn conformance_prio3_sum() {
+    install_test_trace_subscriber();
+    let test_vector: TestVector<u128, u128> =
+        serde_json::from_str(include_str!("test_vectors/Prio3Sum_0.json")).unwrap();
+    let bits = test_vector.bits.unwrap();
+
+    run_test_vector(
+        "conformance_prio3_sum",
+        VdafInstance::Prio3Sum { bits },
+        Prio3::new_sum(2, bits).unwrap(),
+        &test_vector.verify_key,
+        AggregationTestCase {
+            measurements: test_vector
+                .prep
+                .iter()
+                .map(|prep| prep.measurement)
+                .collect(),
+            aggregation_parameter: (),
+            aggregate_result: test_vector.agg_result,
+        },
+    )
+    .await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn conformance_prio3_histogram() {
+    install_test_trace_subscriber();
+    let test_vector: TestVector<usize, Vec<u128>> =
+        serde_json::from_str(include_str!("test_vectors/Prio3Histogram_0.json")).unwrap();
+    let length = test_vector.length.unwrap();
+    let chunk_length = test_vector.chunk_length.unwrap();
+
+    run_test_vector(
+        "conformance_prio3_histogram",
+        VdafInstance::Prio3Histogram {
+            length,
+            chunk_length,
+        },
+        Prio3::new_histogram(2, length, chunk_length).unwrap(),
+        &test_vector.verify_key,
+        AggregationTestCase {
+            measurements: test_vector
+                .prep
+                .iter()
+                .map(|prep| prep.measurement)
+                .collect(),
+            aggregation_parameter: (),
+            aggregate_result: test_vector.agg_result,
+        },
+    )
+    .await;
+}
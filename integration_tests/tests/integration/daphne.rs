@@ -4,18 +4,30 @@ use janus_core::{
     test_util::{install_test_trace_subscriber, testcontainers::container_client},
     vdaf::VdafInstance,
 };
-#[cfg(feature = "testcontainer")]
-use janus_integration_tests::janus::JanusContainer;
 use janus_integration_tests::{
-    client::ClientBackend, daphne::Daphne, janus::JanusInProcess, AggregatorEndpointFragments,
+    client::ClientBackend,
+    daphne::{
+        parse_daphne_collection_job_uri, Daphne, DaphneCollectionRequest, DAPHNE_PATH_PREFIX,
+    },
+    janus::JanusInProcess,
+    AggregatorEndpointFragments,
 };
+#[cfg(feature = "testcontainer")]
+use janus_integration_tests::{daphne::daphne_aggregator_endpoint, janus::JanusContainer};
 use janus_interop_binaries::test_util::generate_network_name;
-use janus_messages::Role;
+use janus_messages::{
+    query_type::TimeInterval, CollectionJobId, CollectionReq, Duration as DurationMsg, Interval,
+    Query, Role, TaskId, Time,
+};
+use rand::random;
 use std::time::Duration;
 
-// This test places Daphne in the leader role & Janus in the helper role.
+// This test places Daphne in the leader role & Janus in the helper role. The Daphne leader is driven
+// by calling its internal process endpoint, so this exercises Janus' helper-side handling of
+// aggregation jobs and aggregate share requests created by another implementation.
 #[tokio::test(flavor = "multi_thread")]
-#[ignore = "Daphne does not yet publish a leader container image"]
+#[ignore = "Daphne does not currently support DAP-07 (issue #1669), and the Daphne leader image \
+            has not been verified"]
 #[cfg(feature = "testcontainer")]
 async fn daphne_janus() {
     static TEST_NAME: &str = "daphne_janus";
@@ -34,9 +46,9 @@ async fn daphne_janus() {
     task_parameters
         .endpoint_fragments
         .leader
-        .set_path("/v04/".to_string());
-    let mut leader_aggregator_endpoint = task_builder.leader_aggregator_endpoint().clone();
-    leader_aggregator_endpoint.set_path("/v04/");
+        .set_path(DAPHNE_PATH_PREFIX.to_string());
+    let leader_aggregator_endpoint =
+        daphne_aggregator_endpoint(task_builder.leader_aggregator_endpoint());
     let task = task_builder
         .with_leader_aggregator_endpoint(leader_aggregator_endpoint)
         .build();
@@ -77,9 +89,9 @@ async fn janus_daphne() {
     task_parameters
         .endpoint_fragments
         .helper
-        .set_path("/v04/".to_string());
-    let mut helper_aggregator_endpoint = task_builder.helper_aggregator_endpoint().clone();
-    helper_aggregator_endpoint.set_path("/v04/");
+        .set_path(DAPHNE_PATH_PREFIX.to_string());
+    let helper_aggregator_endpoint =
+        daphne_aggregator_endpoint(task_builder.helper_aggregator_endpoint());
     let task = task_builder
         .with_helper_aggregator_endpoint(helper_aggregator_endpoint)
         .build();
@@ -122,7 +134,7 @@ async fn janus_in_process_daphne() {
     task_parameters
         .endpoint_fragments
         .helper
-        .set_path("/v04/".to_owned());
+        .set_path(DAPHNE_PATH_PREFIX.to_owned());
     let helper = Daphne::new(
         TEST_NAME,
         &container_client,
@@ -148,3 +160,41 @@ async fn janus_in_process_daphne() {
     )
     .await;
 }
+
+#[test]
+fn daphne_collection_request() {
+    let leader_endpoint = "http://leader:8080/some/path/".parse().unwrap();
+    let task_id: TaskId = random();
+    let collection_job_id: CollectionJobId = random();
+    let request = CollectionReq::<TimeInterval>::new(
+        Query::new_time_interval(
+            Interval::new(
+                Time::from_seconds_since_epoch(3600),
+                DurationMsg::from_seconds(3600),
+            )
+            .unwrap(),
+        ),
+        Vec::new(),
+    );
+
+    let daphne_request =
+        DaphneCollectionRequest::new(&leader_endpoint, &task_id, &collection_job_id, &request);
+    assert_eq!(
+        daphne_request.uri.as_str(),
+        format!("http://leader:8080/v04/tasks/{task_id}/collection_jobs/{collection_job_id}")
+    );
+    assert_eq!(daphne_request.content_type, "application/dap-collect-req");
+    assert_eq!(
+        parse_daphne_collection_job_uri(&daphne_request.uri),
+        Some((task_id, collection_job_id))
+    );
+
+    for uri in [
+        format!("http://leader:8080/tasks/{task_id}/collection_jobs/{collection_job_id}"),
+        format!("http://leader:8080/v04/tasks/{task_id}/aggregation_jobs/{collection_job_id}"),
+        format!("http://leader:8080/v04/tasks/{task_id}/collection_jobs/{collection_job_id}/x"),
+        format!("http://leader:8080/v04/tasks/{task_id}/collection_jobs/AAAA"),
+    ] {
+        assert_eq!(parse_daphne_collection_job_uri(&uri.parse().unwrap()), None);
+    }
+}
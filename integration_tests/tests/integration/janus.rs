@@ -1,11 +1,23 @@
 use crate::common::{build_test_task, submit_measurements_and_verify_aggregate, TestContext};
+use janus_aggregator::binaries::aggregator::GarbageCollectorConfig;
 use janus_aggregator_core::task::{test_util::TaskBuilder, QueryType};
 #[cfg(feature = "testcontainer")]
 use janus_core::test_util::testcontainers::container_client;
-use janus_core::{test_util::install_test_trace_subscriber, vdaf::VdafInstance};
+use janus_core::{
+    auth_tokens::DAP_AUTH_HEADER, test_util::install_test_trace_subscriber, time::DurationExt,
+    vdaf::VdafInstance,
+};
+use janus_integration_tests::{
+    client::ClientBackend,
+    janus::JanusInProcess,
+    traffic_capture::{replay, TrafficCapture, REDACTED},
+    TaskParameters,
+};
 #[cfg(feature = "testcontainer")]
-use janus_integration_tests::janus::JanusContainer;
-use janus_integration_tests::{client::ClientBackend, janus::JanusInProcess, TaskParameters};
+use janus_integration_tests::{
+    janus::JanusContainer,
+    latency_proxy::{LatencyProxy, NetworkLatency},
+};
 #[cfg(feature = "testcontainer")]
 use janus_interop_binaries::test_util::generate_network_name;
 use janus_messages::Role;
@@ -24,6 +36,8 @@ struct JanusContainerPair<'a> {
     leader: JanusContainer<'a>,
     /// Handle to the helper's resources, which are released on drop.
     helper: JanusContainer<'a>,
+    /// Handle to the proxy between the leader and the helper, if any, which is released on drop.
+    _latency_proxy: Option<LatencyProxy<'a>>,
 }
 
 #[cfg(feature = "testcontainer")]
@@ -35,6 +49,18 @@ impl<'a> JanusContainerPair<'a> {
         container_client: &'a Cli,
         vdaf: VdafInstance,
         query_type: QueryType,
+    ) -> JanusContainerPair<'a> {
+        Self::new_with_network_latency(test_name, container_client, vdaf, query_type, None).await
+    }
+
+    /// Like [`Self::new`], but if `network_latency` is provided, the leader reaches the helper
+    /// through a proxy that delays the helper's responses accordingly.
+    pub async fn new_with_network_latency(
+        test_name: &str,
+        container_client: &'a Cli,
+        vdaf: VdafInstance,
+        query_type: QueryType,
+        network_latency: Option<NetworkLatency>,
     ) -> JanusContainerPair<'a> {
         let (task_parameters, task_builder) = build_test_task(
             TaskBuilder::new(query_type, vdaf),
@@ -42,18 +68,55 @@ impl<'a> JanusContainerPair<'a> {
             Duration::from_millis(500),
             Duration::from_secs(60),
         );
-        let task = task_builder.build();
 
         let network = generate_network_name();
-        let leader =
-            JanusContainer::new(test_name, container_client, &network, &task, Role::Leader).await;
-        let helper =
-            JanusContainer::new(test_name, container_client, &network, &task, Role::Helper).await;
+        let helper_task = task_builder.clone().build();
+        let helper = JanusContainer::new(
+            test_name,
+            container_client,
+            &network,
+            &helper_task,
+            Role::Helper,
+        )
+        .await;
+
+        let (leader_task, latency_proxy) = match network_latency {
+            Some(network_latency) => {
+                let helper_host = helper_task.helper_aggregator_endpoint().host_str().unwrap();
+                let latency_proxy = LatencyProxy::new(
+                    test_name,
+                    container_client,
+                    &network,
+                    &format!("{helper_host}-proxy"),
+                    helper_host,
+                    network_latency,
+                )
+                .await;
+                (
+                    task_builder
+                        .with_helper_aggregator_endpoint(
+                            latency_proxy.endpoint_for_virtual_network(),
+                        )
+                        .build(),
+                    Some(latency_proxy),
+                )
+            }
+            None => (helper_task, None),
+        };
+        let leader = JanusContainer::new(
+            test_name,
+            container_client,
+            &network,
+            &leader_task,
+            Role::Leader,
+        )
+        .await;
 
         Self {
             task_parameters,
             leader,
             helper,
+            _latency_proxy: latency_proxy,
         }
     }
 }
@@ -74,6 +137,15 @@ impl JanusInProcessPair {
     /// Set up a new pair of in-process Janus test instances, and set up a new task in each using
     /// the given VDAF and query type.
     pub async fn new(task_builder: TaskBuilder) -> JanusInProcessPair {
+        Self::new_with_garbage_collection(task_builder, None).await
+    }
+
+    /// Like [`Self::new`], but both aggregators also run the garbage collector with the given
+    /// configuration, if any.
+    pub async fn new_with_garbage_collection(
+        task_builder: TaskBuilder,
+        garbage_collection: Option<GarbageCollectorConfig>,
+    ) -> JanusInProcessPair {
         let (task_parameters, mut task_builder) = build_test_task(
             task_builder,
             TestContext::Host,
@@ -81,13 +153,23 @@ impl JanusInProcessPair {
             Duration::from_secs(60),
         );
 
-        let helper = JanusInProcess::new(&task_builder.clone().build(), Role::Helper).await;
+        let helper = JanusInProcess::new_with_garbage_collection(
+            &task_builder.clone().build(),
+            Role::Helper,
+            garbage_collection.clone(),
+        )
+        .await;
         let helper_url = task_parameters
             .endpoint_fragments
             .helper
             .endpoint_for_host(helper.port());
         task_builder = task_builder.with_helper_aggregator_endpoint(helper_url);
-        let leader = JanusInProcess::new(&task_builder.build(), Role::Leader).await;
+        let leader = JanusInProcess::new_with_garbage_collection(
+            &task_builder.build(),
+            Role::Leader,
+            garbage_collection,
+        )
+        .await;
 
         Self {
             task_parameters,
@@ -124,6 +206,38 @@ async fn janus_janus_count() {
     .await;
 }
 
+/// This test exercises Prio3Count with Janus as both the leader and the helper, with realistic
+/// latency between the two.
+#[tokio::test(flavor = "multi_thread")]
+#[cfg(feature = "testcontainer")]
+async fn janus_janus_count_network_latency() {
+    static TEST_NAME: &str = "janus_janus_count_network_latency";
+    install_test_trace_subscriber();
+
+    // Start servers.
+    let container_client = container_client();
+    let janus_pair = JanusContainerPair::new_with_network_latency(
+        TEST_NAME,
+        &container_client,
+        VdafInstance::Prio3Count,
+        QueryType::TimeInterval,
+        Some(NetworkLatency {
+            latency: Duration::from_millis(200),
+            jitter: Duration::from_millis(100),
+        }),
+    )
+    .await;
+
+    // Run the behavioral test.
+    submit_measurements_and_verify_aggregate(
+        TEST_NAME,
+        &janus_pair.task_parameters,
+        (janus_pair.leader.port(), janus_pair.helper.port()),
+        &ClientBackend::InProcess,
+    )
+    .await;
+}
+
 /// This test exercises Prio3Count with Janus as both the leader and the helper.
 #[tokio::test(flavor = "multi_thread")]
 async fn janus_in_process_count() {
@@ -146,6 +260,123 @@ async fn janus_in_process_count() {
     .await;
 }
 
+/// This test exercises Prio3Count with Janus as both the leader and the helper, capturing the
+/// traffic sent to the helper, and then replays the captured requests against the helper.
+#[tokio::test(flavor = "multi_thread")]
+async fn janus_in_process_count_traffic_capture() {
+    install_test_trace_subscriber();
+    let capture_path = tempfile::tempdir().unwrap();
+
+    // Start servers.
+    let (task_parameters, mut task_builder) = build_test_task(
+        TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Prio3Count),
+        TestContext::Host,
+        Duration::from_millis(500),
+        Duration::from_secs(60),
+    );
+    let helper_task = task_builder.clone().build();
+    let helper = JanusInProcess::new_with_traffic_capture(
+        &helper_task,
+        Role::Helper,
+        capture_path.path().to_path_buf(),
+    )
+    .await;
+    let helper_url = task_parameters
+        .endpoint_fragments
+        .helper
+        .endpoint_for_host(helper.port());
+    task_builder = task_builder.with_helper_aggregator_endpoint(helper_url.clone());
+    let leader = JanusInProcess::new(&task_builder.build(), Role::Leader).await;
+
+    // Run the behavioral test.
+    submit_measurements_and_verify_aggregate(
+        "janus_in_process_count_traffic_capture",
+        &task_parameters,
+        (leader.port(), helper.port()),
+        &ClientBackend::InProcess,
+    )
+    .await;
+
+    // The leader's auth token is redacted from the capture.
+    let capture = helper.traffic_capture().unwrap();
+    assert!(!capture.exchanges.is_empty());
+    for exchange in &capture.exchanges {
+        for (name, value) in &exchange.request_headers {
+            if name.eq_ignore_ascii_case(DAP_AUTH_HEADER) {
+                assert_eq!(value, REDACTED);
+            }
+        }
+    }
+
+    // The helper treats replayed requests as retries, so it responds as it did originally,
+    // except that aggregate shares are encrypted afresh.
+    let outcomes = replay(
+        &capture,
+        0..capture.exchanges.len(),
+        &helper_url,
+        Some(helper_task.aggregator_auth_token()),
+    )
+    .await
+    .unwrap();
+    assert_eq!(outcomes.len(), capture.exchanges.len());
+    for outcome in outcomes {
+        assert_eq!(
+            outcome.captured_status, outcome.replayed_status,
+            "{outcome:?}"
+        );
+        if outcome.path.contains("/aggregation_jobs/") {
+            assert!(outcome.body_matches, "{outcome:?}");
+        }
+    }
+
+    // The capture is written out when the helper is dropped. It also includes the replayed
+    // requests, which went through the same proxy.
+    drop(helper);
+    let written_capture = TrafficCapture::read(
+        &capture_path
+            .path()
+            .join(format!("{}-helper.json", helper_task.id())),
+    )
+    .unwrap();
+    assert!(written_capture.exchanges.starts_with(&capture.exchanges));
+}
+
+/// This test exercises Prio3Count with Janus as both the leader and the helper, while both
+/// aggregators run the garbage collector every second and delete aggregation jobs a second after
+/// they terminate. Garbage collection must not delete anything that in-progress aggregation or
+/// collection still needs, so the aggregate must still be correct.
+#[tokio::test(flavor = "multi_thread")]
+async fn janus_in_process_count_aggressive_garbage_collection() {
+    install_test_trace_subscriber();
+
+    // Start servers.
+    let janus_pair = JanusInProcessPair::new_with_garbage_collection(
+        TaskBuilder::new(QueryType::TimeInterval, VdafInstance::Prio3Count)
+            .with_report_expiry_age(Some(janus_messages::Duration::from_hours(24).unwrap())),
+        Some(GarbageCollectorConfig {
+            gc_frequency_s: 1,
+            report_limit: 5,
+            aggregation_limit: 5,
+            collection_limit: 5,
+            tasks_per_tx: 1,
+            concurrent_tx_limit: None,
+            leader_lease_duration_s: None,
+            aggregation_job_ttl_s: Some(1),
+            retention_period_s: 0,
+        }),
+    )
+    .await;
+
+    // Run the behavioral test.
+    submit_measurements_and_verify_aggregate(
+        "janus_in_process_count_aggressive_garbage_collection",
+        &janus_pair.task_parameters,
+        (janus_pair.leader.port(), janus_pair.helper.port()),
+        &ClientBackend::InProcess,
+    )
+    .await;
+}
+
 /// This test exercises Prio3Sum with Janus as both the leader and the helper.
 #[tokio::test(flavor = "multi_thread")]
 #[cfg(feature = "testcontainer")]
@@ -1,4 +1,5 @@
 mod common;
+mod conformance;
 mod daphne;
 mod divviup_ts;
 mod in_cluster;
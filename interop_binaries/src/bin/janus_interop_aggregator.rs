@@ -100,23 +100,23 @@ async fn handle_add_task(
         }
     };
 
-    let task = AggregatorTask::new(
+    let task = AggregatorTask::builder(
         request.task_id,
         peer_aggregator_endpoint,
         query_type,
         vdaf,
         vdaf_verify_key,
-        request.max_batch_query_count,
-        request.task_expiration.map(Time::from_seconds_since_epoch),
-        None,
-        request.min_batch_size,
-        time_precision,
-        // We can be strict about clock skew since this executable is only intended for use with
-        // other aggregators running on the same host.
-        Duration::from_seconds(1),
-        [hpke_keypair],
         aggregator_parameters,
     )
+    .with_max_batch_query_count(request.max_batch_query_count)
+    .with_task_expiration(request.task_expiration.map(Time::from_seconds_since_epoch))
+    .with_min_batch_size(request.min_batch_size)
+    .with_time_precision(time_precision)
+    // We can be strict about clock skew since this executable is only intended for use with
+    // other aggregators running on the same host.
+    .with_tolerable_clock_skew(Duration::from_seconds(1))
+    .with_hpke_keys([hpke_keypair])
+    .build()
     .context("error constructing task")?;
 
     datastore
@@ -133,6 +133,7 @@ async fn make_handler<R: Runtime + Send + Sync + 'static>(
     runtime: R,
     meter: &Meter,
     dap_serving_prefix: String,
+    strict_conformance: bool,
 ) -> anyhow::Result<impl Handler> {
     let keyring = Keyring::new();
     let dap_handler = aggregator_handler(
@@ -144,6 +145,7 @@ async fn make_handler<R: Runtime + Send + Sync + 'static>(
             max_upload_batch_size: 100,
             max_upload_batch_write_delay: std::time::Duration::from_millis(100),
             batch_aggregation_shard_count: 32,
+            strict_conformance,
             ..Default::default()
         },
     )
@@ -219,6 +221,10 @@ struct Config {
     /// Path prefix, e.g. `/dap/`, to serve DAP from.
     #[serde(default = "default_dap_serving_prefix")]
     dap_serving_prefix: String,
+
+    /// Whether to reject DAP requests that don't strictly conform to the specification.
+    #[serde(default)]
+    strict_conformance: bool,
 }
 
 impl BinaryConfig for Config {
@@ -251,6 +257,7 @@ async fn main() -> anyhow::Result<()> {
             TokioRuntime,
             &ctx.meter,
             ctx.config.dap_serving_prefix,
+            ctx.config.strict_conformance,
         )
         .await?;
         trillium_tokio::config()
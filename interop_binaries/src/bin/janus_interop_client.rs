@@ -2,23 +2,14 @@ use anyhow::{anyhow, Context};
 use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use clap::{value_parser, Arg, Command};
 use derivative::Derivative;
-#[cfg(feature = "fpvec_bounded_l2")]
-use fixed::{
-    types::extra::{U15, U31},
-    FixedI16, FixedI32,
-};
-#[cfg(feature = "fpvec_bounded_l2")]
-use janus_core::vdaf::Prio3FixedPointBoundedL2VecSumBitSize;
-use janus_core::vdaf::{new_prio3_sum_vec_field64_multiproof_hmacsha256_aes128, VdafInstance};
+use janus_core::{vdaf::VdafInstance, vdaf_dispatch_no_aggregation_parameter};
 use janus_interop_binaries::{
     install_tracing_subscriber,
     status::{ERROR, SUCCESS},
     ErrorHandler, NumberAsString, VdafObject,
 };
 use janus_messages::{Duration, TaskId, Time};
-#[cfg(feature = "fpvec_bounded_l2")]
-use prio::vdaf::prio3::Prio3FixedPointBoundedL2VecSumMultithreaded;
-use prio::{codec::Decode, vdaf::prio3::Prio3};
+use prio::{codec::Decode, vdaf::Vdaf};
 use serde::{Deserialize, Serialize};
 use std::{fmt::Display, net::Ipv4Addr, str::FromStr};
 use trillium::{Conn, Handler};
@@ -49,6 +40,39 @@ where
     )
 }
 
+/// A VDAF measurement that can be parsed from its intermediate JSON representation.
+trait ParseMeasurement: Sized {
+    fn parse_measurement(value: serde_json::Value) -> anyhow::Result<Self>;
+}
+
+impl ParseMeasurement for bool {
+    fn parse_measurement(value: serde_json::Value) -> anyhow::Result<Self> {
+        Ok(parse_primitive_measurement::<u64>(value)? != 0)
+    }
+}
+
+impl ParseMeasurement for u128 {
+    fn parse_measurement(value: serde_json::Value) -> anyhow::Result<Self> {
+        parse_primitive_measurement(value)
+    }
+}
+
+impl ParseMeasurement for usize {
+    fn parse_measurement(value: serde_json::Value) -> anyhow::Result<Self> {
+        parse_primitive_measurement(value)
+    }
+}
+
+impl<T> ParseMeasurement for Vec<T>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    fn parse_measurement(value: serde_json::Value) -> anyhow::Result<Self> {
+        parse_vector_measurement(value)
+    }
+}
+
 #[derive(Derivative, Deserialize)]
 #[derivative(Debug)]
 struct UploadRequest {
@@ -110,85 +134,15 @@ async fn handle_upload(
     http_client: &reqwest::Client,
     request: UploadRequest,
 ) -> anyhow::Result<()> {
-    let vdaf_instance = request.vdaf.clone().into();
-    match vdaf_instance {
-        VdafInstance::Prio3Count {} => {
-            let measurement = parse_primitive_measurement::<u64>(request.measurement.clone())?;
-            let vdaf = Prio3::new_count(2).context("failed to construct Prio3Count VDAF")?;
-            handle_upload_generic(http_client, vdaf, request, measurement != 0).await?;
-        }
-
-        VdafInstance::Prio3Sum { bits } => {
-            let measurement = parse_primitive_measurement::<u128>(request.measurement.clone())?;
-            let vdaf = Prio3::new_sum(2, bits).context("failed to construct Prio3Sum VDAF")?;
-            handle_upload_generic(http_client, vdaf, request, measurement).await?;
-        }
-
-        VdafInstance::Prio3SumVec {
-            bits,
-            length,
-            chunk_length,
-        } => {
-            let measurement = parse_vector_measurement::<u128>(request.measurement.clone())?;
-            let vdaf = Prio3::new_sum_vec_multithreaded(2, bits, length, chunk_length)
-                .context("failed to construct Prio3SumVec VDAF")?;
-            handle_upload_generic(http_client, vdaf, request, measurement).await?;
-        }
-
-        VdafInstance::Prio3SumVecField64MultiproofHmacSha256Aes128 {
-            proofs,
-            bits,
-            length,
-            chunk_length,
-        } => {
-            let measurement = parse_vector_measurement::<u64>(request.measurement.clone())?;
-            let vdaf = new_prio3_sum_vec_field64_multiproof_hmacsha256_aes128(
-                proofs,
-                bits,
-                length,
-                chunk_length,
-            )
-            .context("failed to construct Prio3SumVecField64MultiproofHmacSha256Aes128 VDAF")?;
-            handle_upload_generic(http_client, vdaf, request, measurement).await?;
-        }
-
-        VdafInstance::Prio3Histogram {
-            length,
-            chunk_length,
-        } => {
-            let measurement = parse_primitive_measurement::<usize>(request.measurement.clone())?;
-            let vdaf = Prio3::new_histogram(2, length, chunk_length)
-                .context("failed to construct Prio3Histogram VDAF")?;
-            handle_upload_generic(http_client, vdaf, request, measurement).await?;
-        }
-
-        #[cfg(feature = "fpvec_bounded_l2")]
-        VdafInstance::Prio3FixedPointBoundedL2VecSum {
-            bitsize,
-            dp_strategy: _,
-            length,
-        } => match bitsize {
-            Prio3FixedPointBoundedL2VecSumBitSize::BitSize16 => {
-                let measurement =
-                    parse_vector_measurement::<FixedI16<U15>>(request.measurement.clone())?;
-                let vdaf: Prio3FixedPointBoundedL2VecSumMultithreaded<FixedI16<U15>> =
-                    Prio3::new_fixedpoint_boundedl2_vec_sum_multithreaded(2, length).context(
-                        "failed to construct Prio3FixedPoint16BitBoundedL2VecSumZCdp VDAF",
-                    )?;
-                handle_upload_generic(http_client, vdaf, request, measurement).await?;
-            }
-            Prio3FixedPointBoundedL2VecSumBitSize::BitSize32 => {
-                let measurement =
-                    parse_vector_measurement::<FixedI32<U31>>(request.measurement.clone())?;
-                let vdaf: Prio3FixedPointBoundedL2VecSumMultithreaded<FixedI32<U31>> =
-                    Prio3::new_fixedpoint_boundedl2_vec_sum_multithreaded(2, length).context(
-                        "failed to construct Prio3FixedPoint32BitBoundedL2VecSumZCdp VDAF",
-                    )?;
-                handle_upload_generic(http_client, vdaf, request, measurement).await?;
-            }
-        },
-        _ => panic!("Unsupported VDAF: {vdaf_instance:?}"),
-    }
+    let vdaf_instance: VdafInstance = request.vdaf.clone().into();
+    vdaf_dispatch_no_aggregation_parameter!(&vdaf_instance, (vdaf, VdafType, _VERIFY_KEY_LEN) => {
+        let measurement = <<VdafType as Vdaf>::Measurement as ParseMeasurement>::parse_measurement(
+            request.measurement.clone(),
+        )?;
+        handle_upload_generic(http_client, vdaf, request, measurement).await?;
+    }, _ => {
+        panic!("Unsupported VDAF: {vdaf_instance:?}")
+    });
     Ok(())
 }
 
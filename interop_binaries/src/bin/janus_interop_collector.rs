@@ -3,15 +3,11 @@ use backoff::ExponentialBackoffBuilder;
 use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use clap::{value_parser, Arg, Command};
 use derivative::Derivative;
-#[cfg(feature = "fpvec_bounded_l2")]
-use fixed::types::extra::{U15, U31};
-#[cfg(feature = "fpvec_bounded_l2")]
-use fixed::{FixedI16, FixedI32};
 use janus_collector::Collector;
-use janus_core::vdaf::new_prio3_sum_vec_field64_multiproof_hmacsha256_aes128;
-#[cfg(feature = "fpvec_bounded_l2")]
-use janus_core::vdaf::Prio3FixedPointBoundedL2VecSumBitSize;
-use janus_core::{auth_tokens::AuthenticationToken, hpke::HpkeKeypair, vdaf::VdafInstance};
+use janus_core::{
+    auth_tokens::AuthenticationToken, hpke::HpkeKeypair, vdaf::VdafInstance,
+    vdaf_dispatch_no_aggregation_parameter,
+};
 use janus_interop_binaries::Keyring;
 use janus_interop_binaries::{
     install_tracing_subscriber,
@@ -22,11 +18,9 @@ use janus_messages::{
     query_type::QueryType, BatchId, Duration, FixedSizeQuery, HpkeConfig, Interval,
     PartialBatchSelector, Query, TaskId, Time,
 };
-#[cfg(feature = "fpvec_bounded_l2")]
-use prio::vdaf::prio3::Prio3FixedPointBoundedL2VecSumMultithreaded;
 use prio::{
     codec::{Decode, Encode},
-    vdaf::{self, prio3::Prio3},
+    vdaf,
 };
 use rand::{distributions::Standard, prelude::Distribution, random};
 use reqwest::Url;
@@ -111,6 +105,48 @@ enum AggregationResult {
     FloatVec(Vec<NumberAsString<f64>>),
 }
 
+/// Conversion from a VDAF's aggregate result to its representation in the interop API.
+trait IntoAggregationResult {
+    fn to_aggregation_result(&self) -> AggregationResult;
+}
+
+impl IntoAggregationResult for u64 {
+    fn to_aggregation_result(&self) -> AggregationResult {
+        AggregationResult::Number(NumberAsString((*self).into()))
+    }
+}
+
+impl IntoAggregationResult for u128 {
+    fn to_aggregation_result(&self) -> AggregationResult {
+        AggregationResult::Number(NumberAsString(*self))
+    }
+}
+
+impl IntoAggregationResult for Vec<u64> {
+    fn to_aggregation_result(&self) -> AggregationResult {
+        AggregationResult::NumberVec(
+            self.iter()
+                .cloned()
+                .map(u128::from)
+                .map(NumberAsString)
+                .collect(),
+        )
+    }
+}
+
+impl IntoAggregationResult for Vec<u128> {
+    fn to_aggregation_result(&self) -> AggregationResult {
+        AggregationResult::NumberVec(self.iter().cloned().map(NumberAsString).collect())
+    }
+}
+
+#[cfg(feature = "fpvec_bounded_l2")]
+impl IntoAggregationResult for Vec<f64> {
+    fn to_aggregation_result(&self) -> AggregationResult {
+        AggregationResult::FloatVec(self.iter().cloned().map(NumberAsString).collect())
+    }
+}
+
 #[derive(Debug, Serialize)]
 struct CollectPollResponse {
     status: &'static str,
@@ -193,11 +229,11 @@ async fn handle_collect_generic<V, Q>(
     vdaf: V,
     agg_param_encoded: &[u8],
     batch_convert_fn: impl Fn(&PartialBatchSelector<Q>) -> Option<BatchId> + Send + 'static,
-    result_convert_fn: impl Fn(&V::AggregateResult) -> AggregationResult + Send + 'static,
 ) -> anyhow::Result<JoinHandle<anyhow::Result<CollectResult>>>
 where
     V: vdaf::Collector + Send + Sync + 'static,
     V::AggregationParam: Send + Sync + 'static,
+    V::AggregateResult: IntoAggregationResult,
     Q: QueryType,
 {
     let collector = Collector::builder(
@@ -233,7 +269,7 @@ where
             report_count: collect_result.report_count(),
             interval_start: interval_start.timestamp(),
             interval_duration: interval_duration.num_seconds(),
-            aggregation_result: result_convert_fn(collect_result.aggregate_result()),
+            aggregation_result: collect_result.aggregate_result().to_aggregation_result(),
         })
     });
     Ok(handle)
@@ -301,134 +337,10 @@ async fn handle_collection_start(
         }
     };
 
-    let vdaf_instance = task_state.vdaf.clone().into();
-    let task_handle = match (query, vdaf_instance) {
-        (ParsedQuery::TimeInterval(batch_interval), VdafInstance::Prio3Count {}) => {
-            let vdaf = Prio3::new_count(2).context("failed to construct Prio3Count VDAF")?;
-            handle_collect_generic(
-                http_client,
-                task_state,
-                Query::new_time_interval(batch_interval),
-                vdaf,
-                &agg_param,
-                |_| None,
-                |result| AggregationResult::Number(NumberAsString((*result).into())),
-            )
-            .await?
-        }
-
-        (ParsedQuery::TimeInterval(batch_interval), VdafInstance::Prio3Sum { bits }) => {
-            let vdaf = Prio3::new_sum(2, bits).context("failed to construct Prio3Sum VDAF")?;
-            handle_collect_generic(
-                http_client,
-                task_state,
-                Query::new_time_interval(batch_interval),
-                vdaf,
-                &agg_param,
-                |_| None,
-                |result| AggregationResult::Number(NumberAsString(*result)),
-            )
-            .await?
-        }
-
-        (
-            ParsedQuery::TimeInterval(batch_interval),
-            VdafInstance::Prio3SumVec {
-                bits,
-                length,
-                chunk_length,
-            },
-        ) => {
-            let vdaf = Prio3::new_sum_vec_multithreaded(2, bits, length, chunk_length)
-                .context("failed to construct Prio3SumVec VDAF")?;
-            handle_collect_generic(
-                http_client,
-                task_state,
-                Query::new_time_interval(batch_interval),
-                vdaf,
-                &agg_param,
-                |_| None,
-                |result| {
-                    let converted = result.iter().cloned().map(NumberAsString).collect();
-                    AggregationResult::NumberVec(converted)
-                },
-            )
-            .await?
-        }
-
-        (
-            ParsedQuery::TimeInterval(batch_interval),
-            VdafInstance::Prio3SumVecField64MultiproofHmacSha256Aes128 {
-                proofs,
-                bits,
-                length,
-                chunk_length,
-            },
-        ) => {
-            let vdaf = new_prio3_sum_vec_field64_multiproof_hmacsha256_aes128(
-                proofs,
-                bits,
-                length,
-                chunk_length,
-            )
-            .context("failed to construct Prio3SumVecField64MultiproofHmacSha256Aes128 VDAF")?;
-            handle_collect_generic(
-                http_client,
-                task_state,
-                Query::new_time_interval(batch_interval),
-                vdaf,
-                &agg_param,
-                |_| None,
-                |result: &Vec<u64>| {
-                    let converted = result
-                        .iter()
-                        .cloned()
-                        .map(u128::from)
-                        .map(NumberAsString)
-                        .collect();
-                    AggregationResult::NumberVec(converted)
-                },
-            )
-            .await?
-        }
-
-        (
-            ParsedQuery::TimeInterval(batch_interval),
-            VdafInstance::Prio3Histogram {
-                length,
-                chunk_length,
-            },
-        ) => {
-            let vdaf = Prio3::new_histogram(2, length, chunk_length)
-                .context("failed to construct Prio3Histogram VDAF")?;
-            handle_collect_generic(
-                http_client,
-                task_state,
-                Query::new_time_interval(batch_interval),
-                vdaf,
-                &agg_param,
-                |_| None,
-                |result| {
-                    let converted = result.iter().cloned().map(NumberAsString).collect();
-                    AggregationResult::NumberVec(converted)
-                },
-            )
-            .await?
-        }
-
-        #[cfg(feature = "fpvec_bounded_l2")]
-        (
-            ParsedQuery::TimeInterval(batch_interval),
-            janus_core::vdaf::VdafInstance::Prio3FixedPointBoundedL2VecSum {
-                bitsize,
-                dp_strategy: _,
-                length,
-            },
-        ) => match bitsize {
-            Prio3FixedPointBoundedL2VecSumBitSize::BitSize16 => {
-                let vdaf: Prio3FixedPointBoundedL2VecSumMultithreaded<FixedI16<U15>> =
-                    Prio3::new_fixedpoint_boundedl2_vec_sum_multithreaded(2, length)
-                        .context("failed to construct Prio3FixedPoint16BitBoundedL2VecSum VDAF")?;
+    let vdaf_instance: VdafInstance = task_state.vdaf.clone().into();
+    let task_handle = match query {
+        ParsedQuery::TimeInterval(batch_interval) => {
+            vdaf_dispatch_no_aggregation_parameter!(&vdaf_instance, (vdaf, _VdafType, _VERIFY_KEY_LEN) => {
                 handle_collect_generic(
                     http_client,
                     task_state,
@@ -436,60 +348,15 @@ async fn handle_collection_start(
                     vdaf,
                     &agg_param,
                     |_| None,
-                    |result| {
-                        let converted = result.iter().cloned().map(NumberAsString).collect();
-                        AggregationResult::FloatVec(converted)
-                    },
                 )
                 .await?
-            }
-            Prio3FixedPointBoundedL2VecSumBitSize::BitSize32 => {
-                let vdaf: Prio3FixedPointBoundedL2VecSumMultithreaded<FixedI32<U31>> =
-                    Prio3::new_fixedpoint_boundedl2_vec_sum_multithreaded(2, length)
-                        .context("failed to construct Prio3FixedPoint32BitBoundedL2VecSum VDAF")?;
-                handle_collect_generic(
-                    http_client,
-                    task_state,
-                    Query::new_time_interval(batch_interval),
-                    vdaf,
-                    &agg_param,
-                    |_| None,
-                    |result| {
-                        let converted = result.iter().cloned().map(NumberAsString).collect();
-                        AggregationResult::FloatVec(converted)
-                    },
-                )
-                .await?
-            }
-        },
-
-        (ParsedQuery::FixedSize(fixed_size_query), VdafInstance::Prio3Count {}) => {
-            let vdaf = Prio3::new_count(2).context("failed to construct Prio3Count VDAF")?;
-            handle_collect_generic(
-                http_client,
-                task_state,
-                Query::new_fixed_size(fixed_size_query),
-                vdaf,
-                &agg_param,
-                |selector| Some(*selector.batch_id()),
-                |result| AggregationResult::Number(NumberAsString((*result).into())),
-            )
-            .await?
+            }, _ => {
+                panic!("Unsupported VDAF: {vdaf_instance:?}")
+            })
         }
 
-        #[cfg(feature = "fpvec_bounded_l2")]
-        (
-            ParsedQuery::FixedSize(fixed_size_query),
-            janus_core::vdaf::VdafInstance::Prio3FixedPointBoundedL2VecSum {
-                bitsize,
-                dp_strategy: _,
-                length,
-            },
-        ) => match bitsize {
-            Prio3FixedPointBoundedL2VecSumBitSize::BitSize16 => {
-                let vdaf: Prio3FixedPointBoundedL2VecSumMultithreaded<FixedI16<U15>> =
-                    Prio3::new_fixedpoint_boundedl2_vec_sum_multithreaded(2, length)
-                        .context("failed to construct Prio3FixedPoint16BitBoundedL2VecSum VDAF")?;
+        ParsedQuery::FixedSize(fixed_size_query) => {
+            vdaf_dispatch_no_aggregation_parameter!(&vdaf_instance, (vdaf, _VdafType, _VERIFY_KEY_LEN) => {
                 handle_collect_generic(
                     http_client,
                     task_state,
@@ -497,134 +364,11 @@ async fn handle_collection_start(
                     vdaf,
                     &agg_param,
                     |selector| Some(*selector.batch_id()),
-                    |result| {
-                        let converted = result.iter().cloned().map(NumberAsString).collect();
-                        AggregationResult::FloatVec(converted)
-                    },
                 )
                 .await?
-            }
-            Prio3FixedPointBoundedL2VecSumBitSize::BitSize32 => {
-                let vdaf: Prio3FixedPointBoundedL2VecSumMultithreaded<FixedI32<U31>> =
-                    Prio3::new_fixedpoint_boundedl2_vec_sum_multithreaded(2, length)
-                        .context("failed to construct Prio3FixedPoint32BitBoundedL2VecSum VDAF")?;
-                handle_collect_generic(
-                    http_client,
-                    task_state,
-                    Query::new_fixed_size(fixed_size_query),
-                    vdaf,
-                    &agg_param,
-                    |selector| Some(*selector.batch_id()),
-                    |result| {
-                        let converted = result.iter().cloned().map(NumberAsString).collect();
-                        AggregationResult::FloatVec(converted)
-                    },
-                )
-                .await?
-            }
-        },
-
-        (ParsedQuery::FixedSize(fixed_size_query), VdafInstance::Prio3Sum { bits }) => {
-            let vdaf = Prio3::new_sum(2, bits).context("failed to construct Prio3Sum VDAF")?;
-            handle_collect_generic(
-                http_client,
-                task_state,
-                Query::new_fixed_size(fixed_size_query),
-                vdaf,
-                &agg_param,
-                |selector| Some(*selector.batch_id()),
-                |result| AggregationResult::Number(NumberAsString(*result)),
-            )
-            .await?
-        }
-
-        (
-            ParsedQuery::FixedSize(fixed_size_query),
-            VdafInstance::Prio3SumVec {
-                bits,
-                length,
-                chunk_length,
-            },
-        ) => {
-            let vdaf = Prio3::new_sum_vec_multithreaded(2, bits, length, chunk_length)
-                .context("failed to construct Prio3SumVec VDAF")?;
-            handle_collect_generic(
-                http_client,
-                task_state,
-                Query::new_fixed_size(fixed_size_query),
-                vdaf,
-                &agg_param,
-                |selector| Some(*selector.batch_id()),
-                |result| {
-                    let converted = result.iter().cloned().map(NumberAsString).collect();
-                    AggregationResult::NumberVec(converted)
-                },
-            )
-            .await?
-        }
-
-        (
-            ParsedQuery::FixedSize(fixed_size_query),
-            VdafInstance::Prio3SumVecField64MultiproofHmacSha256Aes128 {
-                proofs,
-                bits,
-                length,
-                chunk_length,
-            },
-        ) => {
-            let vdaf = new_prio3_sum_vec_field64_multiproof_hmacsha256_aes128(
-                proofs,
-                bits,
-                length,
-                chunk_length,
-            )
-            .context("failed to construct Prio3SumVecField64MultiproofHmacSha256Aes128 VDAF")?;
-            handle_collect_generic(
-                http_client,
-                task_state,
-                Query::new_fixed_size(fixed_size_query),
-                vdaf,
-                &agg_param,
-                |selector| Some(*selector.batch_id()),
-                |result: &Vec<u64>| {
-                    let converted = result
-                        .iter()
-                        .cloned()
-                        .map(u128::from)
-                        .map(NumberAsString)
-                        .collect();
-                    AggregationResult::NumberVec(converted)
-                },
-            )
-            .await?
-        }
-
-        (
-            ParsedQuery::FixedSize(fixed_size_query),
-            VdafInstance::Prio3Histogram {
-                length,
-                chunk_length,
-            },
-        ) => {
-            let vdaf = Prio3::new_histogram(2, length, chunk_length)
-                .context("failed to construct Prio3Histogram VDAF")?;
-            handle_collect_generic(
-                http_client,
-                task_state,
-                Query::new_fixed_size(fixed_size_query),
-                vdaf,
-                &agg_param,
-                |selector| Some(*selector.batch_id()),
-                |result| {
-                    let converted = result.iter().cloned().map(NumberAsString).collect();
-                    AggregationResult::NumberVec(converted)
-                },
-            )
-            .await?
-        }
-
-        (_, vdaf_instance) => {
-            panic!("Unsupported VDAF: {vdaf_instance:?}")
+            }, _ => {
+                panic!("Unsupported VDAF: {vdaf_instance:?}")
+            })
         }
     };
 
@@ -126,6 +126,24 @@ pub enum VdafObject {
         length: NumberAsString<usize>,
         chunk_length: NumberAsString<usize>,
     },
+    Prio3CountMultiproof {
+        proofs: NumberAsString<u8>,
+    },
+    Prio3SumMultiproof {
+        proofs: NumberAsString<u8>,
+        bits: NumberAsString<usize>,
+    },
+    Prio3SumVecMultiproof {
+        proofs: NumberAsString<u8>,
+        bits: NumberAsString<usize>,
+        length: NumberAsString<usize>,
+        chunk_length: NumberAsString<usize>,
+    },
+    Prio3HistogramMultiproof {
+        proofs: NumberAsString<u8>,
+        length: NumberAsString<usize>,
+        chunk_length: NumberAsString<usize>,
+    },
     #[cfg(feature = "fpvec_bounded_l2")]
     Prio3FixedPointBoundedL2VecSum {
         bitsize: Prio3FixedPointBoundedL2VecSumBitSize,
@@ -174,6 +192,37 @@ impl From<VdafInstance> for VdafObject {
                 chunk_length: NumberAsString(chunk_length),
             },
 
+            VdafInstance::Prio3CountMultiproof { proofs } => VdafObject::Prio3CountMultiproof {
+                proofs: NumberAsString(proofs),
+            },
+
+            VdafInstance::Prio3SumMultiproof { proofs, bits } => VdafObject::Prio3SumMultiproof {
+                proofs: NumberAsString(proofs),
+                bits: NumberAsString(bits),
+            },
+
+            VdafInstance::Prio3SumVecMultiproof {
+                proofs,
+                bits,
+                length,
+                chunk_length,
+            } => VdafObject::Prio3SumVecMultiproof {
+                proofs: NumberAsString(proofs),
+                bits: NumberAsString(bits),
+                length: NumberAsString(length),
+                chunk_length: NumberAsString(chunk_length),
+            },
+
+            VdafInstance::Prio3HistogramMultiproof {
+                proofs,
+                length,
+                chunk_length,
+            } => VdafObject::Prio3HistogramMultiproof {
+                proofs: NumberAsString(proofs),
+                length: NumberAsString(length),
+                chunk_length: NumberAsString(chunk_length),
+            },
+
             #[cfg(feature = "fpvec_bounded_l2")]
             VdafInstance::Prio3FixedPointBoundedL2VecSum {
                 bitsize,
@@ -227,6 +276,37 @@ impl From<VdafObject> for VdafInstance {
                 chunk_length: chunk_length.0,
             },
 
+            VdafObject::Prio3CountMultiproof { proofs } => {
+                VdafInstance::Prio3CountMultiproof { proofs: proofs.0 }
+            }
+
+            VdafObject::Prio3SumMultiproof { proofs, bits } => VdafInstance::Prio3SumMultiproof {
+                proofs: proofs.0,
+                bits: bits.0,
+            },
+
+            VdafObject::Prio3SumVecMultiproof {
+                proofs,
+                bits,
+                length,
+                chunk_length,
+            } => VdafInstance::Prio3SumVecMultiproof {
+                proofs: proofs.0,
+                bits: bits.0,
+                length: length.0,
+                chunk_length: chunk_length.0,
+            },
+
+            VdafObject::Prio3HistogramMultiproof {
+                proofs,
+                length,
+                chunk_length,
+            } => VdafInstance::Prio3HistogramMultiproof {
+                proofs: proofs.0,
+                length: length.0,
+                chunk_length: chunk_length.0,
+            },
+
             #[cfg(feature = "fpvec_bounded_l2")]
             VdafObject::Prio3FixedPointBoundedL2VecSum {
                 bitsize,
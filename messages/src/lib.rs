@@ -23,6 +23,7 @@ use serde::{
 use std::{
     fmt::{self, Debug, Display, Formatter},
     io::{Cursor, Read},
+    marker::PhantomData,
     num::TryFromIntError,
     str,
     str::FromStr,
@@ -30,6 +31,8 @@ use std::{
 };
 
 pub mod problem_type;
+#[cfg(test)]
+mod proptests;
 pub mod taskprov;
 pub use prio::codec;
 
@@ -46,6 +49,21 @@ pub enum Error {
     Base64Decode(#[from] base64::DecodeError),
 }
 
+/// Decodes a base64url-encoded identifier of `LEN` bytes. Inputs of the wrong length are rejected
+/// before any decoding is done, since identifiers are often taken from untrusted request paths.
+fn decode_base64url_id<const LEN: usize>(
+    encoded: &str,
+    length_error: &'static str,
+) -> Result<[u8; LEN], Error> {
+    if base64::encoded_len(LEN, false) != Some(encoded.len()) {
+        return Err(Error::InvalidParameter(length_error));
+    }
+    URL_SAFE_NO_PAD
+        .decode(encoded)?
+        .try_into()
+        .map_err(|_| Error::InvalidParameter(length_error))
+}
+
 /// Wire-representation of an ASCII-encoded URL with minimum length 1 and maximum
 /// length 2^16 - 1.
 #[derive(Clone, PartialEq, Eq)]
@@ -306,7 +324,10 @@ impl FromStr for BatchId {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Self::try_from(URL_SAFE_NO_PAD.decode(s)?.as_ref())
+        Ok(Self(decode_base64url_id(
+            s,
+            "byte slice has incorrect length for BatchId",
+        )?))
     }
 }
 
@@ -427,7 +448,10 @@ impl FromStr for ReportId {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Self::try_from(URL_SAFE_NO_PAD.decode(s)?.as_ref())
+        Ok(Self(decode_base64url_id(
+            s,
+            "byte slice has incorrect length for ReportId",
+        )?))
     }
 }
 
@@ -701,7 +725,10 @@ impl FromStr for TaskId {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Self::try_from(URL_SAFE_NO_PAD.decode(s)?.as_ref())
+        Ok(Self(decode_base64url_id(
+            s,
+            "byte slice has incorrect length for TaskId",
+        )?))
     }
 }
 
@@ -918,17 +945,20 @@ impl Decode for Extension {
 }
 
 /// DAP protocol message representing the type of an extension included in a client report.
-#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq, TryFromPrimitive)]
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq, FromPrimitive, IntoPrimitive)]
 #[repr(u16)]
 #[non_exhaustive]
 pub enum ExtensionType {
     Tbd = 0,
     Taskprov = 0xFF00,
+    /// Unrecognized extension types.
+    #[num_enum(catch_all)]
+    Unknown(u16),
 }
 
 impl Encode for ExtensionType {
     fn encode(&self, bytes: &mut Vec<u8>) -> Result<(), CodecError> {
-        (*self as u16).encode(bytes)
+        u16::from(*self).encode(bytes)
     }
 
     fn encoded_len(&self) -> Option<usize> {
@@ -939,9 +969,7 @@ impl Encode for ExtensionType {
 impl Decode for ExtensionType {
     fn decode(bytes: &mut Cursor<&[u8]>) -> Result<Self, CodecError> {
         let val = u16::decode(bytes)?;
-        Self::try_from(val).map_err(|_| {
-            CodecError::Other(anyhow!("unexpected ExtensionType value {}", val).into())
-        })
+        Ok(Self::from(val))
     }
 }
 
@@ -1694,7 +1722,10 @@ impl FromStr for CollectionJobId {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Self::try_from(URL_SAFE_NO_PAD.decode(s)?.as_ref())
+        Ok(Self(decode_base64url_id(
+            s,
+            "byte slice has incorrect length for CollectionId",
+        )?))
     }
 }
 
@@ -2449,7 +2480,10 @@ impl FromStr for AggregationJobId {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Self::try_from(URL_SAFE_NO_PAD.decode(s)?.as_ref())
+        Ok(Self(decode_base64url_id(
+            s,
+            "byte slice has incorrect length for AggregationJobId",
+        )?))
     }
 }
 
@@ -2552,6 +2586,58 @@ impl<Q: QueryType> Decode for AggregationJobInitializeReq<Q> {
     }
 }
 
+impl<Q: QueryType> AggregationJobInitializeReq<Q> {
+    /// Decodes an aggregation job initialization request without decoding its preparation
+    /// initialization messages up front. Instead, each message is decoded as the request's
+    /// [`StreamingAggregationJobInitializeReq::prepare_inits`] are iterated over, so that the
+    /// decoded messages need not all be held in memory at once.
+    pub fn decode_streaming(
+        bytes: &[u8],
+    ) -> Result<StreamingAggregationJobInitializeReq<'_, Q>, CodecError> {
+        let mut cursor = Cursor::new(bytes);
+        let aggregation_parameter = decode_u32_items(&(), &mut cursor)?;
+        let partial_batch_selector = PartialBatchSelector::decode(&mut cursor)?;
+        let prepare_inits = U32ItemsDecoder::new(&mut cursor)?;
+        check_no_bytes_left_over(&cursor)?;
+
+        Ok(StreamingAggregationJobInitializeReq {
+            aggregation_parameter,
+            partial_batch_selector,
+            prepare_inits,
+        })
+    }
+}
+
+/// An aggregation job initialization request whose preparation initialization messages are
+/// decoded as they are iterated over. See [`AggregationJobInitializeReq::decode_streaming`].
+#[derive(Clone, Derivative)]
+#[derivative(Debug)]
+pub struct StreamingAggregationJobInitializeReq<'a, Q: QueryType> {
+    #[derivative(Debug = "ignore")]
+    aggregation_parameter: Vec<u8>,
+    partial_batch_selector: PartialBatchSelector<Q>,
+    prepare_inits: U32ItemsDecoder<'a, PrepareInit>,
+}
+
+impl<'a, Q: QueryType> StreamingAggregationJobInitializeReq<'a, Q> {
+    /// Gets the aggregation parameter associated with this aggregate initialization request.
+    pub fn aggregation_parameter(&self) -> &[u8] {
+        &self.aggregation_parameter
+    }
+
+    /// Gets the partial batch selector associated with this aggregate initialization request.
+    pub fn batch_selector(&self) -> &PartialBatchSelector<Q> {
+        &self.partial_batch_selector
+    }
+
+    /// Returns an iterator which decodes the preparation initialization messages associated with
+    /// this aggregate initialization request, in order. Each call returns a new iterator starting
+    /// from the first message.
+    pub fn prepare_inits(&self) -> U32ItemsDecoder<'a, PrepareInit> {
+        self.prepare_inits.clone()
+    }
+}
+
 /// Type representing the step of an aggregation job.
 #[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct AggregationJobStep(u16);
@@ -2706,6 +2792,95 @@ impl Decode for AggregationJobResp {
     }
 }
 
+impl AggregationJobResp {
+    /// Decodes an aggregation job response into an iterator which decodes each of its prepare
+    /// responses as it is advanced, so that the decoded responses need not all be held in memory
+    /// at once.
+    pub fn decode_streaming(bytes: &[u8]) -> Result<U32ItemsDecoder<'_, PrepareResp>, CodecError> {
+        let mut cursor = Cursor::new(bytes);
+        let prepare_resps = U32ItemsDecoder::new(&mut cursor)?;
+        check_no_bytes_left_over(&cursor)?;
+        Ok(prepare_resps)
+    }
+}
+
+/// An iterator over a vector of items encoded with a 32-bit length prefix, which decodes each item
+/// as it is advanced rather than decoding the whole vector up front.
+///
+/// The length prefix is checked when the iterator is constructed, but the items themselves are
+/// not, so a malformed item is only reported when the iterator reaches it. The iterator yields no
+/// further items after an error.
+pub struct U32ItemsDecoder<'a, T> {
+    cursor: Cursor<&'a [u8]>,
+    _phantom: PhantomData<fn() -> T>,
+}
+
+impl<'a, T: Decode> U32ItemsDecoder<'a, T> {
+    /// Reads the length prefix of an encoded vector from `bytes`, and advances `bytes` past the
+    /// vector's items, returning an iterator over them.
+    fn new(bytes: &mut Cursor<&'a [u8]>) -> Result<Self, CodecError> {
+        let length =
+            usize::try_from(u32::decode(bytes)?).map_err(|_| CodecError::UnexpectedValue)?;
+        let start = usize::try_from(bytes.position()).map_err(|_| CodecError::UnexpectedValue)?;
+        let items = bytes
+            .get_ref()
+            .get(start..)
+            .and_then(|remaining| remaining.get(..length))
+            .ok_or(CodecError::LengthPrefixTooBig(length))?;
+        bytes.set_position(bytes.position() + u64::try_from(length).unwrap());
+
+        Ok(Self {
+            cursor: Cursor::new(items),
+            _phantom: PhantomData,
+        })
+    }
+}
+
+impl<'a, T: Decode> Iterator for U32ItemsDecoder<'a, T> {
+    type Item = Result<T, CodecError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let end = self.cursor.get_ref().len() as u64;
+        if self.cursor.position() >= end {
+            return None;
+        }
+        let result = T::decode(&mut self.cursor);
+        if result.is_err() {
+            self.cursor.set_position(end);
+        }
+        Some(result)
+    }
+}
+
+impl<'a, T> Clone for U32ItemsDecoder<'a, T> {
+    fn clone(&self) -> Self {
+        Self {
+            cursor: self.cursor.clone(),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<'a, T> Debug for U32ItemsDecoder<'a, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("U32ItemsDecoder")
+            .field("len", &self.cursor.get_ref().len())
+            .field("position", &self.cursor.position())
+            .finish()
+    }
+}
+
+/// Returns an error if any bytes remain to be decoded.
+fn check_no_bytes_left_over(bytes: &Cursor<&[u8]>) -> Result<(), CodecError> {
+    let remaining = (bytes.get_ref().len() as u64).saturating_sub(bytes.position());
+    if remaining > 0 {
+        return Err(CodecError::BytesLeftOver(
+            usize::try_from(remaining).unwrap(),
+        ));
+    }
+    Ok(())
+}
+
 /// DAP protocol message identifying a batch of interest.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct BatchSelector<Q: QueryType> {
@@ -2938,13 +3113,14 @@ encoded:\t\t{encoded_val:02x?}\nexpected encoding:\t{encoding:02x?}"
 mod tests {
     use crate::{
         query_type, roundtrip_encoding, AggregateShare, AggregateShareAad, AggregateShareReq,
-        AggregationJobContinueReq, AggregationJobInitializeReq, AggregationJobResp,
-        AggregationJobStep, BatchId, BatchSelector, Collection, CollectionReq, Duration, Extension,
-        ExtensionType, FixedSize, FixedSizeQuery, HpkeAeadId, HpkeCiphertext, HpkeConfig,
-        HpkeConfigId, HpkeConfigList, HpkeKdfId, HpkeKemId, HpkePublicKey, InputShareAad, Interval,
-        PartialBatchSelector, PlaintextInputShare, PrepareContinue, PrepareError, PrepareInit,
-        PrepareResp, PrepareStepResult, Query, Report, ReportId, ReportIdChecksum, ReportMetadata,
-        ReportShare, Role, TaskId, Time, TimeInterval, Url,
+        AggregationJobContinueReq, AggregationJobId, AggregationJobInitializeReq,
+        AggregationJobResp, AggregationJobStep, BatchId, BatchSelector, Collection,
+        CollectionJobId, CollectionReq, Duration, Error, Extension, ExtensionType, FixedSize,
+        FixedSizeQuery, HpkeAeadId, HpkeCiphertext, HpkeConfig, HpkeConfigId, HpkeConfigList,
+        HpkeKdfId, HpkeKemId, HpkePublicKey, InputShareAad, Interval, PartialBatchSelector,
+        PlaintextInputShare, PrepareContinue, PrepareError, PrepareInit, PrepareResp,
+        PrepareStepResult, Query, Report, ReportId, ReportIdChecksum, ReportMetadata, ReportShare,
+        Role, TaskId, Time, TimeInterval, Url,
     };
     use assert_matches::assert_matches;
     use prio::{
@@ -3192,7 +3368,11 @@ mod tests {
 
     #[test]
     fn roundtrip_extension_type() {
-        roundtrip_encoding(&[(ExtensionType::Tbd, "0000")])
+        roundtrip_encoding(&[
+            (ExtensionType::Tbd, "0000"),
+            (ExtensionType::Taskprov, "FF00"),
+            (ExtensionType::Unknown(0x1234), "1234"),
+        ])
     }
 
     #[test]
@@ -4768,6 +4948,141 @@ mod tests {
         )])
     }
 
+    #[test]
+    fn decode_streaming_aggregation_job_initialize_req() {
+        let req = AggregationJobInitializeReq::new(
+            Vec::from("012345"),
+            PartialBatchSelector::new_fixed_size(BatchId::from([3u8; 32])),
+            Vec::from([
+                PrepareInit::new(
+                    ReportShare::new(
+                        ReportMetadata::new(
+                            ReportId::from([1u8; 16]),
+                            Time::from_seconds_since_epoch(54321),
+                        ),
+                        Vec::from("0123"),
+                        HpkeCiphertext::new(
+                            HpkeConfigId::from(42),
+                            Vec::from("012345"),
+                            Vec::from("543210"),
+                        ),
+                    ),
+                    PingPongMessage::Initialize {
+                        prep_share: Vec::from("012345"),
+                    },
+                ),
+                PrepareInit::new(
+                    ReportShare::new(
+                        ReportMetadata::new(
+                            ReportId::from([2u8; 16]),
+                            Time::from_seconds_since_epoch(73542),
+                        ),
+                        Vec::new(),
+                        HpkeCiphertext::new(
+                            HpkeConfigId::from(13),
+                            Vec::from("abce"),
+                            Vec::from("abfd"),
+                        ),
+                    ),
+                    PingPongMessage::Finish {
+                        prep_msg: Vec::new(),
+                    },
+                ),
+            ]),
+        );
+        let encoded = req.get_encoded().unwrap();
+
+        let streaming_req =
+            AggregationJobInitializeReq::<FixedSize>::decode_streaming(&encoded).unwrap();
+        assert_eq!(
+            streaming_req.aggregation_parameter(),
+            req.aggregation_parameter()
+        );
+        assert_eq!(streaming_req.batch_selector(), req.batch_selector());
+        // Each iterator starts from the first preparation initialization message.
+        for _ in 0..2 {
+            assert_eq!(
+                streaming_req
+                    .prepare_inits()
+                    .collect::<Result<Vec<_>, _>>()
+                    .unwrap(),
+                req.prepare_inits()
+            );
+        }
+
+        // Trailing bytes are rejected up front.
+        let mut trailing = encoded.clone();
+        trailing.push(0);
+        assert_matches!(
+            AggregationJobInitializeReq::<FixedSize>::decode_streaming(&trailing),
+            Err(CodecError::BytesLeftOver(1))
+        );
+
+        // A length prefix which overruns the message is rejected up front.
+        assert_matches!(
+            AggregationJobInitializeReq::<FixedSize>::decode_streaming(
+                &encoded[..encoded.len() - 1]
+            ),
+            Err(CodecError::LengthPrefixTooBig(_))
+        );
+
+        // A malformed preparation initialization message is reported when it is reached, after
+        // which the iterator ends.
+        let prepare_inits_len = req.prepare_inits()[0].encoded_len().unwrap()
+            + req.prepare_inits()[1].encoded_len().unwrap();
+        let mut malformed = encoded[..encoded.len() - prepare_inits_len - 4].to_vec();
+        let first_prepare_init = req.prepare_inits()[0].get_encoded().unwrap();
+        malformed.extend_from_slice(
+            &u32::try_from(first_prepare_init.len() + 1)
+                .unwrap()
+                .to_be_bytes(),
+        );
+        malformed.extend_from_slice(&first_prepare_init);
+        malformed.push(0xFF);
+        let mut prepare_inits =
+            AggregationJobInitializeReq::<FixedSize>::decode_streaming(&malformed)
+                .unwrap()
+                .prepare_inits();
+        assert_eq!(
+            prepare_inits.next().unwrap().unwrap(),
+            req.prepare_inits()[0]
+        );
+        assert_matches!(prepare_inits.next(), Some(Err(_)));
+        assert_matches!(prepare_inits.next(), None);
+    }
+
+    #[test]
+    fn decode_streaming_aggregation_job_resp() {
+        let resp = AggregationJobResp::new(Vec::from([
+            PrepareResp::new(
+                ReportId::from([1u8; 16]),
+                PrepareStepResult::Continue {
+                    message: PingPongMessage::Continue {
+                        prep_msg: Vec::from("01234"),
+                        prep_share: Vec::from("56789"),
+                    },
+                },
+            ),
+            PrepareResp::new(ReportId::from([2u8; 16]), PrepareStepResult::Finished),
+        ]));
+        let encoded = resp.get_encoded().unwrap();
+
+        assert_eq!(
+            AggregationJobResp::decode_streaming(&encoded)
+                .unwrap()
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap(),
+            resp.prepare_resps()
+        );
+
+        let mut trailing = encoded;
+        trailing.push(0);
+        assert_matches!(
+            AggregationJobResp::decode_streaming(&trailing),
+            Err(CodecError::BytesLeftOver(1))
+        );
+    }
+
     #[test]
     fn roundtrip_batch_selector() {
         // TimeInterval.
@@ -5112,6 +5427,47 @@ mod tests {
         );
     }
 
+    #[test]
+    fn id_from_str() {
+        assert_eq!(
+            "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA"
+                .parse::<TaskId>()
+                .unwrap(),
+            TaskId::from([0; 32])
+        );
+        assert_eq!(
+            "AQEBAQEBAQEBAQEBAQEBAQ"
+                .parse::<AggregationJobId>()
+                .unwrap(),
+            AggregationJobId::from([1; 16])
+        );
+        assert_matches!(
+            "/AAAAAAAAAAAAAAAAAAAAA".parse::<CollectionJobId>(),
+            Err(Error::Base64Decode(_))
+        );
+
+        // Inputs of the wrong length are rejected, whether or not they are valid base64url.
+        for input in [
+            "",
+            "AAAAAAAAAAAAAAAAAAAAAA==",
+            "AAAAAAAAAAAAAAAAAAAAAAA",
+            "AAAAAAAAAAAAAAAAAAAAA",
+            &"/".repeat(4096),
+        ] {
+            assert_matches!(
+                input.parse::<CollectionJobId>(),
+                Err(Error::InvalidParameter(_))
+            );
+            assert_matches!(
+                input.parse::<AggregationJobId>(),
+                Err(Error::InvalidParameter(_))
+            );
+            assert_matches!(input.parse::<ReportId>(), Err(Error::InvalidParameter(_)));
+            assert_matches!(input.parse::<BatchId>(), Err(Error::InvalidParameter(_)));
+            assert_matches!(input.parse::<TaskId>(), Err(Error::InvalidParameter(_)));
+        }
+    }
+
     #[test]
     fn hpke_public_key_serde() {
         assert_tokens(
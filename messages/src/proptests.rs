@@ -0,0 +1,373 @@
+//! Property-based tests checking that arbitrary DAP messages survive an encode/decode roundtrip,
+//! and that truncated or overlong encodings are rejected.
+
+use crate::{
+    query_type::{FixedSize, QueryType, TimeInterval},
+    AggregateShare, AggregateShareAad, AggregateShareReq, AggregationJobContinueReq,
+    AggregationJobInitializeReq, AggregationJobResp, AggregationJobStep, BatchId, BatchSelector,
+    Collection, CollectionReq, Duration, Extension, ExtensionType, FixedSizeQuery, HpkeAeadId,
+    HpkeCiphertext, HpkeConfig, HpkeConfigId, HpkeConfigList, HpkeKdfId, HpkeKemId, HpkePublicKey,
+    InputShareAad, Interval, PartialBatchSelector, PlaintextInputShare, PrepareContinue,
+    PrepareError, PrepareInit, PrepareResp, PrepareStepResult, Query, Report, ReportId,
+    ReportIdChecksum, ReportMetadata, ReportShare, TaskId, Time,
+};
+use prio::{
+    codec::{Decode, Encode},
+    topology::ping_pong::PingPongMessage,
+};
+use proptest::{collection::vec, prelude::*};
+use std::fmt::Debug;
+
+/// Checks that `message` roundtrips through its encoding, that its encoded length is reported
+/// correctly, and that every strict prefix of its encoding, as well as its encoding followed by a
+/// trailing byte, fails to decode.
+fn check_encoding<M>(message: &M) -> Result<(), TestCaseError>
+where
+    M: Encode + Decode + PartialEq + Debug,
+{
+    let encoded = message.get_encoded().unwrap();
+    prop_assert_eq!(&M::get_decoded(&encoded).unwrap(), message);
+    prop_assert_eq!(message.encoded_len(), Some(encoded.len()));
+
+    for len in 0..encoded.len() {
+        prop_assert!(
+            M::get_decoded(&encoded[..len]).is_err(),
+            "truncated encoding of length {} decoded successfully",
+            len
+        );
+    }
+
+    let mut overlong = encoded;
+    overlong.push(0);
+    prop_assert!(M::get_decoded(&overlong).is_err());
+    Ok(())
+}
+
+fn bytes() -> impl Strategy<Value = Vec<u8>> {
+    vec(any::<u8>(), 0..32)
+}
+
+fn time() -> impl Strategy<Value = Time> {
+    any::<u32>().prop_map(|seconds| Time::from_seconds_since_epoch(seconds.into()))
+}
+
+fn interval() -> impl Strategy<Value = Interval> {
+    (time(), any::<u32>()).prop_map(|(start, duration)| {
+        Interval::new(start, Duration::from_seconds(duration.into())).unwrap()
+    })
+}
+
+fn task_id() -> impl Strategy<Value = TaskId> {
+    any::<[u8; TaskId::LEN]>().prop_map(TaskId::from)
+}
+
+fn batch_id() -> impl Strategy<Value = BatchId> {
+    any::<[u8; BatchId::LEN]>().prop_map(BatchId::from)
+}
+
+fn report_id() -> impl Strategy<Value = ReportId> {
+    any::<[u8; ReportId::LEN]>().prop_map(ReportId::from)
+}
+
+fn report_id_checksum() -> impl Strategy<Value = ReportIdChecksum> {
+    any::<[u8; ReportIdChecksum::LEN]>().prop_map(ReportIdChecksum::from)
+}
+
+fn report_metadata() -> impl Strategy<Value = ReportMetadata> {
+    (report_id(), time()).prop_map(|(report_id, time)| ReportMetadata::new(report_id, time))
+}
+
+fn hpke_ciphertext() -> impl Strategy<Value = HpkeCiphertext> {
+    (any::<u8>(), bytes(), bytes()).prop_map(|(config_id, encapsulated_key, payload)| {
+        HpkeCiphertext::new(HpkeConfigId::from(config_id), encapsulated_key, payload)
+    })
+}
+
+fn hpke_config() -> impl Strategy<Value = HpkeConfig> {
+    (
+        any::<u8>(),
+        any::<u16>(),
+        any::<u16>(),
+        any::<u16>(),
+        bytes(),
+    )
+        .prop_map(|(id, kem_id, kdf_id, aead_id, public_key)| {
+            HpkeConfig::new(
+                HpkeConfigId::from(id),
+                HpkeKemId::from(kem_id),
+                HpkeKdfId::from(kdf_id),
+                HpkeAeadId::from(aead_id),
+                HpkePublicKey::from(public_key),
+            )
+        })
+}
+
+fn extension() -> impl Strategy<Value = Extension> {
+    (
+        prop_oneof![Just(ExtensionType::Tbd), Just(ExtensionType::Taskprov)],
+        bytes(),
+    )
+        .prop_map(|(extension_type, extension_data)| Extension::new(extension_type, extension_data))
+}
+
+fn ping_pong_message() -> impl Strategy<Value = PingPongMessage> {
+    prop_oneof![
+        bytes().prop_map(|prep_share| PingPongMessage::Initialize { prep_share }),
+        (bytes(), bytes()).prop_map(|(prep_msg, prep_share)| PingPongMessage::Continue {
+            prep_msg,
+            prep_share
+        }),
+        bytes().prop_map(|prep_msg| PingPongMessage::Finish { prep_msg }),
+    ]
+}
+
+fn prepare_step_result() -> impl Strategy<Value = PrepareStepResult> {
+    prop_oneof![
+        ping_pong_message().prop_map(|message| PrepareStepResult::Continue { message }),
+        Just(PrepareStepResult::Finished),
+        (0u8..=9)
+            .prop_map(|error| PrepareStepResult::Reject(PrepareError::try_from(error).unwrap())),
+    ]
+}
+
+fn report_share() -> impl Strategy<Value = ReportShare> {
+    (report_metadata(), bytes(), hpke_ciphertext()).prop_map(
+        |(metadata, public_share, encrypted_input_share)| {
+            ReportShare::new(metadata, public_share, encrypted_input_share)
+        },
+    )
+}
+
+fn prepare_init() -> impl Strategy<Value = PrepareInit> {
+    (report_share(), ping_pong_message())
+        .prop_map(|(report_share, message)| PrepareInit::new(report_share, message))
+}
+
+fn time_interval_partial_batch_selector(
+) -> impl Strategy<Value = PartialBatchSelector<TimeInterval>> {
+    Just(PartialBatchSelector::new_time_interval())
+}
+
+fn fixed_size_partial_batch_selector() -> impl Strategy<Value = PartialBatchSelector<FixedSize>> {
+    batch_id().prop_map(PartialBatchSelector::new_fixed_size)
+}
+
+fn time_interval_batch_selector() -> impl Strategy<Value = BatchSelector<TimeInterval>> {
+    interval().prop_map(BatchSelector::new_time_interval)
+}
+
+fn fixed_size_batch_selector() -> impl Strategy<Value = BatchSelector<FixedSize>> {
+    batch_id().prop_map(BatchSelector::new_fixed_size)
+}
+
+fn time_interval_query() -> impl Strategy<Value = Query<TimeInterval>> {
+    interval().prop_map(Query::new_time_interval)
+}
+
+fn fixed_size_query() -> impl Strategy<Value = Query<FixedSize>> {
+    prop_oneof![
+        batch_id().prop_map(|batch_id| FixedSizeQuery::ByBatchId { batch_id }),
+        Just(FixedSizeQuery::CurrentBatch),
+    ]
+    .prop_map(Query::new_fixed_size)
+}
+
+fn aggregation_job_initialize_req<Q: QueryType>(
+    partial_batch_selector: impl Strategy<Value = PartialBatchSelector<Q>>,
+) -> impl Strategy<Value = AggregationJobInitializeReq<Q>> {
+    (bytes(), partial_batch_selector, vec(prepare_init(), 0..4)).prop_map(
+        |(aggregation_parameter, partial_batch_selector, prepare_inits)| {
+            AggregationJobInitializeReq::new(
+                aggregation_parameter,
+                partial_batch_selector,
+                prepare_inits,
+            )
+        },
+    )
+}
+
+fn collection_req<Q: QueryType>(
+    query: impl Strategy<Value = Query<Q>>,
+) -> impl Strategy<Value = CollectionReq<Q>> {
+    (query, bytes())
+        .prop_map(|(query, aggregation_parameter)| CollectionReq::new(query, aggregation_parameter))
+}
+
+fn collection<Q: QueryType>(
+    partial_batch_selector: impl Strategy<Value = PartialBatchSelector<Q>>,
+) -> impl Strategy<Value = Collection<Q>> {
+    (
+        partial_batch_selector,
+        any::<u64>(),
+        interval(),
+        hpke_ciphertext(),
+        hpke_ciphertext(),
+    )
+        .prop_map(
+            |(partial_batch_selector, report_count, interval, leader_share, helper_share)| {
+                Collection::new(
+                    partial_batch_selector,
+                    report_count,
+                    interval,
+                    leader_share,
+                    helper_share,
+                )
+            },
+        )
+}
+
+fn aggregate_share_req<Q: QueryType>(
+    batch_selector: impl Strategy<Value = BatchSelector<Q>>,
+) -> impl Strategy<Value = AggregateShareReq<Q>> {
+    (batch_selector, bytes(), any::<u64>(), report_id_checksum()).prop_map(
+        |(batch_selector, aggregation_parameter, report_count, checksum)| {
+            AggregateShareReq::new(
+                batch_selector,
+                aggregation_parameter,
+                report_count,
+                checksum,
+            )
+        },
+    )
+}
+
+fn aggregate_share_aad<Q: QueryType>(
+    batch_selector: impl Strategy<Value = BatchSelector<Q>>,
+) -> impl Strategy<Value = AggregateShareAad<Q>> {
+    (task_id(), bytes(), batch_selector).prop_map(
+        |(task_id, aggregation_parameter, batch_selector)| {
+            AggregateShareAad::new(task_id, aggregation_parameter, batch_selector)
+        },
+    )
+}
+
+proptest! {
+    #[test]
+    fn roundtrip_hpke_config_list(configs in vec(hpke_config(), 0..4)) {
+        check_encoding(&HpkeConfigList::new(configs))?;
+    }
+
+    #[test]
+    fn roundtrip_report(
+        metadata in report_metadata(),
+        public_share in bytes(),
+        leader_share in hpke_ciphertext(),
+        helper_share in hpke_ciphertext(),
+    ) {
+        check_encoding(&Report::new(metadata, public_share, leader_share, helper_share))?;
+    }
+
+    #[test]
+    fn roundtrip_plaintext_input_share(
+        extensions in vec(extension(), 0..4),
+        payload in bytes(),
+    ) {
+        check_encoding(&PlaintextInputShare::new(extensions, payload))?;
+    }
+
+    #[test]
+    fn roundtrip_input_share_aad(
+        task_id in task_id(),
+        metadata in report_metadata(),
+        public_share in bytes(),
+    ) {
+        check_encoding(&InputShareAad::new(task_id, metadata, public_share))?;
+    }
+
+    #[test]
+    fn roundtrip_aggregation_job_initialize_req_time_interval(
+        req in aggregation_job_initialize_req(time_interval_partial_batch_selector()),
+    ) {
+        check_encoding(&req)?;
+    }
+
+    #[test]
+    fn roundtrip_aggregation_job_initialize_req_fixed_size(
+        req in aggregation_job_initialize_req(fixed_size_partial_batch_selector()),
+    ) {
+        check_encoding(&req)?;
+    }
+
+    #[test]
+    fn roundtrip_aggregation_job_continue_req(
+        step in any::<u16>(),
+        prepare_continues in vec(
+            (report_id(), ping_pong_message())
+                .prop_map(|(report_id, message)| PrepareContinue::new(report_id, message)),
+            0..4,
+        ),
+    ) {
+        check_encoding(&AggregationJobContinueReq::new(
+            AggregationJobStep::from(step),
+            prepare_continues,
+        ))?;
+    }
+
+    #[test]
+    fn roundtrip_aggregation_job_resp(
+        prepare_resps in vec(
+            (report_id(), prepare_step_result())
+                .prop_map(|(report_id, result)| PrepareResp::new(report_id, result)),
+            0..4,
+        ),
+    ) {
+        check_encoding(&AggregationJobResp::new(prepare_resps))?;
+    }
+
+    #[test]
+    fn roundtrip_collection_req_time_interval(req in collection_req(time_interval_query())) {
+        check_encoding(&req)?;
+    }
+
+    #[test]
+    fn roundtrip_collection_req_fixed_size(req in collection_req(fixed_size_query())) {
+        check_encoding(&req)?;
+    }
+
+    #[test]
+    fn roundtrip_collection_time_interval(
+        collection in collection(time_interval_partial_batch_selector()),
+    ) {
+        check_encoding(&collection)?;
+    }
+
+    #[test]
+    fn roundtrip_collection_fixed_size(
+        collection in collection(fixed_size_partial_batch_selector()),
+    ) {
+        check_encoding(&collection)?;
+    }
+
+    #[test]
+    fn roundtrip_aggregate_share_req_time_interval(
+        req in aggregate_share_req(time_interval_batch_selector()),
+    ) {
+        check_encoding(&req)?;
+    }
+
+    #[test]
+    fn roundtrip_aggregate_share_req_fixed_size(
+        req in aggregate_share_req(fixed_size_batch_selector()),
+    ) {
+        check_encoding(&req)?;
+    }
+
+    #[test]
+    fn roundtrip_aggregate_share(ciphertext in hpke_ciphertext()) {
+        check_encoding(&AggregateShare::new(ciphertext))?;
+    }
+
+    #[test]
+    fn roundtrip_aggregate_share_aad_time_interval(
+        aad in aggregate_share_aad(time_interval_batch_selector()),
+    ) {
+        check_encoding(&aad)?;
+    }
+
+    #[test]
+    fn roundtrip_aggregate_share_aad_fixed_size(
+        aad in aggregate_share_aad(fixed_size_batch_selector()),
+    ) {
+        check_encoding(&aad)?;
+    }
+}
@@ -4,11 +4,15 @@ use serde::Deserialize;
 use std::{
     collections::HashMap,
     env::{self},
-    fs::File,
-    process::Command,
+    fs::{self, File},
+    io::BufReader,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
 };
 use tempfile::tempdir;
 
+mod oci;
+
 /// Command line arguments that will get passed through to Cargo.
 #[derive(Args)]
 struct CargoArgs {
@@ -40,6 +44,27 @@ enum Subcommand {
         #[clap(flatten)]
         cargo_args: CargoArgs,
     },
+
+    /// Build self-contained OCI images of the interop client and collector, without Docker
+    ///
+    /// The binaries are statically linked, and each image contains only its binary. Images are
+    /// written as OCI image layouts, in one directory per image.
+    BuildOciImages {
+        /// Directory to write the image layouts to
+        #[clap(long)]
+        output: PathBuf,
+
+        /// Tag of the images. Defaults to the version of the interop binaries
+        #[clap(long)]
+        tag: Option<String>,
+
+        /// Target triple to build the binaries for. The target must link statically
+        #[clap(long, default_value = "x86_64-unknown-linux-musl")]
+        target: String,
+
+        #[clap(flatten)]
+        cargo_args: CargoArgs,
+    },
 }
 
 fn main() -> Result<()> {
@@ -53,6 +78,17 @@ fn main() -> Result<()> {
         Subcommand::TestDockerWithImages { images, cargo_args } => {
             run_docker_tests(images, cargo_args)?
         }
+        Subcommand::BuildOciImages {
+            output,
+            tag,
+            target,
+            cargo_args,
+        } => {
+            let tag = tag.unwrap_or_else(|| env!("CARGO_PKG_VERSION").to_string());
+            for (binary, digest) in build_oci_images(&output, &tag, &target, cargo_args)? {
+                println!("{binary}: {digest}");
+            }
+        }
     }
     Ok(())
 }
@@ -125,6 +161,106 @@ fn build_container_images() -> Result<ContainerImages> {
     })
 }
 
+/// The interop binaries that are packaged by `build-oci-images`. The interop aggregator is not
+/// included, because its image also bundles PostgreSQL and a process supervisor.
+const OCI_IMAGE_BINARIES: [&str; 2] = ["janus_interop_client", "janus_interop_collector"];
+
+/// Builds static interop binaries for `target`, and writes an OCI image layout for each of them
+/// to a subdirectory of `output` named after the binary. Returns the manifest digest of each
+/// image.
+fn build_oci_images(
+    output: &Path,
+    tag: &str,
+    target: &str,
+    cargo_args: CargoArgs,
+) -> Result<Vec<(&'static str, String)>> {
+    let architecture = oci::architecture_of_target(target)
+        .with_context(|| format!("unsupported target {target}"))?;
+
+    let cargo_path = env::var_os("CARGO").context("CARGO environment variable was not set")?;
+    let mut command = Command::new(cargo_path);
+    command.arg("build");
+    command.arg(format!(
+        "--profile={}",
+        cargo_args.profile.as_deref().unwrap_or("release")
+    ));
+    if cargo_args.locked {
+        command.arg("--locked");
+    }
+    command.args([
+        "--package=janus_interop_binaries",
+        "--features=fpvec_bounded_l2",
+        "--message-format=json-render-diagnostics",
+    ]);
+    command.arg(format!("--target={target}"));
+    for binary in OCI_IMAGE_BINARIES {
+        command.arg(format!("--bin={binary}"));
+    }
+    // Link statically, so that the images need no C library.
+    command.env("RUSTFLAGS", "-C target-feature=+crt-static");
+    let mut child = command.stdout(Stdio::piped()).spawn()?;
+
+    // Find the built binaries in Cargo's build messages.
+    let mut executables = HashMap::new();
+    // Unwrap safety: stdout was piped above.
+    let messages =
+        serde_json::Deserializer::from_reader(BufReader::new(child.stdout.take().unwrap()))
+            .into_iter::<CargoMessage>();
+    for message in messages {
+        if let CargoMessage {
+            reason,
+            target: Some(CargoTarget { name }),
+            executable: Some(executable),
+        } = message?
+        {
+            if reason == "compiler-artifact" {
+                executables.insert(name, executable);
+            }
+        }
+    }
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(anyhow!("cargo build exited with status code {status}"));
+    }
+
+    OCI_IMAGE_BINARIES
+        .into_iter()
+        .map(|binary| {
+            let executable = executables
+                .get(binary)
+                .with_context(|| format!("cargo build did not produce {binary}"))?;
+            let image = oci::Image {
+                architecture: architecture.to_string(),
+                directories: Vec::from(["/logs".to_string()]),
+                files: Vec::from([oci::ImageFile {
+                    path: format!("/{binary}"),
+                    mode: 0o755,
+                    contents: fs::read(executable)
+                        .with_context(|| format!("couldn't read {executable:?}"))?,
+                }]),
+                entrypoint: Vec::from([format!("/{binary}")]),
+                env: Vec::from(["RUST_LOG=info".to_string()]),
+                exposed_ports: Vec::from([8080]),
+            };
+            let digest = image.write_layout(&output.join(binary), tag)?;
+            Ok((binary, digest))
+        })
+        .collect()
+}
+
+/// The parts of Cargo's JSON build messages that are needed to find built binaries.
+#[derive(Deserialize)]
+struct CargoMessage {
+    reason: String,
+    target: Option<CargoTarget>,
+    executable: Option<PathBuf>,
+}
+
+#[derive(Deserialize)]
+struct CargoTarget {
+    name: String,
+}
+
 fn run_docker_tests(images: ContainerImages, cargo_args: CargoArgs) -> Result<()> {
     let cargo_path = env::var_os("CARGO").context("CARGO environment variable was not set")?;
     let mut command = Command::new(cargo_path);
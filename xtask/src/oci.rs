@@ -0,0 +1,249 @@
+//! A minimal builder for single-layer OCI images, written as OCI image layouts.
+//!
+//! This lets us package statically linked interop binaries without a Docker daemon or a base
+//! image. The resulting image layout directories can be copied to a registry or into a local
+//! container engine with standard tools, e.g. `skopeo copy oci:<dir>:<tag> docker://<image>`.
+//!
+//! Images are reproducible: file timestamps and ownership are fixed, and no creation time is
+//! recorded, so building the same binaries twice produces the same image digest.
+
+use anyhow::{Context, Result};
+use flate2::{write::GzEncoder, Compression};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::{fs, path::Path};
+
+const MANIFEST_MEDIA_TYPE: &str = "application/vnd.oci.image.manifest.v1+json";
+const CONFIG_MEDIA_TYPE: &str = "application/vnd.oci.image.config.v1+json";
+const LAYER_MEDIA_TYPE: &str = "application/vnd.oci.image.layer.v1.tar+gzip";
+
+/// A file to include in an image.
+pub struct ImageFile {
+    /// Absolute path of the file in the image.
+    pub path: String,
+    /// Unix permission bits of the file.
+    pub mode: u32,
+    pub contents: Vec<u8>,
+}
+
+/// Description of a single-layer image.
+pub struct Image {
+    /// Architecture of the image, in Go's `GOARCH` notation, e.g. `amd64`.
+    pub architecture: String,
+    /// Empty directories to create in the image.
+    pub directories: Vec<String>,
+    pub files: Vec<ImageFile>,
+    pub entrypoint: Vec<String>,
+    pub env: Vec<String>,
+    pub exposed_ports: Vec<u16>,
+}
+
+impl Image {
+    /// Writes this image to an OCI image layout at `directory`, tagged with `tag`, and returns the
+    /// image's manifest digest.
+    pub fn write_layout(&self, directory: &Path, tag: &str) -> Result<String> {
+        let blobs_directory = directory.join("blobs").join("sha256");
+        fs::create_dir_all(&blobs_directory)
+            .with_context(|| format!("couldn't create {blobs_directory:?}"))?;
+        let write_blob = |contents: &[u8]| -> Result<(String, usize)> {
+            let digest = sha256_digest(contents);
+            let path = blobs_directory.join(digest.trim_start_matches("sha256:"));
+            fs::write(&path, contents).with_context(|| format!("couldn't write {path:?}"))?;
+            Ok((digest, contents.len()))
+        };
+
+        let layer = self.layer()?;
+        let diff_id = sha256_digest(&layer);
+        let (layer_digest, layer_size) = write_blob(&gzip(&layer)?)?;
+
+        let config = json!({
+            "architecture": self.architecture,
+            "os": "linux",
+            "config": {
+                "Entrypoint": self.entrypoint,
+                "Env": self.env,
+                "ExposedPorts": self
+                    .exposed_ports
+                    .iter()
+                    .map(|port| (format!("{port}/tcp"), json!({})))
+                    .collect::<serde_json::Map<_, _>>(),
+            },
+            "rootfs": {
+                "type": "layers",
+                "diff_ids": [diff_id],
+            },
+        });
+        let (config_digest, config_size) = write_blob(&serde_json::to_vec(&config)?)?;
+
+        let manifest = json!({
+            "schemaVersion": 2,
+            "mediaType": MANIFEST_MEDIA_TYPE,
+            "config": {
+                "mediaType": CONFIG_MEDIA_TYPE,
+                "digest": config_digest,
+                "size": config_size,
+            },
+            "layers": [{
+                "mediaType": LAYER_MEDIA_TYPE,
+                "digest": layer_digest,
+                "size": layer_size,
+            }],
+        });
+        let (manifest_digest, manifest_size) = write_blob(&serde_json::to_vec(&manifest)?)?;
+
+        let index = json!({
+            "schemaVersion": 2,
+            "manifests": [{
+                "mediaType": MANIFEST_MEDIA_TYPE,
+                "digest": manifest_digest,
+                "size": manifest_size,
+                "annotations": {
+                    "org.opencontainers.image.ref.name": tag,
+                },
+            }],
+        });
+        fs::write(directory.join("index.json"), serde_json::to_vec(&index)?)
+            .context("couldn't write index.json")?;
+        fs::write(
+            directory.join("oci-layout"),
+            serde_json::to_vec(&json!({ "imageLayoutVersion": "1.0.0" }))?,
+        )
+        .context("couldn't write oci-layout")?;
+
+        Ok(manifest_digest)
+    }
+
+    /// Builds the uncompressed tar archive of this image's only layer.
+    fn layer(&self) -> Result<Vec<u8>> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for directory in &self.directories {
+            let mut header = new_header(tar::EntryType::Directory, 0o755, 0);
+            builder.append_data(&mut header, directory.trim_start_matches('/'), &[][..])?;
+        }
+        for file in &self.files {
+            let mut header = new_header(
+                tar::EntryType::Regular,
+                file.mode,
+                u64::try_from(file.contents.len())?,
+            );
+            builder.append_data(
+                &mut header,
+                file.path.trim_start_matches('/'),
+                file.contents.as_slice(),
+            )?;
+        }
+        Ok(builder.into_inner()?)
+    }
+}
+
+fn new_header(entry_type: tar::EntryType, mode: u32, size: u64) -> tar::Header {
+    let mut header = tar::Header::new_gnu();
+    header.set_entry_type(entry_type);
+    header.set_mode(mode);
+    header.set_size(size);
+    header.set_uid(0);
+    header.set_gid(0);
+    header.set_mtime(0);
+    header
+}
+
+fn gzip(contents: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    std::io::Write::write_all(&mut encoder, contents)?;
+    Ok(encoder.finish()?)
+}
+
+fn sha256_digest(contents: &[u8]) -> String {
+    format!("sha256:{:x}", Sha256::digest(contents))
+}
+
+/// Returns the Go `GOARCH` name of the architecture of a Rust target triple.
+pub fn architecture_of_target(target: &str) -> Option<&'static str> {
+    match target.split('-').next()? {
+        "x86_64" => Some("amd64"),
+        "aarch64" => Some("arm64"),
+        "armv7" => Some("arm"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{architecture_of_target, Image, ImageFile};
+    use serde_json::Value;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn image() -> Image {
+        Image {
+            architecture: "amd64".to_string(),
+            directories: Vec::from(["/logs".to_string()]),
+            files: Vec::from([ImageFile {
+                path: "/binary".to_string(),
+                mode: 0o755,
+                contents: b"not really a binary".to_vec(),
+            }]),
+            entrypoint: Vec::from(["/binary".to_string()]),
+            env: Vec::from(["RUST_LOG=info".to_string()]),
+            exposed_ports: Vec::from([8080]),
+        }
+    }
+
+    #[test]
+    fn write_layout() {
+        let first = tempdir().unwrap();
+        let second = tempdir().unwrap();
+        let digest = image().write_layout(first.path(), "1.0.0").unwrap();
+
+        // Builds are reproducible.
+        assert_eq!(
+            image().write_layout(second.path(), "1.0.0").unwrap(),
+            digest
+        );
+
+        let index: Value =
+            serde_json::from_slice(&fs::read(first.path().join("index.json")).unwrap()).unwrap();
+        assert_eq!(index["manifests"][0]["digest"], digest.as_str());
+        assert_eq!(
+            index["manifests"][0]["annotations"]["org.opencontainers.image.ref.name"],
+            "1.0.0"
+        );
+
+        let blob = |digest: &str| {
+            fs::read(
+                first
+                    .path()
+                    .join("blobs/sha256")
+                    .join(digest.trim_start_matches("sha256:")),
+            )
+            .unwrap()
+        };
+        let manifest: Value = serde_json::from_slice(&blob(&digest)).unwrap();
+        let config: Value =
+            serde_json::from_slice(&blob(manifest["config"]["digest"].as_str().unwrap())).unwrap();
+        assert_eq!(config["config"]["Entrypoint"][0], "/binary");
+        assert!(config["config"]["ExposedPorts"]["8080/tcp"].is_object());
+
+        let layer = blob(manifest["layers"][0]["digest"].as_str().unwrap());
+        let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(layer.as_slice()));
+        let paths: Vec<_> = archive
+            .entries()
+            .unwrap()
+            .map(|entry| entry.unwrap().path().unwrap().display().to_string())
+            .collect();
+        assert_eq!(paths, ["logs", "binary"]);
+    }
+
+    #[test]
+    fn architectures() {
+        assert_eq!(
+            architecture_of_target("x86_64-unknown-linux-musl"),
+            Some("amd64")
+        );
+        assert_eq!(
+            architecture_of_target("aarch64-unknown-linux-musl"),
+            Some("arm64")
+        );
+        assert_eq!(architecture_of_target("mips-unknown-linux-musl"), None);
+    }
+}